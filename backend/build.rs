@@ -0,0 +1,7 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // protoc ikut dibundel supaya build tidak butuh protoc di sistem
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_prost_build::compile_protos("proto/dcs.proto")?;
+    println!("cargo:rerun-if-changed=proto/dcs.proto");
+    Ok(())
+}
@@ -0,0 +1,8 @@
+#![no_main]
+
+// Byte acak dari port serial ke semua parser frame ESP32 (SENSOR_DATA, RELAY_STATUS, DIAG, ...)
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    influxdb_thingsboard_bridge::serial::fuzz_frame(data);
+});
@@ -0,0 +1,149 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
+
+pub use dcs_model::{Alarm, AlarmContext, Severity};
+
+// Perubahan status alarm untuk subscriber (notifikasi)
+#[derive(Debug, Clone)]
+pub enum AlarmChange {
+    Raised(Alarm),
+    Cleared(Alarm),
+}
+
+// Mode maintenance: alarm dengan prefix id tertentu ditekan selama teknisi bekerja
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    pub suppress: Vec<String>,
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub default_duration: Duration,
+    // Batas durasi shelve/maintenance yang boleh diminta lewat REST/gRPC
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub max_duration: Duration,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            suppress: vec!["stale_".to_string(), "rate_".to_string(), "actuator_".to_string()],
+            default_duration: Duration::from_secs(2 * 3600),
+            max_duration: Duration::from_secs(24 * 3600),
+        }
+    }
+}
+
+// Menyimpan alarm yang sedang aktif, dikunci berdasarkan id
+#[derive(Debug, Default)]
+pub struct AlarmManager {
+    active: BTreeMap<String, Alarm>,
+    // Alarm yang di-shelve sampai waktu tertentu
+    shelved: BTreeMap<String, Instant>,
+    maintenance_until: Option<Instant>,
+    suppress_prefixes: Vec<String>,
+    // Alarm yang penekanannya sudah dicatat di audit (sekali per periode)
+    suppressed_noted: BTreeSet<String>,
+}
+
+impl AlarmManager {
+    pub fn new(maintenance: &MaintenanceConfig) -> Self {
+        Self { suppress_prefixes: maintenance.suppress.clone(), ..Default::default() }
+    }
+
+    // Alasan alarm ditekan saat ini ("shelved"/"maintenance"), None jika tidak
+    pub fn suppression(&self, id: &str, now: Instant) -> Option<&'static str> {
+        if self.shelved.get(id).is_some_and(|until| *until > now) {
+            return Some("shelved");
+        }
+        let in_maintenance = self.maintenance_until.is_some_and(|until| until > now);
+        if in_maintenance && self.suppress_prefixes.iter().any(|p| id.starts_with(p.as_str())) {
+            return Some("maintenance");
+        }
+        None
+    }
+
+    // true jika penekanan alarm ini belum pernah dicatat pada periode sekarang
+    pub fn note_suppressed(&mut self, id: &str) -> bool {
+        self.suppressed_noted.insert(id.to_string())
+    }
+
+    // Shelve alarm; jika sedang aktif, alarm disembunyikan sampai shelve berakhir
+    pub fn shelve(&mut self, id: &str, until: Instant) -> Option<Alarm> {
+        self.shelved.insert(id.to_string(), until);
+        self.active.remove(id)
+    }
+
+    pub fn unshelve(&mut self, id: &str) -> bool {
+        self.suppressed_noted.remove(id);
+        self.shelved.remove(id).is_some()
+    }
+
+    pub fn set_maintenance(&mut self, until: Option<Instant>) -> Option<Instant> {
+        if until.is_none() {
+            self.suppressed_noted.clear();
+        }
+        std::mem::replace(&mut self.maintenance_until, until)
+    }
+
+    // Sisa waktu maintenance mode dan shelve per alarm
+    pub fn maintenance_remaining(&self, now: Instant) -> Option<Duration> {
+        self.maintenance_until.map(|until| until.saturating_duration_since(now))
+    }
+
+    pub fn shelved(&self, now: Instant) -> impl Iterator<Item = (&String, Duration)> {
+        self.shelved.iter().map(move |(id, until)| (id, until.saturating_duration_since(now)))
+    }
+
+    // Lepas shelve dan maintenance yang sudah kedaluwarsa; mengembalikan id yang dilepas
+    pub fn expire(&mut self, now: Instant) -> Vec<String> {
+        let expired: Vec<String> = self.shelved.iter().filter(|(_, until)| **until <= now).map(|(id, _)| id.clone()).collect();
+        for id in &expired {
+            self.unshelve(id);
+        }
+        let mut released = expired;
+        if self.maintenance_until.is_some_and(|until| until <= now) {
+            self.set_maintenance(None);
+            released.push("maintenance".to_string());
+        }
+        released
+    }
+
+    // Mengembalikan alarm hanya jika baru aktif (bukan yang sudah aktif sebelumnya)
+    pub fn raise(&mut self, id: &str, severity: Severity, message: String) -> Option<Alarm> {
+        self.raise_with(id, severity, message, AlarmContext::default())
+    }
+
+    pub fn raise_with(&mut self, id: &str, severity: Severity, message: String, context: AlarmContext) -> Option<Alarm> {
+        if self.active.contains_key(id) {
+            return None;
+        }
+        let alarm = Alarm {
+            id: id.to_string(),
+            severity,
+            message,
+            acknowledged: false,
+            context,
+        };
+        self.active.insert(id.to_string(), alarm.clone());
+        Some(alarm)
+    }
+
+    // Mengembalikan alarm jika aktif dan belum pernah di-ack
+    pub fn acknowledge(&mut self, id: &str) -> Option<Alarm> {
+        let alarm = self.active.get_mut(id).filter(|a| !a.acknowledged)?;
+        alarm.acknowledged = true;
+        Some(alarm.clone())
+    }
+
+    pub fn is_active(&self, id: &str) -> bool {
+        self.active.contains_key(id)
+    }
+
+    pub fn clear(&mut self, id: &str) -> Option<Alarm> {
+        self.active.remove(id)
+    }
+
+    pub fn active(&self) -> impl Iterator<Item = &Alarm> {
+        self.active.values()
+    }
+}
@@ -0,0 +1,184 @@
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+use crate::alarms::Severity;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyMethod {
+    ZScore,
+    #[default]
+    Mad,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AnomalyConfig {
+    pub enabled: bool,
+    pub method: AnomalyMethod,
+    // Jumlah sampel rolling sebagai referensi
+    pub window: usize,
+    pub threshold: f64,
+    // Nilai identik sebanyak ini berturut-turut dianggap sensor macet (0 = nonaktif)
+    pub stuck_samples: usize,
+    pub fields: Vec<String>,
+    pub raise_alarm: bool,
+    pub severity: Severity,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            method: AnomalyMethod::Mad,
+            window: 60,
+            threshold: 3.5,
+            stuck_samples: 30,
+            fields: vec!["temperature".to_string(), "humidity".to_string()],
+            raise_alarm: false,
+            severity: Severity::Warning,
+        }
+    }
+}
+
+pub struct AnomalyDetector {
+    config: AnomalyConfig,
+    values: VecDeque<f64>,
+}
+
+impl AnomalyDetector {
+    pub fn new(config: AnomalyConfig) -> Self {
+        Self { config, values: VecDeque::new() }
+    }
+
+    // Kembalikan alasan jika nilai ini tidak wajar dibanding jendela rolling
+    pub fn check(&mut self, x: f64) -> Option<String> {
+        let reason = self.score(x);
+        self.values.push_back(x);
+        while self.values.len() > self.config.window.max(self.config.stuck_samples) {
+            self.values.pop_front();
+        }
+        reason
+    }
+
+    fn score(&self, x: f64) -> Option<String> {
+        let stuck = self.config.stuck_samples;
+        if stuck > 0 && self.values.len() + 1 >= stuck {
+            let recent = self.values.iter().rev().take(stuck - 1);
+            if recent.clone().count() == stuck - 1 && recent.into_iter().all(|v| *v == x) {
+                return Some(format!("stuck at {:.2} for {} samples", x, stuck));
+            }
+        }
+
+        let window: Vec<f64> = self.values.iter().rev().take(self.config.window).copied().collect();
+        if window.len() < self.config.window / 2 || window.len() < 3 {
+            return None;
+        }
+
+        let score = match self.config.method {
+            AnomalyMethod::ZScore => {
+                let n = window.len() as f64;
+                let mean = window.iter().sum::<f64>() / n;
+                let std = (window.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+                if std < 1e-9 {
+                    return None;
+                }
+                (x - mean).abs() / std
+            }
+            AnomalyMethod::Mad => {
+                let med = median(window.clone());
+                let mad = median(window.iter().map(|v| (v - med).abs()).collect());
+                if mad < 1e-9 {
+                    return None;
+                }
+                0.6745 * (x - med).abs() / mad
+            }
+        };
+
+        (score > self.config.threshold)
+            .then(|| format!("score {:.2} > {} ({:?})", score, self.config.threshold, self.config.method))
+    }
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector(method: AnomalyMethod, stuck_samples: usize) -> AnomalyDetector {
+        AnomalyDetector::new(AnomalyConfig { method, window: 10, stuck_samples, ..Default::default() })
+    }
+
+    // Isi jendela dengan nilai bergantian low/high
+    fn fill(detector: &mut AnomalyDetector, low: f64, high: f64, n: usize) {
+        for i in 0..n {
+            let x = if i % 2 == 0 { low } else { high };
+            assert!(detector.check(x).is_none(), "baseline sample {} flagged", i);
+        }
+    }
+
+    #[test]
+    fn mad_threshold() {
+        // median 20.5, MAD 0.5 -> skor = 1.349 * |x - 20.5|
+        let score = |x| {
+            let mut mad = detector(AnomalyMethod::Mad, 0);
+            fill(&mut mad, 20.0, 21.0, 10);
+            mad.check(x)
+        };
+        assert!(score(23.0).is_none());
+        assert_eq!(score(23.2).as_deref(), Some("score 3.64 > 3.5 (Mad)"));
+        assert!(score(17.8).is_some());
+    }
+
+    #[test]
+    fn z_score_threshold() {
+        // mean 21, std 1
+        let score = |x| {
+            let mut z = detector(AnomalyMethod::ZScore, 0);
+            fill(&mut z, 20.0, 22.0, 10);
+            z.check(x)
+        };
+        assert!(score(24.4).is_none());
+        assert_eq!(score(17.4).as_deref(), Some("score 3.60 > 3.5 (ZScore)"));
+    }
+
+    #[test]
+    fn needs_half_a_window_before_scoring() {
+        let mut mad = detector(AnomalyMethod::Mad, 0);
+        fill(&mut mad, 20.0, 21.0, 4);
+        assert!(mad.check(100.0).is_none());
+        // Sekarang 5 sampel = setengah jendela
+        assert!(mad.check(100.0).is_some());
+    }
+
+    #[test]
+    fn flat_window_never_scores() {
+        let mut mad = detector(AnomalyMethod::Mad, 0);
+        let mut z = detector(AnomalyMethod::ZScore, 0);
+        fill(&mut mad, 20.0, 20.0, 10);
+        fill(&mut z, 20.0, 20.0, 10);
+        assert!(mad.check(50.0).is_none());
+        assert!(z.check(50.0).is_none());
+    }
+
+    #[test]
+    fn stuck_sensor_after_identical_samples() {
+        let mut mad = detector(AnomalyMethod::Mad, 5);
+        fill(&mut mad, 20.0, 21.0, 6);
+        for _ in 0..3 {
+            assert!(mad.check(21.0).is_none());
+        }
+        // Sampel kelima identik berturut-turut (termasuk yang dari fill)
+        assert_eq!(mad.check(21.0).as_deref(), Some("stuck at 21.00 for 5 samples"));
+        assert!(mad.check(20.0).is_none());
+    }
+}
@@ -0,0 +1,888 @@
+use anyhow::Result;
+use axum::{
+    extract::{ConnectInfo, Extension, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use log::info;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::audit::{AuditEntry, AuditQuery};
+use crate::auth::Principal;
+use crate::autotune::AutotuneStatus;
+use crate::calibration::Calibration;
+use crate::control::{Actuator, ActuatorId, ActuatorMode};
+use crate::events::{Event, EventSource};
+use crate::publish::BreakerState;
+use crate::series::{self, SeriesQuery};
+use crate::setpoint::SetpointSource;
+use crate::settings::SettingsPatch;
+use crate::state::AppState;
+
+type ApiResult = Result<Json<Value>, (StatusCode, String)>;
+
+// Loop utama berjalan tiap 10 detik; tiga siklus terlewat dianggap macet
+const LIVENESS: Duration = Duration::from_secs(30);
+
+// Identitas pemanggil untuk audit log
+struct Caller {
+    principal: Principal,
+    addr: SocketAddr,
+}
+
+impl Caller {
+    fn audit(&self, state: &AppState, action: &str, target: impl Into<String>, detail: impl Into<String>) {
+        state.audit.record(AuditEntry::new(
+            self.principal.name.clone(),
+            "rest",
+            self.addr.ip().to_string(),
+            action,
+            target,
+            detail,
+        ));
+    }
+}
+
+pub async fn serve(listen: String, state: Arc<AppState>, http: reqwest::Client) -> Result<()> {
+    let app = router(state, http);
+    let listener = tokio::net::TcpListener::bind(&listen).await?;
+    info!("REST API listening on {}", listen);
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+    Ok(())
+}
+
+fn router(state: Arc<AppState>, http: reqwest::Client) -> Router {
+    let api = Router::new()
+        .route("/api/whoami", get(whoami))
+        .route("/api/runtime", get(get_runtime))
+        .route("/api/actuators/{actuator}/mode", axum::routing::put(put_actuator_mode))
+        .route("/api/maintenance/{actuator}/reset", post(reset_runtime))
+        .route("/api/events", get(get_events))
+        .route("/api/audit", get(get_audit))
+        .route("/api/sinks", get(get_sinks))
+        .route("/api/alarms", get(get_alarms))
+        .route("/api/alarms/{id}/shelve", post(shelve_alarm).delete(unshelve_alarm))
+        .route("/api/alarms/{id}/ack", post(ack_alarm))
+        .route("/api/maintenance-mode", axum::routing::put(set_maintenance_mode))
+        .route("/api/autotune", get(get_autotune))
+        .route("/api/autotune/{zone}", post(start_autotune).delete(cancel_autotune))
+        .route("/api/calibration", get(get_calibration))
+        .route("/api/calibration/{device}/{field}", get(get_field_calibration).put(put_calibration))
+        .route("/api/calibration/{device}/{field}/two-point", post(two_point_calibration))
+        .route("/api/drift", get(get_drift))
+        .route("/api/drift/{device}/reference", post(post_drift_reference))
+        .route("/api/drift/{device}", axum::routing::delete(reset_drift))
+        .route("/api/settings", get(get_settings).put(put_settings))
+        .route("/api/setpoints", get(get_setpoints))
+        .route("/api/setpoints/{zone}", axum::routing::put(put_setpoint))
+        .route("/api/series", get(get_series))
+        .route("/api/export.xlsx", get(get_export))
+        .route("/api/schema", get(get_schema))
+        .route("/status", get(get_status));
+    // Injeksi gangguan untuk latihan skenario gagal; hanya di build dengan feature "chaos"
+    #[cfg(feature = "chaos")]
+    let api = {
+        log::warn!("🧪 Built with the \"chaos\" feature: fault injection enabled at /api/chaos");
+        api.route("/api/chaos", get(get_chaos).put(put_chaos).delete(clear_chaos))
+    };
+    api.layer(Extension(http))
+        .layer(middleware::from_fn_with_state(state.clone(), crate::auth::require_role))
+        // Probe orkestrasi container dan scrape Prometheus tanpa API key
+        .route("/healthz", get(healthz))
+        .route("/metrics", get(get_metrics))
+        // Image OTA untuk ESP32 (tanpa header); hanya image yang sedang di-relay yang disajikan
+        .route("/ota/{file}", get(get_ota_image))
+        .with_state(state)
+}
+
+fn parse_actuator(name: &str) -> Result<Actuator, (StatusCode, String)> {
+    Actuator::parse(name).ok_or_else(|| (StatusCode::NOT_FOUND, format!("unknown actuator '{}'", name)))
+}
+
+// ?zone=<nama>; tanpa zone berlaku untuk aktuator jenis itu di semua zone
+#[derive(Deserialize)]
+struct ZoneParam {
+    zone: Option<String>,
+}
+
+fn actuator_ids(state: &AppState, name: &str, zone: Option<&str>) -> Result<Vec<ActuatorId>, (StatusCode, String)> {
+    let ids = state.actuator_ids(parse_actuator(name)?, zone);
+    if ids.is_empty() {
+        return Err((StatusCode::NOT_FOUND, format!("no zone owns actuator '{}'", name)));
+    }
+    Ok(ids)
+}
+
+async fn whoami(Extension(principal): Extension<Principal>) -> ApiResult {
+    Ok(Json(json!({ "name": principal.name, "role": principal.role.as_str() })))
+}
+
+// Per aktuator fisik, key "<zone>/<jenis>"
+async fn get_runtime(State(state): State<Arc<AppState>>) -> ApiResult {
+    let runtime = state.runtime.lock().unwrap();
+    let body: serde_json::Map<String, Value> = state
+        .zones
+        .iter()
+        .flat_map(|z| z.actuator_ids())
+        .map(|a| {
+            let r = runtime.get(&a);
+            (a.to_string(), json!({ "on_hours": r.on_hours(), "switch_count": r.switch_count }))
+        })
+        .collect();
+    Ok(Json(Value::Object(body)))
+}
+
+async fn reset_runtime(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(name): Path<String>,
+    Query(params): Query<ZoneParam>,
+) -> ApiResult {
+    let caller = Caller { principal, addr };
+    let mut previous = serde_json::Map::new();
+    for actuator in actuator_ids(&state, &name, params.zone.as_deref())? {
+        let cleared = state.runtime.lock().unwrap().reset(&actuator);
+        info!(
+            "🔧 Maintenance reset for {}: {:.1} h, {} switches cleared",
+            actuator,
+            cleared.on_hours(),
+            cleared.switch_count
+        );
+        state.events.record(
+            Event::new("config", format!("{}_runtime", actuator), "0.0 h")
+                .old(format!("{:.1} h", cleared.on_hours()))
+                .reason("maintenance reset")
+                .source(EventSource::Manual),
+        );
+        caller.audit(
+            &state,
+            "maintenance_reset",
+            actuator.to_string(),
+            format!("{:.1} h, {} switches cleared", cleared.on_hours(), cleared.switch_count),
+        );
+        previous.insert(actuator.to_string(), json!({ "on_hours": cleared.on_hours(), "switch_count": cleared.switch_count }));
+    }
+    Ok(Json(json!({ "actuator": name, "previous": previous })))
+}
+
+#[derive(Deserialize)]
+struct ModeBody {
+    mode: ActuatorMode,
+}
+
+// Paksa aktuator ON/OFF atau kembalikan ke AUTO (role operator); jalur yang sama dengan gRPC
+// SetActuator, termasuk perintah RELAY ke ESP32 serial. 503 jika ESP32 tidak membalas ACK.
+async fn put_actuator_mode(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(name): Path<String>,
+    Query(params): Query<ZoneParam>,
+    Json(body): Json<ModeBody>,
+) -> ApiResult {
+    let caller = Caller { principal, addr };
+    let mut previous = serde_json::Map::new();
+    for actuator in actuator_ids(&state, &name, params.zone.as_deref())? {
+        let old = state
+            .set_actuator_mode(&actuator, body.mode, EventSource::Manual, "REST actuator mode")
+            .await
+            .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
+        caller.audit(&state, "set_actuator", actuator.to_string(), format!("{} -> {}", old.as_str(), body.mode.as_str()));
+        previous.insert(actuator.to_string(), json!(old.as_str()));
+    }
+    Ok(Json(json!({ "actuator": name, "mode": body.mode.as_str(), "previous": previous })))
+}
+
+fn check_field(field: &str) -> Result<(), (StatusCode, String)> {
+    match field {
+        "temperature" | "humidity" => Ok(()),
+        _ => Err((StatusCode::NOT_FOUND, format!("unknown field '{}'", field))),
+    }
+}
+
+async fn get_calibration(State(state): State<Arc<AppState>>) -> ApiResult {
+    let table = state.calibration.lock().unwrap();
+    Ok(Json(json!(*table)))
+}
+
+async fn get_field_calibration(
+    State(state): State<Arc<AppState>>,
+    Path((device, field)): Path<(String, String)>,
+) -> ApiResult {
+    check_field(&field)?;
+    let table = state.calibration.lock().unwrap();
+    Ok(Json(json!(crate::calibration::lookup(&table, &device, &field))))
+}
+
+fn store_calibration(state: &AppState, caller: Caller, device: &str, field: &str, calibration: Calibration) {
+    state
+        .calibration
+        .lock()
+        .unwrap()
+        .entry(device.to_string())
+        .or_default()
+        .insert(field.to_string(), calibration);
+    info!(
+        "🎯 Calibration {}/{} set: gain={:.4}, offset={:.3}",
+        device, field, calibration.gain, calibration.offset
+    );
+    state.events.record(
+        Event::new(
+            "config",
+            format!("calibration/{device}/{field}"),
+            format!("gain={:.4},offset={:.3}", calibration.gain, calibration.offset),
+        )
+        .reason("REST API")
+        .source(EventSource::Manual),
+    );
+    caller.audit(
+        state,
+        "calibration",
+        format!("{device}/{field}"),
+        format!("gain={:.4},offset={:.3}", calibration.gain, calibration.offset),
+    );
+}
+
+async fn put_calibration(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path((device, field)): Path<(String, String)>,
+    Json(calibration): Json<Calibration>,
+) -> ApiResult {
+    check_field(&field)?;
+    store_calibration(&state, Caller { principal, addr }, &device, &field, calibration);
+    Ok(Json(json!(calibration)))
+}
+
+// Dua pasangan bacaan: nilai raw sensor dan nilai alat referensi
+#[derive(Deserialize)]
+struct TwoPointRequest {
+    raw_low: f64,
+    reference_low: f64,
+    raw_high: f64,
+    reference_high: f64,
+}
+
+async fn two_point_calibration(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path((device, field)): Path<(String, String)>,
+    Json(req): Json<TwoPointRequest>,
+) -> ApiResult {
+    check_field(&field)?;
+    let calibration = Calibration::two_point(req.raw_low, req.reference_low, req.raw_high, req.reference_high)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    store_calibration(&state, Caller { principal, addr }, &device, &field, calibration);
+    Ok(Json(json!(calibration)))
+}
+
+fn now_ms() -> u64 {
+    crate::now_ns() / 1_000_000
+}
+
+async fn get_drift(State(state): State<Arc<AppState>>) -> ApiResult {
+    Ok(Json(state.drift.snapshot(now_ms())))
+}
+
+// Bacaan hygrometer referensi; measured kosong = humidity sensor terakhir (setelah kalibrasi)
+#[derive(Deserialize)]
+struct DriftReferenceRequest {
+    reference: f64,
+    measured: Option<f64>,
+    note: Option<String>,
+}
+
+async fn post_drift_reference(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(device): Path<String>,
+    Json(req): Json<DriftReferenceRequest>,
+) -> ApiResult {
+    let entry = state
+        .drift
+        .add_reference(&device, req.reference, req.measured, &principal.name, req.note, now_ms())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let detail = entry.reference.as_ref().map(|r| format!("reference={:.2},measured={:.2}", r.reference, r.measured)).unwrap_or_default();
+    info!(
+        "💧 Drift reference for {}: {}, correction {:+.2} -> {:+.2} %RH",
+        device, detail, entry.correction_before, entry.correction_after
+    );
+    state.events.record(
+        Event::new("config", format!("drift/{device}"), format!("{:+.3}", entry.correction_after))
+            .old(format!("{:+.3}", entry.correction_before))
+            .reason(detail.clone())
+            .source(EventSource::Manual),
+    );
+    Caller { principal, addr }.audit(&state, "drift_reference", device, detail);
+    Ok(Json(json!(entry)))
+}
+
+async fn reset_drift(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(device): Path<String>,
+) -> ApiResult {
+    let entry = state
+        .drift
+        .reset(&device, &principal.name, now_ms())
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("no drift model for '{}'", device)))?;
+    info!("💧 Drift model for {} reset (was {:+.2} %RH)", device, entry.correction_before);
+    state.events.record(
+        Event::new("config", format!("drift/{device}"), "reset").old(format!("{:+.3}", entry.correction_before)).source(EventSource::Manual),
+    );
+    Caller { principal, addr }.audit(&state, "drift_reset", device, "");
+    Ok(Json(json!(entry)))
+}
+
+async fn get_settings(State(state): State<Arc<AppState>>) -> ApiResult {
+    Ok(Json(json!(state.settings())))
+}
+
+// Body sebagian, mis. {"temp_on": 31.0, "zones": {"main": {"humidity_on_below": 55}}}
+async fn put_settings(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(patch): Json<SettingsPatch>,
+) -> ApiResult {
+    let changes = state
+        .update_settings(&patch, "REST API", EventSource::Manual)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+    if !changes.is_empty() {
+        let detail = changes.iter().map(|(key, _, new)| format!("{key}={new}")).collect::<Vec<_>>().join(",");
+        Caller { principal, addr }.audit(&state, "settings", state.device_id.clone(), detail);
+    }
+    let changed: Vec<Value> = changes.iter().map(|(key, old, new)| json!({ "key": key, "old": old, "new": new })).collect();
+    Ok(Json(json!({ "settings": state.settings(), "changed": changed })))
+}
+
+// Per zone: urutan sumber, nilai tiap sumber dan setpoint yang dipakai siklus terakhir
+async fn get_setpoints(State(state): State<Arc<AppState>>) -> ApiResult {
+    let active = state.active_setpoints.lock().unwrap().clone();
+    let zones: Vec<Value> = state
+        .zones
+        .iter()
+        .map(|zone| {
+            let config = zone.setpoint_config();
+            let current = active.get(&zone.name);
+            json!({
+                "zone": zone.name,
+                "sources": config.sources,
+                "api": state.setpoint_override(&zone.name),
+                "thingsboard": state.tb_setpoint(&zone.name),
+                "static": config.value,
+                "fallback": config.fallback,
+                "active": current.map(|(value, _)| value),
+                "active_source": current.map(|(_, source)| source.as_str()),
+            })
+        })
+        .collect();
+    Ok(Json(json!(zones)))
+}
+
+#[derive(Deserialize)]
+struct SetpointBody {
+    // null = hapus override (sumber berikutnya di daftar yang dipakai)
+    temperature_c: Option<f64>,
+}
+
+// Sumber "api"; sama dengan gRPC SetSetpoint
+async fn put_setpoint(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(zone): Path<String>,
+    Json(body): Json<SetpointBody>,
+) -> ApiResult {
+    check_zone(&state, &zone)?;
+    if body.temperature_c.is_some_and(|t| !t.is_finite()) {
+        return Err((StatusCode::BAD_REQUEST, "temperature_c must be finite".to_string()));
+    }
+    let previous = state.set_setpoint_override(&zone, body.temperature_c);
+    let label = |v: Option<f64>| v.map(|t| format!("{:.2}", t)).unwrap_or_else(|| "none".to_string());
+    info!("🎯 Setpoint override [{}] {} → {} (REST, {})", zone, label(previous), label(body.temperature_c), principal.name);
+    state.events.record(
+        Event::new("config", format!("setpoint/{}/temperature", zone), label(body.temperature_c))
+            .old(label(previous))
+            .reason("REST API")
+            .source(EventSource::Manual),
+    );
+    let sources = state.zones.iter().find(|z| z.name == zone).map(|z| z.setpoint_config()).unwrap_or_default();
+    Caller { principal, addr }.audit(&state, "set_setpoint", zone.clone(), format!("{} -> {}", label(previous), label(body.temperature_c)));
+    Ok(Json(json!({
+        "zone": zone,
+        "previous": previous,
+        "temperature_c": body.temperature_c,
+        // Override tersimpan tetapi tidak dipakai jika "api" tidak ada di sources
+        "used": sources.uses(SetpointSource::Api),
+    })))
+}
+
+#[derive(Deserialize)]
+struct SeriesParams {
+    fields: Option<String>,
+    range: Option<String>,
+    every: Option<String>,
+    zone: Option<String>,
+}
+
+// GET /api/series?fields=temperature,humidity&range=-24h&every=1m[&zone=..]
+// -> {"range": "-24h", "every": "1m", "time": [ms...], "values": {"temperature": [..], ...}}
+async fn get_series(
+    State(state): State<Arc<AppState>>,
+    Extension(http): Extension<reqwest::Client>,
+    headers: HeaderMap,
+    Query(params): Query<SeriesParams>,
+) -> Result<Response, (StatusCode, String)> {
+    let query = SeriesQuery::parse(params.fields.as_deref(), params.range.as_deref(), params.every.as_deref(), params.zone.as_deref())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let location = match &query.zone {
+        Some(zone) if !state.zones.iter().any(|z| z.name == *zone) => {
+            return Err((StatusCode::NOT_FOUND, format!("unknown zone '{}'", zone)));
+        }
+        Some(zone) => state.zone_location(zone),
+        None => state.tags.location(&state.device_id),
+    };
+    // Zone milik tenant dibaca dari org/bucket tenant dengan token-nya
+    let tenant = query.zone.as_deref().and_then(crate::tenants::for_zone);
+    let bucket = crate::bridge::bucket_for(crate::bridge::SENSOR_MEAS);
+    let flux = query.flux(tenant.map_or(bucket, |t| t.bucket_or(bucket)), crate::bridge::SENSOR_MEAS, &crate::bridge::location_filter(location));
+
+    // Lewat query_cache: panel dashboard yang refresh bersamaan cukup memicu satu query.
+    // Key diberi prefix supaya tidak bertabrakan dengan CSV mentah untuk teks Flux yang sama.
+    let key = format!("series\ntenant={}\n{}", tenant.map_or("", |t| t.name.as_str()), flux);
+    let body = crate::query_cache::fetch(&key, || async {
+        let mut builder = series::SeriesBuilder::new(&query.fields);
+        crate::bridge::post_influx_rows(&http, tenant, &flux, |row| builder.push(row)).await?;
+        let result = builder.finish();
+        let json = json!({
+            "range": format!("-{}", query.range),
+            "every": query.every,
+            "time": result.time,
+            "values": result.values,
+        });
+        Ok(json.to_string())
+    })
+    .await
+    .map_err(|e| (StatusCode::BAD_GATEWAY, format!("{:#}", e)))?;
+
+    let gzip = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|e| e.trim().split(';').next() == Some("gzip")));
+    let response = if gzip {
+        let headers = [
+            (header::CONTENT_TYPE, "application/json"),
+            (header::CONTENT_ENCODING, "gzip"),
+            (header::VARY, "Accept-Encoding"),
+        ];
+        (headers, series::gzip(body.as_bytes())).into_response()
+    } else {
+        ([(header::CONTENT_TYPE, "application/json"), (header::VARY, "Accept-Encoding")], body).into_response()
+    };
+    Ok(response)
+}
+
+// Workbook Excel (lihat export.rs); parameter sama dengan /api/series, default range 7d dan every 1m
+async fn get_export(
+    State(state): State<Arc<AppState>>,
+    Extension(http): Extension<reqwest::Client>,
+    Query(params): Query<SeriesParams>,
+) -> Result<Response, (StatusCode, String)> {
+    let query = state
+        .export
+        .query(params.fields.as_deref(), params.range.as_deref(), params.every.as_deref(), params.zone.as_deref())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let location = match &query.zone {
+        Some(zone) if !state.zones.iter().any(|z| z.name == *zone) => {
+            return Err((StatusCode::NOT_FOUND, format!("unknown zone '{}'", zone)));
+        }
+        Some(zone) => state.zone_location(zone),
+        None => state.tags.location(&state.device_id),
+    };
+    let series = crate::export::fetch(&http, &query, location)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("{:#}", e)))?;
+    let workbook = crate::export::workbook(&query, &series, &state.export).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))?;
+    let disposition = format!("attachment; filename=\"{}-{}.xlsx\"", state.device_id, query.range);
+    let headers = [
+        (header::CONTENT_TYPE, "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet".to_string()),
+        (header::CONTENT_DISPOSITION, disposition),
+    ];
+    Ok((headers, workbook).into_response())
+}
+
+#[derive(Deserialize)]
+struct EventQuery {
+    limit: Option<usize>,
+    kind: Option<String>,
+}
+
+async fn get_events(State(state): State<Arc<AppState>>, Query(query): Query<EventQuery>) -> ApiResult {
+    let events = state.events.recent(query.limit.unwrap_or(100), query.kind.as_deref());
+    Ok(Json(json!(events)))
+}
+
+async fn get_audit(State(state): State<Arc<AppState>>, Query(query): Query<AuditQuery>) -> ApiResult {
+    let entries = state
+        .audit
+        .query(&query)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))?;
+    Ok(Json(json!(entries)))
+}
+
+async fn get_autotune(State(state): State<Arc<AppState>>) -> ApiResult {
+    Ok(Json(json!(*state.autotune.lock().unwrap())))
+}
+
+fn check_zone(state: &AppState, zone: &str) -> Result<(), (StatusCode, String)> {
+    match state.zones.iter().find(|z| z.name == zone) {
+        Some(z) if z.has(Actuator::ExhaustFan) => Ok(()),
+        Some(_) => Err((StatusCode::BAD_REQUEST, format!("zone '{}' has no exhaust fan", zone))),
+        None => Err((StatusCode::NOT_FOUND, format!("unknown zone '{}'", zone))),
+    }
+}
+
+// Eksperimen dijalankan loop utama pada siklus berikutnya
+async fn start_autotune(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(zone): Path<String>,
+) -> ApiResult {
+    check_zone(&state, &zone)?;
+    {
+        let mut autotune = state.autotune.lock().unwrap();
+        if matches!(autotune.get(&zone), Some(AutotuneStatus::Requested | AutotuneStatus::Running { .. })) {
+            return Err((StatusCode::CONFLICT, format!("autotune already running for zone '{}'", zone)));
+        }
+        autotune.insert(zone.clone(), AutotuneStatus::Requested);
+    }
+    Caller { principal, addr }.audit(&state, "autotune_start", zone.clone(), "relay experiment requested");
+    Ok(Json(json!({ "zone": zone, "phase": "requested" })))
+}
+
+async fn cancel_autotune(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(zone): Path<String>,
+) -> ApiResult {
+    check_zone(&state, &zone)?;
+    {
+        let mut autotune = state.autotune.lock().unwrap();
+        if !matches!(autotune.get(&zone), Some(AutotuneStatus::Requested | AutotuneStatus::Running { .. })) {
+            return Err((StatusCode::CONFLICT, format!("no autotune running for zone '{}'", zone)));
+        }
+        autotune.insert(zone.clone(), AutotuneStatus::Cancelled);
+    }
+    Caller { principal, addr }.audit(&state, "autotune_cancel", zone.clone(), "relay experiment cancelled");
+    Ok(Json(json!({ "zone": zone, "phase": "cancelled" })))
+}
+
+async fn get_alarms(State(state): State<Arc<AppState>>) -> ApiResult {
+    let now = std::time::Instant::now();
+    let alarms = state.alarms.lock().unwrap();
+    let active: Vec<Value> = alarms
+        .active()
+        .map(|a| json!(a))
+        .collect();
+    let shelved: Vec<Value> = alarms
+        .shelved(now)
+        .map(|(id, remaining)| json!({ "id": id, "remaining_s": remaining.as_secs() }))
+        .collect();
+    let maintenance = alarms.maintenance_remaining(now);
+    Ok(Json(json!({
+        "active": active,
+        "shelved": shelved,
+        "maintenance": {
+            "enabled": maintenance.is_some_and(|r| !r.is_zero()),
+            "remaining_s": maintenance.map(|r| r.as_secs()),
+        },
+    })))
+}
+
+// Ack menghentikan eskalasi notifikasi; alarm tetap aktif sampai kondisinya hilang
+async fn ack_alarm(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+) -> ApiResult {
+    if state.acknowledge_alarm(&id, &principal.name, EventSource::Manual).is_none() {
+        let active = state.alarms.lock().unwrap().is_active(&id);
+        return Err(if active {
+            (StatusCode::CONFLICT, format!("alarm '{}' is already acknowledged", id))
+        } else {
+            (StatusCode::NOT_FOUND, format!("alarm '{}' is not active", id))
+        });
+    }
+    Caller { principal, addr }.audit(&state, "alarm_ack", id.clone(), "manual");
+    Ok(Json(json!({ "id": id, "acknowledged": true })))
+}
+
+#[derive(Deserialize)]
+struct ShelveRequest {
+    minutes: u64,
+}
+
+async fn shelve_alarm(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    Json(req): Json<ShelveRequest>,
+) -> ApiResult {
+    let duration = state.suppression_duration(req.minutes).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    state.shelve_alarm(&id, duration);
+    Caller { principal, addr }.audit(&state, "alarm_shelve", id.clone(), format!("{} min", req.minutes));
+    Ok(Json(json!({ "id": id, "shelved_minutes": req.minutes })))
+}
+
+async fn unshelve_alarm(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+) -> ApiResult {
+    if !state.unshelve_alarm(&id) {
+        return Err((StatusCode::NOT_FOUND, format!("alarm '{}' is not shelved", id)));
+    }
+    Caller { principal, addr }.audit(&state, "alarm_unshelve", id.clone(), "manual");
+    Ok(Json(json!({ "id": id, "shelved": false })))
+}
+
+#[derive(Deserialize)]
+struct MaintenanceRequest {
+    enabled: bool,
+    // Default dari [maintenance] default_duration
+    minutes: Option<u64>,
+}
+
+async fn set_maintenance_mode(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(req): Json<MaintenanceRequest>,
+) -> ApiResult {
+    let duration = match (req.enabled, req.minutes) {
+        (false, _) => None,
+        (true, None) => Some(state.maintenance_duration),
+        (true, Some(minutes)) => Some(state.suppression_duration(minutes).map_err(|e| (StatusCode::BAD_REQUEST, e))?),
+    };
+    state.set_maintenance(duration, EventSource::Manual);
+    let detail = match duration {
+        Some(d) => format!("enabled for {} min", d.as_secs() / 60),
+        None => "disabled".to_string(),
+    };
+    Caller { principal, addr }.audit(&state, "maintenance_mode", "global", detail);
+    Ok(Json(json!({ "enabled": req.enabled, "minutes": duration.map(|d| d.as_secs() / 60) })))
+}
+
+#[cfg(feature = "chaos")]
+async fn get_chaos() -> ApiResult {
+    Ok(Json(json!(crate::chaos::active())))
+}
+
+// Body menggantikan semua gangguan aktif; field yang tidak disebut = tidak aktif
+#[cfg(feature = "chaos")]
+async fn put_chaos(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(faults): Json<crate::chaos::Faults>,
+) -> ApiResult {
+    crate::chaos::inject(&faults);
+    let detail = format!(
+        "drop_serial_frames={} influx_fail_s={} mqtt_delay_ms={}",
+        faults.drop_serial_frames, faults.influx_fail_s, faults.mqtt_delay_ms
+    );
+    log::warn!("🧪 Chaos faults injected by {}: {}", principal.name, detail);
+    Caller { principal, addr }.audit(&state, "chaos_inject", "global", detail);
+    Ok(Json(json!(crate::chaos::active())))
+}
+
+#[cfg(feature = "chaos")]
+async fn clear_chaos(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> ApiResult {
+    crate::chaos::inject(&crate::chaos::Faults::default());
+    info!("🧪 Chaos faults cleared by {}", principal.name);
+    Caller { principal, addr }.audit(&state, "chaos_clear", "global", "all faults");
+    Ok(Json(json!(crate::chaos::active())))
+}
+
+// JSON schema model data bersama (dcs-model); salinan statis di model/schema.json
+async fn get_schema() -> ApiResult {
+    Ok(Json(dcs_model::schema()))
+}
+
+async fn get_sinks(State(state): State<Arc<AppState>>) -> ApiResult {
+    Ok(Json(json!(state.sink_health())))
+}
+
+// Histogram latensi dan gauge antrian sink (metrics.rs) dalam format teks Prometheus
+async fn get_metrics(State(state): State<Arc<AppState>>) -> ([(axum::http::HeaderName, &'static str); 1], String) {
+    let body = crate::metrics::METRICS.render()
+        + &crate::metrics::render_sinks(&state.sink_health())
+        + &crate::query_cache::render_metrics();
+    ([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+async fn healthz(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Value>) {
+    let live = state.is_live(LIVENESS);
+    let status = if live { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let body = json!({
+        "status": if live { "ok" } else { "stalled" },
+        "uptime_s": state.started.elapsed().as_secs(),
+    });
+    (status, Json(body))
+}
+
+// Ringkasan semua subsistem untuk dashboard
+async fn get_status(State(state): State<Arc<AppState>>) -> ApiResult {
+    let now = Instant::now();
+    let sinks = state.sink_health();
+    let sink = |name: &str| {
+        sinks.get(name).map(|h| {
+            json!({
+                "reachable": h.state == BreakerState::Closed,
+                "breaker": h.state.as_str(),
+                "buffered": h.queued,
+                "dropped": h.dropped,
+                "last_error": h.last_error,
+            })
+        })
+    };
+    let frames: serde_json::Map<String, Value> = state
+        .last_sample
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(device, at)| (device.clone(), json!(now.saturating_duration_since(*at).as_secs_f64())))
+        .collect();
+    let (active, maintenance) = {
+        let alarms = state.alarms.lock().unwrap();
+        let active: Vec<Value> = alarms
+            .active()
+            .map(|a| json!({ "id": a.id, "severity": a.severity.as_str() }))
+            .collect();
+        (active, alarms.maintenance_remaining(now).is_some_and(|r| !r.is_zero()))
+    };
+
+    let resets = state.device_resets.lock().unwrap().clone();
+    let serial_ok = state.serial_connected();
+    let identity = state.device_identity.lock().unwrap().clone();
+    let mqtt_ok = state.mqtt_connected();
+    let destinations: serde_json::Map<String, Value> = state
+        .mqtt_status()
+        .into_iter()
+        .map(|(name, connected)| {
+            let publish = sink(&name);
+            (name, json!({ "connected": connected, "publish": publish }))
+        })
+        .collect();
+    let ota = state.ota.state.lock().unwrap().clone().map(|(fw_state, error)| {
+        let target = state.ota.target.lock().unwrap().clone();
+        json!({ "state": fw_state.as_str(), "error": error, "target": target })
+    });
+    let sinks_ok = sinks.values().all(|h| h.state == BreakerState::Closed);
+    let live = state.is_live(LIVENESS);
+    let overall = match (live, serial_ok && mqtt_ok && sinks_ok) {
+        (false, _) => "stalled",
+        (true, false) => "degraded",
+        (true, true) => "ok",
+    };
+    Ok(Json(json!({
+        "status": overall,
+        "uptime_s": state.started.elapsed().as_secs(),
+        // device null = firmware tanpa handshake HELLO atau belum menjawab
+        "serial": {
+            "connected": serial_ok,
+            "last_frame_age_s": frames,
+            "unexpected_resets": resets,
+            "device": identity.map(|id| json!({
+                "device_id": id.device,
+                "firmware": id.firmware,
+                "protocol": id.protocol,
+                "protocol_supported": id.supported(),
+            })),
+        },
+        "influxdb": sink("influxdb"),
+        // null tanpa [influx_failover]
+        "influxdb_active": crate::influx_failover::active().map(|t| t.as_str()),
+        "mqtt": { "connected": mqtt_ok, "publish": sink("thingsboard"), "destinations": destinations },
+        "sinks": sinks,
+        "alarms": { "active": active, "maintenance_mode": maintenance },
+        // null selama belum ada update firmware dari ThingsBoard
+        "ota": ota,
+    })))
+}
+
+async fn get_ota_image(State(state): State<Arc<AppState>>, Path(file): Path<String>) -> Response {
+    let Some(path) = state.ota.staged_path(&file) else {
+        return (StatusCode::NOT_FOUND, "no such firmware image").into_response();
+    };
+    match tokio::fs::read(&path).await {
+        Ok(image) => ([(header::CONTENT_TYPE, "application/octet-stream")], image).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("failed to read {}: {}", path.display(), e)).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{ApiKey, Role};
+    use crate::config::Config;
+    use crate::secrets::Secret;
+
+    // REST API dengan key viewer dan operator di port acak; mengembalikan base URL dan state
+    async fn spawn_api(name: &str) -> (String, Arc<AppState>) {
+        let dir = std::env::temp_dir().join(format!("dcs-api-{}-{}", name, std::process::id()));
+        let mut config = Config::default();
+        config.audit.path = dir.join("audit.jsonl");
+        config.auth.enabled = true;
+        let key = |name: &str, key: &str, role| ApiKey { name: name.to_string(), key: Secret::new(key), role };
+        config.auth.keys = vec![key("dashboard", "view-key", Role::Viewer), key("scada", "op-key", Role::Operator)];
+        let state = Arc::new(AppState::new(&config));
+        let app = router(state.clone(), reqwest::Client::new());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await });
+        (base, state)
+    }
+
+    #[tokio::test]
+    async fn actuator_mode_requires_operator() {
+        let (base, state) = spawn_api("mode").await;
+        let client = reqwest::Client::new();
+        let put = |key: &str| {
+            client
+                .put(format!("{base}/api/actuators/exhaust_fan/mode"))
+                .header("x-api-key", key)
+                .json(&json!({ "mode": "on" }))
+                .send()
+        };
+        assert_eq!(put("view-key").await.unwrap().status(), reqwest::StatusCode::FORBIDDEN);
+        assert_eq!(put("wrong-key").await.unwrap().status(), reqwest::StatusCode::UNAUTHORIZED);
+        // Operator masuk ke jalur gRPC yang sama: ESP32 belum terhubung, jadi perintah ditolak
+        let response = put("op-key").await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert!(response.text().await.unwrap().contains("not connected"));
+        assert_eq!(state.mode(&state.zones[0].actuator(Actuator::ExhaustFan)), ActuatorMode::Auto);
+    }
+}
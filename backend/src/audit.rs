@@ -0,0 +1,124 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    pub path: PathBuf,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self { path: PathBuf::from("audit.jsonl") }
+    }
+}
+
+// Satu aksi kontrol: siapa, apa, kapan, dari mana
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub ts_ns: u64,
+    pub actor: String,
+    // rest, grpc, mqtt
+    pub channel: String,
+    // IP pemanggil atau topic/request id MQTT
+    pub origin: String,
+    pub action: String,
+    pub target: String,
+    pub detail: String,
+}
+
+impl AuditEntry {
+    pub fn new(
+        actor: impl Into<String>,
+        channel: &str,
+        origin: impl Into<String>,
+        action: &str,
+        target: impl Into<String>,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            ts_ns: crate::now_ns(),
+            actor: actor.into(),
+            channel: channel.to_string(),
+            origin: origin.into(),
+            action: action.to_string(),
+            target: target.into(),
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    pub limit: Option<usize>,
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub since_ns: Option<u64>,
+}
+
+// Append-only JSON lines; file tidak pernah ditulis ulang atau dipotong
+pub struct AuditLog {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(config: &AuditConfig) -> Self {
+        Self { path: config.path.clone(), lock: Mutex::new(()) }
+    }
+
+    fn append(&self, entry: &AuditEntry) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open audit log {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    // Kegagalan menulis audit tidak menggagalkan aksi, tapi harus terlihat di log
+    pub fn record(&self, entry: AuditEntry) {
+        log::info!(
+            "🧾 Audit {} {} by {} via {} ({}): {}",
+            entry.action,
+            entry.target,
+            entry.actor,
+            entry.channel,
+            entry.origin,
+            entry.detail
+        );
+        if let Err(e) = self.append(&entry) {
+            log::error!("Failed to write audit entry: {:#}", e);
+        }
+    }
+
+    // Entri terbaru yang cocok dengan filter, urut dari yang paling lama
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>> {
+        let _guard = self.lock.lock().unwrap();
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to open audit log {}", self.path.display())),
+        };
+        let mut entries: Vec<AuditEntry> = BufReader::new(file)
+            .lines()
+            .map_while(|line| line.ok())
+            .filter_map(|line| serde_json::from_str::<AuditEntry>(&line).ok())
+            .filter(|e| query.actor.as_deref().is_none_or(|a| e.actor == a))
+            .filter(|e| query.action.as_deref().is_none_or(|a| e.action == a))
+            .filter(|e| query.since_ns.is_none_or(|t| e.ts_ns >= t))
+            .collect();
+        let limit = query.limit.unwrap_or(100);
+        if entries.len() > limit {
+            entries.drain(..entries.len() - limit);
+        }
+        Ok(entries)
+    }
+}
@@ -0,0 +1,196 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use log::warn;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::secrets::Secret;
+use crate::state::AppState;
+
+// Viewer hanya boleh membaca; operator boleh mengirim perintah dan mengubah konfigurasi
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Operator,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKey {
+    pub name: String,
+    // Secret: tidak ikut tercetak di Debug (check-config, log error)
+    pub key: Secret,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    pub enabled: bool,
+    pub keys: Vec<ApiKey>,
+    // Izinkan auth mati pada listen non-loopback (REST/gRPC terbuka ke jaringan); hanya untuk lab tertutup
+    pub allow_insecure: bool,
+}
+
+// Identitas pemanggil yang sudah lolos autentikasi
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    Unauthenticated,
+    Forbidden,
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::Unauthenticated => write!(f, "missing or invalid API key"),
+            AuthError::Forbidden => write!(f, "operator role required"),
+        }
+    }
+}
+
+// Bandingkan digest SHA-256 tanpa early-exit supaya waktu respon tidak membocorkan isi
+// maupun panjang key
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let (a, b) = (Sha256::digest(a), Sha256::digest(b));
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// Terima "Authorization: Bearer <key>" atau "x-api-key: <key>"
+pub fn extract_key<'a>(authorization: Option<&'a str>, api_key: Option<&'a str>) -> Option<&'a str> {
+    authorization
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or(api_key)
+        .map(str::trim)
+}
+
+fn is_loopback(listen: &str) -> bool {
+    match listen.parse::<SocketAddr>() {
+        Ok(addr) => addr.ip().is_loopback(),
+        Err(_) => listen.rsplit_once(':').is_some_and(|(host, _)| host == "localhost"),
+    }
+}
+
+impl AuthConfig {
+    // Tolak start jika endpoint tulis (relay, setpoint, shelve) terbuka tanpa auth di luar loopback
+    pub fn check_exposure(&self, listeners: &[(&str, &str)]) -> Result<(), String> {
+        if self.enabled || self.allow_insecure {
+            return Ok(());
+        }
+        match listeners.iter().find(|(_, listen)| !is_loopback(listen)) {
+            Some((name, listen)) => Err(format!(
+                "{name} listens on {listen} with [auth] disabled; enable auth, bind to 127.0.0.1 or set auth.allow_insecure = true"
+            )),
+            None => Ok(()),
+        }
+    }
+
+    pub fn authorize(&self, key: Option<&str>, required: Role) -> Result<Principal, AuthError> {
+        if !self.enabled {
+            return Ok(Principal { name: "anonymous".to_string(), role: Role::Operator });
+        }
+        let key = key.ok_or(AuthError::Unauthenticated)?;
+        let entry = self
+            .keys
+            .iter()
+            .find(|k| !k.key.is_empty() && constant_time_eq(k.key.expose().as_bytes(), key.as_bytes()))
+            .ok_or(AuthError::Unauthenticated)?;
+        if entry.role < required {
+            return Err(AuthError::Forbidden);
+        }
+        Ok(Principal { name: entry.name.clone(), role: entry.role })
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+// Middleware REST: GET cukup viewer, method lain butuh operator
+pub async fn require_role(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let required = match *request.method() {
+        Method::GET | Method::HEAD => Role::Viewer,
+        _ => Role::Operator,
+    };
+    let key = extract_key(header(request.headers(), "authorization"), header(request.headers(), "x-api-key"));
+    match state.auth.authorize(key, required) {
+        Ok(principal) => {
+            request.extensions_mut().insert(principal);
+            Ok(next.run(request).await)
+        }
+        Err(e) => {
+            warn!("🔒 {} {} rejected: {}", request.method(), request.uri().path(), e);
+            let status = match e {
+                AuthError::Unauthenticated => StatusCode::UNAUTHORIZED,
+                AuthError::Forbidden => StatusCode::FORBIDDEN,
+            };
+            Err((status, e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AuthConfig {
+        let key = |name: &str, key: &str, role| ApiKey { name: name.to_string(), key: Secret::new(key), role };
+        AuthConfig {
+            enabled: true,
+            keys: vec![key("dashboard", "view-key", Role::Viewer), key("scada", "op-key", Role::Operator)],
+            allow_insecure: false,
+        }
+    }
+
+    #[test]
+    fn keys_are_matched_by_role() {
+        let auth = config();
+        assert_eq!(auth.authorize(Some("view-key"), Role::Viewer).map(|p| p.name), Ok("dashboard".to_string()));
+        assert_eq!(auth.authorize(Some("view-key"), Role::Operator).map(|p| p.name), Err(AuthError::Forbidden));
+        assert_eq!(auth.authorize(Some("op-key"), Role::Operator).map(|p| p.role), Ok(Role::Operator));
+        for key in [None, Some(""), Some("op-ke"), Some("op-key2")] {
+            assert_eq!(auth.authorize(key, Role::Viewer).map(|p| p.name), Err(AuthError::Unauthenticated), "{key:?}");
+        }
+    }
+
+    #[test]
+    fn open_listeners_require_auth() {
+        let open = AuthConfig::default();
+        assert!(open.check_exposure(&[("api", "127.0.0.1:8080"), ("grpc", "[::1]:50051"), ("api", "localhost:8080")]).is_ok());
+        for listen in ["0.0.0.0:8080", "192.168.1.10:8080", "[::]:8080", "greenhouse.local:8080"] {
+            assert!(open.check_exposure(&[("api", listen)]).is_err(), "{listen}");
+        }
+        assert!(config().check_exposure(&[("api", "0.0.0.0:8080")]).is_ok());
+        let insecure = AuthConfig { allow_insecure: true, ..AuthConfig::default() };
+        assert!(insecure.check_exposure(&[("api", "0.0.0.0:8080")]).is_ok());
+    }
+
+    #[test]
+    fn keys_are_redacted_in_debug() {
+        assert!(!format!("{:?}", config()).contains("op-key"));
+    }
+}
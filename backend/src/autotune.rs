@@ -0,0 +1,343 @@
+use anyhow::{anyhow, Context, Result};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::cascade;
+use crate::config::Config;
+use crate::events::{Event, EventSource};
+use crate::state::AppState;
+use crate::zone::Zone;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TuneMethod {
+    #[default]
+    ZieglerNichols,
+    Amigo,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AutotuneConfig {
+    // Pita hysteresis relay di sekitar titik kerja (°C)
+    pub hysteresis: f64,
+    // Jumlah periode osilasi yang diukur (setelah siklus transien pertama)
+    pub cycles: usize,
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub max_duration: Duration,
+    pub method: TuneMethod,
+}
+
+impl Default for AutotuneConfig {
+    fn default() -> Self {
+        Self { hysteresis: 0.2, cycles: 4, max_duration: Duration::from_secs(2 * 3600), method: TuneMethod::default() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TuneResult {
+    pub method: TuneMethod,
+    // Ultimate gain dan periode osilasi (detik)
+    pub ku: f64,
+    pub tu: f64,
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+}
+
+impl TuneResult {
+    // Output relay 0/1 -> amplitudo d = 0.5, Ku = 4d / (π a)
+    pub fn compute(method: TuneMethod, amplitude: f64, tu: f64) -> Self {
+        let ku = 4.0 * 0.5 / (std::f64::consts::PI * amplitude);
+        let (kp, ti, td) = match method {
+            TuneMethod::ZieglerNichols => (0.6 * ku, 0.5 * tu, 0.125 * tu),
+            // AMIGO versi frekuensi dengan κ ≈ 0 (proses dominan lag, gain statis tidak diketahui)
+            TuneMethod::Amigo => (0.3 * ku, 0.6 * tu, 0.15 * tu),
+        };
+        Self { method, ku, tu, kp, ki: kp / ti, kd: kp * td }
+    }
+}
+
+// Status per zone yang dibaca REST API dan loop utama
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "phase", rename_all = "snake_case")]
+pub enum AutotuneStatus {
+    Requested,
+    Running { setpoint: f64, cycles: usize },
+    Done(TuneResult),
+    Failed { reason: String },
+    Cancelled,
+}
+
+pub enum TuneStep {
+    Relay(bool),
+    Done(TuneResult),
+    Failed(String),
+}
+
+// Eksperimen relay Åström–Hägglund pada fan (aksi langsung: panas -> fan ON)
+pub struct RelayTuner {
+    config: AutotuneConfig,
+    pub setpoint: f64,
+    on: bool,
+    started: Instant,
+    cycle_start: Option<Instant>,
+    transient_skipped: bool,
+    cycle_min: f64,
+    cycle_max: f64,
+    periods: Vec<f64>,
+    amplitudes: Vec<f64>,
+}
+
+impl RelayTuner {
+    pub fn new(config: AutotuneConfig, setpoint: f64, now: Instant) -> Self {
+        Self {
+            config,
+            setpoint,
+            on: false,
+            started: now,
+            cycle_start: None,
+            transient_skipped: false,
+            cycle_min: f64::INFINITY,
+            cycle_max: f64::NEG_INFINITY,
+            periods: Vec::new(),
+            amplitudes: Vec::new(),
+        }
+    }
+
+    pub fn cycles(&self) -> usize {
+        self.periods.len()
+    }
+
+    pub fn step(&mut self, temp: f64, now: Instant) -> TuneStep {
+        if now.saturating_duration_since(self.started) > self.config.max_duration {
+            return TuneStep::Failed(format!(
+                "no sustained oscillation within {}s ({} cycle(s) measured)",
+                self.config.max_duration.as_secs(),
+                self.periods.len()
+            ));
+        }
+        self.cycle_min = self.cycle_min.min(temp);
+        self.cycle_max = self.cycle_max.max(temp);
+
+        let h = self.config.hysteresis;
+        if !self.on && temp > self.setpoint + h {
+            self.on = true;
+            // Satu periode penuh = jarak antar switch OFF -> ON
+            if let Some(start) = self.cycle_start {
+                if self.transient_skipped {
+                    self.periods.push(now.saturating_duration_since(start).as_secs_f64());
+                    self.amplitudes.push((self.cycle_max - self.cycle_min) / 2.0);
+                } else {
+                    self.transient_skipped = true;
+                }
+            }
+            self.cycle_start = Some(now);
+            self.cycle_min = temp;
+            self.cycle_max = temp;
+        } else if self.on && temp < self.setpoint - h {
+            self.on = false;
+        }
+
+        if self.periods.len() >= self.config.cycles.max(1) {
+            let n = self.periods.len() as f64;
+            let tu = self.periods.iter().sum::<f64>() / n;
+            let amplitude = self.amplitudes.iter().sum::<f64>() / n;
+            if amplitude <= 0.0 || tu <= 0.0 {
+                return TuneStep::Failed("oscillation amplitude or period is zero".to_string());
+            }
+            return TuneStep::Done(TuneResult::compute(self.config.method, amplitude, tu));
+        }
+        TuneStep::Relay(self.on)
+    }
+}
+
+// Tulis gain hasil tuning ke file konfigurasi tanpa mengubah komentar/format lain.
+// Zone implisit -> [cascade]; zone eksplisit -> cascade di entri [[zones]] yang sesuai.
+pub fn persist_gains(path: &str, zone: &str, implicit_zone: bool, result: &TuneResult) -> Result<()> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    let mut doc: toml_edit::DocumentMut = text.parse().with_context(|| format!("Invalid config file {}", path))?;
+
+    let cascade = if implicit_zone {
+        doc.entry("cascade").or_insert(toml_edit::table())
+    } else {
+        let entry = doc
+            .get_mut("zones")
+            .and_then(|z| z.as_array_of_tables_mut())
+            .and_then(|zones| zones.iter_mut().find(|t| t.get("name").and_then(|n| n.as_str()) == Some(zone)))
+            .ok_or_else(|| anyhow!("zone '{}' not found in {}", zone, path))?;
+        entry.entry("cascade").or_insert(toml_edit::table())
+    };
+    let table = cascade.as_table_like_mut().ok_or_else(|| anyhow!("cascade is not a table"))?;
+    let round = |v: f64| (v * 10_000.0).round() / 10_000.0;
+    table.insert("kp", toml_edit::value(round(result.kp)));
+    table.insert("ki", toml_edit::value(round(result.ki)));
+    table.insert("kd", toml_edit::value(round(result.kd)));
+
+    // Tulis ke file sementara lalu rename supaya config tidak pernah setengah tertulis;
+    // permission file asli (mis. 0600 karena berisi secret) ikut dipertahankan
+    let tmp = format!("{path}.tmp");
+    std::fs::write(&tmp, doc.to_string()).with_context(|| format!("Failed to write {}", tmp))?;
+    let permissions = std::fs::metadata(path).with_context(|| format!("Failed to stat {}", path))?.permissions();
+    std::fs::set_permissions(&tmp, permissions).with_context(|| format!("Failed to chmod {}", tmp))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path))?;
+    Ok(())
+}
+
+// Jalankan satu langkah eksperimen relay autotune untuk zone; Some(status fan)
+// selama eksperimen berjalan, None jika zone dikontrol normal
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn step_zone(
+    state: &AppState,
+    config: &Config,
+    config_path: &str,
+    zone: &Zone,
+    tuners: &mut HashMap<String, RelayTuner>,
+    cascades: &mut HashMap<String, cascade::CascadeLoop>,
+    sensor_temp: f64,
+    payload: &mut serde_json::Map<String, serde_json::Value>,
+    prefix: &str,
+) -> Option<bool> {
+    let now = Instant::now();
+    let requested = state.autotune.lock().unwrap().get(&zone.name).cloned();
+    match requested {
+        Some(AutotuneStatus::Requested) if !tuners.contains_key(&zone.name) => {
+            info!("🎚️  Zone {}: autotune relay experiment started around {:.2}°C", zone.name, sensor_temp);
+            tuners.insert(zone.name.clone(), RelayTuner::new(config.autotune.clone(), sensor_temp, now));
+            state.events.record(
+                Event::new("autotune", zone.name.as_str(), "running")
+                    .reason(format!("relay around {:.2}°C", sensor_temp))
+                    .source(EventSource::Manual),
+            );
+        }
+        Some(AutotuneStatus::Cancelled) if tuners.remove(&zone.name).is_some() => {
+            warn!("🎚️  Zone {}: autotune cancelled", zone.name);
+            state.events.record(
+                Event::new("autotune", zone.name.as_str(), "cancelled").old("running").source(EventSource::Manual),
+            );
+        }
+        _ => {}
+    }
+
+    let tuner = tuners.get_mut(&zone.name)?;
+    let (status, relay) = match tuner.step(sensor_temp, now) {
+        TuneStep::Relay(on) => {
+            payload.insert(format!("{prefix}autotune_cycles"), json!(tuner.cycles()));
+            (AutotuneStatus::Running { setpoint: tuner.setpoint, cycles: tuner.cycles() }, Some(on))
+        }
+        TuneStep::Done(result) => {
+            tuners.remove(&zone.name);
+            info!(
+                "🎚️  Zone {}: autotune done Ku={:.3} Tu={:.0}s → kp={:.4} ki={:.5} kd={:.3}",
+                zone.name, result.ku, result.tu, result.kp, result.ki, result.kd
+            );
+            for (name, value) in [("ku", result.ku), ("tu", result.tu), ("kp", result.kp), ("ki", result.ki), ("kd", result.kd)] {
+                payload.insert(format!("{prefix}autotune_{name}"), json!(value));
+            }
+            // Gain baru langsung dipakai dan disimpan ke file konfigurasi
+            let mut tuned = zone.cascade.clone().unwrap_or_default();
+            tuned.kp = result.kp;
+            tuned.ki = result.ki;
+            tuned.kd = result.kd;
+            cascades.insert(zone.name.clone(), cascade::CascadeLoop::new(tuned));
+            if let Err(e) = persist_gains(config_path, &zone.name, config.zone_list.is_empty(), &result) {
+                error!("Failed to store tuned gains in {}: {:#}", config_path, e);
+            }
+            state.events.record(
+                Event::new("autotune", zone.name.as_str(), "done")
+                    .old("running")
+                    .reason(format!("kp={:.4} ki={:.5} kd={:.3} (Ku={:.3}, Tu={:.0}s)", result.kp, result.ki, result.kd, result.ku, result.tu))
+                    .source(EventSource::Manual),
+            );
+            (AutotuneStatus::Done(result), None)
+        }
+        TuneStep::Failed(reason) => {
+            tuners.remove(&zone.name);
+            warn!("🎚️  Zone {}: autotune failed: {}", zone.name, reason);
+            state.events.record(
+                Event::new("autotune", zone.name.as_str(), "failed").old("running").reason(reason.clone()),
+            );
+            (AutotuneStatus::Failed { reason }, None)
+        }
+    };
+    state.autotune.lock().unwrap().insert(zone.name.clone(), status);
+    relay
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    // Proses integrator dengan dead time: suhu naik `rate` °C/s saat fan OFF, turun saat ON.
+    // Limit cycle teoretis: a = h + rate·L, Tu = 4·(h + rate·L) / rate (±5% karena sampling 1 s)
+    fn run_tuner(method: TuneMethod) -> TuneResult {
+        let (rate, dead_time, h, setpoint) = (0.01, 10, 0.2, 30.0);
+        let config = AutotuneConfig { hysteresis: h, method, ..AutotuneConfig::default() };
+        let start = Instant::now();
+        let mut tuner = RelayTuner::new(config, setpoint, start);
+        let mut delayed: VecDeque<bool> = VecDeque::from(vec![false; dead_time]);
+        let mut temp = setpoint;
+        for t in 1..=3600 {
+            let fan = delayed.pop_front().unwrap();
+            temp += if fan { -rate } else { rate };
+            match tuner.step(temp, start + Duration::from_secs(t)) {
+                TuneStep::Relay(on) => delayed.push_back(on),
+                TuneStep::Done(result) => return result,
+                TuneStep::Failed(reason) => panic!("tuning failed: {reason}"),
+            }
+        }
+        panic!("no result after 3600 steps ({} cycles)", tuner.cycles());
+    }
+
+    #[test]
+    fn relay_limit_cycle_gives_ultimate_gain_and_period() {
+        let result = run_tuner(TuneMethod::ZieglerNichols);
+        let amplitude = 0.2 + 0.01 * 10.0;
+        let tu = 4.0 * amplitude / 0.01;
+        assert!((result.tu - tu).abs() / tu < 0.05, "tu = {} expected {}", result.tu, tu);
+        let ku = 2.0 / (std::f64::consts::PI * amplitude);
+        assert!((result.ku - ku).abs() / ku < 0.05, "ku = {} expected {}", result.ku, ku);
+        assert!((result.kp - 0.6 * result.ku).abs() < 1e-9);
+        assert!((result.ki - result.kp / (0.5 * result.tu)).abs() < 1e-9);
+        assert!((result.kd - result.kp * 0.125 * result.tu).abs() < 1e-9);
+    }
+
+    #[test]
+    fn amigo_is_more_conservative() {
+        let zn = run_tuner(TuneMethod::ZieglerNichols);
+        let amigo = run_tuner(TuneMethod::Amigo);
+        assert!(amigo.kp < zn.kp && amigo.ki < zn.ki);
+    }
+
+    #[test]
+    fn flat_process_times_out() {
+        let config = AutotuneConfig { max_duration: Duration::from_secs(60), ..AutotuneConfig::default() };
+        let start = Instant::now();
+        let mut tuner = RelayTuner::new(config, 30.0, start);
+        assert!(matches!(tuner.step(30.0, start + Duration::from_secs(30)), TuneStep::Relay(false)));
+        assert!(matches!(tuner.step(30.0, start + Duration::from_secs(61)), TuneStep::Failed(_)));
+    }
+
+    #[test]
+    fn persist_requires_existing_config() {
+        let dir = std::env::temp_dir().join(format!("dcs-autotune-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let path = path.to_str().unwrap();
+        let result = TuneResult::compute(TuneMethod::ZieglerNichols, 0.3, 120.0);
+        assert!(persist_gains(path, "main", true, &result).is_err());
+        assert!(!std::path::Path::new(path).exists());
+
+        std::fs::write(path, "# komentar\n[cascade]\nkp = 1.0\n").unwrap();
+        persist_gains(path, "main", true, &result).unwrap();
+        let text = std::fs::read_to_string(path).unwrap();
+        assert!(text.starts_with("# komentar"));
+        assert!(text.contains(&format!("kp = {}", (result.kp * 10_000.0).round() / 10_000.0)));
+        assert!(!std::path::Path::new(&format!("{path}.tmp")).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
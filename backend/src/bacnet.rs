@@ -0,0 +1,908 @@
+use anyhow::Result;
+use log::{debug, info, warn};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+use crate::state::AppState;
+use crate::zone::Zone;
+
+// Server BACnet/IP read-only (Annex J, UDP 47808) untuk BMS gedung: titik telemetry sebagai
+// objek analog-value/binary-value di satu device. Layanan: Who-Is/I-Am, ReadProperty dan
+// ReadPropertyMultiple; WriteProperty ditolak (write-access-denied), tanpa segmentasi.
+// Nilai dari snapshot telemetry loop utama; snapshot yang lebih tua dari stale_after atau key
+// yang tidak ada = status-flags fault dan reliability no-sensor, sehingga BMS bisa alarm.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BacnetConfig {
+    pub enabled: bool,
+    pub listen: String,
+    // Device instance unik di jaringan BACnet (0..4194302)
+    pub device_instance: u32,
+    pub device_name: String,
+    pub vendor_id: u16,
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub stale_after: Duration,
+    // Kosong = titik bawaan per zone (suhu, kelembaban, setpoint, fan, pump)
+    pub points: Vec<BacnetPoint>,
+}
+
+impl Default for BacnetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: "0.0.0.0:47808".to_string(),
+            device_instance: 1001,
+            device_name: "DCS Bridge".to_string(),
+            vendor_id: 0,
+            stale_after: Duration::from_secs(60),
+            points: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PointKind {
+    // analog-value, present-value REAL
+    Analog,
+    // binary-value, present-value active jika nilai telemetry bukan 0
+    Binary,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Units {
+    DegreesCelsius,
+    PercentRelativeHumidity,
+    Percent,
+    Kilopascals,
+    #[default]
+    NoUnits,
+}
+
+impl Units {
+    // BACnetEngineeringUnits
+    fn code(self) -> u32 {
+        match self {
+            Units::DegreesCelsius => 62,
+            Units::PercentRelativeHumidity => 29,
+            Units::Percent => 98,
+            Units::Kilopascals => 54,
+            Units::NoUnits => 95,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BacnetPoint {
+    // Key telemetry (seperti di payload ThingsBoard), juga object-name jika name kosong
+    pub key: String,
+    pub kind: PointKind,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub units: Units,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl BacnetPoint {
+    fn new(key: String, kind: PointKind, units: Units, description: String) -> Self {
+        Self { key, kind, name: String::new(), units, description }
+    }
+
+    pub fn object_name(&self) -> &str {
+        if self.name.is_empty() { &self.key } else { &self.name }
+    }
+}
+
+impl BacnetConfig {
+    // Titik yang diekspos; key multi-zone diberi prefix <zone>_ seperti payload telemetry
+    pub fn points(&self, zones: &[Zone]) -> Vec<BacnetPoint> {
+        if !self.points.is_empty() {
+            return self.points.clone();
+        }
+        let mut points = Vec::new();
+        for zone in zones {
+            let prefix = if zones.len() > 1 { format!("{}_", zone.name) } else { String::new() };
+            let point = |key: &str, kind, units, what: &str| BacnetPoint::new(format!("{prefix}{key}"), kind, units, format!("{} {}", zone.name, what));
+            points.push(point("sht20_temperature", PointKind::Analog, Units::DegreesCelsius, "temperature"));
+            points.push(point("sht20_humidity", PointKind::Analog, Units::PercentRelativeHumidity, "relative humidity"));
+            points.push(point("setpoint_temperature", PointKind::Analog, Units::DegreesCelsius, "temperature setpoint"));
+            points.push(point("exhaust_fan_status", PointKind::Binary, Units::NoUnits, "exhaust fan running"));
+            points.push(point("pump_status", PointKind::Binary, Units::NoUnits, "pump running"));
+        }
+        points
+    }
+}
+
+// BACnetObjectType
+const ANALOG_VALUE: u16 = 2;
+const BINARY_VALUE: u16 = 5;
+const DEVICE: u16 = 8;
+
+// BACnetPropertyIdentifier
+mod prop {
+    pub const ALL: u32 = 8;
+    pub const APDU_TIMEOUT: u32 = 11;
+    pub const APPLICATION_SOFTWARE_VERSION: u32 = 12;
+    pub const DESCRIPTION: u32 = 28;
+    pub const DEVICE_ADDRESS_BINDING: u32 = 30;
+    pub const EVENT_STATE: u32 = 36;
+    pub const FIRMWARE_REVISION: u32 = 44;
+    pub const MAX_APDU_LENGTH_ACCEPTED: u32 = 62;
+    pub const MODEL_NAME: u32 = 70;
+    pub const NUMBER_OF_APDU_RETRIES: u32 = 73;
+    pub const OBJECT_IDENTIFIER: u32 = 75;
+    pub const OBJECT_LIST: u32 = 76;
+    pub const OBJECT_NAME: u32 = 77;
+    pub const OBJECT_TYPE: u32 = 79;
+    pub const OPTIONAL: u32 = 80;
+    pub const OUT_OF_SERVICE: u32 = 81;
+    pub const PRESENT_VALUE: u32 = 85;
+    pub const PROTOCOL_OBJECT_TYPES_SUPPORTED: u32 = 96;
+    pub const PROTOCOL_SERVICES_SUPPORTED: u32 = 97;
+    pub const PROTOCOL_VERSION: u32 = 98;
+    pub const RELIABILITY: u32 = 103;
+    pub const REQUIRED: u32 = 105;
+    pub const SEGMENTATION_SUPPORTED: u32 = 107;
+    pub const STATUS_FLAGS: u32 = 111;
+    pub const SYSTEM_STATUS: u32 = 112;
+    pub const UNITS: u32 = 117;
+    pub const VENDOR_IDENTIFIER: u32 = 120;
+    pub const VENDOR_NAME: u32 = 121;
+    pub const PROTOCOL_REVISION: u32 = 139;
+    pub const DATABASE_REVISION: u32 = 155;
+    pub const PROPERTY_LIST: u32 = 371;
+}
+
+const DEVICE_REQUIRED: [u32; 20] = [
+    prop::OBJECT_IDENTIFIER,
+    prop::OBJECT_NAME,
+    prop::OBJECT_TYPE,
+    prop::SYSTEM_STATUS,
+    prop::VENDOR_NAME,
+    prop::VENDOR_IDENTIFIER,
+    prop::MODEL_NAME,
+    prop::FIRMWARE_REVISION,
+    prop::APPLICATION_SOFTWARE_VERSION,
+    prop::PROTOCOL_VERSION,
+    prop::PROTOCOL_REVISION,
+    prop::PROTOCOL_SERVICES_SUPPORTED,
+    prop::PROTOCOL_OBJECT_TYPES_SUPPORTED,
+    prop::OBJECT_LIST,
+    prop::MAX_APDU_LENGTH_ACCEPTED,
+    prop::SEGMENTATION_SUPPORTED,
+    prop::APDU_TIMEOUT,
+    prop::NUMBER_OF_APDU_RETRIES,
+    prop::DEVICE_ADDRESS_BINDING,
+    prop::DATABASE_REVISION,
+];
+const VALUE_REQUIRED: [u32; 7] =
+    [prop::OBJECT_IDENTIFIER, prop::OBJECT_NAME, prop::OBJECT_TYPE, prop::PRESENT_VALUE, prop::STATUS_FLAGS, prop::EVENT_STATE, prop::OUT_OF_SERVICE];
+
+// Error (class, code)
+type BacnetError = (u32, u32);
+const UNKNOWN_OBJECT: BacnetError = (1, 31);
+const UNKNOWN_PROPERTY: BacnetError = (2, 32);
+const WRITE_ACCESS_DENIED: BacnetError = (2, 40);
+const INVALID_ARRAY_INDEX: BacnetError = (2, 42);
+const NOT_AN_ARRAY: BacnetError = (2, 50);
+
+// Layanan confirmed
+const READ_PROPERTY: u8 = 12;
+const READ_PROPERTY_MULTIPLE: u8 = 14;
+const WRITE_PROPERTY: u8 = 15;
+const WRITE_PROPERTY_MULTIPLE: u8 = 16;
+// Layanan unconfirmed
+const I_AM: u8 = 0;
+const WHO_IS: u8 = 8;
+
+const MAX_APDU: usize = 1476;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ObjectId {
+    kind: u16,
+    instance: u32,
+}
+
+impl ObjectId {
+    fn encode(self) -> u32 {
+        ((self.kind as u32) << 22) | (self.instance & 0x3F_FFFF)
+    }
+
+    fn decode(raw: u32) -> Self {
+        Self { kind: (raw >> 22) as u16, instance: raw & 0x3F_FFFF }
+    }
+}
+
+pub struct BacnetServer {
+    config: BacnetConfig,
+    points: Vec<BacnetPoint>,
+    state: Arc<AppState>,
+}
+
+pub async fn serve(config: BacnetConfig, state: Arc<AppState>) -> Result<()> {
+    let socket = UdpSocket::bind(&config.listen).await?;
+    let server = BacnetServer::new(config, state);
+    info!(
+        "🏢 BACnet/IP device {} ({}) listening on {} with {} point(s)",
+        server.config.device_instance,
+        server.config.device_name,
+        server.config.listen,
+        server.points.len()
+    );
+    let mut buf = vec![0u8; 1500];
+    loop {
+        let (len, from) = socket.recv_from(&mut buf).await?;
+        if let Some((reply, to)) = server.handle(&buf[..len], from) {
+            if let Err(e) = socket.send_to(&reply, to).await {
+                warn!("BACnet reply to {} failed: {}", to, e);
+            }
+        }
+    }
+}
+
+impl BacnetServer {
+    pub fn new(config: BacnetConfig, state: Arc<AppState>) -> Self {
+        let points = config.points(&state.zones);
+        Self { config, points, state }
+    }
+
+    // Satu datagram BVLC; Some((balasan, tujuan)) jika perlu dijawab
+    pub fn handle(&self, packet: &[u8], from: SocketAddr) -> Option<(Vec<u8>, SocketAddr)> {
+        if packet.len() < 4 || packet[0] != 0x81 || usize::from(u16::from_be_bytes([packet[2], packet[3]])) != packet.len() {
+            return None;
+        }
+        let (npdu, reply_to) = match packet[1] {
+            // Original-Unicast-NPDU, Original-Broadcast-NPDU
+            0x0A | 0x0B => (&packet[4..], from),
+            // Forwarded-NPDU dari BBMD: alamat B/IP pengirim asli di 6 byte pertama
+            0x04 if packet.len() >= 10 => {
+                let ip = std::net::Ipv4Addr::new(packet[4], packet[5], packet[6], packet[7]);
+                (&packet[10..], SocketAddr::from((ip, u16::from_be_bytes([packet[8], packet[9]]))))
+            }
+            _ => return None,
+        };
+        let (source, apdu) = parse_npdu(npdu)?;
+        let response = self.apdu(apdu)?;
+        let mut out = vec![0x81, 0x0A, 0, 0];
+        // Balasan ke device di balik router: DNET/DADR = SNET/SADR permintaan
+        match source {
+            Some((net, addr)) => {
+                out.extend_from_slice(&[0x01, 0x20]);
+                out.extend_from_slice(&net.to_be_bytes());
+                out.push(addr.len() as u8);
+                out.extend_from_slice(addr);
+                out.push(0xFF);
+            }
+            None => out.extend_from_slice(&[0x01, 0x00]),
+        }
+        out.extend_from_slice(&response);
+        let len = out.len() as u16;
+        out[2..4].copy_from_slice(&len.to_be_bytes());
+        Some((out, reply_to))
+    }
+
+    fn apdu(&self, apdu: &[u8]) -> Option<Vec<u8>> {
+        match apdu.first()? >> 4 {
+            // Confirmed-Request
+            0 => {
+                let (invoke, service) = (*apdu.get(2)?, *apdu.get(3)?);
+                if apdu[0] & 0x08 != 0 {
+                    // Segmentasi tidak didukung: Abort segmentation-not-supported
+                    return Some(vec![0x71, invoke, 4]);
+                }
+                let max_apdu = match apdu[1] & 0x0F {
+                    0 => 50,
+                    1 => 128,
+                    2 => 206,
+                    3 => 480,
+                    4 => 1024,
+                    _ => MAX_APDU,
+                };
+                let body = &apdu[4..];
+                let result = match service {
+                    READ_PROPERTY => self.read_property(body),
+                    READ_PROPERTY_MULTIPLE => self.read_property_multiple(body),
+                    WRITE_PROPERTY | WRITE_PROPERTY_MULTIPLE => Err(Some(WRITE_ACCESS_DENIED)),
+                    // Reject unrecognized-service
+                    _ => return Some(vec![0x60, invoke, 9]),
+                };
+                Some(match result {
+                    Ok(payload) if payload.len() + 3 <= max_apdu => [vec![0x30, invoke, service], payload].concat(),
+                    // Abort segmentation-not-supported: jawaban tidak muat di satu APDU
+                    Ok(_) => vec![0x71, invoke, 4],
+                    Err(Some((class, code))) => {
+                        let mut out = vec![0x50, invoke, service];
+                        enumerated(&mut out, class);
+                        enumerated(&mut out, code);
+                        out
+                    }
+                    // Reject missing-required-parameter / invalid-tag
+                    Err(None) => vec![0x60, invoke, 5],
+                })
+            }
+            // Unconfirmed-Request
+            1 if *apdu.get(1)? == WHO_IS => self.who_is(&apdu[2..]),
+            _ => None,
+        }
+    }
+
+    fn who_is(&self, body: &[u8]) -> Option<Vec<u8>> {
+        let instance = self.config.device_instance;
+        if !body.is_empty() {
+            let mut reader = Reader::new(body);
+            let low = reader.context_unsigned(0)?;
+            let high = reader.context_unsigned(1)?;
+            if instance < low || instance > high {
+                return None;
+            }
+        }
+        debug!("BACnet Who-Is answered with I-Am {}", instance);
+        let mut out = vec![0x10, I_AM];
+        object_id(&mut out, ObjectId { kind: DEVICE, instance });
+        unsigned(&mut out, 2, MAX_APDU as u32);
+        enumerated(&mut out, 3);
+        unsigned(&mut out, 2, self.config.vendor_id as u32);
+        Some(out)
+    }
+
+    // ReadProperty-ACK: objectIdentifier, propertyIdentifier, [arrayIndex], propertyValue
+    fn read_property(&self, body: &[u8]) -> Result<Vec<u8>, Option<BacnetError>> {
+        let mut reader = Reader::new(body);
+        let object = ObjectId::decode(reader.context_unsigned(0).ok_or(None)?);
+        let property = reader.context_unsigned(1).ok_or(None)?;
+        let index = reader.optional_context_unsigned(2);
+        let value = self.property(object, property, index).map_err(Some)?;
+        let mut out = Vec::new();
+        context_object_id(&mut out, 0, object);
+        context_unsigned(&mut out, 1, property);
+        if let Some(index) = index {
+            context_unsigned(&mut out, 2, index);
+        }
+        out.push(0x3E);
+        out.extend_from_slice(&value);
+        out.push(0x3F);
+        Ok(out)
+    }
+
+    // Error per properti dikembalikan di dalam hasil (propertyAccessError), bukan Error PDU
+    fn read_property_multiple(&self, body: &[u8]) -> Result<Vec<u8>, Option<BacnetError>> {
+        let mut reader = Reader::new(body);
+        let mut out = Vec::new();
+        while !reader.is_empty() {
+            let object = ObjectId::decode(reader.context_unsigned(0).ok_or(None)?);
+            reader.opening(1).ok_or(None)?;
+            context_object_id(&mut out, 0, object);
+            out.push(0x1E);
+            while !reader.closing(1) {
+                let property = reader.context_unsigned(0).ok_or(None)?;
+                let index = reader.optional_context_unsigned(1);
+                let properties = match property {
+                    prop::ALL | prop::REQUIRED | prop::OPTIONAL => match self.property_list(object) {
+                        Some((required, optional)) => match property {
+                            prop::ALL => [required, optional].concat(),
+                            prop::REQUIRED => required,
+                            _ => optional,
+                        },
+                        None => vec![property],
+                    },
+                    _ => vec![property],
+                };
+                for property in properties {
+                    context_unsigned(&mut out, 2, property);
+                    if let Some(index) = index {
+                        context_unsigned(&mut out, 3, index);
+                    }
+                    match self.property(object, property, index) {
+                        Ok(value) => {
+                            out.push(0x4E);
+                            out.extend_from_slice(&value);
+                            out.push(0x4F);
+                        }
+                        Err((class, code)) => {
+                            out.push(0x5E);
+                            enumerated(&mut out, class);
+                            enumerated(&mut out, code);
+                            out.push(0x5F);
+                        }
+                    }
+                }
+            }
+            out.push(0x1F);
+        }
+        Ok(out)
+    }
+
+    fn point(&self, object: ObjectId) -> Option<(usize, &BacnetPoint)> {
+        let kind = match object.kind {
+            ANALOG_VALUE => PointKind::Analog,
+            BINARY_VALUE => PointKind::Binary,
+            _ => return None,
+        };
+        self.points.iter().filter(|p| p.kind == kind).enumerate().nth(object.instance as usize)
+    }
+
+    fn objects(&self) -> Vec<ObjectId> {
+        let mut objects = vec![ObjectId { kind: DEVICE, instance: self.config.device_instance }];
+        for kind in [PointKind::Analog, PointKind::Binary] {
+            let type_code = if kind == PointKind::Analog { ANALOG_VALUE } else { BINARY_VALUE };
+            let count = self.points.iter().filter(|p| p.kind == kind).count();
+            objects.extend((0..count as u32).map(|instance| ObjectId { kind: type_code, instance }));
+        }
+        objects
+    }
+
+    // (wajib, opsional) untuk ALL/REQUIRED/OPTIONAL dan property-list
+    fn property_list(&self, object: ObjectId) -> Option<(Vec<u32>, Vec<u32>)> {
+        if object == (ObjectId { kind: DEVICE, instance: self.config.device_instance }) {
+            return Some(([DEVICE_REQUIRED.as_slice(), &[prop::PROPERTY_LIST]].concat(), vec![prop::DESCRIPTION]));
+        }
+        let (_, point) = self.point(object)?;
+        let mut required = [VALUE_REQUIRED.as_slice(), &[prop::PROPERTY_LIST]].concat();
+        if point.kind == PointKind::Analog {
+            required.push(prop::UNITS);
+        }
+        Some((required, vec![prop::DESCRIPTION, prop::RELIABILITY]))
+    }
+
+    // Nilai terbaru key telemetry; None jika tidak ada atau snapshot sudah basi
+    fn value(&self, key: &str) -> Option<f64> {
+        let telemetry = self.state.telemetry.lock().unwrap();
+        let telemetry = telemetry.as_ref()?;
+        let age_ms = (crate::now_ns() / 1_000_000).saturating_sub(telemetry.timestamp_ms);
+        if age_ms > self.config.stale_after.as_millis() as u64 {
+            return None;
+        }
+        telemetry.values.get(key).copied()
+    }
+
+    // Nilai properti sebagai application-tagged data
+    fn property(&self, object: ObjectId, property: u32, index: Option<u32>) -> Result<Vec<u8>, BacnetError> {
+        let (required, optional) = self.property_list(object).ok_or(UNKNOWN_OBJECT)?;
+        if !required.contains(&property) && !optional.contains(&property) {
+            return Err(UNKNOWN_PROPERTY);
+        }
+        let mut out = Vec::new();
+        // Properti array: object-list dan property-list
+        let array = match property {
+            prop::OBJECT_LIST => Some(self.objects().into_iter().map(|o| o.encode()).collect::<Vec<_>>()),
+            prop::PROPERTY_LIST => Some(
+                required.iter().chain(&optional).copied().filter(|p| !matches!(*p, prop::OBJECT_IDENTIFIER | prop::OBJECT_NAME | prop::OBJECT_TYPE | prop::PROPERTY_LIST)).collect(),
+            ),
+            _ => None,
+        };
+        if let Some(items) = array {
+            let encode = |out: &mut Vec<u8>, item: u32| {
+                if property == prop::OBJECT_LIST { object_id(out, ObjectId::decode(item)) } else { enumerated(out, item) }
+            };
+            match index {
+                None => items.iter().for_each(|item| encode(&mut out, *item)),
+                Some(0) => unsigned(&mut out, 2, items.len() as u32),
+                Some(i) => encode(&mut out, *items.get(i as usize - 1).ok_or(INVALID_ARRAY_INDEX)?),
+            }
+            return Ok(out);
+        }
+        if index.is_some() {
+            return Err(NOT_AN_ARRAY);
+        }
+
+        if object.kind == DEVICE {
+            match property {
+                prop::OBJECT_IDENTIFIER => object_id(&mut out, object),
+                prop::OBJECT_NAME => character_string(&mut out, &self.config.device_name),
+                prop::OBJECT_TYPE => enumerated(&mut out, DEVICE as u32),
+                prop::DESCRIPTION => character_string(&mut out, &format!("SHT20 DCS bridge {}", self.state.device_id)),
+                // operational
+                prop::SYSTEM_STATUS => enumerated(&mut out, 0),
+                prop::VENDOR_NAME => character_string(&mut out, "RUST_DCS"),
+                prop::VENDOR_IDENTIFIER => unsigned(&mut out, 2, self.config.vendor_id as u32),
+                prop::MODEL_NAME => character_string(&mut out, env!("CARGO_PKG_NAME")),
+                prop::FIRMWARE_REVISION | prop::APPLICATION_SOFTWARE_VERSION => character_string(&mut out, env!("CARGO_PKG_VERSION")),
+                prop::PROTOCOL_VERSION => unsigned(&mut out, 2, 1),
+                prop::PROTOCOL_REVISION => unsigned(&mut out, 2, 14),
+                prop::PROTOCOL_SERVICES_SUPPORTED => {
+                    bit_string(&mut out, 41, &[READ_PROPERTY as usize, READ_PROPERTY_MULTIPLE as usize, WRITE_PROPERTY as usize, 26, 34])
+                }
+                prop::PROTOCOL_OBJECT_TYPES_SUPPORTED => {
+                    bit_string(&mut out, 60, &[ANALOG_VALUE as usize, BINARY_VALUE as usize, DEVICE as usize])
+                }
+                prop::MAX_APDU_LENGTH_ACCEPTED => unsigned(&mut out, 2, MAX_APDU as u32),
+                // no-segmentation
+                prop::SEGMENTATION_SUPPORTED => enumerated(&mut out, 3),
+                prop::APDU_TIMEOUT => unsigned(&mut out, 2, 3000),
+                prop::NUMBER_OF_APDU_RETRIES => unsigned(&mut out, 2, 3),
+                // List kosong
+                prop::DEVICE_ADDRESS_BINDING => {}
+                prop::DATABASE_REVISION => unsigned(&mut out, 2, 0),
+                _ => return Err(UNKNOWN_PROPERTY),
+            }
+            return Ok(out);
+        }
+
+        let (_, point) = self.point(object).ok_or(UNKNOWN_OBJECT)?;
+        let value = self.value(&point.key);
+        match property {
+            prop::OBJECT_IDENTIFIER => object_id(&mut out, object),
+            prop::OBJECT_NAME => character_string(&mut out, point.object_name()),
+            prop::OBJECT_TYPE => enumerated(&mut out, object.kind as u32),
+            prop::DESCRIPTION => character_string(&mut out, &point.description),
+            prop::PRESENT_VALUE => match point.kind {
+                PointKind::Analog => real(&mut out, value.unwrap_or(0.0) as f32),
+                PointKind::Binary => enumerated(&mut out, value.is_some_and(|v| v != 0.0) as u32),
+            },
+            // in-alarm, fault, overridden, out-of-service
+            prop::STATUS_FLAGS => bit_string(&mut out, 4, if value.is_none() { &[1] } else { &[] }),
+            // normal
+            prop::EVENT_STATE => enumerated(&mut out, 0),
+            prop::OUT_OF_SERVICE => out.push(0x10),
+            // no-fault-detected / no-sensor
+            prop::RELIABILITY => enumerated(&mut out, if value.is_none() { 1 } else { 0 }),
+            prop::UNITS => enumerated(&mut out, point.units.code()),
+            _ => return Err(UNKNOWN_PROPERTY),
+        }
+        Ok(out)
+    }
+}
+
+// SNET dan SADR pengirim di balik router BACnet
+type Source<'a> = (u16, &'a [u8]);
+
+// NPDU: (SNET/SADR jika ada, APDU). Pesan network-layer dan yang ditujukan ke network lain diabaikan.
+fn parse_npdu(npdu: &[u8]) -> Option<(Option<Source<'_>>, &[u8])> {
+    if npdu.len() < 2 || npdu[0] != 0x01 || npdu[1] & 0x80 != 0 {
+        return None;
+    }
+    let control = npdu[1];
+    let mut pos = 2;
+    if control & 0x20 != 0 {
+        let dnet = u16::from_be_bytes([*npdu.get(pos)?, *npdu.get(pos + 1)?]);
+        if dnet != 0xFFFF {
+            return None;
+        }
+        pos += 3 + usize::from(*npdu.get(pos + 2)?);
+    }
+    let mut source = None;
+    if control & 0x08 != 0 {
+        let snet = u16::from_be_bytes([*npdu.get(pos)?, *npdu.get(pos + 1)?]);
+        let slen = usize::from(*npdu.get(pos + 2)?);
+        source = Some((snet, npdu.get(pos + 3..pos + 3 + slen)?));
+        pos += 3 + slen;
+    }
+    if control & 0x20 != 0 {
+        // hop count
+        pos += 1;
+    }
+    Some((source, npdu.get(pos..)?))
+}
+
+// Pembaca tag BACnet (ASN.1 sederhana di clause 20.2)
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    // (nomor tag, context?, lvt, panjang header)
+    fn header(&self) -> Option<(u8, bool, u8, usize)> {
+        let first = *self.data.get(self.pos)?;
+        let mut len = 1;
+        let mut number = first >> 4;
+        if number == 0x0F {
+            number = *self.data.get(self.pos + 1)?;
+            len += 1;
+        }
+        Some((number, first & 0x08 != 0, first & 0x07, len))
+    }
+
+    fn context_unsigned(&mut self, tag: u8) -> Option<u32> {
+        let (number, context, lvt, header) = self.header()?;
+        if !context || number != tag || lvt > 4 || lvt == 0 {
+            return None;
+        }
+        let start = self.pos + header;
+        let bytes = self.data.get(start..start + usize::from(lvt))?;
+        self.pos = start + usize::from(lvt);
+        Some(bytes.iter().fold(0u32, |acc, b| (acc << 8) | u32::from(*b)))
+    }
+
+    fn optional_context_unsigned(&mut self, tag: u8) -> Option<u32> {
+        match self.header() {
+            Some((number, true, lvt, _)) if number == tag && lvt <= 4 => self.context_unsigned(tag),
+            _ => None,
+        }
+    }
+
+    fn opening(&mut self, tag: u8) -> Option<()> {
+        let (number, context, lvt, header) = self.header()?;
+        (context && number == tag && lvt == 6).then(|| self.pos += header)
+    }
+
+    // true (dan dilewati) jika tag berikutnya closing tag ini; data habis juga dianggap penutup
+    fn closing(&mut self, tag: u8) -> bool {
+        match self.header() {
+            Some((number, true, 7, header)) if number == tag => {
+                self.pos += header;
+                true
+            }
+            Some(_) => false,
+            None => true,
+        }
+    }
+}
+
+fn tag_header(out: &mut Vec<u8>, number: u8, context: bool, len: usize) {
+    let class = if context { 0x08 } else { 0 };
+    if len <= 4 {
+        out.push((number << 4) | class | len as u8);
+    } else {
+        out.push((number << 4) | class | 5);
+        if len < 254 {
+            out.push(len as u8);
+        } else {
+            out.push(254);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+    }
+}
+
+fn unsigned_bytes(value: u32) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take(3).take_while(|b| **b == 0).count();
+    bytes[skip..].to_vec()
+}
+
+// Application tag 2 (unsigned) atau 9 (enumerated)
+fn unsigned(out: &mut Vec<u8>, tag: u8, value: u32) {
+    let bytes = unsigned_bytes(value);
+    tag_header(out, tag, false, bytes.len());
+    out.extend_from_slice(&bytes);
+}
+
+fn enumerated(out: &mut Vec<u8>, value: u32) {
+    unsigned(out, 9, value);
+}
+
+fn context_unsigned(out: &mut Vec<u8>, tag: u8, value: u32) {
+    let bytes = unsigned_bytes(value);
+    tag_header(out, tag, true, bytes.len());
+    out.extend_from_slice(&bytes);
+}
+
+fn real(out: &mut Vec<u8>, value: f32) {
+    tag_header(out, 4, false, 4);
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+// UTF-8 (character set 0)
+fn character_string(out: &mut Vec<u8>, text: &str) {
+    tag_header(out, 7, false, text.len() + 1);
+    out.push(0);
+    out.extend_from_slice(text.as_bytes());
+}
+
+// Bit 0 = MSB byte pertama
+fn bit_string(out: &mut Vec<u8>, bits: usize, set: &[usize]) {
+    let mut bytes = vec![0u8; bits.div_ceil(8)];
+    for bit in set.iter().filter(|b| **b < bits) {
+        bytes[bit / 8] |= 0x80 >> (bit % 8);
+    }
+    tag_header(out, 8, false, bytes.len() + 1);
+    out.push((bytes.len() * 8 - bits) as u8);
+    out.extend_from_slice(&bytes);
+}
+
+fn object_id(out: &mut Vec<u8>, object: ObjectId) {
+    tag_header(out, 12, false, 4);
+    out.extend_from_slice(&object.encode().to_be_bytes());
+}
+
+fn context_object_id(out: &mut Vec<u8>, tag: u8, object: ObjectId) {
+    tag_header(out, tag, true, 4);
+    out.extend_from_slice(&object.encode().to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::state::Telemetry;
+
+    const FROM: &str = "10.0.0.9:47808";
+
+    fn server(values: &[(&str, f64)], age_ms: u64) -> BacnetServer {
+        let state = Arc::new(AppState::new(&Config::default()));
+        state.publish_telemetry(Telemetry {
+            timestamp_ms: crate::now_ns() / 1_000_000 - age_ms,
+            values: values.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
+            ..Default::default()
+        });
+        BacnetServer::new(BacnetConfig::default(), state)
+    }
+
+    // BVLC Original-Unicast-NPDU di sekitar NPDU+APDU
+    fn packet(function: u8, npdu: &[u8]) -> Vec<u8> {
+        let len = (npdu.len() + 4) as u16;
+        [vec![0x81, function], len.to_be_bytes().to_vec(), npdu.to_vec()].concat()
+    }
+
+    // Confirmed-Request invoke 1, max APDU 1476
+    fn confirmed(service: u8, body: &[u8]) -> Vec<u8> {
+        packet(0x0A, &[&[0x01, 0x04, 0x00, 0x05, 0x01, service], body].concat())
+    }
+
+    // APDU balasan tanpa BVLC dan NPDU lokal
+    fn reply(server: &BacnetServer, packet: &[u8]) -> Vec<u8> {
+        let (out, to) = server.handle(packet, FROM.parse().unwrap()).expect("reply");
+        assert_eq!(to, FROM.parse().unwrap());
+        assert_eq!(usize::from(u16::from_be_bytes([out[2], out[3]])), out.len());
+        assert_eq!(&out[..2], &[0x81, 0x0A]);
+        assert_eq!(&out[4..6], &[0x01, 0x00]);
+        out[6..].to_vec()
+    }
+
+    fn read(object: ObjectId, property: u32, index: Option<u32>) -> Vec<u8> {
+        let mut body = Vec::new();
+        context_object_id(&mut body, 0, object);
+        context_unsigned(&mut body, 1, property);
+        if let Some(index) = index {
+            context_unsigned(&mut body, 2, index);
+        }
+        confirmed(READ_PROPERTY, &body)
+    }
+
+    fn analog(instance: u32) -> ObjectId {
+        ObjectId { kind: ANALOG_VALUE, instance }
+    }
+
+    #[test]
+    fn primitive_encoding() {
+        assert_eq!(unsigned_bytes(0), vec![0]);
+        assert_eq!(unsigned_bytes(1476), vec![0x05, 0xC4]);
+        assert_eq!(unsigned_bytes(0x0100_0000), vec![1, 0, 0, 0]);
+
+        let mut out = Vec::new();
+        unsigned(&mut out, 2, 300);
+        enumerated(&mut out, 3);
+        context_unsigned(&mut out, 1, 85);
+        real(&mut out, 25.5);
+        assert_eq!(out, vec![0x22, 0x01, 0x2C, 0x91, 0x03, 0x19, 0x55, 0x44, 0x41, 0xCC, 0x00, 0x00]);
+
+        let mut out = Vec::new();
+        character_string(&mut out, "AV");
+        character_string(&mut out, "temp");
+        assert_eq!(out, vec![0x73, 0x00, b'A', b'V', 0x75, 0x05, 0x00, b't', b'e', b'm', b'p']);
+
+        let mut out = Vec::new();
+        tag_header(&mut out, 7, false, 300);
+        assert_eq!(out, vec![0x75, 254, 0x01, 0x2C]);
+
+        // status-flags: fault (bit 1) dari 4 bit
+        let mut out = Vec::new();
+        bit_string(&mut out, 4, &[1, 9]);
+        assert_eq!(out, vec![0x82, 0x04, 0x40]);
+
+        let device = ObjectId { kind: DEVICE, instance: 1001 };
+        assert_eq!(device.encode(), 0x0200_03E9);
+        assert_eq!(ObjectId::decode(device.encode()), device);
+        let mut out = Vec::new();
+        object_id(&mut out, device);
+        context_object_id(&mut out, 0, analog(2));
+        assert_eq!(out, vec![0xC4, 0x02, 0x00, 0x03, 0xE9, 0x0C, 0x00, 0x80, 0x00, 0x02]);
+    }
+
+    #[test]
+    fn who_is_answers_with_i_am_in_range() {
+        let server = server(&[], 0);
+        let i_am = reply(&server, &packet(0x0B, &[0x01, 0x00, 0x10, WHO_IS]));
+        assert_eq!(i_am, vec![0x10, I_AM, 0xC4, 0x02, 0x00, 0x03, 0xE9, 0x22, 0x05, 0xC4, 0x91, 0x03, 0x21, 0x00]);
+
+        // Range 1000..=1001 mengandung device 1001, 2000..=3000 tidak
+        let ranged = |low: &[u8], high: &[u8]| packet(0x0B, &[&[0x01, 0x00, 0x10, WHO_IS], low, high].concat());
+        assert!(server.handle(&ranged(&[0x0A, 0x03, 0xE8], &[0x1A, 0x03, 0xE9]), FROM.parse().unwrap()).is_some());
+        assert!(server.handle(&ranged(&[0x0A, 0x07, 0xD0], &[0x1A, 0x0B, 0xB8]), FROM.parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn read_property_present_value_and_status() {
+        let server = server(&[("sht20_temperature", 25.5), ("pump_status", 1.0)], 0);
+        assert_eq!(
+            reply(&server, &read(analog(0), prop::PRESENT_VALUE, None)),
+            vec![0x30, 0x01, READ_PROPERTY, 0x0C, 0x00, 0x80, 0x00, 0x00, 0x19, 0x55, 0x3E, 0x44, 0x41, 0xCC, 0x00, 0x00, 0x3F]
+        );
+        let pump = ObjectId { kind: BINARY_VALUE, instance: 1 };
+        assert!(reply(&server, &read(pump, prop::PRESENT_VALUE, None)).ends_with(&[0x3E, 0x91, 0x01, 0x3F]));
+        assert!(reply(&server, &read(analog(0), prop::STATUS_FLAGS, None)).ends_with(&[0x3E, 0x82, 0x04, 0x00, 0x3F]));
+        assert!(reply(&server, &read(analog(0), prop::UNITS, None)).ends_with(&[0x3E, 0x91, 62, 0x3F]));
+
+        // Key yang tidak ada di telemetry = fault / no-sensor
+        assert!(reply(&server, &read(analog(1), prop::STATUS_FLAGS, None)).ends_with(&[0x3E, 0x82, 0x04, 0x40, 0x3F]));
+        assert!(reply(&server, &read(analog(1), prop::RELIABILITY, None)).ends_with(&[0x3E, 0x91, 0x01, 0x3F]));
+    }
+
+    #[test]
+    fn stale_snapshot_reports_fault() {
+        let server = server(&[("sht20_temperature", 25.5)], 120_000);
+        assert!(reply(&server, &read(analog(0), prop::PRESENT_VALUE, None)).ends_with(&[0x3E, 0x44, 0, 0, 0, 0, 0x3F]));
+        assert!(reply(&server, &read(analog(0), prop::STATUS_FLAGS, None)).ends_with(&[0x3E, 0x82, 0x04, 0x40, 0x3F]));
+    }
+
+    #[test]
+    fn arrays_and_errors() {
+        let server = server(&[], 0);
+        let device = ObjectId { kind: DEVICE, instance: 1001 };
+        // Device + 3 analog + 2 binary
+        assert!(reply(&server, &read(device, prop::OBJECT_LIST, Some(0))).ends_with(&[0x3E, 0x21, 0x06, 0x3F]));
+        assert!(reply(&server, &read(device, prop::OBJECT_LIST, Some(2))).ends_with(&[0x3E, 0xC4, 0x00, 0x80, 0x00, 0x00, 0x3F]));
+
+        let error = |class: u8, code: u8, service: u8| vec![0x50, 0x01, service, 0x91, class, 0x91, code];
+        assert_eq!(reply(&server, &read(device, prop::OBJECT_LIST, Some(7))), error(2, 42, READ_PROPERTY));
+        assert_eq!(reply(&server, &read(analog(0), prop::PRESENT_VALUE, Some(1))), error(2, 50, READ_PROPERTY));
+        assert_eq!(reply(&server, &read(analog(3), prop::PRESENT_VALUE, None)), error(1, 31, READ_PROPERTY));
+        assert_eq!(reply(&server, &read(analog(0), prop::VENDOR_NAME, None)), error(2, 32, READ_PROPERTY));
+        assert_eq!(reply(&server, &confirmed(WRITE_PROPERTY, &[0x0C, 0, 0x80, 0, 0])), error(2, 40, WRITE_PROPERTY));
+
+        // Reject missing-required-parameter, reject unrecognized-service, abort segmentasi
+        assert_eq!(reply(&server, &confirmed(READ_PROPERTY, &[0x0C, 0, 0x80, 0, 0])), vec![0x60, 0x01, 5]);
+        assert_eq!(reply(&server, &confirmed(5, &[])), vec![0x60, 0x01, 9]);
+        assert_eq!(reply(&server, &packet(0x0A, &[0x01, 0x04, 0x08, 0x05, 0x01, READ_PROPERTY])), vec![0x71, 0x01, 4]);
+    }
+
+    #[test]
+    fn read_property_multiple_expands_required_and_reports_per_property_errors() {
+        let server = server(&[("pump_status", 1.0)], 0);
+        let pump = ObjectId { kind: BINARY_VALUE, instance: 1 };
+        let mut body = Vec::new();
+        context_object_id(&mut body, 0, pump);
+        body.extend_from_slice(&[0x1E, 0x09, prop::REQUIRED as u8, 0x09, prop::UNITS as u8, 0x1F]);
+        let ack = reply(&server, &confirmed(READ_PROPERTY_MULTIPLE, &body));
+
+        assert_eq!(&ack[..3], &[0x30, 0x01, READ_PROPERTY_MULTIPLE]);
+        let contains = |needle: &[u8]| ack.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(&[0x29, 0x55, 0x4E, 0x91, 0x01, 0x4F]));
+        assert!(contains(&[0x29, 0x6F, 0x4E, 0x82, 0x04, 0x00, 0x4F]));
+        // Binary value tidak punya units: propertyAccessError unknown-property
+        assert!(contains(&[0x29, 0x75, 0x5E, 0x91, 0x02, 0x91, 0x20, 0x5F]));
+        assert!(ack.ends_with(&[0x5F, 0x1F]));
+
+        // Jawaban yang tidak muat di max APDU peminta (50 byte) di-abort
+        let mut body = Vec::new();
+        context_object_id(&mut body, 0, ObjectId { kind: DEVICE, instance: 1001 });
+        body.extend_from_slice(&[0x1E, 0x09, prop::ALL as u8, 0x1F]);
+        let small = packet(0x0A, &[&[0x01, 0x04, 0x00, 0x00, 0x01, READ_PROPERTY_MULTIPLE], body.as_slice()].concat());
+        assert_eq!(reply(&server, &small), vec![0x71, 0x01, 4]);
+    }
+
+    #[test]
+    fn routed_and_forwarded_requests() {
+        let server = server(&[], 0);
+        // SNET 5, SADR 0x07 -> balasan dengan DNET/DADR yang sama
+        let routed = packet(0x0A, &[0x01, 0x0C, 0x00, 0x05, 0x01, 0x07, 0x10, WHO_IS]);
+        let (out, _) = server.handle(&routed, FROM.parse().unwrap()).unwrap();
+        assert_eq!(&out[4..11], &[0x01, 0x20, 0x00, 0x05, 0x01, 0x07, 0xFF]);
+
+        let forwarded = packet(0x04, &[192, 168, 1, 20, 0xBA, 0xC0, 0x01, 0x00, 0x10, WHO_IS]);
+        let (_, to) = server.handle(&forwarded, FROM.parse().unwrap()).unwrap();
+        assert_eq!(to, "192.168.1.20:47808".parse().unwrap());
+
+        // Panjang BVLC salah, ditujukan ke network lain, pesan network-layer
+        let mut bad = packet(0x0B, &[0x01, 0x00, 0x10, WHO_IS]);
+        bad[3] += 1;
+        assert!(server.handle(&bad, FROM.parse().unwrap()).is_none());
+        let other_net = packet(0x0B, &[0x01, 0x20, 0x00, 0x09, 0x00, 0xFF, 0x10, WHO_IS]);
+        assert!(server.handle(&other_net, FROM.parse().unwrap()).is_none());
+        assert!(server.handle(&packet(0x0B, &[0x01, 0x80, 0x00]), FROM.parse().unwrap()).is_none());
+    }
+}
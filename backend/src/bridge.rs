@@ -0,0 +1,1699 @@
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use rumqttc::{Client as MqttClient, Event as MqttEvent, Incoming, MqttOptions, Outgoing, QoS};
+use serde_json::json;
+use std::{collections::{HashMap, HashSet}, sync::{atomic::Ordering, Arc, OnceLock}, thread, time::{Duration, Instant}};
+use log::{info, error, warn};
+
+use crate::{
+    alarms, api, bacnet, calibration, cascade, chaos, checkpoint, compaction, dedupe, energy, events, experiments, flux_csv, forecast, grpc, kpi,
+    notify, ota, provision, publish, query_cache, raw_mirror, reconcile, report, retention, secrets, sink, state, stats, tb_alarms, vpd, zone,
+};
+use crate::alarms::Alarm;
+use crate::audit::AuditEntry;
+use crate::checkpoint::Checkpoint;
+use crate::config::{Aggregate, Config};
+use crate::control::{Actuator, ActuatorId, ActuatorMode, Controller, ZoneLoops};
+use crate::energy::EnergyMeter;
+use crate::events::{Event, EventSource};
+use crate::flux_csv::Record;
+use crate::ingest::Sample;
+use crate::mqtt_source::MqttSource;
+use crate::pipeline::{Pipeline, SampleSink, SensorSource};
+use crate::quality::Quality;
+use crate::influx_failover::{self as failover, Target};
+use crate::interlock::InterlockEngine;
+use crate::line_protocol::{self, Point};
+use crate::metrics::{self, METRICS};
+use crate::serial::{AuxReading, Diagnostics, SerialSource};
+use crate::setpoint::{self as setpoints, SetpointSource};
+use crate::settings::{self as runtime_settings, Settings};
+use crate::rules::RuleEngine;
+use crate::state::AppState;
+use crate::systemd::{self, Notifier};
+use crate::telemetry_keys::{self, KeyMapConfig};
+use crate::tenants::{self, Tenant};
+
+// ===================== KONFIGURASI ANDA =====================
+// Default; bisa diganti lewat [connections] di config.toml
+pub const INFLUX_URL: &str = "http://localhost:8086";
+static INFLUX_BASE: OnceLock<String> = OnceLock::new();
+pub(crate) const ORG: &str = "ITS";
+// Token InfluxDB dari env INFLUX_TOKEN atau file INFLUX_TOKEN_FILE (wajib)
+static INFLUX_TOKEN: OnceLock<secrets::Secret> = OnceLock::new();
+// InfluxDB cadangan dari [influx_failover]: (url, token)
+static INFLUX_SECONDARY: OnceLock<(String, secrets::Secret)> = OnceLock::new();
+
+// Data dari sensor SHT20
+pub(crate) const SENSOR_BUCKET: &str = "SENSOR_DATA";
+pub(crate) const SENSOR_MEAS:   &str = "sht20_sensor";
+
+// Data dari DWSIM
+const DWSIM_BUCKET: &str = "DWSIM_DATA";
+pub const DWSIM_MEAS:   &str = "dwsim_temperature";
+
+// ThingsBoard
+pub const TB_HOST:  &str = "demo.thingsboard.io";
+pub const TB_PORT:  u16 = 1883;
+// Access token dari env TB_TOKEN / TB_TOKEN_FILE, atau dari [provision]
+
+// Rentang waktu & window untuk query InfluxDB
+const RANGE:  &str = "-1h";
+const WINDOW: &str = "1m";
+// Jeda antar siklus loop utama
+const CYCLE_INTERVAL: Duration = Duration::from_secs(10);
+// Serial port configuration
+pub const SERIAL_PORT: &str = "/dev/ttyUSB0";
+pub const BAUD_RATE: u32 = 115200;
+// ==========================================================
+
+fn influx_url() -> &'static str {
+    INFLUX_BASE.get().map(String::as_str).unwrap_or(INFLUX_URL)
+}
+
+// Header Authorization InfluxDB (ditandai sensitif, tidak muncul di log debug)
+fn influx_auth() -> reqwest::header::HeaderValue {
+    INFLUX_TOKEN.get().map(|t| t.header("Token")).unwrap_or_else(|| reqwest::header::HeaderValue::from_static(""))
+}
+
+// (base URL, header Authorization) untuk satu endpoint InfluxDB
+fn influx_endpoint(target: Target) -> (&'static str, reqwest::header::HeaderValue) {
+    match (target, INFLUX_SECONDARY.get()) {
+        (Target::Secondary, Some((url, token))) => (url.as_str(), token.header("Token")),
+        _ => (influx_url(), influx_auth()),
+    }
+}
+
+// Urutan endpoint untuk query: target aktif failover dulu, lalu yang lain sebagai cadangan
+fn influx_query_targets() -> Vec<Target> {
+    match failover::active() {
+        None => vec![Target::Primary],
+        Some(Target::Primary) => vec![Target::Primary, Target::Secondary],
+        Some(Target::Secondary) => vec![Target::Secondary, Target::Primary],
+    }
+}
+
+// Sink InfluxDB: line protocol diantrikan lalu dikirim task sendiri (lihat publish.rs).
+// Primary selalu menerima (dan menampung) semua data; secondary hanya selama failover aktif.
+// Data zone tenant lewat sink tenant masing-masing, selalu ke primary (tanpa failover).
+#[derive(Clone)]
+pub(crate) struct Influx {
+    primary: publish::Outbox<InfluxWrite>,
+    secondary: Option<publish::Outbox<InfluxWrite>>,
+    tenants: HashMap<String, publish::Outbox<InfluxWrite>>,
+}
+
+// Body line protocol untuk satu bucket (lihat [retention]); tenant = org dan token tenant
+#[derive(Debug, Clone)]
+struct InfluxWrite {
+    tenant: Option<&'static Tenant>,
+    bucket: &'static str,
+    body: String,
+}
+
+impl Influx {
+    // Selama failover, antrian primary yang penuh tidak dianggap gagal karena data sudah masuk secondary
+    fn push(&self, write: InfluxWrite) -> Result<()> {
+        if let Some(tenant) = write.tenant {
+            let outbox = self.tenants.get(&tenant.name).ok_or_else(|| anyhow!("no InfluxDB sink for tenant {}", tenant.name))?;
+            return outbox.push(write);
+        }
+        match &self.secondary {
+            Some(secondary) if failover::active() == Some(Target::Secondary) => {
+                let _ = self.primary.push(write.clone());
+                secondary.push(write)
+            }
+            _ => self.primary.push(write),
+        }
+    }
+}
+
+// Tujuan InfluxDB untuk test: semua write diterima lalu dibuang
+#[cfg(test)]
+impl Influx {
+    pub(crate) fn discard(state: &AppState) -> Self {
+        let primary = publish::spawn("influx", publish::BreakerConfig::default(), state, |_| async { Ok(()) });
+        Self { primary, secondary: None, tenants: HashMap::new() }
+    }
+}
+
+// Bucket tujuan tulis dan query untuk measurement; tanpa routing [retention] = SENSOR_BUCKET
+pub(crate) fn bucket_for(measurement: &str) -> &'static str {
+    retention::route(measurement).unwrap_or(SENSOR_BUCKET)
+}
+
+// Pesan MQTT ke ThingsBoard, juga lewat antrian dan circuit breaker sendiri
+#[derive(Debug, Clone)]
+pub(crate) struct TbMessage {
+    topic: &'static str,
+    pub(crate) body: String,
+}
+// Semua tujuan ThingsBoard aktif (lihat spawn_thingsboard). Key keluar melewati
+// [telemetry_keys] saat pesan dibentuk, sama untuk semua tujuan.
+#[derive(Clone)]
+pub(crate) struct ThingsBoard {
+    outboxes: publish::Fanout<TbMessage>,
+    keys: Arc<KeyMapConfig>,
+    tenants: Arc<TenantDevices>,
+}
+
+// Device ThingsBoard milik tenant ([tenants.<name>] thingsboard_token): key zone tenant dikirim
+// ke sana, bukan ke tujuan bersama. Prefix <zone>_ dibuang jika tenant hanya punya satu zone.
+#[derive(Default)]
+struct TenantDevices {
+    // (prefix key zone, indeks di devices) urut dari prefix terpanjang; None = zone tanpa device tenant
+    zones: Vec<(String, Option<usize>)>,
+    // (outbox, buang prefix)
+    devices: Vec<(publish::Outbox<TbMessage>, bool)>,
+}
+
+impl ThingsBoard {
+    // Telemetry dengan timestamp eksplisit (ms), supaya pesan yang tertahan di antrian
+    // atau data backlog tampil pada waktu sampel, bukan waktu terkirim
+    pub(crate) fn telemetry(&self, timestamp_ns: u64, values: serde_json::Map<String, serde_json::Value>) -> TbMessage {
+        let values = telemetry_keys::apply(&self.keys, values);
+        let body = json!({ "ts": timestamp_ns / 1_000_000, "values": values }).to_string();
+        TbMessage { topic: "v1/devices/me/telemetry", body }
+    }
+
+    pub(crate) fn attributes(&self, values: serde_json::Map<String, serde_json::Value>) -> TbMessage {
+        let body = serde_json::Value::Object(telemetry_keys::apply(&self.keys, values)).to_string();
+        TbMessage { topic: "v1/devices/me/attributes", body }
+    }
+
+    pub(crate) fn push(&self, message: TbMessage) -> Result<()> {
+        self.outboxes.push(message)
+    }
+
+    // Kirim key zone tenant ke device tenant; sisanya dikembalikan untuk tujuan bersama
+    fn route_tenants(&self, timestamp_ns: u64, values: serde_json::Map<String, serde_json::Value>) -> serde_json::Map<String, serde_json::Value> {
+        if self.tenants.devices.is_empty() {
+            return values;
+        }
+        let mut shared = serde_json::Map::new();
+        let mut per_device = vec![serde_json::Map::new(); self.tenants.devices.len()];
+        for (key, value) in values {
+            match self.tenants.zones.iter().find(|(prefix, _)| key.starts_with(prefix.as_str())) {
+                Some((prefix, Some(device))) => {
+                    let key = if self.tenants.devices[*device].1 { key[prefix.len()..].to_string() } else { key };
+                    per_device[*device].insert(key, value);
+                }
+                _ => {
+                    shared.insert(key, value);
+                }
+            }
+        }
+        for ((outbox, _), values) in self.tenants.devices.iter().zip(per_device) {
+            if !values.is_empty() {
+                if let Err(e) = outbox.push(self.telemetry(timestamp_ns, values)) {
+                    error!("MQTT tenant publish error: {e:#}");
+                }
+            }
+        }
+        shared
+    }
+
+    // Telemetry ke device tenant dan tujuan bersama
+    fn send_telemetry(&self, timestamp_ns: u64, values: serde_json::Map<String, serde_json::Value>) -> Result<()> {
+        let shared = self.route_tenants(timestamp_ns, values);
+        if shared.is_empty() {
+            return Ok(());
+        }
+        self.push(self.telemetry(timestamp_ns, shared))
+    }
+}
+
+// Satu tujuan ThingsBoard: koneksi MQTT, thread event (status koneksi + shared attribute kalibrasi)
+// dan outbox dengan nama sink sendiri. Publish hanya dicoba saat terhubung; selama putus
+// circuit breaker tujuan ini terbuka tanpa menahan tujuan lain.
+fn spawn_thingsboard(
+    name: String,
+    client_id: String,
+    (host, port): (String, u16),
+    token: &secrets::Secret,
+    breaker: publish::BreakerConfig,
+    state: &Arc<AppState>,
+) -> publish::Outbox<TbMessage> {
+    let mut mqtt = MqttOptions::new(client_id, host.clone(), port);
+    mqtt.set_credentials(token.expose(), "");
+    mqtt.set_keep_alive(Duration::from_secs(30));
+
+    let (cli, mut conn) = MqttClient::new(mqtt, 10);
+    let link = state.mqtt_link(&name);
+    let mqtt_state = state.clone();
+    let mqtt_sub = cli.clone();
+    let mqtt_link = link.clone();
+    let mqtt_name = name.clone();
+    thread::spawn(move || {
+        // pkid publish QoS1 yang belum di-ACK -> waktu kirim, untuk histogram latensi MQTT
+        let mut in_flight: HashMap<u16, Instant> = HashMap::new();
+        for ev in conn.iter() {
+            match ev {
+                Ok(MqttEvent::Outgoing(Outgoing::Publish(pkid))) => {
+                    in_flight.insert(pkid, Instant::now());
+                }
+                Ok(MqttEvent::Incoming(Incoming::PubAck(ack))) => {
+                    if let Some(sent) = in_flight.remove(&ack.pkid) {
+                        METRICS.observe(&metrics::MQTT_PUBLISH, &mqtt_name, sent.elapsed());
+                    }
+                }
+                Ok(MqttEvent::Incoming(Incoming::ConnAck(_))) => {
+                    info!("✓ MQTT connected to ThingsBoard ({} at {}:{})", mqtt_name, host, port);
+                    mqtt_link.store(true, Ordering::Relaxed);
+                    // Device tenant hanya menerima telemetry; atribut shared-nya tidak mengubah kontrol
+                    if mqtt_name.starts_with("thingsboard_tenant_") {
+                        continue;
+                    }
+                    // Shared attribute (kalibrasi, dll) dikirim ThingsBoard lewat topic ini
+                    if let Err(e) = mqtt_sub.try_subscribe("v1/devices/me/attributes", QoS::AtLeastOnce) {
+                        error!("MQTT subscribe error ({}): {e:#}", mqtt_name);
+                    }
+                    // Setpoint ThingsBoard yang sudah ada tidak dikirim ulang saat connect, jadi diminta sekali;
+                    // begitu juga fw_* untuk relay OTA (hanya tujuan utama, token-nya dipakai untuk download)
+                    let mut keys = setpoint_attribute_keys(&mqtt_state);
+                    if mqtt_name == "thingsboard" && mqtt_state.ota.enabled {
+                        keys.extend(ota::ATTRIBUTE_KEYS.map(String::from));
+                    }
+                    if !keys.is_empty() {
+                        let request = json!({ "sharedKeys": keys.join(",") }).to_string();
+                        if let Err(e) = mqtt_sub
+                            .try_subscribe("v1/devices/me/attributes/response/+", QoS::AtLeastOnce)
+                            .and_then(|_| mqtt_sub.try_publish("v1/devices/me/attributes/request/1", QoS::AtLeastOnce, false, request))
+                        {
+                            error!("MQTT setpoint attribute request error ({}): {e:#}", mqtt_name);
+                        }
+                    }
+                }
+                Ok(MqttEvent::Incoming(Incoming::Publish(p))) if p.topic.starts_with("v1/devices/me/attributes/response/") => {
+                    match serde_json::from_slice::<serde_json::Value>(&p.payload) {
+                        Ok(attributes) => {
+                            apply_setpoint_attributes(&mqtt_state, &mqtt_name, &attributes, &p.topic);
+                            if mqtt_name == "thingsboard" {
+                                mqtt_state.ota.apply_attributes(&attributes);
+                            }
+                        }
+                        Err(e) => warn!("Invalid attribute response from ThingsBoard ({}): {}", mqtt_name, e),
+                    }
+                }
+                Ok(MqttEvent::Incoming(Incoming::Publish(p))) if p.topic == "v1/devices/me/attributes" => {
+                    match serde_json::from_slice::<serde_json::Value>(&p.payload) {
+                        Ok(attributes) => {
+                            apply_setting_attributes(&mqtt_state, &mqtt_name, &attributes, p.pkid);
+                            apply_setpoint_attributes(&mqtt_state, &mqtt_name, &attributes, &format!("{} pkid={}", p.topic, p.pkid));
+                            if mqtt_name == "thingsboard" {
+                                mqtt_state.ota.apply_attributes(&attributes);
+                            }
+                            let mut table = mqtt_state.calibration.lock().unwrap();
+                            let changed = calibration::apply_attributes(&mut table, &mqtt_state.device_id, &attributes);
+                            if changed > 0 {
+                                info!("🎯 Calibration updated from ThingsBoard attributes ({}, {} value(s))", mqtt_name, changed);
+                                mqtt_state.events.record(
+                                    Event::new("config", "calibration", attributes.to_string())
+                                        .reason(format!("ThingsBoard shared attributes ({})", mqtt_name))
+                                        .source(EventSource::Rpc),
+                                );
+                                mqtt_state.audit.record(AuditEntry::new(
+                                    mqtt_name.clone(),
+                                    "mqtt",
+                                    format!("{} pkid={}", p.topic, p.pkid),
+                                    "calibration",
+                                    mqtt_state.device_id.clone(),
+                                    attributes.to_string(),
+                                ));
+                            }
+                        }
+                        Err(e) => warn!("Invalid attribute update from ThingsBoard ({}): {}", mqtt_name, e),
+                    }
+                }
+                Ok(MqttEvent::Incoming(Incoming::PingResp)) => {} // Do nothing for PingResp
+                Err(e) => {
+                    // Publish yang belum di-ACK dikirim ulang dengan waktu baru setelah reconnect
+                    in_flight.clear();
+                    mqtt_link.store(false, Ordering::Relaxed);
+                    error!("MQTT event error ({}): {e:#}", mqtt_name);
+                }
+                _ => {} // Ignore other events
+            }
+        }
+    });
+
+    let target = name.clone();
+    publish::spawn(name, breaker, state, move |msg: TbMessage| {
+        let cli = cli.clone();
+        let connected = link.load(Ordering::Relaxed);
+        let target = target.clone();
+        async move {
+            if !connected {
+                return Err(anyhow!("not connected to ThingsBoard ({})", target));
+            }
+            if let Some(delay) = chaos::mqtt_delay() {
+                tokio::time::sleep(delay).await;
+            }
+            cli.try_publish(msg.topic, QoS::AtLeastOnce, false, msg.body)
+                .map_err(|e| anyhow!("MQTT publish to {} ({}) failed: {}", msg.topic, target, e))
+        }
+    })
+}
+
+// Atribut shared setting_* dari ThingsBoard; ditolak seluruhnya jika ada nilai tidak valid
+fn apply_setting_attributes(state: &AppState, destination: &str, attributes: &serde_json::Value, pkid: u16) {
+    let Some(patch) = runtime_settings::patch_from_attributes(&state.settings(), attributes) else {
+        return;
+    };
+    let reason = format!("ThingsBoard shared attributes ({})", destination);
+    match state.update_settings(&patch, &reason, EventSource::Rpc) {
+        Ok(changes) if !changes.is_empty() => {
+            let detail = changes.iter().map(|(key, _, new)| format!("{key}={new}")).collect::<Vec<_>>().join(",");
+            state.audit.record(AuditEntry::new(
+                destination,
+                "mqtt",
+                format!("v1/devices/me/attributes pkid={}", pkid),
+                "settings",
+                state.device_id.clone(),
+                detail,
+            ));
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Rejected settings from ThingsBoard ({}): {:#}", destination, e),
+    }
+}
+
+// Atribut shared setpoint untuk zone dengan sumber "thingsboard"
+fn setpoint_attribute_keys(state: &AppState) -> Vec<String> {
+    let multi = state.zones.len() > 1;
+    state
+        .zones
+        .iter()
+        .filter(|z| z.setpoint_config().uses(SetpointSource::Thingsboard))
+        .map(|z| setpoints::attribute_key(&z.name, multi))
+        .collect()
+}
+
+// setpoint_temperature / setpoint_<zone>_temperature dari ThingsBoard; null atau dihapus = tidak ada nilai
+fn apply_setpoint_attributes(state: &AppState, destination: &str, attributes: &serde_json::Value, origin: &str) {
+    let label = |v: Option<f64>| v.map(|t| format!("{:.2}", t)).unwrap_or_else(|| "none".to_string());
+    for (zone, value) in setpoints::from_attributes(&state.zones, attributes) {
+        let previous = state.set_tb_setpoint(&zone, value);
+        if previous == value {
+            continue;
+        }
+        info!("🎯 ThingsBoard setpoint [{}] {} → {} ({})", zone, label(previous), label(value), destination);
+        state.events.record(
+            Event::new("config", format!("setpoint/{}/thingsboard", zone), label(value))
+                .old(label(previous))
+                .reason(format!("ThingsBoard shared attributes ({})", destination))
+                .source(EventSource::Rpc),
+        );
+        state.audit.record(AuditEntry::new(
+            destination,
+            "mqtt",
+            origin,
+            "set_setpoint",
+            zone,
+            format!("thingsboard {} -> {}", label(previous), label(value)),
+        ));
+    }
+}
+
+// Sink utama bridge: InfluxDB plus backfill ThingsBoard untuk sampel backlog
+struct SampleOutputs {
+    influx: Influx,
+    // Sumber tag location/firmware per device
+    state: Arc<AppState>,
+    tb: ThingsBoard,
+    zones: usize,
+    // Sampel lebih tua dari ini dianggap backlog
+    backlog_after: Duration,
+}
+
+impl SampleSink for SampleOutputs {
+    fn publish(&self, sample: &Sample) {
+        if let Err(e) = write_sensor_to_influx(&self.influx, sample, self.state.device_tags(&sample.device_id)) {
+            error!("Failed to upload sensor data: {}", e);
+        }
+        // Sampel backlog (mis. flush setelah putus) tidak akan terbaca loop bridge yang hanya
+        // mengambil titik terakhir, jadi dikirim langsung ke ThingsBoard dengan timestamp-nya
+        let age_ns = now_ns().saturating_sub(sample.timestamp_ns);
+        if !sample.outlier && age_ns > self.backlog_after.as_nanos() as u64 {
+            let prefix = if self.zones > 1 { format!("{}_", sample.zone) } else { String::new() };
+            let mut values = serde_json::Map::new();
+            values.insert(format!("{prefix}temperature"), json!(sample.data.temperature));
+            values.insert(format!("{prefix}humidity"), json!(sample.data.humidity));
+            info!("⏪ Backfilling ThingsBoard with sample from {:.0}s ago", age_ns as f64 / 1e9);
+            if let Err(e) = self.tb.send_telemetry(sample.timestamp_ns, values) {
+                error!("MQTT backfill publish error: {e:#}");
+            }
+        }
+        for (alarm, active) in &sample.alarms {
+            if let Err(e) = write_alarm_to_influx(&self.influx, alarm, *active) {
+                error!("Failed to write alarm to InfluxDB: {}", e);
+            }
+        }
+    }
+
+    fn publish_diagnostics(&self, device_id: &str, diag: &Diagnostics) {
+        let ts = now_ns();
+        let fields = diag.fields();
+        let point = fields
+            .iter()
+            .fold(Point::new("device_diag").tag("device", device_id).tags(self.state.device_tags(device_id)), |point, (name, value)| {
+                point.float(name, *value)
+            })
+            .timestamp(ts);
+        if let Err(e) = write_points(&self.influx, &[point]) {
+            error!("Failed to write diagnostics to InfluxDB: {}", e);
+        }
+        let prefix = if self.zones > 1 { format!("{device_id}_") } else { String::new() };
+        let values = fields.iter().map(|(name, value)| (format!("{prefix}diag_{name}"), json!(value))).collect();
+        if let Err(e) = self.tb.push(self.tb.telemetry(ts, values)) {
+            error!("MQTT diagnostics publish error: {e:#}");
+        }
+    }
+
+    // Disimpan di measurement sensor seperti SENSOR_DATA dengan tag slave; key ThingsBoard ber-prefix
+    // zone seperti sampel dan ber-suffix alamat slave supaya beberapa probe tidak saling menimpa
+    fn publish_aux(&self, device_id: &str, slave: Option<u8>, data: &AuxReading) {
+        let ts = if data.timestamp >= crate::clock::PLAUSIBLE_EPOCH_NS { data.timestamp } else { now_ns() };
+        let zone = zone::for_device(&self.state.zones, device_id).map(|z| z.name.clone()).unwrap_or_default();
+        if let Err(e) = write_points(&self.influx, &[aux_point(&self.state, device_id, &zone, slave, data).timestamp(ts)]) {
+            error!("Failed to write auxiliary data to InfluxDB: {}", e);
+        }
+        let prefix = if self.zones > 1 { format!("{zone}_") } else { String::new() };
+        let suffix = slave.map(|s| format!("_{s}")).unwrap_or_default();
+        let values = data.fields().into_iter().map(|(name, value)| (format!("{prefix}{name}{suffix}"), json!(value))).collect();
+        if let Err(e) = self.tb.send_telemetry(ts, values) {
+            error!("MQTT auxiliary publish error: {e:#}");
+        }
+    }
+}
+
+fn aux_point(state: &AppState, device_id: &str, zone: &str, slave: Option<u8>, data: &AuxReading) -> Point {
+    let series = Point::new(SENSOR_MEAS)
+        .tag("zone", zone)
+        .tag("device", device_id)
+        .tag("slave", slave.map(|s| s.to_string()).unwrap_or_default())
+        .tags(state.device_tags(device_id));
+    data.fields().into_iter().fold(series, |point, (name, value)| point.float_prec(name, value as f64, 1))
+}
+
+// Titik level zone: tag zone dan location sensor utamanya (jika dikonfigurasi)
+pub(crate) fn zone_point(state: &AppState, measurement: &str, zone: &str) -> Point {
+    Point::new(measurement).tag("zone", zone).tag("location", state.zone_location(zone).unwrap_or_default())
+}
+
+// Filter Flux per lokasi; titik lama tanpa tag location tetap ikut. `\` dan `"` di-escape
+// seperti filter zone di SeriesQuery::flux
+pub(crate) fn location_filter(location: Option<&str>) -> String {
+    match location {
+        Some(location) => {
+            let location = location.replace('\\', "\\\\").replace('"', "\\\"");
+            format!("  |> filter(fn: (r) => not exists r[\"location\"] or r[\"location\"] == \"{}\")\n", location)
+        }
+        None => String::new(),
+    }
+}
+
+// Helper function to write data to InfluxDB
+fn write_sensor_to_influx(influx: &Influx, sample: &Sample, device_tags: Vec<(&'static str, String)>) -> Result<()> {
+    let data = &sample.data;
+    // Tag time_source menunjukkan asal timestamp: device, host, atau corrected (skew)
+    let series = Point::new(SENSOR_MEAS)
+        .tag("zone", &sample.zone)
+        .tag("device", &sample.device_id)
+        .tag("time_source", sample.time_source.as_str())
+        .tags(device_tags);
+    // Outlier yang di-flag disimpan sebagai raw_* agar tidak ikut query kontrol (membaca temperature/humidity)
+    let mut point = if sample.outlier {
+        series
+            .clone()
+            .float_prec("raw_temperature", data.temperature as f64, 2)
+            .float_prec("raw_humidity", data.humidity as f64, 2)
+            .float("outlier", 1.0)
+    } else {
+        series
+            .clone()
+            .float_prec("temperature", data.temperature as f64, 2)
+            .float_prec("humidity", data.humidity as f64, 2)
+    };
+
+    for (field, value) in &sample.extra {
+        point = point.float_prec(field, *value, 3);
+    }
+    point = point.string("quality", sample.quality.as_str());
+    if let Some(gap) = &sample.gap {
+        point = point.float_prec("gap_seconds", gap.seconds, 1);
+    }
+
+    // Only save pump_status, NOT exhaust_fan_status (will be calculated virtually by backend)
+    if let Some(pump) = data.pump_status {
+        point = point.float("pump_status", if pump { 1.0 } else { 0.0 });
+    }
+    if let Some(duty) = data.fan_duty {
+        point = point.float_prec("fan_duty", duty as f64, 0);
+    }
+
+    let mut points = vec![point.timestamp(sample.timestamp_ns)];
+
+    // Titik interpolasi untuk gap pendek, ditandai quality="interpolated"
+    if let Some(gap) = &sample.gap {
+        for (ts, t, h) in &gap.interpolated {
+            points.push(
+                series
+                    .clone()
+                    .float_prec("temperature", *t as f64, 2)
+                    .float_prec("humidity", *h as f64, 2)
+                    .string("quality", Quality::Interpolated.as_str())
+                    .timestamp(*ts),
+            );
+        }
+    }
+
+    write_points(influx, &points)?;
+    let pump_str = data.pump_status.map(|p| if p { "ON" } else { "OFF" }).unwrap_or("N/A");
+    info!("Data queued: T={:.1}°C, H={:.1}%, Pump={}", data.temperature, data.humidity, pump_str);
+    Ok(())
+}
+
+pub fn now_ns() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+// Validasi + serialisasi titik lalu antrikan ke sink InfluxDB (tidak menunggu InfluxDB), satu write per
+// tenant dan bucket; titik dengan tag zone milik tenant masuk org/bucket tenant itu.
+// Selama eksperimen berjalan semua titik diberi tag experiment=<id>
+pub(crate) fn write_points(influx: &Influx, points: &[Point]) -> Result<()> {
+    let experiment = experiments::active();
+    let mut batches: Vec<(Option<&'static Tenant>, &'static str, Vec<Point>)> = Vec::new();
+    for point in points {
+        let point = match &experiment {
+            Some(id) => point.clone().tag("experiment", id),
+            None => point.clone(),
+        };
+        let tenant = point.tag_value("zone").and_then(tenants::for_zone);
+        let bucket = bucket_for(point.measurement());
+        let bucket = tenant.map_or(bucket, |t| t.bucket_or(bucket));
+        let same_tenant = |t: Option<&Tenant>| t.map(|t| &t.name) == tenant.map(|t| &t.name);
+        match batches.iter_mut().find(|(t, b, _)| same_tenant(*t) && *b == bucket) {
+            Some((_, _, batch)) => batch.push(point),
+            None => batches.push((tenant, bucket, vec![point])),
+        }
+    }
+    let mut result = Ok(());
+    for (tenant, bucket, batch) in batches {
+        let pushed = line_protocol::join(&batch).and_then(|body| influx.push(InfluxWrite { tenant, bucket, body }));
+        if result.is_ok() {
+            result = pushed;
+        }
+    }
+    result
+}
+
+// (base URL, header Authorization, org) untuk endpoint dan tenant; tenant selalu di primary
+fn influx_target(target: Target, tenant: Option<&Tenant>) -> (&'static str, reqwest::header::HeaderValue, &str) {
+    match tenant {
+        Some(tenant) => (influx_url(), tenant.auth(), tenant.org.as_str()),
+        None => {
+            let (base, auth) = influx_endpoint(target);
+            (base, auth, ORG)
+        }
+    }
+}
+
+// Kirim line protocol ke bucket; dipanggil task sink InfluxDB
+pub(crate) async fn post_influx_write(client: &Client, target: Target, tenant: Option<&Tenant>, bucket: &str, line: String) -> Result<()> {
+    let (base, auth, org) = influx_target(target, tenant);
+    let url = format!("{}/api/v2/write", base);
+    if chaos::influx_failing() {
+        return Err(anyhow!("InfluxDB write failed: {} (chaos)", reqwest::StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    let started = Instant::now();
+    let response = client
+        .post(&url)
+        .header("Authorization", auth)
+        .header("Content-Type", "text/plain")
+        .query(&[("org", org), ("bucket", bucket)])
+        .body(line)
+        .send()
+        .await;
+    METRICS.observe(&metrics::INFLUX_WRITE, tenants::label(tenant), started.elapsed());
+    let response = response?;
+
+    let status = response.status();
+    if status.is_client_error() && status != reqwest::StatusCode::REQUEST_TIMEOUT && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        // Body ditolak (line protocol salah, bucket tidak ada, token tidak berhak): bukan gangguan sementara
+        let detail = response.text().await.unwrap_or_default();
+        return Err(publish::Rejected(format!("InfluxDB write rejected: {} {}", status, detail.trim())).into());
+    }
+    if !status.is_success() {
+        return Err(anyhow!("InfluxDB write failed: {}", status));
+    }
+    Ok(())
+}
+
+// Event log perpindahan target InfluxDB (failover ke secondary / kembali ke primary)
+fn record_influx_failover(state: &AppState, target: Target, error: Option<&anyhow::Error>) {
+    let old = match target {
+        Target::Primary => Target::Secondary,
+        Target::Secondary => Target::Primary,
+    };
+    let reason = match error {
+        Some(e) => format!("primary write failed: {}", e),
+        None => "primary accepts writes again".to_string(),
+    };
+    match target {
+        Target::Secondary => warn!("🔀 InfluxDB failover: writes and queries switched to secondary ({})", reason),
+        Target::Primary => info!("🔀 InfluxDB primary recovered, queries switched back to primary"),
+    }
+    state.events.record(Event::new("influx", "failover", target.as_str()).old(old.as_str()).reason(reason));
+}
+
+// Catat perubahan status alarm ke InfluxDB (measurement "alarms")
+pub(crate) fn write_alarm_to_influx(influx: &Influx, alarm: &Alarm, active: bool) -> Result<()> {
+    let point = Point::new("alarms")
+        .tag("alarm", &alarm.id)
+        .tag("severity", alarm.severity.as_str())
+        .float("active", if active { 1.0 } else { 0.0 })
+        .timestamp(now_ns());
+    write_points(influx, &[point])
+}
+
+// Simpan counter jam operasi & jumlah switch per aktuator (measurement "actuator_runtime")
+fn write_runtime_to_influx(influx: &Influx, state: &AppState) -> Result<()> {
+    let ts = now_ns();
+    let points: Vec<Point> = {
+        let runtime = state.runtime.lock().unwrap();
+        state
+            .zones
+            .iter()
+            .flat_map(|z| z.actuator_ids())
+            .map(|a| {
+                let r = runtime.get(&a);
+                zone_point(state, "actuator_runtime", &a.zone)
+                    .tag("actuator", a.name())
+                    .float_prec("on_seconds", r.on_seconds, 1)
+                    .float("switch_count", r.switch_count as f64)
+                    .timestamp(ts)
+            })
+            .collect()
+    };
+    write_points(influx, &points)
+}
+
+// Tujuan InfluxDB dan kebijakan query dari config; cukup untuk subcommand yang hanya membaca
+pub(crate) fn install(config: &Config) {
+    let _ = INFLUX_BASE.set(config.connections.influx_url.trim_end_matches('/').to_string());
+    retention::install(&config.retention);
+    query_cache::install(&config.query_cache);
+}
+
+pub(crate) fn require_influx_token() -> Result<()> {
+    let _ = INFLUX_TOKEN.set(secrets::require("INFLUX_TOKEN")?);
+    Ok(())
+}
+
+// Secret, failover, tenant dan bucket InfluxDB; dipakai bridge dan subcommand compact/export
+pub(crate) async fn connect(http: &Client, config: &Config) -> Result<()> {
+    config.auth.check_exposure(&config.listeners()).map_err(|e| anyhow!(e))?;
+    // Secret dicek di awal agar bridge gagal cepat dengan pesan yang jelas
+    require_influx_token()?;
+    if config.influx_failover.enabled() {
+        let failover_config = &config.influx_failover;
+        let token = secrets::load_or("INFLUX_SECONDARY_TOKEN", &failover_config.token)?;
+        let token = if token.is_empty() { INFLUX_TOKEN.get().cloned().unwrap_or_default() } else { token };
+        let _ = INFLUX_SECONDARY.set((failover_config.secondary_url.trim().trim_end_matches('/').to_string(), token));
+        failover::install(failover_config.failure_threshold);
+    }
+    let tenant_count = tenants::install(&config.tenants, &config.zones())?;
+    if tenant_count > 0 {
+        info!("🏘️  {} tenant(s): {}", tenant_count, tenants::all().iter().map(|t| format!("{} (org {})", t.name, t.org)).collect::<Vec<_>>().join(", "));
+    }
+    if config.retention.create_buckets {
+        ensure_buckets(http, &config.retention).await;
+    }
+    Ok(())
+}
+
+// Startup, task latar dan loop kontrol utama; `experiment` = rencana dari `backend experiment run`
+pub(crate) async fn run(
+    http: Client,
+    config: Config,
+    config_path: &str,
+    experiment: Option<(experiments::Plan, String)>,
+) -> Result<()> {
+    // TB_TOKEN eksplisit menang; tanpa itu token diambil dari cache/provisioning.
+    // Tujuan utama yang dimatikan tidak butuh token.
+    let tb_token = match (config.connections.thingsboard_enabled, secrets::load("TB_TOKEN")?, &config.provision) {
+        (false, _, _) => None,
+        (true, Some(token), _) => Some(token),
+        (true, None, Some(provision)) => Some(provision::access_token(&http, provision, &config.device_id).await?),
+        (true, None, None) => return Err(anyhow!("Missing secret TB_TOKEN: set TB_TOKEN or TB_TOKEN_FILE, or configure [provision]")),
+    };
+    let mut grafana = config.grafana.clone();
+    grafana.api_key = secrets::load_or("GRAFANA_API_KEY", &grafana.api_key)?;
+    let mut notifications = config.notifications.clone();
+    notifications.telegram.bot_token = secrets::load_or("TELEGRAM_BOT_TOKEN", &notifications.telegram.bot_token)?;
+    notifications.webhook.token = secrets::load_or("NOTIFY_WEBHOOK_TOKEN", &notifications.webhook.token)?;
+    notifications.email.password = secrets::load_or("SMTP_PASSWORD", &notifications.email.password)?;
+    let mut tb_alarms = config.thingsboard_alarms.clone();
+    tb_alarms.username = secrets::load_or("TB_USERNAME", &tb_alarms.username)?;
+    tb_alarms.password = secrets::load_or("TB_PASSWORD", &tb_alarms.password)?;
+    let interlocks = InterlockEngine::new(&config.interlocks)?;
+    let rules = RuleEngine::load(&config.control_rules)?;
+    let mut energy = EnergyMeter::new();
+    let state = Arc::new(AppState::new(&config));
+
+    // Settings runtime yang tersimpan menang atas config.toml; file rusak/tidak valid = nilai config
+    match Settings::load(&config.settings.path).and_then(|stored| stored.map(|s| state.settings().merge(&s)).transpose()) {
+        Ok(Some(settings)) => {
+            info!("⚙️  Restored runtime settings from {} (overrides config.toml)", config.settings.path);
+            let (fan, pump) = config.device_bands(&settings);
+            state.queue_device_settings(config.device_set_command(fan, pump));
+            *state.settings.lock().unwrap() = settings;
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Ignoring runtime settings file: {:#}", e),
+    }
+    match state.drift.restore() {
+        Ok(0) => {}
+        Ok(sensors) => info!("💧 Restored humidity drift model for {} sensor(s) from {}", sensors, config.drift.path),
+        Err(e) => warn!("Ignoring humidity drift file: {:#}", e),
+    }
+
+    // Checkpoint lokal lebih lengkap dari InfluxDB; bagian kaskade diterapkan setelah loop dibuat
+    let mut checkpoint = None;
+    if config.checkpoint.enabled {
+        match Checkpoint::load(&config.checkpoint.path) {
+            Ok(loaded) => checkpoint = loaded,
+            Err(e) => warn!("Could not load control state checkpoint: {:#}", e),
+        }
+    }
+
+    // Tanpa checkpoint, pulihkan counter jam operasi dari InfluxDB agar restart tidak mereset jadwal maintenance
+    if checkpoint.is_none() {
+        match get_runtime_counters(&http, bucket_for("actuator_runtime"), state.tags.location(&state.device_id)).await {
+            Ok(restored) => {
+                let mut runtime = state.runtime.lock().unwrap();
+                for (actuator, on_seconds, switch_count) in restored {
+                    info!("Restored {} runtime: {:.1} h, {} switches", actuator, on_seconds / 3600.0, switch_count);
+                    runtime.restore(actuator, on_seconds, switch_count);
+                }
+            }
+            Err(e) => warn!("Could not restore actuator runtime counters: {}", e),
+        }
+    }
+
+    // Setiap sink punya task, antrian, dan circuit breaker sendiri
+    let influx_http = http.clone();
+    let failover_state = state.clone();
+    let primary = publish::spawn("influxdb", config.publish.influx.clone(), &state, move |write: InfluxWrite| {
+        let client = influx_http.clone();
+        let state = failover_state.clone();
+        async move {
+            let result = post_influx_write(&client, Target::Primary, None, write.bucket, write.body).await;
+            // Penolakan 4xx berarti primary tetap terjangkau, bukan alasan failover
+            let reachable = result.as_ref().map_or_else(publish::is_rejected, |_| true);
+            if let Some(target) = failover::record_primary(reachable) {
+                record_influx_failover(&state, target, result.as_ref().err());
+            }
+            result
+        }
+    });
+    let secondary = INFLUX_SECONDARY.get().map(|_| {
+        let client = http.clone();
+        publish::spawn("influxdb_secondary", config.publish.influx.clone(), &state, move |write: InfluxWrite| {
+            let client = client.clone();
+            async move { post_influx_write(&client, Target::Secondary, None, write.bucket, write.body).await }
+        })
+    });
+    // Satu sink per tenant: token yang salah atau org yang penuh tidak menahan data tenant lain
+    let tenant_sinks = tenants::all()
+        .iter()
+        .map(|tenant| {
+            let client = http.clone();
+            let outbox = publish::spawn(tenant.sink_name(), config.publish.influx.clone(), &state, move |write: InfluxWrite| {
+                let client = client.clone();
+                async move { post_influx_write(&client, Target::Primary, write.tenant, write.bucket, write.body).await }
+            });
+            (tenant.name.clone(), outbox)
+        })
+        .collect();
+    let influx = Influx { primary, secondary, tenants: tenant_sinks };
+
+    let sink = config.sink.clone().map(sink::spawn);
+    if let Some(rx) = state.events.take_receiver() {
+        tokio::spawn(events::run_writer(http.clone(), influx.clone(), grafana, sink.clone(), rx));
+    }
+
+    // SMTP yang sama dipakai laporan harian
+    let smtp = notifications.email.clone();
+    // Dengan mirror semua alarm sudah diikuti di ThingsBoard; kanal notifikasi "thingsboard" dilewati
+    if notifications.enabled {
+        let tb = (notifications.uses(notify::Channel::Thingsboard) && !tb_alarms.mirror)
+            .then(|| tb_alarms::TbAlarmClient::new(http.clone(), tb_alarms.clone(), &state.device_id));
+        tokio::spawn(notify::run(http.clone(), state.clone(), notifications, tb));
+    }
+    if tb_alarms.mirror {
+        tokio::spawn(tb_alarms::run_mirror(http.clone(), state.clone(), tb_alarms));
+    }
+
+    let api_state = state.clone();
+    let api_listen = config.api.listen.clone();
+    let api_http = http.clone();
+    tokio::spawn(async move {
+        if let Err(e) = api::serve(api_listen, api_state, api_http).await {
+            error!("REST API failed: {}", e);
+        }
+    });
+
+    if config.grpc.enabled {
+        let grpc_state = state.clone();
+        let grpc_listen = config.grpc.listen.clone();
+        tokio::spawn(async move {
+            if let Err(e) = grpc::serve(grpc_listen, grpc_state).await {
+                error!("gRPC server failed: {}", e);
+            }
+        });
+    }
+
+    if config.bacnet.enabled {
+        let bacnet_state = state.clone();
+        let bacnet_config = config.bacnet.clone();
+        tokio::spawn(async move {
+            if let Err(e) = bacnet::serve(bacnet_config, bacnet_state).await {
+                error!("BACnet/IP server failed: {}", e);
+            }
+        });
+    }
+
+    // Tujuan ThingsBoard: utama dari [connections] + TB_TOKEN/[provision], tambahan dari [[thingsboard]].
+    // Payload yang sama dipush ke semua; masing-masing punya koneksi, antrian dan breaker sendiri.
+    let mut destinations = Vec::new();
+    if let Some(token) = &tb_token {
+        let connections = &config.connections;
+        destinations.push(spawn_thingsboard(
+            "thingsboard".to_string(),
+            "rust-bridge".to_string(),
+            (connections.thingsboard_host.clone(), connections.thingsboard_port),
+            token,
+            config.publish.thingsboard.clone(),
+            &state,
+        ));
+    }
+    for destination in config.thingsboard.iter().filter(|d| d.enabled) {
+        let token = secrets::load_or(&destination.token_env(), &destination.token)?;
+        if token.is_empty() {
+            return Err(anyhow!("Missing token for ThingsBoard destination '{}': set token or {}", destination.name, destination.token_env()));
+        }
+        destinations.push(spawn_thingsboard(
+            destination.sink_name(),
+            format!("rust-bridge-{}", destination.name),
+            (destination.host.clone(), destination.port),
+            &token,
+            config.publish.thingsboard.clone(),
+            &state,
+        ));
+    }
+    if destinations.is_empty() {
+        warn!("⚠️  All ThingsBoard destinations are disabled, telemetry goes to InfluxDB only");
+    }
+    let mut tenant_devices = TenantDevices::default();
+    let zones = config.zones();
+    let mut device_of = HashMap::new();
+    let mut tenant_names: Vec<&String> = config.tenants.keys().collect();
+    tenant_names.sort();
+    for name in tenant_names {
+        let tenant = &config.tenants[name];
+        let token = secrets::load_or(&tenants::TenantConfig::thingsboard_token_env(name), &tenant.thingsboard_token)?;
+        if token.is_empty() {
+            continue;
+        }
+        let host = if tenant.thingsboard_host.is_empty() { config.connections.thingsboard_host.clone() } else { tenant.thingsboard_host.clone() };
+        let port = if tenant.thingsboard_port == 0 { config.connections.thingsboard_port } else { tenant.thingsboard_port };
+        let owned = zones.iter().filter(|z| z.tenant.as_deref() == Some(name.as_str())).count();
+        device_of.insert(name.as_str(), tenant_devices.devices.len());
+        tenant_devices.devices.push((
+            spawn_thingsboard(format!("thingsboard_tenant_{name}"), format!("rust-bridge-tenant-{name}"), (host, port), &token, config.publish.thingsboard.clone(), &state),
+            owned == 1,
+        ));
+    }
+    if !tenant_devices.devices.is_empty() {
+        // Prefix key sama dengan payload: <zone>_ jika lebih dari satu zone
+        let multi = zones.len() > 1;
+        for zone in &zones {
+            let prefix = if multi { format!("{}_", zone.name) } else { String::new() };
+            tenant_devices.zones.push((prefix, zone.tenant.as_deref().and_then(|t| device_of.get(t).copied())));
+        }
+        tenant_devices.zones.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+    }
+    let tb = ThingsBoard {
+        outboxes: publish::Fanout::new(destinations),
+        keys: Arc::new(config.telemetry_keys.clone()),
+        tenants: Arc::new(tenant_devices),
+    };
+
+    // Relay OTA: firmware dari ThingsBoard diunduh dengan token device utama lalu diteruskan ke ESP32
+    if config.ota.enabled {
+        match tb_token.clone() {
+            Some(token) => {
+                let ota_tb = tb.clone();
+                tokio::spawn(ota::run(http.clone(), state.clone(), config.ota.clone(), token, move |values| {
+                    if let Err(e) = ota_tb.push(ota_tb.telemetry(now_ns(), values)) {
+                        warn!("Failed to queue OTA state for ThingsBoard: {}", e);
+                    }
+                }));
+            }
+            None => warn!("⚠️  [ota] enabled but the primary ThingsBoard destination is disabled, firmware updates are not relayed"),
+        }
+    }
+
+    // Serial ESP32 (dan MQTT lokal jika dikonfigurasi) masuk lewat pipeline ingest yang sama
+    let mut pipeline = Pipeline::new(config.clone(), state.clone());
+    pipeline.add_sink(SampleOutputs {
+        influx: influx.clone(),
+        state: state.clone(),
+        tb: tb.clone(),
+        zones: config.zones().len(),
+        backlog_after: config.quality.stale_after(),
+    });
+    if let Some(sink) = &sink {
+        pipeline.add_sink(sink.clone());
+    }
+    let pipeline = pipeline.shared();
+    // Debug: baris serial mentah ke broker MQTT lokal
+    let raw_tap = match config.raw_mirror.clone() {
+        mut mirror if mirror.enabled => {
+            mirror.password = secrets::load_or("RAW_MIRROR_PASSWORD", &mirror.password)?;
+            Some(raw_mirror::spawn(mirror, &config.device_id))
+        }
+        _ => None,
+    };
+    let mut sources: Vec<Box<dyn SensorSource>> = vec![Box::new(SerialSource {
+        port_name: config.connections.serial_port.clone(),
+        baud_rate: config.connections.baud_rate,
+        device_id: config.device_id.clone(),
+        settings: state.device_settings.clone(),
+        relays: state.device_relays.clone(),
+        ota: state.ota.command.clone(),
+        relay_aliases: config.relay_aliases.clone(),
+        raw_tap,
+        strict_handshake: config.connections.strict_handshake,
+        command_timeout: config.connections.command_timeout,
+        command_retries: config.connections.command_retries,
+    })];
+    // Gateway lain yang publish JSON ke broker lokal
+    if let Some(source) = config.mqtt_source.clone() {
+        let password = secrets::load_or("MQTT_SOURCE_PASSWORD", &source.password)?;
+        sources.push(Box::new(MqttSource { config: source, password }));
+    }
+    for source in sources {
+        source.start(pipeline.clone());
+    }
+
+    if config.compaction.enabled {
+        tokio::spawn(compaction::run_task(http.clone(), config.compaction.clone()));
+    }
+
+    if config.stats.enabled {
+        let location = config.tags.location(&config.device_id).map(str::to_string);
+        tokio::spawn(stats::run_task(http.clone(), influx.clone(), tb.clone(), config.stats.clone(), location));
+    }
+
+    if config.report.enabled {
+        let location = config.tags.location(&config.device_id).map(str::to_string);
+        let report = config.report.clone();
+        tokio::spawn(report::run_task(http.clone(), report, smtp, state.device_id.clone(), location));
+    }
+
+    if config.kpi.enabled {
+        tokio::spawn(kpi::run_task(state.clone(), influx.clone(), tb.clone(), config.kpi.clone()));
+    }
+
+    info!("🚀 Backend started:");
+    info!("  - Serial monitoring: {} @ {} baud", config.connections.serial_port, config.connections.baud_rate);
+    let dwsim_zones: Vec<String> = config.zones().into_iter().filter(|z| z.uses_dwsim()).map(|z| z.name).collect();
+    if !dwsim_zones.is_empty() {
+        info!("  - DWSIM setpoint control enabled: zone(s) {}", dwsim_zones.join(", "));
+    }
+    info!("  - InfluxDB bridge: {} → ThingsBoard", influx_url());
+    if let Some((url, _)) = INFLUX_SECONDARY.get() {
+        info!("  - InfluxDB secondary (failover): {}", url);
+    }
+    info!("  - Query interval: {} seconds", CYCLE_INTERVAL.as_secs());
+    for zone in config.zones() {
+        let actuators: Vec<&str> = zone.actuators.iter().map(|a| a.name()).collect();
+        let dwsim = if zone.uses_dwsim() { format!("{}/{}", zone.dwsim_measurement, zone.dwsim_stream) } else { "off".to_string() };
+        info!("  - Zone {}: sensor {}, DWSIM {}, actuators [{}]", zone.name, zone.device_id, dwsim, actuators.join(", "));
+    }
+    if !interlocks.is_empty() {
+        info!("  - Interlocks: {} rule(s) loaded", config.interlocks.len());
+    }
+    if !rules.path().is_empty() {
+        let actuators: Vec<&str> = rules.rules().actuators().map(Actuator::name).collect();
+        info!("  - Control rules: {} [{}] (reloaded on change)", rules.path(), actuators.join(", "));
+    }
+    if config.shadow {
+        warn!("🧪 Shadow mode: fan/pump decisions are logged as *_proposed only, no actuator status is published");
+    }
+    if config.auth.enabled {
+        info!("  - API auth: {} key(s)", config.auth.keys.len());
+        if config.auth.keys.is_empty() {
+            warn!("⚠️  Auth enabled but no keys configured: every API request will be rejected");
+        }
+    } else {
+        warn!("⚠️  API auth disabled: REST and gRPC control endpoints are open");
+    }
+
+    let mut notifier = Notifier::from_env(&config.systemd);
+    notifier.describe(CYCLE_INTERVAL);
+    notifier.starting("Waiting for serial, MQTT and InfluxDB");
+
+    let zones = config.zones();
+    let last_fields = config.last_data.fields();
+    let last_aggregates = config.last_data.fields_by_aggregate();
+    let mut forecasters: HashMap<String, forecast::Forecaster> = HashMap::new();
+    // Terakhir kali DWSIM punya data per zone (awal = start backend)
+    let mut dwsim_seen: HashMap<String, Instant> = HashMap::new();
+    let mut reconciler = reconcile::Reconciler::new(&config.reconcile);
+    let mut cascades: HashMap<String, cascade::CascadeLoop> = zones
+        .iter()
+        .filter_map(|z| Some((z.name.clone(), cascade::CascadeLoop::new(z.cascade.clone()?))))
+        .collect();
+    if let Some(checkpoint) = checkpoint {
+        info!("♻️  Restored control state from {}: {}", config.checkpoint.path, checkpoint::describe(&checkpoint));
+        checkpoint.restore(&state, &mut cascades, &mut energy);
+    }
+    let mut loops = ZoneLoops {
+        controller: Controller::new(&config.actuators),
+        energy,
+        deduper: dedupe::WriteDeduper::new(&config.dedupe),
+        rules,
+        interlocks,
+        tuners: HashMap::new(),
+        cascades,
+        fan_bands: HashMap::new(),
+        heater_bands: HashMap::new(),
+        pump_bands: HashMap::new(),
+        device_band: config.device_bands(&state.settings()),
+        device_shared: HashSet::new(),
+    };
+    let mut last_checkpoint = Instant::now();
+    let mut experiment = experiment.map(|(plan, zone)| {
+        let report_dir = plan.report_dir.clone();
+        (report_dir, tokio::spawn(experiments::run(state.clone(), plan, zone)))
+    });
+    loop {
+        let cycle_start = Instant::now();
+        info!("Querying InfluxDB for bridge data...");
+        // Minimal satu query zone berhasil siklus ini (readiness systemd)
+        let mut influx_ok = false;
+        state.expire_suppressions();
+        // File rule yang diubah operator dimuat ulang; versi tidak valid tidak menggantikan yang berjalan
+        let rules = &mut loops.rules;
+        if let Some(result) = rules.reload_if_changed() {
+            let change = match result {
+                Ok(count) => {
+                    info!("📐 Reloaded {} control rule(s) from {}", count, rules.path());
+                    state.events.record(Event::new("config", "control_rules", "reloaded").reason(format!("{} rule(s) from {}", count, rules.path())));
+                    state.clear_alarm("control_rules_invalid").map(|alarm| (alarm, false))
+                }
+                Err(e) => {
+                    error!("📐 {:#}", e);
+                    state.raise_alarm("control_rules_invalid", alarms::Severity::Warning, format!("{:#}", e)).map(|alarm| (alarm, true))
+                }
+            };
+            if let Some((alarm, active)) = change {
+                if let Err(e) = write_alarm_to_influx(&influx, &alarm, active) {
+                    error!("Failed to write alarm to InfluxDB: {}", e);
+                }
+            }
+        }
+        // Parameter kontrol aktif siklus ini; ikut di payload untuk ketertelusuran
+        let mut cycle = zone::Cycle {
+            influx: &influx,
+            state: &state,
+            config: &config,
+            config_path,
+            settings: state.settings(),
+            maintenance: state.alarms.lock().unwrap().maintenance_remaining(Instant::now()).is_some_and(|r| !r.is_zero()),
+            payload: serde_json::Map::new(),
+            data_quality: Quality::Good,
+        };
+        cycle.payload.extend(cycle.settings.telemetry());
+
+        for zone in &zones {
+            // InfluxDB tidak terjangkau: lewati zone, publikasi ThingsBoard tetap jalan
+            let bucket = tenants::for_zone(&zone.name).map_or(bucket_for(SENSOR_MEAS), |t| t.bucket_or(bucket_for(SENSOR_MEAS)));
+            let rows = match get_last_data(&http, bucket, SENSOR_MEAS, &zone.name, state.zone_location(&zone.name), &last_aggregates, RANGE, WINDOW).await {
+                Ok(rows) => {
+                    influx_ok = true;
+                    rows
+                }
+                Err(e) => {
+                    error!("Zone {}: InfluxDB query failed: {}", zone.name, e);
+                    cycle.data_quality = Quality::Stale;
+                    continue;
+                }
+            };
+            // Tanpa DWSIM (bukan sumber setpoint dan tanpa kaskade) query dilewati
+            let uses_dwsim = zone.uses_dwsim();
+            let dwsim_temp = if uses_dwsim {
+                match get_dwsim_temperature(&http, DWSIM_BUCKET, &zone.dwsim_measurement, &zone.dwsim_stream, RANGE, WINDOW).await {
+                    Ok(data) => data.temp,
+                    Err(e) => {
+                        error!("Zone {}: DWSIM query failed: {}", zone.name, e);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            if uses_dwsim {
+                zone::check_dwsim(&cycle, zone, &mut dwsim_seen, dwsim_temp);
+            }
+
+            let sensor_data = zone::fuse_sensors(&mut cycle, zone, &rows, &last_fields);
+            let zone_quality = zone::check_quality(&mut cycle, zone);
+            zone::publish_readings(&mut cycle, zone, &sensor_data, dwsim_temp);
+            if let (true, Some(temp)) = (config.forecast.enabled, sensor_data.control_temp()) {
+                zone::forecast(&mut cycle, zone, &mut forecasters, temp);
+            }
+            if config.comfort.enabled {
+                zone::comfort(&mut cycle, zone, &sensor_data, zone_quality);
+            }
+            loops.step_zone(&mut cycle, zone, &sensor_data, dwsim_temp);
+        }
+        let zone::Cycle { mut payload, data_quality, .. } = cycle;
+        // Perintah backend vs RELAY_STATUS ESP32 utama; mode shadow tidak memerintah apa pun
+        if config.reconcile.enabled && !config.shadow {
+            reconcile::check_device(&mut reconciler, &influx, &state, &config, &loops);
+        }
+        // Key per aktuator memakai prefix <zone>_ seperti key zone lain jika lebih dari satu zone
+        let actuator_key = |id: &ActuatorId, suffix: &str| {
+            if zones.len() > 1 { format!("{}_{}_{}", id.zone, id.name(), suffix) } else { format!("{}_{}", id.name(), suffix) }
+        };
+        let actuators: Vec<ActuatorId> = zones.iter().flat_map(|z| z.actuator_ids()).collect();
+        for actuator in &actuators {
+            let mode = state.mode(actuator);
+            if mode != ActuatorMode::Auto {
+                payload.insert(actuator_key(actuator, "mode"), json!(mode.as_str()));
+            }
+        }
+
+        {
+            let alarms = state.alarms.lock().unwrap();
+            payload.insert("active_alarms".into(), json!(alarms.active().count()));
+            let maintenance = alarms.maintenance_remaining(Instant::now()).is_some_and(|r| !r.is_zero());
+            payload.insert("maintenance_mode".into(), json!(maintenance as i32));
+        }
+        if config.shadow {
+            payload.insert("shadow_mode".into(), json!(1));
+        }
+
+        // Jam operasi untuk jadwal maintenance
+        {
+            let runtime = state.runtime.lock().unwrap();
+            for actuator in &actuators {
+                let r = runtime.get(actuator);
+                payload.insert(actuator_key(actuator, "run_hours"), json!((r.on_hours() * 100.0).round() / 100.0));
+                payload.insert(actuator_key(actuator, "switch_count"), json!(r.switch_count));
+            }
+        }
+        if let Err(e) = write_runtime_to_influx(&influx, &state) {
+            error!("Failed to write actuator runtime to InfluxDB: {}", e);
+        }
+
+        // Total energi harian (kWh) untuk laporan biaya kontrol iklim
+        if let Some(finished) = loops.energy.roll_day() {
+            let total: f64 = finished.values().sum();
+            info!("⚡ Daily energy total: {:.3} kWh", total);
+            for (actuator, kwh) in &finished {
+                payload.insert(actuator_key(actuator, "energy_daily_kwh"), json!(kwh));
+            }
+            payload.insert("energy_daily_kwh".into(), json!(total));
+        }
+        let mut today_total = 0.0;
+        for actuator in &actuators {
+            let today = loops.energy.totals(actuator).today_kwh;
+            today_total += today;
+            payload.insert(actuator_key(actuator, "energy_today_kwh"), json!((today * 1000.0).round() / 1000.0));
+        }
+        payload.insert("energy_today_kwh".into(), json!((today_total * 1000.0).round() / 1000.0));
+        if let Err(e) = energy::write_to_influx(&influx, &loops.energy, &config, &state) {
+            error!("Failed to write energy estimate to InfluxDB: {}", e);
+        }
+
+        // Kesehatan sink: measurement "sink_health" dan key sink_<name>_up
+        let ts = now_ns();
+        let mut points = Vec::new();
+        for (name, health) in state.sink_health() {
+            let up = health.state == publish::BreakerState::Closed;
+            payload.insert(format!("sink_{name}_up"), json!(up as i32));
+            points.push(
+                Point::new("sink_health")
+                    .tag("sink", name)
+                    .string("state", health.state.as_str())
+                    .float("up", up as i32 as f64)
+                    .float("queued", health.queued as f64)
+                    .float("peak_queued", health.peak_queued as f64)
+                    .float("capacity", health.capacity as f64)
+                    .float("delivered", health.delivered as f64)
+                    .float("failed", health.failed as f64)
+                    .float("dropped", health.dropped as f64)
+                    .float("dead_lettered", health.dead_lettered as f64)
+                    .timestamp(ts),
+            );
+        }
+        // Kosong jika belum ada sink terdaftar
+        if !points.is_empty() {
+            if let Err(e) = write_points(&influx, &points) {
+                error!("Failed to write sink health to InfluxDB: {}", e);
+            }
+        }
+
+        state.publish_telemetry(state::Telemetry {
+            timestamp_ms: now_ns() / 1_000_000,
+            values: payload.iter().filter_map(|(k, v)| Some((k.clone(), v.as_f64().or_else(|| v.as_bool().map(|b| b as i32 as f64))?))).collect(),
+            data_quality: data_quality.as_str().to_string(),
+            active_alarms: state.alarms.lock().unwrap().active().map(|a| a.id.clone()).collect(),
+        });
+
+        if payload.is_empty() {
+            error!("⚠️  No data from InfluxDB (check range/window/measurement/tag/field).");
+        } else {
+            let sample_ts = state.latest_sample_ts().unwrap_or_else(now_ns);
+            // Umur sampel saat diserahkan ke ThingsBoard: batas bawah lag dashboard sebelum antrian/MQTT
+            METRICS.observe(&metrics::TELEMETRY_AGE, "", Duration::from_nanos(now_ns().saturating_sub(sample_ts)));
+            let shared = tb.route_tenants(sample_ts, payload);
+            if !shared.is_empty() {
+                let message = tb.telemetry(sample_ts, shared);
+                info!("→ Publishing to ThingsBoard: {}", message.body);
+                if let Err(e) = tb.push(message) {
+                    error!("MQTT publish error: {e:#}");
+                }
+            }
+        }
+
+        if config.checkpoint.enabled && last_checkpoint.elapsed() >= config.checkpoint.interval {
+            if let Err(e) = Checkpoint::capture(&state, &loops.cascades, &loops.energy).save(&config.checkpoint.path) {
+                warn!("💾 Failed to save control state checkpoint: {:#}", e);
+            }
+            last_checkpoint = Instant::now();
+        }
+
+        *state.last_cycle.lock().unwrap() = Some(Instant::now());
+        METRICS.observe(&metrics::LOOP_CYCLE, "", cycle_start.elapsed());
+        notifier.cycle(&systemd::Health {
+            serial: state.serial_connected(),
+            mqtt: state.mqtt_connected(),
+            influx: influx_ok,
+            active_alarms: state.alarms.lock().unwrap().active().count(),
+        });
+
+        if experiment.as_ref().is_some_and(|(_, task)| task.is_finished()) {
+            let (report_dir, task) = experiment.take().unwrap();
+            notifier.stopping("Experiment finished, writing report");
+            return finish_experiment(&influx, &state, task.await?, &report_dir).await;
+        }
+        tokio::time::sleep(CYCLE_INTERVAL).await;
+    }
+}
+
+// Ringkasan eksperimen ke measurement "experiment_summary" (satu titik per langkah + overall) dan
+// file JSON; antrian InfluxDB ditunggu kosong (maks 10 detik) sebelum proses keluar
+async fn finish_experiment(influx: &Influx, state: &AppState, report: experiments::Report, report_dir: &str) -> Result<()> {
+    report.log();
+    let ts = now_ns();
+    let points: Vec<Point> = report
+        .steps
+        .iter()
+        .chain(std::iter::once(&report.overall))
+        .map(|summary| {
+            let mut point = zone_point(state, "experiment_summary", &report.zone)
+                .tag("experiment", &report.id)
+                .tag("step", &summary.name)
+                .float("duration_s", summary.duration_s)
+                .float("time_in_band_s", summary.time_in_band_s)
+                .int("completed", report.completed as i64);
+            for (field, stats) in [("temperature", &summary.temperature), ("humidity", &summary.humidity)] {
+                for (stat, value) in [("min", stats.min), ("max", stats.max), ("mean", stats.mean)] {
+                    if let Some(value) = value {
+                        point = point.float_prec(&format!("{field}_{stat}"), value, 3);
+                    }
+                }
+            }
+            if let Some(pct) = summary.time_in_band_pct {
+                point = point.float_prec("time_in_band_pct", pct, 2);
+            }
+            if let Some(setpoint) = summary.setpoint {
+                point = point.float_prec("setpoint", setpoint, 2);
+            }
+            point.timestamp(ts)
+        })
+        .collect();
+    if let Err(e) = write_points(influx, &points) {
+        error!("Failed to write experiment summary to InfluxDB: {}", e);
+    }
+    let path = report.save(report_dir)?;
+    info!("🧪 Experiment report written to {}", path);
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        let queued = state.sinks.lock().unwrap().get("influxdb").map_or(0, |h| h.lock().unwrap().queued);
+        if queued == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    Ok(())
+}
+
+// Nilai terakhir per field ([last_data]); accessor untuk field bawaan yang dipakai kontrol
+#[derive(Default, Debug, Clone)]
+pub(crate) struct LastRow(HashMap<String, f64>);
+
+impl LastRow {
+    pub(crate) fn get(&self, field: &str) -> Option<f64> {
+        self.0.get(field).copied()
+    }
+
+    pub(crate) fn set(&mut self, field: &str, value: Option<f64>) {
+        if let Some(value) = value {
+            self.0.insert(field.to_string(), value);
+        }
+    }
+
+    pub(crate) fn temp(&self) -> Option<f64> { self.get("temperature") }
+    pub(crate) fn hum(&self) -> Option<f64> { self.get("humidity") }
+    pub(crate) fn temp_filtered(&self) -> Option<f64> { self.get("temperature_filtered") }
+    pub(crate) fn hum_filtered(&self) -> Option<f64> { self.get("humidity_filtered") }
+    pub(crate) fn exhaust_fan_status(&self) -> Option<f64> { self.get("exhaust_fan_status") }
+    pub(crate) fn pump_status(&self) -> Option<f64> { self.get("pump_status") }
+    pub(crate) fn fan_duty(&self) -> Option<f64> { self.get("fan_duty") }
+
+    // Nilai untuk keputusan kontrol: terfilter jika filter dikonfigurasi
+    pub(crate) fn control_temp(&self) -> Option<f64> { self.temp_filtered().or(self.temp()) }
+    pub(crate) fn control_hum(&self) -> Option<f64> { self.hum_filtered().or(self.hum()) }
+    pub(crate) fn control_vpd(&self) -> Option<f64> {
+        self.control_temp().zip(self.control_hum()).map(|(t, h)| vpd::vpd_kpa(t, h))
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+struct DwsimRow { temp: Option<f64> }
+
+// Buat bucket [retention] yang belum ada; gagal (mis. token tanpa izin) hanya warning
+async fn ensure_buckets(client: &Client, config: &retention::RetentionConfig) {
+    for bucket in &config.buckets {
+        match ensure_bucket(client, bucket).await {
+            Ok(true) => info!("🪣 Created InfluxDB bucket {} (retention {}s)", bucket.name, bucket.retention.as_secs()),
+            Ok(false) => {}
+            Err(e) => warn!("Could not ensure InfluxDB bucket {}: {:#}", bucket.name, e),
+        }
+    }
+}
+
+// true jika bucket baru dibuat
+async fn ensure_bucket(client: &Client, bucket: &retention::BucketRoute) -> Result<bool> {
+    let base = influx_url();
+    let existing: serde_json::Value = client
+        .get(format!("{}/api/v2/buckets", base))
+        .header("Authorization", influx_auth())
+        .query(&[("org", ORG), ("name", bucket.name.as_str())])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if existing["buckets"].as_array().is_some_and(|b| !b.is_empty()) {
+        return Ok(false);
+    }
+    let orgs: serde_json::Value = client
+        .get(format!("{}/api/v2/orgs", base))
+        .header("Authorization", influx_auth())
+        .query(&[("org", ORG)])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let org_id = orgs["orgs"][0]["id"].as_str().ok_or_else(|| anyhow!("organization {} not found", ORG))?;
+    let rules = if bucket.retention.is_zero() {
+        json!([])
+    } else {
+        json!([{ "type": "expire", "everySeconds": bucket.retention.as_secs() }])
+    };
+    let response = client
+        .post(format!("{}/api/v2/buckets", base))
+        .header("Authorization", influx_auth())
+        .json(&json!({ "orgID": org_id, "name": bucket.name, "retentionRules": rules }))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("create failed: {}", response.status()));
+    }
+    Ok(true)
+}
+
+// Hapus titik dari bucket lewat API delete InfluxDB v2 (endpoint aktif failover)
+pub(crate) async fn post_influx_delete(client: &Client, bucket: &str, body: &serde_json::Value) -> Result<()> {
+    let (base, auth) = influx_endpoint(influx_query_targets()[0]);
+    let response = client
+        .post(format!("{}/api/v2/delete", base))
+        .header("Authorization", auth)
+        .query(&[("org", ORG), ("bucket", bucket)])
+        .json(body)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("InfluxDB delete failed: {}", response.status()));
+    }
+    Ok(())
+}
+
+// Fungsi untuk mengirim query ke InfluxDB; dengan failover dicoba endpoint aktif dulu lalu yang lain
+pub(crate) async fn post_influx(client: &Client, flux: String) -> Result<Arc<[Record]>> {
+    post_influx_as(client, None, flux).await
+}
+
+// Query dengan org dan token tenant (hanya primary); cache dipisah per tenant
+pub(crate) async fn post_influx_as(client: &Client, tenant: Option<&Tenant>, flux: String) -> Result<Arc<[Record]>> {
+    let key = match tenant {
+        Some(tenant) => format!("tenant={}\n{}", tenant.name, flux),
+        None => flux.clone(),
+    };
+    let targets = if tenant.is_some() { vec![Target::Primary] } else { influx_query_targets() };
+    query_cache::fetch(&key, || async {
+        let mut last_error = None;
+        for target in targets {
+            match post_influx_query(client, target, tenant, &flux).await {
+                Ok(rows) => return Ok(rows),
+                Err(e) => {
+                    if INFLUX_SECONDARY.get().is_some() {
+                        warn!("InfluxDB {} query failed: {}", target.as_str(), e);
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow!("no InfluxDB endpoint")))
+    })
+    .await
+}
+
+// Query besar (mis. /api/series berhari-hari): baris diserahkan ke `on_row` tanpa disalin,
+// body tidak pernah dimuat utuh ke memori dan tidak lewat query_cache. Failover hanya sebelum body mulai dibaca.
+pub(crate) async fn post_influx_rows(client: &Client, tenant: Option<&Tenant>, flux: &str, mut on_row: impl FnMut(&flux_csv::Row)) -> Result<()> {
+    let mut last_error = None;
+    let targets = if tenant.is_some() { vec![Target::Primary] } else { influx_query_targets() };
+    for target in targets {
+        let started = Instant::now();
+        let resp = match send_influx_query(client, target, tenant, flux).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                if INFLUX_SECONDARY.get().is_some() {
+                    warn!("InfluxDB {} query failed: {}", target.as_str(), e);
+                }
+                last_error = Some(e);
+                continue;
+            }
+        };
+        flux_csv::stream(resp.bytes_stream(), &mut on_row).await?;
+        METRICS.observe(&metrics::INFLUX_QUERY, tenants::label(tenant), started.elapsed());
+        return Ok(());
+    }
+    Err(last_error.unwrap_or_else(|| anyhow!("no InfluxDB endpoint")))
+}
+
+// Response dengan status sukses; body belum dibaca
+async fn send_influx_query(client: &Client, target: Target, tenant: Option<&Tenant>, flux: &str) -> Result<reqwest::Response> {
+    let (base, auth, org) = influx_target(target, tenant);
+    let url = format!("{}/api/v2/query", base);
+    let resp = client
+        .post(&url)
+        .query(&[("org", org)])
+        .header("Authorization", auth)
+        .header("Accept", "application/csv")
+        .header("Content-Type", "application/vnd.flux")
+        .body(flux.to_string())
+        .send()
+        .await?;
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(anyhow!("Influx query FAILED: {} | {}", status, body.trim()));
+    }
+    Ok(resp)
+}
+
+// Body dibaca per chunk lewat parser CSV inkremental; hanya baris hasil yang disimpan
+async fn post_influx_query(client: &Client, target: Target, tenant: Option<&Tenant>, flux: &str) -> Result<Arc<[Record]>> {
+    let started = Instant::now();
+    let rows = flux_csv::collect(send_influx_query(client, target, tenant, flux).await?.bytes_stream()).await?;
+    METRICS.observe(&metrics::INFLUX_QUERY, tenants::label(tenant), started.elapsed());
+    log::debug!("InfluxDB query returned {} row(s)", rows.len());
+    Ok(rows.into())
+}
+
+// Mengambil data terakhir menggunakan metode aggregateWindow (cara yang benar)
+#[allow(clippy::too_many_arguments)]
+async fn get_last_data(
+    client: &Client,
+    bucket: &str,
+    measurement: &str,
+    zone: &str,
+    location: Option<&str>,
+    fields: &[(Aggregate, Vec<String>)],
+    range: &str,
+    window: &str,
+) -> Result<HashMap<String, LastRow>> {
+    let location = location_filter(location);
+    // Satu stream per fungsi agregasi, digabung dengan union jika lebih dari satu
+    let streams: Vec<String> = fields
+        .iter()
+        .map(|(aggregate, fields)| {
+            let filter = fields.iter().map(|f| format!(r#"r["_field"] == "{f}""#)).collect::<Vec<_>>().join(" or ");
+            format!("data\n  |> filter(fn: (r) => {filter})\n  |> aggregateWindow(every: {window}, fn: {}, createEmpty: false)", aggregate.flux())
+        })
+        .collect();
+    let aggregated = match streams.as_slice() {
+        [single] => single.clone(),
+        _ => format!("union(tables: [\n{}\n])", streams.join(",\n")),
+    };
+    let flux = format!(r#"data = from(bucket: "{bucket}")
+  |> range(start: {range})
+  |> filter(fn: (r) => r["_measurement"] == "{measurement}")
+  |> filter(fn: (r) => r["zone"] == "{zone}")
+{location}{aggregated}
+  |> group(columns: ["device", "_field"])
+  |> last()
+"#);
+
+    let rows = post_influx_as(client, tenants::for_zone(zone), flux).await?;
+    Ok(last_rows(&rows))
+}
+
+// Mengambil data temperature dari DWSIM_DATA bucket untuk Water_i stream
+async fn get_dwsim_temperature(
+    client: &Client,
+    bucket: &str,
+    measurement: &str,
+    stream: &str,
+    range: &str,
+    window: &str,
+) -> Result<DwsimRow> {
+    // Filter stream DWSIM milik zone (default Water_i) untuk suhu simulasi sebenarnya
+    let flux = format!(r#"from(bucket: "{bucket}")
+  |> range(start: {range})
+  |> filter(fn: (r) => r["_measurement"] == "{measurement}")
+  |> filter(fn: (r) => r["stream"] == "{stream}")
+  |> filter(fn: (r) => r["_field"] == "temperature_celsius")
+  |> aggregateWindow(every: {window}, fn: mean, createEmpty: false)
+  |> last()
+"#);
+
+    let rows = post_influx(client, flux).await?;
+    Ok(dwsim_row(&rows))
+}
+
+// Mengambil counter runtime terakhir per aktuator
+async fn get_runtime_counters(client: &Client, bucket: &str, location: Option<&str>) -> Result<Vec<(ActuatorId, f64, u64)>> {
+    let location = location_filter(location);
+    let flux = format!(r#"from(bucket: "{bucket}")
+  |> range(start: -365d)
+  |> filter(fn: (r) => r["_measurement"] == "actuator_runtime")
+{location}  |> group(columns: ["zone", "actuator", "_field"])
+  |> last()
+"#);
+
+    let rows = post_influx(client, flux).await?;
+    Ok(runtime_counters(&rows))
+}
+
+// Hasil query aggregateWindow per kolom device; titik tanpa tag device (status aktuator, data lama) di key "".
+fn last_rows(rows: &[Record]) -> HashMap<String, LastRow> {
+    let mut out: HashMap<String, LastRow> = HashMap::new();
+    for row in rows {
+        let (Some(field), Some(value)) = (row.get("_field"), row.number("_value")) else { continue };
+        let device = row.get("device").unwrap_or("");
+        out.entry(device.to_string()).or_default().set(field, Some(value));
+    }
+    out
+}
+
+// Suhu DWSIM terakhir (field temperature_celsius)
+fn dwsim_row(rows: &[Record]) -> DwsimRow {
+    let temp = rows
+        .iter()
+        .rev()
+        .filter(|row| row.get("_field") == Some("temperature_celsius"))
+        .find_map(|row| row.number("_value"));
+    DwsimRow { temp }
+}
+
+// Counter runtime aktuator (kolom zone, actuator, _field, _value)
+fn runtime_counters(rows: &[Record]) -> Vec<(ActuatorId, f64, u64)> {
+    let mut values: HashMap<ActuatorId, (f64, u64)> = HashMap::new();
+    for row in rows {
+        let (Some(zone), Some(actuator), Some(field), Some(value)) =
+            (row.get("zone"), row.get("actuator").and_then(Actuator::parse), row.get("_field"), row.number("_value"))
+        else {
+            continue;
+        };
+        let entry = values.entry(ActuatorId::new(zone, actuator)).or_default();
+        match field {
+            "on_seconds" => entry.0 = value,
+            "switch_count" => entry.1 = value as u64,
+            _ => {}
+        }
+    }
+    values.into_iter().map(|(a, (secs, count))| (a, secs, count)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aux_readings_carry_slave_tag() {
+        let state = AppState::new(&Config::default());
+        let data = AuxReading { timestamp: 1_700_000_000_000_000_000, soil_moisture: Some(41.2), water_level: None };
+        let line = aux_point(&state, "esp32", "main", Some(3), &data).timestamp(data.timestamp).to_line().unwrap();
+        assert_eq!(line, "sht20_sensor,zone=main,device=esp32,slave=3 soil_moisture=41.2 1700000000000000000");
+        // Kanal device utama (tanpa slave) tanpa tag slave
+        let data = AuxReading { water_level: Some(80.0), ..data };
+        let line = aux_point(&state, "esp32", "main", None, &data).to_line().unwrap();
+        assert_eq!(line, "sht20_sensor,zone=main,device=esp32 soil_moisture=41.2,water_level=80.0");
+    }
+
+    #[test]
+    fn location_filter_escapes_quotes() {
+        assert_eq!(location_filter(None), "");
+        // Lokasi tidak bisa menutup string Flux dan menyisipkan ekspresi sendiri
+        let filter = location_filter(Some(r#"lab "b" or true) // \"#));
+        assert_eq!(filter, concat!(r#"  |> filter(fn: (r) => not exists r["location"] or r["location"] == "lab \"b\" or true) // \\")"#, "\n"));
+    }
+}
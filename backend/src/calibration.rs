@@ -0,0 +1,142 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::serial::SensorData;
+
+// Koreksi linear: nilai = gain * raw + offset
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Calibration {
+    pub gain: f64,
+    pub offset: f64,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self { gain: 1.0, offset: 0.0 }
+    }
+}
+
+impl Calibration {
+    pub fn apply(&self, raw: f64) -> f64 {
+        self.gain * raw + self.offset
+    }
+
+    // Hitung gain/offset dari dua pasangan (bacaan sensor, nilai referensi)
+    pub fn two_point(raw_low: f64, ref_low: f64, raw_high: f64, ref_high: f64) -> Result<Self> {
+        let span = raw_high - raw_low;
+        if span.abs() < 1e-6 {
+            return Err(anyhow!("reference readings must differ (raw {} vs {})", raw_low, raw_high));
+        }
+        let gain = (ref_high - ref_low) / span;
+        Ok(Self { gain, offset: ref_low - gain * raw_low })
+    }
+}
+
+// Kalibrasi per device lalu per field (temperature, humidity)
+pub type CalibrationTable = BTreeMap<String, BTreeMap<String, Calibration>>;
+
+pub fn lookup(table: &CalibrationTable, device: &str, field: &str) -> Calibration {
+    table
+        .get(device)
+        .and_then(|fields| fields.get(field))
+        .copied()
+        .unwrap_or_default()
+}
+
+pub fn apply(table: &CalibrationTable, device: &str, data: &mut SensorData) {
+    data.temperature = lookup(table, device, "temperature").apply(data.temperature as f64) as f32;
+    data.humidity = lookup(table, device, "humidity").apply(data.humidity as f64) as f32;
+}
+
+// Update dari atribut shared ThingsBoard, format:
+// {"calibration_temperature_gain": 1.0, "calibration_temperature_offset": -1.2}
+pub fn apply_attributes(table: &mut CalibrationTable, device: &str, attributes: &serde_json::Value) -> usize {
+    let Some(map) = attributes.as_object() else { return 0 };
+    let mut changed = 0;
+    for (key, value) in map {
+        let Some(rest) = key.strip_prefix("calibration_") else { continue };
+        let Some(number) = value.as_f64() else { continue };
+        let (field, param) = match rest.rsplit_once('_') {
+            Some((field, param)) if param == "gain" || param == "offset" => (field, param),
+            _ => continue,
+        };
+        let entry = table
+            .entry(device.to_string())
+            .or_default()
+            .entry(field.to_string())
+            .or_default();
+        if param == "gain" {
+            entry.gain = number;
+        } else {
+            entry.offset = number;
+        }
+        changed += 1;
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn two_point_gain_and_offset() {
+        // Sensor membaca 1 °C terlalu tinggi di 0 dan 3 °C terlalu tinggi di 100
+        let cal = Calibration::two_point(1.0, 0.0, 103.0, 100.0).unwrap();
+        assert!(close(cal.gain, 100.0 / 102.0));
+        assert!(close(cal.offset, -100.0 / 102.0));
+        assert!(close(cal.apply(1.0), 0.0));
+        assert!(close(cal.apply(103.0), 100.0));
+
+        // Urutan titik tidak berpengaruh
+        let swapped = Calibration::two_point(103.0, 100.0, 1.0, 0.0).unwrap();
+        assert!(close(swapped.gain, cal.gain) && close(swapped.offset, cal.offset));
+        // Offset murni
+        let shift = Calibration::two_point(20.0, 18.8, 30.0, 28.8).unwrap();
+        assert!(close(shift.gain, 1.0));
+        assert!(close(shift.offset, -1.2));
+    }
+
+    #[test]
+    fn two_point_rejects_equal_readings() {
+        let err = Calibration::two_point(25.0, 20.0, 25.0, 30.0).unwrap_err();
+        assert!(err.to_string().contains("must differ"));
+        assert!(Calibration::two_point(25.0, 20.0, 25.0000001, 30.0).is_err());
+        // Referensi sama dengan bacaan berbeda tetap valid (gain 0)
+        let flat = Calibration::two_point(10.0, 20.0, 30.0, 20.0).unwrap();
+        assert_eq!(flat, Calibration { gain: 0.0, offset: 20.0 });
+    }
+
+    #[test]
+    fn attributes_update_table_and_apply_per_device() {
+        let mut table = CalibrationTable::new();
+        let attrs = serde_json::json!({
+            "calibration_temperature_offset": -1.5,
+            "calibration_humidity_gain": 0.5,
+            "calibration_humidity_scale": 2.0,
+            "calibration_temperature_gain": "1.1",
+            "setpoint": 25.0,
+        });
+        assert_eq!(apply_attributes(&mut table, "esp32-1", &attrs), 2);
+        assert_eq!(lookup(&table, "esp32-1", "temperature"), Calibration { gain: 1.0, offset: -1.5 });
+        assert_eq!(lookup(&table, "esp32-2", "temperature"), Calibration::default());
+
+        let mut data = SensorData {
+            timestamp: 0,
+            temperature: 26.5,
+            humidity: 80.0,
+            exhaust_fan_status: None,
+            pump_status: None,
+            fan_duty: None,
+            aux: Vec::new(),
+        };
+        apply(&table, "esp32-1", &mut data);
+        assert_eq!((data.temperature, data.humidity), (25.0, 40.0));
+    }
+}
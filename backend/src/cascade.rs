@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+// Kontrol kaskade fan: DWSIM sebagai setpoint luar/feedforward, PID dalam di sensor riil.
+//   setpoint    = model_weight * dwsim + (1 - model_weight) * base_setpoint
+//   feedforward = feedforward_gain * (dwsim - setpoint)
+//   output      = PID(sensor - setpoint) + feedforward, dibatasi 0..1
+// Fan ON jika output >= on_above, OFF jika output <= off_below (hysteresis).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CascadeConfig {
+    pub base_setpoint: f64,
+    pub model_weight: f64,
+    pub feedforward_gain: f64,
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+    pub on_above: f64,
+    pub off_below: f64,
+}
+
+impl Default for CascadeConfig {
+    fn default() -> Self {
+        Self {
+            base_setpoint: 28.0,
+            model_weight: 0.5,
+            feedforward_gain: 0.2,
+            kp: 0.5,
+            ki: 0.01,
+            kd: 0.0,
+            on_above: 0.6,
+            off_below: 0.3,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Pid {
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+    integral: f64,
+    last: Option<(f64, Instant)>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PidTerms {
+    pub p: f64,
+    pub i: f64,
+    pub d: f64,
+}
+
+impl Pid {
+    pub fn new(kp: f64, ki: f64, kd: f64) -> Self {
+        Self { kp, ki, kd, ..Default::default() }
+    }
+
+    // `bias` (feedforward) ikut dihitung saat cek saturasi untuk anti-windup
+    pub fn update(&mut self, error: f64, bias: f64, now: Instant) -> (f64, PidTerms) {
+        let (dt, derivative) = match self.last {
+            Some((last_error, at)) => {
+                let dt = now.saturating_duration_since(at).as_secs_f64();
+                (dt, if dt > 0.0 { (error - last_error) / dt } else { 0.0 })
+            }
+            None => (0.0, 0.0),
+        };
+        self.last = Some((error, now));
+
+        let p = self.kp * error;
+        let d = self.kd * derivative;
+        let candidate = self.integral + error * dt;
+        let unclamped = p + self.ki * candidate + d + bias;
+        // Integrasi kondisional: jangan menambah integral yang mendorong makin jauh ke saturasi
+        let saturating = (unclamped > 1.0 && error > 0.0) || (unclamped < 0.0 && error < 0.0);
+        if !saturating {
+            self.integral = candidate;
+        }
+        let terms = PidTerms { p, i: self.ki * self.integral, d };
+        ((terms.p + terms.i + terms.d + bias).clamp(0.0, 1.0), terms)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CascadeOutput {
+    pub setpoint: f64,
+    pub feedforward: f64,
+    pub output: f64,
+    pub terms: PidTerms,
+    pub demand: bool,
+}
+
+// State internal yang disimpan di checkpoint
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CascadeSnapshot {
+    pub integral: f64,
+    pub demand: bool,
+}
+
+pub struct CascadeLoop {
+    config: CascadeConfig,
+    pid: Pid,
+    demand: bool,
+}
+
+impl CascadeLoop {
+    pub fn new(config: CascadeConfig) -> Self {
+        let pid = Pid::new(config.kp, config.ki, config.kd);
+        Self { config, pid, demand: false }
+    }
+
+    pub fn snapshot(&self) -> CascadeSnapshot {
+        CascadeSnapshot { integral: self.pid.integral, demand: self.demand }
+    }
+
+    // Derivative dimulai ulang; hanya integrator dan hysteresis yang dipulihkan
+    pub fn restore(&mut self, snapshot: CascadeSnapshot) {
+        self.pid.integral = snapshot.integral;
+        self.demand = snapshot.demand;
+    }
+
+    // `setpoint_override` (REST/gRPC) menggantikan setpoint luar sepenuhnya
+    pub fn step(&mut self, sensor: f64, dwsim: Option<f64>, setpoint_override: Option<f64>, now: Instant) -> CascadeOutput {
+        let c = &self.config;
+        let weight = c.model_weight.clamp(0.0, 1.0);
+        let outer = match dwsim {
+            Some(model) => weight * model + (1.0 - weight) * c.base_setpoint,
+            None => c.base_setpoint,
+        };
+        let setpoint = setpoint_override.unwrap_or(outer);
+        let feedforward = dwsim.map(|model| c.feedforward_gain * (model - setpoint)).unwrap_or(0.0);
+
+        // Aksi langsung untuk pendinginan: sensor di atas setpoint -> output naik
+        let (output, terms) = self.pid.update(sensor - setpoint, feedforward, now);
+        if output >= c.on_above {
+            self.demand = true;
+        } else if output <= c.off_below {
+            self.demand = false;
+        }
+        CascadeOutput { setpoint, feedforward, output, terms, demand: self.demand }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const STEP: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn output_is_limited_to_unit_range() {
+        let mut pid = Pid::new(2.0, 0.1, 1.0);
+        let start = Instant::now();
+        for (i, error) in [50.0, -80.0, 1e6, -1e6, 0.0].into_iter().enumerate() {
+            let (output, _) = pid.update(error, 0.3, start + STEP * i as u32);
+            assert!((0.0..=1.0).contains(&output), "output {output} for error {error}");
+        }
+    }
+
+    #[test]
+    fn integral_does_not_wind_up_while_saturated() {
+        let mut pid = Pid::new(0.5, 0.01, 0.0);
+        let start = Instant::now();
+        let mut now = start;
+        // Fan sudah 100% tetapi suhu tetap 5 °C di atas setpoint selama satu jam
+        for _ in 0..360 {
+            now += STEP;
+            assert_eq!(pid.update(5.0, 0.0, now).0, 1.0);
+        }
+        assert!(pid.integral * pid.ki <= 1.0, "integral wound up to {}", pid.integral);
+        // Begitu suhu di bawah setpoint, output harus langsung turun dari saturasi
+        now += STEP;
+        let (output, terms) = pid.update(-1.0, 0.0, now);
+        assert!(output < 0.1, "output {output} stuck by integral {}", terms.i);
+    }
+
+    #[test]
+    fn negative_saturation_does_not_wind_up_either() {
+        let mut pid = Pid::new(0.5, 0.01, 0.0);
+        let start = Instant::now();
+        for i in 1..=360 {
+            assert_eq!(pid.update(-5.0, 0.0, start + STEP * i).0, 0.0);
+        }
+        assert!(pid.integral >= -10.0, "integral wound down to {}", pid.integral);
+    }
+
+    #[test]
+    fn outer_setpoint_blends_model_and_override_wins() {
+        let mut cascade = CascadeLoop::new(CascadeConfig::default());
+        let now = Instant::now();
+        let out = cascade.step(30.0, Some(32.0), None, now);
+        assert_eq!(out.setpoint, 30.0);
+        assert!((out.feedforward - 0.2 * 2.0).abs() < 1e-9);
+        assert_eq!(cascade.step(30.0, None, None, now + STEP).setpoint, 28.0);
+        assert_eq!(cascade.step(30.0, Some(32.0), Some(25.0), now + STEP * 2).setpoint, 25.0);
+    }
+
+    // Ruang sederhana: beban panas tetap, fan mendinginkan sebanding output
+    #[test]
+    fn inner_loop_tracks_outer_setpoint() {
+        let config = CascadeConfig { feedforward_gain: 0.0, ..CascadeConfig::default() };
+        let mut cascade = CascadeLoop::new(config);
+        let start = Instant::now();
+        let mut temp = 33.0;
+        let mut setpoint = 0.0;
+        for (dwsim, steps) in [(32.0, 1500), (30.0, 1500)] {
+            for i in 0..steps {
+                let out = cascade.step(temp, Some(dwsim), None, start + STEP * (i + 1));
+                setpoint = out.setpoint;
+                temp += (0.01 - 0.02 * out.output) * STEP.as_secs_f64();
+            }
+            assert!((temp - setpoint).abs() < 0.05, "temp {temp} setpoint {setpoint}");
+        }
+        assert_eq!(setpoint, 29.0);
+    }
+
+    #[test]
+    fn demand_uses_hysteresis() {
+        let mut cascade = CascadeLoop::new(CascadeConfig { ki: 0.0, feedforward_gain: 0.0, ..CascadeConfig::default() });
+        let now = Instant::now();
+        // kp 0.5: error 1.4 -> 0.7 (ON), 0.9 -> 0.45 (tetap ON), 0.4 -> 0.2 (OFF)
+        assert!(cascade.step(29.4, None, None, now).demand);
+        assert!(cascade.step(28.9, None, None, now + STEP).demand);
+        assert!(!cascade.step(28.4, None, None, now + STEP * 2).demand);
+        assert!(!cascade.step(28.9, None, None, now + STEP * 3).demand);
+    }
+
+    #[test]
+    fn snapshot_restores_integrator_and_demand() {
+        let mut cascade = CascadeLoop::new(CascadeConfig::default());
+        let start = Instant::now();
+        for i in 0..10 {
+            cascade.step(29.5, None, None, start + STEP * i);
+        }
+        let snapshot = cascade.snapshot();
+        let mut restored = CascadeLoop::new(CascadeConfig::default());
+        restored.restore(snapshot);
+        assert_eq!(restored.snapshot().integral, snapshot.integral);
+        assert_eq!(restored.snapshot().demand, snapshot.demand);
+    }
+}
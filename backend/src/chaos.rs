@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Injeksi gangguan saat runtime untuk melatih skenario gagal (buffer InfluxDB, alarm, safe state)
+// sebelum diandalkan di lab. Hook hanya aktif jika backend dibangun dengan feature "chaos"
+// (`cargo run --features chaos`); di build biasa semua hook no-op dan /api/chaos tidak ada.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Faults {
+    // Buang N frame serial berikutnya seperti hilang di kabel (terlihat sebagai celah seq)
+    pub drop_serial_frames: u32,
+    // Semua write InfluxDB gagal dengan 500 selama N detik
+    pub influx_fail_s: u64,
+    // Tunda setiap publish MQTT ThingsBoard; 0 = tanpa tunda
+    pub mqtt_delay_ms: u64,
+}
+
+static DROP_SERIAL: AtomicU32 = AtomicU32::new(0);
+static INFLUX_FAIL_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+static MQTT_DELAY_MS: AtomicU64 = AtomicU64::new(0);
+
+// Gangguan bersifat global per proses: test yang menyuntik gangguan atau menjalankan read loop
+// serial memegang lock ini agar tidak saling mengambil frame yang dibuang
+#[cfg(test)]
+pub(crate) fn test_lock() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Ganti semua gangguan aktif dengan `faults` (Faults::default() = hentikan semuanya)
+pub fn inject(faults: &Faults) {
+    DROP_SERIAL.store(faults.drop_serial_frames, Ordering::Relaxed);
+    *INFLUX_FAIL_UNTIL.lock().unwrap() =
+        (faults.influx_fail_s > 0).then(|| Instant::now() + Duration::from_secs(faults.influx_fail_s));
+    MQTT_DELAY_MS.store(faults.mqtt_delay_ms, Ordering::Relaxed);
+}
+
+// Sisa gangguan yang masih aktif
+pub fn active() -> Faults {
+    let influx_fail_s = INFLUX_FAIL_UNTIL
+        .lock()
+        .unwrap()
+        .map_or(0, |until| until.saturating_duration_since(Instant::now()).as_secs_f64().ceil() as u64);
+    Faults {
+        drop_serial_frames: DROP_SERIAL.load(Ordering::Relaxed),
+        influx_fail_s,
+        mqtt_delay_ms: MQTT_DELAY_MS.load(Ordering::Relaxed),
+    }
+}
+
+// Hook read loop serial: true = frame ini dibuang
+pub fn drop_serial_frame() -> bool {
+    cfg!(feature = "chaos") && DROP_SERIAL.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1)).is_ok()
+}
+
+// Hook write InfluxDB: true = jawab seperti server mengembalikan 500
+pub fn influx_failing() -> bool {
+    cfg!(feature = "chaos") && INFLUX_FAIL_UNTIL.lock().unwrap().is_some_and(|until| Instant::now() < until)
+}
+
+// Hook publish MQTT: tunda sebelum publish
+pub fn mqtt_delay() -> Option<Duration> {
+    let ms = if cfg!(feature = "chaos") { MQTT_DELAY_MS.load(Ordering::Relaxed) } else { 0 };
+    (ms > 0).then(|| Duration::from_millis(ms))
+}
+
+#[cfg(all(test, feature = "chaos"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn injected_faults_are_active_until_used_up() {
+        let _lock = test_lock();
+        inject(&Faults { drop_serial_frames: 3, influx_fail_s: 60, mqtt_delay_ms: 250 });
+        let faults = active();
+        // Bersihkan dulu agar assert yang gagal tidak meninggalkan gangguan untuk test lain
+        let hooks = (influx_failing(), mqtt_delay());
+        let dropped: Vec<bool> = (0..5).map(|_| drop_serial_frame()).collect();
+        inject(&Faults::default());
+
+        assert_eq!(faults.drop_serial_frames, 3);
+        assert!(faults.influx_fail_s > 0 && faults.influx_fail_s <= 60);
+        assert_eq!(faults.mqtt_delay_ms, 250);
+        assert_eq!(hooks, (true, Some(Duration::from_millis(250))));
+        // Tepat tiga frame dibuang, sesudahnya frame lewat lagi
+        assert_eq!(dropped, [true, true, true, false, false]);
+        assert_eq!(active(), Faults::default());
+        assert!(!influx_failing());
+        assert_eq!(mqtt_delay(), None);
+    }
+}
+
+#[cfg(all(test, not(feature = "chaos")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hooks_are_no_ops_without_feature() {
+        let _lock = test_lock();
+        inject(&Faults { drop_serial_frames: 3, influx_fail_s: 60, mqtt_delay_ms: 250 });
+        let hooks = (drop_serial_frame(), influx_failing(), mqtt_delay());
+        let remaining = active();
+        inject(&Faults::default());
+
+        assert_eq!(hooks, (false, false, None));
+        // Counter tidak tersentuh hook yang dimatikan
+        assert_eq!(remaining.drop_serial_frames, 3);
+    }
+}
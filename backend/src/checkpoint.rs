@@ -0,0 +1,116 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::alarms::Alarm;
+use crate::cascade::{CascadeLoop, CascadeSnapshot};
+use crate::control::{ActuatorId, ActuatorMode};
+use crate::energy::{EnergyMeter, EnergyTotals};
+use crate::runtime::ActuatorRuntime;
+use crate::state::AppState;
+
+// Checkpoint state kontrol ke disk agar restart tidak mereset override, jam operasi dan total energi
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CheckpointConfig {
+    pub enabled: bool,
+    pub path: String,
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub interval: Duration,
+}
+
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        Self { enabled: true, path: "control_state.json".to_string(), interval: Duration::from_secs(60) }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub saved_at_ms: u64,
+    pub modes: HashMap<ActuatorId, ActuatorMode>,
+    pub setpoint_override: HashMap<String, f64>,
+    pub runtime: HashMap<ActuatorId, ActuatorRuntime>,
+    // Integrator PID dan status hysteresis kaskade per zone
+    pub cascade: HashMap<String, CascadeSnapshot>,
+    pub alarms: Vec<Alarm>,
+    // Total energi per aktuator dan hari UTC-nya; checkpoint lama tanpa field ini tetap terbaca
+    #[serde(default)]
+    pub energy_day: u64,
+    #[serde(default)]
+    pub energy: HashMap<ActuatorId, EnergyTotals>,
+}
+
+impl Checkpoint {
+    pub fn capture(state: &AppState, cascades: &HashMap<String, CascadeLoop>, energy: &EnergyMeter) -> Self {
+        let runtime = state.runtime.lock().unwrap();
+        let (energy_day, energy) = energy.snapshot();
+        Self {
+            saved_at_ms: crate::now_ns() / 1_000_000,
+            modes: state.modes.lock().unwrap().clone(),
+            setpoint_override: state.setpoint_override.lock().unwrap().clone(),
+            runtime: runtime.all().clone(),
+            cascade: cascades.iter().map(|(zone, c)| (zone.clone(), c.snapshot())).collect(),
+            alarms: state.alarms.lock().unwrap().active().cloned().collect(),
+            energy_day,
+            energy,
+        }
+    }
+
+    // Tulis ke file sementara lalu rename, supaya crash saat menulis tidak merusak checkpoint lama
+    pub fn save(&self, path: &str) -> Result<()> {
+        let tmp = format!("{path}.tmp");
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path))?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Option<Self>> {
+        if !Path::new(path).exists() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+        let checkpoint = serde_json::from_str(&text).with_context(|| format!("Corrupt checkpoint {}", path))?;
+        Ok(Some(checkpoint))
+    }
+
+    // Alarm dipulihkan tanpa event baru; loop kontrol akan clear jika kondisinya sudah hilang
+    pub fn restore(self, state: &AppState, cascades: &mut HashMap<String, CascadeLoop>, energy: &mut EnergyMeter) {
+        *state.modes.lock().unwrap() = self.modes;
+        *state.setpoint_override.lock().unwrap() = self.setpoint_override;
+        {
+            let mut runtime = state.runtime.lock().unwrap();
+            for (actuator, counter) in self.runtime {
+                runtime.restore(actuator, counter.on_seconds, counter.switch_count);
+            }
+        }
+        energy.restore(self.energy_day, self.energy);
+        for (zone, snapshot) in self.cascade {
+            if let Some(cascade) = cascades.get_mut(&zone) {
+                cascade.restore(snapshot);
+            }
+        }
+        let mut alarms = state.alarms.lock().unwrap();
+        for alarm in self.alarms {
+            alarms.raise_with(&alarm.id, alarm.severity, alarm.message, alarm.context);
+            if alarm.acknowledged {
+                alarms.acknowledge(&alarm.id);
+            }
+        }
+    }
+}
+
+// Ringkasan satu baris untuk log startup
+pub fn describe(checkpoint: &Checkpoint) -> String {
+    let manual = checkpoint.modes.values().filter(|m| **m != ActuatorMode::Auto).count();
+    format!(
+        "{} manual mode(s), {} setpoint override(s), {} PID state(s), {} active alarm(s)",
+        manual,
+        checkpoint.setpoint_override.len(),
+        checkpoint.cascade.len(),
+        checkpoint.alarms.len()
+    )
+}
@@ -0,0 +1,102 @@
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use reqwest::Client;
+
+use crate::bridge::{self, bucket_for};
+use crate::config::Config;
+use crate::influx_failover::Target;
+use crate::{check, compaction, experiments, export, import};
+
+// Subcommand baris perintah; tanpa subcommand bridge berjalan normal
+pub async fn run() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let command = args.get(1).map(String::as_str);
+    let config_path = Config::path();
+    match command {
+        // `backend check-config`: validasi config tanpa menjalankan bridge
+        Some("check-config") => std::process::exit(check::run(&config_path)),
+        // `backend schema`: cetak JSON schema model data (sumber model/schema.json)
+        Some("schema") => {
+            println!("{}", serde_json::to_string_pretty(&dcs_model::schema())?);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let http = Client::new();
+    let config = Config::load(&config_path)?;
+    bridge::install(&config);
+    let experiment = match (command, args.get(2).map(String::as_str), args.get(3)) {
+        // `backend import`: backfill CSV historis; --dry-run tidak butuh INFLUX_TOKEN
+        (Some("import"), _, _) => return run_import(&http, &import::ImportArgs::parse(&args[2..])?).await,
+        // `backend experiment run plan.yaml`: bridge berjalan normal sambil menjalankan rencana, keluar setelah laporan
+        (Some("experiment"), Some("run"), Some(path)) => {
+            let plan = experiments::Plan::load(path)?;
+            let zone = plan.validate(&config.zones()).with_context(|| format!("Invalid experiment plan {}", path))?;
+            Some((plan, zone))
+        }
+        (Some("experiment"), _, _) => return Err(anyhow!("usage: backend experiment run <plan.yaml>")),
+        _ => None,
+    };
+
+    bridge::connect(&http, &config).await?;
+    match command {
+        // `backend compact`: satu putaran downsampling lalu keluar
+        Some("compact") => compaction::run_once(&http, &config.compaction).await,
+        // `backend export --out data.xlsx`: workbook Excel dari InfluxDB lalu keluar
+        Some("export") => run_export(&http, &config, &export::ExportArgs::parse(&args[2..])?).await,
+        _ => bridge::run(http, config, &config_path, experiment).await,
+    }
+}
+
+async fn run_export(client: &Client, config: &Config, args: &export::ExportArgs) -> Result<()> {
+    let export_config = config.export.resolved(&config.last_data);
+    let query = export_config.query(args.fields.as_deref(), args.range.as_deref(), args.every.as_deref(), args.zone.as_deref())?;
+    // Lokasi zone = lokasi sensor utamanya, sama seperti /api/series
+    let device_id = match &query.zone {
+        Some(zone) => config.zones().into_iter().find(|z| z.name == *zone).map(|z| z.device_id).ok_or_else(|| anyhow!("unknown zone '{}'", zone))?,
+        None => config.device_id.clone(),
+    };
+    let series = export::fetch(client, &query, config.tags.location(&device_id)).await?;
+    let workbook = export::workbook(&query, &series, &export_config)?;
+    std::fs::write(&args.out, &workbook).with_context(|| format!("Failed to write {}", args.out))?;
+    info!("✅ Exported {} row(s) of {} field(s) over -{} to {}", series.time.len(), query.fields.len(), query.range, args.out);
+    Ok(())
+}
+
+async fn run_import(client: &Client, args: &import::ImportArgs) -> Result<()> {
+    let text = std::fs::read_to_string(&args.csv).with_context(|| format!("Failed to read {}", args.csv))?;
+    let converted = import::convert(args, &text)?;
+    for (line, reason) in converted.skipped.iter().take(10) {
+        warn!("Skipping {} line {}: {}", args.csv, line, reason);
+    }
+    info!(
+        "📥 {}: {} row(s), {} point(s) to write into {}/{}, {} skipped",
+        args.csv,
+        converted.rows,
+        converted.lines.len(),
+        bucket_for(&args.measurement),
+        args.measurement,
+        converted.skipped.len()
+    );
+    if args.dry_run {
+        for line in converted.lines.iter().take(5) {
+            println!("{}", line);
+        }
+        info!("Dry run, nothing written");
+        return Ok(());
+    }
+
+    bridge::require_influx_token()?;
+    let total = converted.lines.len();
+    let mut written = 0;
+    for batch in converted.lines.chunks(args.batch) {
+        bridge::post_influx_write(client, Target::Primary, None, bucket_for(&args.measurement), batch.join("\n"))
+            .await
+            .with_context(|| format!("Import stopped after {} of {} point(s)", written, total))?;
+        written += batch.len();
+        info!("📥 Imported {}/{} point(s) ({:.0}%)", written, total, written as f64 * 100.0 / total as f64);
+    }
+    info!("✅ Import of {} finished", args.csv);
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// Unix time 2020-01-01 dalam nanodetik; di bawah ini timestamp device dianggap waktu sejak boot
+pub const PLAUSIBLE_EPOCH_NS: u64 = 1_577_836_800_000_000_000;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ClockConfig {
+    // Selisih maksimum agar timestamp device dipakai apa adanya
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub max_device_skew: Duration,
+    // Lompatan offset lebih besar dari ini dianggap device reboot
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub reset_threshold: Duration,
+    pub window: usize,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            max_device_skew: Duration::from_secs(5),
+            reset_threshold: Duration::from_secs(60),
+            window: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeSource {
+    Device,
+    Host,
+    Corrected,
+}
+
+impl TimeSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TimeSource::Device => "device",
+            TimeSource::Host => "host",
+            TimeSource::Corrected => "corrected",
+        }
+    }
+}
+
+// Estimasi offset antara clock device dan host. Memakai minimum offset
+// dalam jendela rolling karena latensi transmisi selalu menambah offset.
+pub struct SkewEstimator {
+    config: ClockConfig,
+    offsets: VecDeque<i128>,
+}
+
+impl SkewEstimator {
+    pub fn new(config: ClockConfig) -> Self {
+        Self { config, offsets: VecDeque::new() }
+    }
+
+    pub fn offset_ns(&self) -> Option<i128> {
+        self.offsets.iter().min().copied()
+    }
+
+    // Kembalikan timestamp terkoreksi (ns) dan sumber waktunya
+    pub fn correct(&mut self, device_ns: u64, host_ns: u64) -> (u64, TimeSource) {
+        if device_ns == 0 {
+            return (host_ns, TimeSource::Host);
+        }
+
+        let skew = host_ns as i128 - device_ns as i128;
+        if device_ns >= PLAUSIBLE_EPOCH_NS && skew.unsigned_abs() <= self.config.max_device_skew.as_nanos() {
+            return (device_ns, TimeSource::Device);
+        }
+
+        if let Some(current) = self.offset_ns() {
+            if (skew - current).unsigned_abs() > self.config.reset_threshold.as_nanos() {
+                log::warn!(
+                    "Device clock jumped by {:.1}s (reboot?), resetting skew estimate",
+                    (skew - current) as f64 / 1e9
+                );
+                self.offsets.clear();
+            }
+        }
+        self.offsets.push_back(skew);
+        while self.offsets.len() > self.config.window.max(1) {
+            self.offsets.pop_front();
+        }
+
+        let offset = self.offset_ns().unwrap_or(skew);
+        let corrected = device_ns as i128 + offset;
+        if corrected <= 0 {
+            return (host_ns, TimeSource::Host);
+        }
+        (corrected as u64, TimeSource::Corrected)
+    }
+}
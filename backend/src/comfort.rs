@@ -0,0 +1,106 @@
+use serde::Deserialize;
+
+use crate::quality::Quality;
+
+// Ringkasan kondisi zone untuk widget dashboard: satu status (OK, TOO_HOT, TOO_DRY, ...) dan
+// indeks kenyamanan 0..100 dari suhu/kelembaban terhadap threshold dan kualitas data.
+// Batas panas = temp_on dan batas kering = humidity_on_below (settings runtime), batas
+// dingin dan lembap dari config ini.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ComfortConfig {
+    pub enabled: bool,
+    // Di bawah ini TOO_COLD (°C)
+    pub temp_low: f64,
+    // Di atas ini TOO_HUMID (%RH)
+    pub humidity_high: f64,
+    // Pengurangan indeks per °C / per %RH di luar band
+    pub temp_penalty: f64,
+    pub humidity_penalty: f64,
+}
+
+impl Default for ComfortConfig {
+    fn default() -> Self {
+        Self { enabled: true, temp_low: 18.0, humidity_high: 85.0, temp_penalty: 10.0, humidity_penalty: 2.0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComfortStatus {
+    Ok,
+    TooHot,
+    TooCold,
+    TooDry,
+    TooHumid,
+    SensorFault,
+}
+
+impl ComfortStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ComfortStatus::Ok => "OK",
+            ComfortStatus::TooHot => "TOO_HOT",
+            ComfortStatus::TooCold => "TOO_COLD",
+            ComfortStatus::TooDry => "TOO_DRY",
+            ComfortStatus::TooHumid => "TOO_HUMID",
+            ComfortStatus::SensorFault => "SENSOR_FAULT",
+        }
+    }
+}
+
+// Batas band nyaman zone pada siklus ini
+#[derive(Debug, Clone, Copy)]
+pub struct Band {
+    pub temp_low: f64,
+    pub temp_high: f64,
+    pub humidity_low: f64,
+    pub humidity_high: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Comfort {
+    pub status: ComfortStatus,
+    // None jika data tidak valid (SENSOR_FAULT)
+    pub index: Option<f64>,
+}
+
+impl ComfortConfig {
+    pub fn band(&self, temp_on: f64, humidity_on_below: f64) -> Band {
+        Band { temp_low: self.temp_low, temp_high: temp_on, humidity_low: humidity_on_below, humidity_high: self.humidity_high }
+    }
+
+    // Suhu diprioritaskan di atas kelembaban; penyimpangan terbesar (setelah penalty) menentukan status
+    pub fn evaluate(&self, temperature: Option<f64>, humidity: Option<f64>, quality: Quality, band: Band) -> Comfort {
+        let (Some(t), Some(h)) = (temperature, humidity) else {
+            return Comfort { status: ComfortStatus::SensorFault, index: None };
+        };
+        if quality != Quality::Good || !t.is_finite() || !h.is_finite() {
+            return Comfort { status: ComfortStatus::SensorFault, index: None };
+        }
+        let temp_excess = if t > band.temp_high {
+            (t - band.temp_high, ComfortStatus::TooHot)
+        } else if t < band.temp_low {
+            (band.temp_low - t, ComfortStatus::TooCold)
+        } else {
+            (0.0, ComfortStatus::Ok)
+        };
+        let hum_excess = if h < band.humidity_low {
+            (band.humidity_low - h, ComfortStatus::TooDry)
+        } else if h > band.humidity_high {
+            (h - band.humidity_high, ComfortStatus::TooHumid)
+        } else {
+            (0.0, ComfortStatus::Ok)
+        };
+        let temp_cost = temp_excess.0 * self.temp_penalty;
+        let hum_cost = hum_excess.0 * self.humidity_penalty;
+        let status = if temp_excess.1 != ComfortStatus::Ok && temp_cost >= hum_cost {
+            temp_excess.1
+        } else if hum_excess.1 != ComfortStatus::Ok {
+            hum_excess.1
+        } else {
+            temp_excess.1
+        };
+        let index = (100.0 - temp_cost - hum_cost).clamp(0.0, 100.0);
+        Comfort { status, index: Some((index * 10.0).round() / 10.0) }
+    }
+}
@@ -0,0 +1,283 @@
+use anyhow::Result;
+use log::{error, info};
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::bridge::{bucket_for, now_ns, post_influx, post_influx_delete, ORG, SENSOR_BUCKET};
+use crate::flux_csv::Record;
+
+const DAY_NS: u64 = 86_400 * 1_000_000_000;
+
+// Downsampling data raw lama ke bucket jangka panjang (mean per window)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CompactionConfig {
+    pub enabled: bool,
+    pub target_bucket: String,
+    pub measurements: Vec<String>,
+    // Hanya data yang lebih tua dari ini yang dipadatkan
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub older_than: Duration,
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub window: Duration,
+    // Hapus titik raw setelah agregatnya tertulis
+    pub delete_raw: bool,
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub interval: Duration,
+    // Batas mundur saat bucket tujuan masih kosong
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub max_backfill: Duration,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_bucket: "SENSOR_LONGTERM".to_string(),
+            measurements: vec!["sht20_sensor".to_string()],
+            older_than: Duration::from_secs(7 * 86_400),
+            window: Duration::from_secs(300),
+            delete_raw: false,
+            interval: Duration::from_secs(3600),
+            max_backfill: Duration::from_secs(90 * 86_400),
+        }
+    }
+}
+
+// Satu potongan rentang waktu [start, stop) dalam nanodetik
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    pub start_ns: u64,
+    pub stop_ns: u64,
+}
+
+impl CompactionConfig {
+    fn window_ns(&self) -> u64 {
+        self.window.as_nanos().max(1) as u64
+    }
+
+    // Rentang yang perlu dipadatkan, dipotong per hari dan selaras dengan window
+    pub fn chunks(&self, watermark_ns: Option<u64>, now_ns: u64) -> Vec<Chunk> {
+        let window = self.window_ns();
+        let stop = now_ns.saturating_sub(self.older_than.as_nanos() as u64) / window * window;
+        let start = watermark_ns
+            .unwrap_or_else(|| stop.saturating_sub(self.max_backfill.as_nanos() as u64))
+            / window
+            * window;
+        let step = (DAY_NS / window).max(1) * window;
+        let mut chunks = Vec::new();
+        let mut cursor = start;
+        while cursor < stop {
+            let end = (cursor + step).min(stop);
+            chunks.push(Chunk { start_ns: cursor, stop_ns: end });
+            cursor = end;
+        }
+        chunks
+    }
+
+    fn measurement_filter(&self) -> String {
+        self.measurements
+            .iter()
+            .map(|m| format!(r#"r["_measurement"] == "{m}""#))
+            .collect::<Vec<_>>()
+            .join(" or ")
+    }
+
+    // Waktu titik agregat terakhir di bucket tujuan (akhir window) sebagai int ns
+    pub fn watermark_flux(&self) -> String {
+        format!(
+            r#"from(bucket: "{}")
+  |> range(start: -{}s)
+  |> filter(fn: (r) => {})
+  |> keep(columns: ["_time"])
+  |> group()
+  |> sort(columns: ["_time"])
+  |> last(column: "_time")
+  |> map(fn: (r) => ({{ _value: int(v: r._time) }}))
+"#,
+            self.target_bucket,
+            self.max_backfill.as_secs() + self.older_than.as_secs(),
+            self.measurement_filter()
+        )
+    }
+
+    // Mean per window untuk field numerik; field string/bool (status, kode fault) dibawa dengan
+    // last() supaya delete_raw yang menghapus seluruh measurement tidak menghilangkannya.
+    // Ditulis ke bucket tujuan; hasilnya jumlah titik
+    pub fn aggregate_flux(&self, raw_bucket: &str, org: &str, chunk: Chunk) -> String {
+        format!(
+            r#"import "types"
+
+data = from(bucket: "{raw_bucket}")
+  |> range(start: time(v: {}), stop: time(v: {}))
+  |> filter(fn: (r) => {})
+numeric = (r) => types.isType(v: r._value, type: "float") or types.isType(v: r._value, type: "int") or types.isType(v: r._value, type: "uint")
+means = data
+  |> filter(fn: numeric)
+  |> aggregateWindow(every: {every}s, fn: mean, createEmpty: false)
+lasts = data
+  |> filter(fn: (r) => not numeric(r: r))
+  |> aggregateWindow(every: {every}s, fn: last, createEmpty: false)
+union(tables: [means, lasts])
+  |> to(bucket: "{}", org: "{org}")
+  |> group()
+  |> count()
+"#,
+            chunk.start_ns,
+            chunk.stop_ns,
+            self.measurement_filter(),
+            self.target_bucket,
+            every = self.window.as_secs().max(1),
+        )
+    }
+
+    // Body JSON untuk POST /api/v2/delete, satu per measurement
+    pub fn delete_bodies(&self, chunk: Chunk) -> Vec<serde_json::Value> {
+        self.measurements
+            .iter()
+            .map(|m| {
+                serde_json::json!({
+                    "start": rfc3339(chunk.start_ns),
+                    "stop": rfc3339(chunk.stop_ns),
+                    "predicate": format!(r#"_measurement="{m}""#),
+                })
+            })
+            .collect()
+    }
+}
+
+// Nilai _value pertama hasil query (count atau watermark)
+pub fn parse_first_value(rows: &[Record]) -> Option<u64> {
+    rows.iter().find_map(|row| row.get("_value")?.parse::<i64>().ok()).map(|v| v.max(0) as u64)
+}
+
+// Timestamp ns ke RFC3339 UTC (API delete InfluxDB tidak menerima epoch)
+pub(crate) fn rfc3339(ns: u64) -> String {
+    let secs = ns / 1_000_000_000;
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+// Hari sejak epoch -> (tahun, bulan, tanggal); algoritma civil_from_days (Howard Hinnant)
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Satu putaran downsampling: raw lebih tua dari older_than -> mean per window di bucket jangka panjang
+pub(crate) async fn run_once(client: &Client, config: &CompactionConfig) -> Result<()> {
+    // Semua measurement compaction harus di bucket raw yang sama (dicek check-config)
+    let raw_bucket = config.measurements.first().map_or(SENSOR_BUCKET, |m| bucket_for(m));
+    let watermark = parse_first_value(&post_influx(client, config.watermark_flux()).await?);
+    let chunks = config.chunks(watermark, now_ns());
+    if chunks.is_empty() {
+        info!("🗜️  Compaction: nothing older than {}s to compact", config.older_than.as_secs());
+        return Ok(());
+    }
+    for chunk in chunks {
+        let rows = post_influx(client, config.aggregate_flux(raw_bucket, ORG, chunk)).await?;
+        let points = parse_first_value(&rows).unwrap_or(0);
+        info!(
+            "🗜️  Compacted {}s of raw data into {} point(s) in {}",
+            (chunk.stop_ns - chunk.start_ns) / 1_000_000_000,
+            points,
+            config.target_bucket
+        );
+        if config.delete_raw {
+            for body in config.delete_bodies(chunk) {
+                post_influx_delete(client, raw_bucket, &body).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) async fn run_task(client: Client, config: CompactionConfig) {
+    let mut ticker = tokio::time::interval(config.interval);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = run_once(&client, &config).await {
+            error!("Compaction failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN_NS: u64 = 60 * 1_000_000_000;
+
+    fn config() -> CompactionConfig {
+        CompactionConfig {
+            older_than: Duration::from_secs(86_400),
+            window: Duration::from_secs(300),
+            max_backfill: Duration::from_secs(3 * 86_400),
+            ..CompactionConfig::default()
+        }
+    }
+
+    #[test]
+    fn chunks_are_window_aligned_and_contiguous() {
+        let now = 10 * DAY_NS + 7 * MIN_NS + 123;
+        let chunks = config().chunks(None, now);
+        // stop = now - older_than, dibulatkan turun ke window 5 menit
+        let stop = 9 * DAY_NS + 5 * MIN_NS;
+        assert_eq!(chunks.first().unwrap().start_ns, stop - 3 * DAY_NS);
+        assert_eq!(chunks.last().unwrap().stop_ns, stop);
+        assert_eq!(chunks.len(), 3);
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].stop_ns, pair[1].start_ns);
+        }
+        for chunk in &chunks {
+            assert_eq!(chunk.start_ns % (5 * MIN_NS), 0);
+            assert!(chunk.stop_ns - chunk.start_ns <= DAY_NS);
+        }
+    }
+
+    #[test]
+    fn chunks_resume_from_watermark() {
+        let now = 10 * DAY_NS;
+        // Watermark di tengah window dibulatkan turun: window terakhir dipadatkan ulang utuh
+        let chunks = config().chunks(Some(9 * DAY_NS - 12 * MIN_NS), now);
+        assert_eq!(chunks, vec![Chunk { start_ns: 9 * DAY_NS - 15 * MIN_NS, stop_ns: 9 * DAY_NS }]);
+        assert!(config().chunks(Some(9 * DAY_NS), now).is_empty());
+        assert!(config().chunks(Some(11 * DAY_NS), now).is_empty());
+    }
+
+    #[test]
+    fn chunks_near_epoch_do_not_underflow() {
+        assert!(config().chunks(None, 3_600 * 1_000_000_000).is_empty());
+        let huge = CompactionConfig { window: Duration::from_secs(2 * 86_400), ..config() };
+        let chunks = huge.chunks(Some(0), 10 * DAY_NS);
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks.iter().all(|c| c.stop_ns - c.start_ns == 2 * DAY_NS));
+    }
+
+    #[test]
+    fn aggregate_keeps_non_numeric_fields() {
+        let flux = config().aggregate_flux("SENSOR_DATA", "org", Chunk { start_ns: 0, stop_ns: DAY_NS });
+        assert!(flux.contains("fn: mean"));
+        assert!(flux.contains("not numeric(r: r)"));
+        assert!(flux.contains("fn: last"));
+        assert!(flux.contains("union(tables: [means, lasts])"));
+    }
+}
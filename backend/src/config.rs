@@ -0,0 +1,671 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::alarms::{MaintenanceConfig, Severity};
+use crate::anomaly::AnomalyConfig;
+use crate::audit::AuditConfig;
+use crate::auth::AuthConfig;
+use crate::autotune::AutotuneConfig;
+use crate::bacnet::BacnetConfig;
+use crate::calibration::CalibrationTable;
+use crate::cascade::CascadeConfig;
+use crate::clock::ClockConfig;
+use crate::comfort::ComfortConfig;
+use crate::control::Actuator;
+use crate::dedupe::{self, DedupeConfig};
+use crate::drift::DriftConfig;
+use crate::export::ExportConfig;
+use crate::filters::FilterSpec;
+use crate::forecast::ForecastConfig;
+use crate::grafana::GrafanaConfig;
+use crate::mqtt_source::MqttSourceConfig;
+use crate::ota::OtaConfig;
+use crate::influx_failover::InfluxFailoverConfig;
+use crate::raw_mirror::RawMirrorConfig;
+use crate::report::ReportConfig;
+use crate::query_cache::QueryCacheConfig;
+use crate::reconcile::ReconcileConfig;
+use crate::rules::RulesConfig;
+use crate::systemd::SystemdConfig;
+use crate::retention::RetentionConfig;
+use crate::tenants::TenantConfig;
+use crate::notify::NotifyConfig;
+use crate::quality::QualityConfig;
+use crate::rate::RateAlarmRule;
+use crate::checkpoint::CheckpointConfig;
+use crate::compaction::CompactionConfig;
+use crate::provision::ProvisionConfig;
+use crate::publish::PublishConfig;
+use crate::secrets::Secret;
+use crate::settings::{Settings, SettingsConfig};
+use crate::sink::SinkConfig;
+use crate::stats::StatsConfig;
+use crate::kpi::KpiConfig;
+use crate::setpoint::SetpointConfig;
+use crate::tb_alarms::TbAlarmConfig;
+use crate::telemetry_keys::KeyMapConfig;
+use crate::validation::ValidationConfig;
+use crate::vpd::VpdTarget;
+use crate::zone::{self, Zone};
+
+// Lokasi default file konfigurasi, bisa dioverride lewat env BRIDGE_CONFIG
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Identitas device di ujung link serial
+    pub device_id: String,
+    // Mode shadow: keputusan fan/pump hanya dicatat sebagai *_proposed
+    pub shadow: bool,
+    pub connections: ConnectionsConfig,
+    // Kosong = satu zone "main" berisi device_id dan semua aktuator
+    #[serde(rename = "zones")]
+    pub zone_list: Vec<Zone>,
+    // Default kontrol kaskade untuk zone yang tidak punya [zones.cascade] sendiri
+    pub cascade: Option<CascadeConfig>,
+    // Default mode greenhouse (target VPD) untuk zone tanpa [zones.vpd]
+    pub vpd: Option<VpdTarget>,
+    // Default sumber setpoint untuk zone tanpa [zones.setpoint]
+    pub setpoint: SetpointConfig,
+    pub autotune: AutotuneConfig,
+    pub interlocks: Vec<InterlockRule>,
+    pub actuators: HashMap<Actuator, ActuatorConfig>,
+    pub thresholds: ThresholdConfig,
+    // Pemanas + exhaust fan split-range untuk zone yang punya aktuator heater
+    pub split_range: Option<SplitRangeConfig>,
+    // Nama relay tambahan di RELAY_STATUS, mis. relay = "pump" (motor/fan sudah dikenal)
+    pub relay_aliases: HashMap<String, Actuator>,
+    pub tags: TagsConfig,
+    pub api: ApiConfig,
+    pub auth: AuthConfig,
+    pub audit: AuditConfig,
+    pub validation: ValidationConfig,
+    // Rantai filter per field sebelum keputusan fan/pump
+    pub filters: HashMap<String, Vec<FilterSpec>>,
+    pub stats: StatsConfig,
+    pub kpi: KpiConfig,
+    pub anomaly: AnomalyConfig,
+    pub rate_alarms: Vec<RateAlarmRule>,
+    pub forecast: ForecastConfig,
+    pub maintenance: MaintenanceConfig,
+    pub calibration: CalibrationTable,
+    pub clock: ClockConfig,
+    pub quality: QualityConfig,
+    pub grafana: GrafanaConfig,
+    // Routing notifikasi alarm per severity (Telegram, webhook, alarm ThingsBoard)
+    pub notifications: NotifyConfig,
+    // Alarm native ThingsBoard (REST): kanal notifikasi "thingsboard" dan mode mirror
+    pub thingsboard_alarms: TbAlarmConfig,
+    pub grpc: GrpcConfig,
+    // Opsional: fan-out ke Kafka/NATS
+    pub sink: Option<SinkConfig>,
+    // Opsional: subscriber MQTT lokal sebagai sumber data sensor tambahan
+    pub mqtt_source: Option<MqttSourceConfig>,
+    pub raw_mirror: RawMirrorConfig,
+    // InfluxDB cadangan untuk tulis dan query saat primary gagal
+    pub influx_failover: InfluxFailoverConfig,
+    // Laporan harian HTML/Markdown ke direktori dan email
+    pub report: ReportConfig,
+    // Measurement -> bucket dengan retention sendiri; default semua ke SENSOR_DATA
+    pub retention: RetentionConfig,
+    // Cache TTL + penggabungan query Flux identik
+    pub query_cache: QueryCacheConfig,
+    // Perintah aktuator vs RELAY_STATUS ESP32 (alarm command_mismatch_<actuator>)
+    pub reconcile: ReconcileConfig,
+    // Field tambahan untuk query data terakhir per siklus
+    pub last_data: LastDataConfig,
+    // Notifikasi readiness/watchdog saat dijalankan sebagai service systemd
+    pub systemd: SystemdConfig,
+    // Logika AUTO aktuator sebagai ekspresi di file rule yang bisa diubah tanpa restart
+    pub control_rules: RulesConfig,
+    // Relay update firmware ESP32 dari ThingsBoard OTA
+    pub ota: OtaConfig,
+    // Export workbook Excel (`backend export`, GET /api/export.xlsx)
+    pub export: ExportConfig,
+    // Kompensasi drift humidity dari bacaan hygrometer referensi (REST /api/drift)
+    pub drift: DriftConfig,
+    // Server BACnet/IP read-only untuk BMS gedung (analog-value/binary-value)
+    pub bacnet: BacnetConfig,
+    // Org/bucket/token InfluxDB (dan device ThingsBoard opsional) per kelompok riset; dipilih lewat zones.tenant
+    pub tenants: HashMap<String, TenantConfig>,
+    // Status ringkas (OK/TOO_HOT/...) dan indeks kenyamanan per zone
+    pub comfort: ComfortConfig,
+    // Tanpa section ini dipakai TB_TOKEN statis
+    pub provision: Option<ProvisionConfig>,
+    // Tujuan ThingsBoard tambahan selain [connections] (mis. ThingsBoard CE lokal)
+    pub thingsboard: Vec<TbDestination>,
+    // Rename/konversi/drop key telemetry dan atribut ThingsBoard
+    pub telemetry_keys: KeyMapConfig,
+    // File nilai parameter kontrol yang diubah saat runtime
+    pub settings: SettingsConfig,
+    pub publish: PublishConfig,
+    pub compaction: CompactionConfig,
+    pub checkpoint: CheckpointConfig,
+    // Heartbeat per field status aktuator; titik yang sama tidak ditulis ulang
+    pub dedupe: DedupeConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            device_id: "sht20".to_string(),
+            shadow: false,
+            connections: ConnectionsConfig::default(),
+            zone_list: Vec::new(),
+            cascade: None,
+            vpd: None,
+            setpoint: SetpointConfig::default(),
+            autotune: AutotuneConfig::default(),
+            interlocks: Vec::new(),
+            actuators: HashMap::new(),
+            thresholds: ThresholdConfig::default(),
+            split_range: None,
+            relay_aliases: HashMap::new(),
+            tags: TagsConfig::default(),
+            api: ApiConfig::default(),
+            auth: AuthConfig::default(),
+            audit: AuditConfig::default(),
+            validation: ValidationConfig::default(),
+            filters: HashMap::new(),
+            stats: StatsConfig::default(),
+            kpi: KpiConfig::default(),
+            anomaly: AnomalyConfig::default(),
+            rate_alarms: Vec::new(),
+            forecast: ForecastConfig::default(),
+            maintenance: MaintenanceConfig::default(),
+            calibration: CalibrationTable::new(),
+            clock: ClockConfig::default(),
+            quality: QualityConfig::default(),
+            grafana: GrafanaConfig::default(),
+            notifications: NotifyConfig::default(),
+            thingsboard_alarms: TbAlarmConfig::default(),
+            grpc: GrpcConfig::default(),
+            sink: None,
+            mqtt_source: None,
+            raw_mirror: RawMirrorConfig::default(),
+            influx_failover: InfluxFailoverConfig::default(),
+            report: ReportConfig::default(),
+            retention: RetentionConfig::default(),
+            query_cache: QueryCacheConfig::default(),
+            reconcile: ReconcileConfig::default(),
+            last_data: LastDataConfig::default(),
+            systemd: SystemdConfig::default(),
+            control_rules: RulesConfig::default(),
+            ota: OtaConfig::default(),
+            export: ExportConfig::default(),
+            drift: DriftConfig::default(),
+            bacnet: BacnetConfig::default(),
+            tenants: HashMap::new(),
+            comfort: ComfortConfig::default(),
+            provision: None,
+            thingsboard: Vec::new(),
+            telemetry_keys: HashMap::new(),
+            settings: SettingsConfig::default(),
+            publish: PublishConfig::default(),
+            compaction: CompactionConfig::default(),
+            checkpoint: CheckpointConfig::default(),
+            dedupe: dedupe::default_config(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    pub listen: String,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self { listen: "127.0.0.1:8080".to_string() }
+    }
+}
+
+// Field sensor yang dibaca loop utama dari InfluxDB setiap siklus. Field bawaan (suhu, kelembaban,
+// versi terfilter, status aktuator, fan_duty) selalu ikut; extra_fields menambah field lain
+// (mis. soil_moisture) yang di-fusion per zone, masuk telemetry dan bisa dipakai di [[interlocks]].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LastDataConfig {
+    pub extra_fields: Vec<String>,
+    // Fungsi aggregateWindow per field; tanpa entri dipilih dari nama field (lihat aggregate_for)
+    pub aggregate: HashMap<String, Aggregate>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregate {
+    Mean,
+    Last,
+    Max,
+    Min,
+}
+
+impl Aggregate {
+    // Nama fungsi Flux
+    pub fn flux(self) -> &'static str {
+        match self {
+            Aggregate::Mean => "mean",
+            Aggregate::Last => "last",
+            Aggregate::Max => "max",
+            Aggregate::Min => "min",
+        }
+    }
+}
+
+impl LastDataConfig {
+    pub const BUILTIN: [&'static str; 7] =
+        ["temperature", "humidity", "temperature_filtered", "humidity_filtered", "exhaust_fan_status", "pump_status", "fan_duty"];
+
+    // Field bawaan lalu extra_fields, tanpa duplikat
+    pub fn fields(&self) -> Vec<String> {
+        let mut fields: Vec<String> = Self::BUILTIN.iter().map(|f| f.to_string()).collect();
+        for field in &self.extra_fields {
+            if !fields.contains(field) {
+                fields.push(field.clone());
+            }
+        }
+        fields
+    }
+
+    // Status (*_status) memakai last agar tidak menjadi 0.4, alarm/anomaly memakai max agar
+    // kejadian singkat dalam window tetap terlihat, nilai analog memakai mean
+    pub fn aggregate_for(&self, field: &str) -> Aggregate {
+        if let Some(aggregate) = self.aggregate.get(field) {
+            return *aggregate;
+        }
+        if field.ends_with("_status") {
+            Aggregate::Last
+        } else if field.contains("alarm") || field.contains("anomaly") {
+            Aggregate::Max
+        } else {
+            Aggregate::Mean
+        }
+    }
+
+    // Field dikelompokkan per fungsi agregasi, urutan pertama kali muncul
+    pub fn fields_by_aggregate(&self) -> Vec<(Aggregate, Vec<String>)> {
+        let mut groups: Vec<(Aggregate, Vec<String>)> = Vec::new();
+        for field in self.fields() {
+            let aggregate = self.aggregate_for(&field);
+            match groups.iter_mut().find(|(a, _)| *a == aggregate) {
+                Some((_, fields)) => fields.push(field),
+                None => groups.push((aggregate, vec![field])),
+            }
+        }
+        groups
+    }
+}
+
+// Alamat InfluxDB, broker MQTT ThingsBoard dan port serial; default sama dengan
+// konstanta di main.rs, bisa diarahkan ke server lain (mis. stub saat integration test)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConnectionsConfig {
+    pub influx_url: String,
+    // false = tujuan ThingsBoard utama dimatikan (mis. hanya memakai [[thingsboard]])
+    pub thingsboard_enabled: bool,
+    pub thingsboard_host: String,
+    pub thingsboard_port: u16,
+    pub serial_port: String,
+    pub baud_rate: u32,
+    // true = tolak ESP32 yang tidak menjawab HELLO atau protokolnya tidak didukung (port ditutup lalu dicoba lagi)
+    pub strict_handshake: bool,
+    // Perintah SET/RELAY/OTA ke firmware protokol >= 3 menunggu ACK ber-id selama ini,
+    // lalu dikirim ulang maksimal command_retries kali sebelum dicatat sebagai event "command"
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub command_timeout: Duration,
+    pub command_retries: u32,
+}
+
+impl Default for ConnectionsConfig {
+    fn default() -> Self {
+        Self {
+            influx_url: crate::INFLUX_URL.to_string(),
+            thingsboard_enabled: true,
+            thingsboard_host: crate::TB_HOST.to_string(),
+            thingsboard_port: crate::TB_PORT,
+            serial_port: crate::SERIAL_PORT.to_string(),
+            baud_rate: crate::BAUD_RATE,
+            strict_handshake: false,
+            command_timeout: Duration::from_secs(3),
+            command_retries: 2,
+        }
+    }
+}
+
+// Tujuan MQTT ThingsBoard tambahan dengan kredensial sendiri; menerima payload yang sama
+// dengan tujuan utama dan punya antrian/circuit breaker sendiri (sink thingsboard_<name>)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TbDestination {
+    pub name: String,
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    // Bisa juga lewat env TB_TOKEN_<NAME>(_FILE), mis. TB_TOKEN_LOCAL
+    pub token: Secret,
+}
+
+impl Default for TbDestination {
+    fn default() -> Self {
+        Self { name: String::new(), enabled: true, host: "localhost".to_string(), port: 1883, token: Secret::default() }
+    }
+}
+
+impl TbDestination {
+    pub fn sink_name(&self) -> String {
+        format!("thingsboard_{}", self.name)
+    }
+
+    pub fn token_env(&self) -> String {
+        format!("TB_TOKEN_{}", self.name.to_uppercase().replace('-', "_"))
+    }
+}
+
+// Server gRPC (proto/dcs.proto): stream telemetry dan perintah kontrol
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GrpcConfig {
+    pub enabled: bool,
+    pub listen: String,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self { enabled: true, listen: "127.0.0.1:50051".to_string() }
+    }
+}
+
+// Proteksi motor per aktuator (anti short-cycle)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ActuatorConfig {
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub min_on: Duration,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub min_off: Duration,
+    pub max_cycles_per_hour: Option<u32>,
+    // Daya nominal untuk estimasi energi (watt)
+    pub rated_power_w: f64,
+}
+
+// Split-range di sekitar setpoint (DWSIM, manual, atau `setpoint` di bawah): heater ON di bawah
+// setpoint - deadband, fan ON di atas setpoint + deadband, keduanya OFF kembali di setpoint.
+// Aktuator yang satu tidak dinyalakan selama yang lain masih ON.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SplitRangeConfig {
+    pub deadband: f64,
+    // Setpoint jika tidak ada setpoint DWSIM/manual
+    pub setpoint: f64,
+}
+
+impl Default for SplitRangeConfig {
+    fn default() -> Self {
+        Self { deadband: 0.5, setpoint: 27.5 }
+    }
+}
+
+impl SplitRangeConfig {
+    // (on_above, off_below) exhaust fan
+    pub fn cooling_band(&self, setpoint: f64) -> (f64, f64) {
+        (setpoint + self.deadband, setpoint)
+    }
+
+    // (on_below, off_above) heater
+    pub fn heating_band(&self, setpoint: f64) -> (f64, f64) {
+        (setpoint - self.deadband, setpoint)
+    }
+}
+
+// Band hysteresis exhaust fan (°C), sumber tunggal untuk logika virtual backend dan relay AUTO ESP32.
+// Dengan setpoint DWSIM band digeser: ON di setpoint, OFF di setpoint - (temp_on - temp_off).
+// Pump: ON di humidity_on_below zone, OFF kembali di humidity_on_below + humidity_band (%RH).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThresholdConfig {
+    pub temp_on: f64,
+    pub temp_off: f64,
+    pub humidity_band: f64,
+}
+
+impl Default for ThresholdConfig {
+    fn default() -> Self {
+        Self { temp_on: 30.0, temp_off: 25.0, humidity_band: 5.0 }
+    }
+}
+
+impl ThresholdConfig {
+    pub fn band(&self) -> f64 {
+        self.temp_on - self.temp_off
+    }
+
+    // Band pump (hum_on, hum_off); firmware menolak hum_off di atas 100 %RH
+    pub fn pump_band(&self, humidity_on_below: f64) -> (f64, f64) {
+        (humidity_on_below, (humidity_on_below + self.humidity_band).min(100.0))
+    }
+}
+
+// Tag InfluxDB tambahan untuk membedakan device: lokasi fisik per device (default `location`).
+// Versi firmware diambil dari frame DIAG, bukan dari config.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TagsConfig {
+    pub location: String,
+    // device_id -> lokasi, untuk slave/gateway yang tidak di ruang yang sama
+    pub locations: HashMap<String, String>,
+}
+
+impl TagsConfig {
+    pub fn location(&self, device_id: &str) -> Option<&str> {
+        let location = self.locations.get(device_id).unwrap_or(&self.location);
+        (!location.is_empty()).then_some(location.as_str())
+    }
+}
+
+// Satu aturan interlock: jika ekspresi `when` bernilai true, aktuator dipaksa ke `force`
+#[derive(Debug, Clone, Deserialize)]
+pub struct InterlockRule {
+    pub name: String,
+    pub actuator: Actuator,
+    pub when: String,
+    #[serde(default)]
+    pub force: ForcedState,
+    #[serde(default = "default_interlock_severity")]
+    pub severity: Severity,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForcedState {
+    #[default]
+    Off,
+    On,
+}
+
+impl ForcedState {
+    pub fn is_on(self) -> bool {
+        self == ForcedState::On
+    }
+}
+
+fn default_interlock_severity() -> Severity {
+    Severity::Warning
+}
+
+// Format durasi: "90s", "5m", "1h", "2d" atau angka polos (detik)
+pub fn parse_duration(text: &str) -> Result<Duration> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let value: u64 = number
+        .parse()
+        .map_err(|_| anyhow!("malformed duration '{}'", text))?;
+    let scale = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        other => return Err(anyhow!("unknown duration unit '{}' in '{}'", other, text)),
+    };
+    let secs = value.checked_mul(scale).ok_or_else(|| anyhow!("duration '{}' is too large", text))?;
+    Ok(Duration::from_secs(secs))
+}
+
+pub fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Secs(u64),
+        Text(String),
+    }
+    match Raw::deserialize(deserializer)? {
+        Raw::Secs(secs) => Ok(Duration::from_secs(secs)),
+        Raw::Text(text) => parse_duration(&text).map_err(serde::de::Error::custom),
+    }
+}
+
+impl Config {
+    // SET|temp_on=..|temp_off=..|hum_on=..|hum_off=..[|fan_min_on=..] untuk ESP32: band fan (temp_on, temp_off),
+    // band pump (hum_on, hum_off) + waktu tahan minimum dari [actuators] (yang tidak dikonfigurasi memakai default firmware)
+    pub fn device_set_command(&self, fan: (f64, f64), pump: (f64, f64)) -> String {
+        let mut parts = vec![
+            format!("temp_on={:.2}", fan.0),
+            format!("temp_off={:.2}", fan.1),
+            format!("hum_on={:.2}", pump.0),
+            format!("hum_off={:.2}", pump.1),
+        ];
+        for (actuator, prefix) in [(Actuator::ExhaustFan, "fan"), (Actuator::Pump, "pump")] {
+            let Some(limits) = self.actuators.get(&actuator) else { continue };
+            for (name, hold) in [("min_on", limits.min_on), ("min_off", limits.min_off)] {
+                if !hold.is_zero() {
+                    parts.push(format!("{}_{}={}", prefix, name, hold.as_secs()));
+                }
+            }
+        }
+        format!("SET|{}", parts.join("|"))
+    }
+
+    // Band (fan, pump) zone ESP32 serial utama dari settings runtime, seperti yang dikirim lewat SET
+    pub fn device_bands(&self, settings: &Settings) -> ((f64, f64), (f64, f64)) {
+        let zones = self.zones();
+        let zone = zones.iter().find(|z| z.device_id == self.device_id).unwrap_or(&zones[0]);
+        (settings.temp_band(&zone.name), self.thresholds.pump_band(settings.humidity_on_below(zone)))
+    }
+
+    pub fn path() -> String {
+        std::env::var("BRIDGE_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string())
+    }
+
+    // File yang tidak ada bukan error: semua nilai default dipakai
+    pub fn load(path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            log::warn!("Config file {} not found, using defaults", path);
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path))?;
+        let config: Self = toml::from_str(&text).with_context(|| format!("Invalid config file {}", path))?;
+        zone::validate(&config.zones()).with_context(|| format!("Invalid zones in {}", path))?;
+        Ok(config)
+    }
+
+    // Alamat listen REST/gRPC yang aktif, untuk AuthConfig::check_exposure
+    pub fn listeners(&self) -> Vec<(&'static str, &str)> {
+        let mut listeners = vec![("api.listen", self.api.listen.as_str())];
+        if self.grpc.enabled {
+            listeners.push(("grpc.listen", self.grpc.listen.as_str()));
+        }
+        listeners
+    }
+
+    pub fn zones(&self) -> Vec<Zone> {
+        let mut zones = if self.zone_list.is_empty() {
+            vec![Zone::default_for(&self.device_id)]
+        } else {
+            self.zone_list.clone()
+        };
+        for zone in &mut zones {
+            if zone.cascade.is_none() {
+                zone.cascade = self.cascade.clone();
+            }
+            if zone.vpd.is_none() {
+                zone.vpd = self.vpd.clone();
+            }
+            if zone.setpoint.is_none() {
+                zone.setpoint = Some(self.setpoint.clone());
+            }
+        }
+        zones
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_parse_with_units() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration(" 5m ").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("5 m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_duration("2d").unwrap(), Duration::from_secs(2 * 86400));
+    }
+
+    #[test]
+    fn zero_duration_is_allowed() {
+        assert_eq!(parse_duration("0").unwrap(), Duration::ZERO);
+        assert_eq!(parse_duration("0d").unwrap(), Duration::ZERO);
+    }
+
+    #[test]
+    fn overflowing_durations_are_rejected() {
+        assert!(parse_duration(&format!("{}s", u64::MAX)).is_ok());
+        assert!(parse_duration(&format!("{}m", u64::MAX)).unwrap_err().to_string().contains("too large"));
+        assert!(parse_duration(&format!("{}d", u64::MAX / 86400 + 1)).is_err());
+        assert!(parse_duration("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn garbage_durations_are_rejected() {
+        for text in ["", "m", "-5m", "1.5h", "5 minutes", "5w", "1h30m", "abc"] {
+            assert!(parse_duration(text).is_err(), "{text:?} should not parse");
+        }
+    }
+
+    #[test]
+    fn durations_deserialize_from_number_or_text() {
+        #[derive(Deserialize)]
+        struct Probe {
+            #[serde(deserialize_with = "deserialize_duration")]
+            d: Duration,
+        }
+        assert_eq!(toml::from_str::<Probe>("d = 30").unwrap().d, Duration::from_secs(30));
+        assert_eq!(toml::from_str::<Probe>("d = \"1h\"").unwrap().d, Duration::from_secs(3600));
+        assert!(toml::from_str::<Probe>(&format!("d = \"{}h\"", u64::MAX / 60)).is_err());
+    }
+
+    #[test]
+    fn set_command_carries_fan_and_pump_bands() {
+        let mut config = Config::default();
+        config.actuators.insert(Actuator::ExhaustFan, ActuatorConfig { min_on: Duration::from_secs(60), ..Default::default() });
+        config.actuators.insert(Actuator::Pump, ActuatorConfig { min_off: Duration::from_secs(120), ..Default::default() });
+        assert_eq!(
+            config.device_set_command((31.0, 29.0), config.thresholds.pump_band(60.0)),
+            "SET|temp_on=31.00|temp_off=29.00|hum_on=60.00|hum_off=65.00|fan_min_on=60|pump_min_off=120"
+        );
+        // Band pump tidak melewati batas firmware 100 %RH
+        assert_eq!(config.thresholds.pump_band(98.0), (98.0, 100.0));
+        assert_eq!(
+            config.device_set_command(config.device_bands(&Settings::from_config(&config)).0, (40.0, 60.0)),
+            "SET|temp_on=30.00|temp_off=25.00|hum_on=40.00|hum_off=60.00|fan_min_on=60|pump_min_off=120"
+        );
+    }
+}
@@ -0,0 +1,949 @@
+use anyhow::Result;
+use dcs_model::RelayMode;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::alarms::AlarmContext;
+use crate::autotune::{self, AutotuneStatus, RelayTuner};
+use crate::bridge::{self, Influx, LastRow, SENSOR_MEAS};
+use crate::cascade::CascadeLoop;
+use crate::config::{ActuatorConfig, Config, SplitRangeConfig};
+use crate::dedupe::WriteDeduper;
+use crate::energy::EnergyMeter;
+use crate::events::Event;
+use crate::interlock::{InterlockEngine, Trip};
+use crate::line_protocol::Point;
+use crate::rules::{RuleEngine, Signals};
+use crate::setpoint::SetpointSource;
+use crate::zone::{Cycle, Zone};
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+// Pergeseran setpoint (°C) sebelum band fan baru dikirim ulang ke ESP32 (hemat tulis NVS device)
+const DEVICE_BAND_STEP: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Actuator {
+    ExhaustFan,
+    Pump,
+    // Hanya dikendalikan dengan [split_range]; perintah lewat RELAY|heater=ON/OFF di serial
+    Heater,
+}
+
+impl Actuator {
+    pub const ALL: [Actuator; 3] = [Actuator::ExhaustFan, Actuator::Pump, Actuator::Heater];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Actuator::ExhaustFan => "exhaust_fan",
+            Actuator::Pump => "pump",
+            Actuator::Heater => "heater",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Actuator> {
+        Actuator::ALL.into_iter().find(|a| a.name() == name)
+    }
+}
+
+// Satu aktuator fisik: jenis + zone pemiliknya. Setiap zone punya fan/pump/heater sendiri, jadi
+// state kontrol, mode, jam operasi dan energi disimpan per id ini. Teks: "<zone>/<jenis>".
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct ActuatorId {
+    pub zone: String,
+    pub kind: Actuator,
+}
+
+impl ActuatorId {
+    pub fn new(zone: &str, kind: Actuator) -> Self {
+        Self { zone: zone.to_string(), kind }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.kind.name()
+    }
+}
+
+impl std::fmt::Display for ActuatorId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.zone, self.kind.name())
+    }
+}
+
+impl From<ActuatorId> for String {
+    fn from(id: ActuatorId) -> String {
+        id.to_string()
+    }
+}
+
+impl TryFrom<String> for ActuatorId {
+    type Error = String;
+
+    // Checkpoint lama menyimpan jenis saja ("pump"); itu milik zone implisit "main"
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let (zone, kind) = text.split_once('/').unwrap_or(("main", &text));
+        let kind = Actuator::parse(kind).ok_or_else(|| format!("unknown actuator '{}'", text))?;
+        Ok(ActuatorId::new(zone, kind))
+    }
+}
+
+// Mode operasi aktuator: otomatis atau dipaksa manual (REST/gRPC)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActuatorMode {
+    #[default]
+    Auto,
+    On,
+    Off,
+}
+
+impl ActuatorMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ActuatorMode::Auto => "auto",
+            ActuatorMode::On => "on",
+            ActuatorMode::Off => "off",
+        }
+    }
+
+    // Permintaan akhir: mode manual mengalahkan hasil logika otomatis
+    pub fn resolve(self, auto_demand: bool) -> bool {
+        match self {
+            ActuatorMode::Auto => auto_demand,
+            ActuatorMode::On => true,
+            ActuatorMode::Off => false,
+        }
+    }
+}
+
+// Mode manual ke relay ESP32: ON/OFF = REMOTE, auto = kembali ke logika AUTO device
+impl From<ActuatorMode> for RelayMode {
+    fn from(mode: ActuatorMode) -> Self {
+        match mode {
+            ActuatorMode::Auto => RelayMode::Auto,
+            ActuatorMode::On => RelayMode::On,
+            ActuatorMode::Off => RelayMode::Off,
+        }
+    }
+}
+
+// Hasil satu siklus keputusan untuk satu aktuator
+#[derive(Debug, Clone)]
+pub struct Decision {
+    pub actuator: ActuatorId,
+    pub state: bool,
+    pub previous: Option<bool>,
+    // true jika perintah ditahan oleh anti short-cycle
+    pub held: bool,
+    pub trip: Option<Trip>,
+}
+
+// Band hysteresis fan (aturan yang sama dengan relay AUTO ESP32): ON saat suhu >= on_above,
+// OFF saat <= off_below, di antaranya state sebelumnya dipertahankan
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hysteresis {
+    on: bool,
+}
+
+impl Hysteresis {
+    pub fn update(&mut self, value: f64, on_above: f64, off_below: f64) -> bool {
+        if value >= on_above {
+            self.on = true;
+        } else if value <= off_below {
+            self.on = false;
+        }
+        self.on
+    }
+
+    // Kebalikan untuk pemanas: ON saat nilai <= on_below, OFF saat >= off_above
+    pub fn update_below(&mut self, value: f64, on_below: f64, off_above: f64) -> bool {
+        if value <= on_below {
+            self.on = true;
+        } else if value >= off_above {
+            self.on = false;
+        }
+        self.on
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CycleViolation {
+    MinOn { remaining: Duration },
+    MinOff { remaining: Duration },
+    MaxCycles { limit: u32 },
+}
+
+impl std::fmt::Display for CycleViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CycleViolation::MinOn { remaining } => write!(f, "minimum ON time not reached ({}s left)", remaining.as_secs()),
+            CycleViolation::MinOff { remaining } => write!(f, "minimum OFF time not reached ({}s left)", remaining.as_secs()),
+            CycleViolation::MaxCycles { limit } => write!(f, "max {} cycles/hour reached", limit),
+        }
+    }
+}
+
+// Anti short-cycle: minimum ON/OFF time dan batas jumlah siklus per jam
+#[derive(Debug, Default)]
+pub struct CycleGuard {
+    limits: ActuatorConfig,
+    state: Option<bool>,
+    last_change: Option<Instant>,
+    switch_ons: VecDeque<Instant>,
+}
+
+impl CycleGuard {
+    pub fn new(limits: ActuatorConfig) -> Self {
+        Self { limits, ..Default::default() }
+    }
+
+    pub fn state(&self) -> Option<bool> {
+        self.state
+    }
+
+    // Cek apakah perubahan ke `requested` diizinkan saat ini
+    pub fn check(&mut self, requested: bool, now: Instant) -> Result<(), CycleViolation> {
+        let (Some(current), Some(since)) = (self.state, self.last_change) else {
+            return Ok(());
+        };
+        if current == requested {
+            return Ok(());
+        }
+
+        let elapsed = now.saturating_duration_since(since);
+        let min_hold = if current { self.limits.min_on } else { self.limits.min_off };
+        if elapsed < min_hold {
+            let remaining = min_hold - elapsed;
+            return Err(if current {
+                CycleViolation::MinOn { remaining }
+            } else {
+                CycleViolation::MinOff { remaining }
+            });
+        }
+
+        if requested {
+            if let Some(limit) = self.limits.max_cycles_per_hour {
+                self.prune(now);
+                if self.switch_ons.len() as u32 >= limit {
+                    return Err(CycleViolation::MaxCycles { limit });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Catat status akhir yang benar-benar dikirim ke aktuator
+    pub fn record(&mut self, state: bool, now: Instant) {
+        if self.state == Some(state) {
+            return;
+        }
+        if state && self.state.is_some() {
+            self.switch_ons.push_back(now);
+        }
+        self.state = Some(state);
+        self.last_change = Some(now);
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some(t) = self.switch_ons.front() {
+            if now.saturating_duration_since(*t) >= HOUR {
+                self.switch_ons.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+// State kontrol per aktuator fisik; batas anti short-cycle per jenis dari [actuators]
+pub struct Controller {
+    limits: HashMap<Actuator, ActuatorConfig>,
+    guards: HashMap<ActuatorId, CycleGuard>,
+}
+
+impl Controller {
+    pub fn new(actuators: &HashMap<Actuator, ActuatorConfig>) -> Self {
+        Self { limits: actuators.clone(), guards: HashMap::new() }
+    }
+
+    fn guard(&mut self, id: &ActuatorId) -> &mut CycleGuard {
+        let limits = &self.limits;
+        self.guards
+            .entry(id.clone())
+            .or_insert_with(|| CycleGuard::new(limits.get(&id.kind).cloned().unwrap_or_default()))
+    }
+
+    // Terapkan anti short-cycle; jika dilanggar, aktuator tetap di status sekarang
+    pub fn limit_cycling(&mut self, id: &ActuatorId, requested: bool, now: Instant) -> bool {
+        let guard = self.guard(id);
+        match guard.check(requested, now) {
+            Ok(()) => requested,
+            Err(violation) => {
+                let held = guard.state().unwrap_or(requested);
+                warn!(
+                    "⏱️  {} {} blocked: {}",
+                    id,
+                    if requested { "ON" } else { "OFF" },
+                    violation
+                );
+                held
+            }
+        }
+    }
+
+    pub fn record(&mut self, id: &ActuatorId, state: bool, now: Instant) {
+        self.guard(id).record(state, now);
+    }
+
+    // Urutan: permintaan -> anti short-cycle -> interlock (keselamatan menang) -> catat
+    pub fn decide(
+        &mut self,
+        id: &ActuatorId,
+        requested: bool,
+        interlocks: &InterlockEngine,
+        fields: &HashMap<String, f64>,
+        now: Instant,
+    ) -> Decision {
+        let previous = self.state(id);
+        let allowed = self.limit_cycling(id, requested, now);
+        let (state, trip) = interlocks.apply(id.kind, allowed, fields);
+        self.record(id, state, now);
+        Decision { actuator: id.clone(), state, previous, held: allowed != requested, trip }
+    }
+
+    // Status terakhir yang dikirim ke aktuator
+    pub fn state(&self, id: &ActuatorId) -> Option<bool> {
+        self.guards.get(id).and_then(|g| g.state())
+    }
+}
+
+// State kontrol semua zone yang bertahan antar siklus
+pub(crate) struct ZoneLoops {
+    pub controller: Controller,
+    pub energy: EnergyMeter,
+    pub deduper: WriteDeduper,
+    pub rules: RuleEngine,
+    pub interlocks: InterlockEngine,
+    pub tuners: HashMap<String, RelayTuner>,
+    pub cascades: HashMap<String, CascadeLoop>,
+    pub fan_bands: HashMap<String, Hysteresis>,
+    pub heater_bands: HashMap<String, Hysteresis>,
+    pub pump_bands: HashMap<String, Hysteresis>,
+    // Band terakhir yang dikirim ke ESP32: fan (temp_on, temp_off) dan pump (hum_on, hum_off)
+    pub device_band: ((f64, f64), (f64, f64)),
+    // Aktuator zone ESP32 serial yang keputusan terakhirnya sama dengan yang diambil relay device:
+    // mode AUTO dengan band yang disinkronkan lewat SET (tanpa rule/VPD/kaskade), atau mode manual
+    // yang dikirim lewat RELAY; selalu tanpa interlock. Hanya ini yang dibandingkan dengan
+    // RELAY_STATUS oleh reconcile
+    pub device_shared: HashSet<Actuator>,
+}
+
+// Rencana fan siklus ini: demand otomatis terhadap setpoint yang dipakai
+struct FanPlan {
+    sensor_temp: f64,
+    setpoint: f64,
+    demand: bool,
+    reason: String,
+    // true jika demand berasal dari band yang juga dipakai relay AUTO ESP32
+    shared: bool,
+}
+
+// Input kontrol satu zone; nilai kontrol memakai nilai terfilter jika filter dikonfigurasi
+struct ZoneInput<'z> {
+    zone: &'z Zone,
+    fields: HashMap<String, f64>,
+    signals: Signals,
+    temp: Option<f64>,
+    hum: Option<f64>,
+    vpd: Option<f64>,
+    // Status fan dari eksperimen relay autotune; selama berjalan rule dan heater diam
+    relay: Option<bool>,
+}
+
+impl ZoneLoops {
+    // Satu siklus kontrol zone: setpoint dan sinyal rule, rencana fan, lalu keputusan fan/heater/pump
+    pub(crate) fn step_zone(&mut self, cx: &mut Cycle, zone: &Zone, sensor: &LastRow, dwsim_temp: Option<f64>) {
+        let fields = interlock_fields(cx.config, sensor, dwsim_temp);
+        // Sinyal rule: field interlock + parameter kontrol; <aktuator>_default diisi saat evaluasi
+        let mut signals = Signals::new(&fields);
+        let (setpoint, explicit_setpoint) = resolve_setpoint(cx, zone, dwsim_temp);
+        signals.number("setpoint", setpoint);
+        let (temp_on, temp_off) = cx.settings.temp_band(&zone.name);
+        signals.number("temp_on", Some(temp_on));
+        signals.number("temp_off", Some(temp_off));
+        signals.number("humidity_on_below", Some(cx.settings.humidity_on_below(zone)));
+        signals.flag("maintenance_mode", cx.maintenance);
+        for actuator in zone.actuator_ids() {
+            if let Some(on) = self.controller.state(&actuator) {
+                signals.flag(&format!("{}_on", actuator.name()), on);
+            }
+        }
+        let relay = match (zone.has(Actuator::ExhaustFan), sensor.control_temp()) {
+            (true, Some(sensor_temp)) => {
+                let prefix = cx.prefix(zone);
+                autotune::step_zone(
+                    cx.state, cx.config, cx.config_path, zone, &mut self.tuners, &mut self.cascades, sensor_temp, &mut cx.payload, &prefix,
+                )
+            }
+            _ => None,
+        };
+        let mut input = ZoneInput {
+            zone,
+            fields,
+            signals,
+            temp: sensor.control_temp(),
+            hum: sensor.control_hum(),
+            vpd: sensor.control_vpd(),
+            relay,
+        };
+
+        let fan_plan = self.fan_plan(cx, &mut input, setpoint, explicit_setpoint, dwsim_temp);
+        // Setpoint heater split-range: sama dengan fan (termasuk setpoint kaskade)
+        let heat_setpoint = fan_plan.as_ref().map(|plan| plan.setpoint).or(setpoint);
+        if let Some(plan) = fan_plan {
+            self.apply_fan(cx, &input, plan);
+        }
+        self.apply_heater(cx, &mut input, heat_setpoint);
+        self.apply_pump(cx, &mut input);
+    }
+
+    // exhaust_fan_status: relay autotune, kontrol kaskade jika zone punya konfigurasi cascade, atau
+    // band on/off terhadap setpoint; lalu VPD (mode greenhouse) dan rule [control_rules]
+    fn fan_plan(
+        &mut self,
+        cx: &mut Cycle,
+        input: &mut ZoneInput,
+        setpoint: Option<f64>,
+        explicit_setpoint: Option<f64>,
+        dwsim_temp: Option<f64>,
+    ) -> Option<FanPlan> {
+        let zone = input.zone;
+        let config = cx.config;
+        let sensor_temp = input.temp.filter(|_| zone.has(Actuator::ExhaustFan))?;
+        let split_range = split_range(config, zone);
+        let plan = match (input.relay, self.cascades.get_mut(&zone.name)) {
+            (Some(on), _) => {
+                let tuning_setpoint = match cx.state.autotune.lock().unwrap().get(&zone.name) {
+                    Some(AutotuneStatus::Running { setpoint, .. }) => *setpoint,
+                    _ => sensor_temp,
+                };
+                FanPlan { sensor_temp, setpoint: tuning_setpoint, demand: on, reason: "autotune relay experiment".to_string(), shared: false }
+            }
+            (None, Some(cascade)) => {
+                let out = cascade.step(sensor_temp, dwsim_temp, explicit_setpoint, Instant::now());
+                cx.set(zone, "cascade_output", json!((out.output * 1000.0).round() / 1000.0));
+                cx.set(zone, "cascade_feedforward", json!((out.feedforward * 1000.0).round() / 1000.0));
+                let point = cx
+                    .point("cascade", zone)
+                    .float_prec("setpoint", out.setpoint, 2)
+                    .float_prec("output", out.output, 4)
+                    .float_prec("feedforward", out.feedforward, 4)
+                    .float_prec("p", out.terms.p, 4)
+                    .float_prec("i", out.terms.i, 4)
+                    .float_prec("d", out.terms.d, 4)
+                    .timestamp(bridge::now_ns());
+                cx.write(point, "cascade state");
+                let reason = format!("cascade output {:.2} (setpoint {:.2}°C, sensor {:.2}°C)", out.output, out.setpoint, sensor_temp);
+                FanPlan { sensor_temp, setpoint: out.setpoint, demand: out.demand, reason, shared: false }
+            }
+            (None, None) => {
+                // Tanpa setpoint DWSIM/manual dipakai threshold absolut zone (settings temp_on/temp_off);
+                // zone split-range memakai band di atas setpoint + deadband
+                let (on_above, off_below) = match (split_range, setpoint) {
+                    (Some(split), sp) => split.cooling_band(sp.unwrap_or(split.setpoint)),
+                    (None, Some(sp)) => (sp, sp - cx.settings.band(&zone.name)),
+                    (None, None) => cx.settings.temp_band(&zone.name),
+                };
+                // ESP32 serial mengikuti band yang sama; setpoint DWSIM bergerak pelan,
+                // jadi SET hanya dikirim ulang jika bergeser >= DEVICE_BAND_STEP
+                let (sent_on, sent_off) = self.device_band.0;
+                let shifted = (on_above - sent_on).abs() >= DEVICE_BAND_STEP || (off_below - sent_off).abs() >= DEVICE_BAND_STEP;
+                if zone.device_id == config.device_id && shifted {
+                    self.device_band.0 = (on_above, off_below);
+                    cx.state.queue_device_settings(config.device_set_command(self.device_band.0, self.device_band.1));
+                }
+                let demand = self.fan_bands.entry(zone.name.clone()).or_default().update(sensor_temp, on_above, off_below);
+                let reason = format!("sensor {:.2}°C vs band {:.2}-{:.2}°C", sensor_temp, off_below, on_above);
+                // Setpoint split-range = batas OFF fan
+                let setpoint = if split_range.is_some() { off_below } else { on_above };
+                FanPlan { sensor_temp, setpoint, demand, reason, shared: true }
+            }
+        };
+        if input.relay.is_some() {
+            return Some(plan);
+        }
+        // Mode greenhouse: fan juga membuang udara lembap saat VPD terlalu rendah
+        let plan = match (cx.settings.vpd_target(zone), input.vpd) {
+            (Some(target), Some(vpd)) if !plan.demand && target.fan_demand(vpd) => {
+                let reason = format!("VPD {:.2} kPa below {:.2} kPa", vpd, target.setpoint - target.band);
+                FanPlan { demand: true, reason, shared: false, ..plan }
+            }
+            _ => plan,
+        };
+        // Rule [control_rules] menggantikan demand bawaan; eksperimen autotune tidak diganggu
+        let (demand, reason) = self.rules.demand(&zone.name, Actuator::ExhaustFan, &mut input.signals, plan.demand, plan.reason);
+        let shared = plan.shared && demand == plan.demand;
+        Some(FanPlan { demand, reason, shared, ..plan })
+    }
+
+    fn apply_fan(&mut self, cx: &mut Cycle, input: &ZoneInput, plan: FanPlan) {
+        let zone = input.zone;
+        let shadow = cx.config.shadow;
+        let (fan, heater) = (zone.actuator(Actuator::ExhaustFan), zone.actuator(Actuator::Heater));
+        let now = Instant::now();
+        let mode = cx.state.mode(&fan);
+        // Split-range: fan tidak dinyalakan selama heater masih ON
+        let heater_on = split_range(cx.config, zone).is_some() && self.controller.state(&heater) == Some(true);
+        let requested = mode.resolve(plan.demand) && !heater_on;
+        let decision = self.controller.decide(&fan, requested, &self.interlocks, &input.fields, now);
+        let reason = mode_reason(mode, plan.demand, heater_on.then_some("heater still ON (split-range)"), plan.reason);
+        let fan_on = self.commit(cx, zone, &decision, reason, "exhaust_fan_status", now);
+        if zone.device_id == cx.config.device_id {
+            self.share_with_device(Actuator::ExhaustFan, (plan.shared || mode != ActuatorMode::Auto) && !heater_on && decision.trip.is_none());
+        }
+
+        info!("🔥 [{}] Fan Status{}: Sensor={:.2}°C, Setpoint={:.2}°C → Fan={}",
+              zone.name, if shadow { " (proposed)" } else { "" }, plan.sensor_temp, plan.setpoint, if fan_on == 1 { "ON" } else { "OFF" });
+        cx.set(zone, "dwsim_temperature_setpoint", json!(plan.setpoint));
+
+        // Simpan fan status yang sudah dihitung ke InfluxDB (hanya saat berubah atau heartbeat)
+        if self.deduper.should_write("exhaust_fan_status", &zone.name, fan_on, now) {
+            if let Err(e) = write_fan_status_to_influx(cx.influx, cx.point(SENSOR_MEAS, zone), fan_on, plan.sensor_temp, plan.setpoint, shadow) {
+                error!("Failed to write fan status to InfluxDB: {}", e);
+            }
+        }
+    }
+
+    // Heater split-range: ON di bawah setpoint - deadband, OFF kembali di setpoint; tidak pernah
+    // bersamaan dengan fan. Perintah ke ESP32 lewat RELAY|heater=ON/OFF di link serial.
+    fn apply_heater(&mut self, cx: &mut Cycle, input: &mut ZoneInput, heat_setpoint: Option<f64>) {
+        let zone = input.zone;
+        let config = cx.config;
+        let (Some(split), Some(sensor_temp)) = (split_range(config, zone), input.temp) else { return };
+        let (fan, heater) = (zone.actuator(Actuator::ExhaustFan), zone.actuator(Actuator::Heater));
+        let now = Instant::now();
+        let mode = cx.state.mode(&heater);
+        let sp = heat_setpoint.unwrap_or(split.setpoint);
+        let (on_below, off_above) = split.heating_band(sp);
+        // Eksperimen autotune me-relay fan; heater ikut diam
+        let demand = input.relay.is_none() && self.heater_bands.entry(zone.name.clone()).or_default().update_below(sensor_temp, on_below, off_above);
+        let reason = format!("sensor {:.2}°C vs band {:.2}-{:.2}°C", sensor_temp, on_below, off_above);
+        let (demand, reason) = match input.relay {
+            None => self.rules.demand(&zone.name, Actuator::Heater, &mut input.signals, demand, reason),
+            Some(_) => (demand, reason),
+        };
+        let fan_on = self.controller.state(&fan) == Some(true);
+        let requested = mode.resolve(demand) && !fan_on;
+        let decision = self.controller.decide(&heater, requested, &self.interlocks, &input.fields, now);
+        let reason = mode_reason(mode, demand, fan_on.then_some("exhaust fan still ON (split-range)"), reason);
+        let heater_on = self.commit(cx, zone, &decision, reason, "heater_status", now);
+        if !config.shadow && zone.device_id == config.device_id {
+            cx.state.command_relay(Actuator::Heater, decision.state.into());
+        }
+
+        info!("♨️  [{}] Heater Status{}: Sensor={:.2}°C, Setpoint={:.2}°C → Heater={}",
+              zone.name, if config.shadow { " (proposed)" } else { "" }, sensor_temp, sp, if heater_on == 1 { "ON" } else { "OFF" });
+
+        if self.deduper.should_write("heater_status", &zone.name, heater_on, now) {
+            let point = cx
+                .point(SENSOR_MEAS, zone)
+                .float(if config.shadow { "heater_status_proposed" } else { "heater_status" }, heater_on as f64)
+                .float_prec("sensor_temp", sensor_temp, 2)
+                .float_prec("setpoint_temp", sp, 2)
+                .timestamp(bridge::now_ns());
+            cx.write(point, "heater status");
+        }
+    }
+
+    // pump_status berdasarkan humidity (ON di ambang zone, default 60%, OFF kembali setelah
+    // humidity_band; band yang sama dengan relay AUTO ESP32), atau berdasarkan setpoint VPD jika
+    // zone dalam mode greenhouse
+    fn apply_pump(&mut self, cx: &mut Cycle, input: &mut ZoneInput) {
+        let zone = input.zone;
+        let config = cx.config;
+        let shadow = config.shadow;
+        let (true, Some(humidity)) = (zone.has(Actuator::Pump), input.hum) else { return };
+        let pump = zone.actuator(Actuator::Pump);
+        let now = Instant::now();
+        let mode = cx.state.mode(&pump);
+        let (hum_on, hum_off) = config.thresholds.pump_band(cx.settings.humidity_on_below(zone));
+        if zone.device_id == config.device_id && self.device_band.1 != (hum_on, hum_off) {
+            self.device_band.1 = (hum_on, hum_off);
+            cx.state.queue_device_settings(config.device_set_command(self.device_band.0, self.device_band.1));
+        }
+        let band_demand = self.pump_bands.entry(zone.name.clone()).or_default().update_below(humidity, hum_on, hum_off);
+        let (demand, reason) = match (cx.settings.vpd_target(zone), input.vpd) {
+            (Some(target), Some(vpd)) => (
+                target.pump_demand(vpd),
+                format!("VPD {:.2} kPa vs setpoint {:.2} kPa", vpd, target.setpoint),
+            ),
+            _ => (band_demand, format!("humidity {:.1}% vs band {:.0}-{:.0}%", humidity, hum_on, hum_off)),
+        };
+        let (rule_demand, reason) = self.rules.demand(&zone.name, Actuator::Pump, &mut input.signals, demand, reason);
+        let shared = demand == band_demand && rule_demand == band_demand;
+        let demand = rule_demand;
+        let decision = self.controller.decide(&pump, mode.resolve(demand), &self.interlocks, &input.fields, now);
+        let pump_on = self.commit(cx, zone, &decision, mode_reason(mode, demand, None, reason), "pump_calculated_status", now);
+        if zone.device_id == config.device_id {
+            self.share_with_device(Actuator::Pump, (shared || mode != ActuatorMode::Auto) && decision.trip.is_none());
+        }
+
+        info!("💧 [{}] Pump Status{}: Humidity={:.1}% → Pump={}",
+              zone.name, if shadow { " (proposed)" } else { "" }, humidity, if pump_on == 1 { "ON" } else { "OFF" });
+
+        // Simpan pump status yang sudah dihitung ke InfluxDB (hanya saat berubah atau heartbeat)
+        if self.deduper.should_write("pump_calculated_status", &zone.name, pump_on, now) {
+            if let Err(e) = write_pump_status_to_influx(cx.influx, cx.point(SENSOR_MEAS, zone), pump_on, humidity, shadow) {
+                error!("Failed to write pump status to InfluxDB: {}", e);
+            }
+        }
+    }
+
+    fn share_with_device(&mut self, actuator: Actuator, shared: bool) {
+        if shared {
+            self.device_shared.insert(actuator);
+        } else {
+            self.device_shared.remove(&actuator);
+        }
+    }
+
+    // Efek satu keputusan: alarm/event, lalu jam operasi, energi dan key status di payload;
+    // mode shadow hanya mengisi <key>_proposed. Hasil 1/0 untuk log dan InfluxDB.
+    fn commit(&mut self, cx: &mut Cycle, zone: &Zone, decision: &Decision, reason: String, key: &str, now: Instant) -> i32 {
+        apply_decision(cx, &self.interlocks, decision, reason);
+        let on = if decision.state { 1 } else { 0 };
+        if cx.config.shadow {
+            cx.set(zone, &format!("{key}_proposed"), json!(on));
+        } else {
+            let actuator = &decision.actuator;
+            let rated_power = cx.config.actuators.get(&actuator.kind).map(|c| c.rated_power_w).unwrap_or_default();
+            cx.state.runtime.lock().unwrap().update(actuator, decision.state, now);
+            self.energy.update(actuator, decision.state, rated_power, now);
+            cx.set(zone, key, json!(on));
+        }
+        on
+    }
+}
+
+fn split_range<'c>(config: &'c Config, zone: &Zone) -> Option<&'c SplitRangeConfig> {
+    config.split_range.as_ref().filter(|_| zone.has(Actuator::Heater))
+}
+
+// Alasan event: aktuator lain yang menahan (split-range), logika otomatis, atau mode manual
+fn mode_reason(mode: ActuatorMode, demand: bool, blocked_by: Option<&str>, reason: String) -> String {
+    match (mode, blocked_by) {
+        (_, Some(blocked)) if mode.resolve(demand) => blocked.to_string(),
+        (ActuatorMode::Auto, _) => reason,
+        (manual, _) => format!("manual mode {}", manual.as_str()),
+    }
+}
+
+// Field live zone ini yang bisa dipakai di ekspresi interlock dan rule
+fn interlock_fields(config: &Config, sensor: &LastRow, dwsim_temp: Option<f64>) -> HashMap<String, f64> {
+    let mut values = vec![
+        ("temperature", sensor.temp()),
+        ("humidity", sensor.hum()),
+        ("exhaust_fan_status", sensor.exhaust_fan_status()),
+        ("pump_status", sensor.pump_status()),
+        ("temperature_filtered", sensor.temp_filtered()),
+        ("humidity_filtered", sensor.hum_filtered()),
+    ];
+    values.extend(config.last_data.extra_fields.iter().map(|field| (field.as_str(), sensor.get(field))));
+    values.push(("dwsim_temperature", dwsim_temp));
+    values.push(("vpd", sensor.control_vpd()));
+    values.into_iter().filter_map(|(name, value)| Some((name.to_string(), value?))).collect()
+}
+
+// Setpoint zone = sumber pertama yang punya nilai di [setpoint].sources. Hasil kedua untuk kaskade:
+// setpoint selain DWSIM/fallback menggantikan setpoint luar (blend DWSIM/base_setpoint)
+fn resolve_setpoint(cx: &mut Cycle, zone: &Zone, dwsim_temp: Option<f64>) -> (Option<f64>, Option<f64>) {
+    let state = cx.state;
+    let resolved = zone.setpoint_config().resolve(state.setpoint_override(&zone.name), state.tb_setpoint(&zone.name), dwsim_temp);
+    match resolved {
+        Some((value, source)) => {
+            cx.set(zone, "setpoint_temperature", json!(value));
+            cx.set(zone, "setpoint_source", json!(source.as_str()));
+            let previous = state.active_setpoints.lock().unwrap().insert(zone.name.clone(), (value, source));
+            if source == SetpointSource::Fallback && previous.is_none_or(|(_, s)| s != SetpointSource::Fallback) {
+                warn!("🎯 Zone {}: no setpoint source has a value, using fallback {:.2}°C", zone.name, value);
+            }
+        }
+        None => {
+            cx.set(zone, "setpoint_source", json!("none"));
+            state.active_setpoints.lock().unwrap().remove(&zone.name);
+        }
+    }
+    let explicit = resolved
+        .filter(|(_, source)| !matches!(source, SetpointSource::Dwsim | SetpointSource::Fallback))
+        .map(|(value, _)| value);
+    (resolved.map(|(value, _)| value), explicit)
+}
+
+// Efek samping satu keputusan aktuator: alarm interlock dan event perubahan status
+fn apply_decision(cx: &Cycle, interlocks: &InterlockEngine, decision: &Decision, auto_reason: String) {
+    handle_interlock(cx, interlocks, &decision.actuator, decision.trip.as_ref());
+
+    if decision.previous == Some(decision.state) {
+        return;
+    }
+    let reason = match &decision.trip {
+        Some(trip) => format!("interlock {}", trip.rule.name),
+        None if decision.held => "anti short-cycle hold".to_string(),
+        None => auto_reason,
+    };
+    let label = |on: bool| if on { "ON" } else { "OFF" };
+    // Mode shadow: keputusan hanya usulan, dicatat sebagai <actuator>_proposed
+    let subject = if cx.config.shadow { format!("{}_proposed", decision.actuator) } else { decision.actuator.to_string() };
+    let mut event = Event::new("actuator", subject, label(decision.state)).reason(reason);
+    if let Some(previous) = decision.previous {
+        event = event.old(label(previous));
+    }
+    cx.state.events.record(event);
+}
+
+// Raise/clear alarm interlock untuk satu aktuator sesuai hasil evaluasi terakhir
+fn handle_interlock(cx: &Cycle, interlocks: &InterlockEngine, actuator: &ActuatorId, trip: Option<&Trip>) {
+    let state = cx.state;
+    // Rule interlock berlaku per jenis aktuator; alarm per zone jika ada lebih dari satu zone
+    let alarm_id = |rule: &str| if state.zones.len() > 1 { format!("interlock_{}_{}", actuator.zone, rule) } else { format!("interlock_{rule}") };
+    if let Some(trip) = trip {
+        let id = alarm_id(&trip.rule.name);
+        let message = format!(
+            "Interlock '{}' forces {} {} ({})",
+            trip.rule.name,
+            actuator.name(),
+            if trip.rule.force.is_on() { "ON" } else { "OFF" },
+            trip.rule.when
+        );
+        let zone = actuator.zone.as_str();
+        if let Some(alarm) = state.raise_alarm_with(&id, trip.rule.severity, message, AlarmContext::zone(zone)) {
+            warn!("🔒 {}", alarm.message);
+            if let Err(e) = write_interlock_to_influx(cx.influx, bridge::zone_point(state, "interlock", zone), trip) {
+                error!("Failed to write interlock trip to InfluxDB: {}", e);
+            }
+            cx.record_alarm(Some((alarm, true)));
+        }
+    }
+
+    let tripped = trip.map(|t| t.rule.name.as_str());
+    let cleared: Vec<String> = interlocks
+        .rule_names(actuator.kind)
+        .filter(|name| Some(*name) != tripped)
+        .map(alarm_id)
+        .collect();
+    for id in cleared {
+        if let Some(alarm) = state.clear_alarm(&id) {
+            info!("🔓 Interlock cleared: {}", alarm.id);
+            cx.record_alarm(Some((alarm, false)));
+        }
+    }
+}
+
+// Write calculated exhaust fan status to InfluxDB
+// Mode shadow: disimpan sebagai exhaust_fan_status_proposed, bukan status aktuator
+fn write_fan_status_to_influx(influx: &Influx, series: Point, fan_on: i32, sensor_temp: f64, setpoint_temp: f64, shadow: bool) -> Result<()> {
+    let point = series
+        .float(if shadow { "exhaust_fan_status_proposed" } else { "exhaust_fan_status" }, fan_on as f64)
+        .float_prec("sensor_temp", sensor_temp, 2)
+        .float_prec("setpoint_temp", setpoint_temp, 2)
+        .timestamp(bridge::now_ns());
+
+    bridge::write_points(influx, &[point])?;
+    info!("Fan status queued for InfluxDB: {}", if fan_on == 1 { "ON" } else { "OFF" });
+    Ok(())
+}
+
+// Write calculated pump status to InfluxDB based on humidity
+fn write_pump_status_to_influx(influx: &Influx, series: Point, pump_on: i32, humidity: f64, shadow: bool) -> Result<()> {
+    let point = series
+        .float(if shadow { "pump_calculated_status_proposed" } else { "pump_calculated_status" }, pump_on as f64)
+        .float_prec("humidity", humidity, 2)
+        .timestamp(bridge::now_ns());
+
+    bridge::write_points(influx, &[point])?;
+    info!("💧 Pump status queued for InfluxDB: {} (Humidity: {:.1}%)", if pump_on == 1 { "ON" } else { "OFF" }, humidity);
+    Ok(())
+}
+
+// Catat interlock trip ke InfluxDB (measurement "interlock")
+fn write_interlock_to_influx(influx: &Influx, series: Point, trip: &Trip) -> Result<()> {
+    let point = series
+        .tag("rule", &trip.rule.name)
+        .tag("actuator", trip.rule.actuator.name())
+        .float("tripped", 1.0)
+        .float("requested", if trip.requested { 1.0 } else { 0.0 })
+        .float("forced", if trip.rule.force.is_on() { 1.0 } else { 0.0 })
+        .timestamp(bridge::now_ns());
+    bridge::write_points(influx, &[point])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zones_hold_their_own_fan() {
+        let limits = ActuatorConfig { min_on: Duration::from_secs(60), ..Default::default() };
+        let mut controller = Controller::new(&HashMap::from([(Actuator::ExhaustFan, limits)]));
+        let interlocks = InterlockEngine::new(&[]).unwrap();
+        let (a, b) = (ActuatorId::new("a", Actuator::ExhaustFan), ActuatorId::new("b", Actuator::ExhaustFan));
+        let start = Instant::now();
+        controller.decide(&a, true, &interlocks, &HashMap::new(), start);
+        controller.decide(&b, true, &interlocks, &HashMap::new(), start);
+
+        // Fan zone b baru 10 s ON: ditahan min_on, fan zone a tidak ikut tertahan oleh riwayat zone b
+        let later = start + Duration::from_secs(10);
+        let held = controller.decide(&b, false, &interlocks, &HashMap::new(), later);
+        assert!(held.state && held.held);
+        assert_eq!(controller.state(&a), Some(true));
+        assert_eq!(controller.state(&ActuatorId::new("c", Actuator::ExhaustFan)), None);
+    }
+
+    #[test]
+    fn hysteresis_switches_at_band_edges_and_holds_inside() {
+        let mut band = Hysteresis::default();
+        assert!(!band.update(29.99, 30.0, 28.0));
+        // Tepat di batas ON menyala, di dalam band status dipertahankan
+        assert!(band.update(30.0, 30.0, 28.0));
+        assert!(band.update(28.01, 30.0, 28.0));
+        // Tepat di batas OFF mati, lalu tetap OFF sampai batas ON lagi
+        assert!(!band.update(28.0, 30.0, 28.0));
+        assert!(!band.update(29.99, 30.0, 28.0));
+
+        let mut heater = Hysteresis::default();
+        assert!(!heater.update_below(18.01, 18.0, 20.0));
+        assert!(heater.update_below(18.0, 18.0, 20.0));
+        assert!(heater.update_below(19.99, 18.0, 20.0));
+        assert!(!heater.update_below(20.0, 18.0, 20.0));
+        assert!(!heater.update_below(18.5, 18.0, 20.0));
+    }
+
+    #[test]
+    fn pump_band_matches_device_hysteresis() {
+        let (hum_on, hum_off) = Config::default().thresholds.pump_band(60.0);
+        let mut pump = Hysteresis::default();
+        assert!(!pump.update_below(60.1, hum_on, hum_off));
+        // ON di hum_on seperti firmware (humidity <= hum_on), tetap ON sampai hum_off
+        assert!(pump.update_below(60.0, hum_on, hum_off));
+        assert!(pump.update_below(64.9, hum_on, hum_off));
+        assert!(!pump.update_below(65.0, hum_on, hum_off));
+        assert!(!pump.update_below(62.0, hum_on, hum_off));
+    }
+
+    #[tokio::test]
+    async fn split_range_never_runs_heater_with_fan_and_merges_relay_frame() {
+        let config = Config { split_range: Some(SplitRangeConfig::default()), ..Config::default() };
+        let state = crate::state::AppState::new(&config);
+        let influx = Influx::discard(&state);
+        let zone = state.zones[0].clone();
+        let mut loops = ZoneLoops {
+            controller: Controller::new(&HashMap::new()),
+            energy: EnergyMeter::new(),
+            deduper: WriteDeduper::new(&config.dedupe),
+            rules: RuleEngine::load(&config.control_rules).unwrap(),
+            interlocks: InterlockEngine::new(&[]).unwrap(),
+            tuners: HashMap::new(),
+            cascades: HashMap::new(),
+            fan_bands: HashMap::new(),
+            heater_bands: HashMap::new(),
+            pump_bands: HashMap::new(),
+            device_band: config.device_bands(&state.settings()),
+            device_shared: HashSet::new(),
+        };
+        let (fan, heater) = (zone.actuator(Actuator::ExhaustFan), zone.actuator(Actuator::Heater));
+        // Setpoint split-range 27.5 °C: heater ON <= 27.0, fan ON >= 28.0
+        for temp in [20.0, 27.2, 35.0, 35.0, 27.8, 20.0, 20.0, 35.0] {
+            let mut sensor = LastRow::default();
+            sensor.set("temperature", Some(temp));
+            sensor.set("humidity", Some(70.0));
+            let mut cx = Cycle {
+                influx: &influx,
+                state: &state,
+                config: &config,
+                config_path: "config.toml",
+                settings: state.settings(),
+                maintenance: false,
+                payload: serde_json::Map::new(),
+                data_quality: crate::quality::Quality::Good,
+            };
+            loops.step_zone(&mut cx, &zone, &sensor, None);
+            let (fan_on, heater_on) = (loops.controller.state(&fan) == Some(true), loops.controller.state(&heater) == Some(true));
+            assert!(!(fan_on && heater_on), "fan and heater both ON at {temp} °C");
+            // Heater ikut baris RELAY yang dikirim serial, dengan status keputusan siklus ini
+            let frame = state.device_relays.lock().unwrap().clone();
+            assert_eq!(frame, format!("RELAY|heater={}", if heater_on { "ON" } else { "OFF" }));
+        }
+        // Perintah relay lain tidak menghapus heater dari frame, dan sebaliknya
+        state.command_relay(Actuator::Pump, dcs_model::RelayMode::Auto);
+        assert_eq!(*state.device_relays.lock().unwrap(), "RELAY|pump=AUTO|heater=OFF");
+    }
+
+    fn guard(min_on: u64, min_off: u64, max_cycles: Option<u32>) -> CycleGuard {
+        CycleGuard::new(ActuatorConfig {
+            min_on: Duration::from_secs(min_on),
+            min_off: Duration::from_secs(min_off),
+            max_cycles_per_hour: max_cycles,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn min_on_and_min_off_hold_until_elapsed() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut guard = guard(60, 30, None);
+        // Status pertama tidak pernah ditahan
+        assert_eq!(guard.check(true, start), Ok(()));
+        guard.record(true, start);
+        assert_eq!(guard.check(true, at(1)), Ok(()));
+        assert_eq!(guard.check(false, at(45)), Err(CycleViolation::MinOn { remaining: Duration::from_secs(15) }));
+        assert_eq!(guard.check(false, at(60)), Ok(()));
+        guard.record(false, at(60));
+        assert_eq!(guard.check(true, at(80)), Err(CycleViolation::MinOff { remaining: Duration::from_secs(10) }));
+        assert_eq!(guard.check(true, at(90)), Ok(()));
+    }
+
+    #[test]
+    fn cycle_limit_counts_switch_ons_in_the_last_hour() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut guard = guard(0, 0, Some(2));
+        guard.record(false, start);
+        for (on, off) in [(10, 20), (30, 40)] {
+            assert_eq!(guard.check(true, at(on)), Ok(()));
+            guard.record(true, at(on));
+            guard.record(false, at(off));
+        }
+        assert_eq!(guard.check(true, at(50)), Err(CycleViolation::MaxCycles { limit: 2 }));
+        // Mematikan tidak dibatasi; switch-on pertama keluar dari jendela satu jam setelah 10 + 3600 s
+        assert_eq!(guard.check(false, at(50)), Ok(()));
+        assert_eq!(guard.check(true, at(3609)), Err(CycleViolation::MaxCycles { limit: 2 }));
+        assert_eq!(guard.check(true, at(3610)), Ok(()));
+    }
+
+    #[test]
+    fn held_decision_keeps_current_state_and_reports_hold() {
+        let limits = ActuatorConfig { min_off: Duration::from_secs(120), max_cycles_per_hour: Some(1), ..Default::default() };
+        let mut controller = Controller::new(&HashMap::from([(Actuator::Pump, limits)]));
+        let interlocks = InterlockEngine::new(&[]).unwrap();
+        let pump = ActuatorId::new("main", Actuator::Pump);
+        let start = Instant::now();
+        let first = controller.decide(&pump, false, &interlocks, &HashMap::new(), start);
+        assert_eq!((first.state, first.previous, first.held), (false, None, false));
+
+        let early = controller.decide(&pump, true, &interlocks, &HashMap::new(), start + Duration::from_secs(60));
+        assert_eq!((early.state, early.previous, early.held), (false, Some(false), true));
+
+        let on = controller.decide(&pump, true, &interlocks, &HashMap::new(), start + Duration::from_secs(120));
+        assert!(on.state && !on.held);
+        controller.decide(&pump, false, &interlocks, &HashMap::new(), start + Duration::from_secs(130));
+        // Batas siklus: ON kedua dalam satu jam ditahan di OFF
+        let limited = controller.decide(&pump, true, &interlocks, &HashMap::new(), start + Duration::from_secs(400));
+        assert!(!limited.state && limited.held);
+    }
+}
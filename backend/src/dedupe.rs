@@ -0,0 +1,56 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Status aktuator hanya ditulis saat berubah, plus heartbeat agar last() di query tetap menemukan titik
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DedupeRule {
+    pub enabled: bool,
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub heartbeat: Duration,
+}
+
+impl Default for DedupeRule {
+    fn default() -> Self {
+        Self { enabled: true, heartbeat: Duration::from_secs(300) }
+    }
+}
+
+// Key = nama field status (exhaust_fan_status, pump_calculated_status, heater_status); tanpa rule ditulis tiap siklus
+pub type DedupeConfig = HashMap<String, DedupeRule>;
+
+pub fn default_config() -> DedupeConfig {
+    ["exhaust_fan_status", "pump_calculated_status", "heater_status"]
+        .into_iter()
+        .map(|field| (field.to_string(), DedupeRule::default()))
+        .collect()
+}
+
+pub struct WriteDeduper {
+    rules: DedupeConfig,
+    // (field, zone) -> nilai dan waktu tulis terakhir
+    last: HashMap<(String, String), (i32, Instant)>,
+}
+
+impl WriteDeduper {
+    pub fn new(rules: &DedupeConfig) -> Self {
+        Self { rules: rules.clone(), last: HashMap::new() }
+    }
+
+    // true jika titik perlu ditulis; dicatat sebagai tulisan terakhir
+    pub fn should_write(&mut self, field: &str, zone: &str, value: i32, now: Instant) -> bool {
+        let Some(rule) = self.rules.get(field).filter(|r| r.enabled) else {
+            return true;
+        };
+        let key = (field.to_string(), zone.to_string());
+        let write = match self.last.get(&key) {
+            Some((last_value, at)) => *last_value != value || now.saturating_duration_since(*at) >= rule.heartbeat,
+            None => true,
+        };
+        if write {
+            self.last.insert(key, (value, now));
+        }
+        write
+    }
+}
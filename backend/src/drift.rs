@@ -0,0 +1,392 @@
+use anyhow::{bail, Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::serial::SensorData;
+
+// Kompensasi drift sensor kelembaban: operator memasukkan bacaan hygrometer referensi lewat
+// REST, backend mencocokkan model offset linear terhadap waktu (offset + slope per hari) per
+// sensor dari referensi dalam `window`, lalu menambahkan koreksinya ke humidity setelah kalibrasi.
+// Koreksi yang dipakai ditulis sebagai field humidity_drift di setiap sampel (raw = humidity -
+// humidity_drift) dan setiap referensi/reset tercatat di history file `path`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DriftConfig {
+    pub path: String,
+    // Referensi yang lebih tua dari ini (dihitung dari referensi terbaru) tidak ikut model
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub window: Duration,
+    // Batas koreksi (%RH); referensi yang selisihnya lebih besar ditolak (sensor rusak, bukan drift)
+    pub max_correction: f64,
+    // Bacaan sensor yang dipasangkan dengan referensi tidak boleh lebih tua dari ini
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub max_reading_age: Duration,
+}
+
+impl Default for DriftConfig {
+    fn default() -> Self {
+        Self {
+            path: "drift.json".to_string(),
+            window: Duration::from_secs(90 * 86_400),
+            max_correction: 10.0,
+            max_reading_age: Duration::from_secs(300),
+        }
+    }
+}
+
+// Slope hanya dihitung jika referensi dalam window tersebar minimal selama ini
+const MIN_SLOPE_SPAN_MS: f64 = 86_400_000.0;
+const DAY_MS: f64 = 86_400_000.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reference {
+    pub ts_ms: u64,
+    // Bacaan hygrometer referensi (%RH)
+    pub reference: f64,
+    // Humidity sensor setelah kalibrasi, sebelum koreksi drift
+    pub measured: f64,
+    pub by: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl Reference {
+    fn residual(&self) -> f64 {
+        self.reference - self.measured
+    }
+}
+
+// offset(t) = offset + slope_per_day * (t - anchor_ms) / 1 hari
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct DriftModel {
+    pub offset: f64,
+    pub slope_per_day: f64,
+    pub anchor_ms: u64,
+    pub points: usize,
+}
+
+impl DriftModel {
+    pub fn at(&self, ts_ms: u64) -> f64 {
+        self.offset + self.slope_per_day * (ts_ms as f64 - self.anchor_ms as f64) / DAY_MS
+    }
+
+    // Least squares residual terhadap waktu; satu titik atau rentang pendek = offset konstan
+    fn fit(references: &[&Reference]) -> Option<Self> {
+        let latest = references.iter().map(|r| r.ts_ms).max()?;
+        let n = references.len() as f64;
+        let mean_t = references.iter().map(|r| r.ts_ms as f64 - latest as f64).sum::<f64>() / n;
+        let mean_r = references.iter().map(|r| r.residual()).sum::<f64>() / n;
+        let span = references.iter().map(|r| latest - r.ts_ms).max().unwrap_or(0) as f64;
+        let slope_per_ms = if span >= MIN_SLOPE_SPAN_MS {
+            let (mut cov, mut var) = (0.0, 0.0);
+            for r in references {
+                let dt = r.ts_ms as f64 - latest as f64 - mean_t;
+                cov += dt * (r.residual() - mean_r);
+                var += dt * dt;
+            }
+            cov / var
+        } else {
+            0.0
+        };
+        Some(Self { offset: mean_r - slope_per_ms * mean_t, slope_per_day: slope_per_ms * DAY_MS, anchor_ms: latest, points: references.len() })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub ts_ms: u64,
+    // "reference" atau "reset"
+    pub action: String,
+    pub by: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<Reference>,
+    // Koreksi saat itu sebelum dan sesudah perubahan (%RH)
+    pub correction_before: f64,
+    pub correction_after: f64,
+}
+
+// Isi file `path` per device
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SensorDrift {
+    pub references: Vec<Reference>,
+    pub history: Vec<HistoryEntry>,
+}
+
+pub struct Drift {
+    config: DriftConfig,
+    sensors: Mutex<BTreeMap<String, (SensorDrift, Option<DriftModel>)>>,
+    // Humidity terakhir per device sebelum koreksi drift, untuk dipasangkan dengan referensi
+    last_reading: Mutex<HashMap<String, (f64, Instant)>>,
+}
+
+impl Drift {
+    pub fn new(config: &DriftConfig) -> Self {
+        Self { config: config.clone(), sensors: Mutex::new(BTreeMap::new()), last_reading: Mutex::new(HashMap::new()) }
+    }
+
+    // Muat referensi dan history tersimpan; Ok(n) = jumlah sensor dengan model
+    pub fn restore(&self) -> Result<usize> {
+        if !Path::new(&self.config.path).exists() {
+            return Ok(0);
+        }
+        let text = std::fs::read_to_string(&self.config.path).with_context(|| format!("Failed to read {}", self.config.path))?;
+        let stored: BTreeMap<String, SensorDrift> =
+            serde_json::from_str(&text).with_context(|| format!("Corrupt drift file {}", self.config.path))?;
+        let mut sensors = self.sensors.lock().unwrap();
+        *sensors = stored
+            .into_iter()
+            .map(|(device, drift)| {
+                let model = self.fit(&drift.references);
+                (device, (drift, model))
+            })
+            .collect();
+        Ok(sensors.values().filter(|(_, model)| model.is_some()).count())
+    }
+
+    fn fit(&self, references: &[Reference]) -> Option<DriftModel> {
+        let latest = references.iter().map(|r| r.ts_ms).max()?;
+        let window_ms = self.config.window.as_millis() as u64;
+        let recent: Vec<&Reference> = references.iter().filter(|r| latest - r.ts_ms <= window_ms).collect();
+        DriftModel::fit(&recent)
+    }
+
+    // Koreksi dibatasi max_correction, dibulatkan 0.001 %RH (nilai yang sama ditulis ke humidity_drift)
+    fn clamp(&self, correction: f64) -> f64 {
+        (correction.clamp(-self.config.max_correction, self.config.max_correction) * 1000.0).round() / 1000.0
+    }
+
+    // Bacaan live terbaru (setelah kalibrasi, sebelum koreksi); sampel backlog tidak dicatat
+    pub fn note_reading(&self, device_id: &str, humidity: f64) {
+        self.last_reading.lock().unwrap().insert(device_id.to_string(), (humidity, Instant::now()));
+    }
+
+    // Dipanggil ingest setelah kalibrasi; Some(koreksi) jika device punya model drift
+    pub fn correct(&self, device_id: &str, timestamp_ns: u64, data: &mut SensorData) -> Option<f64> {
+        let model = self.sensors.lock().unwrap().get(device_id).and_then(|(_, model)| *model)?;
+        let correction = self.clamp(model.at(timestamp_ns / 1_000_000));
+        data.humidity = (data.humidity as f64 + correction).clamp(0.0, 100.0) as f32;
+        Some(correction)
+    }
+
+    // Referensi baru dari operator; measured None = bacaan sensor terakhir (harus cukup baru)
+    pub fn add_reference(&self, device_id: &str, reference: f64, measured: Option<f64>, by: &str, note: Option<String>, now_ms: u64) -> Result<HistoryEntry> {
+        if !(0.0..=100.0).contains(&reference) {
+            bail!("reference {} is outside 0..100 %RH", reference);
+        }
+        let measured = match measured {
+            Some(measured) => measured,
+            None => match self.last_reading.lock().unwrap().get(device_id) {
+                Some((value, at)) if at.elapsed() <= self.config.max_reading_age => *value,
+                Some(_) => bail!("last reading of {} is older than {}s, pass \"measured\" explicitly", device_id, self.config.max_reading_age.as_secs()),
+                None => bail!("no reading from {} yet, pass \"measured\" explicitly", device_id),
+            },
+        };
+        if (reference - measured).abs() > self.config.max_correction {
+            bail!(
+                "reference {:.2} differs from sensor {:.2} by more than max_correction {} %RH; recalibrate or replace the sensor",
+                reference, measured, self.config.max_correction
+            );
+        }
+        let entry = {
+            let mut sensors = self.sensors.lock().unwrap();
+            let (drift, model) = sensors.entry(device_id.to_string()).or_default();
+            let before = model.map_or(0.0, |m| self.clamp(m.at(now_ms)));
+            let reference = Reference { ts_ms: now_ms, reference, measured, by: by.to_string(), note };
+            drift.references.push(reference.clone());
+            *model = self.fit(&drift.references);
+            let after = model.map_or(0.0, |m| self.clamp(m.at(now_ms)));
+            let entry = HistoryEntry { ts_ms: now_ms, action: "reference".into(), by: by.to_string(), reference: Some(reference), correction_before: before, correction_after: after };
+            drift.history.push(entry.clone());
+            entry
+        };
+        self.persist();
+        Ok(entry)
+    }
+
+    // Hapus model device; history tetap disimpan
+    pub fn reset(&self, device_id: &str, by: &str, now_ms: u64) -> Option<HistoryEntry> {
+        let entry = {
+            let mut sensors = self.sensors.lock().unwrap();
+            let (drift, model) = sensors.get_mut(device_id)?;
+            let before = model.map_or(0.0, |m| self.clamp(m.at(now_ms)));
+            drift.references.clear();
+            *model = None;
+            let entry = HistoryEntry { ts_ms: now_ms, action: "reset".into(), by: by.to_string(), reference: None, correction_before: before, correction_after: 0.0 };
+            drift.history.push(entry.clone());
+            entry
+        };
+        self.persist();
+        Some(entry)
+    }
+
+    // Ringkasan untuk GET /api/drift: model, koreksi saat ini, referensi dan history per device
+    pub fn snapshot(&self, now_ms: u64) -> serde_json::Value {
+        let sensors = self.sensors.lock().unwrap();
+        let map: serde_json::Map<String, serde_json::Value> = sensors
+            .iter()
+            .map(|(device, (drift, model))| {
+                let correction = model.map(|m| self.clamp(m.at(now_ms)));
+                let value = serde_json::json!({
+                    "model": model,
+                    "correction": correction,
+                    "references": drift.references,
+                    "history": drift.history,
+                });
+                (device.clone(), value)
+            })
+            .collect();
+        serde_json::Value::Object(map)
+    }
+
+    fn persist(&self) {
+        if let Err(e) = self.save() {
+            warn!("💾 Drift correction applied but not persisted: {:#}", e);
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let stored: BTreeMap<String, SensorDrift> = self.sensors.lock().unwrap().iter().map(|(device, (drift, _))| (device.clone(), drift.clone())).collect();
+        let path = &self.config.path;
+        let tmp = format!("{path}.tmp");
+        let json = serde_json::to_vec_pretty(&stored)?;
+        std::fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T0: u64 = 1_700_000_000_000;
+    const DAY: u64 = 86_400_000;
+
+    fn drift(name: &str) -> (Drift, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("dcs-drift-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("drift.json").to_str().unwrap().to_string();
+        (Drift::new(&DriftConfig { path, ..Default::default() }), dir)
+    }
+
+    fn sample(humidity: f32) -> SensorData {
+        SensorData {
+            timestamp: 0,
+            temperature: 25.0,
+            humidity,
+            exhaust_fan_status: None,
+            pump_status: None,
+            fan_duty: None,
+            aux: Vec::new(),
+        }
+    }
+
+    fn drift_with_two_day_span() -> (Drift, std::path::PathBuf) {
+        let (drift, dir) = drift("span");
+        drift.add_reference("esp32-1", 55.0, Some(50.0), "op", None, T0).unwrap();
+        let entry = drift.add_reference("esp32-1", 57.0, Some(50.0), "op", None, T0 + 2 * DAY).unwrap();
+        assert!(close(entry.correction_before, 5.0));
+        assert!(close(entry.correction_after, 7.0));
+        (drift, dir)
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn single_reference_is_a_constant_offset() {
+        let (drift, dir) = drift("single");
+        let mut data = sample(60.0);
+        assert!(drift.correct("esp32-1", T0 * 1_000_000, &mut data).is_none());
+
+        let entry = drift.add_reference("esp32-1", 62.5, Some(60.0), "op", None, T0).unwrap();
+        assert_eq!((entry.correction_before, entry.correction_after), (0.0, 2.5));
+        assert_eq!(drift.correct("esp32-1", (T0 + 30 * DAY) * 1_000_000, &mut data), Some(2.5));
+        assert_eq!(data.humidity, 62.5);
+
+        // Hasil koreksi tetap di 0..100
+        let mut wet = sample(99.0);
+        drift.correct("esp32-1", T0 * 1_000_000, &mut wet);
+        assert_eq!(wet.humidity, 100.0);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn slope_needs_references_a_day_apart() {
+        let (drift, dir) = drift("slope");
+        drift.add_reference("esp32-1", 52.0, Some(50.0), "op", None, T0).unwrap();
+        drift.add_reference("esp32-1", 54.0, Some(50.0), "op", None, T0 + DAY / 24).unwrap();
+        let model = drift.fit(&drift.sensors.lock().unwrap()["esp32-1"].0.references).unwrap();
+        assert!(close(model.offset, 3.0));
+        assert_eq!(model.slope_per_day, 0.0);
+
+        // Residual 5 di hari 0, 7 di hari 2 -> 1 %RH per hari
+        let (drift, dir2) = drift_with_two_day_span();
+        let model = drift.sensors.lock().unwrap()["esp32-1"].1.unwrap();
+        assert!(close(model.slope_per_day, 1.0));
+        assert!(close(model.at(T0 + 2 * DAY), 7.0));
+        assert!(close(model.at(T0 + 4 * DAY), 9.0));
+        // Ekstrapolasi dibatasi max_correction
+        let mut data = sample(50.0);
+        assert_eq!(drift.correct("esp32-1", (T0 + 6 * DAY) * 1_000_000, &mut data), Some(10.0));
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_dir_all(dir2).unwrap();
+    }
+
+    #[test]
+    fn references_outside_window_are_ignored() {
+        let (drift, dir) = drift("window");
+        drift.add_reference("esp32-1", 59.0, Some(50.0), "op", None, T0).unwrap();
+        drift.add_reference("esp32-1", 51.0, Some(50.0), "op", None, T0 + 100 * DAY).unwrap();
+        let model = drift.sensors.lock().unwrap()["esp32-1"].1.unwrap();
+        assert_eq!(model.points, 1);
+        assert!(close(model.offset, 1.0));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_references() {
+        let (drift, dir) = drift("reject");
+        assert!(drift.add_reference("esp32-1", 101.0, Some(99.0), "op", None, T0).is_err());
+        let err = drift.add_reference("esp32-1", 50.0, None, "op", None, T0).unwrap_err();
+        assert!(err.to_string().starts_with("no reading from esp32-1 yet"));
+        let err = drift.add_reference("esp32-1", 70.0, Some(59.0), "op", None, T0).unwrap_err();
+        assert!(err.to_string().contains("by more than max_correction 10 %RH"));
+        assert!(drift.sensors.lock().unwrap().is_empty());
+
+        // Tanpa measured, bacaan live terakhir yang dipasangkan
+        drift.note_reading("esp32-1", 48.0);
+        let entry = drift.add_reference("esp32-1", 50.0, None, "op", Some("hygrometer".into()), T0).unwrap();
+        assert_eq!(entry.reference.unwrap().measured, 48.0);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reset_and_restore_keep_history() {
+        let (drift, dir) = drift_with_two_day_span();
+        let restored = Drift::new(&drift.config);
+        assert_eq!(restored.restore().unwrap(), 1);
+        let model = restored.sensors.lock().unwrap()["esp32-1"].1.unwrap();
+        assert!(close(model.slope_per_day, 1.0));
+
+        assert!(drift.reset("esp32-2", "op", T0).is_none());
+        let entry = drift.reset("esp32-1", "op", T0 + 2 * DAY).unwrap();
+        assert!(close(entry.correction_before, 7.0));
+        assert!(drift.correct("esp32-1", T0 * 1_000_000, &mut sample(50.0)).is_none());
+
+        let restored = Drift::new(&drift.config);
+        assert_eq!(restored.restore().unwrap(), 0);
+        let snapshot = restored.snapshot(T0);
+        assert_eq!(snapshot["esp32-1"]["history"].as_array().unwrap().len(), 3);
+        assert!(snapshot["esp32-1"]["correction"].is_null());
+
+        std::fs::write(&drift.config.path, "{").unwrap();
+        assert!(restored.restore().is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,253 @@
+use anyhow::{bail, Context, Result};
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::alarms::Alarm;
+use crate::secrets::Secret;
+
+// Kanal notifikasi email (SMTP) untuk operator yang tidak memakai Telegram.
+// Placeholder template: {severity} {state} {device} {id} {message} {zone} {value} {threshold} {dashboard}
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EmailConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: String,
+    // Bisa juga lewat env SMTP_PASSWORD / SMTP_PASSWORD_FILE
+    pub password: Secret,
+    // "DCS <dcs@example.com>" atau alamat saja
+    pub from: String,
+    pub to: Vec<String>,
+    pub subject: String,
+    pub body: String,
+    // Link di template {dashboard}, mis. dashboard ThingsBoard atau Grafana
+    pub dashboard_url: String,
+    // Aturan pertama yang prefix-nya cocok dengan id alarm menimpa penerima/template default
+    pub rules: Vec<EmailRule>,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: 587,
+            security: SmtpSecurity::Starttls,
+            username: String::new(),
+            password: Secret::default(),
+            from: String::new(),
+            to: Vec::new(),
+            subject: "[{severity}] {device}: {id} {state}".to_string(),
+            body: "Alarm {id} {state} on {device}\n\nSeverity:  {severity}\nZone:      {zone}\nValue:     {value}\nThreshold: {threshold}\n\n{message}\n\nDashboard: {dashboard}\n".to_string(),
+            dashboard_url: String::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmailRule {
+    // Prefix id alarm, mis. "forecast_" atau "rate_temp_fast"
+    pub alarm: String,
+    // [] = alarm ini tidak dikirim lewat email
+    pub to: Option<Vec<String>>,
+    pub subject: Option<String>,
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    // Port 587: koneksi biasa lalu STARTTLS (wajib berhasil)
+    #[default]
+    Starttls,
+    // Port 465: TLS sejak awal
+    Tls,
+    // Tanpa enkripsi, hanya untuk relay lokal tanpa login (AUTH ditolak tanpa TLS)
+    None,
+}
+
+pub struct Mail {
+    pub to: Vec<String>,
+    pub subject: String,
+    pub body: String,
+    // true = body HTML (mis. laporan harian)
+    pub html: bool,
+}
+
+impl EmailConfig {
+    fn rule(&self, alarm_id: &str) -> Option<&EmailRule> {
+        self.rules.iter().find(|rule| alarm_id.starts_with(&rule.alarm))
+    }
+
+    // None jika alarm ini tidak punya penerima
+    pub fn compose(&self, device_id: &str, state: &str, alarm: &Alarm) -> Option<Mail> {
+        let rule = self.rule(&alarm.id);
+        let to = rule.and_then(|r| r.to.clone()).unwrap_or_else(|| self.to.clone());
+        if to.is_empty() {
+            return None;
+        }
+        let subject = rule.and_then(|r| r.subject.as_deref()).unwrap_or(&self.subject);
+        let body = rule.and_then(|r| r.body.as_deref()).unwrap_or(&self.body);
+        let render = |template: &str| render(template, device_id, state, &self.dashboard_url, alarm);
+        // Header Subject satu baris
+        let subject = render(subject).replace(['\r', '\n'], " ");
+        Some(Mail { to, subject, body: render(body), html: false })
+    }
+}
+
+fn render(template: &str, device_id: &str, state: &str, dashboard: &str, alarm: &Alarm) -> String {
+    let number = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.2}", v));
+    let context = &alarm.context;
+    template
+        .replace("{severity}", &alarm.severity.as_str().to_uppercase())
+        .replace("{state}", state)
+        .replace("{device}", device_id)
+        .replace("{id}", &alarm.id)
+        .replace("{message}", &alarm.message)
+        .replace("{zone}", context.zone.as_deref().unwrap_or("-"))
+        .replace("{value}", &number(context.value))
+        .replace("{threshold}", &number(context.threshold))
+        .replace("{dashboard}", if dashboard.is_empty() { "-" } else { dashboard })
+}
+
+// Batas waktu koneksi dan setiap perintah SMTP
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// Satu sesi SMTP singkat per email (lettre, transport tokio); notifikasi alarm jarang,
+// tidak perlu koneksi persisten
+pub async fn send(config: &EmailConfig, mail: &Mail) -> Result<()> {
+    // AUTH PLAIN hanya base64: tanpa TLS password terbaca siapa pun di jalur jaringan
+    if config.security == SmtpSecurity::None && !config.username.is_empty() {
+        bail!("refusing to send SMTP credentials without TLS: set security = \"starttls\" or \"tls\", or clear username for a local relay");
+    }
+    let message = message(config, mail)?;
+    let builder = match config.security {
+        SmtpSecurity::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?,
+        SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+        SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+    };
+    let mut builder = builder.port(config.port).timeout(Some(TIMEOUT));
+    if !config.username.is_empty() {
+        builder = builder.credentials(Credentials::new(config.username.clone(), config.password.expose().to_string()));
+    }
+    builder
+        .build()
+        .send(message)
+        .await
+        .with_context(|| format!("SMTP delivery via {}:{} failed", config.host, config.port))?;
+    Ok(())
+}
+
+// Alamat dari config diparse sebagai mailbox, jadi CR/LF tidak bisa menyisipkan header baru;
+// encoding subject/body (UTF-8) dan pelipatan header diurus lettre
+fn message(config: &EmailConfig, mail: &Mail) -> Result<Message> {
+    let mailbox = |address: &str| address.parse::<Mailbox>().with_context(|| format!("invalid email address '{}'", address));
+    let mut builder = Message::builder().from(mailbox(&config.from)?).subject(mail.subject.as_str());
+    for recipient in &mail.to {
+        builder = builder.to(mailbox(recipient)?);
+    }
+    let content_type = if mail.html { ContentType::TEXT_HTML } else { ContentType::TEXT_PLAIN };
+    Ok(builder.header(content_type).body(mail.body.clone())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alarms::{AlarmContext, Severity};
+
+    fn alarm(id: &str) -> Alarm {
+        Alarm {
+            id: id.to_string(),
+            severity: Severity::Critical,
+            message: "Zone a temperature predicted to reach 36.0°C".to_string(),
+            acknowledged: false,
+            context: AlarmContext::zone("a").value(36.0).threshold(35.0),
+        }
+    }
+
+    fn config() -> EmailConfig {
+        EmailConfig {
+            host: "smtp.example.com".to_string(),
+            from: "RUST DCS <dcs@example.com>".to_string(),
+            to: vec!["ops@example.com".to_string()],
+            dashboard_url: "https://tb.example.com/d/1".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn compose_fills_every_placeholder() {
+        let mail = config().compose("esp32-1", "raised", &alarm("forecast_a")).unwrap();
+        assert_eq!(mail.to, ["ops@example.com"]);
+        assert_eq!(mail.subject, "[CRITICAL] esp32-1: forecast_a raised");
+        assert!(mail.body.contains("Zone:      a\n"));
+        assert!(mail.body.contains("Value:     36.00\nThreshold: 35.00\n"));
+        assert!(mail.body.contains("Dashboard: https://tb.example.com/d/1"));
+
+        // Tanpa konteks dan tanpa dashboard: "-"
+        let bare = Alarm { context: AlarmContext::default(), ..alarm("stale_a") };
+        let mail = EmailConfig { dashboard_url: String::new(), ..config() }.compose("esp32-1", "cleared", &bare).unwrap();
+        assert!(mail.body.contains("Zone:      -\nValue:     -\nThreshold: -"));
+        assert!(mail.body.ends_with("Dashboard: -\n"));
+    }
+
+    #[test]
+    fn first_matching_rule_overrides_recipients_and_templates() {
+        let mut config = config();
+        config.rules = vec![
+            EmailRule { alarm: "forecast_".to_string(), to: Some(vec!["lead@example.com".to_string()]), subject: Some("Zone {zone}\n> {threshold}".to_string()), body: None },
+            EmailRule { alarm: "forecast_a".to_string(), to: Some(vec![]), subject: None, body: None },
+            EmailRule { alarm: "stale_".to_string(), to: Some(vec![]), subject: None, body: Some("{message}".to_string()) },
+        ];
+        let mail = config.compose("esp32-1", "raised", &alarm("forecast_a")).unwrap();
+        assert_eq!(mail.to, ["lead@example.com"]);
+        // Subject selalu satu baris
+        assert_eq!(mail.subject, "Zone a > 35.00");
+        assert!(mail.body.starts_with("Alarm forecast_a raised on esp32-1"));
+        assert!(config.compose("esp32-1", "raised", &alarm("stale_a")).is_none());
+    }
+
+    #[test]
+    fn message_carries_addresses_subject_and_utf8_body() {
+        let mail = Mail {
+            to: vec!["ops@example.com".to_string(), "Lead <lead@example.com>".to_string()],
+            subject: "🔥 Zone a suhu tinggi".to_string(),
+            body: "Suhu 36°C\n.\nselesai".to_string(),
+            html: false,
+        };
+        let built = message(&config(), &mail).unwrap();
+        let envelope = built.envelope();
+        assert_eq!(envelope.from().map(|a| a.to_string()), Some("dcs@example.com".to_string()));
+        let to: Vec<String> = envelope.to().iter().map(|a| a.to_string()).collect();
+        assert_eq!(to, ["ops@example.com", "lead@example.com"]);
+
+        let text = String::from_utf8(built.formatted()).unwrap();
+        assert!(text.contains("From: \"RUST DCS\" <dcs@example.com>\r\n"), "{text}");
+        assert!(text.contains("Content-Type: text/plain; charset=utf-8\r\n"), "{text}");
+        // Subject non-ASCII sebagai encoded-word, baris header tetap <= 78 karakter
+        assert!(text.contains("Subject: =?utf-8?b?"), "{text}");
+        assert!(text.split("\r\n").all(|line| line.len() <= 78));
+
+        let html = message(&config(), &Mail { html: true, ..mail }).unwrap();
+        assert!(String::from_utf8(html.formatted()).unwrap().contains("Content-Type: text/html; charset=utf-8\r\n"));
+    }
+
+    #[test]
+    fn addresses_cannot_inject_headers() {
+        let mail = Mail { to: vec!["a@example.com\r\nBcc: x@evil.test".to_string()], subject: "x".to_string(), body: "y".to_string(), html: false };
+        let error = message(&config(), &mail).unwrap_err();
+        assert!(error.to_string().contains("invalid email address"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn refuses_auth_without_tls() {
+        let config = EmailConfig { security: SmtpSecurity::None, username: "dcs".to_string(), port: 1, ..config() };
+        let mail = Mail { to: config.to.clone(), subject: "x".to_string(), body: "y".to_string(), html: false };
+        let error = send(&config, &mail).await.unwrap_err();
+        assert!(error.to_string().contains("without TLS"), "{error}");
+    }
+}
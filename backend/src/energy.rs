@@ -0,0 +1,173 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::bridge::{now_ns, write_points, zone_point, Influx};
+use crate::config::Config;
+use crate::control::ActuatorId;
+use crate::line_protocol::Point;
+use crate::state::AppState;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnergyTotals {
+    pub total_kwh: f64,
+    pub today_kwh: f64,
+}
+
+#[derive(Debug, Default)]
+struct Meter {
+    totals: EnergyTotals,
+    state: Option<bool>,
+    last_update: Option<Instant>,
+}
+
+// Estimasi konsumsi energi dari waktu ON x daya nominal, per aktuator fisik (zone + jenis).
+// Batas hari memakai UTC (hari sejak epoch).
+#[derive(Debug, Default)]
+pub struct EnergyMeter {
+    meters: HashMap<ActuatorId, Meter>,
+    day: u64,
+}
+
+fn current_day() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 86400
+}
+
+impl EnergyMeter {
+    pub fn new() -> Self {
+        Self { meters: HashMap::new(), day: current_day() }
+    }
+
+    pub fn update(&mut self, actuator: &ActuatorId, state: bool, rated_power_w: f64, now: Instant) {
+        let meter = self.meters.entry(actuator.clone()).or_default();
+        if let (Some(true), Some(last)) = (meter.state, meter.last_update) {
+            let hours = now.saturating_duration_since(last).as_secs_f64() / 3600.0;
+            let kwh = rated_power_w * hours / 1000.0;
+            meter.totals.total_kwh += kwh;
+            meter.totals.today_kwh += kwh;
+        }
+        meter.state = Some(state);
+        meter.last_update = Some(now);
+    }
+
+    // Jika hari sudah berganti, kembalikan total harian yang selesai lalu mulai dari nol
+    pub fn roll_day(&mut self) -> Option<HashMap<ActuatorId, f64>> {
+        let today = current_day();
+        if today == self.day {
+            return None;
+        }
+        self.day = today;
+        let finished = self
+            .meters
+            .iter_mut()
+            .map(|(a, m)| (a.clone(), std::mem::take(&mut m.totals.today_kwh)))
+            .collect();
+        Some(finished)
+    }
+
+    pub fn totals(&self, actuator: &ActuatorId) -> EnergyTotals {
+        self.meters.get(actuator).map(|m| m.totals).unwrap_or_default()
+    }
+
+    // Untuk checkpoint: hari UTC yang sedang dihitung dan total per aktuator
+    pub fn snapshot(&self) -> (u64, HashMap<ActuatorId, EnergyTotals>) {
+        (self.day, self.meters.iter().map(|(a, m)| (a.clone(), m.totals)).collect())
+    }
+
+    // Pulihkan total dari checkpoint. Jika hari sudah berganti selama mati, roll_day berikutnya
+    // melaporkan total hari itu lalu mereset today_kwh seperti biasa.
+    pub fn restore(&mut self, day: u64, totals: HashMap<ActuatorId, EnergyTotals>) {
+        if day == 0 {
+            return;
+        }
+        self.day = day;
+        for (actuator, totals) in totals {
+            self.meters.entry(actuator).or_default().totals = totals;
+        }
+    }
+}
+
+// Estimasi energi per aktuator (measurement "energy")
+pub(crate) fn write_to_influx(influx: &Influx, meter: &EnergyMeter, config: &Config, state: &AppState) -> Result<()> {
+    let ts = now_ns();
+    let points: Vec<Point> = state
+        .zones
+        .iter()
+        .flat_map(|z| z.actuator_ids())
+        .map(|a| {
+            let totals = meter.totals(&a);
+            let power = config.actuators.get(&a.kind).map(|c| c.rated_power_w).unwrap_or_default();
+            zone_point(state, "energy", &a.zone)
+                .tag("actuator", a.name())
+                .float_prec("kwh_total", totals.total_kwh, 4)
+                .float_prec("kwh_today", totals.today_kwh, 4)
+                .float_prec("rated_power_w", power, 1)
+                .timestamp(ts)
+        })
+        .collect();
+    write_points(influx, &points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::Actuator;
+
+    fn heater() -> ActuatorId {
+        ActuatorId::new("main", Actuator::Heater)
+    }
+
+    fn restored(day: u64) -> EnergyMeter {
+        let mut meter = EnergyMeter::new();
+        let totals = EnergyTotals { total_kwh: 120.5, today_kwh: 1.25 };
+        meter.restore(day, HashMap::from([(heater(), totals)]));
+        meter
+    }
+
+    #[test]
+    fn totals_survive_restart() {
+        let mut meter = restored(current_day());
+        assert!(meter.roll_day().is_none());
+        let start = Instant::now();
+        meter.update(&heater(), true, 1000.0, start);
+        meter.update(&heater(), false, 1000.0, start + std::time::Duration::from_secs(1800));
+        let totals = meter.totals(&heater());
+        assert!((totals.total_kwh - 121.0).abs() < 1e-9);
+        assert!((totals.today_kwh - 1.75).abs() < 1e-9);
+        assert_eq!(meter.snapshot().1[&heater()], totals);
+    }
+
+    #[test]
+    fn day_finished_while_down_is_reported() {
+        let mut meter = restored(current_day() - 1);
+        let finished = meter.roll_day().unwrap();
+        assert_eq!(finished[&heater()], 1.25);
+        assert_eq!(meter.totals(&heater()), EnergyTotals { total_kwh: 120.5, today_kwh: 0.0 });
+    }
+
+    #[test]
+    fn zones_meter_their_own_actuators() {
+        let mut meter = EnergyMeter::new();
+        let (a, b) = (ActuatorId::new("a", Actuator::Pump), ActuatorId::new("b", Actuator::Pump));
+        let start = Instant::now();
+        meter.update(&a, true, 500.0, start);
+        meter.update(&b, false, 500.0, start);
+        let later = start + std::time::Duration::from_secs(3600);
+        meter.update(&a, false, 500.0, later);
+        meter.update(&b, false, 500.0, later);
+        assert!((meter.totals(&a).total_kwh - 0.5).abs() < 1e-9);
+        assert_eq!(meter.totals(&b).total_kwh, 0.0);
+    }
+
+    #[test]
+    fn empty_checkpoint_is_ignored() {
+        let mut meter = EnergyMeter::new();
+        meter.restore(0, HashMap::new());
+        assert!(meter.roll_day().is_none());
+    }
+}
@@ -0,0 +1,181 @@
+use log::error;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+use crate::bridge::{write_points, Influx};
+use crate::line_protocol::Point;
+use crate::{grafana, sink};
+
+// Jumlah event terakhir yang disimpan di memori untuk REST API
+const RECENT_CAPACITY: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventSource {
+    Auto,
+    Manual,
+    Rpc,
+}
+
+impl EventSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventSource::Auto => "auto",
+            EventSource::Manual => "manual",
+            EventSource::Rpc => "rpc",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub ts_ns: u64,
+    // actuator, serial, alarm, config, autotune, influx, command_mismatch
+    pub kind: &'static str,
+    pub subject: String,
+    pub old: Option<String>,
+    pub new: String,
+    pub reason: String,
+    pub source: EventSource,
+}
+
+impl Event {
+    pub fn new(kind: &'static str, subject: impl Into<String>, new: impl Into<String>) -> Self {
+        Self {
+            ts_ns: crate::now_ns(),
+            kind,
+            subject: subject.into(),
+            old: None,
+            new: new.into(),
+            reason: String::new(),
+            source: EventSource::Auto,
+        }
+    }
+
+    pub fn old(mut self, old: impl Into<String>) -> Self {
+        self.old = Some(old.into());
+        self
+    }
+
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = reason.into();
+        self
+    }
+
+    pub fn source(mut self, source: EventSource) -> Self {
+        self.source = source;
+        self
+    }
+}
+
+// Event disimpan di ring buffer memori dan diteruskan ke writer InfluxDB lewat channel
+pub struct EventLog {
+    recent: Mutex<VecDeque<Event>>,
+    tx: mpsc::Sender<Event>,
+    rx: Mutex<Option<mpsc::Receiver<Event>>>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel(256);
+        Self {
+            recent: Mutex::new(VecDeque::new()),
+            tx,
+            rx: Mutex::new(Some(rx)),
+        }
+    }
+
+    pub fn record(&self, event: Event) {
+        log::info!(
+            "📝 Event [{}] {}: {} -> {} ({}, {})",
+            event.kind,
+            event.subject,
+            event.old.as_deref().unwrap_or("-"),
+            event.new,
+            event.source.as_str(),
+            event.reason
+        );
+        {
+            let mut recent = self.recent.lock().unwrap();
+            recent.push_back(event.clone());
+            while recent.len() > RECENT_CAPACITY {
+                recent.pop_front();
+            }
+        }
+        if self.tx.try_send(event).is_err() {
+            log::warn!("Event writer queue full, event not stored in InfluxDB");
+        }
+    }
+
+    pub fn recent(&self, limit: usize, kind: Option<&str>) -> Vec<Event> {
+        let recent = self.recent.lock().unwrap();
+        let mut events: Vec<Event> = recent
+            .iter()
+            .rev()
+            .filter(|e| kind.is_none_or(|k| e.kind == k))
+            .take(limit)
+            .cloned()
+            .collect();
+        events.reverse();
+        events
+    }
+
+    // Receiver hanya bisa diambil sekali oleh task writer
+    pub fn take_receiver(&self) -> Option<mpsc::Receiver<Event>> {
+        self.rx.lock().unwrap().take()
+    }
+}
+
+// Menulis event dari EventLog ke measurement "events", plus anotasi Grafana jika diaktifkan
+pub(crate) async fn run_writer(
+    client: Client,
+    influx: Influx,
+    grafana: grafana::GrafanaConfig,
+    sink: Option<sink::SinkSender>,
+    mut rx: mpsc::Receiver<Event>,
+) {
+    while let Some(event) = rx.recv().await {
+        if let Some(sink) = &sink {
+            sink.send_event(&event);
+        }
+
+        if grafana.wants(&event) {
+            let result = match grafana.mode {
+                grafana::AnnotationMode::Api => grafana::post_annotation(&client, &grafana, &event).await,
+                grafana::AnnotationMode::Measurement => {
+                    let point = Point::new("annotations")
+                        .tag("kind", event.kind)
+                        .string("title", &event.subject)
+                        .string("text", grafana::annotation_text(&event))
+                        .timestamp(event.ts_ns);
+                    write_points(&influx, &[point])
+                }
+            };
+            if let Err(e) = result {
+                error!("Failed to write Grafana annotation: {}", e);
+            }
+        }
+
+        let mut point = Point::new("events")
+            .tag("kind", event.kind)
+            .tag("subject", &event.subject)
+            .tag("source", event.source.as_str())
+            .string("new", &event.new)
+            .string("reason", &event.reason);
+        if let Some(old) = &event.old {
+            point = point.string("old", old);
+        }
+        if let Err(e) = write_points(&influx, &[point.timestamp(event.ts_ns)]) {
+            error!("Failed to write event to InfluxDB: {}", e);
+        }
+    }
+}
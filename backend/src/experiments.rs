@@ -0,0 +1,301 @@
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::Instant;
+
+use crate::audit::AuditEntry;
+use crate::control::{Actuator, ActuatorId, ActuatorMode};
+use crate::events::{Event, EventSource};
+use crate::state::{AppState, Telemetry};
+
+// Eksperimen batch (`backend experiment run plan.yaml`): urutan setpoint dan mode pump/fan dengan
+// durasi per langkah. Selama berjalan semua titik InfluxDB diberi tag experiment=<id>; di akhir
+// dibuat ringkasan min/max/mean dan time-in-band per langkah.
+
+// Id eksperimen yang sedang berjalan, dipakai write_points untuk tag InfluxDB
+static ACTIVE: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn active() -> Option<String> {
+    ACTIVE.lock().unwrap().clone()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Plan {
+    pub name: String,
+    // Kosong = satu-satunya zone
+    #[serde(default)]
+    pub zone: Option<String>,
+    // Suhu dianggap "in band" jika |suhu - setpoint| <= band (°C)
+    #[serde(default = "default_band")]
+    pub band: f64,
+    // Folder laporan JSON experiment_<id>.json
+    #[serde(default = "default_report_dir")]
+    pub report_dir: String,
+    pub steps: Vec<Step>,
+}
+
+fn default_band() -> f64 {
+    0.5
+}
+
+fn default_report_dir() -> String {
+    ".".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub duration: Duration,
+    // Override setpoint zone (sumber "api"); tanpa ini override sebelum eksperimen
+    #[serde(default)]
+    pub setpoint: Option<f64>,
+    // Mode pump/fan selama langkah; tanpa ini mode sebelum eksperimen
+    #[serde(default)]
+    pub pump: Option<ActuatorMode>,
+    #[serde(default)]
+    pub fan: Option<ActuatorMode>,
+}
+
+impl Plan {
+    pub fn load(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Cannot read experiment plan {}", path))?;
+        serde_yaml::from_str(&text).with_context(|| format!("Invalid experiment plan {}", path))
+    }
+
+    // Cek rencana terhadap zone yang dikonfigurasi; mengembalikan nama zone yang dipakai
+    pub fn validate(&self, zones: &[crate::zone::Zone]) -> Result<String> {
+        if self.name.is_empty() || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            bail!("experiment name must be non-empty and use only letters, digits, '-' and '_'");
+        }
+        if self.steps.is_empty() {
+            bail!("experiment plan has no steps");
+        }
+        if !(self.band.is_finite() && self.band > 0.0) {
+            bail!("band must be greater than 0");
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            if step.duration.is_zero() {
+                bail!("step {} ({}) has zero duration", i + 1, step.label(i));
+            }
+            if step.setpoint.is_some_and(|sp| !sp.is_finite()) {
+                bail!("step {} ({}) setpoint must be finite", i + 1, step.label(i));
+            }
+        }
+        let zone = match (&self.zone, zones) {
+            (None, [only]) => only,
+            (None, _) => bail!("zone is required when several zones are configured"),
+            (Some(name), zones) => zones.iter().find(|z| &z.name == name).with_context(|| format!("unknown zone '{}'", name))?,
+        };
+        if self.steps.iter().any(|s| s.setpoint.is_some()) && !zone.setpoint_config().uses(crate::setpoint::SetpointSource::Api) {
+            bail!("zone '{}' does not use the \"api\" setpoint source, step setpoints would be ignored", zone.name);
+        }
+        for (actuator, used) in [(Actuator::Pump, self.steps.iter().any(|s| s.pump.is_some())), (Actuator::ExhaustFan, self.steps.iter().any(|s| s.fan.is_some()))] {
+            if used && !zone.has(actuator) {
+                bail!("zone '{}' has no {}", zone.name, actuator.name());
+            }
+        }
+        Ok(zone.name.clone())
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.steps.iter().map(|s| s.duration).sum()
+    }
+}
+
+impl Step {
+    fn label(&self, index: usize) -> String {
+        self.name.clone().unwrap_or_else(|| format!("step{}", index + 1))
+    }
+}
+
+// min/max/mean satu field
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FieldStats {
+    pub samples: usize,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    #[serde(skip)]
+    sum: f64,
+}
+
+impl FieldStats {
+    fn add(&mut self, value: f64) {
+        self.samples += 1;
+        self.sum += value;
+        self.min = Some(self.min.map_or(value, |m| m.min(value)));
+        self.max = Some(self.max.map_or(value, |m| m.max(value)));
+        self.mean = Some(self.sum / self.samples as f64);
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Summary {
+    pub name: String,
+    pub setpoint: Option<f64>,
+    pub pump: Option<ActuatorMode>,
+    pub fan: Option<ActuatorMode>,
+    pub duration_s: f64,
+    pub temperature: FieldStats,
+    pub humidity: FieldStats,
+    // Detik (dibobot selang antar siklus) dengan suhu dalam band setpoint
+    pub time_in_band_s: f64,
+    // Persentase terhadap waktu yang punya suhu dan setpoint; None jika tidak ada data
+    pub time_in_band_pct: Option<f64>,
+    #[serde(skip)]
+    observed_s: f64,
+}
+
+impl Summary {
+    // Selang antar siklus dibatasi agar gap data (InfluxDB mati) tidak dihitung sebagai in band
+    fn add(&mut self, temperature: Option<f64>, humidity: Option<f64>, setpoint: Option<f64>, band: f64, dt: f64) {
+        if let Some(t) = temperature {
+            self.temperature.add(t);
+        }
+        if let Some(h) = humidity {
+            self.humidity.add(h);
+        }
+        if let (Some(t), Some(sp)) = (temperature, setpoint) {
+            self.observed_s += dt;
+            if (t - sp).abs() <= band {
+                self.time_in_band_s += dt;
+            }
+            self.time_in_band_pct = Some(100.0 * self.time_in_band_s / self.observed_s);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub id: String,
+    pub name: String,
+    pub zone: String,
+    pub band: f64,
+    pub started_ms: u64,
+    pub finished_ms: u64,
+    // false jika dihentikan (Ctrl-C) sebelum semua langkah selesai
+    pub completed: bool,
+    pub steps: Vec<Summary>,
+    pub overall: Summary,
+}
+
+impl Report {
+    // Ditulis ke report_dir/experiment_<id>.json
+    pub fn save(&self, dir: &str) -> Result<String> {
+        std::fs::create_dir_all(dir).with_context(|| format!("Cannot create report directory {}", dir))?;
+        let path = std::path::Path::new(dir).join(format!("experiment_{}.json", self.id));
+        std::fs::write(&path, serde_json::to_vec_pretty(self)?).with_context(|| format!("Cannot write {}", path.display()))?;
+        Ok(path.display().to_string())
+    }
+
+    pub fn log(&self) {
+        info!("🧪 Experiment {} {}: band ±{:.2}°C", self.id, if self.completed { "finished" } else { "aborted" }, self.band);
+        for summary in self.steps.iter().chain(std::iter::once(&self.overall)) {
+            let fmt = |v: Option<f64>| v.map(|v| format!("{:.2}", v)).unwrap_or_else(|| "-".to_string());
+            info!(
+                "   {:<12} {:>6.0}s  T min/max/mean {}/{}/{} °C  RH mean {} %  in band {} %",
+                summary.name,
+                summary.duration_s,
+                fmt(summary.temperature.min),
+                fmt(summary.temperature.max),
+                fmt(summary.temperature.mean),
+                fmt(summary.humidity.mean),
+                fmt(summary.time_in_band_pct)
+            );
+        }
+    }
+}
+
+fn record(state: &AppState, id: &str, action: &str, target: &str, detail: String) {
+    state.audit.record(AuditEntry::new(id, "experiment", "local", action, target, detail.clone()));
+    state.events.record(Event::new("experiment", id, action).reason(detail).source(EventSource::Manual));
+}
+
+// Jalankan rencana: terapkan tiap langkah, kumpulkan telemetry loop utama, lalu kembalikan
+// setpoint dan mode seperti sebelum eksperimen. Ctrl-C menghentikan lebih awal (laporan parsial).
+pub async fn run(state: Arc<AppState>, plan: Plan, zone: String) -> Report {
+    let started_ms = crate::now_ns() / 1_000_000;
+    let id = format!("{}-{}", plan.name, started_ms / 1000);
+    *ACTIVE.lock().unwrap() = Some(id.clone());
+    record(&state, &id, "experiment_start", &zone, format!("{} steps, {} s", plan.steps.len(), plan.duration().as_secs()));
+    info!("🧪 Experiment {} started on zone {}: {} steps, {} min", id, zone, plan.steps.len(), plan.duration().as_secs() / 60);
+
+    let previous_setpoint = state.setpoint_override(&zone);
+    let (pump, fan) = (ActuatorId::new(&zone, Actuator::Pump), ActuatorId::new(&zone, Actuator::ExhaustFan));
+    let previous_modes: HashMap<ActuatorId, ActuatorMode> = [&pump, &fan].into_iter().map(|a| (a.clone(), state.mode(a))).collect();
+    let prefix = if state.zones.len() > 1 { format!("{}_", zone) } else { String::new() };
+    let value = |t: &Telemetry, key: &str| t.values.get(&format!("{prefix}{key}")).copied();
+
+    let mut telemetry = state.subscribe_telemetry();
+    let mut steps = Vec::new();
+    let mut overall = Summary { name: "overall".to_string(), ..Default::default() };
+    let mut completed = true;
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    'steps: for (i, step) in plan.steps.iter().enumerate() {
+        let label = step.label(i);
+        state.set_setpoint_override(&zone, step.setpoint.or(previous_setpoint));
+        for (actuator, mode) in [(&pump, step.pump), (&fan, step.fan)] {
+            state.set_mode(actuator, mode.unwrap_or(previous_modes[actuator]));
+        }
+        let setpoint = step.setpoint.map(|sp| format!("{:.2}", sp)).unwrap_or_else(|| "unchanged".to_string());
+        let mode = |m: Option<ActuatorMode>| m.map(ActuatorMode::as_str).unwrap_or("unchanged");
+        let detail = format!("{} setpoint={} pump={} fan={} for {}s", label, setpoint, mode(step.pump), mode(step.fan), step.duration.as_secs());
+        info!("🧪 [{}/{}] {}", i + 1, plan.steps.len(), detail);
+        record(&state, &id, "experiment_step", &zone, detail);
+
+        let mut summary =
+            Summary { name: label, setpoint: step.setpoint, pump: step.pump, fan: step.fan, ..Default::default() };
+        let step_start = Instant::now();
+        let deadline = step_start + step.duration;
+        let mut last_ms: Option<u64> = None;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => break,
+                _ = &mut ctrl_c => {
+                    warn!("🧪 Experiment {} interrupted during {}", id, summary.name);
+                    completed = false;
+                    summary.duration_s = step_start.elapsed().as_secs_f64();
+                    overall.duration_s += summary.duration_s;
+                    steps.push(summary);
+                    break 'steps;
+                }
+                received = telemetry.recv() => match received {
+                    Ok(t) => {
+                        // Siklus pertama langkah mewakili satu interval loop (10 detik)
+                        let dt = last_ms.map_or(10.0, |last| (t.timestamp_ms.saturating_sub(last) as f64 / 1000.0).min(30.0));
+                        last_ms = Some(t.timestamp_ms);
+                        let temperature = value(&t, "sht20_temperature");
+                        let humidity = value(&t, "sht20_humidity");
+                        let setpoint = step.setpoint.or_else(|| value(&t, "setpoint_temperature"));
+                        summary.add(temperature, humidity, setpoint, plan.band, dt);
+                        overall.add(temperature, humidity, setpoint, plan.band, dt);
+                    }
+                    Err(RecvError::Lagged(missed)) => warn!("🧪 Experiment missed {} telemetry cycle(s)", missed),
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+        summary.duration_s = step.duration.as_secs_f64();
+        overall.duration_s += summary.duration_s;
+        steps.push(summary);
+    }
+
+    state.set_setpoint_override(&zone, previous_setpoint);
+    for (actuator, mode) in previous_modes {
+        state.set_mode(&actuator, mode);
+    }
+    *ACTIVE.lock().unwrap() = None;
+    record(&state, &id, if completed { "experiment_finish" } else { "experiment_abort" }, &zone, "setpoint and modes restored".to_string());
+
+    Report { id, name: plan.name, zone, band: plan.band, started_ms, finished_ms: crate::now_ns() / 1_000_000, completed, steps, overall }
+}
@@ -0,0 +1,193 @@
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::series::{Series, SeriesBuilder, SeriesQuery};
+use crate::vpd;
+
+// Export data sensor ke Excel (.xlsx) untuk supervisor lab: satu sheet per hari lokal berisi
+// rata-rata per `every` untuk semua field plus metrik turunan (VPD, titik embun), dan sheet
+// Overview dengan min/rata-rata/maks harian. Lewat `backend export` atau GET /api/export.xlsx.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    // Batas hari dan jam di workbook (Excel tidak menyimpan zona waktu)
+    pub utc_offset: String,
+    // Kosong = field [last_data] (bawaan + extra_fields)
+    pub fields: Vec<String>,
+    // Batas baris (range / every) per export
+    pub max_rows: usize,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self { utc_offset: "+07:00".to_string(), fields: Vec::new(), max_rows: 200_000 }
+    }
+}
+
+// `backend export`: workbook dari InfluxDB ke file
+//   backend export --out data.xlsx [--range 7d] [--every 1m] [--fields temperature,humidity] [--zone main]
+pub const USAGE: &str = "usage: backend export --out <file.xlsx> [--range 7d] [--every 1m] [--fields <field,...>] [--zone <zone>]";
+
+const DEFAULT_RANGE: &str = "7d";
+const DEFAULT_EVERY: &str = "1m";
+
+// Kolom turunan dari temperature + humidity (hanya jika keduanya diekspor)
+type Derive = fn(f64, f64) -> f64;
+const DERIVED: [(&str, Derive); 2] = [("vpd_kpa", vpd::vpd_kpa), ("dew_point", vpd::dew_point)];
+
+#[derive(Debug, Clone, Default)]
+pub struct ExportArgs {
+    pub out: String,
+    pub range: Option<String>,
+    pub every: Option<String>,
+    pub fields: Option<String>,
+    pub zone: Option<String>,
+}
+
+impl ExportArgs {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut parsed = ExportArgs::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let value = iter.next().ok_or_else(|| anyhow!("{} needs a value\n{}", arg, USAGE))?.clone();
+            match arg.as_str() {
+                "--out" => parsed.out = value,
+                "--range" => parsed.range = Some(value),
+                "--every" => parsed.every = Some(value),
+                "--fields" => parsed.fields = Some(value),
+                "--zone" => parsed.zone = Some(value),
+                other => return Err(anyhow!("unknown option {}\n{}", other, USAGE)),
+            }
+        }
+        if parsed.out.is_empty() {
+            return Err(anyhow!("--out is required\n{}", USAGE));
+        }
+        Ok(parsed)
+    }
+}
+
+impl ExportConfig {
+    // fields kosong diisi field [last_data]
+    pub fn resolved(&self, last_data: &crate::config::LastDataConfig) -> Self {
+        let mut config = self.clone();
+        if config.fields.is_empty() {
+            config.fields = last_data.fields();
+        }
+        config
+    }
+
+    // Query rata-rata per window; default range 7d, every 1m, field dari config
+    pub fn query(&self, fields: Option<&str>, range: Option<&str>, every: Option<&str>, zone: Option<&str>) -> Result<SeriesQuery> {
+        let default_fields = self.fields.join(",");
+        let fields = fields.or(Some(default_fields.as_str()).filter(|f| !f.is_empty()));
+        SeriesQuery::parse_with_limit(fields, Some(range.unwrap_or(DEFAULT_RANGE)), Some(every.unwrap_or(DEFAULT_EVERY)), zone, self.max_rows as f64)
+    }
+
+    fn offset_secs(&self) -> i64 {
+        crate::import::parse_offset(&self.utc_offset).unwrap_or_default()
+    }
+}
+
+pub async fn fetch(http: &Client, query: &SeriesQuery, location: Option<&str>) -> Result<Series> {
+    let measurement = crate::bridge::SENSOR_MEAS;
+    let tenant = query.zone.as_deref().and_then(crate::tenants::for_zone);
+    let bucket = crate::bridge::bucket_for(measurement);
+    let flux = query.flux(tenant.map_or(bucket, |t| t.bucket_or(bucket)), measurement, &crate::bridge::location_filter(location));
+    let mut builder = SeriesBuilder::new(&query.fields);
+    crate::bridge::post_influx_rows(http, tenant, &flux, |row| builder.push(row)).await?;
+    Ok(builder.finish())
+}
+
+// Field dalam urutan query lalu kolom turunan, sesuai urutan kolom workbook
+fn columns(query: &SeriesQuery, series: &Series) -> Vec<(String, Vec<Option<f64>>)> {
+    let mut columns: Vec<(String, Vec<Option<f64>>)> =
+        query.fields.iter().filter_map(|name| Some((name.clone(), series.values.get(name)?.clone()))).collect();
+    if let (Some(temperature), Some(humidity)) = (series.values.get("temperature"), series.values.get("humidity")) {
+        for (name, derive) in DERIVED {
+            let values = temperature
+                .iter()
+                .zip(humidity)
+                .map(|(t, h)| Some((derive((*t)?, (*h)?) * 1000.0).round() / 1000.0))
+                .collect();
+            columns.push((name.to_string(), values));
+        }
+    }
+    columns
+}
+
+// (min, rata-rata, maks) dari baris `rows`; None jika kolom kosong di hari itu
+fn day_stats(values: &[Option<f64>], rows: &[usize]) -> Option<(f64, f64, f64)> {
+    let present: Vec<f64> = rows.iter().filter_map(|i| values[*i]).collect();
+    if present.is_empty() {
+        return None;
+    }
+    let min = present.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = present.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    Some((min, present.iter().sum::<f64>() / present.len() as f64, max))
+}
+
+// Workbook .xlsx: sheet Overview lalu satu sheet per hari (nama YYYY-MM-DD, waktu lokal)
+pub fn workbook(query: &SeriesQuery, series: &Series, config: &ExportConfig) -> Result<Vec<u8>> {
+    let offset = config.offset_secs();
+    let columns = columns(query, series);
+    let mut days: BTreeMap<i64, Vec<usize>> = BTreeMap::new();
+    for (i, ts) in series.time.iter().enumerate() {
+        days.entry((ts.div_euclid(1000) + offset).div_euclid(86_400)).or_default().push(i);
+    }
+
+    let mut workbook = Workbook::new();
+    let bold = Format::new().set_bold();
+    let number = Format::new().set_num_format("0.00");
+    let time = Format::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+
+    let overview = workbook.add_worksheet().set_name("Overview")?;
+    overview.write_string_with_format(0, 0, "Date", &bold)?;
+    overview.write_string_with_format(0, 1, "Samples", &bold)?;
+    for (c, (name, _)) in columns.iter().enumerate() {
+        for (k, stat) in ["min", "mean", "max"].iter().enumerate() {
+            overview.write_string_with_format(0, (2 + c * 3 + k) as u16, format!("{name} {stat}"), &bold)?;
+        }
+    }
+    for (r, (day, rows)) in days.iter().enumerate() {
+        let row = r as u32 + 1;
+        overview.write_string(row, 0, crate::report::date(*day))?;
+        overview.write_number(row, 1, rows.len() as f64)?;
+        for (c, (_, values)) in columns.iter().enumerate() {
+            if let Some((min, mean, max)) = day_stats(values, rows) {
+                for (k, value) in [min, mean, max].into_iter().enumerate() {
+                    overview.write_number_with_format(row, (2 + c * 3 + k) as u16, value, &number)?;
+                }
+            }
+        }
+    }
+    if days.is_empty() {
+        overview.write_string(1, 0, "no data in the requested range")?;
+    }
+    overview.set_column_width(0, 12)?;
+    overview.set_freeze_panes(1, 1)?;
+
+    for (day, rows) in &days {
+        let sheet = workbook.add_worksheet().set_name(crate::report::date(*day))?;
+        sheet.write_string_with_format(0, 0, "Time", &bold)?;
+        for (c, (name, _)) in columns.iter().enumerate() {
+            sheet.write_string_with_format(0, c as u16 + 1, name, &bold)?;
+            sheet.set_column_width(c as u16 + 1, (name.len() + 2).max(10) as f64)?;
+        }
+        for (r, i) in rows.iter().enumerate() {
+            let row = r as u32 + 1;
+            let local = ExcelDateTime::from_timestamp(series.time[*i].div_euclid(1000) + offset)?;
+            sheet.write_datetime_with_format(row, 0, local, &time)?;
+            for (c, (_, values)) in columns.iter().enumerate() {
+                if let Some(value) = values[*i] {
+                    sheet.write_number(row, c as u16 + 1, value)?;
+                }
+            }
+        }
+        sheet.set_column_width(0, 20)?;
+        sheet.set_freeze_panes(1, 1)?;
+    }
+    Ok(workbook.save_to_buffer()?)
+}
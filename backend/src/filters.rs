@@ -0,0 +1,141 @@
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+
+// Satu tahap filter dari konfigurasi, dijalankan berurutan
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FilterSpec {
+    MovingAverage { window: usize },
+    Ema { alpha: f64 },
+    Median { window: usize },
+}
+
+enum Stage {
+    MovingAverage { window: usize, values: VecDeque<f64> },
+    Ema { alpha: f64, value: Option<f64> },
+    Median { window: usize, values: VecDeque<f64> },
+}
+
+impl Stage {
+    fn new(spec: &FilterSpec) -> Self {
+        match *spec {
+            FilterSpec::MovingAverage { window } => Stage::MovingAverage { window: window.max(1), values: VecDeque::new() },
+            FilterSpec::Ema { alpha } => Stage::Ema { alpha: alpha.clamp(0.0, 1.0), value: None },
+            FilterSpec::Median { window } => Stage::Median { window: window.max(1), values: VecDeque::new() },
+        }
+    }
+
+    fn apply(&mut self, x: f64) -> f64 {
+        match self {
+            Stage::MovingAverage { window, values } => {
+                push_window(values, *window, x);
+                values.iter().sum::<f64>() / values.len() as f64
+            }
+            Stage::Ema { alpha, value } => {
+                let next = match value {
+                    Some(prev) => *alpha * x + (1.0 - *alpha) * *prev,
+                    None => x,
+                };
+                *value = Some(next);
+                next
+            }
+            Stage::Median { window, values } => {
+                push_window(values, *window, x);
+                let mut sorted: Vec<f64> = values.iter().copied().collect();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                let mid = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) {
+                    (sorted[mid - 1] + sorted[mid]) / 2.0
+                } else {
+                    sorted[mid]
+                }
+            }
+        }
+    }
+}
+
+fn push_window(values: &mut VecDeque<f64>, window: usize, x: f64) {
+    values.push_back(x);
+    while values.len() > window {
+        values.pop_front();
+    }
+}
+
+pub struct FilterChain {
+    stages: Vec<Stage>,
+}
+
+impl FilterChain {
+    pub fn new(specs: &[FilterSpec]) -> Self {
+        Self { stages: specs.iter().map(Stage::new).collect() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    pub fn apply(&mut self, x: f64) -> f64 {
+        self.stages.iter_mut().fold(x, |value, stage| stage.apply(value))
+    }
+}
+
+// Satu rantai filter per field (temperature, humidity)
+pub fn build_chains(config: &HashMap<String, Vec<FilterSpec>>) -> HashMap<String, FilterChain> {
+    config
+        .iter()
+        .map(|(field, specs)| (field.clone(), FilterChain::new(specs)))
+        .filter(|(_, chain)| !chain.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(specs: &[FilterSpec], input: &[f64]) -> Vec<f64> {
+        let mut chain = FilterChain::new(specs);
+        input.iter().map(|x| chain.apply(*x)).collect()
+    }
+
+    #[test]
+    fn moving_average_over_window() {
+        let out = run(&[FilterSpec::MovingAverage { window: 3 }], &[3.0, 6.0, 9.0, 12.0, 0.0]);
+        assert_eq!(out, vec![3.0, 4.5, 6.0, 9.0, 7.0]);
+    }
+
+    #[test]
+    fn ema_starts_at_first_sample() {
+        let out = run(&[FilterSpec::Ema { alpha: 0.5 }], &[10.0, 20.0, 20.0, 0.0]);
+        assert_eq!(out, vec![10.0, 15.0, 17.5, 8.75]);
+
+        // alpha di luar [0, 1] di-clamp
+        assert_eq!(run(&[FilterSpec::Ema { alpha: 2.0 }], &[1.0, 5.0]), vec![1.0, 5.0]);
+        assert_eq!(run(&[FilterSpec::Ema { alpha: -1.0 }], &[1.0, 5.0]), vec![1.0, 1.0]);
+    }
+
+    #[test]
+    fn median_removes_spikes() {
+        let out = run(&[FilterSpec::Median { window: 3 }], &[20.0, 21.0, 99.0, 22.0, 23.0]);
+        assert_eq!(out, vec![20.0, 20.5, 21.0, 22.0, 23.0]);
+    }
+
+    #[test]
+    fn stages_run_in_order() {
+        let specs = [FilterSpec::Median { window: 3 }, FilterSpec::MovingAverage { window: 2 }];
+        let out = run(&specs, &[20.0, 21.0, 99.0, 22.0]);
+        assert_eq!(out, vec![20.0, 20.25, 20.75, 21.5]);
+    }
+
+    #[test]
+    fn zero_window_passes_through_and_empty_chains_are_dropped() {
+        assert_eq!(run(&[FilterSpec::MovingAverage { window: 0 }], &[1.0, 4.0]), vec![1.0, 4.0]);
+
+        let config = HashMap::from([
+            ("temperature".to_string(), vec![FilterSpec::Ema { alpha: 0.3 }]),
+            ("humidity".to_string(), Vec::new()),
+        ]);
+        let chains = build_chains(&config);
+        assert_eq!(chains.len(), 1);
+        assert!(chains.contains_key("temperature"));
+    }
+}
@@ -0,0 +1,213 @@
+// Parser CSV hasil query Flux yang inkremental: body HTTP diumpankan per chunk dan setiap baris
+// diproses begitu lengkap, jadi respons besar (mis. seminggu data untuk /api/series) tidak perlu
+// dimuat utuh ke memori gateway. Kolom dibaca sebagai slice dari buffer baris (tanpa alokasi per baris).
+//
+// Format: header di baris pertama setiap tabel, tabel dipisah baris kosong, anotasi '#' dilewati.
+// Nilai ber-quote dikembalikan tanpa tanda kutip luar ("" di dalamnya tidak di-unescape).
+
+use tokio_stream::{Stream, StreamExt};
+
+#[derive(Debug, Default)]
+pub struct FluxCsv {
+    // Sisa chunk sebelumnya yang belum membentuk satu baris utuh
+    pending: Vec<u8>,
+    table: Table,
+}
+
+#[derive(Debug, Default)]
+struct Table {
+    header: Vec<String>,
+    expect_header: bool,
+    // (awal, akhir) setiap kolom baris saat ini; dipakai ulang antar baris
+    spans: Vec<(usize, usize)>,
+}
+
+// Satu baris data; kolom dicari lewat nama header tabelnya
+pub struct Row<'a> {
+    line: &'a str,
+    spans: &'a [(usize, usize)],
+    header: &'a [String],
+}
+
+impl<'a> Row<'a> {
+    pub fn get(&self, column: &str) -> Option<&'a str> {
+        let index = self.header.iter().position(|name| name == column)?;
+        let (start, end) = *self.spans.get(index)?;
+        Some(self.line[start..end].trim())
+    }
+
+    pub fn number(&self, column: &str) -> Option<f64> {
+        self.get(column)?.parse().ok()
+    }
+
+    pub fn to_record(&self) -> Record {
+        Record(self.header.iter().zip(self.spans).map(|(name, &(start, end))| (name.clone(), self.line[start..end].trim().to_string())).collect())
+    }
+}
+
+// Baris yang disalin keluar dari buffer parser; untuk hasil query kecil yang disimpan (dan di-cache)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Record(Vec<(String, String)>);
+
+impl Record {
+    pub fn get(&self, column: &str) -> Option<&str> {
+        self.0.iter().find(|(name, _)| name == column).map(|(_, value)| value.as_str())
+    }
+
+    pub fn number(&self, column: &str) -> Option<f64> {
+        self.get(column)?.parse().ok()
+    }
+}
+
+// Body yang datang per chunk (reqwest `bytes_stream()`); berhenti di error pertama dari stream
+pub async fn stream<B, E>(body: impl Stream<Item = Result<B, E>>, mut on_row: impl FnMut(&Row)) -> Result<(), E>
+where
+    B: AsRef<[u8]>,
+{
+    let mut body = std::pin::pin!(body);
+    let mut parser = FluxCsv::new();
+    while let Some(chunk) = body.next().await {
+        parser.feed(chunk?.as_ref(), &mut on_row);
+    }
+    parser.finish(&mut on_row);
+    Ok(())
+}
+
+// Semua baris body sebagai Record
+pub async fn collect<B, E>(body: impl Stream<Item = Result<B, E>>) -> Result<Vec<Record>, E>
+where
+    B: AsRef<[u8]>,
+{
+    let mut records = Vec::new();
+    stream(body, |row| records.push(row.to_record())).await?;
+    Ok(records)
+}
+
+// CSV yang sudah ada di memori (file, test) sebagai Record
+pub fn records(csv: &str) -> Vec<Record> {
+    let mut records = Vec::new();
+    FluxCsv::parse(csv, |row| records.push(row.to_record()));
+    records
+}
+
+impl FluxCsv {
+    pub fn new() -> Self {
+        Self { pending: Vec::new(), table: Table { expect_header: true, ..Default::default() } }
+    }
+
+    // Umpankan satu chunk body; `on_row` dipanggil untuk setiap baris data yang sudah lengkap
+    pub fn feed(&mut self, chunk: &[u8], on_row: &mut impl FnMut(&Row)) {
+        self.pending.extend_from_slice(chunk);
+        let mut consumed = 0;
+        while let Some(newline) = self.pending[consumed..].iter().position(|b| *b == b'\n') {
+            let end = consumed + newline;
+            self.table.line(&self.pending[consumed..end], on_row);
+            consumed = end + 1;
+        }
+        self.pending.drain(..consumed);
+    }
+
+    // Akhir body: baris terakhir tanpa newline
+    pub fn finish(&mut self, on_row: &mut impl FnMut(&Row)) {
+        if !self.pending.is_empty() {
+            self.table.line(&self.pending, on_row);
+            self.pending.clear();
+        }
+    }
+
+    // Parse seluruh CSV yang sudah ada di memori
+    pub fn parse(csv: &str, mut on_row: impl FnMut(&Row)) {
+        let mut table = Self::new().table;
+        for line in csv.split('\n') {
+            table.line(line.as_bytes(), &mut on_row);
+        }
+    }
+}
+
+impl Table {
+    fn line(&mut self, bytes: &[u8], on_row: &mut impl FnMut(&Row)) {
+        let Ok(line) = std::str::from_utf8(bytes) else { return };
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.trim().is_empty() {
+            self.expect_header = true;
+            return;
+        }
+        if line.starts_with('#') {
+            return;
+        }
+        split(line, &mut self.spans);
+        if self.expect_header {
+            self.header = self.spans.iter().map(|&(start, end)| line[start..end].trim().to_string()).collect();
+            self.expect_header = false;
+            return;
+        }
+        on_row(&Row { line, spans: &self.spans, header: &self.header });
+    }
+}
+
+// Batas kolom dipisah koma; koma di dalam "..." bukan pemisah
+fn split(line: &str, spans: &mut Vec<(usize, usize)>) {
+    spans.clear();
+    let bytes = line.as_bytes();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, byte) in bytes.iter().enumerate() {
+        match byte {
+            b'"' => quoted = !quoted,
+            b',' if !quoted => {
+                spans.push(unquote(bytes, start, i));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    spans.push(unquote(bytes, start, bytes.len()));
+}
+
+fn unquote(bytes: &[u8], start: usize, end: usize) -> (usize, usize) {
+    if end >= start + 2 && bytes[start] == b'"' && bytes[end - 1] == b'"' {
+        (start + 1, end - 1)
+    } else {
+        (start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "#datatype,string,long,string,double\r\n,result,table,_field,_value\r\n,_result,0,temperature,31.25\r\n,_result,0,humidity,\"58,5\"\r\n\r\n,result,table,device,_value\r\n,_result,1,esp32-1,7\r\n";
+
+    fn fields(records: &[Record]) -> Vec<(Option<&str>, Option<&str>)> {
+        records.iter().map(|r| (r.get("_field").or(r.get("device")), r.get("_value"))).collect()
+    }
+
+    #[tokio::test]
+    async fn chunks_split_mid_line_parse_like_one_body() {
+        let whole = records(CSV);
+        assert_eq!(
+            fields(&whole),
+            [(Some("temperature"), Some("31.25")), (Some("humidity"), Some("58,5")), (Some("esp32-1"), Some("7"))]
+        );
+        // Setiap titik potong, termasuk di tengah nilai, di dalam quote dan di antara \r dan \n
+        for size in 1..=CSV.len() {
+            let chunks: Vec<Result<&[u8], std::convert::Infallible>> = CSV.as_bytes().chunks(size).map(Ok).collect();
+            let streamed = collect(tokio_stream::iter(chunks)).await.unwrap();
+            assert_eq!(streamed, whole, "chunk size {size}");
+        }
+    }
+
+    #[tokio::test]
+    async fn last_line_without_newline_is_kept() {
+        let chunks: Vec<Result<&[u8], std::convert::Infallible>> = vec![Ok(b",_value\n,1"), Ok(b"2.5")];
+        let records = collect(tokio_stream::iter(chunks)).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].number("_value"), Some(12.5));
+    }
+
+    #[tokio::test]
+    async fn stream_error_stops_parsing() {
+        let chunks: Vec<Result<&[u8], &str>> = vec![Ok(b",_value\n1\n"), Err("connection reset")];
+        assert_eq!(collect(tokio_stream::iter(chunks)).await, Err("connection reset"));
+    }
+}
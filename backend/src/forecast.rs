@@ -0,0 +1,113 @@
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::alarms::Severity;
+
+// Prediksi suhu jangka pendek (regresi linear atas jendela terakhir), supaya
+// alarm bisa muncul sebelum ambang terlewati, bukan sesudahnya
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ForecastConfig {
+    pub enabled: bool,
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub window: Duration,
+    // Horizon prediksi dalam menit, misal [10, 30]
+    pub horizons_minutes: Vec<u64>,
+    // Minimal titik di jendela sebelum memprediksi
+    pub min_points: usize,
+    // Alarm forecast_<zone> jika prediksi horizon mana pun melewati batas ini
+    pub alarm_above: Option<f64>,
+    pub alarm_below: Option<f64>,
+    pub severity: Severity,
+}
+
+impl Default for ForecastConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window: Duration::from_secs(30 * 60),
+            horizons_minutes: vec![10, 30],
+            min_points: 12,
+            alarm_above: None,
+            alarm_below: None,
+            severity: Severity::Warning,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Prediction {
+    pub minutes: u64,
+    pub value: f64,
+}
+
+// Batas yang diprediksi terlewati: (horizon menit, nilai prediksi, batas)
+#[derive(Debug, Clone, Copy)]
+pub struct Breach {
+    pub minutes: u64,
+    pub value: f64,
+    pub limit: f64,
+}
+
+pub struct Forecaster {
+    config: ForecastConfig,
+    points: VecDeque<(Instant, f64)>,
+}
+
+impl Forecaster {
+    pub fn new(config: ForecastConfig) -> Self {
+        Self { config, points: VecDeque::new() }
+    }
+
+    pub fn observe(&mut self, now: Instant, value: f64) {
+        self.points.push_back((now, value));
+        while self.points.front().is_some_and(|(t, _)| now.saturating_duration_since(*t) > self.config.window) {
+            self.points.pop_front();
+        }
+    }
+
+    // Kemiringan (per detik) dan intercept pada waktu titik terbaru
+    fn fit(&self) -> Option<(f64, f64)> {
+        let (last, _) = *self.points.back()?;
+        if self.points.len() < self.config.min_points.max(2) {
+            return None;
+        }
+        let xs: Vec<(f64, f64)> =
+            self.points.iter().map(|(t, v)| (-(last.saturating_duration_since(*t).as_secs_f64()), *v)).collect();
+        let n = xs.len() as f64;
+        let mean_x = xs.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = xs.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let sxx: f64 = xs.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        if sxx <= f64::EPSILON {
+            return None;
+        }
+        let sxy: f64 = xs.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        let slope = sxy / sxx;
+        Some((slope, mean_y - slope * mean_x))
+    }
+
+    pub fn slope_per_hour(&self) -> Option<f64> {
+        self.fit().map(|(slope, _)| slope * 3600.0)
+    }
+
+    pub fn predict(&self) -> Vec<Prediction> {
+        let Some((slope, intercept)) = self.fit() else { return Vec::new() };
+        self.config
+            .horizons_minutes
+            .iter()
+            .map(|&minutes| Prediction { minutes, value: intercept + slope * (minutes * 60) as f64 })
+            .collect()
+    }
+
+    // Horizon terdekat yang melewati batas; nilai sekarang yang sudah lewat batas bukan urusan forecast
+    pub fn breach(&self, current: f64, predictions: &[Prediction]) -> Option<Breach> {
+        let mut sorted = predictions.to_vec();
+        sorted.sort_by_key(|p| p.minutes);
+        sorted.into_iter().find_map(|p| {
+            let above = self.config.alarm_above.filter(|limit| current < *limit && p.value >= *limit);
+            let below = self.config.alarm_below.filter(|limit| current > *limit && p.value <= *limit);
+            above.or(below).map(|limit| Breach { minutes: p.minutes, value: p.value, limit })
+        })
+    }
+}
@@ -0,0 +1,115 @@
+use serde::Deserialize;
+
+// Strategi penggabungan beberapa sensor dalam satu zone
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FusionStrategy {
+    #[default]
+    Mean,
+    Median,
+    Min,
+    Max,
+    Weighted,
+}
+
+impl FusionStrategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FusionStrategy::Mean => "mean",
+            FusionStrategy::Median => "median",
+            FusionStrategy::Min => "min",
+            FusionStrategy::Max => "max",
+            FusionStrategy::Weighted => "weighted",
+        }
+    }
+
+    // readings: (nilai, bobot). Bobot hanya dipakai oleh strategi weighted.
+    pub fn fuse(self, readings: &[(f64, f64)]) -> Option<f64> {
+        let values: Vec<f64> = readings.iter().map(|(v, _)| *v).filter(|v| v.is_finite()).collect();
+        if values.is_empty() {
+            return None;
+        }
+        let n = values.len() as f64;
+        match self {
+            FusionStrategy::Mean => Some(values.iter().sum::<f64>() / n),
+            FusionStrategy::Median => {
+                let mut sorted = values;
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                let mid = sorted.len() / 2;
+                Some(if sorted.len().is_multiple_of(2) { (sorted[mid - 1] + sorted[mid]) / 2.0 } else { sorted[mid] })
+            }
+            FusionStrategy::Min => values.into_iter().reduce(f64::min),
+            FusionStrategy::Max => values.into_iter().reduce(f64::max),
+            FusionStrategy::Weighted => {
+                let (sum, weights) = readings
+                    .iter()
+                    .filter(|(v, w)| v.is_finite() && *w > 0.0)
+                    .fold((0.0, 0.0), |(s, ws), (v, w)| (s + v * w, ws + w));
+                (weights > 0.0).then(|| sum / weights)
+            }
+        }
+    }
+}
+
+// Sensor stale tidak ikut fusion selama masih ada yang segar; jika semuanya stale,
+// semua tetap dipakai supaya zone tidak kehilangan nilai kontrol
+pub fn fresh_only<T>(readings: Vec<(T, bool)>) -> Vec<T> {
+    let any_fresh = readings.iter().any(|(_, fresh)| *fresh);
+    readings.into_iter().filter(|(_, fresh)| *fresh || !any_fresh).map(|(reading, _)| reading).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unweighted(values: &[f64]) -> Vec<(f64, f64)> {
+        values.iter().map(|v| (*v, 1.0)).collect()
+    }
+
+    #[test]
+    fn mean_and_median() {
+        let readings = unweighted(&[24.0, 25.0, 30.0]);
+        assert_eq!(FusionStrategy::Mean.fuse(&readings), Some(79.0 / 3.0));
+        assert_eq!(FusionStrategy::Median.fuse(&readings), Some(25.0));
+        assert_eq!(FusionStrategy::Median.fuse(&unweighted(&[30.0, 24.0, 25.0, 26.0])), Some(25.5));
+        assert_eq!(FusionStrategy::Min.fuse(&readings), Some(24.0));
+        assert_eq!(FusionStrategy::Max.fuse(&readings), Some(30.0));
+    }
+
+    // Median tahan terhadap satu sensor rusak, mean tidak
+    #[test]
+    fn median_ignores_single_outlier() {
+        let readings = unweighted(&[25.0, 25.2, 85.0]);
+        assert_eq!(FusionStrategy::Median.fuse(&readings), Some(25.2));
+        assert!(FusionStrategy::Mean.fuse(&readings).unwrap() > 40.0);
+    }
+
+    // Bobot memberi prioritas pada sensor referensi; bobot 0 mengeluarkan sensor
+    #[test]
+    fn weighted_prefers_heavier_sensors() {
+        let readings = [(24.0, 3.0), (28.0, 1.0), (90.0, 0.0)];
+        assert_eq!(FusionStrategy::Weighted.fuse(&readings), Some(25.0));
+        assert_eq!(FusionStrategy::Weighted.fuse(&[(24.0, 0.0), (28.0, -1.0)]), None);
+    }
+
+    #[test]
+    fn missing_readings_are_skipped() {
+        assert_eq!(FusionStrategy::Mean.fuse(&[]), None);
+        let readings = unweighted(&[f64::NAN, 26.0, f64::INFINITY, 24.0]);
+        for strategy in [FusionStrategy::Mean, FusionStrategy::Median, FusionStrategy::Weighted] {
+            assert_eq!(strategy.fuse(&readings), Some(25.0), "{}", strategy.as_str());
+        }
+        assert_eq!(FusionStrategy::Median.fuse(&unweighted(&[f64::NAN])), None);
+    }
+
+    #[test]
+    fn stale_sensors_are_dropped_while_one_is_fresh() {
+        let fresh = fresh_only(vec![("a", true), ("b", false), ("c", true)]);
+        assert_eq!(fresh, ["a", "c"]);
+        assert_eq!(fresh_only(vec![("a", false), ("b", false)]), ["a", "b"]);
+        assert!(fresh_only::<&str>(Vec::new()).is_empty());
+
+        let readings = fresh_only(vec![((25.0, 1.0), true), ((40.0, 1.0), false)]);
+        assert_eq!(FusionStrategy::Mean.fuse(&readings), Some(25.0));
+    }
+}
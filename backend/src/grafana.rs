@@ -0,0 +1,83 @@
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::events::Event;
+use crate::secrets::Secret;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationMode {
+    // POST ke Grafana HTTP API /api/annotations
+    #[default]
+    Api,
+    // Tulis ke measurement "annotations" yang dibaca Grafana dari InfluxDB
+    Measurement,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GrafanaConfig {
+    pub enabled: bool,
+    pub mode: AnnotationMode,
+    pub url: String,
+    // Bisa juga lewat env GRAFANA_API_KEY / GRAFANA_API_KEY_FILE
+    pub api_key: Secret,
+    pub dashboard_uid: Option<String>,
+    // Jenis event yang dianotasi
+    pub kinds: Vec<String>,
+}
+
+impl Default for GrafanaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: AnnotationMode::Api,
+            url: "http://localhost:3000".to_string(),
+            api_key: Secret::default(),
+            dashboard_uid: None,
+            kinds: vec!["actuator".to_string(), "alarm".to_string()],
+        }
+    }
+}
+
+impl GrafanaConfig {
+    pub fn wants(&self, event: &Event) -> bool {
+        self.enabled && self.kinds.iter().any(|k| k == event.kind)
+    }
+}
+
+pub fn annotation_text(event: &Event) -> String {
+    let change = match &event.old {
+        Some(old) => format!("{} → {}", old, event.new),
+        None => event.new.clone(),
+    };
+    if event.reason.is_empty() {
+        format!("{}: {}", event.subject, change)
+    } else {
+        format!("{}: {} ({})", event.subject, change, event.reason)
+    }
+}
+
+pub async fn post_annotation(client: &Client, config: &GrafanaConfig, event: &Event) -> Result<()> {
+    let mut body = json!({
+        "time": event.ts_ns / 1_000_000,
+        "tags": [event.kind, event.subject, event.source.as_str()],
+        "text": annotation_text(event),
+    });
+    if let Some(uid) = &config.dashboard_uid {
+        body["dashboardUID"] = json!(uid);
+    }
+
+    let response = client
+        .post(format!("{}/api/annotations", config.url.trim_end_matches('/')))
+        .header("Authorization", config.api_key.header("Bearer"))
+        .json(&body)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Grafana annotation failed: {}", response.status()));
+    }
+    Ok(())
+}
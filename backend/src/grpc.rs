@@ -0,0 +1,290 @@
+use anyhow::Result;
+use log::info;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+use crate::audit::AuditEntry;
+use crate::auth::{self, AuthError, Principal, Role};
+use crate::control::{Actuator, ActuatorMode};
+use crate::events::{Event, EventSource};
+use crate::state::{AppState, Telemetry};
+
+pub mod pb {
+    tonic::include_proto!("dcs.v1");
+}
+
+use pb::dcs_server::{Dcs, DcsServer};
+
+pub async fn serve(listen: String, state: Arc<AppState>) -> Result<()> {
+    let addr = listen.parse()?;
+    info!("gRPC server listening on {}", listen);
+    tonic::transport::Server::builder()
+        .add_service(DcsServer::new(DcsService { state }))
+        .serve(addr)
+        .await?;
+    Ok(())
+}
+
+struct DcsService {
+    state: Arc<AppState>,
+}
+
+impl From<Telemetry> for pb::Telemetry {
+    fn from(t: Telemetry) -> Self {
+        Self {
+            timestamp_ms: t.timestamp_ms,
+            values: t.values.into_iter().collect(),
+            data_quality: t.data_quality,
+            active_alarms: t.active_alarms,
+        }
+    }
+}
+
+fn to_pb_mode(mode: ActuatorMode) -> pb::ActuatorMode {
+    match mode {
+        ActuatorMode::Auto => pb::ActuatorMode::Auto,
+        ActuatorMode::On => pb::ActuatorMode::On,
+        ActuatorMode::Off => pb::ActuatorMode::Off,
+    }
+}
+
+type TelemetryStream = Pin<Box<dyn Stream<Item = Result<pb::Telemetry, Status>> + Send>>;
+
+impl DcsService {
+    fn audit(&self, origin: Option<SocketAddr>, principal: &Principal, action: &str, target: &str, detail: String) {
+        let origin = origin.map(|a| a.ip().to_string()).unwrap_or_default();
+        self.state
+            .audit
+            .record(AuditEntry::new(principal.name.clone(), "grpc", origin, action, target, detail));
+    }
+
+    // Key dibaca dari metadata "authorization: Bearer <key>" atau "x-api-key"
+    fn authorize<T>(&self, request: &Request<T>, required: Role) -> Result<Principal, Status> {
+        let meta = request.metadata();
+        let get = |name| meta.get(name).and_then(|v| v.to_str().ok());
+        let key = auth::extract_key(get("authorization"), get("x-api-key"));
+        self.state.auth.authorize(key, required).map_err(|e| match e {
+            AuthError::Unauthenticated => Status::unauthenticated(e.to_string()),
+            AuthError::Forbidden => Status::permission_denied(e.to_string()),
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl Dcs for DcsService {
+    async fn get_latest(&self, request: Request<pb::GetLatestRequest>) -> Result<Response<pb::Telemetry>, Status> {
+        self.authorize(&request, Role::Viewer)?;
+        let latest = self.state.telemetry.lock().unwrap().clone();
+        latest
+            .map(|t| Response::new(t.into()))
+            .ok_or_else(|| Status::unavailable("no telemetry yet"))
+    }
+
+    type StreamTelemetryStream = TelemetryStream;
+
+    async fn stream_telemetry(
+        &self,
+        request: Request<pb::StreamTelemetryRequest>,
+    ) -> Result<Response<Self::StreamTelemetryStream>, Status> {
+        self.authorize(&request, Role::Viewer)?;
+        // Subscriber yang tertinggal cukup melewatkan snapshot lama
+        let stream = BroadcastStream::new(self.state.subscribe_telemetry())
+            .filter_map(|item| item.ok())
+            .map(|t| Ok(t.into()));
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn set_actuator(
+        &self,
+        request: Request<pb::SetActuatorRequest>,
+    ) -> Result<Response<pb::SetActuatorResponse>, Status> {
+        let principal = self.authorize(&request, Role::Operator)?;
+        let origin = request.remote_addr();
+        let req = request.into_inner();
+        let kind = Actuator::parse(&req.actuator)
+            .ok_or_else(|| Status::not_found(format!("unknown actuator '{}'", req.actuator)))?;
+        let mode = match req.mode() {
+            pb::ActuatorMode::Auto => ActuatorMode::Auto,
+            pb::ActuatorMode::On => ActuatorMode::On,
+            pb::ActuatorMode::Off => ActuatorMode::Off,
+            pb::ActuatorMode::Unspecified => return Err(Status::invalid_argument("mode is required")),
+        };
+        let zone = Some(req.zone.as_str()).filter(|z| !z.is_empty());
+        let actuators = self.state.actuator_ids(kind, zone);
+        if actuators.is_empty() {
+            return Err(Status::not_found(format!("no zone owns actuator '{}'", req.actuator)));
+        }
+
+        // Zone kosong: semua zone diubah, previous = mode zone pertama. Relay ESP32 serial harus
+        // membalas ACK dulu; jika tidak, mode zone itu tidak berubah dan RPC gagal
+        let mut previous = None;
+        for actuator in &actuators {
+            let old = self
+                .state
+                .set_actuator_mode(actuator, mode, EventSource::Rpc, "gRPC SetActuator")
+                .await
+                .map_err(Status::unavailable)?;
+            previous.get_or_insert(old);
+            self.audit(
+                origin,
+                &principal,
+                "set_actuator",
+                &actuator.to_string(),
+                format!("{} -> {}", old.as_str(), mode.as_str()),
+            );
+        }
+        let previous = previous.unwrap_or_default();
+
+        Ok(Response::new(pb::SetActuatorResponse {
+            previous: to_pb_mode(previous).into(),
+            mode: to_pb_mode(mode).into(),
+        }))
+    }
+
+    async fn set_setpoint(
+        &self,
+        request: Request<pb::SetSetpointRequest>,
+    ) -> Result<Response<pb::SetSetpointResponse>, Status> {
+        let principal = self.authorize(&request, Role::Operator)?;
+        let origin = request.remote_addr();
+        let req = request.into_inner();
+        if let Some(t) = req.temperature_c {
+            if !t.is_finite() {
+                return Err(Status::invalid_argument("temperature_c must be finite"));
+            }
+        }
+
+        let zone = match (req.zone.as_str(), self.state.zones.as_slice()) {
+            ("", [only]) => only.name.clone(),
+            ("", _) => return Err(Status::invalid_argument("zone is required when several zones are configured")),
+            (name, zones) if zones.iter().any(|z| z.name == name) => name.to_string(),
+            (name, _) => return Err(Status::not_found(format!("unknown zone '{}'", name))),
+        };
+
+        let previous = self.state.set_setpoint_override(&zone, req.temperature_c);
+        let label = |v: Option<f64>| v.map(|t| format!("{:.2}", t)).unwrap_or_else(|| "none".to_string());
+        info!(
+            "🎯 Setpoint override [{}] {} → {} (gRPC, {})",
+            zone,
+            label(previous),
+            label(req.temperature_c),
+            principal.name
+        );
+        self.state.events.record(
+            Event::new("config", format!("setpoint/{}/temperature", zone), label(req.temperature_c))
+                .old(label(previous))
+                .reason("gRPC SetSetpoint")
+                .source(EventSource::Rpc),
+        );
+        self.audit(
+            origin,
+            &principal,
+            "set_setpoint",
+            &zone,
+            format!("{} -> {}", label(previous), label(req.temperature_c)),
+        );
+        Ok(Response::new(pb::SetSetpointResponse { previous_c: previous, temperature_c: req.temperature_c }))
+    }
+
+    async fn set_maintenance_mode(
+        &self,
+        request: Request<pb::SetMaintenanceModeRequest>,
+    ) -> Result<Response<pb::SetMaintenanceModeResponse>, Status> {
+        let principal = self.authorize(&request, Role::Operator)?;
+        let origin = request.remote_addr();
+        let req = request.into_inner();
+        let duration = match (req.enabled, req.minutes) {
+            (false, _) => None,
+            (true, 0) => Some(self.state.maintenance_duration),
+            (true, minutes) => Some(self.state.suppression_duration(minutes.into()).map_err(Status::invalid_argument)?),
+        };
+        self.state.set_maintenance(duration, EventSource::Rpc);
+        info!(
+            "🛠️  Maintenance mode {} (gRPC, {})",
+            if req.enabled { "ON" } else { "OFF" },
+            principal.name
+        );
+        let detail = match duration {
+            Some(d) => format!("enabled for {} min", d.as_secs() / 60),
+            None => "disabled".to_string(),
+        };
+        self.audit(origin, &principal, "maintenance_mode", "global", detail);
+        Ok(Response::new(pb::SetMaintenanceModeResponse {
+            enabled: req.enabled,
+            remaining_seconds: duration.map(|d| d.as_secs() as u32).unwrap_or(0),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::serial::DeviceIdentity;
+    use crate::state::CommandReply;
+    use std::time::Duration;
+
+    fn service(name: &str) -> DcsService {
+        let dir = std::env::temp_dir().join(format!("dcs-grpc-{}-{}", name, std::process::id()));
+        let mut config = Config::default();
+        config.audit.path = dir.join("audit.jsonl");
+        config.connections.command_timeout = Duration::from_millis(200);
+        config.connections.command_retries = 0;
+        let state = AppState::new(&config);
+        state.serial_connected.store(true, std::sync::atomic::Ordering::Relaxed);
+        *state.device_identity.lock().unwrap() = DeviceIdentity::parse("HELLO|fw=1.3.0|proto=3|device=esp32");
+        DcsService { state: Arc::new(state) }
+    }
+
+    fn fan_on() -> Request<pb::SetActuatorRequest> {
+        Request::new(pb::SetActuatorRequest { actuator: "exhaust_fan".to_string(), mode: pb::ActuatorMode::On.into(), zone: String::new() })
+    }
+
+    // Balas perintah RELAY pertama yang muncul di baris untuk serial, seperti monitor serial
+    async fn reply_to_relay(state: Arc<AppState>, result: Result<(), String>) -> String {
+        loop {
+            let frame = state.device_relays.lock().unwrap().clone();
+            if !frame.is_empty() {
+                state.command_replied(CommandReply { command: frame.clone(), result });
+                return frame;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn set_actuator_enqueues_device_command() {
+        let service = service("ack");
+        let device = tokio::spawn(reply_to_relay(service.state.clone(), Ok(())));
+        let response = service.set_actuator(fan_on()).await.unwrap().into_inner();
+        assert_eq!(device.await.unwrap(), "RELAY|exhaust_fan=ON");
+        assert_eq!(response.previous(), pb::ActuatorMode::Auto);
+        let fan = service.state.zones[0].actuator(Actuator::ExhaustFan);
+        assert_eq!(service.state.mode(&fan), ActuatorMode::On);
+    }
+
+    #[tokio::test]
+    async fn set_actuator_fails_without_ack() {
+        let service = service("nak");
+        let fan = service.state.zones[0].actuator(Actuator::ExhaustFan);
+        let device = tokio::spawn(reply_to_relay(service.state.clone(), Err("NAK: busy".to_string())));
+        let status = service.set_actuator(fan_on()).await.unwrap_err();
+        device.await.unwrap();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        assert_eq!(service.state.mode(&fan), ActuatorMode::Auto);
+        // Perintah yang ditolak ditarik kembali dari baris RELAY
+        assert_eq!(*service.state.device_relays.lock().unwrap(), "");
+
+        // Tanpa balasan sama sekali: gagal setelah batas tunggu
+        let status = service.set_actuator(fan_on()).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        assert!(status.message().contains("no reply"), "{}", status.message());
+
+        service.state.serial_connected.store(false, std::sync::atomic::Ordering::Relaxed);
+        let status = service.set_actuator(fan_on()).await.unwrap_err();
+        assert!(status.message().contains("not connected"), "{}", status.message());
+    }
+}
@@ -0,0 +1,203 @@
+use anyhow::{anyhow, Context, Result};
+
+use crate::line_protocol::Point;
+
+// `backend import`: isi ulang InfluxDB dari CSV export lama (laptop logging)
+//   backend import --csv file.csv --measurement sht20_sensor --map temp=temperature,rh=humidity
+//                  [--time-column timestamp] [--tags zone=main,device=sht20]
+//                  [--utc-offset +07:00] [--batch 5000] [--dry-run]
+pub const USAGE: &str = "usage: backend import --csv <file> --map <column=field,...> [--measurement sht20_sensor] \
+[--time-column timestamp] [--tags <tag=value,...>] [--utc-offset +07:00] [--batch 5000] [--dry-run]";
+
+#[derive(Debug, Clone)]
+pub struct ImportArgs {
+    pub csv: String,
+    pub measurement: String,
+    // Kolom CSV -> nama field InfluxDB; kolom lain diabaikan
+    pub map: Vec<(String, String)>,
+    pub time_column: String,
+    pub tags: Vec<(String, String)>,
+    // Timestamp tanpa zona waktu dianggap waktu lokal dengan offset ini
+    pub utc_offset_secs: i64,
+    pub batch: usize,
+    pub dry_run: bool,
+}
+
+impl ImportArgs {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut parsed = ImportArgs {
+            csv: String::new(),
+            measurement: "sht20_sensor".to_string(),
+            map: Vec::new(),
+            time_column: "timestamp".to_string(),
+            tags: Vec::new(),
+            utc_offset_secs: 0,
+            batch: 5000,
+            dry_run: false,
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--dry-run" {
+                parsed.dry_run = true;
+                continue;
+            }
+            let value = iter.next().ok_or_else(|| anyhow!("{} needs a value\n{}", arg, USAGE))?;
+            match arg.as_str() {
+                "--csv" => parsed.csv = value.clone(),
+                "--measurement" => parsed.measurement = value.clone(),
+                "--map" => parsed.map = pairs(value)?,
+                "--time-column" => parsed.time_column = value.clone(),
+                "--tags" => parsed.tags = pairs(value)?,
+                "--utc-offset" => parsed.utc_offset_secs = parse_offset(value)?,
+                "--batch" => parsed.batch = value.parse().with_context(|| format!("invalid --batch {}", value))?,
+                other => return Err(anyhow!("unknown option {}\n{}", other, USAGE)),
+            }
+        }
+        if parsed.csv.is_empty() || parsed.map.is_empty() {
+            return Err(anyhow!("--csv and --map are required\n{}", USAGE));
+        }
+        // Nama measurement/tag/field divalidasi line_protocol sebelum baris pertama dikonversi
+        Point::new(&parsed.measurement)
+            .tags(parsed.tags.iter().map(|(k, v)| (k, v)))
+            .float("probe", 0.0)
+            .to_line()
+            .with_context(|| format!("invalid --measurement/--tags\n{}", USAGE))?;
+        parsed.batch = parsed.batch.max(1);
+        Ok(parsed)
+    }
+}
+
+// "a=b,c=d" -> [(a, b), (c, d)]
+fn pairs(value: &str) -> Result<Vec<(String, String)>> {
+    value
+        .split(',')
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| {
+            let (k, v) = p.split_once('=').ok_or_else(|| anyhow!("expected key=value, got '{}'", p))?;
+            Ok((k.trim().to_string(), v.trim().to_string()))
+        })
+        .collect()
+}
+
+// "+07:00", "-0530", "7" -> detik
+pub(crate) fn parse_offset(value: &str) -> Result<i64> {
+    let (sign, rest) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let digits: String = rest.chars().filter(|c| *c != ':').collect();
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.as_str(), "0"),
+        4 => digits.split_at(2),
+        _ => return Err(anyhow!("invalid UTC offset {}", value)),
+    };
+    let hours: i64 = hours.parse().with_context(|| format!("invalid UTC offset {}", value))?;
+    let minutes: i64 = minutes.parse().with_context(|| format!("invalid UTC offset {}", value))?;
+    Ok(sign * (hours * 3600 + minutes * 60))
+}
+
+// Hasil konversi: line protocol siap tulis dan jumlah baris yang dilewati
+pub struct Converted {
+    pub lines: Vec<String>,
+    pub rows: usize,
+    pub skipped: Vec<(usize, String)>,
+}
+
+pub fn convert(args: &ImportArgs, text: &str) -> Result<Converted> {
+    let mut rows = text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+    let (_, header) = rows.next().ok_or_else(|| anyhow!("{} is empty", args.csv))?;
+    let header: Vec<String> = split_row(header);
+    let column = |name: &str| {
+        header.iter().position(|h| h == name).ok_or_else(|| anyhow!("column '{}' not found in {}", name, args.csv))
+    };
+    let time_idx = column(&args.time_column)?;
+    let fields: Vec<(usize, &str)> =
+        args.map.iter().map(|(col, field)| Ok((column(col)?, field.as_str()))).collect::<Result<_>>()?;
+
+    let series = Point::new(&args.measurement).tags(args.tags.iter().map(|(k, v)| (k, v)));
+
+    let mut converted = Converted { lines: Vec::new(), rows: 0, skipped: Vec::new() };
+    for (i, row) in rows {
+        converted.rows += 1;
+        let line_no = i + 1;
+        let cols = split_row(row);
+        let Some(ts) = cols.get(time_idx).and_then(|t| parse_timestamp(t, args.utc_offset_secs)) else {
+            converted.skipped.push((line_no, "invalid timestamp".to_string()));
+            continue;
+        };
+        let point = fields
+            .iter()
+            .filter_map(|(idx, field)| {
+                let value: f64 = cols.get(*idx)?.parse().ok()?;
+                value.is_finite().then_some((*field, value))
+            })
+            .fold(series.clone(), |point, (field, value)| point.float(field, value));
+        if !point.has_fields() {
+            converted.skipped.push((line_no, "no numeric mapped value".to_string()));
+            continue;
+        }
+        match point.timestamp(ts).to_line() {
+            Ok(line) => converted.lines.push(line),
+            Err(e) => converted.skipped.push((line_no, e.to_string())),
+        }
+    }
+    Ok(converted)
+}
+
+// Pemisah koma sederhana; tanda kutip di sekitar sel dibuang
+fn split_row(row: &str) -> Vec<String> {
+    row.split(',').map(|c| c.trim().trim_matches('"').to_string()).collect()
+}
+
+// Epoch angka (s/ms/us/ns ditebak dari besarnya) atau ISO 8601 "YYYY-MM-DD[T ]HH:MM:SS[.frac][Z|±HH:MM]"
+pub fn parse_timestamp(value: &str, utc_offset_secs: i64) -> Option<u64> {
+    if let Ok(epoch) = value.parse::<f64>() {
+        let scale = match epoch.abs() {
+            e if e < 1e11 => 1e9,
+            e if e < 1e14 => 1e6,
+            e if e < 1e17 => 1e3,
+            _ => 1.0,
+        };
+        return (epoch > 0.0).then_some((epoch * scale) as u64);
+    }
+
+    let (date, time) = value.split_once(['T', ' '])?;
+    let mut date_parts = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date_parts.next()??, date_parts.next()??, date_parts.next()??);
+
+    // Zona waktu eksplisit menggantikan --utc-offset
+    let (clock, offset) = if let Some(clock) = time.strip_suffix('Z') {
+        (clock, 0)
+    } else if let Some(pos) = time.rfind(['+', '-']) {
+        (&time[..pos], parse_offset(&time[pos..]).ok()?)
+    } else {
+        (time, utc_offset_secs)
+    };
+    let (hms, frac) = clock.split_once('.').unwrap_or((clock, ""));
+    let mut hms_parts = hms.splitn(3, ':').map(|p| p.parse::<i64>().ok());
+    let (hour, minute) = (hms_parts.next()??, hms_parts.next()??);
+    let second = hms_parts.next().unwrap_or(Some(0))?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let nanos: u64 = if frac.is_empty() {
+        0
+    } else {
+        let digits: String = frac.chars().take(9).collect();
+        digits.parse::<u64>().ok()? * 10u64.pow(9 - digits.len() as u32)
+    };
+
+    let secs = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    (secs >= 0).then_some(secs as u64 * 1_000_000_000 + nanos)
+}
+
+// Algoritma days_from_civil (Howard Hinnant), kebalikan rfc3339 di compaction.rs
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
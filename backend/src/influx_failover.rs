@@ -0,0 +1,83 @@
+use serde::Deserialize;
+use std::sync::{Mutex, OnceLock};
+
+use crate::secrets::Secret;
+
+// InfluxDB cadangan. Tulis selalu ke primary (antrian [publish.influx] menampung selama primary mati);
+// setelah failure_threshold kegagalan tulis berturut-turut data juga ditulis ke secondary dan query
+// diarahkan ke secondary, sampai primary kembali menerima tulis.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct InfluxFailoverConfig {
+    // Kosong = failover nonaktif
+    pub secondary_url: String,
+    // Bisa juga lewat env INFLUX_SECONDARY_TOKEN(_FILE); kosong = token primary
+    pub token: Secret,
+    pub failure_threshold: u32,
+}
+
+impl Default for InfluxFailoverConfig {
+    fn default() -> Self {
+        Self { secondary_url: String::new(), token: Secret::default(), failure_threshold: 3 }
+    }
+}
+
+impl InfluxFailoverConfig {
+    pub fn enabled(&self) -> bool {
+        !self.secondary_url.trim().is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Primary,
+    Secondary,
+}
+
+impl Target {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Target::Primary => "primary",
+            Target::Secondary => "secondary",
+        }
+    }
+}
+
+struct Failover {
+    threshold: u32,
+    // (kegagalan berturut-turut, target aktif)
+    status: Mutex<(u32, Target)>,
+}
+
+static FAILOVER: OnceLock<Failover> = OnceLock::new();
+
+// Dipanggil sekali saat startup jika secondary dikonfigurasi
+pub fn install(threshold: u32) {
+    let _ = FAILOVER.set(Failover { threshold: threshold.max(1), status: Mutex::new((0, Target::Primary)) });
+}
+
+// None = failover tidak dikonfigurasi
+pub fn active() -> Option<Target> {
+    FAILOVER.get().map(|f| f.status.lock().unwrap().1)
+}
+
+// Hasil satu tulis ke primary; Some(target baru) jika hasil ini memindahkan target aktif
+pub fn record_primary(ok: bool) -> Option<Target> {
+    let failover = FAILOVER.get()?;
+    let mut status = failover.status.lock().unwrap();
+    let (failures, target) = &mut *status;
+    if ok {
+        *failures = 0;
+        if *target == Target::Secondary {
+            *target = Target::Primary;
+            return Some(Target::Primary);
+        }
+        return None;
+    }
+    *failures += 1;
+    if *target == Target::Primary && *failures >= failover.threshold {
+        *target = Target::Secondary;
+        return Some(Target::Secondary);
+    }
+    None
+}
@@ -0,0 +1,288 @@
+use log::warn;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::alarms::{Alarm, AlarmContext};
+use crate::anomaly::{AnomalyConfig, AnomalyDetector};
+use crate::calibration;
+use crate::clock::{SkewEstimator, TimeSource};
+use crate::config::Config;
+use crate::filters::{build_chains, FilterChain};
+use crate::metrics::{self, METRICS};
+use crate::quality::{Gap, GapDetector, Quality};
+use crate::rate::{Direction, RateTracker};
+use crate::serial::SensorData;
+use crate::state::AppState;
+use crate::validation::{OutlierMode, Validator};
+use crate::vpd;
+use crate::zone;
+
+// Titik sensor yang sudah melewati tahap pemrosesan, siap ditulis ke InfluxDB
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub data: SensorData,
+    pub device_id: String,
+    // Zone yang dilayani device sensor ini
+    pub zone: String,
+    pub timestamp_ns: u64,
+    pub time_source: TimeSource,
+    pub quality: Quality,
+    // Gap sebelum titik ini, jika ada
+    pub gap: Option<Gap>,
+    pub outlier: bool,
+    // Field tambahan hasil pemrosesan, misal temperature_filtered
+    pub extra: Vec<(String, f64)>,
+    // Transisi alarm yang dipicu saat memproses titik ini (alarm, aktif)
+    pub alarms: Vec<(Alarm, bool)>,
+}
+
+// Tahapan pemrosesan data sensor sebelum masuk InfluxDB dan logika kontrol
+pub struct Ingest {
+    validator: Validator,
+    filters: HashMap<String, FilterChain>,
+    anomaly: AnomalyConfig,
+    detectors: HashMap<String, AnomalyDetector>,
+    clock: SkewEstimator,
+    gaps: GapDetector,
+    rates: Vec<RateTracker>,
+    device_id: String,
+    zone: String,
+    state: Arc<AppState>,
+}
+
+impl Ingest {
+    pub fn new(config: &Config, state: Arc<AppState>) -> Self {
+        Self::for_device(config, state, &config.device_id)
+    }
+
+    // Tiap device sumber (serial, MQTT) punya state filter, clock, dan gap sendiri
+    pub fn for_device(config: &Config, state: Arc<AppState>, device_id: &str) -> Self {
+        let detectors = if config.anomaly.enabled {
+            config
+                .anomaly
+                .fields
+                .iter()
+                .map(|f| (f.clone(), AnomalyDetector::new(config.anomaly.clone())))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        Self {
+            validator: Validator::new(config.validation.clone()),
+            filters: build_chains(&config.filters),
+            anomaly: config.anomaly.clone(),
+            detectors,
+            clock: SkewEstimator::new(config.clock.clone()),
+            gaps: GapDetector::new(config.quality.clone()),
+            rates: config.rate_alarms.iter().cloned().map(RateTracker::new).collect(),
+            device_id: device_id.to_string(),
+            zone: zone::for_device(&config.zones(), device_id)
+                .map(|z| z.name.clone())
+                .unwrap_or_else(|| device_id.to_string()),
+            state,
+        }
+    }
+
+    // None berarti titik dibuang
+    pub fn process(&mut self, mut data: SensorData) -> Option<Sample> {
+        let (timestamp_ns, time_source) = self.clock.correct(data.timestamp, crate::now_ns());
+        self.state.note_sample_ts(&self.device_id, timestamp_ns);
+        calibration::apply(&self.state.calibration.lock().unwrap(), &self.device_id, &mut data);
+        let raw_humidity = data.humidity as f64;
+        let drift = self.state.drift.correct(&self.device_id, timestamp_ns, &mut data);
+        let reasons = self.validator.check(&data, Instant::now());
+        if reasons.is_empty() {
+            // Outlier tidak boleh membuat sensor terlihat hidup atau menjadi referensi drift
+            let previous = self.state.last_sample.lock().unwrap().insert(self.device_id.clone(), Instant::now());
+            if let Some(previous) = previous {
+                METRICS.observe(&metrics::FRAME_INTERVAL, &self.device_id, previous.elapsed());
+            }
+            self.state.drift.note_reading(&self.device_id, raw_humidity);
+            let gap = self.gaps.observe(timestamp_ns, data.temperature, data.humidity);
+            if let Some(gap) = &gap {
+                warn!(
+                    "⏳ Data gap of {:.0}s ({} sample(s) missed, {} interpolated)",
+                    gap.seconds,
+                    gap.missed,
+                    gap.interpolated.len()
+                );
+            }
+            let mut extra = self.filter(&data);
+            extra.push(("vpd".to_string(), vpd::vpd_kpa(data.temperature as f64, data.humidity as f64)));
+            extra.extend(drift.map(|correction| ("humidity_drift".to_string(), correction)));
+            extra.extend(data.aux.iter().map(|(name, value)| (name.clone(), *value as f64)));
+            let mut alarms = Vec::new();
+            if !self.detectors.is_empty() {
+                let anomalous = self.detect_anomalies(&data, &mut alarms);
+                extra.push(("anomaly".to_string(), if anomalous { 1.0 } else { 0.0 }));
+            }
+            self.check_rates(&data, timestamp_ns, &extra, &mut alarms);
+            return Some(Sample {
+                data,
+                device_id: self.device_id.clone(),
+                zone: self.zone.clone(),
+                timestamp_ns,
+                time_source,
+                quality: Quality::Good,
+                gap,
+                outlier: false,
+                extra,
+                alarms,
+            });
+        }
+
+        match self.validator.mode() {
+            OutlierMode::Reject => {
+                warn!("🚫 Outlier rejected (T={:.2}, H={:.2}): {}", data.temperature, data.humidity, reasons.join("; "));
+                None
+            }
+            OutlierMode::Flag => {
+                warn!("⚠️  Outlier flagged (T={:.2}, H={:.2}): {}", data.temperature, data.humidity, reasons.join("; "));
+                Some(Sample {
+                    data,
+                    device_id: self.device_id.clone(),
+                    zone: self.zone.clone(),
+                    timestamp_ns,
+                    time_source,
+                    quality: Quality::SensorFault,
+                    gap: None,
+                    outlier: true,
+                    extra: Vec::new(),
+                    alarms: Vec::new(),
+                })
+            }
+        }
+    }
+
+    // Titik yang dikirim ulang dari buffer flash device (BACKLOG): timestamp device dipakai
+    // apa adanya dan state filter/gap/alarm tidak disentuh karena urutannya sudah lewat
+    pub fn backlog(&mut self, mut data: SensorData) -> Sample {
+        let timestamp_ns = data.timestamp;
+        self.state.note_sample_ts(&self.device_id, timestamp_ns);
+        calibration::apply(&self.state.calibration.lock().unwrap(), &self.device_id, &mut data);
+        let drift = self.state.drift.correct(&self.device_id, timestamp_ns, &mut data);
+        let mut extra = vec![("vpd".to_string(), vpd::vpd_kpa(data.temperature as f64, data.humidity as f64))];
+        extra.extend(drift.map(|correction| ("humidity_drift".to_string(), correction)));
+        Sample {
+            data,
+            device_id: self.device_id.clone(),
+            zone: self.zone.clone(),
+            timestamp_ns,
+            time_source: TimeSource::Device,
+            quality: Quality::Good,
+            gap: None,
+            outlier: false,
+            extra,
+            alarms: Vec::new(),
+        }
+    }
+
+    // Nilai raw tetap disimpan; hasil filter ditulis sebagai <field>_filtered
+    fn filter(&mut self, data: &SensorData) -> Vec<(String, f64)> {
+        let raw = [("temperature", data.temperature as f64), ("humidity", data.humidity as f64)];
+        raw.into_iter()
+            .filter_map(|(field, value)| {
+                let chain = self.filters.get_mut(field)?;
+                Some((format!("{field}_filtered"), chain.apply(value)))
+            })
+            .collect()
+    }
+
+    fn detect_anomalies(&mut self, data: &SensorData, alarms: &mut Vec<(Alarm, bool)>) -> bool {
+        let mut anomalous = false;
+        for (field, value) in [("temperature", data.temperature as f64), ("humidity", data.humidity as f64)] {
+            let Some(detector) = self.detectors.get_mut(field) else { continue };
+            let reason = detector.check(value);
+            if let Some(reason) = &reason {
+                warn!("🔎 Anomaly on {} = {:.2}: {}", field, value, reason);
+                anomalous = true;
+            }
+            if !self.anomaly.raise_alarm {
+                continue;
+            }
+
+            let id = format!("anomaly_{field}");
+            match reason {
+                Some(reason) => {
+                    let message = format!("Anomalous {} reading {:.2}: {}", field, value, reason);
+                    let context = AlarmContext::zone(self.zone.as_str()).value(value);
+                    if let Some(alarm) = self.state.raise_alarm_with(&id, self.anomaly.severity, message, context) {
+                        alarms.push((alarm, true));
+                    }
+                }
+                None => {
+                    if let Some(alarm) = self.state.clear_alarm(&id) {
+                        alarms.push((alarm, false));
+                    }
+                }
+            }
+        }
+        anomalous
+    }
+
+    // Alarm laju perubahan dihitung pada nilai terfilter jika ada (noise tidak memicu alarm)
+    fn check_rates(&mut self, data: &SensorData, ts_ns: u64, extra: &[(String, f64)], alarms: &mut Vec<(Alarm, bool)>) {
+        for tracker in &mut self.rates {
+            let field = tracker.rule.field.clone();
+            let filtered = format!("{field}_filtered");
+            let value = extra.iter().find(|(name, _)| *name == filtered).map(|(_, v)| *v).or(match field.as_str() {
+                "temperature" => Some(data.temperature as f64),
+                "humidity" => Some(data.humidity as f64),
+                _ => None,
+            });
+            let Some(value) = value else { continue };
+            let Some((active, delta)) = tracker.observe(ts_ns, value) else { continue };
+
+            let rule = &tracker.rule;
+            let id = format!("rate_{}", rule.name);
+            if active {
+                let message = format!(
+                    "{} {} {:.2} in {}s (limit {:.2})",
+                    field,
+                    if rule.direction == Direction::Rise { "rose" } else { "fell" },
+                    delta.abs(),
+                    rule.window.as_secs(),
+                    rule.change
+                );
+                warn!("📈 Rate alarm {}: {}", rule.name, message);
+                let context = AlarmContext::zone(self.zone.as_str()).value(delta.abs()).threshold(rule.change);
+                if let Some(alarm) = self.state.raise_alarm_with(&id, rule.severity, message, context) {
+                    alarms.push((alarm, true));
+                }
+            } else if let Some(alarm) = self.state.clear_alarm(&id) {
+                alarms.push((alarm, false));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(temperature: f32, humidity: f32) -> SensorData {
+        SensorData {
+            timestamp: crate::now_ns(),
+            temperature,
+            humidity,
+            exhaust_fan_status: None,
+            pump_status: None,
+            fan_duty: None,
+            aux: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn rejected_outlier_does_not_mark_sensor_alive() {
+        let config = Config::default();
+        let state = Arc::new(AppState::new(&config));
+        let mut ingest = Ingest::for_device(&config, state.clone(), "esp32-1");
+
+        assert!(ingest.process(reading(150.0, 50.0)).is_none());
+        assert!(state.last_sample_age("esp32-1").is_none());
+
+        assert!(ingest.process(reading(25.0, 50.0)).is_some());
+        assert!(state.last_sample_age("esp32-1").is_some());
+    }
+}
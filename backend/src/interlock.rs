@@ -0,0 +1,173 @@
+use anyhow::{anyhow, Result};
+use evalexpr::{build_operator_tree, ContextWithMutableVariables, HashMapContext, Node, Value};
+use log::warn;
+use std::collections::HashMap;
+
+use crate::config::InterlockRule;
+use crate::control::Actuator;
+
+struct CompiledRule {
+    rule: InterlockRule,
+    tree: Node,
+}
+
+// Hasil evaluasi interlock untuk satu aktuator
+#[derive(Debug, Clone)]
+pub struct Trip {
+    pub rule: InterlockRule,
+    pub requested: bool,
+}
+
+pub struct InterlockEngine {
+    rules: Vec<CompiledRule>,
+}
+
+impl InterlockEngine {
+    pub fn new(rules: &[InterlockRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                build_operator_tree(&rule.when)
+                    .map(|tree| CompiledRule { rule: rule.clone(), tree })
+                    .map_err(|e| anyhow!("Interlock '{}': invalid expression '{}': {}", rule.name, rule.when, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // Evaluasi semua aturan untuk aktuator ini sebelum perintah dikirim.
+    // Aturan pertama yang aktif menang; ekspresi yang tidak bisa dievaluasi
+    // (misal field belum tersedia) dilewati dengan warning.
+    pub fn apply(
+        &self,
+        actuator: Actuator,
+        requested: bool,
+        fields: &HashMap<String, f64>,
+    ) -> (bool, Option<Trip>) {
+        let context = build_context(fields);
+
+        for compiled in self.rules.iter().filter(|c| c.rule.actuator == actuator) {
+            match compiled.tree.eval_boolean_with_context(&context) {
+                Ok(true) => {
+                    let trip = Trip { rule: compiled.rule.clone(), requested };
+                    return (compiled.rule.force.is_on(), Some(trip));
+                }
+                Ok(false) => {}
+                Err(e) => warn!("Interlock '{}' not evaluated: {}", compiled.rule.name, e),
+            }
+        }
+        (requested, None)
+    }
+
+    // Nama semua aturan, dipakai untuk meng-clear alarm yang tidak lagi aktif
+    pub fn rule_names(&self, actuator: Actuator) -> impl Iterator<Item = &str> {
+        self.rules
+            .iter()
+            .filter(move |c| c.rule.actuator == actuator)
+            .map(|c| c.rule.name.as_str())
+    }
+}
+
+fn build_context(fields: &HashMap<String, f64>) -> HashMapContext {
+    let mut context = HashMapContext::new();
+    for (name, value) in fields {
+        // Nama field selalu valid sebagai identifier, error di sini tidak mungkin terjadi
+        let _ = context.set_value(name.clone(), Value::Float(*value));
+    }
+    context
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ForcedState;
+    use crate::control::{ActuatorId, Controller};
+    use std::time::Instant;
+
+    fn rule(name: &str, actuator: Actuator, when: &str, force: ForcedState) -> InterlockRule {
+        InterlockRule { name: name.to_string(), actuator, when: when.to_string(), force, severity: crate::alarms::Severity::Warning }
+    }
+
+    fn engine() -> InterlockEngine {
+        InterlockEngine::new(&[
+            rule("pump_low_tank", Actuator::Pump, "tank_level < 10", ForcedState::Off),
+            rule("fan_overheat", Actuator::ExhaustFan, "temperature >= 40", ForcedState::On),
+            rule("fan_door_open", Actuator::ExhaustFan, "door_open > 0.5", ForcedState::Off),
+        ])
+        .unwrap()
+    }
+
+    fn fields(values: &[(&str, f64)]) -> HashMap<String, f64> {
+        values.iter().map(|(name, value)| (name.to_string(), *value)).collect()
+    }
+
+    #[test]
+    fn each_rule_forces_its_actuator_state() {
+        let engine = engine();
+        let (state, trip) = engine.apply(Actuator::Pump, true, &fields(&[("tank_level", 5.0)]));
+        let trip = trip.unwrap();
+        assert!(!state);
+        assert_eq!((trip.rule.name.as_str(), trip.requested), ("pump_low_tank", true));
+
+        let (state, trip) = engine.apply(Actuator::ExhaustFan, false, &fields(&[("temperature", 40.0), ("door_open", 0.0)]));
+        assert!(state);
+        assert_eq!(trip.unwrap().rule.name, "fan_overheat");
+
+        let (state, trip) = engine.apply(Actuator::ExhaustFan, true, &fields(&[("temperature", 30.0), ("door_open", 1.0)]));
+        assert!(!state);
+        assert_eq!(trip.unwrap().rule.name, "fan_door_open");
+    }
+
+    #[test]
+    fn released_rule_passes_the_request_through() {
+        let engine = engine();
+        for requested in [false, true] {
+            assert!(matches!(engine.apply(Actuator::Pump, requested, &fields(&[("tank_level", 10.0)])), (state, None) if state == requested));
+        }
+        // Aturan aktuator lain tidak berlaku untuk pump
+        assert!(matches!(engine.apply(Actuator::Pump, true, &fields(&[("tank_level", 50.0), ("temperature", 45.0)])), (true, None)));
+    }
+
+    #[test]
+    fn first_active_rule_wins_and_missing_fields_are_skipped() {
+        let engine = engine();
+        let (state, trip) = engine.apply(Actuator::ExhaustFan, false, &fields(&[("temperature", 45.0), ("door_open", 1.0)]));
+        assert!(state);
+        assert_eq!(trip.unwrap().rule.name, "fan_overheat");
+        // temperature belum tersedia: fan_overheat dilewati, fan_door_open tetap dievaluasi
+        let (state, trip) = engine.apply(Actuator::ExhaustFan, true, &fields(&[("door_open", 1.0)]));
+        assert!(!state);
+        assert_eq!(trip.unwrap().rule.name, "fan_door_open");
+        assert!(matches!(engine.apply(Actuator::Pump, true, &HashMap::new()), (true, None)));
+    }
+
+    #[test]
+    fn interlock_overrides_and_releases_through_the_controller() {
+        let engine = engine();
+        let mut controller = Controller::new(&HashMap::new());
+        let pump = ActuatorId::new("main", Actuator::Pump);
+        let now = Instant::now();
+        let tripped = controller.decide(&pump, true, &engine, &fields(&[("tank_level", 2.0)]), now);
+        assert!(!tripped.state);
+        assert_eq!(tripped.trip.map(|t| t.rule.name), Some("pump_low_tank".to_string()));
+        assert_eq!(controller.state(&pump), Some(false));
+
+        let released = controller.decide(&pump, true, &engine, &fields(&[("tank_level", 40.0)]), now);
+        assert!(released.state && released.trip.is_none());
+        assert_eq!(released.previous, Some(false));
+    }
+
+    #[test]
+    fn rejects_invalid_expressions_and_lists_rules_per_actuator() {
+        let error = InterlockEngine::new(&[rule("broken", Actuator::Pump, "(tank_level < 10", ForcedState::Off)]).err().unwrap();
+        assert!(error.to_string().contains("Interlock 'broken'"));
+        let engine = engine();
+        assert_eq!(engine.rule_names(Actuator::ExhaustFan).collect::<Vec<_>>(), ["fan_overheat", "fan_door_open"]);
+        assert_eq!(engine.rule_names(Actuator::Heater).count(), 0);
+        assert!(InterlockEngine::new(&[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,331 @@
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::bridge::{now_ns, write_points, zone_point, Influx, ThingsBoard};
+use crate::control::Actuator;
+use crate::state::{AppState, Telemetry};
+use crate::zone::Zone;
+
+// KPI performa kontrol per zone dari telemetry loop utama: % waktu suhu dalam band setpoint,
+// integral absolute error (IAE) dan jumlah siklus ON aktuator per jam, atas jendela rolling.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct KpiConfig {
+    pub enabled: bool,
+    // Suhu "in band" jika |suhu - setpoint| <= band (°C)
+    pub band: f64,
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub interval: Duration,
+    // Jendela rolling, format durasi "1h", "1d"
+    pub windows: Vec<String>,
+    // Batas hari untuk ringkasan harian ke ThingsBoard (waktu lokal)
+    pub utc_offset: String,
+}
+
+impl Default for KpiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            band: 0.5,
+            interval: Duration::from_secs(300),
+            windows: vec!["1h".to_string(), "1d".to_string()],
+            utc_offset: "+07:00".to_string(),
+        }
+    }
+}
+
+impl KpiConfig {
+    // (nama, durasi) jendela yang valid; yang tidak valid ditolak check-config
+    pub fn windows(&self) -> Vec<(String, Duration)> {
+        self.windows.iter().filter_map(|w| Some((w.clone(), crate::config::parse_duration(w).ok()?))).collect()
+    }
+
+    fn offset_secs(&self) -> i64 {
+        crate::import::parse_offset(&self.utc_offset).unwrap_or_default()
+    }
+
+    // Nomor hari lokal untuk epoch ms
+    pub fn day(&self, ts_ms: u64) -> i64 {
+        (ts_ms as i64 / 1000 + self.offset_secs()).div_euclid(86_400)
+    }
+
+    // [awal, akhir) hari lokal dalam epoch ms
+    pub fn day_range(&self, day: i64) -> (u64, u64) {
+        let start = (day * 86_400 - self.offset_secs()) * 1000;
+        (start.max(0) as u64, (start + 86_400_000).max(0) as u64)
+    }
+}
+
+// Status aktuator yang dihitung siklusnya, dengan key telemetry-nya
+const TRACKED: [(Actuator, &str); 3] = [
+    (Actuator::ExhaustFan, "exhaust_fan_status"),
+    (Actuator::Pump, "pump_calculated_status"),
+    (Actuator::Heater, "heater_status"),
+];
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    ts_ms: u64,
+    // Selang ke siklus sebelumnya (detik), dibatasi agar gap data tidak ikut dihitung
+    dt_s: f64,
+    temperature: Option<f64>,
+    setpoint: Option<f64>,
+    actuators: [Option<bool>; 3],
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Kpi {
+    // Detik dengan suhu dan setpoint diketahui
+    pub observed_s: f64,
+    pub time_in_band_pct: f64,
+    // °C·jam
+    pub iae: f64,
+    pub cycles_per_hour: Vec<(Actuator, f64)>,
+}
+
+pub struct KpiTracker {
+    // zone -> prefix key telemetry
+    zones: Vec<(String, String)>,
+    samples: HashMap<String, VecDeque<Sample>>,
+    retain_ms: u64,
+}
+
+impl KpiTracker {
+    pub fn new(zones: &[Zone], retain: Duration) -> Self {
+        let multi = zones.len() > 1;
+        let zones = zones.iter().map(|z| (z.name.clone(), if multi { format!("{}_", z.name) } else { String::new() })).collect();
+        Self { zones, samples: HashMap::new(), retain_ms: retain.as_millis() as u64 }
+    }
+
+    pub fn zones(&self) -> impl Iterator<Item = &str> {
+        self.zones.iter().map(|(name, _)| name.as_str())
+    }
+
+    // Suhu kontrol (terfilter jika ada) dan setpoint efektif dari payload; mode shadow memakai *_proposed
+    pub fn record(&mut self, telemetry: &Telemetry) {
+        let now = telemetry.timestamp_ms;
+        for (zone, prefix) in &self.zones {
+            let value = |key: &str| telemetry.values.get(&format!("{prefix}{key}")).copied();
+            let temperature = value("sht20_temperature_filtered").or_else(|| value("sht20_temperature"));
+            let setpoint = value("setpoint_temperature").or_else(|| value("dwsim_temperature_setpoint"));
+            let actuators = TRACKED.map(|(_, key)| value(key).or_else(|| value(&format!("{key}_proposed"))).map(|v| v > 0.5));
+            let samples = self.samples.entry(zone.clone()).or_default();
+            let dt_s = samples.back().map_or(10.0, |last| (now.saturating_sub(last.ts_ms) as f64 / 1000.0).min(30.0));
+            samples.push_back(Sample { ts_ms: now, dt_s, temperature, setpoint, actuators });
+            while samples.front().is_some_and(|s| s.ts_ms + self.retain_ms < now) {
+                samples.pop_front();
+            }
+        }
+    }
+
+    // KPI satu zone untuk sampel dalam [from_ms, to_ms); None jika tidak ada data suhu/setpoint
+    pub fn compute(&self, zone: &str, from_ms: u64, to_ms: u64, band: f64) -> Option<Kpi> {
+        let samples = self.samples.get(zone)?;
+        let mut kpi = Kpi::default();
+        let mut in_band_s = 0.0;
+        let mut cycles = [0u32; 3];
+        let mut known_s = [0.0; 3];
+        let mut previous: [Option<bool>; 3] = [None; 3];
+        for sample in samples.iter().filter(|s| s.ts_ms >= from_ms && s.ts_ms < to_ms) {
+            if let (Some(t), Some(sp)) = (sample.temperature, sample.setpoint) {
+                let error = (t - sp).abs();
+                kpi.observed_s += sample.dt_s;
+                kpi.iae += error * sample.dt_s / 3600.0;
+                if error <= band {
+                    in_band_s += sample.dt_s;
+                }
+            }
+            for i in 0..TRACKED.len() {
+                let Some(on) = sample.actuators[i] else { continue };
+                known_s[i] += sample.dt_s;
+                if on && previous[i] == Some(false) {
+                    cycles[i] += 1;
+                }
+                previous[i] = Some(on);
+            }
+        }
+        if kpi.observed_s <= 0.0 {
+            return None;
+        }
+        kpi.time_in_band_pct = 100.0 * in_band_s / kpi.observed_s;
+        kpi.cycles_per_hour = TRACKED
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| known_s[*i] > 0.0)
+            .map(|(i, (actuator, _))| (*actuator, cycles[i] as f64 * 3600.0 / known_s[i]))
+            .collect();
+        Some(kpi)
+    }
+}
+
+// KPI kontrol per zone dari telemetry loop utama: jendela rolling ke measurement "control_kpi" tiap
+// `interval`, ringkasan hari lokal sebelumnya (window=day) juga ke ThingsBoard sebagai telemetry kpi_daily_*.
+pub(crate) async fn run_task(state: Arc<AppState>, influx: Influx, tb: ThingsBoard, config: KpiConfig) {
+    let windows = config.windows();
+    let retain = windows.iter().map(|(_, d)| *d).max().unwrap_or_default().max(Duration::from_secs(25 * 3600));
+    let mut tracker = KpiTracker::new(&state.zones, retain);
+    let mut telemetry = state.subscribe_telemetry();
+    let mut ticker = tokio::time::interval(config.interval);
+    ticker.tick().await;
+    let mut today = config.day(now_ns() / 1_000_000);
+    let multi = state.zones.len() > 1;
+    let kpi_point = |zone: &str, window: &str, kpi: &Kpi, ts_ns: u64| {
+        let mut point = zone_point(&state, "control_kpi", zone)
+            .tag("window", window)
+            .float_prec("time_in_band_pct", kpi.time_in_band_pct, 2)
+            .float_prec("iae", kpi.iae, 4)
+            .float_prec("observed_s", kpi.observed_s, 0);
+        for (actuator, rate) in &kpi.cycles_per_hour {
+            point = point.float_prec(&format!("{}_cycles_per_hour", actuator.name()), *rate, 2);
+        }
+        point.timestamp(ts_ns)
+    };
+    loop {
+        tokio::select! {
+            received = telemetry.recv() => match received {
+                Ok(t) => tracker.record(&t),
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => warn!("KPI tracker missed {} telemetry cycle(s)", missed),
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+            },
+            _ = ticker.tick() => {
+                let now_ms = now_ns() / 1_000_000;
+                let mut points = Vec::new();
+                for zone in tracker.zones() {
+                    for (name, window) in &windows {
+                        let from = now_ms.saturating_sub(window.as_millis() as u64);
+                        if let Some(kpi) = tracker.compute(zone, from, now_ms + 1, config.band) {
+                            points.push(kpi_point(zone, name, &kpi, now_ms * 1_000_000));
+                        }
+                    }
+                }
+
+                // Hari lokal berganti: ringkasan hari kemarin, timestamp akhir hari
+                let day = config.day(now_ms);
+                if day != today {
+                    let (start, end) = config.day_range(today);
+                    let end_ns = (end - 1) * 1_000_000;
+                    let mut values = serde_json::Map::new();
+                    for zone in tracker.zones() {
+                        let Some(kpi) = tracker.compute(zone, start, end, config.band) else { continue };
+                        let prefix = if multi { format!("{zone}_") } else { String::new() };
+                        info!("📈 [{}] Daily KPI: in band {:.1}%, IAE {:.2} °C·h", zone, kpi.time_in_band_pct, kpi.iae);
+                        values.insert(format!("{prefix}kpi_daily_time_in_band_pct"), json!((kpi.time_in_band_pct * 100.0).round() / 100.0));
+                        values.insert(format!("{prefix}kpi_daily_iae"), json!((kpi.iae * 1000.0).round() / 1000.0));
+                        for (actuator, rate) in &kpi.cycles_per_hour {
+                            values.insert(format!("{prefix}kpi_daily_{}_cycles_per_hour", actuator.name()), json!((rate * 100.0).round() / 100.0));
+                        }
+                        points.push(kpi_point(zone, "day", &kpi, end_ns));
+                    }
+                    if !values.is_empty() {
+                        if let Err(e) = tb.push(tb.telemetry(end_ns, values)) {
+                            error!("MQTT daily KPI publish error: {e:#}");
+                        }
+                    }
+                    today = day;
+                }
+
+                if !points.is_empty() {
+                    if let Err(e) = write_points(&influx, &points) {
+                        error!("Failed to write control KPIs to InfluxDB: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(retain_s: u64) -> KpiTracker {
+        KpiTracker::new(&[Zone::default_for("esp32-1")], Duration::from_secs(retain_s))
+    }
+
+    fn telemetry(ts_ms: u64, values: &[(&str, f64)]) -> Telemetry {
+        Telemetry { timestamp_ms: ts_ms, values: values.iter().map(|(k, v)| (k.to_string(), *v)).collect(), ..Default::default() }
+    }
+
+    // Deret sintetis tiap 10 detik mulai t=0
+    fn feed(tracker: &mut KpiTracker, series: &[&[(&str, f64)]]) {
+        for (i, values) in series.iter().enumerate() {
+            tracker.record(&telemetry(i as u64 * 10_000, values));
+        }
+    }
+
+    fn rate(kpi: &Kpi, actuator: Actuator) -> Option<f64> {
+        kpi.cycles_per_hour.iter().find(|(a, _)| *a == actuator).map(|(_, r)| *r)
+    }
+
+    #[test]
+    fn band_edges_are_inclusive() {
+        let mut kpi = tracker(3600);
+        let at = |t: f64| [("sht20_temperature", t), ("setpoint_temperature", 20.0)];
+        // Tepat ±0.5 °C masih dalam band, ±0.6 °C di luar
+        feed(&mut kpi, &[&at(20.5), &at(19.5), &at(20.6), &at(19.4)]);
+        let result = kpi.compute("main", 0, u64::MAX, 0.5).unwrap();
+        assert_eq!(result.observed_s, 40.0);
+        assert!((result.time_in_band_pct - 50.0).abs() < 1e-9);
+        assert!((result.iae - 2.2 * 10.0 / 3600.0).abs() < 1e-9);
+
+        // Suhu terfilter diutamakan; sampel tanpa setpoint tidak ikut dihitung
+        let mut kpi = tracker(3600);
+        let filtered = [("sht20_temperature", 30.0), ("sht20_temperature_filtered", 20.4), ("dwsim_temperature_setpoint", 20.0)];
+        feed(&mut kpi, &[&filtered, &[("sht20_temperature", 20.0)]]);
+        let result = kpi.compute("main", 0, u64::MAX, 0.5).unwrap();
+        assert_eq!(result.observed_s, 10.0);
+        assert_eq!(result.time_in_band_pct, 100.0);
+        assert!(kpi.compute("other", 0, u64::MAX, 0.5).is_none());
+    }
+
+    #[test]
+    fn window_rolls_off_old_samples() {
+        let mut kpi = tracker(60);
+        let sample: &[(&str, f64)] = &[("sht20_temperature", 21.0), ("setpoint_temperature", 20.0)];
+        feed(&mut kpi, &[sample; 13]);
+        // t=0..120 s, retensi 60 s: tersisa t=60..120
+        let result = kpi.compute("main", 0, u64::MAX, 0.5).unwrap();
+        assert_eq!(result.observed_s, 70.0);
+        assert_eq!(result.time_in_band_pct, 0.0);
+        assert!((result.iae - 70.0 / 3600.0).abs() < 1e-9);
+
+        // Jendela [from, to) memotong sampel di luar rentang
+        let result = kpi.compute("main", 90_000, 120_000, 0.5).unwrap();
+        assert_eq!(result.observed_s, 30.0);
+        assert!(kpi.compute("main", 0, 60_000, 0.5).is_none());
+
+        // Gap data dibatasi 30 detik per sampel
+        kpi.record(&telemetry(600_000, sample));
+        let result = kpi.compute("main", 600_000, u64::MAX, 0.5).unwrap();
+        assert_eq!(result.observed_s, 30.0);
+    }
+
+    #[test]
+    fn counts_off_to_on_transitions() {
+        let mut kpi = tracker(3600);
+        let at = |pump: f64, fan: f64| {
+            [("sht20_temperature", 20.0), ("setpoint_temperature", 20.0), ("pump_calculated_status", pump), ("exhaust_fan_status_proposed", fan)]
+        };
+        // Pompa: ON di awal tidak dihitung, lalu dua transisi OFF -> ON dalam 60 detik
+        feed(&mut kpi, &[&at(1.0, 0.0), &at(0.0, 0.0), &at(1.0, 1.0), &at(1.0, 1.0), &at(0.0, 0.0), &at(1.0, 0.0)]);
+        let result = kpi.compute("main", 0, u64::MAX, 0.5).unwrap();
+        assert_eq!(rate(&result, Actuator::Pump), Some(2.0 * 3600.0 / 60.0));
+        // Mode shadow: status *_proposed dipakai jika status aktual tidak ada
+        assert_eq!(rate(&result, Actuator::ExhaustFan), Some(3600.0 / 60.0));
+        // Heater tanpa data status tidak dilaporkan
+        assert_eq!(rate(&result, Actuator::Heater), None);
+    }
+
+    #[test]
+    fn zones_use_prefixed_keys_when_multiple() {
+        let zone = |name: &str| Zone { name: name.to_string(), ..Zone::default_for("esp32-1") };
+        let mut kpi = KpiTracker::new(&[zone("a"), zone("b")], Duration::from_secs(3600));
+        kpi.record(&telemetry(0, &[("a_sht20_temperature", 20.0), ("a_setpoint_temperature", 20.0), ("sht20_temperature", 20.0)]));
+        assert!(kpi.compute("a", 0, u64::MAX, 0.5).is_some());
+        assert!(kpi.compute("b", 0, u64::MAX, 0.5).is_none());
+    }
+}
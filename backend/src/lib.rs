@@ -0,0 +1,83 @@
+// Library bridge sensor SHT20 -> InfluxDB -> ThingsBoard. Binary `main.rs` hanya
+// memanggil `run()`; tool lain (mis. supervisor plant) bisa memakai pipeline
+// ingest/kontrol langsung lewat `Pipeline`, `Config`, `SensorData` dan modul control.
+
+pub mod alarms;
+pub mod anomaly;
+pub mod audit;
+pub mod auth;
+pub mod autotune;
+pub mod bacnet;
+pub mod calibration;
+pub mod cascade;
+pub mod chaos;
+pub mod checkpoint;
+pub mod clock;
+pub mod comfort;
+pub mod compaction;
+pub mod config;
+pub mod control;
+pub mod dedupe;
+pub mod drift;
+pub mod email;
+pub mod energy;
+pub mod events;
+pub mod experiments;
+pub mod export;
+pub mod filters;
+pub mod flux_csv;
+pub mod forecast;
+pub mod fusion;
+pub mod grafana;
+pub mod influx_failover;
+pub mod ingest;
+pub mod interlock;
+pub mod kpi;
+pub mod line_protocol;
+pub mod metrics;
+pub mod mqtt_source;
+pub mod notify;
+pub mod ota;
+pub mod pipeline;
+pub mod provision;
+pub mod publish;
+pub mod quality;
+pub mod query_cache;
+pub mod rate;
+pub mod raw_mirror;
+pub mod reconcile;
+pub mod report;
+pub mod retention;
+pub mod rules;
+pub mod runtime;
+pub mod secrets;
+pub mod serial;
+pub mod series;
+pub mod setpoint;
+pub mod settings;
+pub mod sink;
+pub mod state;
+pub mod stats;
+pub mod tb_alarms;
+pub mod telemetry_keys;
+pub mod tenants;
+pub mod validation;
+pub mod vpd;
+pub mod zone;
+
+// Khusus binary: REST/gRPC, subcommand, dan loop bridge
+mod api;
+mod bridge;
+mod check;
+mod cli;
+mod grpc;
+mod import;
+mod systemd;
+
+pub use bridge::now_ns;
+pub use cli::run;
+pub use config::Config;
+pub use pipeline::{Pipeline, SampleSink, SensorSource};
+pub use serial::SensorData;
+
+pub(crate) use bridge::{BAUD_RATE, DWSIM_MEAS, INFLUX_URL, SERIAL_PORT, TB_HOST, TB_PORT};
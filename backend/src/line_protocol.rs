@@ -0,0 +1,363 @@
+use anyhow::{anyhow, bail, Result};
+
+// Builder satu titik InfluxDB line protocol:
+//   <measurement>[,<tag>=<value>...] <field>=<value>[,...] [timestamp]
+// Semua escaping di sini; pemanggil tidak lagi menyusun line protocol dengan format!.
+
+// Batas panjang string field InfluxDB (64 KiB)
+const MAX_STRING_LEN: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    // Ditulis tanpa suffix sehingga bertipe float di InfluxDB; presisi None = representasi terpendek
+    Float(f64, Option<usize>),
+    // Suffix i (integer); jangan dipakai untuk field lama yang sudah bertipe float
+    Int(i64),
+    Bool(bool),
+    Str(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    measurement: String,
+    tags: Vec<(String, String)>,
+    fields: Vec<(String, FieldValue)>,
+    timestamp: Option<u64>,
+}
+
+impl Point {
+    pub fn new(measurement: impl Into<String>) -> Self {
+        Self { measurement: measurement.into(), tags: Vec::new(), fields: Vec::new(), timestamp: None }
+    }
+
+    pub fn measurement(&self) -> &str {
+        &self.measurement
+    }
+
+    // Urutan tag mengikuti urutan pemanggilan; key yang sama menimpa nilai sebelumnya.
+    // Nilai kosong dilewati (InfluxDB menolak tag tanpa nilai).
+    pub fn tag(mut self, key: &str, value: impl AsRef<str>) -> Self {
+        let value = value.as_ref();
+        if value.is_empty() {
+            return self;
+        }
+        match self.tags.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.tags.push((key.to_string(), value.to_string())),
+        }
+        self
+    }
+
+    pub fn tag_value(&self, key: &str) -> Option<&str> {
+        self.tags.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn tags<K: AsRef<str>, V: AsRef<str>>(self, tags: impl IntoIterator<Item = (K, V)>) -> Self {
+        tags.into_iter().fold(self, |point, (key, value)| point.tag(key.as_ref(), value))
+    }
+
+    pub fn field(mut self, key: &str, value: FieldValue) -> Self {
+        match self.fields.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.fields.push((key.to_string(), value)),
+        }
+        self
+    }
+
+    pub fn float(self, key: &str, value: f64) -> Self {
+        self.field(key, FieldValue::Float(value, None))
+    }
+
+    // Float dengan jumlah desimal tetap, mis. suhu 2 desimal seperti frame ESP32
+    pub fn float_prec(self, key: &str, value: f64, decimals: usize) -> Self {
+        self.field(key, FieldValue::Float(value, Some(decimals)))
+    }
+
+    pub fn int(self, key: &str, value: i64) -> Self {
+        self.field(key, FieldValue::Int(value))
+    }
+
+    pub fn boolean(self, key: &str, value: bool) -> Self {
+        self.field(key, FieldValue::Bool(value))
+    }
+
+    pub fn string(self, key: &str, value: impl Into<String>) -> Self {
+        self.field(key, FieldValue::Str(value.into()))
+    }
+
+    // Timestamp ns; tanpa timestamp InfluxDB memakai waktu server
+    pub fn timestamp(mut self, ns: u64) -> Self {
+        self.timestamp = Some(ns);
+        self
+    }
+
+    pub fn has_fields(&self) -> bool {
+        !self.fields.is_empty()
+    }
+
+    // Validasi lalu serialisasi; Err berisi alasan titik ini tidak bisa ditulis
+    pub fn to_line(&self) -> Result<String> {
+        check_name("measurement", &self.measurement)?;
+        if self.measurement.starts_with('#') {
+            bail!("measurement '{}' would be parsed as a comment", self.measurement);
+        }
+        if self.fields.is_empty() {
+            bail!("point '{}' has no fields", self.measurement);
+        }
+
+        let mut line = escape_measurement(&self.measurement);
+        for (key, value) in &self.tags {
+            check_name("tag key", key)?;
+            check_name("tag value", value)?;
+            line.push(',');
+            line.push_str(&escape_key(key));
+            line.push('=');
+            line.push_str(&escape_key(value));
+        }
+        for (i, (key, value)) in self.fields.iter().enumerate() {
+            check_name("field key", key)?;
+            line.push(if i == 0 { ' ' } else { ',' });
+            line.push_str(&escape_key(key));
+            line.push('=');
+            line.push_str(&format_value(key, value)?);
+        }
+        if let Some(ts) = self.timestamp {
+            line.push(' ');
+            line.push_str(&ts.to_string());
+        }
+        Ok(line)
+    }
+}
+
+fn check_name(kind: &str, name: &str) -> Result<()> {
+    if name.is_empty() {
+        bail!("empty {}", kind);
+    }
+    if name.contains(['\n', '\r']) {
+        bail!("{} '{}' contains a newline", kind, name.escape_debug());
+    }
+    // Backslash di akhir akan meng-escape pemisah berikutnya
+    if name.ends_with('\\') {
+        bail!("{} '{}' ends with a backslash", kind, name);
+    }
+    if kind != "measurement" && kind != "tag value" && name == "time" {
+        bail!("'time' is not allowed as {}", kind);
+    }
+    Ok(())
+}
+
+fn format_value(key: &str, value: &FieldValue) -> Result<String> {
+    Ok(match value {
+        FieldValue::Float(v, _) if !v.is_finite() => return Err(anyhow!("field '{}' is not finite ({})", key, v)),
+        FieldValue::Float(v, Some(decimals)) => format!("{:.*}", decimals, v),
+        FieldValue::Float(v, None) => format!("{}", v),
+        FieldValue::Int(v) => format!("{}i", v),
+        FieldValue::Bool(v) => v.to_string(),
+        FieldValue::Str(v) if v.len() > MAX_STRING_LEN => bail!("field '{}' longer than {} bytes", key, MAX_STRING_LEN),
+        FieldValue::Str(v) => format!("\"{}\"", escape_string(v)),
+    })
+}
+
+// Measurement: koma dan spasi
+pub fn escape_measurement(value: &str) -> String {
+    value.replace(',', "\\,").replace(' ', "\\ ")
+}
+
+// Tag key, tag value dan field key: koma, sama dengan, spasi
+pub fn escape_key(value: &str) -> String {
+    value.replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ")
+}
+
+// Nilai string field: backslash dan kutip ganda. Baris baru diganti spasi supaya satu titik
+// tetap satu baris (mis. pesan panic dari ESP32 di event).
+pub fn escape_string(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace(['\n', '\r'], " ")
+}
+
+// Gabungkan titik menjadi satu body write; titik yang tidak valid dilewati dengan warning.
+// Err jika tidak ada satu pun yang valid.
+pub fn join(points: &[Point]) -> Result<String> {
+    let mut lines = Vec::with_capacity(points.len());
+    let mut first_error = None;
+    for point in points {
+        match point.to_line() {
+            Ok(line) => lines.push(line),
+            Err(e) => {
+                log::warn!("⚠️  Skipping invalid InfluxDB point: {:#}", e);
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    match (lines.is_empty(), first_error) {
+        (true, Some(e)) => Err(e),
+        (true, None) => bail!("no points to write"),
+        _ => Ok(lines.join("\n")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simple_point() {
+        let line = Point::new("sht20_sensor")
+            .tag("zone", "main")
+            .tag("device", "sht20")
+            .float_prec("temperature", 25.3, 2)
+            .float_prec("humidity", 65.2, 2)
+            .timestamp(1_694_168_400_000_000_000)
+            .to_line()
+            .unwrap();
+        assert_eq!(line, "sht20_sensor,zone=main,device=sht20 temperature=25.30,humidity=65.20 1694168400000000000");
+    }
+
+    #[test]
+    fn without_tags_and_timestamp() {
+        assert_eq!(Point::new("m").float("v", 1.5).to_line().unwrap(), "m v=1.5");
+    }
+
+    #[test]
+    fn field_types() {
+        let line = Point::new("m")
+            .float("f", 1.0)
+            .float("g", -0.125)
+            .float_prec("p", 2.0 / 3.0, 3)
+            .int("i", -42)
+            .boolean("b", true)
+            .string("s", "ok")
+            .to_line()
+            .unwrap();
+        assert_eq!(line, "m f=1,g=-0.125,p=0.667,i=-42i,b=true,s=\"ok\"");
+    }
+
+    #[test]
+    fn escapes_measurement() {
+        let line = Point::new("my meas,x=y").float("v", 1.0).to_line().unwrap();
+        assert_eq!(line, "my\\ meas\\,x=y v=1");
+    }
+
+    #[test]
+    fn escapes_tags_and_field_keys() {
+        let line = Point::new("m")
+            .tag("site name", "green house, north=1")
+            .float("temp c", 20.0)
+            .float("a=b,c", 1.0)
+            .to_line()
+            .unwrap();
+        assert_eq!(line, "m,site\\ name=green\\ house\\,\\ north\\=1 temp\\ c=20,a\\=b\\,c=1");
+    }
+
+    #[test]
+    fn escapes_string_values() {
+        let line = Point::new("events")
+            .string("reason", r#"said "hi" at C:\temp"#)
+            .string("multi", "line one\nline two\r")
+            .string("plain", "a, b = c")
+            .to_line()
+            .unwrap();
+        assert_eq!(
+            line,
+            r#"events reason="said \"hi\" at C:\\temp",multi="line one line two ",plain="a, b = c""#
+        );
+    }
+
+    #[test]
+    fn empty_tag_values_are_skipped() {
+        let line = Point::new("m").tag("location", "").tag("zone", "main").float("v", 1.0).to_line().unwrap();
+        assert_eq!(line, "m,zone=main v=1");
+    }
+
+    #[test]
+    fn repeated_keys_overwrite_in_place() {
+        let line = Point::new("m")
+            .tag("zone", "a")
+            .tag("device", "d")
+            .tag("zone", "b")
+            .float("v", 1.0)
+            .float("w", 2.0)
+            .float("v", 3.0)
+            .to_line()
+            .unwrap();
+        assert_eq!(line, "m,zone=b,device=d v=3,w=2");
+    }
+
+    #[test]
+    fn tags_from_iterator() {
+        let tags = vec![("zone".to_string(), "main".to_string()), ("device".to_string(), "sht20".to_string())];
+        let line = Point::new("m").tags(tags).tags([("location", "gh 1")]).float("v", 1.0).to_line().unwrap();
+        assert_eq!(line, "m,zone=main,device=sht20,location=gh\\ 1 v=1");
+    }
+
+    #[test]
+    fn rejects_point_without_fields() {
+        let err = Point::new("m").tag("zone", "main").to_line().unwrap_err();
+        assert!(err.to_string().contains("no fields"), "{err}");
+    }
+
+    #[test]
+    fn rejects_empty_names() {
+        assert!(Point::new("").float("v", 1.0).to_line().is_err());
+        assert!(Point::new("m").tag("", "x").float("v", 1.0).to_line().is_err());
+        assert!(Point::new("m").float("", 1.0).to_line().is_err());
+    }
+
+    #[test]
+    fn rejects_non_finite_floats() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(Point::new("m").float("v", value).to_line().is_err());
+            assert!(Point::new("m").float_prec("v", value, 2).to_line().is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_newlines_outside_strings() {
+        assert!(Point::new("m\n").float("v", 1.0).to_line().is_err());
+        assert!(Point::new("m").tag("zone", "a\nb").float("v", 1.0).to_line().is_err());
+        assert!(Point::new("m").tag("zo\rne", "a").float("v", 1.0).to_line().is_err());
+        assert!(Point::new("m").float("v\n", 1.0).to_line().is_err());
+    }
+
+    #[test]
+    fn rejects_trailing_backslash() {
+        assert!(Point::new("m").tag("path", "C:\\").float("v", 1.0).to_line().is_err());
+        assert!(Point::new("m\\").float("v", 1.0).to_line().is_err());
+        // Backslash di tengah dibiarkan apa adanya
+        assert_eq!(Point::new("m").tag("path", "a\\b").float("v", 1.0).to_line().unwrap(), "m,path=a\\b v=1");
+    }
+
+    #[test]
+    fn rejects_reserved_time_key() {
+        assert!(Point::new("m").float("time", 1.0).to_line().is_err());
+        assert!(Point::new("m").tag("time", "x").float("v", 1.0).to_line().is_err());
+        assert!(Point::new("m").tag("kind", "time").float("v", 1.0).to_line().is_ok());
+    }
+
+    #[test]
+    fn rejects_comment_measurement() {
+        assert!(Point::new("#m").float("v", 1.0).to_line().is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_strings() {
+        let long = "x".repeat(MAX_STRING_LEN + 1);
+        assert!(Point::new("m").string("s", long).to_line().is_err());
+        assert!(Point::new("m").string("s", "x".repeat(MAX_STRING_LEN)).to_line().is_ok());
+    }
+
+    #[test]
+    fn join_skips_invalid_points() {
+        let points = [
+            Point::new("m").float("v", 1.0).timestamp(1),
+            Point::new("m").float("v", f64::NAN),
+            Point::new("m").float("v", 2.0).timestamp(2),
+        ];
+        assert_eq!(join(&points).unwrap(), "m v=1 1\nm v=2 2");
+    }
+
+    #[test]
+    fn join_fails_when_nothing_is_valid() {
+        assert!(join(&[]).is_err());
+        assert!(join(&[Point::new("m")]).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::publish::SinkHealth;
+
+// Histogram latensi untuk GET /metrics (format teks Prometheus). Dipakai untuk melacak
+// keterlambatan dashboard: InfluxDB, MQTT, antrian sink, interval frame, atau loop 10 detik.
+
+// Operasi jaringan (detik)
+const FAST: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+// Interval frame, durasi loop dan umur data (detik)
+const SLOW: &[f64] = &[0.5, 1.0, 2.0, 5.0, 10.0, 15.0, 20.0, 30.0, 60.0, 120.0, 300.0];
+
+pub struct Family {
+    pub name: &'static str,
+    help: &'static str,
+    // Nama label; "" = tanpa label
+    label: &'static str,
+    buckets: &'static [f64],
+}
+
+pub const INFLUX_WRITE: Family = Family {
+    name: "dcs_influx_write_seconds",
+    help: "HTTP duration of InfluxDB line protocol writes per tenant",
+    label: "tenant",
+    buckets: FAST,
+};
+pub const INFLUX_QUERY: Family = Family {
+    name: "dcs_influx_query_seconds",
+    help: "HTTP duration of InfluxDB Flux queries including the CSV body, per tenant",
+    label: "tenant",
+    buckets: FAST,
+};
+pub const MQTT_PUBLISH: Family = Family {
+    name: "dcs_mqtt_publish_seconds",
+    help: "Time from sending a ThingsBoard MQTT publish to its PUBACK",
+    label: "destination",
+    buckets: FAST,
+};
+pub const SINK_DELIVERY: Family = Family {
+    name: "dcs_sink_delivery_seconds",
+    help: "Time from enqueueing a message on a sink to its successful delivery (queue wait and retries included)",
+    label: "sink",
+    buckets: FAST,
+};
+pub const FRAME_INTERVAL: Family = Family {
+    name: "dcs_frame_interval_seconds",
+    help: "Interval between consecutive sensor frames per device",
+    label: "device",
+    buckets: SLOW,
+};
+pub const LOOP_CYCLE: Family = Family {
+    name: "dcs_loop_cycle_seconds",
+    help: "Duration of one main bridge loop iteration (queries, control, publish)",
+    label: "",
+    buckets: SLOW,
+};
+pub const TELEMETRY_AGE: Family = Family {
+    name: "dcs_telemetry_age_seconds",
+    help: "Age of the newest sensor sample when its telemetry is handed to ThingsBoard",
+    label: "",
+    buckets: SLOW,
+};
+
+const FAMILIES: [&Family; 7] = [&INFLUX_WRITE, &INFLUX_QUERY, &MQTT_PUBLISH, &SINK_DELIVERY, &FRAME_INTERVAL, &LOOP_CYCLE, &TELEMETRY_AGE];
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    // Jumlah per bucket (bukan kumulatif); bucket terakhir = +Inf
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(buckets: usize) -> Self {
+        Self { counts: vec![0; buckets + 1], sum: 0.0, count: 0 }
+    }
+}
+
+// Registry global seperti klien Prometheus pada umumnya: fungsi HTTP InfluxDB di bridge
+// dipanggil dari banyak tempat yang tidak memegang AppState
+pub static METRICS: Metrics = Metrics::new();
+
+pub struct Metrics {
+    // (nama family, nilai label)
+    histograms: Mutex<BTreeMap<(&'static str, String), Histogram>>,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self { histograms: Mutex::new(BTreeMap::new()) }
+    }
+
+    pub fn observe(&self, family: &Family, label: &str, value: Duration) {
+        let seconds = value.as_secs_f64();
+        let slot = family.buckets.iter().position(|bound| seconds <= *bound).unwrap_or(family.buckets.len());
+        let mut histograms = self.histograms.lock().unwrap();
+        let histogram = histograms
+            .entry((family.name, label.to_string()))
+            .or_insert_with(|| Histogram::new(family.buckets.len()));
+        histogram.counts[slot] += 1;
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+
+    pub fn render(&self) -> String {
+        let histograms = self.histograms.lock().unwrap().clone();
+        let mut out = String::new();
+        for family in FAMILIES {
+            let _ = writeln!(out, "# HELP {} {}", family.name, family.help);
+            let _ = writeln!(out, "# TYPE {} histogram", family.name);
+            for ((_, label), histogram) in histograms.range((family.name, String::new())..).take_while(|((name, _), _)| *name == family.name) {
+                let labels = |extra: Option<String>| {
+                    let mut parts = Vec::new();
+                    if !family.label.is_empty() {
+                        parts.push(format!("{}=\"{}\"", family.label, escape_label(label)));
+                    }
+                    parts.extend(extra);
+                    if parts.is_empty() {
+                        String::new()
+                    } else {
+                        format!("{{{}}}", parts.join(","))
+                    }
+                };
+                let mut cumulative = 0;
+                for (i, count) in histogram.counts.iter().enumerate() {
+                    cumulative += count;
+                    let le = family.buckets.get(i).map_or("+Inf".to_string(), |bound| bound.to_string());
+                    let _ = writeln!(out, "{}_bucket{} {}", family.name, labels(Some(format!("le=\"{le}\""))), cumulative);
+                }
+                let _ = writeln!(out, "{}_sum{} {}", family.name, labels(None), histogram.sum);
+                let _ = writeln!(out, "{}_count{} {}", family.name, labels(None), histogram.count);
+            }
+        }
+        out
+    }
+}
+
+type SinkValue = fn(&SinkHealth) -> f64;
+
+// Gauge antrian sink (publish.rs) untuk GET /metrics, dari snapshot AppState::sink_health
+pub fn render_sinks(sinks: &BTreeMap<String, SinkHealth>) -> String {
+    let series: [(&str, &str, &str, SinkValue); 5] = [
+        ("dcs_sink_queue_depth", "gauge", "Messages waiting in the sink queue", |h| h.queued as f64),
+        ("dcs_sink_queue_peak", "gauge", "Highest sink queue depth since start", |h| h.peak_queued as f64),
+        ("dcs_sink_queue_capacity", "gauge", "Configured sink queue capacity", |h| h.capacity as f64),
+        ("dcs_sink_dropped_total", "counter", "Messages dropped because the sink queue was full", |h| h.dropped as f64),
+        ("dcs_sink_dead_lettered_total", "counter", "Messages dropped after the sink rejected them", |h| h.dead_lettered as f64),
+    ];
+    let mut out = String::new();
+    for (name, kind, help, value) in series {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} {kind}");
+        for (sink, health) in sinks {
+            let _ = writeln!(out, "{name}{{sink=\"{}\"}} {}", escape_label(sink), value(health));
+        }
+    }
+    out
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
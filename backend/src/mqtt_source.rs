@@ -0,0 +1,190 @@
+use anyhow::{anyhow, Context, Result};
+use log::{error, info, warn};
+use rumqttc::{Client, Event, Incoming, MqttOptions, QoS};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::Duration;
+
+use crate::pipeline::{SensorSource, SharedPipeline};
+use crate::secrets::Secret;
+use crate::serial::SensorData;
+
+// Jeda sebelum reconnect ke broker lokal setelah error
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+// Field SensorData yang bisa dipetakan dari payload JSON
+pub const FIELDS: [&str; 6] = ["temperature", "humidity", "exhaust_fan_status", "pump_status", "fan_duty", "timestamp"];
+
+// Subscriber MQTT (mis. Mosquitto lokal) untuk gateway lain yang sudah publish JSON
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MqttSourceConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: String,
+    // Bisa juga lewat env MQTT_SOURCE_PASSWORD(_FILE)
+    pub password: Secret,
+    pub topics: Vec<TopicMapping>,
+}
+
+impl Default for MqttSourceConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "rust-dcs-ingest".to_string(),
+            username: String::new(),
+            password: Secret::default(),
+            topics: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampUnit {
+    S,
+    #[default]
+    Ms,
+    Us,
+    Ns,
+}
+
+impl TimestampUnit {
+    fn to_ns(self, value: f64) -> u64 {
+        let scale = match self {
+            TimestampUnit::S => 1e9,
+            TimestampUnit::Ms => 1e6,
+            TimestampUnit::Us => 1e3,
+            TimestampUnit::Ns => 1.0,
+        };
+        (value * scale).max(0.0) as u64
+    }
+}
+
+// Satu topic (boleh wildcard + / #) dan pemetaan field -> JSON pointer
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TopicMapping {
+    pub topic: String,
+    // Kosong = nama topic yang diterima dipakai sebagai device_id
+    pub device_id: String,
+    pub fields: BTreeMap<String, String>,
+    pub timestamp_unit: TimestampUnit,
+}
+
+impl Default for TopicMapping {
+    fn default() -> Self {
+        Self {
+            topic: String::new(),
+            device_id: String::new(),
+            fields: [("temperature", "/temperature"), ("humidity", "/humidity")]
+                .into_iter()
+                .map(|(field, pointer)| (field.to_string(), pointer.to_string()))
+                .collect(),
+            timestamp_unit: TimestampUnit::Ms,
+        }
+    }
+}
+
+impl TopicMapping {
+    fn device_id<'a>(&'a self, topic: &'a str) -> &'a str {
+        if self.device_id.is_empty() { topic } else { &self.device_id }
+    }
+
+    fn lookup<'a>(&self, payload: &'a Value, field: &str) -> Option<&'a Value> {
+        self.fields.get(field).and_then(|pointer| payload.pointer(pointer))
+    }
+
+    fn number(&self, payload: &Value, field: &str) -> Result<Option<f64>> {
+        let Some(value) = self.lookup(payload, field) else { return Ok(None) };
+        let number = match value {
+            Value::Number(n) => n.as_f64(),
+            Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        };
+        number.map(Some).ok_or_else(|| anyhow!("field {} is not numeric: {}", field, value))
+    }
+
+    // Payload JSON -> SensorData; temperature dan humidity wajib ada
+    pub fn extract(&self, payload: &Value) -> Result<SensorData> {
+        let required = |field: &str| -> Result<f32> {
+            let value = self.number(payload, field)?;
+            value.map(|v| v as f32).ok_or_else(|| anyhow!("field {} missing", field))
+        };
+        let status = |field: &str| -> Result<Option<bool>> { Ok(self.number(payload, field)?.map(|v| v != 0.0)) };
+        Ok(SensorData {
+            // 0 = pakai waktu host (lihat clock.rs)
+            timestamp: self.number(payload, "timestamp")?.map(|t| self.timestamp_unit.to_ns(t)).unwrap_or(0),
+            temperature: required("temperature")?,
+            humidity: required("humidity")?,
+            exhaust_fan_status: status("exhaust_fan_status")?,
+            pump_status: status("pump_status")?,
+            fan_duty: self.number(payload, "fan_duty")?.map(|v| v as f32),
+            aux: Vec::new(),
+        })
+    }
+}
+
+// Jalankan subscriber di thread sendiri; setiap payload yang cocok diteruskan sebagai (device_id, data)
+pub fn spawn<F>(config: MqttSourceConfig, password: Secret, mut on_sample: F)
+where
+    F: FnMut(&str, SensorData) + Send + 'static,
+{
+    let mut options = MqttOptions::new(config.client_id.clone(), config.host.clone(), config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if !config.username.is_empty() {
+        options.set_credentials(config.username.clone(), password.expose());
+    }
+    let (client, mut connection) = Client::new(options, 10);
+
+    thread::spawn(move || {
+        for event in connection.iter() {
+            match event {
+                Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                    info!("✓ MQTT source connected to {}:{}", config.host, config.port);
+                    for mapping in &config.topics {
+                        if let Err(e) = client.try_subscribe(mapping.topic.as_str(), QoS::AtMostOnce) {
+                            error!("MQTT source subscribe to {} failed: {e:#}", mapping.topic);
+                        }
+                    }
+                }
+                Ok(Event::Incoming(Incoming::Publish(p))) => {
+                    let Some(mapping) = config.topics.iter().find(|m| rumqttc::matches(&p.topic, &m.topic)) else {
+                        continue;
+                    };
+                    let parsed = serde_json::from_slice::<Value>(&p.payload)
+                        .context("invalid JSON")
+                        .and_then(|payload| mapping.extract(&payload));
+                    match parsed {
+                        Ok(data) => on_sample(mapping.device_id(&p.topic), data),
+                        Err(e) => warn!("📭 Ignoring MQTT message on {}: {:#}", p.topic, e),
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    error!("MQTT source error: {e:#}");
+                    thread::sleep(RECONNECT_DELAY);
+                }
+            }
+        }
+    });
+}
+
+// Subscriber MQTT sebagai sumber pipeline
+pub struct MqttSource {
+    pub config: MqttSourceConfig,
+    pub password: Secret,
+}
+
+impl SensorSource for MqttSource {
+    fn start(self: Box<Self>, pipeline: SharedPipeline) {
+        spawn(self.config, self.password, move |device_id, data| {
+            pipeline.lock().unwrap().process(device_id, data);
+        });
+    }
+}
@@ -0,0 +1,552 @@
+use anyhow::{anyhow, bail, Result};
+use log::{error, info, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::alarms::{Alarm, AlarmChange, Severity};
+use crate::email::{self, EmailConfig};
+use crate::secrets::Secret;
+use crate::state::AppState;
+use crate::tb_alarms::TbAlarmClient;
+
+// Routing notifikasi alarm per severity. Log dan event store selalu; kanal di bawah ini tambahan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Telegram,
+    Webhook,
+    // Alarm native ThingsBoard lewat REST API ([thingsboard_alarms]); tidak perlu jika mirror aktif
+    Thingsboard,
+    // SMTP ([notifications.email]); penerima dan template bisa diatur per aturan alarm
+    Email,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    pub enabled: bool,
+    pub info: Vec<Channel>,
+    pub warning: Vec<Channel>,
+    pub critical: Vec<Channel>,
+    // Offset zona waktu lokal untuk quiet hours, mis. "+07:00"
+    pub utc_offset: String,
+    pub quiet_hours: Option<QuietHours>,
+    // Alarm yang belum di-ack selama ini dikirim ulang ke escalate_to; 0 = tanpa eskalasi
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub escalate_after: Duration,
+    pub escalate_to: Vec<Channel>,
+    pub telegram: TelegramConfig,
+    pub webhook: WebhookConfig,
+    pub email: EmailConfig,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            info: Vec::new(),
+            warning: vec![Channel::Telegram],
+            critical: vec![Channel::Telegram, Channel::Webhook, Channel::Thingsboard],
+            utc_offset: "+07:00".to_string(),
+            quiet_hours: None,
+            escalate_after: Duration::ZERO,
+            escalate_to: vec![Channel::Telegram, Channel::Webhook],
+            telegram: TelegramConfig::default(),
+            webhook: WebhookConfig::default(),
+            email: EmailConfig::default(),
+        }
+    }
+}
+
+impl NotifyConfig {
+    pub fn route(&self, severity: Severity) -> &[Channel] {
+        match severity {
+            Severity::Info => &self.info,
+            Severity::Warning => &self.warning,
+            Severity::Critical => &self.critical,
+        }
+    }
+
+    // Semua kanal yang dipakai routing atau eskalasi
+    pub fn channels(&self) -> impl Iterator<Item = Channel> + '_ {
+        self.info.iter().chain(&self.warning).chain(&self.critical).chain(&self.escalate_to).copied()
+    }
+
+    pub fn uses(&self, channel: Channel) -> bool {
+        self.channels().any(|c| c == channel)
+    }
+
+    // true jika notifikasi alarm dengan severity ini ditahan pada waktu `epoch_secs`
+    pub fn is_quiet(&self, severity: Severity, epoch_secs: u64) -> bool {
+        let Some(quiet) = &self.quiet_hours else { return false };
+        let offset = crate::import::parse_offset(&self.utc_offset).unwrap_or_default();
+        let minute = ((epoch_secs as i64 + offset).rem_euclid(86_400) / 60) as u32;
+        severity < quiet.min_severity && quiet.contains(minute)
+    }
+}
+
+// Jam tenang "HH:MM" waktu lokal; boleh melewati tengah malam (22:00 - 06:00)
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+    // Severity minimal yang tetap dikirim saat jam tenang
+    #[serde(default = "default_quiet_severity")]
+    pub min_severity: Severity,
+}
+
+fn default_quiet_severity() -> Severity {
+    Severity::Critical
+}
+
+impl QuietHours {
+    pub fn contains(&self, minute: u32) -> bool {
+        let (Ok(start), Ok(end)) = (parse_clock(&self.start), parse_clock(&self.end)) else { return false };
+        if start <= end {
+            (start..end).contains(&minute)
+        } else {
+            minute >= start || minute < end
+        }
+    }
+}
+
+// "HH:MM" -> menit sejak tengah malam
+pub fn parse_clock(value: &str) -> Result<u32> {
+    let (hours, minutes) = value.split_once(':').ok_or_else(|| anyhow!("expected HH:MM, got '{}'", value))?;
+    let (hours, minutes): (u32, u32) = (hours.parse()?, minutes.parse()?);
+    if hours > 23 || minutes > 59 {
+        bail!("invalid time of day '{}'", value);
+    }
+    Ok(hours * 60 + minutes)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TelegramConfig {
+    pub api_url: String,
+    // Bisa juga lewat env TELEGRAM_BOT_TOKEN / TELEGRAM_BOT_TOKEN_FILE
+    pub bot_token: Secret,
+    pub chat_id: String,
+}
+
+impl Default for TelegramConfig {
+    fn default() -> Self {
+        Self { api_url: "https://api.telegram.org".to_string(), bot_token: Secret::default(), chat_id: String::new() }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    pub url: String,
+    // Opsional, dikirim sebagai Bearer; bisa juga lewat env NOTIFY_WEBHOOK_TOKEN
+    pub token: Secret,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Raised,
+    Cleared,
+    Escalated,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Raised => "raised",
+            Kind::Cleared => "cleared",
+            Kind::Escalated => "escalated",
+        }
+    }
+}
+
+// Alarm aktif yang dilacak untuk jam tenang dan eskalasi
+struct Open {
+    alarm: Alarm,
+    since: Instant,
+    // Notifikasi raise sudah terkirim (false = ditahan jam tenang)
+    notified: bool,
+    escalated: bool,
+}
+
+struct Notifier {
+    config: NotifyConfig,
+    client: Client,
+    device_id: String,
+    tb: Option<TbAlarmClient>,
+    open: HashMap<String, Open>,
+    // Clear yang ditahan jam tenang untuk alarm yang raise-nya sudah terkirim
+    held_clears: Vec<Alarm>,
+}
+
+impl Notifier {
+    async fn deliver(&mut self, channels: &[Channel], kind: Kind, alarm: &Alarm) {
+        let text = self.text(kind, alarm);
+        for &channel in channels {
+            let result = match channel {
+                Channel::Telegram => self.telegram(&text).await,
+                Channel::Webhook => self.webhook(kind, alarm).await,
+                Channel::Thingsboard => match (&mut self.tb, kind) {
+                    (Some(tb), Kind::Cleared) => tb.clear(&alarm.id).await,
+                    (Some(tb), _) => tb.create(alarm).await,
+                    (None, _) => Ok(()),
+                },
+                Channel::Email => self.email(kind, alarm).await,
+            };
+            if let Err(e) = result {
+                error!("Failed to send {:?} notification for {}: {:#}", channel, alarm.id, e);
+            }
+        }
+    }
+
+    fn text(&self, kind: Kind, alarm: &Alarm) -> String {
+        let icon = match (kind, alarm.severity) {
+            (Kind::Cleared, _) => "✅",
+            (Kind::Escalated, _) => "⏫",
+            (_, Severity::Critical) => "🚨",
+            (_, Severity::Warning) => "⚠️",
+            (_, Severity::Info) => "ℹ️",
+        };
+        let state = match kind {
+            Kind::Raised => String::new(),
+            Kind::Cleared => " cleared".to_string(),
+            Kind::Escalated => format!(" unacknowledged for {} min", self.config.escalate_after.as_secs() / 60),
+        };
+        format!("{} [{}] {}: {}{} ({})", icon, alarm.severity.as_str().to_uppercase(), self.device_id, alarm.message, state, alarm.id)
+    }
+
+    async fn telegram(&self, text: &str) -> Result<()> {
+        let telegram = &self.config.telegram;
+        let url = format!("{}/bot{}/sendMessage", telegram.api_url.trim_end_matches('/'), telegram.bot_token.expose());
+        // URL berisi token bot; jangan sampai ikut di pesan error
+        let response = self
+            .client
+            .post(url)
+            .json(&json!({ "chat_id": telegram.chat_id, "text": text }))
+            .send()
+            .await
+            .map_err(|e| anyhow!("Telegram request failed: {}", e.without_url()))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Telegram sendMessage failed: {}", response.status()));
+        }
+        Ok(())
+    }
+
+    async fn webhook(&self, kind: Kind, alarm: &Alarm) -> Result<()> {
+        let webhook = &self.config.webhook;
+        let body = json!({
+            "event": kind.as_str(),
+            "device_id": self.device_id,
+            "id": alarm.id,
+            "severity": alarm.severity.as_str(),
+            "message": alarm.message,
+            "ts_ms": crate::now_ns() / 1_000_000,
+        });
+        let mut request = self.client.post(&webhook.url).json(&body);
+        if !webhook.token.is_empty() {
+            request = request.header("Authorization", webhook.token.header("Bearer"));
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Webhook returned {}", response.status()));
+        }
+        Ok(())
+    }
+
+    async fn email(&self, kind: Kind, alarm: &Alarm) -> Result<()> {
+        let config = &self.config.email;
+        match config.compose(&self.device_id, kind.as_str(), alarm) {
+            Some(mail) => email::send(config, &mail).await,
+            None => Ok(()),
+        }
+    }
+
+    fn quiet(&self, severity: Severity) -> bool {
+        self.config.is_quiet(severity, crate::now_ns() / 1_000_000_000)
+    }
+
+    async fn on_change(&mut self, change: AlarmChange) {
+        match change {
+            AlarmChange::Raised(alarm) => {
+                let channels = self.config.route(alarm.severity).to_vec();
+                let held = !channels.is_empty() && self.quiet(alarm.severity);
+                if held {
+                    info!("🌙 Quiet hours: notification for {} held", alarm.id);
+                } else {
+                    self.deliver(&channels, Kind::Raised, &alarm).await;
+                }
+                let open = Open { alarm: alarm.clone(), since: Instant::now(), notified: !held, escalated: false };
+                self.open.insert(alarm.id, open);
+            }
+            AlarmChange::Cleared(alarm) => {
+                // Clear hanya dikirim jika raise-nya dulu terkirim
+                let notified = self.open.remove(&alarm.id).is_some_and(|open| open.notified);
+                if !notified {
+                    return;
+                }
+                if self.quiet(alarm.severity) {
+                    self.held_clears.push(alarm);
+                } else {
+                    let channels = self.config.route(alarm.severity).to_vec();
+                    self.deliver(&channels, Kind::Cleared, &alarm).await;
+                }
+            }
+        }
+    }
+
+    // Kirim yang ditahan setelah jam tenang selesai, lalu eskalasi alarm yang belum di-ack
+    async fn tick(&mut self, state: &AppState) {
+        // Alarm yang hilang tanpa clear (shelve/maintenance) tidak dilacak lagi; status ack dari AlarmManager
+        {
+            let alarms = state.alarms.lock().unwrap();
+            let acked: HashMap<&str, bool> = alarms.active().map(|a| (a.id.as_str(), a.acknowledged)).collect();
+            self.open.retain(|id, open| match acked.get(id.as_str()) {
+                Some(acknowledged) => {
+                    open.alarm.acknowledged = *acknowledged;
+                    true
+                }
+                None => false,
+            });
+        }
+
+        let mut pending = Vec::new();
+        for open in self.open.values_mut() {
+            if self.config.is_quiet(open.alarm.severity, crate::now_ns() / 1_000_000_000) {
+                continue;
+            }
+            if !open.notified {
+                open.notified = true;
+                pending.push((self.config.route(open.alarm.severity).to_vec(), Kind::Raised, open.alarm.clone()));
+            }
+            // Alarm yang tidak dirouting ke kanal mana pun (default INFO) tidak dieskalasi
+            let routed = !self.config.route(open.alarm.severity).is_empty();
+            let overdue = !self.config.escalate_after.is_zero() && open.since.elapsed() >= self.config.escalate_after;
+            if routed && overdue && !open.escalated && !open.alarm.acknowledged {
+                open.escalated = true;
+                warn!("⏫ Alarm {} unacknowledged for {}s, escalating", open.alarm.id, open.since.elapsed().as_secs());
+                pending.push((self.config.escalate_to.clone(), Kind::Escalated, open.alarm.clone()));
+            }
+        }
+        let (clears, held): (Vec<Alarm>, Vec<Alarm>) = std::mem::take(&mut self.held_clears).into_iter().partition(|a| !self.quiet(a.severity));
+        self.held_clears = held;
+        for alarm in clears {
+            pending.push((self.config.route(alarm.severity).to_vec(), Kind::Cleared, alarm));
+        }
+        for (channels, kind, alarm) in pending {
+            self.deliver(&channels, kind, &alarm).await;
+        }
+    }
+}
+
+// Task notifikasi: menerima perubahan alarm dari AppState, cek jam tenang dan eskalasi tiap 30 detik.
+// `tb` None = kanal "thingsboard" dilewati (tidak dipakai, atau sudah ditangani mirror).
+pub async fn run(client: Client, state: Arc<AppState>, config: NotifyConfig, tb: Option<TbAlarmClient>) {
+    let mut notifier = Notifier { config, client, device_id: state.device_id.clone(), tb, open: HashMap::new(), held_clears: Vec::new() };
+    let mut changes = state.subscribe_alarms();
+    let mut ticker = tokio::time::interval(Duration::from_secs(30));
+    loop {
+        tokio::select! {
+            change = changes.recv() => match change {
+                Ok(change) => notifier.on_change(change).await,
+                Err(RecvError::Lagged(missed)) => warn!("Notifier lagged, {} alarm change(s) not routed", missed),
+                Err(RecvError::Closed) => return,
+            },
+            _ = ticker.tick() => notifier.tick(&state).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn quiet(start: &str, end: &str) -> QuietHours {
+        QuietHours { start: start.to_string(), end: end.to_string(), min_severity: Severity::Critical }
+    }
+
+    #[test]
+    fn parses_clock_times() {
+        assert_eq!(parse_clock("00:00").unwrap(), 0);
+        assert_eq!(parse_clock("06:30").unwrap(), 390);
+        assert_eq!(parse_clock("23:59").unwrap(), 1439);
+        for bad in ["24:00", "12:60", "1230", "ab:cd", "", "-1:00"] {
+            assert!(parse_clock(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn quiet_hours_within_a_day_and_across_midnight() {
+        let day = quiet("12:00", "13:30");
+        assert!(!day.contains(719));
+        assert!(day.contains(720));
+        assert!(day.contains(809));
+        assert!(!day.contains(810));
+
+        let night = quiet("22:00", "06:00");
+        assert!(night.contains(1320));
+        assert!(night.contains(1439));
+        assert!(night.contains(0));
+        assert!(night.contains(359));
+        assert!(!night.contains(360));
+        assert!(!night.contains(1319));
+
+        // start == end = kosong; jam tidak valid = tidak pernah tenang
+        assert!(!quiet("08:00", "08:00").contains(480));
+        assert!(!quiet("22:00", "6").contains(0));
+    }
+
+    #[test]
+    fn is_quiet_applies_utc_offset_and_min_severity() {
+        let config = NotifyConfig { quiet_hours: Some(quiet("22:00", "06:00")), ..Default::default() };
+        // 15:30 UTC = 22:30 +07:00
+        let evening = 15 * 3600 + 30 * 60;
+        assert!(config.is_quiet(Severity::Warning, evening));
+        assert!(config.is_quiet(Severity::Info, evening));
+        assert!(!config.is_quiet(Severity::Critical, evening));
+        // 22:59 UTC = 05:59, 23:00 UTC = 06:00 (end tidak termasuk)
+        assert!(config.is_quiet(Severity::Warning, 22 * 3600 + 59 * 60));
+        assert!(!config.is_quiet(Severity::Warning, 23 * 3600));
+
+        // Offset negatif melewati tengah malam ke hari sebelumnya
+        let config = NotifyConfig { utc_offset: "-05:00".to_string(), ..config };
+        assert!(config.is_quiet(Severity::Warning, 3 * 3600 + 86_400));
+        assert!(!NotifyConfig::default().is_quiet(Severity::Info, evening));
+    }
+
+    #[test]
+    fn routes_by_severity() {
+        let config = NotifyConfig { info: vec![Channel::Email], escalate_to: vec![Channel::Webhook], ..Default::default() };
+        assert_eq!(config.route(Severity::Info), &[Channel::Email]);
+        assert_eq!(config.route(Severity::Warning), &[Channel::Telegram]);
+        assert!(config.uses(Channel::Thingsboard));
+        assert!(!NotifyConfig { critical: Vec::new(), escalate_to: Vec::new(), ..Default::default() }.uses(Channel::Webhook));
+    }
+
+    // Webhook lokal yang mencatat "<event>:<id>" setiap notifikasi
+    async fn webhook_sink() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let (mut request, mut buf) = (Vec::new(), [0u8; 4096]);
+                let body = loop {
+                    let n = stream.read(&mut buf).await.unwrap_or(0);
+                    if n == 0 {
+                        break None;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some(body) = text.split_once("\r\n\r\n").and_then(|(_, b)| serde_json::from_str::<serde_json::Value>(b).ok()) {
+                        break Some(body);
+                    }
+                };
+                if let Some(body) = body {
+                    seen.lock().unwrap().push(format!("{}:{}", body["event"].as_str().unwrap(), body["id"].as_str().unwrap()));
+                }
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").await;
+            }
+        });
+        (url, events)
+    }
+
+    // Jam tenang 10 menit mulai menit UTC sekarang
+    fn quiet_now() -> QuietHours {
+        let minute = (crate::now_ns() / 60_000_000_000 % 1440) as u32;
+        let clock = |m: u32| format!("{:02}:{:02}", m / 60 % 24, m % 60);
+        quiet(&clock(minute), &clock(minute + 10))
+    }
+
+    async fn notifier(escalate_after: Duration) -> (Notifier, AppState, Arc<Mutex<Vec<String>>>) {
+        let (url, events) = webhook_sink().await;
+        let config = NotifyConfig {
+            enabled: true,
+            warning: vec![Channel::Webhook],
+            critical: vec![Channel::Webhook],
+            utc_offset: "+00:00".to_string(),
+            quiet_hours: Some(quiet_now()),
+            escalate_after,
+            escalate_to: vec![Channel::Webhook],
+            webhook: WebhookConfig { url, ..Default::default() },
+            ..Default::default()
+        };
+        let client = Client::builder().no_proxy().build().unwrap();
+        let notifier = Notifier { config, client, device_id: "dcs-test".to_string(), tb: None, open: HashMap::new(), held_clears: Vec::new() };
+        (notifier, AppState::new(&Config::default()), events)
+    }
+
+    fn raise(state: &AppState, id: &str, severity: Severity) -> AlarmChange {
+        AlarmChange::Raised(state.alarms.lock().unwrap().raise(id, severity, format!("{id} test")).unwrap())
+    }
+
+    fn clear(state: &AppState, id: &str) -> AlarmChange {
+        AlarmChange::Cleared(state.alarms.lock().unwrap().clear(id).unwrap())
+    }
+
+    #[tokio::test]
+    async fn quiet_hours_hold_raise_until_they_end() {
+        let (mut notifier, state, events) = notifier(Duration::ZERO).await;
+        notifier.on_change(raise(&state, "high_temp", Severity::Warning)).await;
+        notifier.on_change(raise(&state, "sensor_fail", Severity::Critical)).await;
+        assert_eq!(*events.lock().unwrap(), vec!["raised:sensor_fail"]);
+
+        notifier.tick(&state).await;
+        assert_eq!(events.lock().unwrap().len(), 1);
+
+        notifier.config.quiet_hours = None;
+        notifier.tick(&state).await;
+        notifier.tick(&state).await;
+        assert_eq!(*events.lock().unwrap(), vec!["raised:sensor_fail", "raised:high_temp"]);
+    }
+
+    #[tokio::test]
+    async fn clear_of_held_raise_is_dropped_and_sent_clear_is_held() {
+        let (mut notifier, state, events) = notifier(Duration::ZERO).await;
+        let night = notifier.config.quiet_hours.take();
+        notifier.on_change(raise(&state, "high_temp", Severity::Warning)).await;
+        notifier.config.quiet_hours = night;
+        notifier.on_change(raise(&state, "low_humidity", Severity::Warning)).await;
+
+        // Raise high_temp sudah terkirim: clear ditahan. Raise low_humidity ditahan: clear dibuang.
+        notifier.on_change(clear(&state, "high_temp")).await;
+        notifier.on_change(clear(&state, "low_humidity")).await;
+        assert_eq!(notifier.held_clears.len(), 1);
+        assert!(notifier.open.is_empty());
+
+        notifier.tick(&state).await;
+        assert_eq!(*events.lock().unwrap(), vec!["raised:high_temp"]);
+        notifier.config.quiet_hours = None;
+        notifier.tick(&state).await;
+        assert_eq!(*events.lock().unwrap(), vec!["raised:high_temp", "cleared:high_temp"]);
+        assert!(notifier.held_clears.is_empty());
+    }
+
+    #[tokio::test]
+    async fn escalates_unacknowledged_alarms_once() {
+        let (mut notifier, state, events) = notifier(Duration::from_millis(200)).await;
+        notifier.config.quiet_hours = None;
+        notifier.on_change(raise(&state, "high_temp", Severity::Warning)).await;
+        notifier.on_change(raise(&state, "sensor_fail", Severity::Critical)).await;
+        notifier.on_change(raise(&state, "stale_esp32", Severity::Warning)).await;
+        state.alarms.lock().unwrap().acknowledge("sensor_fail");
+        // Hilang tanpa clear (mis. di-shelve): tidak dilacak lagi
+        state.alarms.lock().unwrap().clear("stale_esp32");
+
+        notifier.tick(&state).await;
+        assert_eq!(events.lock().unwrap().len(), 3);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        notifier.tick(&state).await;
+        notifier.tick(&state).await;
+        assert_eq!(events.lock().unwrap()[3..], ["escalated:high_temp".to_string()]);
+        assert!(!notifier.open.contains_key("stale_esp32"));
+    }
+}
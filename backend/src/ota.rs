@@ -0,0 +1,302 @@
+use anyhow::{anyhow, bail, Context, Result};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Digest;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::events::Event;
+use crate::secrets::Secret;
+use crate::state::AppState;
+
+// Relay OTA ThingsBoard ke ESP32. ThingsBoard mengumumkan firmware lewat shared attribute
+// fw_title/fw_version/fw_checksum/...; backend mengunduh image dengan token device, memverifikasi
+// checksum, menyajikannya di GET /ota/<file> lalu mengirim OTA|<url> ke ESP32 lewat serial
+// (ESP32 mengunduh sendiri lewat Wi-Fi, lihat sht20/src/ota.rs). Progres dilaporkan sebagai
+// telemetry fw_state (DOWNLOADING, DOWNLOADED, VERIFIED, UPDATING, UPDATED, FAILED + fw_error);
+// selesai = HELLO/DIAG setelah reboot melaporkan fw_version yang diminta.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OtaConfig {
+    pub enabled: bool,
+    // Base URL HTTP ThingsBoard untuk device API (GET /api/v1/<token>/firmware)
+    pub thingsboard_url: String,
+    // Base URL backend yang bisa dijangkau ESP32 lewat Wi-Fi (alamat [api] listen dari sisi device)
+    pub public_url: String,
+    // Folder image terverifikasi
+    pub dir: String,
+    // Batas ukuran image (partisi ota_0/ota_1)
+    pub max_size: u64,
+    // Batas waktu dari OTA| terkirim sampai ESP32 melaporkan versi baru
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub device_timeout: Duration,
+}
+
+impl Default for OtaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            thingsboard_url: String::new(),
+            public_url: String::new(),
+            dir: "firmware".to_string(),
+            max_size: 1_900_000,
+            device_timeout: Duration::from_secs(600),
+        }
+    }
+}
+
+// Shared attribute yang diminta saat MQTT connect
+pub const ATTRIBUTE_KEYS: [&str; 5] = ["fw_title", "fw_version", "fw_checksum", "fw_checksum_algorithm", "fw_size"];
+
+// Jeda polling status device dan target baru
+const POLL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FirmwareTarget {
+    pub title: String,
+    pub version: String,
+    pub checksum: String,
+    pub algorithm: String,
+    pub size: Option<u64>,
+}
+
+impl FirmwareTarget {
+    // Dari update shared attribute atau balasan attribute request ({"shared": {...}});
+    // None jika atribut firmware tidak ada atau tidak lengkap
+    pub fn from_attributes(attributes: &Value) -> Option<Self> {
+        let values = attributes.get("shared").unwrap_or(attributes);
+        let text = |key: &str| values.get(key)?.as_str().map(str::to_string);
+        Some(Self {
+            title: text("fw_title")?,
+            version: text("fw_version")?,
+            checksum: text("fw_checksum")?.to_ascii_lowercase(),
+            algorithm: text("fw_checksum_algorithm").unwrap_or_else(|| "SHA256".to_string()).to_ascii_uppercase(),
+            size: values.get("fw_size").and_then(Value::as_u64),
+        })
+    }
+
+    // Nama file aman untuk URL dan filesystem
+    fn file_name(&self) -> String {
+        let clean = |s: &str| s.chars().map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' }).collect::<String>();
+        format!("{}-{}.bin", clean(&self.title), clean(&self.version))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FwState {
+    Downloading,
+    Downloaded,
+    Verified,
+    Updating,
+    Updated,
+    Failed,
+}
+
+impl FwState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FwState::Downloading => "DOWNLOADING",
+            FwState::Downloaded => "DOWNLOADED",
+            FwState::Verified => "VERIFIED",
+            FwState::Updating => "UPDATING",
+            FwState::Updated => "UPDATED",
+            FwState::Failed => "FAILED",
+        }
+    }
+}
+
+// Bagian AppState yang dibagi thread MQTT, handler serial, task OTA dan REST API
+#[derive(Default)]
+pub struct OtaShared {
+    pub enabled: bool,
+    // fw_* terbaru dari ThingsBoard
+    pub target: Mutex<Option<FirmwareTarget>>,
+    // OTA_STATUS terakhir dari ESP32: (status, detail)
+    pub device_status: Mutex<Option<(String, String)>>,
+    // Image yang boleh diunduh ESP32 lewat GET /ota/<file>: (nama file, path)
+    pub staged: Mutex<Option<(String, PathBuf)>>,
+    // Baris OTA|<url> untuk link serial; dikosongkan begitu ESP32 mulai mengunduh
+    pub command: Arc<Mutex<String>>,
+    // fw_state terakhir (untuk /status)
+    pub state: Mutex<Option<(FwState, Option<String>)>>,
+}
+
+impl OtaShared {
+    pub fn new(config: &OtaConfig) -> Self {
+        Self { enabled: config.enabled, ..Default::default() }
+    }
+
+    // Dipanggil thread MQTT tujuan ThingsBoard utama untuk setiap shared attribute
+    pub fn apply_attributes(&self, attributes: &Value) {
+        if !self.enabled {
+            return;
+        }
+        let Some(target) = FirmwareTarget::from_attributes(attributes) else { return };
+        let mut current = self.target.lock().unwrap();
+        if current.as_ref() != Some(&target) {
+            info!("📦 ThingsBoard advertises firmware {} {}", target.title, target.version);
+            *current = Some(target);
+        }
+    }
+
+    pub fn staged_path(&self, file: &str) -> Option<PathBuf> {
+        match &*self.staged.lock().unwrap() {
+            Some((name, path)) if name == file => Some(path.clone()),
+            _ => None,
+        }
+    }
+}
+
+// Task OTA: satu update pada satu waktu; target yang berubah selama update diproses sesudahnya
+pub async fn run<F>(http: reqwest::Client, state: Arc<AppState>, config: OtaConfig, token: Secret, report: F)
+where
+    F: Fn(serde_json::Map<String, Value>) + Send + Sync + 'static,
+{
+    let mut handled: Option<FirmwareTarget> = None;
+    let mut reported_version: Option<String> = None;
+    loop {
+        tokio::time::sleep(POLL).await;
+        let current = state.firmware.lock().unwrap().get(&state.device_id).cloned();
+        if current.is_some() && current != reported_version {
+            let title = state.ota.target.lock().unwrap().as_ref().map(|t| t.title.clone());
+            let mut values = serde_json::Map::new();
+            values.insert("current_fw_version".into(), json!(current));
+            if let Some(title) = title {
+                values.insert("current_fw_title".into(), json!(title));
+            }
+            report(values);
+            reported_version = current.clone();
+        }
+
+        let Some(target) = state.ota.target.lock().unwrap().clone() else { continue };
+        if handled.as_ref() == Some(&target) {
+            continue;
+        }
+        handled = Some(target.clone());
+        if current.as_deref() == Some(target.version.as_str()) {
+            set_state(&state, &report, FwState::Updated, None);
+            continue;
+        }
+        let result = update(&http, &state, &config, &token, &target, &report).await;
+        state.ota.command.lock().unwrap().clear();
+        *state.ota.staged.lock().unwrap() = None;
+        if let Err(e) = result {
+            error!("❌ OTA {} {} failed: {:#}", target.title, target.version, e);
+            set_state(&state, &report, FwState::Failed, Some(format!("{:#}", e)));
+        }
+    }
+}
+
+async fn update<F>(http: &reqwest::Client, state: &AppState, config: &OtaConfig, token: &Secret, target: &FirmwareTarget, report: &F) -> Result<()>
+where
+    F: Fn(serde_json::Map<String, Value>),
+{
+    set_state(state, report, FwState::Downloading, None);
+    let url = format!("{}/api/v1/{}/firmware", config.thingsboard_url.trim_end_matches('/'), token.expose());
+    let resp = http
+        .get(&url)
+        .query(&[("title", target.title.as_str()), ("version", target.version.as_str())])
+        .timeout(Duration::from_secs(300))
+        .send()
+        .await
+        .map_err(|e| anyhow!("firmware download failed: {}", e.without_url()))?;
+    if !resp.status().is_success() {
+        bail!("firmware download failed: HTTP {}", resp.status());
+    }
+    let image = resp.bytes().await.map_err(|e| anyhow!("firmware download failed: {}", e.without_url()))?;
+    set_state(state, report, FwState::Downloaded, None);
+
+    if image.len() as u64 > config.max_size {
+        bail!("image is {} bytes, larger than max_size {}", image.len(), config.max_size);
+    }
+    if let Some(size) = target.size.filter(|size| *size != image.len() as u64) {
+        bail!("image is {} bytes but fw_size is {}", image.len(), size);
+    }
+    verify_checksum(&image, &target.algorithm, &target.checksum)?;
+    set_state(state, report, FwState::Verified, None);
+
+    let dir = PathBuf::from(&config.dir);
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let file = target.file_name();
+    let path = dir.join(&file);
+    let tmp = dir.join(format!("{file}.tmp"));
+    std::fs::write(&tmp, &image).and_then(|_| std::fs::rename(&tmp, &path)).with_context(|| format!("Failed to write {}", path.display()))?;
+    *state.ota.staged.lock().unwrap() = Some((file.clone(), path));
+
+    // Perintah dikirim link serial saat port terbuka; status lama dibuang supaya tidak salah baca
+    *state.ota.device_status.lock().unwrap() = None;
+    let command = format!("OTA|{}/ota/{}", config.public_url.trim_end_matches('/'), file);
+    info!("📦 Firmware {} {} verified ({} bytes), sending {} to ESP32", target.title, target.version, image.len(), command);
+    *state.ota.command.lock().unwrap() = command;
+
+    let started = Instant::now();
+    let mut updating = false;
+    loop {
+        tokio::time::sleep(POLL).await;
+        if state.firmware.lock().unwrap().get(&state.device_id).map(String::as_str) == Some(target.version.as_str()) {
+            info!("✅ ESP32 now runs firmware {} {}", target.title, target.version);
+            set_state(state, report, FwState::Updated, None);
+            return Ok(());
+        }
+        let status = state.ota.device_status.lock().unwrap().clone();
+        match status {
+            Some((status, detail)) if status == "failed" => bail!("device: {}", detail),
+            Some(_) if !updating => {
+                // ESP32 sudah mulai; perintah tidak dikirim ulang saat port dibuka lagi setelah reboot
+                updating = true;
+                state.ota.command.lock().unwrap().clear();
+                set_state(state, report, FwState::Updating, None);
+            }
+            _ => {}
+        }
+        if started.elapsed() >= config.device_timeout {
+            bail!("device did not report firmware {} within {}s", target.version, config.device_timeout.as_secs());
+        }
+    }
+}
+
+// fw_checksum dari ThingsBoard dalam hex; CRC32 diterima dalam urutan byte mana pun
+fn verify_checksum(image: &[u8], algorithm: &str, expected: &str) -> Result<()> {
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+    let actual = match algorithm {
+        "SHA256" => vec![hex(&sha2::Sha256::digest(image))],
+        "SHA384" => vec![hex(&sha2::Sha384::digest(image))],
+        "SHA512" => vec![hex(&sha2::Sha512::digest(image))],
+        "MD5" => vec![hex(&md5::Md5::digest(image))],
+        "CRC32" => {
+            let crc = crc32fast::hash(image);
+            vec![hex(&crc.to_be_bytes()), hex(&crc.to_le_bytes())]
+        }
+        other => bail!("unsupported checksum algorithm {}", other),
+    };
+    if !actual.iter().any(|a| a == expected) {
+        bail!("{} checksum mismatch: expected {}, got {}", algorithm, expected, actual[0]);
+    }
+    Ok(())
+}
+
+fn set_state<F>(state: &AppState, report: &F, fw_state: FwState, error: Option<String>)
+where
+    F: Fn(serde_json::Map<String, Value>),
+{
+    let mut values = serde_json::Map::new();
+    values.insert("fw_state".into(), json!(fw_state.as_str()));
+    if let Some(error) = &error {
+        values.insert("fw_error".into(), json!(error));
+    }
+    report(values);
+    if fw_state == FwState::Failed {
+        warn!("📦 OTA state {}", fw_state.as_str());
+    } else {
+        info!("📦 OTA state {}", fw_state.as_str());
+    }
+    let mut event = Event::new("firmware", &state.device_id, fw_state.as_str()).reason("ThingsBoard OTA");
+    if let Some(error) = &error {
+        event = event.reason(error.clone());
+    }
+    state.events.record(event);
+    *state.ota.state.lock().unwrap() = Some((fw_state, error));
+}
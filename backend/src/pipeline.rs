@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::config::Config;
+use crate::events::Event;
+use crate::ingest::{Ingest, Sample};
+use crate::serial::{AuxReading, Diagnostics, SensorData};
+use crate::state::AppState;
+
+// Tujuan sampel yang lolos ingest: InfluxDB/ThingsBoard, Kafka/NATS, atau milik embedder
+pub trait SampleSink: Send {
+    fn publish(&self, sample: &Sample);
+
+    // Frame DIAG kesehatan device; sink yang tidak peduli cukup mengabaikannya
+    fn publish_diagnostics(&self, _device_id: &str, _diag: &Diagnostics) {}
+
+    // Frame AUX_DATA slave tambahan (kelembaban tanah, level air); tanpa validasi/filter.
+    // slave = alamat RS485 pengirim, None = kanal device itu sendiri
+    fn publish_aux(&self, _device_id: &str, _slave: Option<u8>, _data: &AuxReading) {}
+}
+
+// Sumber data sensor (serial, MQTT, ...); setiap titik diteruskan ke pipeline bersama
+pub trait SensorSource: Send + 'static {
+    fn start(self: Box<Self>, pipeline: SharedPipeline);
+}
+
+pub type SharedPipeline = Arc<Mutex<Pipeline>>;
+
+// Validasi, kalibrasi, filter dan alarm per device, lalu fan-out ke semua sink
+pub struct Pipeline {
+    config: Config,
+    state: Arc<AppState>,
+    // Tiap device punya state filter, clock dan gap sendiri
+    ingests: HashMap<String, Ingest>,
+    sinks: Vec<Box<dyn SampleSink>>,
+}
+
+impl Pipeline {
+    pub fn new(config: Config, state: Arc<AppState>) -> Self {
+        Self { config, state, ingests: HashMap::new(), sinks: Vec::new() }
+    }
+
+    pub fn add_sink(&mut self, sink: impl SampleSink + 'static) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    pub fn state(&self) -> &Arc<AppState> {
+        &self.state
+    }
+
+    // None berarti titik dibuang (outlier yang ditolak)
+    pub fn process(&mut self, device_id: &str, data: SensorData) -> Option<Sample> {
+        let ingest = self
+            .ingests
+            .entry(device_id.to_string())
+            .or_insert_with(|| Ingest::for_device(&self.config, self.state.clone(), device_id));
+        let sample = ingest.process(data)?;
+        for sink in &self.sinks {
+            sink.publish(&sample);
+        }
+        Some(sample)
+    }
+
+    pub fn diagnostics(&self, device_id: &str, diag: &Diagnostics) {
+        if let Some(version) = &diag.firmware {
+            if let Some(old) = self.state.note_firmware(device_id, version) {
+                log::info!("🆙 {} firmware changed: {} -> {}", device_id, old, version);
+                self.state.events.record(Event::new("firmware", device_id, version.clone()).old(old).reason("DIAG from device"));
+            }
+        }
+        for sink in &self.sinks {
+            sink.publish_diagnostics(device_id, diag);
+        }
+    }
+
+    pub fn aux(&self, device_id: &str, slave: Option<u8>, data: &AuxReading) {
+        for sink in &self.sinks {
+            sink.publish_aux(device_id, slave, data);
+        }
+    }
+
+    // Sampel dari buffer flash device: timestamp asli, tanpa filter/alarm
+    pub fn process_backlog(&mut self, device_id: &str, data: SensorData) -> Sample {
+        let ingest = self
+            .ingests
+            .entry(device_id.to_string())
+            .or_insert_with(|| Ingest::for_device(&self.config, self.state.clone(), device_id));
+        let sample = ingest.backlog(data);
+        for sink in &self.sinks {
+            sink.publish(&sample);
+        }
+        sample
+    }
+
+    pub fn shared(self) -> SharedPipeline {
+        Arc::new(Mutex::new(self))
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::Path;
+
+use crate::secrets::{self, Secret};
+
+// Provisioning device ThingsBoard: token akses diminta sekali saat boot pertama
+// lalu disimpan di `token_cache`, sehingga tiap instalasi tidak perlu TB_TOKEN sendiri
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ProvisionConfig {
+    // Base URL HTTP ThingsBoard (endpoint /api/v1/provision)
+    pub url: String,
+    // Nama device baru di ThingsBoard; kosong = device_id
+    pub device_name: String,
+    // Bisa juga lewat env TB_PROVISION_KEY(_FILE) / TB_PROVISION_SECRET(_FILE)
+    pub key: Secret,
+    pub secret: Secret,
+    pub token_cache: String,
+}
+
+impl Default for ProvisionConfig {
+    fn default() -> Self {
+        Self {
+            url: "https://demo.thingsboard.io".to_string(),
+            device_name: String::new(),
+            key: Secret::default(),
+            secret: Secret::default(),
+            token_cache: "tb_token".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProvisionResponse {
+    status: String,
+    #[serde(default)]
+    credentials_type: String,
+    #[serde(default)]
+    credentials_value: Secret,
+    #[serde(default)]
+    error_msg: String,
+}
+
+// Token dari cache jika ada; jika belum, minta ke ThingsBoard dan simpan
+pub async fn access_token(client: &Client, config: &ProvisionConfig, device_id: &str) -> Result<Secret> {
+    if let Some(token) = read_cache(&config.token_cache)? {
+        info!("🔑 Using cached ThingsBoard access token from {}", config.token_cache);
+        return Ok(token);
+    }
+    let key = secrets::load_or("TB_PROVISION_KEY", &config.key)?;
+    let secret = secrets::load_or("TB_PROVISION_SECRET", &config.secret)?;
+    if key.is_empty() || secret.is_empty() {
+        return Err(anyhow!(
+            "Provisioning key and secret are required: set [provision] key/secret or TB_PROVISION_KEY/TB_PROVISION_SECRET (or *_FILE)"
+        ));
+    }
+
+    let device_name = if config.device_name.is_empty() { device_id } else { config.device_name.as_str() };
+    info!("🆕 Provisioning ThingsBoard device '{}' at {}", device_name, config.url);
+    let body = json!({
+        "deviceName": device_name,
+        "provisionDeviceKey": key.expose(),
+        "provisionDeviceSecret": secret.expose(),
+    });
+    let url = format!("{}/api/v1/provision", config.url.trim_end_matches('/'));
+    let response: ProvisionResponse = client
+        .post(&url)
+        .json(&body)
+        .send()
+        .await
+        .context("ThingsBoard provisioning request failed")?
+        .error_for_status()
+        .context("ThingsBoard provisioning request rejected")?
+        .json()
+        .await
+        .context("Malformed ThingsBoard provisioning response")?;
+
+    if response.status != "SUCCESS" {
+        return Err(anyhow!("ThingsBoard provisioning failed: {} {}", response.status, response.error_msg));
+    }
+    if response.credentials_type != "ACCESS_TOKEN" || response.credentials_value.is_empty() {
+        return Err(anyhow!(
+            "ThingsBoard provisioning returned unsupported credentials type '{}'",
+            response.credentials_type
+        ));
+    }
+
+    let token = response.credentials_value;
+    write_cache(&config.token_cache, &token)?;
+    info!("✅ Device provisioned, access token cached in {}", config.token_cache);
+    Ok(token)
+}
+
+fn read_cache(path: &str) -> Result<Option<Secret>> {
+    if !Path::new(path).exists() {
+        return Ok(None);
+    }
+    let token = std::fs::read_to_string(path).with_context(|| format!("Failed to read token cache {}", path))?;
+    let token = Secret::new(token);
+    if token.is_empty() {
+        warn!("Token cache {} is empty, provisioning again", path);
+        return Ok(None);
+    }
+    Ok(Some(token))
+}
+
+// File cache hanya bisa dibaca pemiliknya: ditulis ke file sementara yang sejak dibuat
+// sudah 0600, lalu di-rename, supaya token tidak pernah terbaca user lain walau sesaat
+fn write_cache(path: &str, token: &Secret) -> Result<()> {
+    use std::io::Write;
+
+    let tmp = format!("{path}.tmp");
+    let _ = std::fs::remove_file(&tmp);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&tmp).with_context(|| format!("Failed to create token cache {}", tmp))?;
+    file.write_all(format!("{}\n", token.expose()).as_bytes())
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("Failed to write token cache {}", tmp))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace token cache {}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_cache_round_trips_and_is_private() {
+        let dir = std::env::temp_dir().join(format!("dcs-provision-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tb_token");
+        let path = path.to_str().unwrap();
+        assert!(read_cache(path).unwrap().is_none());
+
+        write_cache(path, &Secret::new("first")).unwrap();
+        write_cache(path, &Secret::new("A1b2C3")).unwrap();
+        assert_eq!(read_cache(path).unwrap().unwrap().expose(), "A1b2C3");
+        assert!(!Path::new(&format!("{path}.tmp")).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
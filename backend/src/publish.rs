@@ -0,0 +1,402 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+use crate::metrics::{self, METRICS};
+use crate::state::AppState;
+
+// Jeda antar percobaan ulang selama breaker masih tertutup
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+// Antrian dan circuit breaker per sink (InfluxDB, ThingsBoard)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BreakerConfig {
+    // Kapasitas antrian antara sumber data dan sink
+    pub queue: usize,
+    // Pesan yang dibuang saat antrian penuh
+    pub overflow: Overflow,
+    // Kegagalan berturut-turut sebelum breaker terbuka
+    pub failure_threshold: u32,
+    // Lama breaker terbuka sebelum mencoba lagi (half-open)
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub open_for: Duration,
+    // Percobaan untuk pesan yang ditolak permanen (Rejected) sebelum dibuang ke dead letter
+    pub max_rejections: u32,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self { queue: 1000, failure_threshold: 3, open_for: Duration::from_secs(30), overflow: Overflow::DropNewest, max_rejections: 3 }
+    }
+}
+
+// Sink menjawab tetapi menolak pesan ini (HTTP 4xx, payload tidak valid): mengulang tidak akan
+// berhasil, jadi pesan tidak boleh menahan kepala antrian atau membuka breaker
+#[derive(Debug)]
+pub struct Rejected(pub String);
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+pub fn is_rejected(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<Rejected>().is_some()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Overflow {
+    // Pesan baru ditolak, antrian tetap berisi data tertua (backfill lengkap setelah outage singkat)
+    #[default]
+    DropNewest,
+    // Pesan tertua ditimpa, antrian selalu berisi data terbaru
+    DropOldest,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PublishConfig {
+    pub influx: BreakerConfig,
+    pub thingsboard: BreakerConfig,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    #[default]
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl BreakerState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open => "open",
+            BreakerState::HalfOpen => "half_open",
+        }
+    }
+}
+
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+    state: BreakerState,
+    failures: u32,
+    opened_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: BreakerConfig) -> Self {
+        Self { config, state: BreakerState::Closed, failures: 0, opened_at: None }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.state
+    }
+
+    // Breaker terbuka pindah ke half-open setelah open_for berlalu
+    pub fn allow(&mut self, now: Instant) -> bool {
+        if self.state == BreakerState::Open && self.retry_in(now).is_zero() {
+            self.state = BreakerState::HalfOpen;
+        }
+        self.state != BreakerState::Open
+    }
+
+    pub fn retry_in(&self, now: Instant) -> Duration {
+        match self.opened_at {
+            Some(at) if self.state == BreakerState::Open => {
+                self.config.open_for.saturating_sub(now.saturating_duration_since(at))
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn success(&mut self) {
+        self.state = BreakerState::Closed;
+        self.failures = 0;
+        self.opened_at = None;
+    }
+
+    // true jika kegagalan ini membuka breaker
+    pub fn failure(&mut self, now: Instant) -> bool {
+        self.failures += 1;
+        let trip = self.state == BreakerState::HalfOpen || self.failures >= self.config.failure_threshold;
+        if trip {
+            self.state = BreakerState::Open;
+            self.opened_at = Some(now);
+        }
+        trip
+    }
+}
+
+// Status kesehatan satu sink untuk REST API dan metrik
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SinkHealth {
+    pub state: BreakerState,
+    pub queued: usize,
+    pub capacity: usize,
+    // Kedalaman antrian tertinggi sejak start
+    pub peak_queued: usize,
+    pub overflow: Overflow,
+    pub delivered: u64,
+    pub failed: u64,
+    pub dropped: u64,
+    // Pesan ditolak permanen dan dibuang setelah max_rejections percobaan
+    pub dead_lettered: u64,
+    pub last_error: Option<String>,
+}
+
+// Antrian terbatas antara sumber dan task pengirim satu sink; satu konsumen
+struct Queue<T> {
+    // Waktu masuk antrian, untuk histogram latensi pengiriman
+    items: Mutex<VecDeque<(Instant, T)>>,
+    ready: Notify,
+    capacity: usize,
+    overflow: Overflow,
+}
+
+impl<T> Queue<T> {
+    async fn pop(&self) -> (Instant, T) {
+        loop {
+            if let Some(item) = self.items.lock().unwrap().pop_front() {
+                return item;
+            }
+            self.ready.notified().await;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.items.lock().unwrap().len()
+    }
+}
+
+// Handle antrian sink; push tidak pernah menunggu sink yang lambat
+#[derive(Clone)]
+pub struct Outbox<T> {
+    name: String,
+    queue: Arc<Queue<T>>,
+    health: Arc<Mutex<SinkHealth>>,
+}
+
+impl<T> Outbox<T> {
+    // Err hanya jika pesan ini sendiri dibuang (drop_newest); pesan tertua yang ditimpa cukup dihitung di `dropped`
+    pub fn push(&self, item: T) -> Result<()> {
+        let mut items = self.queue.items.lock().unwrap();
+        let full = items.len() >= self.queue.capacity;
+        let accepted = !full || self.queue.overflow == Overflow::DropOldest;
+        if accepted {
+            if full {
+                items.pop_front();
+            }
+            items.push_back((Instant::now(), item));
+        }
+        let queued = items.len();
+        drop(items);
+
+        let mut health = self.health.lock().unwrap();
+        health.queued = queued;
+        health.peak_queued = health.peak_queued.max(queued);
+        if full {
+            health.dropped += 1;
+        }
+        if !accepted {
+            return Err(anyhow!("{} queue full ({} pending, breaker {}), message dropped", self.name, health.queued, health.state.as_str()));
+        }
+        drop(health);
+        self.queue.ready.notify_one();
+        Ok(())
+    }
+}
+
+// Jalankan task pengirim untuk satu sink dan daftarkan kesehatannya di AppState
+pub fn spawn<T, F, Fut>(name: impl Into<String>, config: BreakerConfig, state: &AppState, deliver: F) -> Outbox<T>
+where
+    T: Clone + Send + 'static,
+    F: Fn(T) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<()>> + Send,
+{
+    let name = name.into();
+    let capacity = config.queue.max(1);
+    let queue = Arc::new(Queue::<T> { items: Mutex::new(VecDeque::with_capacity(capacity)), ready: Notify::new(), capacity, overflow: config.overflow });
+    let health = Arc::new(Mutex::new(SinkHealth { capacity, overflow: config.overflow, ..Default::default() }));
+    state.sinks.lock().unwrap().insert(name.clone(), health.clone());
+
+    let task_health = health.clone();
+    let task_name = name.clone();
+    let task_queue = queue.clone();
+    tokio::spawn(async move {
+        let max_rejections = config.max_rejections.max(1);
+        let mut breaker = CircuitBreaker::new(config);
+        loop {
+            let (queued_at, item) = task_queue.pop().await;
+            // Gangguan sementara dicoba ulang tanpa batas (selama breaker terbuka antrian yang
+            // menampung); penolakan permanen dibuang setelah max_rejections percobaan
+            let mut rejections = 0;
+            loop {
+                let now = Instant::now();
+                if !breaker.allow(now) {
+                    tokio::time::sleep(breaker.retry_in(now)).await;
+                    continue;
+                }
+                let result = deliver(item.clone()).await;
+                let done = {
+                    let mut health = task_health.lock().unwrap();
+                    health.queued = task_queue.len();
+                    let done = match result {
+                        Ok(()) => {
+                            if breaker.state() != BreakerState::Closed {
+                                info!("✅ {} sink recovered, circuit closed", task_name);
+                            }
+                            breaker.success();
+                            health.delivered += 1;
+                            METRICS.observe(&metrics::SINK_DELIVERY, &task_name, queued_at.elapsed());
+                            true
+                        }
+                        Err(e) if is_rejected(&e) => {
+                            // Sink terjangkau: breaker tidak dihitung gagal
+                            breaker.success();
+                            health.failed += 1;
+                            health.last_error = Some(e.to_string());
+                            rejections += 1;
+                            if rejections >= max_rejections {
+                                health.dead_lettered += 1;
+                                warn!("☠️  {} sink rejected a message {} time(s), dropping it: {}", task_name, rejections, e);
+                            }
+                            rejections >= max_rejections
+                        }
+                        Err(e) => {
+                            health.failed += 1;
+                            health.last_error = Some(e.to_string());
+                            if breaker.failure(Instant::now()) {
+                                warn!("🔌 {} sink circuit open for {}s: {}", task_name, breaker.retry_in(Instant::now()).as_secs(), e);
+                            }
+                            false
+                        }
+                    };
+                    health.state = breaker.state();
+                    done
+                };
+                if done {
+                    break;
+                }
+                if breaker.state() == BreakerState::Closed {
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    });
+
+    Outbox { name, queue, health }
+}
+
+// Beberapa outbox yang menerima item yang sama (mis. beberapa tujuan ThingsBoard).
+// Setiap outbox tetap punya antrian dan breaker sendiri; satu tujuan penuh tidak menahan yang lain.
+#[derive(Clone)]
+pub struct Fanout<T>(Vec<Outbox<T>>);
+
+impl<T: Clone> Fanout<T> {
+    pub fn new(outboxes: Vec<Outbox<T>>) -> Self {
+        Self(outboxes)
+    }
+
+    // Err berisi semua tujuan yang menolak item; tujuan lain tetap menerima
+    pub fn push(&self, item: T) -> Result<()> {
+        let errors: Vec<String> = self.0.iter().filter_map(|outbox| outbox.push(item.clone()).err()).map(|e| e.to_string()).collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(errors.join("; ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(BreakerConfig { failure_threshold: 2, open_for: Duration::from_secs(30), ..BreakerConfig::default() })
+    }
+
+    #[test]
+    fn breaker_opens_after_threshold() {
+        let (mut breaker, now) = (breaker(), Instant::now());
+        assert!(breaker.allow(now));
+        assert!(!breaker.failure(now));
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.failure(now));
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.allow(now + Duration::from_secs(29)));
+        assert_eq!(breaker.retry_in(now + Duration::from_secs(10)), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn breaker_half_open_then_closed() {
+        let (mut breaker, now) = (breaker(), Instant::now());
+        breaker.failure(now);
+        breaker.failure(now);
+        let later = now + Duration::from_secs(30);
+        assert!(breaker.allow(later));
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        breaker.success();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        // Hitungan kegagalan mulai lagi dari 0 setelah pulih
+        assert!(!breaker.failure(later));
+    }
+
+    #[test]
+    fn half_open_failure_reopens() {
+        let (mut breaker, now) = (breaker(), Instant::now());
+        breaker.failure(now);
+        breaker.failure(now);
+        let probe = now + Duration::from_secs(31);
+        assert!(breaker.allow(probe));
+        assert!(breaker.failure(probe));
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert_eq!(breaker.retry_in(probe), Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn rejected_message_is_dead_lettered() {
+        let state = AppState::new(&Config::default());
+        let attempts = Arc::new(AtomicU32::new(0));
+        let counter = attempts.clone();
+        let config = BreakerConfig { max_rejections: 2, ..BreakerConfig::default() };
+        let outbox = spawn("test", config, &state, move |item: &'static str| {
+            let counter = counter.clone();
+            async move {
+                if item == "poison" {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    return Err(Rejected("400 Bad Request".to_string()).into());
+                }
+                Ok(())
+            }
+        });
+        outbox.push("poison").unwrap();
+        outbox.push("ok").unwrap();
+        for _ in 0..50 {
+            if outbox.health.lock().unwrap().delivered == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let health = outbox.health.lock().unwrap().clone();
+        assert_eq!((health.delivered, health.dead_lettered), (1, 1));
+        assert_eq!(health.state, BreakerState::Closed);
+        assert_eq!(attempts.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,98 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    Good,
+    Stale,
+    Interpolated,
+    SensorFault,
+}
+
+impl Quality {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Quality::Good => "good",
+            Quality::Stale => "stale",
+            Quality::Interpolated => "interpolated",
+            Quality::SensorFault => "sensor_fault",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QualityConfig {
+    // Interval sampling yang diharapkan dari firmware
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub expected_interval: Duration,
+    // Gap dianggap terjadi jika jarak antar sampel > expected_interval * tolerance
+    pub tolerance: f64,
+    // Gap pendek (<= sekian sampel hilang) diisi titik interpolasi linear
+    pub max_interpolated: u32,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            expected_interval: Duration::from_secs(10),
+            tolerance: 1.5,
+            max_interpolated: 3,
+        }
+    }
+}
+
+impl QualityConfig {
+    pub fn stale_after(&self) -> Duration {
+        self.expected_interval.mul_f64(self.tolerance)
+    }
+}
+
+// Hasil deteksi gap antara sampel sebelumnya dan sampel baru
+#[derive(Debug, Clone, Default)]
+pub struct Gap {
+    pub seconds: f64,
+    pub missed: u32,
+    // Titik (timestamp ns, temperature, humidity) untuk mengisi gap pendek
+    pub interpolated: Vec<(u64, f32, f32)>,
+}
+
+pub struct GapDetector {
+    config: QualityConfig,
+    last: Option<(u64, f32, f32)>,
+}
+
+impl GapDetector {
+    pub fn new(config: QualityConfig) -> Self {
+        Self { config, last: None }
+    }
+
+    pub fn observe(&mut self, ts_ns: u64, temperature: f32, humidity: f32) -> Option<Gap> {
+        let previous = self.last.replace((ts_ns, temperature, humidity));
+        let (last_ts, last_t, last_h) = previous?;
+        if ts_ns <= last_ts {
+            return None;
+        }
+
+        let elapsed = Duration::from_nanos(ts_ns - last_ts);
+        if elapsed <= self.config.stale_after() {
+            return None;
+        }
+
+        let interval = self.config.expected_interval.as_nanos().max(1) as u64;
+        let missed = ((ts_ns - last_ts) / interval).saturating_sub(1) as u32;
+        let mut gap = Gap { seconds: elapsed.as_secs_f64(), missed, interpolated: Vec::new() };
+
+        if missed > 0 && missed <= self.config.max_interpolated {
+            for i in 1..=missed {
+                let frac = i as f32 / (missed + 1) as f32;
+                gap.interpolated.push((
+                    last_ts + interval * i as u64,
+                    last_t + (temperature - last_t) * frac,
+                    last_h + (humidity - last_h) * frac,
+                ));
+            }
+        }
+        Some(gap)
+    }
+}
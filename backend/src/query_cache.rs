@@ -0,0 +1,257 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+// Cache hasil query Flux per teks query. Loop utama, REST API dan dashboard sering meminta
+// query yang sama; selama TTL hasilnya dipakai ulang, dan query identik yang sedang berjalan
+// ditunggu bersama sehingga hanya satu request HTTP ke InfluxDB. Query gagal tidak di-cache.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QueryCacheConfig {
+    pub enabled: bool,
+    // Umur maksimum hasil; "0s" = hanya penggabungan query yang sedang berjalan
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub ttl: Duration,
+    pub max_entries: usize,
+}
+
+impl Default for QueryCacheConfig {
+    fn default() -> Self {
+        Self { enabled: true, ttl: Duration::from_secs(5), max_entries: 256 }
+    }
+}
+
+// Ok = (selesai kapan, hasil); Err = pesan error yang dibagikan ke semua penunggu.
+// Hasil bertipe bebas (baris Flux, JSON /api/series); key menentukan tipenya.
+type Outcome = std::result::Result<(Instant, Arc<dyn Any + Send + Sync>), String>;
+
+struct QueryCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<String, Arc<OnceCell<Outcome>>>>,
+    hits: AtomicU64,
+    coalesced: AtomicU64,
+    misses: AtomicU64,
+}
+
+static CACHE: OnceLock<QueryCache> = OnceLock::new();
+
+// Dipanggil sekali saat startup; tanpa install (mis. subcommand) semua query langsung ke InfluxDB
+pub fn install(config: &QueryCacheConfig) {
+    if config.enabled {
+        let _ = CACHE.set(QueryCache::new(config));
+    }
+}
+
+// Hasil `fetch` untuk query ini, dari cache jika masih segar
+pub async fn fetch<T, F, Fut>(flux: &str, fetch: F) -> Result<T>
+where
+    T: Clone + Send + Sync + 'static,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    match CACHE.get() {
+        Some(cache) => cache.fetch(flux, fetch).await,
+        None => fetch().await,
+    }
+}
+
+impl QueryCache {
+    fn new(config: &QueryCacheConfig) -> Self {
+        Self {
+            ttl: config.ttl,
+            max_entries: config.max_entries.max(1),
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            coalesced: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    async fn fetch<T, F, Fut>(&self, flux: &str, fetch: F) -> Result<T>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let cell = {
+            let mut entries = self.entries.lock().unwrap();
+            match entries.get(flux) {
+                Some(cell) if self.fresh(cell) => {
+                    let counter = if cell.initialized() { &self.hits } else { &self.coalesced };
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    cell.clone()
+                }
+                _ => {
+                    self.misses.fetch_add(1, Ordering::Relaxed);
+                    if entries.len() >= self.max_entries {
+                        self.evict(&mut entries);
+                    }
+                    let cell = Arc::new(OnceCell::new());
+                    entries.insert(flux.to_string(), cell.clone());
+                    cell
+                }
+            }
+        };
+        let outcome = cell
+            .get_or_init(|| async {
+                fetch().await.map(|value| (Instant::now(), Arc::new(value) as Arc<dyn Any + Send + Sync>)).map_err(|e| e.to_string())
+            })
+            .await;
+        match outcome {
+            Ok((_, value)) => value.downcast_ref::<T>().cloned().ok_or_else(|| anyhow!("query cache entry holds a different result type")),
+            Err(e) => {
+                // Query berikutnya mencoba lagi ke InfluxDB
+                let mut entries = self.entries.lock().unwrap();
+                if entries.get(flux).is_some_and(|current| Arc::ptr_eq(current, &cell)) {
+                    entries.remove(flux);
+                }
+                Err(anyhow!("{}", e))
+            }
+        }
+    }
+
+    // Masih berjalan, atau selesai sukses dalam TTL
+    fn fresh(&self, cell: &OnceCell<Outcome>) -> bool {
+        match cell.get() {
+            None => true,
+            Some(Ok((at, _))) => at.elapsed() < self.ttl,
+            Some(Err(_)) => false,
+        }
+    }
+
+    // Buang hasil kedaluwarsa; jika masih penuh, buang hasil selesai yang paling lama
+    fn evict(&self, entries: &mut HashMap<String, Arc<OnceCell<Outcome>>>) {
+        entries.retain(|_, cell| self.fresh(cell));
+        while entries.len() >= self.max_entries {
+            let oldest = entries
+                .iter()
+                .filter_map(|(flux, cell)| match cell.get() {
+                    Some(Ok((at, _))) => Some((*at, flux.clone())),
+                    _ => None,
+                })
+                .min();
+            match oldest {
+                Some((_, flux)) => {
+                    entries.remove(&flux);
+                }
+                // Semua query masih berjalan; entri baru tetap ditambahkan
+                None => break,
+            }
+        }
+    }
+}
+
+// Counter cache untuk GET /metrics; kosong jika cache tidak aktif
+pub fn render_metrics() -> String {
+    let Some(cache) = CACHE.get() else { return String::new() };
+    let name = "dcs_influx_query_cache_total";
+    let mut out = String::new();
+    let _ = writeln!(out, "# HELP {name} Flux queries by cache result (hit = cached body, coalesced = joined an in-flight query, miss = sent to InfluxDB)");
+    let _ = writeln!(out, "# TYPE {name} counter");
+    for (result, counter) in [("hit", &cache.hits), ("coalesced", &cache.coalesced), ("miss", &cache.misses)] {
+        let _ = writeln!(out, "{name}{{result=\"{result}\"}} {}", counter.load(Ordering::Relaxed));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn with_ttl(ttl: Duration, max_entries: usize) -> Arc<QueryCache> {
+        Arc::new(QueryCache::new(&QueryCacheConfig { enabled: true, ttl, max_entries }))
+    }
+
+    // Loader yang menghitung request "upstream" dan butuh waktu 50 ms
+    async fn load(calls: &AtomicUsize, value: u32) -> Result<u32> {
+        calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Ok(value)
+    }
+
+    #[tokio::test]
+    async fn concurrent_identical_queries_share_one_request() {
+        let cache = with_ttl(Duration::from_secs(60), 16);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..8)
+            .map(|i| {
+                let (cache, calls) = (cache.clone(), calls.clone());
+                tokio::spawn(async move { cache.fetch("from(bucket: \"dcs\")", || load(&calls, i)).await })
+            })
+            .collect();
+        let mut values = Vec::new();
+        for task in tasks {
+            values.push(task.await.unwrap().unwrap());
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(values.iter().all(|v| *v == values[0]));
+        assert_eq!(cache.misses.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.coalesced.load(Ordering::Relaxed) + cache.hits.load(Ordering::Relaxed), 7);
+
+        // Query berbeda tidak ikut digabung
+        assert_eq!(cache.fetch("other", || load(&calls, 99)).await.unwrap(), 99);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn entries_expire_after_ttl() {
+        let cache = with_ttl(Duration::from_millis(200), 16);
+        let calls = AtomicUsize::new(0);
+        assert_eq!(cache.fetch("q", || load(&calls, 1)).await.unwrap(), 1);
+        assert_eq!(cache.fetch("q", || load(&calls, 2)).await.unwrap(), 1);
+        assert_eq!((calls.load(Ordering::SeqCst), cache.hits.load(Ordering::Relaxed)), (1, 1));
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(cache.fetch("q", || load(&calls, 3)).await.unwrap(), 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // TTL nol: hanya query yang sedang berjalan yang digabung
+        let uncached = with_ttl(Duration::ZERO, 16);
+        uncached.fetch("q", || load(&calls, 4)).await.unwrap();
+        uncached.fetch("q", || load(&calls, 5)).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn failures_are_not_cached() {
+        let cache = with_ttl(Duration::from_secs(60), 16);
+        let calls = AtomicUsize::new(0);
+        let error = cache
+            .fetch::<u32, _, _>("q", || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(anyhow!("influx unavailable"))
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "influx unavailable");
+        assert_eq!(cache.fetch("q", || load(&calls, 7)).await.unwrap(), 7);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Tipe hasil berbeda untuk key yang sama
+        assert!(cache.fetch("q", || async { Ok("text".to_string()) }).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn full_cache_evicts_oldest_result() {
+        let cache = with_ttl(Duration::from_secs(60), 2);
+        let calls = AtomicUsize::new(0);
+        cache.fetch("a", || load(&calls, 1)).await.unwrap();
+        cache.fetch("b", || load(&calls, 2)).await.unwrap();
+        cache.fetch("c", || load(&calls, 3)).await.unwrap();
+        assert_eq!(cache.entries.lock().unwrap().len(), 2);
+
+        cache.fetch("b", || load(&calls, 0)).await.unwrap();
+        cache.fetch("a", || load(&calls, 0)).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,70 @@
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::alarms::Severity;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Rise,
+    Fall,
+}
+
+// Alarm laju perubahan: `field` berubah lebih dari `change` dalam `window`
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateAlarmRule {
+    pub name: String,
+    pub field: String,
+    pub direction: Direction,
+    pub change: f64,
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub window: Duration,
+    #[serde(default = "default_severity")]
+    pub severity: Severity,
+}
+
+fn default_severity() -> Severity {
+    Severity::Warning
+}
+
+// Riwayat nilai satu field dalam jendela waktu aturan
+pub struct RateTracker {
+    pub rule: RateAlarmRule,
+    history: VecDeque<(u64, f64)>,
+    active: bool,
+}
+
+impl RateTracker {
+    pub fn new(rule: RateAlarmRule) -> Self {
+        Self { rule, history: VecDeque::new(), active: false }
+    }
+
+    // Some(true/false) saat status alarm berubah, beserta perubahan terukur
+    pub fn observe(&mut self, ts_ns: u64, value: f64) -> Option<(bool, f64)> {
+        let window_ns = self.rule.window.as_nanos() as u64;
+        self.history.push_back((ts_ns, value));
+        // Simpan satu titik tepat di/sebelum batas jendela sebagai acuan
+        while self.history.len() > 2 && self.history[1].0 + window_ns <= ts_ns {
+            self.history.pop_front();
+        }
+        let (oldest_ts, oldest) = *self.history.front()?;
+        // Jendela belum terisi penuh: belum bisa dinilai
+        if oldest_ts + window_ns > ts_ns {
+            return None;
+        }
+
+        let delta = value - oldest;
+        let change = match self.rule.direction {
+            Direction::Rise => delta,
+            Direction::Fall => -delta,
+        };
+        // Hysteresis: aktif di >= change, kembali normal di < change/2
+        let now_active = if self.active { change >= self.rule.change / 2.0 } else { change >= self.rule.change };
+        if now_active == self.active {
+            return None;
+        }
+        self.active = now_active;
+        Some((now_active, delta))
+    }
+}
@@ -0,0 +1,82 @@
+use log::{error, info};
+use rumqttc::{Client, Event, Incoming, MqttOptions, QoS};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::secrets::Secret;
+
+// Debug: salinan setiap baris serial (masuk dan keluar) ke broker MQTT lokal, topic <topic_prefix>/<device>.
+// Payload teks "< <baris>" untuk yang diterima dari ESP32, "> <baris>" untuk perintah yang dikirim.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RawMirrorConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: String,
+    // Bisa juga lewat env RAW_MIRROR_PASSWORD(_FILE)
+    pub password: Secret,
+    pub topic_prefix: String,
+}
+
+impl Default for RawMirrorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "rust-dcs-raw".to_string(),
+            username: String::new(),
+            password: Secret::default(),
+            topic_prefix: "dcs/raw".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Rx,
+    Tx,
+}
+
+// Dipanggil dari thread serial untuk setiap baris; tidak boleh menahan pembacaan port
+pub type RawTap = Arc<dyn Fn(Direction, &str) + Send + Sync>;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+// Koneksi ke broker di thread sendiri; QoS 0 dan try_publish, jadi broker yang mati hanya membuang salinan
+pub fn spawn(config: RawMirrorConfig, device_id: &str) -> RawTap {
+    let mut options = MqttOptions::new(config.client_id.clone(), config.host.clone(), config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if !config.username.is_empty() {
+        options.set_credentials(config.username.clone(), config.password.expose());
+    }
+    let (client, mut connection) = Client::new(options, 100);
+    let topic = format!("{}/{}", config.topic_prefix.trim_end_matches('/'), device_id);
+    info!("🔎 Mirroring raw serial traffic to MQTT {}:{} topic {}", config.host, config.port, topic);
+
+    thread::spawn(move || {
+        for event in connection.iter() {
+            match event {
+                Ok(Event::Incoming(Incoming::ConnAck(_))) => info!("✓ Raw serial mirror connected to {}:{}", config.host, config.port),
+                Ok(_) => {}
+                Err(e) => {
+                    error!("Raw serial mirror MQTT error: {e:#}");
+                    thread::sleep(RECONNECT_DELAY);
+                }
+            }
+        }
+    });
+
+    Arc::new(move |direction, line| {
+        let marker = match direction {
+            Direction::Rx => '<',
+            Direction::Tx => '>',
+        };
+        // Antrian penuh (broker tidak terjangkau): salinan dibuang diam-diam
+        let _ = client.try_publish(topic.as_str(), QoS::AtMostOnce, false, format!("{marker} {line}"));
+    })
+}
@@ -0,0 +1,206 @@
+use log::{error, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::alarms::{self, Alarm};
+use crate::bridge::{self, Influx};
+use crate::config::Config;
+use crate::control::{Actuator, ZoneLoops};
+use crate::events::Event;
+use crate::state::AppState;
+
+// Bandingkan status aktuator hasil keputusan backend dengan RELAY_STATUS dari ESP32.
+// Beda yang bertahan lebih lama dari `grace` = relay macet, salah kabel, atau perintah tidak sampai.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ReconcileConfig {
+    pub enabled: bool,
+    // Waktu yang diberikan ke ESP32 untuk mengikuti (band SET/RELAY, min on/off di device)
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub grace: Duration,
+}
+
+impl Default for ReconcileConfig {
+    fn default() -> Self {
+        Self { enabled: true, grace: Duration::from_secs(60) }
+    }
+}
+
+// Status relay terakhir dari ESP32 (device_id utama)
+#[derive(Debug, Clone)]
+pub struct ReportedRelay {
+    pub on: Option<bool>,
+    // AUTO, REMOTE, LOCAL dari <relay>_mode
+    pub mode: Option<String>,
+    pub at: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mismatch {
+    pub commanded: bool,
+    pub reported: bool,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct Reconciler {
+    grace: Duration,
+    // Sejak kapan perintah dan laporan berbeda
+    since: HashMap<Actuator, Instant>,
+}
+
+impl Reconciler {
+    pub fn new(config: &ReconcileConfig) -> Self {
+        Self { grace: config.grace, since: HashMap::new() }
+    }
+
+    // Some = beda sudah lebih lama dari grace; None = cocok, belum lewat grace, atau tidak bisa dibandingkan
+    pub fn check(&mut self, actuator: Actuator, commanded: Option<bool>, reported: Option<bool>, now: Instant) -> Option<Mismatch> {
+        let (Some(commanded), Some(reported)) = (commanded, reported) else {
+            self.since.remove(&actuator);
+            return None;
+        };
+        if commanded == reported {
+            self.since.remove(&actuator);
+            return None;
+        }
+        let duration = now.saturating_duration_since(*self.since.entry(actuator).or_insert(now));
+        (duration >= self.grace).then_some(Mismatch { commanded, reported, duration })
+    }
+
+    // Bandingkan perintah dengan RELAY_STATUS terakhir; hasil = alarm yang naik (true) atau hilang (false).
+    // Laporan lebih tua dari grace atau dari override tombol panel (LOCAL) tidak dipakai.
+    fn reconcile(&mut self, state: &AppState, commanded: impl Fn(Actuator) -> Option<bool>, now: Instant) -> Vec<(Alarm, bool)> {
+        let reported = state.reported_relays.lock().unwrap().clone();
+        let mut changes = Vec::new();
+        for actuator in Actuator::ALL {
+            let report = reported
+                .get(&actuator)
+                .filter(|r| r.mode.as_deref() != Some("LOCAL") && now.saturating_duration_since(r.at) <= self.grace)
+                .and_then(|r| r.on);
+            let id = format!("command_mismatch_{}", actuator.name());
+            let label = |on: bool| if on { "ON" } else { "OFF" };
+            let change = match self.check(actuator, commanded(actuator), report, now) {
+                Some(mismatch) => {
+                    let message = format!(
+                        "{} commanded {} but device reports {} for {}s",
+                        actuator.name(), label(mismatch.commanded), label(mismatch.reported), mismatch.duration.as_secs()
+                    );
+                    state.raise_alarm(&id, alarms::Severity::Warning, message).map(|alarm| {
+                        warn!("🔌 {} (stuck relay or wiring fault?)", alarm.message);
+                        state.events.record(
+                            Event::new("command_mismatch", actuator.name(), label(mismatch.reported))
+                                .old(label(mismatch.commanded))
+                                .reason(format!("RELAY_STATUS differs from command for {}s", mismatch.duration.as_secs())),
+                        );
+                        (alarm, true)
+                    })
+                }
+                None => state.clear_alarm(&id).map(|alarm| (alarm, false)),
+            };
+            changes.extend(change);
+        }
+        changes
+    }
+}
+
+// Bandingkan perintah backend dengan RELAY_STATUS ESP32 utama; beda lebih lama dari grace = alarm command_mismatch_<aktuator>
+pub(crate) fn check_device(reconciler: &mut Reconciler, influx: &Influx, state: &AppState, config: &Config, loops: &ZoneLoops) {
+    let zone = state.zones.iter().find(|z| z.device_id == config.device_id);
+    // Hanya aktuator yang keputusan backend-nya juga diambil relay AUTO device (lihat ZoneLoops::device_shared):
+    // rule, VPD, kaskade, interlock dan mode manual membuat backend sengaja berbeda dari band device
+    let commanded = |actuator: Actuator| {
+        let zone = zone.filter(|z| z.has(actuator) && loops.device_shared.contains(&actuator))?;
+        loops.controller.state(&zone.actuator(actuator))
+    };
+    for (alarm, active) in reconciler.reconcile(state, commanded, Instant::now()) {
+        if let Err(e) = bridge::write_alarm_to_influx(influx, &alarm, active) {
+            error!("Failed to write alarm to InfluxDB: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reconciler(grace: u64) -> Reconciler {
+        Reconciler::new(&ReconcileConfig { enabled: true, grace: Duration::from_secs(grace) })
+    }
+
+    #[test]
+    fn mismatch_reported_only_after_grace() {
+        let mut reconciler = reconciler(60);
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        assert_eq!(reconciler.check(Actuator::Pump, Some(true), Some(false), start), None);
+        assert_eq!(reconciler.check(Actuator::Pump, Some(true), Some(false), at(59)), None);
+        let mismatch = reconciler.check(Actuator::Pump, Some(true), Some(false), at(60)).expect("mismatch after grace");
+        assert_eq!(mismatch, Mismatch { commanded: true, reported: false, duration: Duration::from_secs(60) });
+        // Sempat cocok: hitungan grace mulai lagi dari awal
+        assert_eq!(reconciler.check(Actuator::Pump, Some(true), Some(true), at(61)), None);
+        assert_eq!(reconciler.check(Actuator::Pump, Some(true), Some(false), at(62)), None);
+        assert_eq!(reconciler.check(Actuator::Pump, Some(true), Some(false), at(100)), None);
+        // Tanpa perintah atau tanpa laporan tidak ada yang dibandingkan
+        assert_eq!(reconciler.check(Actuator::Pump, None, Some(false), at(200)), None);
+        assert_eq!(reconciler.check(Actuator::Pump, Some(true), None, at(300)), None);
+    }
+
+    #[test]
+    fn actuators_are_tracked_separately() {
+        let mut reconciler = reconciler(10);
+        let start = Instant::now();
+        reconciler.check(Actuator::Pump, Some(true), Some(false), start);
+        reconciler.check(Actuator::ExhaustFan, Some(false), Some(true), start + Duration::from_secs(9));
+        assert!(reconciler.check(Actuator::Pump, Some(true), Some(false), start + Duration::from_secs(10)).is_some());
+        assert!(reconciler.check(Actuator::ExhaustFan, Some(false), Some(true), start + Duration::from_secs(10)).is_none());
+    }
+
+    fn report(state: &AppState, on: bool, mode: &str, at: Instant) {
+        let relay = ReportedRelay { on: Some(on), mode: Some(mode.to_string()), at };
+        state.reported_relays.lock().unwrap().insert(Actuator::Pump, relay);
+    }
+
+    #[test]
+    fn mismatch_alarm_is_raised_and_cleared() {
+        let state = AppState::new(&Config::default());
+        let mut reconciler = reconciler(60);
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let pump_on = |actuator: Actuator| (actuator == Actuator::Pump).then_some(true);
+
+        report(&state, false, "AUTO", start);
+        assert!(reconciler.reconcile(&state, pump_on, start).is_empty());
+        report(&state, false, "AUTO", at(60));
+        let raised = reconciler.reconcile(&state, pump_on, at(60));
+        assert_eq!(raised.len(), 1);
+        assert_eq!((raised[0].0.id.as_str(), raised[0].1), ("command_mismatch_pump", true));
+        assert_eq!(raised[0].0.message, "pump commanded ON but device reports OFF for 60s");
+        // Alarm yang sudah aktif tidak dilaporkan ulang
+        report(&state, false, "AUTO", at(70));
+        assert!(reconciler.reconcile(&state, pump_on, at(70)).is_empty());
+
+        report(&state, true, "AUTO", at(80));
+        let cleared = reconciler.reconcile(&state, pump_on, at(80));
+        assert_eq!(cleared.len(), 1);
+        assert_eq!((cleared[0].0.id.as_str(), cleared[0].1), ("command_mismatch_pump", false));
+        assert!(state.alarms.lock().unwrap().active().next().is_none());
+    }
+
+    #[test]
+    fn local_override_and_stale_reports_are_not_compared() {
+        let state = AppState::new(&Config::default());
+        let mut reconciler = reconciler(60);
+        let start = Instant::now();
+        let pump_on = |actuator: Actuator| (actuator == Actuator::Pump).then_some(true);
+        report(&state, false, "LOCAL", start);
+        reconciler.reconcile(&state, pump_on, start);
+        assert!(reconciler.reconcile(&state, pump_on, start + Duration::from_secs(120)).is_empty());
+
+        // Laporan AUTO yang sudah lebih tua dari grace (RELAY_STATUS berhenti datang)
+        report(&state, false, "AUTO", start);
+        reconciler.reconcile(&state, pump_on, start);
+        assert!(reconciler.reconcile(&state, pump_on, start + Duration::from_secs(61)).is_empty());
+    }
+}
@@ -0,0 +1,343 @@
+use anyhow::{Context, Result};
+use log::{error, info};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::bridge::{bucket_for, location_filter, now_ns, post_influx, SENSOR_MEAS};
+use crate::flux_csv::Record;
+use crate::stats::{self, FieldStats};
+use crate::{compaction, email};
+
+// Laporan harian otomatis (pengganti salin-tempel mingguan dari Grafana): min/max/rata-rata
+// suhu dan kelembapan, jam operasi aktuator, jumlah alarm dan grafik kecil rata-rata per jam.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ReportConfig {
+    pub enabled: bool,
+    pub format: ReportFormat,
+    pub dir: String,
+    // Batas hari laporan (waktu lokal)
+    pub utc_offset: String,
+    // Kosong = tidak dikirim; SMTP memakai [notifications.email]
+    pub email_to: Vec<String>,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            format: ReportFormat::Html,
+            dir: "reports".to_string(),
+            utc_offset: "+07:00".to_string(),
+            email_to: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    // Satu file dengan grafik SVG inline
+    #[default]
+    Html,
+    // File .md plus file .svg per grafik di direktori yang sama
+    Markdown,
+}
+
+impl ReportConfig {
+    fn offset_secs(&self) -> i64 {
+        crate::import::parse_offset(&self.utc_offset).unwrap_or_default()
+    }
+
+    // Nomor hari lokal untuk epoch ms
+    pub fn day(&self, ts_ms: u64) -> i64 {
+        (ts_ms as i64 / 1000 + self.offset_secs()).div_euclid(86_400)
+    }
+
+    // [awal, akhir) hari lokal dalam epoch ns, untuk range Flux
+    pub fn day_range_ns(&self, day: i64) -> (u64, u64) {
+        let start = (day * 86_400 - self.offset_secs()).max(0) as u64;
+        (start * 1_000_000_000, (start + 86_400) * 1_000_000_000)
+    }
+
+    pub fn path(&self, day: i64) -> PathBuf {
+        let extension = match self.format {
+            ReportFormat::Html => "html",
+            ReportFormat::Markdown => "md",
+        };
+        Path::new(&self.dir).join(format!("daily_{}.{}", date(day), extension))
+    }
+}
+
+// "YYYY-MM-DD" untuk nomor hari
+pub fn date(day: i64) -> String {
+    let (year, month, day) = crate::compaction::civil_from_days(day);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Field sensor di laporan: (field InfluxDB, label, satuan)
+pub const FIELDS: [(&str, &str, &str); 2] = [("temperature", "Temperature", "°C"), ("humidity", "Humidity", "%")];
+
+// Query Flux; `range` = `start: ..., stop: ...`, `tag_filter` seperti stats_flux
+pub fn hourly_flux(bucket: &str, measurement: &str, range: &str, tag_filter: &str) -> String {
+    format!(
+        r#"from(bucket: "{bucket}")
+  |> range({range})
+  |> filter(fn: (r) => r["_measurement"] == "{measurement}")
+{tag_filter}  |> filter(fn: (r) => r["_field"] == "temperature" or r["_field"] == "humidity")
+  |> group(columns: ["_field"])
+  |> aggregateWindow(every: 1h, fn: mean, createEmpty: false)
+  |> keep(columns: ["_time", "_field", "_value"])
+"#
+    )
+}
+
+// Selisih counter on_seconds per aktuator fisik (reset counter tidak dihitung negatif)
+pub fn runtime_flux(bucket: &str, range: &str, tag_filter: &str) -> String {
+    format!(
+        r#"from(bucket: "{bucket}")
+  |> range({range})
+  |> filter(fn: (r) => r["_measurement"] == "actuator_runtime" and r["_field"] == "on_seconds")
+{tag_filter}  |> group(columns: ["zone", "actuator"])
+  |> sort(columns: ["_time"])
+  |> difference(nonNegative: true)
+  |> sum()
+"#
+    )
+}
+
+// Jumlah alarm yang menjadi aktif per severity
+pub fn alarms_flux(bucket: &str, range: &str, tag_filter: &str) -> String {
+    format!(
+        r#"from(bucket: "{bucket}")
+  |> range({range})
+  |> filter(fn: (r) => r["_measurement"] == "alarms" and r["_field"] == "active" and r["_value"] == 1.0)
+{tag_filter}  |> group(columns: ["severity"])
+  |> count()
+"#
+    )
+}
+
+// field -> rata-rata per jam, urut waktu
+pub fn parse_hourly(rows: &[Record]) -> BTreeMap<String, Vec<f64>> {
+    let mut out: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    for row in rows {
+        if let (Some(field), Some(value)) = (row.get("_field"), row.number("_value")) {
+            out.entry(field.to_string()).or_default().push(value);
+        }
+    }
+    out
+}
+
+// (kolom group, _value) untuk query runtime dan alarm
+pub fn parse_totals(rows: &[Record], column: &str) -> BTreeMap<String, f64> {
+    rows.iter().filter_map(|row| Some((row.get(column)?.to_string(), row.number("_value")?))).collect()
+}
+
+// Hasil runtime_flux -> "<zone>/<aktuator>" -> detik ON
+pub fn parse_runtime_totals(rows: &[Record]) -> BTreeMap<String, f64> {
+    rows.iter()
+        .filter_map(|row| Some((format!("{}/{}", row.get("zone")?, row.get("actuator")?), row.number("_value")?)))
+        .collect()
+}
+
+pub struct DailyReport {
+    pub day: i64,
+    pub device_id: String,
+    pub utc_offset: String,
+    pub stats: BTreeMap<String, FieldStats>,
+    pub hourly: BTreeMap<String, Vec<f64>>,
+    // <zone>/<aktuator> -> detik ON
+    pub runtime: BTreeMap<String, f64>,
+    // severity -> jumlah alarm
+    pub alarms: BTreeMap<String, f64>,
+}
+
+impl DailyReport {
+    pub fn title(&self) -> String {
+        format!("Daily report {} {}", self.device_id, date(self.day))
+    }
+
+    fn alarm_summary(&self) -> String {
+        let total: f64 = self.alarms.values().sum();
+        let parts: Vec<String> = ["critical", "warning", "info"]
+            .iter()
+            .filter_map(|s| self.alarms.get(*s).map(|n| format!("{} {}", n, s)))
+            .collect();
+        if parts.is_empty() { format!("{}", total) } else { format!("{} ({})", total, parts.join(", ")) }
+    }
+
+    // (label, min, max, rata-rata) per field; "-" jika tidak ada data
+    fn sensor_rows(&self) -> Vec<(String, String, String, String)> {
+        FIELDS
+            .iter()
+            .map(|(field, label, unit)| {
+                let label = format!("{} ({})", label, unit);
+                match self.stats.get(*field) {
+                    Some(s) => (label, format!("{:.1}", s.min), format!("{:.1}", s.max), format!("{:.1}", s.mean)),
+                    None => (label, "-".into(), "-".into(), "-".into()),
+                }
+            })
+            .collect()
+    }
+
+    // Isi laporan plus file tambahan (nama file, isi) untuk format markdown
+    pub fn render(&self, format: ReportFormat) -> (String, Vec<(String, String)>) {
+        match format {
+            ReportFormat::Html => (self.html(), Vec::new()),
+            ReportFormat::Markdown => self.markdown(),
+        }
+    }
+
+    fn html(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title>\n<style>body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse}}td,th{{border:1px solid #ccc;padding:4px 10px;text-align:right}}td:first-child,th:first-child{{text-align:left}}</style></head><body>\n<h1>{title}</h1>\n<p>Local day {date} (UTC{offset})</p>\n",
+            title = escape(&self.title()),
+            date = date(self.day),
+            offset = escape(&self.utc_offset)
+        );
+        out.push_str("<h2>Sensor</h2>\n<table><tr><th>Field</th><th>Min</th><th>Max</th><th>Avg</th><th>Hourly mean</th></tr>\n");
+        for ((field, _, _), (label, min, max, mean)) in FIELDS.iter().zip(self.sensor_rows()) {
+            let chart = self.hourly.get(*field).and_then(|v| sparkline(v)).unwrap_or_else(|| "-".to_string());
+            let _ = writeln!(out, "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>", escape(&label), min, max, mean, chart);
+        }
+        out.push_str("</table>\n<h2>Actuator run-hours</h2>\n<table><tr><th>Actuator</th><th>Hours ON</th></tr>\n");
+        for (actuator, seconds) in &self.runtime {
+            let _ = writeln!(out, "<tr><td>{}</td><td>{:.2}</td></tr>", escape(actuator), seconds / 3600.0);
+        }
+        let _ = write!(out, "</table>\n<h2>Alarms</h2>\n<p>Raised: {}</p>\n</body></html>\n", escape(&self.alarm_summary()));
+        out
+    }
+
+    fn markdown(&self) -> (String, Vec<(String, String)>) {
+        let mut out = String::new();
+        let mut charts = Vec::new();
+        let _ = write!(out, "# {}\n\nLocal day {} (UTC{})\n\n## Sensor\n\n", self.title(), date(self.day), self.utc_offset);
+        out.push_str("| Field | Min | Max | Avg | Hourly mean |\n|---|---:|---:|---:|---|\n");
+        for ((field, _, _), (label, min, max, mean)) in FIELDS.iter().zip(self.sensor_rows()) {
+            let chart = match self.hourly.get(*field).and_then(|v| sparkline(v)) {
+                Some(svg) => {
+                    let name = format!("daily_{}_{}.svg", date(self.day), field);
+                    charts.push((name.clone(), svg));
+                    format!("![{}]({})", field, name)
+                }
+                None => "-".to_string(),
+            };
+            let _ = writeln!(out, "| {} | {} | {} | {} | {} |", label, min, max, mean, chart);
+        }
+        out.push_str("\n## Actuator run-hours\n\n| Actuator | Hours ON |\n|---|---:|\n");
+        for (actuator, seconds) in &self.runtime {
+            let _ = writeln!(out, "| {} | {:.2} |", actuator, seconds / 3600.0);
+        }
+        let _ = writeln!(out, "\n## Alarms\n\nRaised: {}", self.alarm_summary());
+        (out, charts)
+    }
+
+    // Tulis laporan (dan grafik markdown) ke direktori laporan; mengembalikan path laporan
+    pub fn save(&self, config: &ReportConfig) -> Result<(PathBuf, String)> {
+        std::fs::create_dir_all(&config.dir).with_context(|| format!("Cannot create report directory {}", config.dir))?;
+        let (content, charts) = self.render(config.format);
+        for (name, svg) in charts {
+            std::fs::write(Path::new(&config.dir).join(name), svg)?;
+        }
+        let path = config.path(self.day);
+        std::fs::write(&path, &content).with_context(|| format!("Cannot write report {}", path.display()))?;
+        Ok((path, content))
+    }
+}
+
+// Grafik garis kecil (SVG) dari rata-rata per jam; None jika kurang dari dua titik
+fn sparkline(values: &[f64]) -> Option<String> {
+    const WIDTH: f64 = 240.0;
+    const HEIGHT: f64 = 48.0;
+    if values.len() < 2 {
+        return None;
+    }
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let span = (max - min).max(1e-9);
+    let points: Vec<String> = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let x = i as f64 * WIDTH / (values.len() - 1) as f64;
+            let y = HEIGHT - 2.0 - (v - min) / span * (HEIGHT - 4.0);
+            format!("{:.1},{:.1}", x, y)
+        })
+        .collect();
+    Some(format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" viewBox=\"0 0 {WIDTH} {HEIGHT}\"><polyline fill=\"none\" stroke=\"#2a7ae2\" stroke-width=\"1.5\" points=\"{}\"/></svg>",
+        points.join(" ")
+    ))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// Laporan harian: setelah hari lokal berganti, laporan hari sebelumnya ditulis ke [report] dir
+// (dan dikirim email). Laporan yang sudah ada dilewati, jadi restart hanya mengejar yang belum dibuat.
+pub(crate) async fn run_task(client: Client, config: ReportConfig, email: email::EmailConfig, device_id: String, location: Option<String>) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(60));
+    let mut done: Option<i64> = None;
+    let mut retry_at = Instant::now();
+    loop {
+        ticker.tick().await;
+        let yesterday = config.day(now_ns() / 1_000_000) - 1;
+        if done == Some(yesterday) || Instant::now() < retry_at {
+            continue;
+        }
+        if config.path(yesterday).exists() {
+            done = Some(yesterday);
+            continue;
+        }
+        match daily(&client, &config, &email, &device_id, location.as_deref(), yesterday).await {
+            Ok(path) => {
+                info!("📄 Daily report for {} written to {}", date(yesterday), path.display());
+                done = Some(yesterday);
+            }
+            Err(e) => {
+                error!("Daily report for {} failed, retrying in 10 min: {:#}", date(yesterday), e);
+                retry_at = Instant::now() + Duration::from_secs(600);
+            }
+        }
+    }
+}
+
+async fn daily(
+    client: &Client,
+    config: &ReportConfig,
+    email: &email::EmailConfig,
+    device_id: &str,
+    location: Option<&str>,
+    day: i64,
+) -> Result<PathBuf> {
+    let (start, stop) = config.day_range_ns(day);
+    let range = format!("start: {}, stop: {}", compaction::rfc3339(start), compaction::rfc3339(stop));
+    let filter = location_filter(location);
+    let fields: Vec<String> = FIELDS.iter().map(|(f, _, _)| f.to_string()).collect();
+
+    let sensor_bucket = bucket_for(SENSOR_MEAS);
+    let stats = stats::parse_stats(&post_influx(client, stats::stats_flux_range(sensor_bucket, SENSOR_MEAS, &range, &fields, &filter)).await?);
+    let hourly = parse_hourly(&post_influx(client, hourly_flux(sensor_bucket, SENSOR_MEAS, &range, &filter)).await?);
+    let runtime = parse_runtime_totals(&post_influx(client, runtime_flux(bucket_for("actuator_runtime"), &range, &filter)).await?);
+    let alarms = parse_totals(&post_influx(client, alarms_flux(bucket_for("alarms"), &range, &filter)).await?, "severity");
+    let daily = DailyReport { day, device_id: device_id.to_string(), utc_offset: config.utc_offset.clone(), stats, hourly, runtime, alarms };
+    let (path, content) = daily.save(config)?;
+
+    if !config.email_to.is_empty() {
+        let mail = email::Mail { to: config.email_to.clone(), subject: daily.title(), body: content, html: config.format == ReportFormat::Html };
+        // Laporan sudah tersimpan; email yang gagal tidak diulang
+        match email::send(email, &mail).await {
+            Ok(()) => info!("📧 Daily report emailed to {}", config.email_to.join(", ")),
+            Err(e) => error!("Failed to email daily report: {:#}", e),
+        }
+    }
+    Ok(path)
+}
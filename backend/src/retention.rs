@@ -0,0 +1,43 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// Routing measurement ke bucket InfluxDB dengan retention berbeda (mis. raw 30 hari,
+// KPI/event 1 tahun, diagnostik 7 hari). Measurement yang tidak terdaftar tetap ke SENSOR_DATA.
+// Tulis dan query memakai routing yang sama, jadi data lama di SENSOR_DATA tidak ikut terbaca
+// setelah measurement dipindah.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    // Buat bucket yang belum ada (dengan retention-nya) saat startup; token butuh izin write bucket
+    pub create_buckets: bool,
+    pub buckets: Vec<BucketRoute>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BucketRoute {
+    pub name: String,
+    // "0s" = tanpa batas
+    #[serde(default, deserialize_with = "crate::config::deserialize_duration")]
+    pub retention: Duration,
+    pub measurements: Vec<String>,
+}
+
+static ROUTES: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+// Dipanggil sekali saat startup; measurement yang terdaftar dua kali memakai bucket pertama
+pub fn install(config: &RetentionConfig) {
+    let mut routes = HashMap::new();
+    for bucket in &config.buckets {
+        for measurement in &bucket.measurements {
+            routes.entry(measurement.clone()).or_insert_with(|| bucket.name.clone());
+        }
+    }
+    let _ = ROUTES.set(routes);
+}
+
+// Bucket untuk measurement; None = bucket default
+pub fn route(measurement: &str) -> Option<&'static str> {
+    ROUTES.get()?.get(measurement).map(String::as_str)
+}
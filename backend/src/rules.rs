@@ -0,0 +1,381 @@
+use anyhow::{anyhow, Context, Result};
+use evalexpr::{build_operator_tree, ContextWithMutableVariables, HashMapContext, Node, Value};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use crate::control::Actuator;
+
+// Logika AUTO aktuator sebagai ekspresi evalexpr atas sinyal bernama, mis.
+// pump = "humidity < pump_on_threshold && !maintenance_mode". Rule ada di file terpisah yang
+// diperiksa setiap siklus dan dimuat ulang saat berubah (tanpa restart). Hasil rule menggantikan
+// demand bawaan (band suhu, ambang kelembaban, VPD, kaskade), yang tetap tersedia sebagai sinyal
+// <aktuator>_default; mode manual, interlock dan min on/off tetap berlaku setelahnya.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RulesConfig {
+    // Kosong = tanpa rule, semua aktuator memakai logika bawaan
+    pub path: String,
+}
+
+// Sinyal yang diisi loop utama untuk setiap zone, selain field sensor dan [last_data] extra_fields
+pub const SIGNALS: [&str; 19] = [
+    "temperature",
+    "humidity",
+    "temperature_filtered",
+    "humidity_filtered",
+    "exhaust_fan_status",
+    "pump_status",
+    "dwsim_temperature",
+    "vpd",
+    "setpoint",
+    "temp_on",
+    "temp_off",
+    "humidity_on_below",
+    "maintenance_mode",
+    "exhaust_fan_on",
+    "pump_on",
+    "heater_on",
+    "exhaust_fan_default",
+    "pump_default",
+    "heater_default",
+];
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(untagged)]
+enum Constant {
+    Flag(bool),
+    Number(f64),
+}
+
+impl Constant {
+    fn value(self) -> Value {
+        match self {
+            Constant::Flag(on) => Value::Boolean(on),
+            Constant::Number(n) => Value::Float(n),
+        }
+    }
+}
+
+// Isi file rule: konstanta bernama di [signals], satu ekspresi per aktuator di [rules]
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    signals: HashMap<String, Constant>,
+    #[serde(default)]
+    rules: HashMap<Actuator, String>,
+}
+
+#[derive(Default)]
+pub struct RuleSet {
+    constants: HashMap<String, Constant>,
+    rules: HashMap<Actuator, (String, Node)>,
+}
+
+impl RuleSet {
+    pub fn parse(text: &str) -> Result<Self> {
+        let file: RulesFile = toml::from_str(text)?;
+        let rules = file
+            .rules
+            .into_iter()
+            .map(|(actuator, expr)| {
+                build_operator_tree(&expr)
+                    .map(|tree| (actuator, (expr.clone(), tree)))
+                    .map_err(|e| anyhow!("rule {}: invalid expression '{}': {}", actuator.name(), expr, e))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { constants: file.signals, rules })
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read rules file {}", path))?;
+        Self::parse(&text).with_context(|| format!("Invalid rules file {}", path))
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn actuators(&self) -> impl Iterator<Item = Actuator> + '_ {
+        self.rules.keys().copied()
+    }
+
+    pub fn constants(&self) -> impl Iterator<Item = &str> {
+        self.constants.keys().map(String::as_str)
+    }
+
+    // Identifier di ekspresi yang bukan konstanta file maupun sinyal yang diketahui (typo?)
+    pub fn unknown_signals(&self, known: &[String]) -> Vec<(Actuator, String)> {
+        let mut unknown: Vec<(Actuator, String)> = self
+            .rules
+            .iter()
+            .flat_map(|(actuator, (_, tree))| tree.iter_variable_identifiers().map(move |name| (*actuator, name)))
+            .filter(|(_, name)| !self.constants.contains_key(*name) && !known.iter().any(|k| k == name))
+            .map(|(actuator, name)| (actuator, name.to_string()))
+            .collect();
+        unknown.sort_by(|a, b| (a.0.name(), &a.1).cmp(&(b.0.name(), &b.1)));
+        unknown.dedup();
+        unknown
+    }
+}
+
+// Nilai sinyal satu zone pada satu siklus
+#[derive(Default)]
+pub struct Signals(HashMap<String, Value>);
+
+impl Signals {
+    pub fn new(fields: &HashMap<String, f64>) -> Self {
+        Self(fields.iter().map(|(name, value)| (name.clone(), Value::Float(*value))).collect())
+    }
+
+    pub fn number(&mut self, name: &str, value: Option<f64>) {
+        if let Some(value) = value {
+            self.0.insert(name.to_string(), Value::Float(value));
+        }
+    }
+
+    pub fn flag(&mut self, name: &str, value: bool) {
+        self.0.insert(name.to_string(), Value::Boolean(value));
+    }
+}
+
+pub struct RuleEngine {
+    path: String,
+    modified: Option<SystemTime>,
+    set: RuleSet,
+    // Hasil terakhir per (zone, aktuator); perubahan dicatat di log info
+    last: HashMap<(String, Actuator), bool>,
+}
+
+impl RuleEngine {
+    // File yang tidak valid saat startup = error (sama seperti interlock)
+    pub fn load(config: &RulesConfig) -> Result<Self> {
+        let path = config.path.trim().to_string();
+        if path.is_empty() {
+            return Ok(Self { path, modified: None, set: RuleSet::default(), last: HashMap::new() });
+        }
+        let modified = modified(&path);
+        let set = RuleSet::load(&path)?;
+        Ok(Self { path, modified, set, last: HashMap::new() })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.set
+    }
+
+    // Dipanggil setiap siklus. Some(Ok(n)) = dimuat ulang dengan n rule; Some(Err) = file yang
+    // berubah tidak valid atau hilang, rule lama tetap dipakai; None = tidak berubah
+    pub fn reload_if_changed(&mut self) -> Option<Result<usize>> {
+        if self.path.is_empty() {
+            return None;
+        }
+        let current = modified(&self.path);
+        if current == self.modified {
+            return None;
+        }
+        self.modified = current;
+        if current.is_none() {
+            return Some(Err(anyhow!("rules file {} is missing, keeping {} loaded rule(s)", self.path, self.set.len())));
+        }
+        Some(RuleSet::load(&self.path).map(|set| {
+            self.set = set;
+            self.last.clear();
+            self.set.len()
+        }))
+    }
+
+    // Demand AUTO untuk aktuator ini: hasil rule jika ada, selain itu (default, reason) apa adanya.
+    // Rule yang gagal dievaluasi (mis. sensor belum ada) jatuh ke demand bawaan dengan warning.
+    pub fn demand(&mut self, zone: &str, actuator: Actuator, signals: &mut Signals, default: bool, reason: String) -> (bool, String) {
+        signals.flag(&format!("{}_default", actuator.name()), default);
+        let Some((expr, tree)) = self.set.rules.get(&actuator) else { return (default, reason) };
+
+        let mut context = HashMapContext::new();
+        for (name, constant) in &self.set.constants {
+            let _ = context.set_value(name.clone(), constant.value());
+        }
+        // Sinyal live mengalahkan konstanta dengan nama sama
+        for (name, value) in &signals.0 {
+            let _ = context.set_value(name.clone(), value.clone());
+        }
+        let on = match tree.eval_boolean_with_context(&context) {
+            Ok(on) => on,
+            Err(e) => {
+                warn!("📐 [{}] Rule {} not evaluated ({}), using built-in logic", zone, actuator.name(), e);
+                return (default, reason);
+            }
+        };
+
+        let inputs: Vec<String> = tree
+            .iter_variable_identifiers()
+            .map(|name| {
+                let value = signals.0.get(name).cloned().or_else(|| self.set.constants.get(name).map(|c| c.value()));
+                match value {
+                    Some(value) => format!("{}={}", name, value),
+                    None => format!("{}=?", name),
+                }
+            })
+            .collect();
+        let inputs = inputs.join(", ");
+        debug!("📐 [{}] Rule {} = {} ({})", zone, actuator.name(), on, inputs);
+        if self.last.insert((zone.to_string(), actuator), on) != Some(on) {
+            info!("📐 [{}] Rule {} → {} ({}; built-in logic: {})", zone, actuator.name(), if on { "ON" } else { "OFF" }, inputs, default);
+        }
+        (on, format!("rule {} = {} ({})", expr, on, inputs))
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = r#"
+[signals]
+pump_on_threshold = 70.0
+maintenance_mode = true
+
+[rules]
+pump = "humidity < pump_on_threshold && !maintenance_mode"
+heater = "!heater_default"
+"#;
+
+    fn engine(text: &str) -> RuleEngine {
+        RuleEngine { path: String::new(), modified: None, set: RuleSet::parse(text).unwrap(), last: HashMap::new() }
+    }
+
+    fn signals(humidity: f64) -> Signals {
+        let mut signals = Signals::new(&HashMap::from([("humidity".to_string(), humidity)]));
+        signals.flag("maintenance_mode", false);
+        signals
+    }
+
+    #[test]
+    fn evaluates_rules_with_constants_and_live_signals() {
+        let mut engine = engine(RULES);
+        assert_eq!(engine.rules().len(), 2);
+
+        // Sinyal maintenance_mode live mengalahkan konstanta true
+        let (on, reason) = engine.demand("main", Actuator::Pump, &mut signals(60.0), false, "built-in".into());
+        assert!(on);
+        assert!(reason.starts_with("rule humidity < pump_on_threshold && !maintenance_mode = true ("));
+        assert!(reason.contains("pump_on_threshold=70"));
+        let (on, _) = engine.demand("main", Actuator::Pump, &mut signals(75.0), true, "built-in".into());
+        assert!(!on);
+
+        // Tanpa sinyal live, konstanta file yang dipakai
+        let mut only_humidity = Signals::new(&HashMap::from([("humidity".to_string(), 60.0)]));
+        assert!(!engine.demand("main", Actuator::Pump, &mut only_humidity, true, String::new()).0);
+    }
+
+    #[test]
+    fn default_demand_is_exposed_and_used_without_rule() {
+        let mut engine = engine(RULES);
+        let mut signals = signals(50.0);
+        assert!(engine.demand("main", Actuator::Heater, &mut signals, false, String::new()).0);
+        assert!(!engine.demand("main", Actuator::Heater, &mut signals, true, String::new()).0);
+
+        let (on, reason) = engine.demand("main", Actuator::ExhaustFan, &mut signals, true, "temp 31 > 30".into());
+        assert!(on);
+        assert_eq!(reason, "temp 31 > 30");
+        assert_eq!(signals.0.get("exhaust_fan_default"), Some(&Value::Boolean(true)));
+    }
+
+    #[test]
+    fn evaluation_errors_fall_back_to_built_in_logic() {
+        let mut engine = engine(
+            r#"
+[rules]
+pump = "vpd > 1.2"
+exhaust_fan = "temperature + 1"
+"#,
+        );
+        let mut signals = signals(50.0);
+        // vpd belum ada
+        let (on, reason) = engine.demand("main", Actuator::Pump, &mut signals, true, "humidity".into());
+        assert!(on);
+        assert_eq!(reason, "humidity");
+        // Hasil bukan boolean
+        signals.number("temperature", Some(25.0));
+        let (on, reason) = engine.demand("main", Actuator::ExhaustFan, &mut signals, false, "band".into());
+        assert!(!on);
+        assert_eq!(reason, "band");
+
+        signals.number("vpd", Some(1.5));
+        assert!(engine.demand("main", Actuator::Pump, &mut signals, false, String::new()).0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = RuleSet::parse("[rules]\npump = \"(humidity < 60\"\n").err().unwrap();
+        assert!(err.to_string().starts_with("rule pump: invalid expression '(humidity < 60'"));
+        assert!(RuleSet::parse("[rules]\nsprinkler = \"true\"\n").is_err());
+        assert!(RuleSet::parse("[rule]\npump = \"true\"\n").is_err());
+        assert!(RuleSet::parse("[signals]\nthreshold = \"high\"\n").is_err());
+        assert!(RuleSet::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn unknown_signals_lists_typos_once() {
+        let set = RuleSet::parse(
+            r#"
+[signals]
+limit = 60.0
+
+[rules]
+pump = "humidty < limit || humidty > 95"
+exhaust_fan = "temperature > limit && vdp > 1"
+"#,
+        )
+        .unwrap();
+        let known: Vec<String> = SIGNALS.iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            set.unknown_signals(&known),
+            vec![(Actuator::ExhaustFan, "vdp".to_string()), (Actuator::Pump, "humidty".to_string())]
+        );
+    }
+
+    #[test]
+    fn reload_keeps_old_rules_on_error() {
+        let dir = std::env::temp_dir().join(format!("dcs-rules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rules.toml");
+        let config = RulesConfig { path: path.to_str().unwrap().to_string() };
+
+        assert!(RuleEngine::load(&config).is_err());
+        assert!(RuleEngine::load(&RulesConfig::default()).unwrap().rules().is_empty());
+
+        std::fs::write(&path, RULES).unwrap();
+        let mut engine = RuleEngine::load(&config).unwrap();
+        assert!(engine.reload_if_changed().is_none());
+
+        // modified di-reset agar tidak bergantung pada resolusi mtime filesystem
+        std::fs::write(&path, "[rules]\npump = \"humidity <\"\n[").unwrap();
+        engine.modified = None;
+        assert!(engine.reload_if_changed().unwrap().is_err());
+        assert_eq!(engine.rules().len(), 2);
+
+        std::fs::write(&path, "[rules]\npump = \"false\"\n").unwrap();
+        engine.modified = None;
+        assert_eq!(engine.reload_if_changed().unwrap().unwrap(), 1);
+        assert!(!engine.demand("main", Actuator::Pump, &mut signals(10.0), true, String::new()).0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        let err = engine.reload_if_changed().unwrap().unwrap_err();
+        assert!(err.to_string().contains("is missing, keeping 1 loaded rule(s)"));
+        assert_eq!(engine.rules().len(), 1);
+    }
+}
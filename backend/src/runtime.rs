@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::control::ActuatorId;
+
+// Jam operasi kumulatif dan jumlah switch ON per aktuator fisik (zone + jenis)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ActuatorRuntime {
+    pub on_seconds: f64,
+    pub switch_count: u64,
+    #[serde(skip)]
+    state: Option<bool>,
+    #[serde(skip)]
+    last_update: Option<Instant>,
+}
+
+impl ActuatorRuntime {
+    pub fn on_hours(&self) -> f64 {
+        self.on_seconds / 3600.0
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RuntimeCounters {
+    counters: HashMap<ActuatorId, ActuatorRuntime>,
+}
+
+impl RuntimeCounters {
+    // Integrasikan waktu ON sejak update terakhir lalu catat status baru
+    pub fn update(&mut self, actuator: &ActuatorId, state: bool, now: Instant) {
+        let counter = self.counters.entry(actuator.clone()).or_default();
+        if let (Some(true), Some(last)) = (counter.state, counter.last_update) {
+            counter.on_seconds += now.saturating_duration_since(last).as_secs_f64();
+        }
+        if state && counter.state == Some(false) {
+            counter.switch_count += 1;
+        }
+        counter.state = Some(state);
+        counter.last_update = Some(now);
+    }
+
+    // Nilai yang dipulihkan dari checkpoint atau InfluxDB saat startup
+    pub fn restore(&mut self, actuator: ActuatorId, on_seconds: f64, switch_count: u64) {
+        let counter = self.counters.entry(actuator).or_default();
+        counter.on_seconds = on_seconds;
+        counter.switch_count = switch_count;
+    }
+
+    // Reset setelah maintenance; status ON yang sedang berjalan tetap dilanjutkan dari nol
+    pub fn reset(&mut self, actuator: &ActuatorId) -> ActuatorRuntime {
+        let counter = self.counters.entry(actuator.clone()).or_default();
+        let previous = counter.clone();
+        counter.on_seconds = 0.0;
+        counter.switch_count = 0;
+        previous
+    }
+
+    pub fn get(&self, actuator: &ActuatorId) -> ActuatorRuntime {
+        self.counters.get(actuator).cloned().unwrap_or_default()
+    }
+
+    pub fn all(&self) -> &HashMap<ActuatorId, ActuatorRuntime> {
+        &self.counters
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::header::HeaderValue;
+use serde::Deserialize;
+use std::fmt;
+
+// Nilai rahasia (token, API key) yang tidak pernah tampil di log atau pesan error
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into().trim().to_string())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // Header Authorization "<scheme> <secret>", ditandai sensitif agar tidak ikut di-debug
+    pub fn header(&self, scheme: &str) -> HeaderValue {
+        let mut value = HeaderValue::from_str(&format!("{} {}", scheme, self.0)).unwrap_or_else(|_| HeaderValue::from_static(""));
+        value.set_sensitive(true);
+        value
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_empty() { "\"\"" } else { "\"***\"" })
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+// Urutan: <NAME>_FILE (path file, misal Docker/K8s secret) lalu env <NAME>
+pub fn load(name: &str) -> Result<Option<Secret>> {
+    let file_var = format!("{name}_FILE");
+    if let Ok(path) = std::env::var(&file_var) {
+        let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {} ({})", file_var, path))?;
+        let secret = Secret::new(text);
+        if secret.is_empty() {
+            return Err(anyhow!("{} points to an empty file ({})", file_var, path));
+        }
+        return Ok(Some(secret));
+    }
+    match std::env::var(name) {
+        Ok(value) if !value.trim().is_empty() => Ok(Some(Secret::new(value))),
+        _ => Ok(None),
+    }
+}
+
+pub fn require(name: &str) -> Result<Secret> {
+    load(name)?.ok_or_else(|| anyhow!("Missing secret {name}: set {name} or {name}_FILE"))
+}
+
+// Nilai dari file config dipakai jika env/file tidak diset
+pub fn load_or(name: &str, fallback: &Secret) -> Result<Secret> {
+    Ok(load(name)?.unwrap_or_else(|| fallback.clone()))
+}
@@ -1,27 +1,325 @@
-use std::io::{BufRead, BufReader};
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::time::{Duration, Instant};
 use serialport::SerialPort;
-use anyhow::{Result, anyhow};
-use log::{info, error, warn};
+use anyhow::{Result, anyhow, bail};
+use serde::Serialize;
+use log::{debug, info, error, warn};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use dcs_model::{finite, is_aux_field};
 
+use crate::alarms::Severity;
+use crate::chaos;
+use crate::clock::PLAUSIBLE_EPOCH_NS;
+use crate::control::Actuator;
+use crate::events::{Event, EventSource};
+use crate::pipeline::{SensorSource, SharedPipeline};
+use crate::raw_mirror::{Direction, RawTap};
+use crate::reconcile::ReportedRelay;
+use crate::state::CommandReply;
+
+pub use dcs_model::{AuxReading, Diagnostics, SensorData};
+
+// Balasan handshake HELLO|fw=<versi>|proto=<n>|device=<DEVICE_ID> saat port dibuka
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviceIdentity {
+    pub firmware: String,
+    pub protocol: u32,
+    pub device: String,
+}
+
+// Versi protokol serial yang dipahami backend; 1 = frame tanpa trailer seq/CRC, 2 = dengan trailer,
+// 3 = perintah backend diberi prefix #<id>| dan dibalas ACK/NAK dengan prefix yang sama
+pub const SUPPORTED_PROTOCOLS: std::ops::RangeInclusive<u32> = 1..=3;
+const COMMAND_ID_PROTOCOL: u32 = 3;
+
+// Tunggu balasan HELLO selama ini (ESP32 bisa sedang boot karena DTR saat port dibuka)
+const HELLO_TIMEOUT: Duration = Duration::from_secs(20);
+
+impl DeviceIdentity {
+    pub fn parse(line: &str) -> Option<Self> {
+        let (mut firmware, mut protocol, mut device) = (None, None, None);
+        for part in line.strip_prefix("HELLO|")?.split('|') {
+            match part.split_once('=')? {
+                ("fw", value) => firmware = Some(value.to_string()),
+                ("proto", value) => protocol = Some(value.parse().ok()?),
+                ("device", value) => device = Some(value.to_string()),
+                _ => {} // Field baru dari firmware yang lebih baru
+            }
+        }
+        Some(Self { firmware: firmware?, protocol: protocol?, device: device? })
+    }
+
+    pub fn supported(&self) -> bool {
+        SUPPORTED_PROTOCOLS.contains(&self.protocol)
+    }
+
+    // Firmware dengan id perintah membalas ACK/NAK per perintah
+    pub fn acknowledges_commands(&self) -> bool {
+        self.protocol >= COMMAND_ID_PROTOCOL
+    }
+}
+
+// Semua yang dilaporkan monitor serial ke pemanggil
 #[derive(Debug, Clone)]
-pub struct SensorData {
-    pub timestamp: u64,
-    pub temperature: f32,
-    pub humidity: f32,
-    pub exhaust_fan_status: Option<bool>,
-    pub pump_status: Option<bool>,
+pub enum SerialEvent {
+    // slave = alamat Modbus dari suffix |slave=<addr> (firmware lama tidak mengirimnya)
+    Sensor { slave: Option<u8>, data: SensorData },
+    // AUX_DATA|<ts>|soil_moisture=<v>[|water_level=<v>][|slave=<addr>]: slave tambahan di luar SENSOR_DATA
+    Aux { slave: Option<u8>, data: AuxReading },
+    Connected,
+    Disconnected(String),
+    // SENSOR_FAULT|<code>|consecutive=<n>[|slave=<addr>]: pembacaan RS485/Modbus di ESP32 gagal
+    SensorFault { slave: Option<u8>, code: String, consecutive: u32 },
+    // SENSOR_OK|recovered=<n>[|slave=<addr>]
+    SensorRecovered { slave: Option<u8> },
+    // BACKLOG_DATA|<ts>|<t>|<h>|fan=<0/1>|pump=<0/1>: sampel lama dari buffer flash ESP32
+    Backlog(SensorData),
+    // SAFE_STATE|<reason>: relay ESP32 ditahan di state aman karena sensor utama gagal
+    SafeState(String),
+    // BOOT_REASON|<reason>[|panic=<pesan>]: ESP32 baru saja boot
+    Boot { reason: String, panic: Option<String> },
+    Diagnostics(Diagnostics),
+    // Balasan HELLO; firmware lama tanpa handshake tidak pernah mengirimnya
+    Hello(DeviceIdentity),
+    // Status relay dari RELAY_STATUS, untuk dicocokkan dengan perintah backend
+    RelayState(Vec<(Actuator, bool)>),
+    // Mode relay berubah (fan_mode/pump_mode di RELAY_STATUS); LOCAL = override tombol di panel
+    RelayMode { relay: &'static str, old: Option<String>, mode: String },
+    // OTA_STATUS|<status>|<detail> (downloading, progress, done, failed, confirmed) dan ACK|OTA|<url> (accepted)
+    OtaStatus { status: String, detail: String },
+    // Perintah ber-id (SET/RELAY/OTA) dibalas ACK; attempts = jumlah kirim termasuk retry
+    CommandAcked { command: String, attempts: u32, latency: Duration },
+    // Perintah ber-id dibalas NAK (rejected = Some(alasan)) atau tidak dibalas setelah semua retry
+    CommandFailed { command: String, attempts: u32, rejected: Option<String> },
+}
+
+// Reset yang bukan power-on, reset manual, restart software (OTA) atau bangun dari deep sleep
+pub fn is_unexpected_reset(reason: &str) -> bool {
+    !matches!(reason, "poweron" | "external" | "software" | "deepsleep")
 }
 
 pub struct SerialMonitor {
     port_name: String,
     baud_rate: u32,
+    // Baris SET threshold yang harus berlaku di ESP32 (lihat AppState::device_settings)
+    settings: Option<Arc<Mutex<String>>>,
+    // Baris RELAY untuk relay yang diputuskan backend (lihat AppState::device_relays)
+    relays: Option<Arc<Mutex<String>>>,
+    // Baris OTA|<url> dari relay OTA ThingsBoard (lihat ota.rs); kosong = tidak ada update
+    ota: Option<Arc<Mutex<String>>>,
+    relay_aliases: RelayAliases,
+    // Salinan baris mentah untuk debug ([raw_mirror])
+    tap: Option<RawTap>,
+    // true = tutup port jika device tidak menjawab HELLO atau protokolnya tidak didukung
+    strict_handshake: bool,
+    // Tunggu ACK perintah ber-id selama ini, lalu kirim ulang maksimal command_retries kali
+    command_timeout: Duration,
+    command_retries: u32,
+}
+
+// Nama relay di RELAY_STATUS -> aktuator. Firmware lama menulis "motor" untuk exhaust fan;
+// board lain bisa memakai nama sendiri lewat [relay_aliases] di config.toml
+#[derive(Debug, Clone)]
+pub struct RelayAliases(HashMap<String, Actuator>);
+
+impl Default for RelayAliases {
+    fn default() -> Self {
+        Self::new(&HashMap::new())
+    }
+}
+
+impl RelayAliases {
+    pub fn new(extra: &HashMap<String, Actuator>) -> Self {
+        let mut aliases: HashMap<String, Actuator> = [
+            ("exhaust_fan", Actuator::ExhaustFan),
+            ("motor", Actuator::ExhaustFan),
+            ("fan", Actuator::ExhaustFan),
+            ("pump", Actuator::Pump),
+        ]
+        .into_iter()
+        .map(|(name, actuator)| (name.to_string(), actuator))
+        .collect();
+        aliases.extend(extra.iter().map(|(name, actuator)| (name.to_ascii_lowercase(), *actuator)));
+        Self(aliases)
+    }
+
+    fn resolve(&self, name: &str) -> Option<Actuator> {
+        self.0.get(&name.to_ascii_lowercase()).copied()
+    }
+}
+
+// Kegagalan berturut-turut sebelum alarm sensor_fault dinaikkan (~30 detik)
+const FAULT_ALARM_AFTER: u32 = 3;
+
+// Jeda minimum antar pengiriman TIME|<unix_ns> ke ESP32
+const TIME_SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+// Baris lebih panjang dari ini dipotong (noise tanpa newline tidak menghabiskan memori)
+const MAX_LINE_BYTES: u64 = 4096;
+
+// Frame data yang diberi trailer |<seq>|<crc16> oleh firmware baru
+const CHECKED_FRAMES: [&str; 4] = ["SENSOR_DATA|", "AUX_DATA|", "RELAY_STATUS|", "DIAG|"];
+
+// Perintah backend yang menunggu ACK dengan id yang sama
+struct PendingCommand {
+    id: u32,
+    // Indeks di outputs; perintah baru untuk output yang sama menggantikan yang lama
+    output: usize,
+    line: String,
+    attempts: u32,
+    first_sent: Instant,
+    last_sent: Instant,
+}
+
+// Baris outputs yang harus berlaku di ESP32: dikirim saat berubah; firmware protokol >= 3
+// membalas dengan id sehingga timeout, retry dan kegagalan bisa dideteksi. Firmware lama
+// tetap fire-and-forget. Timeout dicek setiap ada baris masuk (ESP32 mengirim data tiap beberapa detik).
+struct Outbox {
+    outputs: Vec<Arc<Mutex<String>>>,
+    sent: Vec<String>,
+    with_ids: bool,
+    next_id: u32,
+    pending: Vec<PendingCommand>,
+    timeout: Duration,
+    retries: u32,
+}
+
+impl Outbox {
+    fn new(outputs: Vec<Arc<Mutex<String>>>, timeout: Duration, retries: u32) -> Self {
+        Self { sent: vec![String::new(); outputs.len()], outputs, with_ids: false, next_id: 1, pending: Vec::new(), timeout, retries }
+    }
+
+    // Baris yang perlu dikirim (sudah diberi prefix id) untuk output yang berubah
+    fn changed(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (index, (output, sent)) in self.outputs.iter().zip(&mut self.sent).enumerate() {
+            let current = output.lock().unwrap().clone();
+            if current.is_empty() || current == *sent {
+                continue;
+            }
+            *sent = current.clone();
+            if !self.with_ids {
+                lines.push(current);
+                continue;
+            }
+            self.pending.retain(|p| p.output != index);
+            let id = self.next_id;
+            self.next_id = self.next_id.wrapping_add(1).max(1);
+            lines.push(format!("#{id}|{current}"));
+            self.pending.push(PendingCommand { id, output: index, line: current, attempts: 1, first_sent: Instant::now(), last_sent: Instant::now() });
+        }
+        lines
+    }
+
+    // (baris yang dikirim ulang, perintah yang menyerah setelah semua retry)
+    fn expired(&mut self) -> (Vec<String>, Vec<PendingCommand>) {
+        let (mut resend, mut failed) = (Vec::new(), Vec::new());
+        let mut i = 0;
+        while i < self.pending.len() {
+            let pending = &mut self.pending[i];
+            if pending.last_sent.elapsed() < self.timeout {
+                i += 1;
+            } else if pending.attempts <= self.retries {
+                pending.attempts += 1;
+                pending.last_sent = Instant::now();
+                warn!("⏱️  No ACK for '{}' (id {}) within {}ms, retry {}/{}", pending.line, pending.id, self.timeout.as_millis(), pending.attempts - 1, self.retries);
+                resend.push(format!("#{}|{}", pending.id, pending.line));
+                i += 1;
+            } else {
+                failed.push(self.pending.remove(i));
+            }
+        }
+        (resend, failed)
+    }
+
+    fn resolve(&mut self, id: u32) -> Option<PendingCommand> {
+        let index = self.pending.iter().position(|p| p.id == id)?;
+        Some(self.pending.remove(index))
+    }
+}
+
+// "#<id>|<baris>" dari firmware protokol >= 3 -> (id, baris)
+fn split_command_id(line: &str) -> Option<(u32, &str)> {
+    let (id, rest) = line.strip_prefix('#')?.split_once('|')?;
+    Some((id.parse().ok()?, rest))
+}
+
+// CRC-16/MODBUS, sama dengan calculate_crc16 di firmware
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            if crc & 0x0001 != 0 {
+                crc = (crc >> 1) ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+// Lepas trailer |<seq>|<crc16> (CRC atas "<frame>|<seq>", 4 digit hex). Frame tanpa trailer
+// (firmware lama, baris lain) dikembalikan apa adanya dengan seq None; Err = CRC tidak cocok.
+fn strip_frame_check(line: &str) -> Result<(&str, Option<u32>), String> {
+    if !CHECKED_FRAMES.iter().any(|prefix| line.starts_with(prefix)) {
+        return Ok((line, None));
+    }
+    let Some((signed, crc)) = line.rsplit_once('|') else { return Ok((line, None)) };
+    let Some((frame, seq)) = signed.rsplit_once('|') else { return Ok((line, None)) };
+    let is_crc = crc.len() == 4 && crc.chars().all(|c| c.is_ascii_hexdigit());
+    let is_seq = !seq.is_empty() && seq.chars().all(|c| c.is_ascii_digit());
+    if !is_crc || !is_seq {
+        return Ok((line, None));
+    }
+    let expected = u16::from_str_radix(crc, 16).map_err(|e| e.to_string())?;
+    let actual = crc16(signed.as_bytes());
+    if actual != expected {
+        return Err(format!("crc {:04X} != {}", actual, crc));
+    }
+    Ok((frame, seq.parse().ok()))
+}
+
+// Jalankan semua parser frame atas satu baris mentah seperti read_loop; entry point target fuzz
+// (`cargo fuzz run serial_frames` dari backend/). Panic = bug parser.
+pub fn fuzz_frame(bytes: &[u8]) {
+    let line = String::from_utf8_lossy(bytes);
+    let Ok((frame, _)) = strip_frame_check(line.trim()) else { return };
+    let _ = DeviceIdentity::parse(frame);
+    let _ = Diagnostics::parse(frame);
+    let _ = SerialMonitor::parse_backlog_data(frame);
+    let _ = SerialMonitor::parse_sensor_fault(frame);
+    let _ = SerialMonitor::parse_relay_status(frame, &RelayAliases::default());
+    if let Some((_, data)) = SerialMonitor::parse_aux_data(frame) {
+        assert!(!data.fields().is_empty() && data.fields().iter().all(|(_, value)| value.is_finite()));
+    }
+    if let Some((_, data)) = SerialMonitor::parse_sensor_data(frame) {
+        assert!(data.temperature.is_finite() && data.humidity.is_finite());
+        assert!(data.fan_duty.is_none_or(f32::is_finite));
+        assert!(data.aux.iter().all(|(name, value)| is_aux_field(name) && value.is_finite()));
+    }
+}
+
+// Isi satu frame RELAY_STATUS; relay yang tidak disebut tidak berubah
+#[derive(Debug, Default, PartialEq)]
+struct RelayUpdate {
+    states: Vec<(Actuator, bool)>,
+    // Dari bagian <relay>_mode=AUTO/REMOTE/LOCAL
+    modes: Vec<(Actuator, String)>,
+    // Bagian yang tidak dikenali (nama relay tanpa alias atau nilai bukan ON/OFF)
+    unknown: Vec<String>,
 }
 
 #[derive(Debug, Default)]
 struct RelayStatus {
     exhaust_fan: Option<bool>,
     pump: Option<bool>,
+    // fan_mode/pump_mode terakhir (AUTO, REMOTE, LOCAL); firmware lama tidak mengirimnya
+    fan_mode: Option<String>,
+    pump_mode: Option<String>,
 }
 
 impl SerialMonitor {
@@ -29,18 +327,71 @@ impl SerialMonitor {
         Self {
             port_name,
             baud_rate,
+            settings: None,
+            relays: None,
+            ota: None,
+            relay_aliases: RelayAliases::default(),
+            tap: None,
+            strict_handshake: false,
+            command_timeout: Duration::from_secs(3),
+            command_retries: 2,
         }
     }
 
-    pub async fn start_monitoring<F>(&self, mut on_data: F) -> Result<()>
+    pub fn with_settings(mut self, settings: Arc<Mutex<String>>) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+
+    pub fn with_relays(mut self, relays: Arc<Mutex<String>>) -> Self {
+        self.relays = Some(relays);
+        self
+    }
+
+    pub fn with_ota(mut self, ota: Arc<Mutex<String>>) -> Self {
+        self.ota = Some(ota);
+        self
+    }
+
+    pub fn with_relay_aliases(mut self, aliases: RelayAliases) -> Self {
+        self.relay_aliases = aliases;
+        self
+    }
+
+    pub fn with_tap(mut self, tap: Option<RawTap>) -> Self {
+        self.tap = tap;
+        self
+    }
+
+    pub fn with_strict_handshake(mut self, strict: bool) -> Self {
+        self.strict_handshake = strict;
+        self
+    }
+
+    pub fn with_command_ack(mut self, timeout: Duration, retries: u32) -> Self {
+        self.command_timeout = timeout;
+        self.command_retries = retries;
+        self
+    }
+
+    pub async fn start_monitoring<F>(&self, mut on_event: F) -> Result<()>
     where
-        F: FnMut(SensorData) -> Result<()> + Send + 'static,
+        F: FnMut(SerialEvent) -> Result<()> + Send + 'static,
     {
         let port_name = self.port_name.clone();
         let baud_rate = self.baud_rate;
+        // Baris yang harus berlaku di ESP32: SET threshold, RELAY dari backend, lalu OTA
+        let outputs: Vec<Arc<Mutex<String>>> = self.settings.iter().chain(&self.relays).chain(&self.ota).cloned().collect();
+        let relay_aliases = self.relay_aliases.clone();
+        let tap = self.tap.clone();
+        let strict_handshake = self.strict_handshake;
+        let (command_timeout, command_retries) = (self.command_timeout, self.command_retries);
 
         tokio::task::spawn_blocking(move || {
             info!("Starting serial monitor on {} @ {} baud", port_name, baud_rate);
+            // Timestamp device terbaru yang sudah diterima; setelah reconnect hanya backlog
+            // yang lebih baru yang diminta
+            let mut last_seen_ns: u64 = 0;
 
             loop {
                 match serialport::new(&port_name, baud_rate)
@@ -49,9 +400,12 @@ impl SerialMonitor {
                 {
                     Ok(port) => {
                         info!("Serial port {} opened successfully", port_name);
+                        let _ = on_event(SerialEvent::Connected);
 
-                        if let Err(e) = Self::read_loop(port, &mut on_data) {
+                        let outbox = Outbox::new(outputs.clone(), command_timeout, command_retries);
+                        if let Err(e) = Self::read_loop(port, &mut on_event, &mut last_seen_ns, outbox, &relay_aliases, tap.as_ref(), strict_handshake) {
                             error!("Serial read loop error: {}", e);
+                            let _ = on_event(SerialEvent::Disconnected(e.to_string()));
                         }
                     }
                     Err(e) => {
@@ -65,53 +419,273 @@ impl SerialMonitor {
         }).await?
     }
 
-    fn read_loop<F>(mut port: Box<dyn SerialPort>, on_data: &mut F) -> Result<()>
+    fn read_loop<F>(
+        mut port: Box<dyn SerialPort>,
+        on_event: &mut F,
+        last_seen_ns: &mut u64,
+        mut outbox: Outbox,
+        relay_aliases: &RelayAliases,
+        tap: Option<&RawTap>,
+        strict_handshake: bool,
+    ) -> Result<()>
     where
-        F: FnMut(SensorData) -> Result<()>,
+        F: FnMut(SerialEvent) -> Result<()>,
     {
+        // Jam ESP32 disetel dari host saat port dibuka dan setiap kali device
+        // masih mengirim timestamp sejak boot (mis. setelah reset)
+        let mut writer = port.try_clone().ok();
+        // Tanyakan identitas firmware dulu; Some = masih menunggu balasan HELLO (sejak kapan)
+        Self::send_line(writer.as_mut(), tap, "HELLO");
+        let mut hello_pending = Some(Instant::now());
+        let mut last_time_sync: Option<Instant> = None;
+        Self::send_time(writer.as_mut(), tap, &mut last_time_sync);
+        // Ambil sampel yang terlewat selama gateway tidak membaca port
+        Self::send_line(writer.as_mut(), tap, &format!("BACKLOG|{}", last_seen_ns));
+        // Threshold fan dan relay backend dikirim ulang setiap port dibuka (ESP32 bisa saja di-reflash/direset NVS-nya),
+        // setelah handshake selesai supaya diketahui apakah firmware memahami id perintah
+
         let mut reader = BufReader::new(&mut *port);
-        let mut line = String::new();
+        let mut buf = Vec::new();
         let mut relay_status = RelayStatus::default();
-        let mut pending_sensor_data: Option<SensorData> = None;
+        // Nomor urut frame terakhir dan statistik link sejak port dibuka
+        let mut last_seq: Option<u32> = None;
+        let mut corrupt_frames: u64 = 0;
+        let mut lost_frames: u64 = 0;
+        // Bagian RELAY_STATUS tak dikenal cukup diperingatkan sekali per port dibuka
+        let mut unknown_relay_keys: HashSet<String> = HashSet::new();
 
         loop {
-            line.clear();
-            match reader.read_line(&mut line) {
+            buf.clear();
+            // Byte non-UTF-8 (noise, baud salah) tidak memutus koneksi; frame rusak ditolak parser
+            match (&mut reader).take(MAX_LINE_BYTES).read_until(b'\n', &mut buf) {
                 Ok(0) => {
                     std::thread::sleep(Duration::from_millis(100));
                     continue;
                 }
                 Ok(_) => {
+                    let line = String::from_utf8_lossy(&buf);
                     let trimmed = line.trim();
 
                     if !trimmed.is_empty() {
                         info!("ESP32: {}", trimmed);
+                        if let Some(tap) = tap {
+                            tap(Direction::Rx, trimmed);
+                        }
                     }
 
-                    if let Some((exhaust_fan, pump)) = Self::parse_relay_status(trimmed) {
-                        relay_status.exhaust_fan = Some(exhaust_fan);
-                        relay_status.pump = Some(pump);
-                        info!("Relay status updated: Exhaust Fan={}, Pump={}",
-                              if exhaust_fan { "ON" } else { "OFF" },
-                              if pump { "ON" } else { "OFF" });
+                    if hello_pending.is_none() {
+                        let (resend, failed) = outbox.expired();
+                        for line in outbox.changed().iter().chain(&resend) {
+                            Self::send_line(writer.as_mut(), tap, line);
+                        }
+                        for pending in failed {
+                            let _ = on_event(SerialEvent::CommandFailed { command: pending.line, attempts: pending.attempts, rejected: None });
+                        }
+                    }
+                    if !trimmed.is_empty() && chaos::drop_serial_frame() {
+                        warn!("🧪 Chaos: dropping serial frame {}", trimmed);
+                        continue;
+                    }
+                    let trimmed = match strip_frame_check(trimmed) {
+                        Ok((frame, seq)) => {
+                            if let Some(seq) = seq {
+                                // Nomor lebih kecil = ESP32 reboot, hitungan mulai lagi dari 0
+                                if let Some(gap) = last_seq.filter(|last| seq > *last).map(|last| seq - last - 1) {
+                                    if gap > 0 {
+                                        lost_frames += gap as u64;
+                                        warn!("⚠️  {} serial frame(s) missing before seq {} ({} lost since port opened)",
+                                              gap, seq, lost_frames);
+                                    }
+                                }
+                                last_seq = Some(seq);
+                            }
+                            frame
+                        }
+                        Err(reason) => {
+                            corrupt_frames += 1;
+                            warn!("⚠️  Dropping corrupted serial frame ({}, {} since port opened): {}",
+                                  reason, corrupt_frames, trimmed);
+                            continue;
+                        }
+                    };
+
+                    // Balasan perintah ber-id: cocokkan dengan yang menunggu, lalu proses seperti baris biasa
+                    let trimmed = match split_command_id(trimmed) {
+                        Some((id, reply)) => {
+                            match outbox.resolve(id) {
+                                Some(pending) if reply.starts_with("NAK|") => {
+                                    let reason = reply.splitn(3, '|').nth(2).unwrap_or(reply).to_string();
+                                    let _ = on_event(SerialEvent::CommandFailed { command: pending.line, attempts: pending.attempts, rejected: Some(reason) });
+                                }
+                                Some(pending) => {
+                                    let latency = pending.first_sent.elapsed();
+                                    let _ = on_event(SerialEvent::CommandAcked { command: pending.line, attempts: pending.attempts, latency });
+                                }
+                                // Balasan retry yang terlambat atau perintah yang sudah digantikan
+                                None => debug!("Reply for unknown or superseded command id {}: {}", id, reply),
+                            }
+                            reply
+                        }
+                        None => trimmed,
+                    };
+
+                    if let Some(identity) = DeviceIdentity::parse(trimmed) {
+                        hello_pending = None;
+                        outbox.with_ids = identity.protocol >= COMMAND_ID_PROTOCOL;
+                        if !identity.supported() {
+                            let message = format!("ESP32 {} (fw {}) speaks serial protocol {}, backend supports {}-{}",
+                                                  identity.device, identity.firmware, identity.protocol,
+                                                  SUPPORTED_PROTOCOLS.start(), SUPPORTED_PROTOCOLS.end());
+                            let _ = on_event(SerialEvent::Hello(identity));
+                            if strict_handshake {
+                                bail!("{}", message);
+                            }
+                            warn!("⚠️  {}; frames may be misread", message);
+                            continue;
+                        }
+                        info!("🤝 ESP32 {} firmware {} (protocol {})", identity.device, identity.firmware, identity.protocol);
+                        let _ = on_event(SerialEvent::Hello(identity));
+                        continue;
+                    }
+                    if trimmed.starts_with("NAK|HELLO") {
+                        hello_pending = None;
+                        if strict_handshake {
+                            bail!("ESP32 firmware predates the HELLO handshake (connections.strict_handshake)");
+                        }
+                        warn!("⚠️  ESP32 firmware predates the HELLO handshake; firmware version and device id unknown");
+                        continue;
+                    }
+                    if let Some(since) = hello_pending {
+                        // Device baru boot: HELLO pertama bisa hilang selama bootloader
+                        if trimmed.starts_with("BOOT_REASON|") {
+                            Self::send_line(writer.as_mut(), tap, "HELLO");
+                            hello_pending = Some(Instant::now());
+                        } else if since.elapsed() >= HELLO_TIMEOUT {
+                            if strict_handshake {
+                                bail!("no HELLO reply from ESP32 within {}s", HELLO_TIMEOUT.as_secs());
+                            }
+                            warn!("⚠️  No HELLO reply from ESP32 within {}s; firmware version and device id unknown",
+                                  HELLO_TIMEOUT.as_secs());
+                            hello_pending = None;
+                        } else if strict_handshake {
+                            // Data dari device yang belum dikenal tidak dipakai
+                            continue;
+                        }
+                    }
+
+                    if let Some(reason) = trimmed.strip_prefix("NAK|SET|") {
+                        warn!("⚠️  ESP32 rejected thresholds from [thresholds]/[actuators]: {}", reason);
+                    }
+                    if let Some(reason) = trimmed.strip_prefix("NAK|RELAY|") {
+                        warn!("⚠️  ESP32 rejected relay command (firmware without heater relay?): {}", reason);
+                    }
+
+                    if let Some(data) = Self::parse_backlog_data(trimmed) {
+                        *last_seen_ns = (*last_seen_ns).max(data.timestamp);
+                        let _ = on_event(SerialEvent::Backlog(data));
+                        continue;
+                    }
+
+                    if let Some(diag) = Diagnostics::parse(trimmed) {
+                        let _ = on_event(SerialEvent::Diagnostics(diag));
+                        continue;
+                    }
+
+                    if let Some(rest) = trimmed.strip_prefix("BOOT_REASON|") {
+                        let (reason, panic) = match rest.split_once("|panic=") {
+                            Some((reason, message)) => (reason, Some(message.to_string())),
+                            None => (rest, None),
+                        };
+                        let _ = on_event(SerialEvent::Boot { reason: reason.to_string(), panic });
+                        continue;
+                    }
+
+                    if let Some(rest) = trimmed.strip_prefix("OTA_STATUS|") {
+                        let (status, detail) = rest.split_once('|').unwrap_or((rest, ""));
+                        let _ = on_event(SerialEvent::OtaStatus { status: status.to_string(), detail: detail.to_string() });
+                        continue;
+                    }
+                    if let Some(url) = trimmed.strip_prefix("ACK|OTA|") {
+                        let _ = on_event(SerialEvent::OtaStatus { status: "accepted".to_string(), detail: url.to_string() });
+                        continue;
+                    }
 
-                        if let Some(mut sensor_data) = pending_sensor_data.take() {
-                            sensor_data.exhaust_fan_status = relay_status.exhaust_fan;
-                            sensor_data.pump_status = relay_status.pump;
+                    if let Some(reason) = trimmed.strip_prefix("SAFE_STATE|") {
+                        let _ = on_event(SerialEvent::SafeState(reason.to_string()));
+                        continue;
+                    }
 
-                            if let Err(e) = on_data(sensor_data) {
-                                error!("Failed to process sensor data: {}", e);
+                    if let Some(event) = Self::parse_sensor_fault(trimmed) {
+                        let _ = on_event(event);
+                        continue;
+                    }
+
+                    if let Some(update) = Self::parse_relay_status(trimmed, relay_aliases) {
+                        for part in update.unknown {
+                            if unknown_relay_keys.insert(part.clone()) {
+                                warn!("⚠️  Unknown RELAY_STATUS entry '{}' (add it to [relay_aliases])", part);
+                            }
+                        }
+                        for (actuator, mode) in update.modes {
+                            let last = match actuator {
+                                Actuator::ExhaustFan => &mut relay_status.fan_mode,
+                                Actuator::Pump => &mut relay_status.pump_mode,
+                                // Status heater diputuskan backend, umpan balik device belum dipakai
+                                Actuator::Heater => continue,
+                            };
+                            if last.as_deref() != Some(mode.as_str()) {
+                                let old = last.replace(mode.clone());
+                                let _ = on_event(SerialEvent::RelayMode { relay: actuator.name(), old, mode });
+                            }
+                        }
+                        if update.states.is_empty() {
+                            continue;
+                        }
+                        let _ = on_event(SerialEvent::RelayState(update.states.clone()));
+                        for (actuator, on) in update.states {
+                            match actuator {
+                                Actuator::ExhaustFan => relay_status.exhaust_fan = Some(on),
+                                Actuator::Pump => relay_status.pump = Some(on),
+                                Actuator::Heater => {}
                             }
                         }
+                        let label = |state: Option<bool>| match state {
+                            Some(true) => "ON",
+                            Some(false) => "OFF",
+                            None => "unknown",
+                        };
+                        info!("Relay status updated: Exhaust Fan={}, Pump={}",
+                              label(relay_status.exhaust_fan), label(relay_status.pump));
+                        continue;
+                    }
+
+                    if trimmed.starts_with("AUX_DATA|") {
+                        match Self::parse_aux_data(trimmed) {
+                            Some((slave, data)) => {
+                                if let Err(e) = on_event(SerialEvent::Aux { slave, data }) {
+                                    error!("Failed to process auxiliary data: {}", e);
+                                }
+                            }
+                            None => warn!("Ignoring malformed AUX_DATA frame: {}", trimmed),
+                        }
+                        continue;
                     }
 
-                    if let Some(mut sensor_data) = Self::parse_sensor_data(trimmed) {
+                    if let Some((slave, mut sensor_data)) = Self::parse_sensor_data(trimmed) {
+                        if sensor_data.timestamp >= PLAUSIBLE_EPOCH_NS {
+                            *last_seen_ns = (*last_seen_ns).max(sensor_data.timestamp);
+                        }
+                        if sensor_data.timestamp < PLAUSIBLE_EPOCH_NS
+                            && last_time_sync.is_none_or(|t| t.elapsed() >= TIME_SYNC_INTERVAL)
+                        {
+                            Self::send_time(writer.as_mut(), tap, &mut last_time_sync);
+                        }
+
+                        // Status relay terakhir yang dilaporkan; sampel dikirim sekali saja
                         sensor_data.exhaust_fan_status = relay_status.exhaust_fan;
                         sensor_data.pump_status = relay_status.pump;
 
-                        pending_sensor_data = Some(sensor_data.clone());
-
-                        if let Err(e) = on_data(sensor_data) {
+                        if let Err(e) = on_event(SerialEvent::Sensor { slave, data: sensor_data }) {
                             error!("Failed to process sensor data: {}", e);
                         }
                     }
@@ -123,44 +697,743 @@ impl SerialMonitor {
         }
     }
 
-    fn parse_sensor_data(line: &str) -> Option<SensorData> {
-        // Parse format: "SENSOR_DATA|timestamp|temperature|humidity"
-        if let Some(stripped) = line.strip_prefix("SENSOR_DATA|") {
+    fn send_time(writer: Option<&mut Box<dyn SerialPort>>, tap: Option<&RawTap>, last_sync: &mut Option<Instant>) {
+        *last_sync = Some(Instant::now());
+        Self::send_line(writer, tap, &format!("TIME|{}", crate::now_ns()));
+    }
+
+    fn send_line(writer: Option<&mut Box<dyn SerialPort>>, tap: Option<&RawTap>, line: &str) {
+        let Some(writer) = writer else { return };
+        if let Some(tap) = tap {
+            tap(Direction::Tx, line);
+        }
+        let line = format!("{}\n", line);
+        if let Err(e) = writer.write_all(line.as_bytes()).and_then(|_| writer.flush()) {
+            warn!("Failed to send {} to ESP32: {}", line.trim(), e);
+        }
+    }
+
+    fn parse_sensor_data(line: &str) -> Option<(Option<u8>, SensorData)> {
+        SensorData::parse_frame(line)
+    }
+
+    fn parse_aux_data(line: &str) -> Option<(Option<u8>, AuxReading)> {
+        AuxReading::parse_frame(line)
+    }
+
+    fn parse_backlog_data(line: &str) -> Option<SensorData> {
+        // Parse format: "BACKLOG_DATA|timestamp|temperature|humidity|fan=1|pump=0"
+        let parts: Vec<&str> = line.strip_prefix("BACKLOG_DATA|")?.split('|').collect();
+        if parts.len() != 5 {
+            return None;
+        }
+        let timestamp = parts[0].parse::<u64>().ok()?;
+        // Buffer hanya diisi setelah jam ESP32 sinkron; selain itu tidak bisa ditempatkan di waktu
+        if timestamp < PLAUSIBLE_EPOCH_NS {
+            return None;
+        }
+        let flag = |part: &str, key: &str| part.strip_prefix(key).map(|v| v == "1");
+        Some(SensorData {
+            timestamp,
+            temperature: finite(parts[1])?,
+            humidity: finite(parts[2])?,
+            exhaust_fan_status: flag(parts[3], "fan="),
+            pump_status: flag(parts[4], "pump="),
+            fan_duty: None,
+            aux: Vec::new(),
+        })
+    }
+
+    fn parse_slave(parts: &[&str]) -> Option<u8> {
+        parts.iter().find_map(|part| part.strip_prefix("slave=")).and_then(|n| n.parse().ok())
+    }
+
+    fn parse_sensor_fault(line: &str) -> Option<SerialEvent> {
+        if line == "SENSOR_OK" {
+            return Some(SerialEvent::SensorRecovered { slave: None });
+        }
+        if let Some(stripped) = line.strip_prefix("SENSOR_OK|") {
             let parts: Vec<&str> = stripped.split('|').collect();
-            if parts.len() == 3 {
-                if let (Ok(timestamp), Ok(temperature), Ok(humidity)) = (
-                    parts[0].parse::<u64>(),
-                    parts[1].parse::<f32>(),
-                    parts[2].parse::<f32>(),
-                ) {
-                    return Some(SensorData {
-                        timestamp,
-                        temperature,
-                        humidity,
-                        exhaust_fan_status: None, // Will be filled by relay status
-                        pump_status: None, // Will be filled by relay status
-                    });
+            return Some(SerialEvent::SensorRecovered { slave: Self::parse_slave(&parts) });
+        }
+        // Parse format: "SENSOR_FAULT|no_response|consecutive=3|total=12|slave=2"
+        let stripped = line.strip_prefix("SENSOR_FAULT|")?;
+        let parts: Vec<&str> = stripped.split('|').collect();
+        let code = parts.first()?.to_string();
+        let consecutive = parts
+            .iter()
+            .find_map(|part| part.strip_prefix("consecutive="))
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(1);
+        Some(SerialEvent::SensorFault { slave: Self::parse_slave(&parts), code, consecutive })
+    }
+
+    // Format: "RELAY_STATUS|exhaust_fan:ON|pump:OFF[|fan_mode=AUTO|pump_mode=LOCAL]"; firmware lama
+    // mengirim "motor:ON". Urutan bebas, ':' atau '=', nilai ON/OFF/1/0/true/false tanpa beda huruf.
+    fn parse_relay_status(line: &str, aliases: &RelayAliases) -> Option<RelayUpdate> {
+        let stripped = line.strip_prefix("RELAY_STATUS|")?;
+        let mut update = RelayUpdate::default();
+        for part in stripped.split('|').map(str::trim).filter(|part| !part.is_empty()) {
+            let Some((key, value)) = part.split_once([':', '=']) else {
+                update.unknown.push(part.to_string());
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            if let Some(relay) = key.strip_suffix("_mode") {
+                match aliases.resolve(relay) {
+                    Some(actuator) if !value.is_empty() => update.modes.push((actuator, value.to_ascii_uppercase())),
+                    _ => update.unknown.push(part.to_string()),
                 }
+                continue;
+            }
+            let state = match value.to_ascii_uppercase().as_str() {
+                "ON" | "1" | "TRUE" => Some(true),
+                "OFF" | "0" | "FALSE" => Some(false),
+                _ => None,
+            };
+            match (aliases.resolve(key), state) {
+                (Some(actuator), Some(on)) => update.states.push((actuator, on)),
+                _ => update.unknown.push(part.to_string()),
             }
         }
-        None
+        Some(update)
     }
+}
 
-    fn parse_relay_status(line: &str) -> Option<(bool, bool)> {
-        // Parse format: "RELAY_STATUS|exhaust_fan:ON|pump:OFF"
-        if let Some(stripped) = line.strip_prefix("RELAY_STATUS|") {
-            let parts: Vec<&str> = stripped.split('|').collect();
-            if parts.len() == 2 {
-                let exhaust_fan_part = parts[0].strip_prefix("exhaust_fan:").unwrap_or("");
-                let pump_part = parts[1].strip_prefix("pump:").unwrap_or("");
+// Port serial ESP32 sebagai sumber pipeline; status koneksi dicatat di AppState
+pub struct SerialSource {
+    pub port_name: String,
+    pub baud_rate: u32,
+    pub device_id: String,
+    pub settings: Arc<Mutex<String>>,
+    pub relays: Arc<Mutex<String>>,
+    pub ota: Arc<Mutex<String>>,
+    pub relay_aliases: HashMap<String, Actuator>,
+    pub raw_tap: Option<RawTap>,
+    pub strict_handshake: bool,
+    pub command_timeout: Duration,
+    pub command_retries: u32,
+}
+
+impl SensorSource for SerialSource {
+    fn start(self: Box<Self>, pipeline: SharedPipeline) {
+        let monitor = SerialMonitor::new(self.port_name.clone(), self.baud_rate)
+            .with_settings(self.settings.clone())
+            .with_relays(self.relays.clone())
+            .with_ota(self.ota.clone())
+            .with_relay_aliases(RelayAliases::new(&self.relay_aliases))
+            .with_tap(self.raw_tap.clone())
+            .with_strict_handshake(self.strict_handshake)
+            .with_command_ack(self.command_timeout, self.command_retries);
+        let state = pipeline.lock().unwrap().state().clone();
+        let port = self.port_name;
+        let base_id = self.device_id;
+        // Slave 1 (atau firmware tanpa tag slave) = device_id lama; slave lain = <device_id>-<addr>
+        let device_for = move |slave: Option<u8>| match slave {
+            None | Some(1) => base_id.clone(),
+            Some(addr) => format!("{base_id}-{addr}"),
+        };
+        // DEVICE_ID dari HELLO/DIAG yang sudah diperingatkan berbeda dengan config
+        let mut warned_device: Option<String> = None;
+        tokio::spawn(async move {
+            if let Err(e) = monitor.start_monitoring(move |event| {
+                match event {
+                    SerialEvent::Sensor { slave, data } => {
+                        let device_id = device_for(slave);
+                        state.clear_alarm(&format!("sensor_fault_{device_id}"));
+                        state.clear_alarm(&format!("safe_state_{device_id}"));
+                        pipeline.lock().unwrap().process(&device_id, data);
+                    }
+                    SerialEvent::Aux { slave, data } => {
+                        // Probe tambahan milik ESP32 ini (zone yang sama); alamatnya jadi tag slave
+                        pipeline.lock().unwrap().aux(&device_for(None), slave, &data);
+                    }
+                    SerialEvent::SensorFault { slave, code, consecutive } => {
+                        let device_id = device_for(slave);
+                        warn!("🧯 ESP32 sensor fault on {}: {} ({} consecutive)", device_id, code, consecutive);
+                        if consecutive >= FAULT_ALARM_AFTER {
+                            let message = format!("RS485 sensor read failing on {device_id}: {code} ({consecutive} consecutive cycles)");
+                            state.raise_alarm(&format!("sensor_fault_{device_id}"), Severity::Critical, message);
+                        }
+                    }
+                    SerialEvent::SensorRecovered { slave } => {
+                        state.clear_alarm(&format!("sensor_fault_{}", device_for(slave)));
+                    }
+                    SerialEvent::SafeState(reason) => {
+                        let device_id = device_for(None);
+                        warn!("🛑 ESP32 {} relays in safe state: {}", device_id, reason);
+                        state.events.record(Event::new("relay", &device_id, "safe_state").reason(reason.clone()));
+                        let message = format!("Relays on {device_id} forced to safe state: {reason}");
+                        state.raise_alarm(&format!("safe_state_{device_id}"), Severity::Critical, message);
+                    }
+                    SerialEvent::Boot { reason, panic } => {
+                        let device_id = device_for(None);
+                        let mut event = Event::new("device", &device_id, "boot").reason(reason.clone());
+                        if is_unexpected_reset(&reason) {
+                            let count = state.note_device_reset(&device_id);
+                            warn!("💥 ESP32 {} reset unexpectedly ({}), {} since backend start{}", device_id, reason, count,
+                                  panic.as_deref().map(|p| format!(": {p}")).unwrap_or_default());
+                            if let Some(message) = panic {
+                                event = event.reason(format!("{reason}: {message}"));
+                            }
+                        } else {
+                            info!("ESP32 {} booted ({})", device_id, reason);
+                        }
+                        state.events.record(event);
+                    }
+                    SerialEvent::RelayState(states) => {
+                        let mut reported = state.reported_relays.lock().unwrap();
+                        for (actuator, on) in states {
+                            let entry = reported.entry(actuator).or_insert(ReportedRelay { on: None, mode: None, at: Instant::now() });
+                            entry.on = Some(on);
+                            entry.at = Instant::now();
+                        }
+                    }
+                    SerialEvent::RelayMode { relay, old, mode } => {
+                        if let Some(actuator) = Actuator::ALL.into_iter().find(|a| a.name() == relay) {
+                            let mut reported = state.reported_relays.lock().unwrap();
+                            let entry = reported.entry(actuator).or_insert(ReportedRelay { on: None, mode: None, at: Instant::now() });
+                            entry.mode = Some(mode.clone());
+                        }
+                        // Frame pertama setelah port dibuka dengan mode AUTO bukan perubahan
+                        if old.is_none() && mode == "AUTO" {
+                            return Ok(());
+                        }
+                        let device_id = device_for(None);
+                        let subject = format!("{device_id}/{relay}");
+                        if mode == "LOCAL" {
+                            warn!("🔘 {} switched to local manual override (panel button)", subject);
+                        } else {
+                            info!("{} relay mode: {}", subject, mode);
+                        }
+                        let mut event = Event::new("relay_mode", subject, mode.clone()).reason("RELAY_STATUS from device");
+                        if let Some(old) = old {
+                            event = event.old(old);
+                        }
+                        if mode == "LOCAL" {
+                            event = event.source(EventSource::Manual).reason("panel button on device");
+                        }
+                        state.events.record(event);
+                    }
+                    SerialEvent::Diagnostics(diag) => {
+                        let device_id = device_for(None);
+                        // device_id tetap dari config; DEVICE_ID firmware hanya dicocokkan
+                        if let Some(reported) = diag.device.as_deref().filter(|reported| *reported != device_id) {
+                            if warned_device.as_deref() != Some(reported) {
+                                warn!("⚠️  ESP32 on {} reports DEVICE_ID {} but config device_id is {}", port, reported, device_id);
+                                warned_device = Some(reported.to_string());
+                            }
+                        }
+                        pipeline.lock().unwrap().diagnostics(&device_id, &diag);
+                    }
+                    SerialEvent::Hello(identity) => {
+                        let device_id = device_for(None);
+                        if identity.device != device_id && warned_device.as_deref() != Some(identity.device.as_str()) {
+                            warn!("⚠️  ESP32 on {} reports DEVICE_ID {} but config device_id is {}", port, identity.device, device_id);
+                            warned_device = Some(identity.device.clone());
+                        }
+                        if let Some(old) = state.note_firmware(&device_id, &identity.firmware) {
+                            info!("🆙 {} firmware changed: {} -> {}", device_id, old, identity.firmware);
+                            state.events.record(Event::new("firmware", &device_id, identity.firmware.clone()).old(old).reason("HELLO from device"));
+                        }
+                        *state.device_identity.lock().unwrap() = Some(identity);
+                    }
+                    SerialEvent::OtaStatus { status, detail } => {
+                        let device_id = device_for(None);
+                        match status.as_str() {
+                            "failed" => warn!("📦 ESP32 {} OTA failed: {}", device_id, detail),
+                            "progress" => debug!("📦 ESP32 {} OTA {}", device_id, detail),
+                            _ => info!("📦 ESP32 {} OTA {} {}", device_id, status, detail),
+                        }
+                        if status != "progress" {
+                            state.events.record(Event::new("ota", &device_id, status.clone()).reason(detail.clone()));
+                        }
+                        *state.ota.device_status.lock().unwrap() = Some((status, detail));
+                    }
+                    SerialEvent::CommandAcked { command, attempts, latency } => {
+                        if attempts > 1 {
+                            info!("✅ ESP32 acknowledged '{}' after {} attempts ({}ms)", command, attempts, latency.as_millis());
+                        } else {
+                            debug!("ESP32 acknowledged '{}' in {}ms", command, latency.as_millis());
+                        }
+                        state.clear_alarm(&format!("command_unacked_{}", device_for(None)));
+                        state.command_replied(CommandReply { command, result: Ok(()) });
+                    }
+                    SerialEvent::CommandFailed { command, attempts, rejected } => {
+                        let device_id = device_for(None);
+                        let name = command.split('|').next().unwrap_or_default().to_ascii_lowercase();
+                        match &rejected {
+                            Some(reason) => {
+                                warn!("⚠️  ESP32 {} rejected '{}': {}", device_id, command, reason);
+                                state.events.record(Event::new("command", format!("{device_id}/{name}"), "rejected").reason(format!("{command}: {reason}")));
+                            }
+                            None => {
+                                warn!("📵 ESP32 {} did not acknowledge '{}' after {} attempts", device_id, command, attempts);
+                                state.events.record(Event::new("command", format!("{device_id}/{name}"), "unacked").reason(format!("{command}: no ACK after {attempts} attempts")));
+                                let message = format!("ESP32 {device_id} did not acknowledge {command} after {attempts} attempts");
+                                state.raise_alarm(&format!("command_unacked_{device_id}"), Severity::Warning, message);
+                            }
+                        }
+                        let result = Err(rejected.map_or_else(|| format!("no ACK after {attempts} attempts"), |reason| format!("NAK: {reason}")));
+                        state.command_replied(CommandReply { command, result });
+                    }
+                    SerialEvent::Backlog(data) => {
+                        pipeline.lock().unwrap().process_backlog(&device_for(None), data);
+                    }
+                    SerialEvent::Connected => {
+                        state.serial_connected.store(true, Ordering::Relaxed);
+                        *state.device_identity.lock().unwrap() = None;
+                        state.events.record(Event::new("serial", &port, "connected").reason("port opened"));
+                    }
+                    SerialEvent::Disconnected(reason) => {
+                        state.serial_connected.store(false, Ordering::Relaxed);
+                        state.reported_relays.lock().unwrap().clear();
+                        state.events.record(Event::new("serial", &port, "disconnected").old("connected").reason(reason));
+                    }
+                }
+                Ok(())
+            }).await {
+                error!("Serial monitoring failed: {}", e);
+            }
+        });
+    }
+}
 
-                let exhaust_fan_on = exhaust_fan_part == "ON";
-                let pump_on = pump_part == "ON";
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dcs_model::RelayMode;
+
+    fn parse(line: &str) -> RelayUpdate {
+        SerialMonitor::parse_relay_status(line, &RelayAliases::default()).expect("RELAY_STATUS frame")
+    }
+
+    #[test]
+    fn current_firmware_format() {
+        let update = parse("RELAY_STATUS|exhaust_fan:ON|pump:OFF|fan_mode=AUTO|pump_mode=LOCAL");
+        assert_eq!(update.states, vec![(Actuator::ExhaustFan, true), (Actuator::Pump, false)]);
+        assert_eq!(
+            update.modes,
+            vec![(Actuator::ExhaustFan, "AUTO".to_string()), (Actuator::Pump, "LOCAL".to_string())]
+        );
+        assert!(update.unknown.is_empty());
+    }
+
+    #[test]
+    fn legacy_motor_naming() {
+        let update = parse("RELAY_STATUS|motor:OFF|pump:ON");
+        assert_eq!(update.states, vec![(Actuator::ExhaustFan, false), (Actuator::Pump, true)]);
+        assert!(update.modes.is_empty());
+        assert!(update.unknown.is_empty());
+    }
+
+    #[test]
+    fn tolerant_order_case_and_separator() {
+        let update = parse("RELAY_STATUS| Pump=1 |Exhaust_Fan:on|motor_mode=remote|");
+        assert_eq!(update.states, vec![(Actuator::Pump, true), (Actuator::ExhaustFan, true)]);
+        assert_eq!(update.modes, vec![(Actuator::ExhaustFan, "REMOTE".to_string())]);
+    }
+
+    #[test]
+    fn unknown_keys_and_values_are_reported() {
+        let update = parse("RELAY_STATUS|heater:ON|pump:HALF|exhaust_fan:OFF|garbage");
+        assert_eq!(update.states, vec![(Actuator::ExhaustFan, false)]);
+        assert_eq!(update.unknown, vec!["heater:ON", "pump:HALF", "garbage"]);
+    }
+
+    #[test]
+    fn configured_aliases() {
+        let extra = HashMap::from([("Relay2".to_string(), Actuator::Pump), ("motor".to_string(), Actuator::Pump)]);
+        let update = SerialMonitor::parse_relay_status("RELAY_STATUS|relay2:ON|motor:OFF", &RelayAliases::new(&extra)).unwrap();
+        assert_eq!(update.states, vec![(Actuator::Pump, true), (Actuator::Pump, false)]);
+        assert!(update.unknown.is_empty());
+    }
+
+    #[test]
+    fn other_frames_are_not_relay_status() {
+        assert!(SerialMonitor::parse_relay_status("SENSOR_DATA|0|25.30|65.20", &RelayAliases::default()).is_none());
+    }
 
-                return Some((exhaust_fan_on, pump_on));
+    #[test]
+    fn hello_identity() {
+        let identity = DeviceIdentity::parse("HELLO|fw=1.2.0|proto=2|device=esp32-zone1").expect("HELLO frame");
+        assert_eq!(identity.firmware, "1.2.0");
+        assert_eq!(identity.protocol, 2);
+        assert_eq!(identity.device, "esp32-zone1");
+        assert!(identity.supported());
+        assert!(!DeviceIdentity::parse("HELLO|fw=9.0.0|proto=7|device=x").unwrap().supported());
+        assert!(DeviceIdentity::parse("HELLO|fw=1.2.0|device=esp32-zone1").is_none());
+    }
+
+    fn sensor(line: &str) -> Option<(Option<u8>, SensorData)> {
+        SerialMonitor::parse_sensor_data(line)
+    }
+
+    // Frame dengan trailer |<seq>|<crc16> seperti yang dikirim firmware
+    fn signed(frame: &str, seq: u32) -> String {
+        let body = format!("{frame}|{seq}");
+        format!("{body}|{:04X}", crc16(body.as_bytes()))
+    }
+
+    #[test]
+    fn sensor_data_minimal_frame() {
+        let (slave, data) = sensor("SENSOR_DATA|1700000000000000000|25.30|65.20").expect("SENSOR_DATA frame");
+        assert_eq!(slave, None);
+        assert_eq!(data.timestamp, 1_700_000_000_000_000_000);
+        assert_eq!(data.temperature, 25.3);
+        assert_eq!(data.humidity, 65.2);
+        assert_eq!(data.fan_duty, None);
+        assert!(data.aux.is_empty());
+        assert_eq!(data.exhaust_fan_status, None);
+    }
+
+    #[test]
+    fn sensor_data_extended_frame() {
+        let (slave, data) = sensor("SENSOR_DATA|12345|-5.5|99.9|slave=2|fan_duty=40|soil_moisture=31.5|Mode=X|water_level=NaN")
+            .expect("SENSOR_DATA frame");
+        assert_eq!(slave, Some(2));
+        assert_eq!(data.temperature, -5.5);
+        assert_eq!(data.fan_duty, Some(40.0));
+        // Field baru yang tidak dikenal dilewati, aux non-numerik dibuang tanpa menolak frame
+        assert_eq!(data.aux, vec![("soil_moisture".to_string(), 31.5)]);
+    }
+
+    #[test]
+    fn sensor_data_truncated_frames() {
+        for line in [
+            "SENSOR_DATA",
+            "SENSOR_DATA|",
+            "SENSOR_DATA|1700000000",
+            "SENSOR_DATA|1700000000|25.3",
+            "SENSOR_DATA|1700000000|25.3|",
+            "SENSOR_DATA|1700000000|25.3|65.2|slave=",
+            "SENSOR_DATA|1700000000|25.3|65.2|fan_duty=",
+            "ENSOR_DATA|1700000000|25.3|65.2",
+        ] {
+            assert!(sensor(line).is_none(), "{line}");
+        }
+        let full = "SENSOR_DATA|1700000000|25.3|65.2|slave=2";
+        for end in 0..full.len() {
+            let _ = sensor(&full[..end]);
+        }
+    }
+
+    #[test]
+    fn sensor_data_interleaved_log_lines() {
+        for line in [
+            "I (1234) sht20: SENSOR_DATA|1700000000|25.3|65.2",
+            "SENSOR_DATA|1700000000|25.3|6I (1234) wifi: sta connected",
+            "SENSOR_DATA|1700000000|25.3|65.2|E (99) modbus: timeout",
+            "SENSOR_DATA|17000I (5) boot: ok|25.3|65.2",
+            "\u{1b}[0;32mI (1234) main: reading sensor\u{1b}[0m",
+        ] {
+            assert!(sensor(line).is_none(), "{line}");
+            assert!(SerialMonitor::parse_backlog_data(line).is_none(), "{line}");
+        }
+    }
+
+    #[test]
+    fn sensor_data_rejects_nan_and_overflow() {
+        for line in [
+            "SENSOR_DATA|1700000000|NaN|65.2",
+            "SENSOR_DATA|1700000000|25.3|nan",
+            "SENSOR_DATA|1700000000|inf|65.2",
+            "SENSOR_DATA|1700000000|-inf|65.2",
+            "SENSOR_DATA|1700000000|1e39|65.2",
+            "SENSOR_DATA|1700000000|25.3|-3.5e38",
+            "SENSOR_DATA|18446744073709551616|25.3|65.2",
+            "SENSOR_DATA|-1|25.3|65.2",
+            "SENSOR_DATA|1700000000|25.3|65.2|slave=256",
+            "SENSOR_DATA|1700000000|25.3|65.2|slave=-1",
+            "SENSOR_DATA|1700000000|25.3|65.2|fan_duty=NaN",
+            "SENSOR_DATA|1700000000|25.3|65.2|fan_duty=1e40",
+        ] {
+            assert!(sensor(line).is_none(), "{line}");
+        }
+        let (_, data) = sensor("SENSOR_DATA|18446744073709551615|3.4e38|0").unwrap();
+        assert_eq!(data.timestamp, u64::MAX);
+        assert!(data.temperature.is_finite());
+    }
+
+    #[test]
+    fn backlog_data_frames() {
+        let data = SerialMonitor::parse_backlog_data("BACKLOG_DATA|1700000000000000000|25.3|65.2|fan=1|pump=0").unwrap();
+        assert_eq!((data.exhaust_fan_status, data.pump_status), (Some(true), Some(false)));
+        for line in [
+            "BACKLOG_DATA|1700000000000000000|NaN|65.2|fan=1|pump=0",
+            "BACKLOG_DATA|1700000000000000000|25.3|inf|fan=1|pump=0",
+            "BACKLOG_DATA|1700000000000000000|25.3|65.2|fan=1",
+            "BACKLOG_DATA|12345|25.3|65.2|fan=1|pump=0",
+        ] {
+            assert!(SerialMonitor::parse_backlog_data(line).is_none(), "{line}");
+        }
+    }
+
+    #[test]
+    fn relay_status_truncated_and_empty_values() {
+        assert_eq!(parse("RELAY_STATUS|"), RelayUpdate::default());
+        let update = parse("RELAY_STATUS|exhaust_fan:|fan_mode=|pump");
+        assert!(update.states.is_empty() && update.modes.is_empty());
+        assert_eq!(update.unknown, vec!["exhaust_fan:", "fan_mode=", "pump"]);
+        assert!(SerialMonitor::parse_relay_status("RELAY_STATUS", &RelayAliases::default()).is_none());
+        assert!(SerialMonitor::parse_relay_status("I (12) relay: RELAY_STATUS|pump:ON", &RelayAliases::default()).is_none());
+    }
+
+    #[test]
+    fn aux_data_frames() {
+        let (slave, data) = SerialMonitor::parse_aux_data("AUX_DATA|1700000000000000000|soil_moisture=41.2|slave=3").expect("AUX_DATA frame");
+        assert_eq!(slave, Some(3));
+        assert_eq!(data.timestamp, 1_700_000_000_000_000_000);
+        assert_eq!((data.soil_moisture, data.water_level), (Some(41.2), None));
+        assert_eq!(data.fields(), vec![("soil_moisture", 41.2)]);
+
+        // Level air tanpa slave (kanal analog device utama); kanal baru dilewati
+        let (slave, data) = SerialMonitor::parse_aux_data("AUX_DATA|12345|water_level=80.0|ph=6.5").unwrap();
+        assert_eq!(slave, None);
+        assert_eq!(data.fields(), vec![("water_level", 80.0)]);
+
+        for line in [
+            "AUX_DATA|1700000000",
+            "AUX_DATA|1700000000|ph=6.5",
+            "AUX_DATA|1700000000|soil_moisture=NaN",
+            "AUX_DATA|1700000000|soil_moisture=",
+            "AUX_DATA|1700000000|soil_moisture=41.2|slave=300",
+            "AUX_DATA|x|soil_moisture=41.2",
+            "I (12) soil: AUX_DATA|1700000000|soil_moisture=41.2",
+        ] {
+            assert!(SerialMonitor::parse_aux_data(line).is_none(), "{line}");
+        }
+    }
+
+    #[test]
+    fn command_ids() {
+        assert_eq!(split_command_id("#42|ACK|RELAY|pump=ON"), Some((42, "ACK|RELAY|pump=ON")));
+        assert_eq!(split_command_id("#7|NAK|SET|temp_on must be > temp_off"), Some((7, "NAK|SET|temp_on must be > temp_off")));
+        assert_eq!(split_command_id("ACK|RELAY|pump=ON"), None);
+        assert_eq!(split_command_id("#x|ACK|SET"), None);
+    }
+
+    #[test]
+    fn outbox_retries_then_gives_up() {
+        let relays = Arc::new(Mutex::new("RELAY|pump=ON".to_string()));
+        let mut outbox = Outbox::new(vec![relays.clone()], Duration::ZERO, 1);
+        // Firmware lama: tanpa id dan tanpa menunggu ACK
+        assert_eq!(outbox.changed(), vec!["RELAY|pump=ON".to_string()]);
+        assert!(outbox.pending.is_empty());
+
+        let mut outbox = Outbox::new(vec![relays.clone()], Duration::ZERO, 1);
+        outbox.with_ids = true;
+        assert_eq!(outbox.changed(), vec!["#1|RELAY|pump=ON".to_string()]);
+        assert!(outbox.changed().is_empty());
+        let (resend, failed) = outbox.expired();
+        assert_eq!(resend, vec!["#1|RELAY|pump=ON".to_string()]);
+        assert!(failed.is_empty());
+        let (resend, failed) = outbox.expired();
+        assert!(resend.is_empty());
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].attempts, 2);
+
+        // Perintah baru menggantikan yang masih menunggu; ACK id lama diabaikan
+        *relays.lock().unwrap() = "RELAY|pump=OFF".to_string();
+        assert_eq!(outbox.changed(), vec!["#2|RELAY|pump=OFF".to_string()]);
+        *relays.lock().unwrap() = "RELAY|pump=AUTO".to_string();
+        assert_eq!(outbox.changed(), vec!["#3|RELAY|pump=AUTO".to_string()]);
+        assert!(outbox.resolve(2).is_none());
+        assert_eq!(outbox.resolve(3).map(|p| p.line), Some("RELAY|pump=AUTO".to_string()));
+        assert!(outbox.pending.is_empty());
+    }
+
+    #[test]
+    fn outbox_sends_merged_relay_frame() {
+        let state = crate::state::AppState::new(&crate::config::Config::default());
+        let mut outbox = Outbox::new(vec![state.device_relays.clone()], Duration::ZERO, 1);
+        assert!(outbox.changed().is_empty());
+        state.command_relay(Actuator::Heater, RelayMode::On);
+        assert_eq!(outbox.changed(), vec!["RELAY|heater=ON".to_string()]);
+        // Relay lain ditambahkan ke baris yang sama; heater tidak hilang dari perintah berikutnya
+        state.command_relay(Actuator::ExhaustFan, RelayMode::Off);
+        assert_eq!(outbox.changed(), vec!["RELAY|exhaust_fan=OFF|heater=ON".to_string()]);
+        assert!(outbox.changed().is_empty());
+    }
+
+    #[test]
+    fn frame_check_trailer() {
+        let line = signed("SENSOR_DATA|1700000000|25.3|65.2", 7);
+        assert_eq!(strip_frame_check(&line), Ok(("SENSOR_DATA|1700000000|25.3|65.2", Some(7))));
+        // Satu byte berubah di tengah jalan = CRC tidak cocok
+        let corrupted = line.replacen("25.3", "26.3", 1);
+        assert!(strip_frame_check(&corrupted).is_err());
+        // Trailer terpotong = frame firmware lama, isinya tetap diperiksa parser
+        let truncated = &line[..line.len() - 2];
+        let (frame, seq) = strip_frame_check(truncated).unwrap();
+        assert_eq!(seq, None);
+        assert!(sensor(frame).is_none());
+    }
+
+    #[test]
+    fn fuzz_frame_never_panics() {
+        let seeds = [
+            signed("SENSOR_DATA|1700000000|25.3|65.2|slave=2|fan_duty=40|soil_moisture=31.5", 1),
+            signed("RELAY_STATUS|exhaust_fan:ON|pump:OFF|fan_mode=AUTO", 2),
+            signed("DIAG|uptime=10|heap=1000|rssi=-60|resets=0|modbus_errors=0|fw=1.0|device=x", 3),
+            "BACKLOG_DATA|1700000000000000000|25.3|65.2|fan=1|pump=0".to_string(),
+            "SENSOR_FAULT|no_response|consecutive=3|total=12|slave=2".to_string(),
+            "HELLO|fw=1.2.0|proto=2|device=esp32".to_string(),
+            signed("AUX_DATA|1700000000000000000|soil_moisture=41.2|water_level=80.0|slave=3", 4),
+        ];
+        // Pseudo-random deterministik (xorshift) supaya kegagalan bisa diulang
+        let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for seed in &seeds {
+            let bytes = seed.as_bytes();
+            for end in 0..=bytes.len() {
+                fuzz_frame(&bytes[..end]);
+            }
+            for _ in 0..2000 {
+                let mut mutated = bytes.to_vec();
+                for _ in 0..1 + next() % 4 {
+                    let at = (next() % mutated.len() as u64) as usize;
+                    match next() % 3 {
+                        0 => mutated[at] = next() as u8,
+                        1 => mutated.insert(at, b"|=:.-e\xff"[(next() % 7) as usize]),
+                        _ => {
+                            mutated.remove(at);
+                        }
+                    }
+                    if mutated.is_empty() {
+                        break;
+                    }
+                }
+                fuzz_frame(&mutated);
             }
         }
-        None
     }
-}
 
+    // Port palsu: membaca isi yang disiapkan lalu gagal supaya read_loop berhenti
+    struct ScriptedPort(std::io::Cursor<Vec<u8>>);
+
+    impl Read for ScriptedPort {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.read(buf)? {
+                0 => Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "end of script")),
+                n => Ok(n),
+            }
+        }
+    }
+
+    impl Write for ScriptedPort {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SerialPort for ScriptedPort {
+        fn name(&self) -> Option<String> { Some("scripted".to_string()) }
+        fn baud_rate(&self) -> serialport::Result<u32> { Ok(115_200) }
+        fn data_bits(&self) -> serialport::Result<serialport::DataBits> { Ok(serialport::DataBits::Eight) }
+        fn flow_control(&self) -> serialport::Result<serialport::FlowControl> { Ok(serialport::FlowControl::None) }
+        fn parity(&self) -> serialport::Result<serialport::Parity> { Ok(serialport::Parity::None) }
+        fn stop_bits(&self) -> serialport::Result<serialport::StopBits> { Ok(serialport::StopBits::One) }
+        fn timeout(&self) -> Duration { Duration::from_millis(100) }
+        fn set_baud_rate(&mut self, _: u32) -> serialport::Result<()> { Ok(()) }
+        fn set_data_bits(&mut self, _: serialport::DataBits) -> serialport::Result<()> { Ok(()) }
+        fn set_flow_control(&mut self, _: serialport::FlowControl) -> serialport::Result<()> { Ok(()) }
+        fn set_parity(&mut self, _: serialport::Parity) -> serialport::Result<()> { Ok(()) }
+        fn set_stop_bits(&mut self, _: serialport::StopBits) -> serialport::Result<()> { Ok(()) }
+        fn set_timeout(&mut self, _: Duration) -> serialport::Result<()> { Ok(()) }
+        fn write_request_to_send(&mut self, _: bool) -> serialport::Result<()> { Ok(()) }
+        fn write_data_terminal_ready(&mut self, _: bool) -> serialport::Result<()> { Ok(()) }
+        fn read_clear_to_send(&mut self) -> serialport::Result<bool> { Ok(true) }
+        fn read_data_set_ready(&mut self) -> serialport::Result<bool> { Ok(true) }
+        fn read_ring_indicator(&mut self) -> serialport::Result<bool> { Ok(false) }
+        fn read_carrier_detect(&mut self) -> serialport::Result<bool> { Ok(true) }
+        fn bytes_to_read(&self) -> serialport::Result<u32> { Ok(0) }
+        fn bytes_to_write(&self) -> serialport::Result<u32> { Ok(0) }
+        fn clear(&self, _: serialport::ClearBuffer) -> serialport::Result<()> { Ok(()) }
+        fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+            Err(serialport::Error::new(serialport::ErrorKind::Unknown, "scripted port cannot be cloned"))
+        }
+        fn set_break(&self) -> serialport::Result<()> { Ok(()) }
+        fn clear_break(&self) -> serialport::Result<()> { Ok(()) }
+    }
+
+    fn run_script(lines: &[&str]) -> Vec<SerialEvent> {
+        let _lock = crate::chaos::test_lock();
+        let script = lines.iter().map(|line| format!("{}\n", line)).collect::<String>();
+        let port = Box::new(ScriptedPort(std::io::Cursor::new(script.into_bytes())));
+        let mut events = Vec::new();
+        let mut last_seen_ns = 0;
+        let result = SerialMonitor::read_loop(
+            port,
+            &mut |event| {
+                events.push(event);
+                Ok(())
+            },
+            &mut last_seen_ns,
+            Outbox::new(Vec::new(), Duration::from_secs(5), 3),
+            &RelayAliases::default(),
+            None,
+            false,
+        );
+        assert!(result.is_err(), "read_loop only stops on a read error");
+        events
+    }
+
+    fn sensor_events(events: &[SerialEvent]) -> Vec<&SensorData> {
+        events.iter().filter_map(|event| match event {
+            SerialEvent::Sensor { data, .. } => Some(data),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn read_loop_emits_each_sample_once() {
+        let events = run_script(&[
+            "SENSOR_DATA|1700000000000000000|25.30|65.20",
+            "RELAY_STATUS|exhaust_fan:ON|pump:OFF",
+        ]);
+        let samples = sensor_events(&events);
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].exhaust_fan_status, None);
+        assert!(events.iter().any(|event| matches!(event, SerialEvent::RelayState(_))));
+    }
+
+    #[test]
+    fn read_loop_emits_aux_readings() {
+        // Trailer seq/CRC seperti SENSOR_DATA: frame rusak dibuang, frame tanpa trailer tetap diterima
+        let corrupted = signed("AUX_DATA|1700000000000000000|soil_moisture=55.0|slave=4", 2).replacen("55.0", "95.0", 1);
+        let events = run_script(&[
+            &signed("AUX_DATA|1700000000000000000|soil_moisture=41.2|slave=3", 1),
+            &corrupted,
+            "AUX_DATA|1700000000000000000|water_level=80.0",
+            "AUX_DATA|1700000000000000000|soil_moisture=oops|slave=3",
+        ]);
+        let aux: Vec<_> = events.iter().filter_map(|event| match event {
+            SerialEvent::Aux { slave, data } => Some((*slave, data.soil_moisture.or(data.water_level))),
+            _ => None,
+        }).collect();
+        assert_eq!(aux, vec![(Some(3), Some(41.2)), (None, Some(80.0))]);
+    }
+
+    #[test]
+    fn read_loop_attaches_relay_state_to_next_sample() {
+        let events = run_script(&[
+            "SENSOR_DATA|1700000000000000000|25.30|65.20",
+            "RELAY_STATUS|exhaust_fan:ON|pump:OFF",
+            "SENSOR_DATA|1700000005000000000|25.40|65.10",
+        ]);
+        let samples = sensor_events(&events);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[1].exhaust_fan_status, Some(true));
+        assert_eq!(samples[1].pump_status, Some(false));
+    }
+}
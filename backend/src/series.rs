@@ -0,0 +1,178 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::flux_csv::Row;
+
+// Query historis untuk dashboard (GET /api/series): semua field dalam satu query Flux,
+// hasil dikirim sebagai array ringkas (satu sumbu waktu + satu array nilai per field).
+
+// Batas titik per field agar satu request tidak menarik seluruh bucket
+pub const MAX_POINTS: f64 = 5000.0;
+const DEFAULT_FIELDS: [&str; 2] = ["temperature", "humidity"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeriesQuery {
+    pub fields: Vec<String>,
+    // Durasi Flux tanpa tanda, mis. "24h"; dipakai sebagai range(start: -<range>)
+    pub range: String,
+    pub every: String,
+    pub zone: Option<String>,
+}
+
+impl SeriesQuery {
+    // fields dipisah koma; range boleh dengan atau tanpa '-' di depan
+    pub fn parse(fields: Option<&str>, range: Option<&str>, every: Option<&str>, zone: Option<&str>) -> Result<Self> {
+        Self::parse_with_limit(fields, range, every, zone, MAX_POINTS)
+    }
+
+    // Sama seperti parse dengan batas titik per field sendiri (export memakai batas lebih besar)
+    pub fn parse_with_limit(fields: Option<&str>, range: Option<&str>, every: Option<&str>, zone: Option<&str>, max_points: f64) -> Result<Self> {
+        let fields: Vec<String> = match fields {
+            Some(list) => list.split(',').map(str::trim).filter(|f| !f.is_empty()).map(String::from).collect(),
+            None => DEFAULT_FIELDS.iter().map(|f| f.to_string()).collect(),
+        };
+        if fields.is_empty() {
+            bail!("no fields requested");
+        }
+        if let Some(field) = fields.iter().find(|f| !f.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')) {
+            bail!("invalid field name '{}'", field);
+        }
+        let range = range.unwrap_or("-1h").trim_start_matches('-').to_string();
+        let every = every.unwrap_or("1m").to_string();
+        let (Some(range_s), Some(every_s)) = (flux_seconds(&range), flux_seconds(&every)) else {
+            bail!("range and every must be Flux durations such as -24h and 1m");
+        };
+        if every_s <= 0.0 || range_s <= 0.0 {
+            bail!("range and every must be positive");
+        }
+        if range_s / every_s > max_points {
+            bail!("range/every yields {:.0} points per field, maximum is {:.0}", range_s / every_s, max_points);
+        }
+        Ok(Self { fields, range, every, zone: zone.map(String::from) })
+    }
+
+    // Satu query untuk semua field; rata-rata per window lintas device (group per _field).
+    // Timestamp dikirim sebagai epoch ms (kolom ts) supaya tidak perlu parse RFC3339.
+    pub fn flux(&self, bucket: &str, measurement: &str, tag_filter: &str) -> String {
+        let filter = self.fields.iter().map(|f| format!(r#"r["_field"] == "{f}""#)).collect::<Vec<_>>().join(" or ");
+        let zone = match &self.zone {
+            Some(zone) => format!("  |> filter(fn: (r) => r[\"zone\"] == \"{}\")\n", zone.replace('\\', "\\\\").replace('"', "\\\"")),
+            None => String::new(),
+        };
+        format!(
+            r#"from(bucket: "{bucket}")
+  |> range(start: -{range})
+  |> filter(fn: (r) => r["_measurement"] == "{measurement}")
+{zone}{tag_filter}  |> filter(fn: (r) => {filter})
+  |> group(columns: ["_field"])
+  |> aggregateWindow(every: {every}, fn: mean, createEmpty: false)
+  |> map(fn: (r) => ({{ r with ts: int(v: r._time) / 1000000 }}))
+  |> keep(columns: ["ts", "_field", "_value"])
+"#,
+            range = self.range,
+            every = self.every,
+        )
+    }
+}
+
+// Durasi Flux (mis. "90m", "1h30m", "7d") dalam detik; mo = 30 hari, y = 365 hari
+pub fn flux_seconds(value: &str) -> Option<f64> {
+    let mut total = 0.0;
+    let mut rest = value;
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        if digits == 0 {
+            return None;
+        }
+        let number: f64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let unit_len = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        let scale = match &rest[..unit_len] {
+            "ns" => 1e-9,
+            "us" | "µs" => 1e-6,
+            "ms" => 1e-3,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            "d" => 86_400.0,
+            "w" => 604_800.0,
+            "mo" => 2_592_000.0,
+            "y" => 31_536_000.0,
+            _ => return None,
+        };
+        total += number * scale;
+        rest = &rest[unit_len..];
+    }
+    Some(total)
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Series {
+    // Epoch ms, urut naik
+    pub time: Vec<i64>,
+    // Satu nilai per timestamp; null jika field tidak punya data di window itu
+    pub values: BTreeMap<String, Vec<Option<f64>>>,
+}
+
+// Kumpulkan baris CSV (kolom ts, _field, _value) menjadi Series; dipakai langsung dari stream body
+#[derive(Debug, Default)]
+pub struct SeriesBuilder {
+    fields: Vec<String>,
+    // Epoch ms -> nilai per field (urutan `fields`)
+    rows: BTreeMap<i64, Vec<Option<f64>>>,
+}
+
+impl SeriesBuilder {
+    pub fn new(fields: &[String]) -> Self {
+        Self { fields: fields.to_vec(), rows: BTreeMap::new() }
+    }
+
+    pub fn push(&mut self, row: &Row) {
+        let (Some(ts), Some(field), Some(value)) = (row.get("ts"), row.get("_field"), row.number("_value")) else { return };
+        let (Ok(ts), Some(index)) = (ts.parse::<i64>(), self.fields.iter().position(|f| f == field)) else { return };
+        let fields = self.fields.len();
+        self.rows.entry(ts).or_insert_with(|| vec![None; fields])[index] = Some((value * 1000.0).round() / 1000.0);
+    }
+
+    pub fn finish(self) -> Series {
+        let time: Vec<i64> = self.rows.keys().copied().collect();
+        let values = self
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| (field.clone(), self.rows.values().map(|row| row[i]).collect()))
+            .collect();
+        Series { time, values }
+    }
+}
+
+// Gzip (RFC 1952) untuk respons besar; menulis ke Vec tidak pernah gagal
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).expect("write to Vec");
+    encoder.finish().expect("write to Vec")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gzip_round_trips() {
+        use std::io::Read;
+
+        let body = br#"{"fields":["temperature"],"points":[[1700000000000,25.5]]}"#.repeat(100);
+        let compressed = gzip(&body);
+        assert_eq!(&compressed[..2], &[0x1f, 0x8b]);
+        assert!(compressed.len() < body.len());
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice()).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, body);
+    }
+}
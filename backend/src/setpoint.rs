@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::zone::Zone;
+
+// Sumber setpoint suhu zone. Urutan di `sources` = prioritas: sumber pertama yang punya nilai dipakai.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SetpointSource {
+    // Override operator lewat PUT /api/setpoints/<zone> atau gRPC SetSetpoint
+    Api,
+    // Atribut shared ThingsBoard setpoint_temperature (setpoint_<zone>_temperature jika multi zone)
+    Thingsboard,
+    // Suhu hasil simulasi DWSIM di InfluxDB
+    Dwsim,
+    // Nilai tetap `value` di config
+    Static,
+    // `fallback` saat tidak ada sumber di `sources` yang punya nilai (mis. DWSIM berhenti menulis);
+    // bukan pilihan di `sources`
+    #[serde(skip_deserializing)]
+    Fallback,
+}
+
+impl SetpointSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SetpointSource::Api => "api",
+            SetpointSource::Thingsboard => "thingsboard",
+            SetpointSource::Dwsim => "dwsim",
+            SetpointSource::Static => "static",
+            SetpointSource::Fallback => "fallback",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SetpointConfig {
+    pub sources: Vec<SetpointSource>,
+    // Setpoint untuk sumber "static"
+    pub value: Option<f64>,
+    // Setpoint cadangan jika semua sumber kosong; None = band absolut temp_on/temp_off
+    pub fallback: Option<f64>,
+    // Alarm dwsim_missing_<zone> jika zone memakai DWSIM tapi datanya tidak ada selama ini
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub dwsim_missing_after: Duration,
+}
+
+impl Default for SetpointConfig {
+    fn default() -> Self {
+        Self {
+            sources: vec![SetpointSource::Api, SetpointSource::Dwsim],
+            value: None,
+            fallback: None,
+            dwsim_missing_after: Duration::from_secs(600),
+        }
+    }
+}
+
+impl SetpointConfig {
+    pub fn uses(&self, source: SetpointSource) -> bool {
+        self.sources.contains(&source)
+    }
+
+    // Nilai per sumber yang sedang tersedia -> (setpoint, sumber aktif)
+    pub fn resolve(&self, api: Option<f64>, thingsboard: Option<f64>, dwsim: Option<f64>) -> Option<(f64, SetpointSource)> {
+        self.sources.iter().find_map(|&source| {
+            let value = match source {
+                SetpointSource::Api => api,
+                SetpointSource::Thingsboard => thingsboard,
+                SetpointSource::Dwsim => dwsim,
+                SetpointSource::Static => self.value,
+                SetpointSource::Fallback => None,
+            };
+            value.map(|v| (v, source))
+        })
+        .or_else(|| self.fallback.map(|v| (v, SetpointSource::Fallback)))
+    }
+}
+
+pub fn attribute_key(zone: &str, multi: bool) -> String {
+    if multi { format!("setpoint_{zone}_temperature") } else { "setpoint_temperature".to_string() }
+}
+
+// Setpoint dari atribut shared (update, respons request {"shared": {..}}, atau {"deleted": [..]}).
+// Per zone yang memakai sumber thingsboard: Some(Some(v)) = nilai baru, Some(None) = dihapus/null.
+pub fn from_attributes(zones: &[Zone], attributes: &Value) -> Vec<(String, Option<f64>)> {
+    let multi = zones.len() > 1;
+    let values = attributes.get("shared").unwrap_or(attributes);
+    let deleted = attributes["deleted"].as_array();
+    zones
+        .iter()
+        .filter(|z| z.setpoint.as_ref().is_some_and(|s| s.uses(SetpointSource::Thingsboard)))
+        .filter_map(|z| {
+            let key = attribute_key(&z.name, multi);
+            let value = match values.get(&key) {
+                Some(Value::Null) => None,
+                Some(v) => Some(v.as_f64().filter(|v| v.is_finite())?),
+                None if deleted.is_some_and(|d| d.iter().any(|k| k.as_str() == Some(key.as_str()))) => None,
+                None => return None,
+            };
+            Some((z.name.clone(), value))
+        })
+        .collect()
+}
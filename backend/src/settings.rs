@@ -0,0 +1,332 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::vpd::VpdTarget;
+use crate::zone::Zone;
+
+// Parameter kontrol yang bisa diubah saat runtime (PUT /api/settings, atribut shared ThingsBoard
+// setting_*). Nilai awal dari config.toml; perubahan disimpan ke [settings] path dan menang atas
+// config.toml saat restart. Hapus file itu untuk kembali ke nilai config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SettingsConfig {
+    pub path: String,
+}
+
+impl Default for SettingsConfig {
+    fn default() -> Self {
+        Self { path: "settings.json".to_string() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    // Band fan absolut (tanpa setpoint DWSIM/manual); lebar band juga dipakai di sekitar setpoint
+    pub temp_on: f64,
+    pub temp_off: f64,
+    pub zones: BTreeMap<String, ZoneSettings>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ZoneSettings {
+    pub humidity_on_below: f64,
+    // Hanya untuk zone mode greenhouse ([zones.vpd])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vpd_setpoint: Option<f64>,
+    // Band fan khusus zone; kosong = temp_on/temp_off global
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temp_on: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temp_off: Option<f64>,
+}
+
+// Perubahan sebagian; field kosong tidak diubah
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SettingsPatch {
+    pub temp_on: Option<f64>,
+    pub temp_off: Option<f64>,
+    #[serde(default)]
+    pub zones: BTreeMap<String, ZonePatch>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ZonePatch {
+    pub humidity_on_below: Option<f64>,
+    pub vpd_setpoint: Option<f64>,
+    pub temp_on: Option<f64>,
+    pub temp_off: Option<f64>,
+}
+
+// Satu nilai yang berubah: (key, lama, baru), key seperti di telemetry (main.humidity_on_below)
+pub type Change = (String, f64, f64);
+
+impl Settings {
+    pub fn from_config(config: &Config) -> Self {
+        let zones = config
+            .zones()
+            .iter()
+            .map(|zone| {
+                let settings = ZoneSettings {
+                    humidity_on_below: zone.humidity_on_below,
+                    vpd_setpoint: zone.vpd.as_ref().map(|target| target.setpoint),
+                    temp_on: zone.temp_on,
+                    temp_off: zone.temp_off,
+                };
+                (zone.name.clone(), settings)
+            })
+            .collect();
+        Self { temp_on: config.thresholds.temp_on, temp_off: config.thresholds.temp_off, zones }
+    }
+
+    // Band fan zone (temp_on, temp_off): nilai zone jika ada, selain itu nilai global
+    pub fn temp_band(&self, zone: &str) -> (f64, f64) {
+        let zone = self.zones.get(zone);
+        (
+            zone.and_then(|z| z.temp_on).unwrap_or(self.temp_on),
+            zone.and_then(|z| z.temp_off).unwrap_or(self.temp_off),
+        )
+    }
+
+    pub fn band(&self, zone: &str) -> f64 {
+        let (on, off) = self.temp_band(zone);
+        on - off
+    }
+
+    pub fn humidity_on_below(&self, zone: &Zone) -> f64 {
+        self.zones.get(&zone.name).map_or(zone.humidity_on_below, |z| z.humidity_on_below)
+    }
+
+    // Target VPD zone dengan setpoint runtime; None jika zone bukan mode greenhouse
+    pub fn vpd_target(&self, zone: &Zone) -> Option<VpdTarget> {
+        let mut target = zone.vpd.clone()?;
+        if let Some(setpoint) = self.zones.get(&zone.name).and_then(|z| z.vpd_setpoint) {
+            target.setpoint = setpoint;
+        }
+        Some(target)
+    }
+
+    pub fn load(path: &str) -> Result<Option<Self>> {
+        if !Path::new(path).exists() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+        let settings = serde_json::from_str(&text).with_context(|| format!("Corrupt settings file {}", path))?;
+        Ok(Some(settings))
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let tmp = format!("{path}.tmp");
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path))?;
+        Ok(())
+    }
+
+    // Nilai tersimpan ditimpakan ke nilai config; zone yang sudah tidak ada diabaikan,
+    // zone baru memakai nilai config. Nilai tersimpan yang tidak valid lagi ditolak seluruhnya.
+    pub fn merge(&self, stored: &Settings) -> Result<Settings> {
+        let patch = SettingsPatch {
+            temp_on: Some(stored.temp_on),
+            temp_off: Some(stored.temp_off),
+            zones: stored
+                .zones
+                .iter()
+                .filter(|(name, _)| self.zones.contains_key(*name))
+                .map(|(name, z)| {
+                    let vpd = self.zones[name].vpd_setpoint.and(z.vpd_setpoint);
+                    let patch = ZonePatch {
+                        humidity_on_below: Some(z.humidity_on_below),
+                        vpd_setpoint: vpd,
+                        temp_on: z.temp_on,
+                        temp_off: z.temp_off,
+                    };
+                    (name.clone(), patch)
+                })
+                .collect(),
+        };
+        let mut merged = self.clone();
+        merged.apply(&patch)?;
+        Ok(merged)
+    }
+
+    // Validasi lalu terapkan; tidak ada yang berubah jika salah satu nilai ditolak
+    pub fn apply(&mut self, patch: &SettingsPatch) -> Result<Vec<Change>> {
+        let mut next = self.clone();
+        next.temp_on = patch.temp_on.unwrap_or(next.temp_on);
+        next.temp_off = patch.temp_off.unwrap_or(next.temp_off);
+        if !next.temp_on.is_finite() || !next.temp_off.is_finite() || next.temp_off >= next.temp_on {
+            bail!("temp_off ({}) must be below temp_on ({})", next.temp_off, next.temp_on);
+        }
+        for (name, zone_patch) in &patch.zones {
+            let zone = next.zones.get_mut(name).ok_or_else(|| anyhow!("unknown zone '{}'", name))?;
+            if let Some(humidity) = zone_patch.humidity_on_below {
+                if !(0.0..=100.0).contains(&humidity) {
+                    bail!("{}.humidity_on_below must be within 0..100 %RH", name);
+                }
+                zone.humidity_on_below = humidity;
+            }
+            if let Some(setpoint) = zone_patch.vpd_setpoint {
+                if zone.vpd_setpoint.is_none() {
+                    bail!("zone '{}' is not in VPD mode (no [zones.vpd])", name);
+                }
+                if !(setpoint > 0.0 && setpoint <= 5.0) {
+                    bail!("{}.vpd_setpoint must be within 0..5 kPa", name);
+                }
+                zone.vpd_setpoint = Some(setpoint);
+            }
+            zone.temp_on = zone_patch.temp_on.or(zone.temp_on);
+            zone.temp_off = zone_patch.temp_off.or(zone.temp_off);
+        }
+        for name in next.zones.keys() {
+            let (on, off) = next.temp_band(name);
+            if !on.is_finite() || !off.is_finite() || off >= on {
+                bail!("{}.temp_off ({}) must be below {}.temp_on ({})", name, off, name, on);
+            }
+        }
+
+        let changes = self.diff(&next);
+        *self = next;
+        Ok(changes)
+    }
+
+    fn diff(&self, next: &Settings) -> Vec<Change> {
+        let mut changes = Vec::new();
+        let mut compare = |key: String, old: f64, new: f64| {
+            if old != new {
+                changes.push((key, old, new));
+            }
+        };
+        compare("temp_on".to_string(), self.temp_on, next.temp_on);
+        compare("temp_off".to_string(), self.temp_off, next.temp_off);
+        for (name, zone) in &self.zones {
+            let new = &next.zones[name];
+            compare(format!("{name}.humidity_on_below"), zone.humidity_on_below, new.humidity_on_below);
+            if let (Some(old), Some(new)) = (zone.vpd_setpoint, new.vpd_setpoint) {
+                compare(format!("{name}.vpd_setpoint"), old, new);
+            }
+            let (old_band, new_band) = (self.temp_band(name), next.temp_band(name));
+            if new.temp_on.is_some() {
+                compare(format!("{name}.temp_on"), old_band.0, new_band.0);
+            }
+            if new.temp_off.is_some() {
+                compare(format!("{name}.temp_off"), old_band.1, new_band.1);
+            }
+        }
+        changes
+    }
+
+    // Nilai aktif untuk payload telemetry: setting_temp_on, setting_temp_off, dan per zone
+    // setting_humidity_on_below / setting_vpd_setpoint / setting_temp_on|off zone (prefix <zone>_
+    // jika lebih dari satu zone)
+    pub fn telemetry(&self) -> Map<String, Value> {
+        let mut values = Map::new();
+        values.insert("setting_temp_on".into(), json!(self.temp_on));
+        values.insert("setting_temp_off".into(), json!(self.temp_off));
+        let multi = self.zones.len() > 1;
+        for (name, zone) in &self.zones {
+            let prefix = if multi { format!("{name}_") } else { String::new() };
+            values.insert(format!("setting_{prefix}humidity_on_below"), json!(zone.humidity_on_below));
+            if let Some(setpoint) = zone.vpd_setpoint {
+                values.insert(format!("setting_{prefix}vpd_setpoint"), json!(setpoint));
+            }
+            for (key, value) in [("temp_on", zone.temp_on), ("temp_off", zone.temp_off)] {
+                if let Some(value) = value {
+                    values.insert(format!("setting_{prefix}{key}"), json!(value));
+                }
+            }
+        }
+        values
+    }
+}
+
+const ZONE_KEYS: [&str; 4] = ["humidity_on_below", "vpd_setpoint", "temp_on", "temp_off"];
+
+// Atribut shared ThingsBoard dengan nama yang sama seperti di telemetry, mis.
+// {"setting_temp_on": 31.0, "setting_humidity_on_below": 55} (satu zone) atau
+// {"setting_nursery_humidity_on_below": 70, "setting_nursery_temp_on": 29} (per zone). Tanpa prefix
+// zone berlaku untuk semua zone, kecuali temp_on/temp_off yang tanpa prefix mengubah band global.
+// None jika tidak ada atribut setting_*.
+pub fn patch_from_attributes(settings: &Settings, attributes: &Value) -> Option<SettingsPatch> {
+    let map = attributes.as_object()?;
+    let mut patch = SettingsPatch::default();
+    let mut found = false;
+    for (key, value) in map {
+        let Some(rest) = key.strip_prefix("setting_") else { continue };
+        let Some(number) = value.as_f64() else { continue };
+        found = true;
+        match rest {
+            "temp_on" => patch.temp_on = Some(number),
+            "temp_off" => patch.temp_off = Some(number),
+            _ => {
+                let Some(field) = ZONE_KEYS.into_iter().find(|field| rest.ends_with(field)) else {
+                    log::warn!("Unknown setting attribute '{}'", key);
+                    continue;
+                };
+                let zone = rest[..rest.len() - field.len()].trim_end_matches('_');
+                // Tanpa prefix: semua zone (vpd_setpoint hanya zone mode greenhouse)
+                let zones: Vec<String> = if zone.is_empty() {
+                    settings
+                        .zones
+                        .iter()
+                        .filter(|(_, z)| field == "humidity_on_below" || z.vpd_setpoint.is_some())
+                        .map(|(name, _)| name.clone())
+                        .collect()
+                } else {
+                    vec![zone.to_string()]
+                };
+                for zone in zones {
+                    let entry = patch.zones.entry(zone).or_default();
+                    match field {
+                        "humidity_on_below" => entry.humidity_on_below = Some(number),
+                        "vpd_setpoint" => entry.vpd_setpoint = Some(number),
+                        "temp_on" => entry.temp_on = Some(number),
+                        _ => entry.temp_off = Some(number),
+                    }
+                }
+            }
+        }
+    }
+    found.then_some(patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> Settings {
+        let zone = |humidity_on_below, temp_on| ZoneSettings { humidity_on_below, vpd_setpoint: None, temp_on, temp_off: None };
+        Settings { temp_on: 30.0, temp_off: 28.0, zones: BTreeMap::from([("a".to_string(), zone(60.0, None)), ("b".to_string(), zone(55.0, Some(33.0)))]) }
+    }
+
+    #[test]
+    fn zone_band_overrides_global() {
+        let settings = settings();
+        assert_eq!(settings.temp_band("a"), (30.0, 28.0));
+        assert_eq!(settings.temp_band("b"), (33.0, 28.0));
+        assert_eq!(settings.band("b"), 5.0);
+        assert_eq!(settings.temp_band("unknown"), (30.0, 28.0));
+    }
+
+    #[test]
+    fn zone_band_is_validated_against_global() {
+        let mut settings = settings();
+        let patch = SettingsPatch { zones: BTreeMap::from([("a".to_string(), ZonePatch { temp_off: Some(31.0), ..Default::default() })]), ..Default::default() };
+        assert!(settings.apply(&patch).is_err());
+        assert_eq!(settings, self::settings());
+
+        let patch = SettingsPatch { zones: BTreeMap::from([("a".to_string(), ZonePatch { temp_on: Some(29.0), ..Default::default() })]), ..Default::default() };
+        assert_eq!(settings.apply(&patch).unwrap(), vec![("a.temp_on".to_string(), 30.0, 29.0)]);
+        assert_eq!(settings.temp_band("a"), (29.0, 28.0));
+    }
+
+    #[test]
+    fn zone_band_from_attributes() {
+        let patch = patch_from_attributes(&settings(), &json!({ "setting_b_temp_off": 27.5, "setting_temp_on": 31.0 })).unwrap();
+        assert_eq!(patch.temp_on, Some(31.0));
+        assert_eq!(patch.zones["b"].temp_off, Some(27.5));
+    }
+}
@@ -0,0 +1,284 @@
+use anyhow::Result;
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+use crate::events::Event;
+use crate::ingest::Sample;
+use crate::pipeline::SampleSink;
+
+// Tujuan fan-out stream telemetry dan event. Setiap kind butuh cargo feature
+// dengan nama yang sama ("kafka" atau "nats").
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum SinkConfig {
+    Kafka {
+        brokers: String,
+        #[serde(default = "default_telemetry_stream")]
+        telemetry_topic: String,
+        #[serde(default = "default_events_stream")]
+        events_topic: String,
+    },
+    Nats {
+        url: String,
+        #[serde(default = "default_telemetry_stream")]
+        telemetry_subject: String,
+        #[serde(default = "default_events_stream")]
+        events_subject: String,
+    },
+}
+
+fn default_telemetry_stream() -> String {
+    "dcs.telemetry".to_string()
+}
+
+fn default_events_stream() -> String {
+    "dcs.events".to_string()
+}
+
+// Jeda sebelum menyambung ulang sink yang berhenti karena error; berlipat dua sampai MAX
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy)]
+enum Stream {
+    Telemetry,
+    Events,
+}
+
+// Tanpa feature broker pesan hanya diantrekan lalu dibuang
+#[derive(Debug)]
+#[cfg_attr(not(any(feature = "kafka", feature = "nats")), allow(dead_code))]
+struct Message {
+    stream: Stream,
+    key: String,
+    payload: String,
+}
+
+// Handle murah untuk dikloning; pengiriman tidak pernah memblokir loop pemanggil
+#[derive(Clone)]
+pub struct SinkSender {
+    tx: mpsc::Sender<Message>,
+    // Task sink sudah berhenti permanen; dilaporkan sekali saja
+    stopped: Arc<AtomicBool>,
+    // Pesan terbuang sejak antrean terakhir kali menerima; warning hanya di awal gangguan
+    dropped: Arc<AtomicU64>,
+}
+
+impl SinkSender {
+    fn send(&self, stream: Stream, key: String, payload: Value) {
+        let message = Message { stream, key, payload: payload.to_string() };
+        match self.tx.try_send(message) {
+            Ok(()) => {
+                let dropped = self.dropped.swap(0, Ordering::Relaxed);
+                if dropped > 0 {
+                    info!("Sink queue accepting messages again, {} message(s) were dropped", dropped);
+                }
+            }
+            Err(TrySendError::Full(_)) => {
+                if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                    warn!("Sink queue full, dropping messages until it drains ({:?} message dropped)", stream);
+                }
+            }
+            Err(TrySendError::Closed(_)) => {
+                if !self.stopped.swap(true, Ordering::Relaxed) {
+                    error!("Output sink is not running, {:?} messages are dropped", stream);
+                }
+            }
+        }
+    }
+
+    pub fn send_sample(&self, sample: &Sample) {
+        self.send(Stream::Telemetry, sample.device_id.clone(), sample_json(sample));
+    }
+
+    pub fn send_event(&self, event: &Event) {
+        self.send(Stream::Events, event.subject.clone(), json!(event));
+    }
+}
+
+impl SampleSink for SinkSender {
+    fn publish(&self, sample: &Sample) {
+        self.send_sample(sample);
+    }
+}
+
+pub fn sample_json(sample: &Sample) -> Value {
+    let data = &sample.data;
+    let mut body = json!({
+        "device_id": sample.device_id,
+        "zone": sample.zone,
+        "ts_ns": sample.timestamp_ns,
+        "device_timestamp": data.timestamp,
+        "temperature": data.temperature,
+        "humidity": data.humidity,
+        "exhaust_fan_status": data.exhaust_fan_status,
+        "pump_status": data.pump_status,
+        "fan_duty": data.fan_duty,
+        "time_source": sample.time_source.as_str(),
+        "quality": sample.quality.as_str(),
+        "outlier": sample.outlier,
+    });
+    for (name, value) in &sample.extra {
+        body[name.as_str()] = json!(value);
+    }
+    body
+}
+
+// Kembalikan sender dan jalankan task publisher di background. Jika koneksi sink putus,
+// task disambung ulang dengan jeda bertambah; pesan menunggu di antrean selama itu.
+pub fn spawn(config: SinkConfig) -> SinkSender {
+    let (tx, mut rx) = mpsc::channel(1024);
+    tokio::spawn(async move {
+        if !config.built_in() {
+            if let Err(e) = run(config, &mut rx).await {
+                error!("Output sink stopped: {:#}", e);
+            }
+            return;
+        }
+        let mut delay = RESTART_DELAY;
+        loop {
+            let started = Instant::now();
+            match run(config.clone(), &mut rx).await {
+                // Semua sender sudah di-drop
+                Ok(()) => return,
+                Err(e) => {
+                    // Sink yang sempat berjalan lama mulai lagi dari jeda terpendek
+                    if started.elapsed() >= MAX_RESTART_DELAY {
+                        delay = RESTART_DELAY;
+                    }
+                    error!("Output sink failed: {:#}, restarting in {}s", e, delay.as_secs());
+                    tokio::time::sleep(delay).await;
+                    delay = next_delay(delay);
+                }
+            }
+        }
+    });
+    SinkSender { tx, stopped: Arc::new(AtomicBool::new(false)), dropped: Arc::new(AtomicU64::new(0)) }
+}
+
+fn next_delay(delay: Duration) -> Duration {
+    (delay * 2).min(MAX_RESTART_DELAY)
+}
+
+impl SinkConfig {
+    // false = cargo feature untuk kind ini tidak ikut dibangun, menyambung ulang tidak ada gunanya
+    fn built_in(&self) -> bool {
+        match self {
+            SinkConfig::Kafka { .. } => cfg!(feature = "kafka"),
+            SinkConfig::Nats { .. } => cfg!(feature = "nats"),
+        }
+    }
+}
+
+async fn run(config: SinkConfig, rx: &mut mpsc::Receiver<Message>) -> Result<()> {
+    match config {
+        SinkConfig::Kafka { brokers, telemetry_topic, events_topic } => {
+            run_kafka(&brokers, [telemetry_topic, events_topic], rx).await
+        }
+        SinkConfig::Nats { url, telemetry_subject, events_subject } => {
+            run_nats(&url, [telemetry_subject, events_subject], rx).await
+        }
+    }
+}
+
+#[cfg(any(feature = "kafka", feature = "nats"))]
+fn target(names: &[String; 2], stream: Stream) -> &str {
+    match stream {
+        Stream::Telemetry => &names[0],
+        Stream::Events => &names[1],
+    }
+}
+
+#[cfg(feature = "kafka")]
+async fn run_kafka(brokers: &str, topics: [String; 2], rx: &mut mpsc::Receiver<Message>) -> Result<()> {
+    use rdkafka::producer::{FutureProducer, FutureRecord};
+
+    let producer: FutureProducer = rdkafka::ClientConfig::new()
+        .set("bootstrap.servers", brokers)
+        .set("message.timeout.ms", "10000")
+        .create()?;
+    info!("📤 Kafka sink connected to {} (topics {}, {})", brokers, topics[0], topics[1]);
+
+    while let Some(message) = rx.recv().await {
+        let topic = target(&topics, message.stream);
+        let record = FutureRecord::to(topic).key(&message.key).payload(&message.payload);
+        if let Err((e, _)) = producer.send(record, Duration::from_secs(5)).await {
+            error!("Kafka publish to {} failed: {}", topic, e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "kafka"))]
+async fn run_kafka(_: &str, _: [String; 2], _: &mut mpsc::Receiver<Message>) -> Result<()> {
+    Err(anyhow::anyhow!("Kafka sink configured but the backend was built without the \"kafka\" feature"))
+}
+
+#[cfg(feature = "nats")]
+async fn run_nats(url: &str, subjects: [String; 2], rx: &mut mpsc::Receiver<Message>) -> Result<()> {
+    let client = async_nats::connect(url).await?;
+    info!("📤 NATS sink connected to {} (subjects {}, {})", url, subjects[0], subjects[1]);
+
+    while let Some(message) = rx.recv().await {
+        let subject = target(&subjects, message.stream).to_string();
+        // NATS tidak punya message key; dikirim sebagai header "key"
+        let mut headers = async_nats::HeaderMap::new();
+        headers.insert("key", message.key.as_str());
+        if let Err(e) = client.publish_with_headers(subject.clone(), headers, message.payload.into()).await {
+            error!("NATS publish to {} failed: {}", subject, e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "nats"))]
+async fn run_nats(_: &str, _: [String; 2], _: &mut mpsc::Receiver<Message>) -> Result<()> {
+    Err(anyhow::anyhow!("NATS sink configured but the backend was built without the \"nats\" feature"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sender(capacity: usize) -> (SinkSender, mpsc::Receiver<Message>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        (SinkSender { tx, stopped: Arc::new(AtomicBool::new(false)), dropped: Arc::new(AtomicU64::new(0)) }, rx)
+    }
+
+    #[test]
+    fn full_queue_is_not_reported_as_stopped() {
+        let (sender, mut rx) = sender(1);
+        sender.send(Stream::Events, "a".into(), json!(1));
+        sender.send(Stream::Events, "b".into(), json!(2));
+        sender.send(Stream::Events, "c".into(), json!(3));
+        assert!(!sender.stopped.load(Ordering::Relaxed));
+        assert_eq!(sender.dropped.load(Ordering::Relaxed), 2);
+        assert_eq!(rx.try_recv().unwrap().key, "a");
+        assert!(rx.try_recv().is_err());
+
+        // Antrean kosong lagi: hitungan drop direset
+        sender.send(Stream::Events, "d".into(), json!(4));
+        assert_eq!(sender.dropped.load(Ordering::Relaxed), 0);
+        assert_eq!(rx.try_recv().unwrap().key, "d");
+    }
+
+    #[test]
+    fn closed_queue_marks_sink_stopped() {
+        let (sender, rx) = sender(1);
+        drop(rx);
+        sender.send(Stream::Telemetry, "a".into(), json!(1));
+        assert!(sender.stopped.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn restart_delay_doubles_up_to_max() {
+        let delays: Vec<u64> = std::iter::successors(Some(RESTART_DELAY), |d| Some(next_delay(*d))).take(8).map(|d| d.as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60]);
+    }
+}
@@ -0,0 +1,573 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use dcs_model::{ActuatorCommand, RelayMode};
+use tokio::sync::broadcast;
+
+use crate::alarms::{Alarm, AlarmChange, AlarmContext, AlarmManager, Severity};
+use crate::audit::{AuditEntry, AuditLog};
+use crate::auth::AuthConfig;
+use crate::autotune::AutotuneStatus;
+use crate::calibration::CalibrationTable;
+use crate::config::{Config, TagsConfig};
+use crate::control::{Actuator, ActuatorId, ActuatorMode};
+use crate::drift::Drift;
+use crate::events::{Event, EventLog, EventSource};
+use crate::export::ExportConfig;
+use crate::ota::OtaShared;
+use crate::publish::SinkHealth;
+use crate::reconcile::ReportedRelay;
+use crate::runtime::RuntimeCounters;
+use crate::serial::DeviceIdentity;
+use crate::setpoint::SetpointSource;
+use crate::settings::{Change, Settings, SettingsPatch};
+use crate::zone::Zone;
+
+// State yang dibagi antara loop utama dan REST API
+pub struct AppState {
+    pub device_id: String,
+    pub auth: AuthConfig,
+    pub runtime: Mutex<RuntimeCounters>,
+    pub alarms: Mutex<AlarmManager>,
+    // Kesehatan sink (antrian + circuit breaker), diisi publish::spawn
+    pub sinks: Mutex<BTreeMap<String, Arc<Mutex<SinkHealth>>>>,
+    // Durasi default maintenance mode jika tidak disebutkan
+    pub maintenance_duration: std::time::Duration,
+    // Batas atas durasi shelve/maintenance ([maintenance] max_duration)
+    pub maintenance_max: std::time::Duration,
+    pub calibration: Mutex<CalibrationTable>,
+    pub zones: Vec<Zone>,
+    // Waktu sampel sensor terakhir per device (untuk deteksi data stale)
+    pub last_sample: Mutex<HashMap<String, Instant>>,
+    // Timestamp terkoreksi (ns) sampel terbaru per device, untuk field "ts" ThingsBoard
+    pub last_sample_ts: Mutex<HashMap<String, u64>>,
+    pub events: EventLog,
+    // Jejak append-only semua aksi kontrol dan perubahan konfigurasi
+    pub audit: AuditLog,
+    // Mode manual per aktuator dan override setpoint suhu (REST/gRPC)
+    pub modes: Mutex<HashMap<ActuatorId, ActuatorMode>>,
+    pub setpoint_override: Mutex<HashMap<String, f64>>,
+    // Setpoint per zone dari atribut shared ThingsBoard (sumber "thingsboard")
+    pub tb_setpoints: Mutex<HashMap<String, f64>>,
+    // Setpoint yang dipakai siklus terakhir per zone beserta sumbernya
+    pub active_setpoints: Mutex<HashMap<String, (f64, SetpointSource)>>,
+    pub telemetry: Mutex<Option<Telemetry>>,
+    // Status autotune relay per zone (diminta lewat REST, dijalankan loop utama)
+    pub autotune: Mutex<HashMap<String, AutotuneStatus>>,
+    // Untuk /healthz dan /status
+    pub started: Instant,
+    pub serial_connected: AtomicBool,
+    // Identitas ESP32 dari handshake HELLO sejak port serial terakhir dibuka; None = belum/tidak menjawab
+    pub device_identity: Mutex<Option<DeviceIdentity>>,
+    // Status/mode relay terakhir dari RELAY_STATUS ESP32 utama; dikosongkan saat port serial putus
+    pub reported_relays: Mutex<HashMap<Actuator, ReportedRelay>>,
+    // Status koneksi MQTT per tujuan ThingsBoard (nama sink)
+    pub mqtt_links: Mutex<BTreeMap<String, Arc<AtomicBool>>>,
+    pub last_cycle: Mutex<Option<Instant>>,
+    // Reset ESP32 yang tidak disengaja (panic, watchdog, brownout) per device sejak backend start
+    pub device_resets: Mutex<HashMap<String, u64>>,
+    // Baris SET threshold terbaru untuk ESP32 di link serial (dikirim saat berubah dan setiap reconnect)
+    // Mode shadow: device_settings tetap kosong, threshold ESP32 tidak disentuh
+    shadow: bool,
+    pub device_settings: Arc<Mutex<String>>,
+    // Satu baris RELAY berisi perintah terakhir setiap aktuator yang diputuskan backend untuk ESP32
+    // di link serial (lihat command_relay); kosong = belum ada
+    pub device_relays: Arc<Mutex<String>>,
+    relay_commands: Mutex<BTreeMap<Actuator, RelayMode>>,
+    // Batas tunggu ACK perintah manual: timeout x (retry + 1) di monitor serial, ditambah jeda
+    command_deadline: Duration,
+    pub tags: TagsConfig,
+    // Parameter kontrol aktif (lihat settings.rs), disimpan ke settings_path setiap berubah
+    pub settings: Mutex<Settings>,
+    settings_path: String,
+    // Versi firmware per device dari HELLO/DIAG (fw=..), untuk tag InfluxDB
+    pub firmware: Mutex<HashMap<String, String>>,
+    // Default export Excel (GET /api/export.xlsx), fields sudah diisi dari [last_data]
+    pub export: ExportConfig,
+    // Model drift humidity per sensor dari bacaan referensi
+    pub drift: Drift,
+    // Relay OTA ThingsBoard (target fw_*, status ESP32, image yang disajikan)
+    pub ota: OtaShared,
+    telemetry_tx: broadcast::Sender<Telemetry>,
+    alarm_tx: broadcast::Sender<AlarmChange>,
+    command_tx: broadcast::Sender<CommandReply>,
+}
+
+// Balasan ESP32 untuk satu perintah ber-id (baris tanpa id); Err = NAK atau tanpa ACK setelah retry
+#[derive(Debug, Clone)]
+pub struct CommandReply {
+    pub command: String,
+    pub result: Result<(), String>,
+}
+
+// Snapshot telemetry satu siklus loop utama (yang dipublish ke ThingsBoard)
+#[derive(Debug, Clone, Default)]
+pub struct Telemetry {
+    pub timestamp_ms: u64,
+    pub values: BTreeMap<String, f64>,
+    pub data_quality: String,
+    pub active_alarms: Vec<String>,
+}
+
+impl AppState {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            device_id: config.device_id.clone(),
+            auth: config.auth.clone(),
+            runtime: Mutex::new(RuntimeCounters::default()),
+            alarms: Mutex::new(AlarmManager::new(&config.maintenance)),
+            maintenance_duration: config.maintenance.default_duration,
+            maintenance_max: config.maintenance.max_duration,
+            sinks: Mutex::new(BTreeMap::new()),
+            calibration: Mutex::new(config.calibration.clone()),
+            zones: config.zones(),
+            last_sample: Mutex::new(HashMap::new()),
+            last_sample_ts: Mutex::new(HashMap::new()),
+            events: EventLog::new(),
+            audit: AuditLog::new(&config.audit),
+            modes: Mutex::new(HashMap::new()),
+            setpoint_override: Mutex::new(HashMap::new()),
+            tb_setpoints: Mutex::new(HashMap::new()),
+            active_setpoints: Mutex::new(HashMap::new()),
+            telemetry: Mutex::new(None),
+            autotune: Mutex::new(HashMap::new()),
+            started: Instant::now(),
+            serial_connected: AtomicBool::new(false),
+            device_identity: Mutex::new(None),
+            reported_relays: Mutex::new(HashMap::new()),
+            mqtt_links: Mutex::new(BTreeMap::new()),
+            last_cycle: Mutex::new(None),
+            device_resets: Mutex::new(HashMap::new()),
+            shadow: config.shadow,
+            device_settings: Arc::new(Mutex::new(if config.shadow {
+                String::new()
+            } else {
+                let (fan, pump) = config.device_bands(&Settings::from_config(config));
+                config.device_set_command(fan, pump)
+            })),
+            device_relays: Arc::new(Mutex::new(String::new())),
+            relay_commands: Mutex::new(BTreeMap::new()),
+            command_deadline: config.connections.command_timeout * (config.connections.command_retries + 1) + Duration::from_secs(1),
+            tags: config.tags.clone(),
+            settings: Mutex::new(Settings::from_config(config)),
+            settings_path: config.settings.path.clone(),
+            firmware: Mutex::new(HashMap::new()),
+            export: config.export.resolved(&config.last_data),
+            drift: Drift::new(&config.drift),
+            ota: OtaShared::new(&config.ota),
+            telemetry_tx: broadcast::channel(64).0,
+            alarm_tx: broadcast::channel(64).0,
+            command_tx: broadcast::channel(16).0,
+        }
+    }
+
+    pub fn mode(&self, actuator: &ActuatorId) -> ActuatorMode {
+        self.modes.lock().unwrap().get(actuator).copied().unwrap_or_default()
+    }
+
+    // Ganti mode aktuator; mengembalikan mode sebelumnya
+    pub fn set_mode(&self, actuator: &ActuatorId, mode: ActuatorMode) -> ActuatorMode {
+        self.modes.lock().unwrap().insert(actuator.clone(), mode).unwrap_or_default()
+    }
+
+    // Aktuator jenis ini di zone tertentu, atau di semua zone yang memilikinya jika zone kosong
+    pub fn actuator_ids(&self, kind: Actuator, zone: Option<&str>) -> Vec<ActuatorId> {
+        self.zones
+            .iter()
+            .filter(|z| z.has(kind) && zone.is_none_or(|name| z.name == name))
+            .map(|z| z.actuator(kind))
+            .collect()
+    }
+
+    pub fn setpoint_override(&self, zone: &str) -> Option<f64> {
+        self.setpoint_override.lock().unwrap().get(zone).copied()
+    }
+
+    // Ganti override setpoint satu zone (None = kembali ke DWSIM); mengembalikan nilai sebelumnya
+    pub fn set_setpoint_override(&self, zone: &str, value: Option<f64>) -> Option<f64> {
+        let mut overrides = self.setpoint_override.lock().unwrap();
+        match value {
+            Some(v) => overrides.insert(zone.to_string(), v),
+            None => overrides.remove(zone),
+        }
+    }
+
+    pub fn tb_setpoint(&self, zone: &str) -> Option<f64> {
+        self.tb_setpoints.lock().unwrap().get(zone).copied()
+    }
+
+    pub fn set_tb_setpoint(&self, zone: &str, value: Option<f64>) -> Option<f64> {
+        let mut setpoints = self.tb_setpoints.lock().unwrap();
+        match value {
+            Some(v) => setpoints.insert(zone.to_string(), v),
+            None => setpoints.remove(zone),
+        }
+    }
+
+    // Sampel backlog yang datang terlambat tidak memundurkan timestamp
+    pub fn note_sample_ts(&self, device_id: &str, timestamp_ns: u64) {
+        let mut last = self.last_sample_ts.lock().unwrap();
+        let entry = last.entry(device_id.to_string()).or_default();
+        *entry = (*entry).max(timestamp_ns);
+    }
+
+    // Mengembalikan jumlah reset device ini setelah dicatat
+    pub fn note_device_reset(&self, device_id: &str) -> u64 {
+        let mut resets = self.device_resets.lock().unwrap();
+        let count = resets.entry(device_id.to_string()).or_default();
+        *count += 1;
+        *count
+    }
+
+    // Mengembalikan versi sebelumnya jika berubah (mis. setelah OTA)
+    pub fn note_firmware(&self, device_id: &str, version: &str) -> Option<String> {
+        let mut firmware = self.firmware.lock().unwrap();
+        match firmware.insert(device_id.to_string(), version.to_string()) {
+            Some(old) if old != version => Some(old),
+            _ => None,
+        }
+    }
+
+    // Tag device untuk line protocol: location (config) dan firmware (DIAG), yang belum diketahui dilewati
+    pub fn device_tags(&self, device_id: &str) -> Vec<(&'static str, String)> {
+        let mut tags = Vec::new();
+        if let Some(location) = self.tags.location(device_id) {
+            tags.push(("location", location.to_string()));
+        }
+        if let Some(version) = self.firmware.lock().unwrap().get(device_id) {
+            tags.push(("firmware", version.clone()));
+        }
+        tags
+    }
+
+    // Lokasi zone = lokasi sensor utamanya
+    pub fn zone_location(&self, zone: &str) -> Option<&str> {
+        let device_id = self.zones.iter().find(|z| z.name == zone).map_or(self.device_id.as_str(), |z| z.device_id.as_str());
+        self.tags.location(device_id)
+    }
+
+    pub fn latest_sample_ts(&self) -> Option<u64> {
+        self.last_sample_ts.lock().unwrap().values().copied().max()
+    }
+
+    pub fn last_sample_age(&self, device_id: &str) -> Option<std::time::Duration> {
+        self.last_sample.lock().unwrap().get(device_id).map(|t| t.elapsed())
+    }
+
+    // Simpan snapshot terbaru dan kirim ke semua subscriber stream
+    pub fn publish_telemetry(&self, telemetry: Telemetry) {
+        *self.telemetry.lock().unwrap() = Some(telemetry.clone());
+        let _ = self.telemetry_tx.send(telemetry);
+    }
+
+    pub fn subscribe_telemetry(&self) -> broadcast::Receiver<Telemetry> {
+        self.telemetry_tx.subscribe()
+    }
+
+    // Alarm yang baru aktif/clear, untuk routing notifikasi
+    pub fn subscribe_alarms(&self) -> broadcast::Receiver<AlarmChange> {
+        self.alarm_tx.subscribe()
+    }
+
+    // Raise alarm dan catat event-nya; None jika alarm sudah aktif atau sedang ditekan
+    pub fn raise_alarm(&self, id: &str, severity: Severity, message: String) -> Option<Alarm> {
+        self.raise_alarm_with(id, severity, message, AlarmContext::default())
+    }
+
+    // Dengan zone/nilai/batas untuk template notifikasi (mis. email)
+    pub fn raise_alarm_with(&self, id: &str, severity: Severity, message: String, context: AlarmContext) -> Option<Alarm> {
+        let mut alarms = self.alarms.lock().unwrap();
+        if let Some(reason) = alarms.suppression(id, Instant::now()) {
+            if alarms.note_suppressed(id) {
+                drop(alarms);
+                self.audit.record(AuditEntry::new("system", "internal", "-", "alarm_suppressed", id, format!("{reason}: {message}")));
+            }
+            return None;
+        }
+        let alarm = alarms.raise_with(id, severity, message, context)?;
+        drop(alarms);
+        self.events.record(
+            Event::new("alarm", id, "active")
+                .old("clear")
+                .reason(format!("[{}] {}", severity.as_str(), alarm.message)),
+        );
+        let _ = self.alarm_tx.send(AlarmChange::Raised(alarm.clone()));
+        Some(alarm)
+    }
+
+    // Menit dari klien (REST/gRPC) -> durasi shelve/maintenance; Err jika 0, overflow atau melebihi batas
+    pub fn suppression_duration(&self, minutes: u64) -> Result<std::time::Duration, String> {
+        minutes
+            .checked_mul(60)
+            .map(std::time::Duration::from_secs)
+            .filter(|d| !d.is_zero() && *d <= self.maintenance_max)
+            .ok_or_else(|| format!("minutes must be between 1 and {}", self.maintenance_max.as_secs() / 60))
+    }
+
+    // Akhir shelve/maintenance, dihitung sebelum mengunci alarms supaya overflow tidak meracuni mutex
+    fn suppression_until(&self, now: Instant, duration: std::time::Duration) -> Instant {
+        now.checked_add(duration.min(self.maintenance_max)).unwrap_or(now)
+    }
+
+    // Shelve alarm selama `duration`; alarm yang sedang aktif ikut disembunyikan
+    pub fn shelve_alarm(&self, id: &str, duration: std::time::Duration) {
+        let until = self.suppression_until(Instant::now(), duration);
+        let hidden = self.alarms.lock().unwrap().shelve(id, until);
+        let mut event = Event::new("alarm", id, "shelved").reason(format!("shelved for {}s", duration.as_secs()));
+        if hidden.is_some() {
+            event = event.old("active");
+        }
+        self.events.record(event.source(EventSource::Manual));
+    }
+
+    pub fn unshelve_alarm(&self, id: &str) -> bool {
+        let removed = self.alarms.lock().unwrap().unshelve(id);
+        if removed {
+            self.events.record(Event::new("alarm", id, "unshelved").old("shelved").source(EventSource::Manual));
+        }
+        removed
+    }
+
+    // None mematikan maintenance mode; alarm aktif yang termasuk kategori tekan ikut disembunyikan
+    pub fn set_maintenance(&self, duration: Option<std::time::Duration>, source: EventSource) {
+        let now = Instant::now();
+        let until = duration.map(|d| self.suppression_until(now, d));
+        let mut alarms = self.alarms.lock().unwrap();
+        let previous = alarms.set_maintenance(until);
+        let hidden: Vec<String> = match duration {
+            Some(_) => alarms
+                .active()
+                .filter(|a| alarms.suppression(&a.id, now).is_some())
+                .map(|a| a.id.clone())
+                .collect(),
+            None => Vec::new(),
+        };
+        for id in &hidden {
+            alarms.clear(id);
+        }
+        drop(alarms);
+        let label = |on: bool| if on { "on" } else { "off" };
+        let was_on = previous.is_some_and(|until| until > now);
+        self.events.record(
+            Event::new("config", "maintenance_mode", label(duration.is_some()))
+                .old(label(was_on))
+                .reason(match duration {
+                    Some(d) => format!("for {}s, {} active alarm(s) suppressed", d.as_secs(), hidden.len()),
+                    None => "ended".to_string(),
+                })
+                .source(source),
+        );
+    }
+
+    // Lepas shelve/maintenance yang kedaluwarsa dan catat di audit
+    pub fn expire_suppressions(&self) {
+        let released = self.alarms.lock().unwrap().expire(Instant::now());
+        for id in released {
+            log::info!("🔔 Suppression expired: {}", id);
+            let (event, action) = if id == "maintenance" {
+                (Event::new("config", "maintenance_mode", "off").old("on"), "maintenance_mode")
+            } else {
+                (Event::new("alarm", id.as_str(), "unshelved").old("shelved"), "alarm_unshelve")
+            };
+            self.events.record(event.reason("expired"));
+            self.audit.record(AuditEntry::new("system", "internal", "-", action, id, "expired"));
+        }
+    }
+
+    // Loop utama dianggap hidup jika siklus terakhir belum lewat `max_age`
+    // (atau belum pernah jalan tapi proses baru mulai)
+    pub fn is_live(&self, max_age: std::time::Duration) -> bool {
+        match *self.last_cycle.lock().unwrap() {
+            Some(at) => at.elapsed() <= max_age,
+            None => self.started.elapsed() <= max_age,
+        }
+    }
+
+    pub fn serial_connected(&self) -> bool {
+        self.serial_connected.load(Ordering::Relaxed)
+    }
+
+    // Ganti baris SET untuk ESP32 (dikirim serial saat berubah); diabaikan di mode shadow
+    pub fn queue_device_settings(&self, command: String) {
+        if !self.shadow {
+            *self.device_settings.lock().unwrap() = command;
+        }
+    }
+
+    // Catat perintah satu relay lalu susun ulang baris RELAY dari semua aktuator, supaya perintah
+    // aktuator lain tidak tertimpa oleh frame yang hanya berisi satu relay; diabaikan di mode shadow.
+    // Mengembalikan perintah relay ini sebelumnya.
+    pub fn command_relay(&self, actuator: Actuator, mode: RelayMode) -> Option<RelayMode> {
+        self.replace_relay(actuator, Some(mode))
+    }
+
+    fn replace_relay(&self, actuator: Actuator, mode: Option<RelayMode>) -> Option<RelayMode> {
+        if self.shadow {
+            return None;
+        }
+        let mut commands = self.relay_commands.lock().unwrap();
+        let previous = match mode {
+            Some(mode) => commands.insert(actuator, mode),
+            None => commands.remove(&actuator),
+        };
+        let frame: Vec<ActuatorCommand> = commands.iter().map(|(actuator, mode)| ActuatorCommand::new(actuator.name(), *mode)).collect();
+        *self.device_relays.lock().unwrap() = if frame.is_empty() { String::new() } else { ActuatorCommand::to_frame(&frame) };
+        previous
+    }
+
+    // Dipanggil monitor serial untuk setiap ACK/NAK perintah ber-id
+    pub fn command_replied(&self, reply: CommandReply) {
+        let _ = self.command_tx.send(reply);
+    }
+
+    // Mode manual satu aktuator, jalur bersama REST dan gRPC. Relay zone ESP32 serial ikut diperintah
+    // lewat baris RELAY dan mode backend baru diganti (dan dicatat sebagai event) setelah ESP32
+    // membalas ACK; Err = perintah tidak sampai (perintah dikembalikan ke sebelumnya).
+    // Mengembalikan mode sebelumnya.
+    pub async fn set_actuator_mode(&self, actuator: &ActuatorId, mode: ActuatorMode, source: EventSource, reason: &str) -> Result<ActuatorMode, String> {
+        let on_serial = self.zones.iter().any(|z| z.name == actuator.zone && z.device_id == self.device_id);
+        if !self.shadow && on_serial {
+            self.command_device(actuator.kind, mode.into()).await?;
+        }
+        let old = self.set_mode(actuator, mode);
+        log::info!("🎛️  {} mode {} → {} ({})", actuator, old.as_str(), mode.as_str(), reason);
+        self.events.record(
+            Event::new("config", format!("mode/{}", actuator), mode.as_str())
+                .old(old.as_str())
+                .reason(reason)
+                .source(source),
+        );
+        Ok(old)
+    }
+
+    async fn command_device(&self, actuator: Actuator, mode: RelayMode) -> Result<(), String> {
+        if !self.serial_connected() {
+            return Err(format!("ESP32 {} is not connected", self.device_id));
+        }
+        if !self.device_identity.lock().unwrap().as_ref().is_some_and(DeviceIdentity::acknowledges_commands) {
+            return Err(format!("ESP32 {} firmware does not acknowledge commands", self.device_id));
+        }
+        let part = format!("{}={}", actuator.name(), mode.as_str());
+        let mut replies = self.command_tx.subscribe();
+        let previous = self.command_relay(actuator, mode);
+        // Frame tidak berubah = tidak dikirim ulang; perintah yang sama sudah diterima sebelumnya
+        if previous == Some(mode) {
+            return Ok(());
+        }
+        let reply = async {
+            loop {
+                match replies.recv().await {
+                    Ok(reply) if reply.command.starts_with("RELAY|") && reply.command.split('|').any(|p| p == part) => return reply.result,
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return Err("serial monitor stopped".to_string()),
+                }
+            }
+        };
+        let result = tokio::time::timeout(self.command_deadline, reply)
+            .await
+            .unwrap_or_else(|_| Err(format!("no reply within {}s", self.command_deadline.as_secs())));
+        if result.is_err() {
+            self.replace_relay(actuator, previous);
+        }
+        result.map_err(|e| format!("ESP32 {} did not accept {}: {}", self.device_id, part, e))
+    }
+
+    pub fn settings(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    // Terapkan perubahan settings (REST atau atribut ThingsBoard), simpan ke disk dan catat
+    // event per nilai yang berubah. Err jika ada nilai yang ditolak; tidak ada yang diterapkan.
+    pub fn update_settings(&self, patch: &SettingsPatch, reason: &str, source: EventSource) -> anyhow::Result<Vec<Change>> {
+        let changes = {
+            let mut settings = self.settings.lock().unwrap();
+            let changes = settings.apply(patch)?;
+            if !changes.is_empty() {
+                if let Err(e) = settings.save(&self.settings_path) {
+                    log::warn!("💾 Settings applied but not persisted: {:#}", e);
+                }
+            }
+            changes
+        };
+        for (key, old, new) in &changes {
+            log::info!("⚙️  Setting {} changed {} -> {} ({})", key, old, new, reason);
+            self.events.record(
+                Event::new("config", format!("settings/{key}"), new.to_string()).old(old.to_string()).reason(reason).source(source),
+            );
+        }
+        Ok(changes)
+    }
+
+    // Daftarkan tujuan ThingsBoard; flag diubah oleh thread event MQTT-nya
+    pub fn mqtt_link(&self, name: &str) -> Arc<AtomicBool> {
+        self.mqtt_links.lock().unwrap().entry(name.to_string()).or_default().clone()
+    }
+
+    // true jika semua tujuan ThingsBoard yang aktif terhubung
+    pub fn mqtt_connected(&self) -> bool {
+        self.mqtt_links.lock().unwrap().values().all(|link| link.load(Ordering::Relaxed))
+    }
+
+    pub fn mqtt_status(&self) -> BTreeMap<String, bool> {
+        self.mqtt_links.lock().unwrap().iter().map(|(name, link)| (name.clone(), link.load(Ordering::Relaxed))).collect()
+    }
+
+    // Snapshot kesehatan semua sink
+    pub fn sink_health(&self) -> BTreeMap<String, SinkHealth> {
+        self.sinks.lock().unwrap().iter().map(|(name, health)| (name.clone(), health.lock().unwrap().clone())).collect()
+    }
+
+    pub fn clear_alarm(&self, id: &str) -> Option<Alarm> {
+        let alarm = self.alarms.lock().unwrap().clear(id)?;
+        self.events.record(Event::new("alarm", id, "clear").old("active").reason("condition cleared"));
+        let _ = self.alarm_tx.send(AlarmChange::Cleared(alarm.clone()));
+        Some(alarm)
+    }
+
+    // Ack operator; None jika alarm tidak aktif atau sudah di-ack
+    pub fn acknowledge_alarm(&self, id: &str, by: &str, source: EventSource) -> Option<Alarm> {
+        let alarm = self.alarms.lock().unwrap().acknowledge(id)?;
+        self.events.record(Event::new("alarm", id, "acknowledged").old("active").reason(format!("by {by}")).source(source));
+        Some(alarm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suppression_duration_is_bounded() {
+        let state = AppState::new(&Config::default());
+        assert_eq!(state.suppression_duration(30), Ok(std::time::Duration::from_secs(1800)));
+        assert_eq!(state.suppression_duration(24 * 60), Ok(std::time::Duration::from_secs(24 * 3600)));
+        for minutes in [0, 24 * 60 + 1, u64::MAX / 60 + 1, u64::MAX] {
+            assert!(state.suppression_duration(minutes).is_err(), "{minutes}");
+        }
+    }
+
+    #[test]
+    fn huge_shelve_does_not_poison_alarms() {
+        let state = AppState::new(&Config::default());
+        state.shelve_alarm("stale_a", std::time::Duration::MAX);
+        state.set_maintenance(Some(std::time::Duration::MAX), EventSource::Manual);
+        let alarms = state.alarms.lock().unwrap();
+        let remaining = alarms.maintenance_remaining(Instant::now()).unwrap();
+        assert!(remaining <= state.maintenance_max);
+    }
+
+    #[test]
+    fn shadow_mode_queues_no_set_command() {
+        let config = Config { shadow: true, ..Config::default() };
+        let state = AppState::new(&config);
+        assert!(state.device_settings.lock().unwrap().is_empty());
+        state.queue_device_settings(config.device_set_command((31.0, 29.0), (60.0, 65.0)));
+        assert!(state.device_settings.lock().unwrap().is_empty());
+
+        let state = AppState::new(&Config::default());
+        assert_eq!(*state.device_settings.lock().unwrap(), "SET|temp_on=30.00|temp_off=25.00|hum_on=60.00|hum_off=65.00");
+        state.queue_device_settings(Config::default().device_set_command((31.0, 29.0), (55.0, 60.0)));
+        assert_eq!(*state.device_settings.lock().unwrap(), "SET|temp_on=31.00|temp_off=29.00|hum_on=55.00|hum_off=60.00");
+    }
+}
@@ -0,0 +1,147 @@
+use log::{error, info};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::bridge::{bucket_for, location_filter, now_ns, post_influx, write_points, Influx, ThingsBoard, SENSOR_MEAS};
+use crate::flux_csv::Record;
+use crate::line_protocol::Point;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StatsConfig {
+    pub enabled: bool,
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub interval: Duration,
+    // Jendela rolling dalam format durasi Flux, misal "1h" dan "1d"
+    pub windows: Vec<String>,
+    pub fields: Vec<String>,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: Duration::from_secs(300),
+            windows: vec!["1h".to_string(), "1d".to_string()],
+            fields: vec!["temperature".to_string(), "humidity".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FieldStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+// Satu query Flux yang menghasilkan min/max/mean/stddev per field dengan kolom "stat".
+// `tag_filter`: baris filter tambahan (mis. per location), boleh kosong
+pub fn stats_flux(bucket: &str, measurement: &str, window: &str, fields: &[String], tag_filter: &str) -> String {
+    stats_flux_range(bucket, measurement, &format!("start: -{window}"), fields, tag_filter)
+}
+
+// Sama dengan stats_flux untuk rentang bebas, mis. `start: 2024-01-01T00:00:00Z, stop: ...`
+pub fn stats_flux_range(bucket: &str, measurement: &str, range: &str, fields: &[String], tag_filter: &str) -> String {
+    let filter = fields
+        .iter()
+        .map(|f| format!(r#"r["_field"] == "{f}""#))
+        .collect::<Vec<_>>()
+        .join(" or ");
+    let stat = |func: &str| {
+        format!(r#"data |> {func}() |> set(key: "stat", value: "{func}") |> keep(columns: ["_field", "_value", "stat"])"#)
+    };
+    format!(
+        r#"data = from(bucket: "{bucket}")
+  |> range({range})
+  |> filter(fn: (r) => r["_measurement"] == "{measurement}")
+{tag_filter}  |> filter(fn: (r) => {filter})
+  |> group(columns: ["_field"])
+
+union(tables: [
+  {},
+  {},
+  {},
+  {},
+])
+"#,
+        stat("min"),
+        stat("max"),
+        stat("mean"),
+        stat("stddev")
+    )
+}
+
+// Baris (_field, stat, _value) hasil stats_flux
+pub fn parse_stats(rows: &[Record]) -> BTreeMap<String, FieldStats> {
+    let mut out: BTreeMap<String, FieldStats> = BTreeMap::new();
+    for row in rows {
+        let (Some(field), Some(stat), Some(value)) = (row.get("_field"), row.get("stat"), row.number("_value")) else { continue };
+        let stats = out.entry(field.to_string()).or_default();
+        match stat {
+            "min" => stats.min = value,
+            "max" => stats.max = value,
+            "mean" => stats.mean = value,
+            "stddev" => stats.stddev = value,
+            _ => {}
+        }
+    }
+    out
+}
+
+// Agregat statistik rolling (min/max/mean/stddev) per jendela waktu.
+// Ditulis ke measurement "sensor_stats" dan dipublish sebagai atribut ThingsBoard.
+pub(crate) async fn run_task(client: Client, influx: Influx, tb: ThingsBoard, config: StatsConfig, location: Option<String>) {
+    let mut ticker = tokio::time::interval(config.interval);
+    let location_tag = location.clone().unwrap_or_default();
+    let location = location_filter(location.as_deref());
+    loop {
+        ticker.tick().await;
+        let mut attributes = serde_json::Map::new();
+        let mut points = Vec::new();
+        let ts = now_ns();
+
+        for window in &config.windows {
+            let flux = stats_flux(bucket_for(SENSOR_MEAS), SENSOR_MEAS, window, &config.fields, &location);
+            let rows = match post_influx(&client, flux).await {
+                Ok(rows) => rows,
+                Err(e) => {
+                    error!("Stats query for window {} failed: {}", window, e);
+                    continue;
+                }
+            };
+            for (field, s) in parse_stats(&rows) {
+                points.push(
+                    Point::new("sensor_stats")
+                        .tag("window", window)
+                        .tag("field", &field)
+                        .tag("location", &location_tag)
+                        .float_prec("min", s.min, 3)
+                        .float_prec("max", s.max, 3)
+                        .float_prec("mean", s.mean, 3)
+                        .float_prec("stddev", s.stddev, 3)
+                        .timestamp(ts),
+                );
+                for (stat, value) in [("min", s.min), ("max", s.max), ("mean", s.mean), ("stddev", s.stddev)] {
+                    attributes.insert(format!("{field}_{window}_{stat}"), json!((value * 100.0).round() / 100.0));
+                }
+            }
+        }
+
+        if points.is_empty() {
+            continue;
+        }
+        if let Err(e) = write_points(&influx, &points) {
+            error!("Failed to write sensor stats to InfluxDB: {}", e);
+        }
+        let message = tb.attributes(attributes);
+        info!("📊 Publishing stats attributes: {}", message.body);
+        if let Err(e) = tb.push(message) {
+            error!("MQTT attribute publish error: {e:#}");
+        }
+    }
+}
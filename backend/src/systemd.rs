@@ -0,0 +1,140 @@
+use log::{info, warn};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+// Integrasi systemd (Type=notify): READY=1 setelah serial, MQTT dan InfluxDB siap, WATCHDOG=1
+// setiap siklus loop utama dan STATUS= ringkasan kondisi (terlihat di `systemctl status`).
+// Loop yang macet berhenti mengirim WATCHDOG sehingga systemd me-restart bridge (WatchdogSec=).
+// Tanpa $NOTIFY_SOCKET (dijalankan manual, docker) semuanya no-op.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SystemdConfig {
+    // Batas menunggu semua koneksi; lewat dari ini READY tetap dikirim (STATUS menyebut yang belum siap)
+    // supaya port serial yang dicabut tidak membuat systemd me-restart bridge terus-menerus
+    #[serde(deserialize_with = "crate::config::deserialize_duration")]
+    pub ready_timeout: Duration,
+}
+
+impl Default for SystemdConfig {
+    fn default() -> Self {
+        Self { ready_timeout: Duration::from_secs(90) }
+    }
+}
+
+// Kondisi satu siklus loop utama
+pub struct Health {
+    pub serial: bool,
+    pub mqtt: bool,
+    pub influx: bool,
+    pub active_alarms: usize,
+}
+
+impl Health {
+    fn missing(&self) -> Vec<&'static str> {
+        [(self.serial, "serial"), (self.mqtt, "MQTT"), (self.influx, "InfluxDB")]
+            .into_iter()
+            .filter_map(|(ok, name)| (!ok).then_some(name))
+            .collect()
+    }
+
+    fn status(&self) -> String {
+        let missing = self.missing();
+        let links = if missing.is_empty() { "all links up".to_string() } else { format!("waiting for {}", missing.join(", ")) };
+        format!("{}, {} active alarm(s)", links, self.active_alarms)
+    }
+}
+
+pub struct Notifier {
+    socket: Option<String>,
+    watchdog: Option<Duration>,
+    ready_timeout: Duration,
+    started: Instant,
+    ready: bool,
+}
+
+impl Notifier {
+    pub fn from_env(config: &SystemdConfig) -> Self {
+        let socket = std::env::var("NOTIFY_SOCKET").ok().filter(|s| !s.is_empty());
+        // WATCHDOG_PID lain = watchdog milik proses induk (mis. wrapper script)
+        let for_us = std::env::var("WATCHDOG_PID").map_or(true, |pid| pid.trim() == std::process::id().to_string());
+        let watchdog = std::env::var("WATCHDOG_USEC")
+            .ok()
+            .and_then(|usec| usec.trim().parse::<u64>().ok())
+            .filter(|usec| *usec > 0 && for_us)
+            .map(Duration::from_micros);
+        Self { socket, watchdog, ready_timeout: config.ready_timeout, started: Instant::now(), ready: false }
+    }
+
+    // Log konfigurasi saat startup; WatchdogSec harus beberapa kali lebih panjang dari satu siklus
+    pub fn describe(&self, cycle: Duration) {
+        let Some(socket) = &self.socket else { return };
+        match self.watchdog {
+            Some(watchdog) if watchdog < cycle * 3 => warn!(
+                "⚠️  systemd WatchdogSec={}s is shorter than three control cycles ({}s each), expect spurious restarts",
+                watchdog.as_secs(), cycle.as_secs()
+            ),
+            Some(watchdog) => info!("  - systemd notify: {} (watchdog {}s)", socket, watchdog.as_secs()),
+            None => info!("  - systemd notify: {} (no watchdog)", socket),
+        }
+    }
+
+    pub fn starting(&self, status: &str) {
+        self.send(&format!("STATUS={status}"));
+    }
+
+    // Dipanggil di akhir setiap siklus loop utama
+    pub fn cycle(&mut self, health: &Health) {
+        if self.socket.is_none() {
+            return;
+        }
+        let mut message = format!("WATCHDOG=1\nSTATUS={}", health.status());
+        if !self.ready {
+            let missing = health.missing();
+            if missing.is_empty() || self.started.elapsed() >= self.ready_timeout {
+                self.ready = true;
+                message.insert_str(0, "READY=1\n");
+                if missing.is_empty() {
+                    info!("✅ systemd: service ready");
+                } else {
+                    warn!("⚠️  systemd: reporting ready after {}s without {}", self.ready_timeout.as_secs(), missing.join(", "));
+                }
+            }
+        }
+        self.send(&message);
+    }
+
+    pub fn stopping(&self, status: &str) {
+        self.send(&format!("STOPPING=1\nSTATUS={status}"));
+    }
+
+    fn send(&self, message: &str) {
+        let Some(socket) = &self.socket else { return };
+        if let Err(e) = send_datagram(socket, message) {
+            warn!("systemd notify to {} failed: {}", socket, e);
+        }
+    }
+}
+
+// "@nama" = socket abstrak Linux, selain itu path file
+#[cfg(unix)]
+fn send_datagram(socket: &str, message: &str) -> std::io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+    let datagram = UnixDatagram::unbound()?;
+    match socket.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            datagram.send_to_addr(message.as_bytes(), &addr)?;
+        }
+        _ => {
+            datagram.send_to(message.as_bytes(), socket)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_datagram(_socket: &str, _message: &str) -> std::io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,393 @@
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use reqwest::{Client, Method, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::alarms::{Alarm, AlarmChange, Severity};
+use crate::secrets::Secret;
+use crate::state::AppState;
+
+// Alarm native ThingsBoard lewat REST API. Device token MQTT tidak bisa membuat alarm, jadi
+// dipakai user tenant: login JWT, cari id device dari namanya, lalu create/clear alarm
+// dengan type = id alarm lokal. Dipakai kanal notifikasi "thingsboard" dan mode mirror.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TbAlarmConfig {
+    // true = setiap alarm lokal >= min_severity dibuat/di-clear di ThingsBoard saat aktif/normal,
+    // terlepas dari routing notifikasi
+    pub mirror: bool,
+    pub min_severity: Severity,
+    pub url: String,
+    // Bisa juga lewat env TB_USERNAME / TB_PASSWORD (atau *_FILE)
+    pub username: Secret,
+    pub password: Secret,
+    // Nama device di ThingsBoard; kosong = device_id
+    pub device: String,
+    pub severity: SeverityMap,
+}
+
+impl Default for TbAlarmConfig {
+    fn default() -> Self {
+        Self {
+            mirror: false,
+            min_severity: Severity::Info,
+            url: "http://localhost:8080".to_string(),
+            username: Secret::default(),
+            password: Secret::default(),
+            device: String::new(),
+            severity: SeverityMap::default(),
+        }
+    }
+}
+
+// Severity ThingsBoard per severity lokal
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SeverityMap {
+    pub info: String,
+    pub warning: String,
+    pub critical: String,
+}
+
+impl Default for SeverityMap {
+    fn default() -> Self {
+        Self { info: "INDETERMINATE".to_string(), warning: "WARNING".to_string(), critical: "CRITICAL".to_string() }
+    }
+}
+
+pub const TB_SEVERITIES: [&str; 5] = ["CRITICAL", "MAJOR", "MINOR", "WARNING", "INDETERMINATE"];
+
+impl SeverityMap {
+    pub fn get(&self, severity: Severity) -> &str {
+        match severity {
+            Severity::Info => &self.info,
+            Severity::Warning => &self.warning,
+            Severity::Critical => &self.critical,
+        }
+    }
+}
+
+pub struct TbAlarmClient {
+    client: Client,
+    config: TbAlarmConfig,
+    device_name: String,
+    jwt: Option<Secret>,
+    device_id: Option<String>,
+    // id alarm lokal -> id alarm ThingsBoard
+    created: HashMap<String, String>,
+}
+
+impl TbAlarmClient {
+    pub fn new(client: Client, config: TbAlarmConfig, device_id: &str) -> Self {
+        let device_name = if config.device.is_empty() { device_id.to_string() } else { config.device.clone() };
+        Self { client, config, device_name, jwt: None, device_id: None, created: HashMap::new() }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.url.trim_end_matches('/'), path)
+    }
+
+    async fn login(&mut self) -> Result<Secret> {
+        let response = self
+            .client
+            .post(self.url("/api/auth/login"))
+            .json(&json!({ "username": self.config.username.expose(), "password": self.config.password.expose() }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("ThingsBoard login failed: {}", response.status()));
+        }
+        let body: Value = response.json().await?;
+        let token = body["token"].as_str().ok_or_else(|| anyhow!("ThingsBoard login response without token"))?;
+        let jwt = Secret::new(token);
+        self.jwt = Some(jwt.clone());
+        Ok(jwt)
+    }
+
+    // JWT kedaluwarsa (401) -> login ulang sekali lalu ulangi request
+    async fn request(&mut self, method: Method, path: &str, query: &[(&str, &str)], body: Option<Value>) -> Result<Value> {
+        for attempt in 0..2 {
+            let jwt = match (&self.jwt, attempt) {
+                (Some(jwt), 0) => jwt.clone(),
+                _ => self.login().await?,
+            };
+            let mut request =
+                self.client.request(method.clone(), self.url(path)).query(query).header("X-Authorization", jwt.header("Bearer"));
+            if let Some(body) = &body {
+                request = request.json(body);
+            }
+            let response = request.send().await?;
+            let status = response.status();
+            if status == StatusCode::UNAUTHORIZED && attempt == 0 {
+                continue;
+            }
+            if !status.is_success() {
+                return Err(anyhow!("ThingsBoard {} {} failed: {}", method, path, status));
+            }
+            let text = response.text().await?;
+            return Ok(if text.is_empty() { Value::Null } else { serde_json::from_str(&text)? });
+        }
+        Err(anyhow!("ThingsBoard rejected credentials"))
+    }
+
+    async fn device(&mut self) -> Result<String> {
+        if let Some(id) = &self.device_id {
+            return Ok(id.clone());
+        }
+        let name = self.device_name.clone();
+        let device = self
+            .request(Method::GET, "/api/tenant/devices", &[("deviceName", &name)], None)
+            .await
+            .with_context(|| format!("ThingsBoard device '{}' not found", name))?;
+        let id = device["id"]["id"].as_str().ok_or_else(|| anyhow!("ThingsBoard device '{}' has no id", self.device_name))?.to_string();
+        self.device_id = Some(id.clone());
+        Ok(id)
+    }
+
+    // Alarm aktif dengan type yang sama tidak digandakan oleh ThingsBoard (diperbarui saja)
+    pub async fn create(&mut self, alarm: &Alarm) -> Result<()> {
+        let device = self.device().await?;
+        let body = json!({
+            "originator": { "entityType": "DEVICE", "id": device },
+            "type": alarm.id,
+            "severity": self.config.severity.get(alarm.severity),
+            "details": { "message": alarm.message },
+        });
+        let created = self.request(Method::POST, "/api/alarm", &[], Some(body)).await?;
+        if let Some(id) = created["id"]["id"].as_str() {
+            self.created.insert(alarm.id.clone(), id.to_string());
+        }
+        Ok(())
+    }
+
+    // Tanpa id tersimpan (mis. setelah restart) alarm dicari di daftar alarm aktif device
+    pub async fn clear(&mut self, id: &str) -> Result<()> {
+        let tb_id = match self.created.remove(id) {
+            Some(tb_id) => Some(tb_id),
+            None => self.find_active(id).await?,
+        };
+        if let Some(tb_id) = tb_id {
+            self.request(Method::POST, &format!("/api/alarm/{tb_id}/clear"), &[], None).await?;
+        }
+        Ok(())
+    }
+
+    async fn find_active(&mut self, id: &str) -> Result<Option<String>> {
+        let device = self.device().await?;
+        let query = [("searchStatus", "ACTIVE"), ("pageSize", "100"), ("page", "0")];
+        let page = self.request(Method::GET, &format!("/api/alarm/DEVICE/{device}"), &query, None).await?;
+        let found = page["data"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|a| a["type"].as_str() == Some(id))
+            .and_then(|a| a["id"]["id"].as_str())
+            .map(str::to_string);
+        Ok(found)
+    }
+}
+
+// Mode mirror: status alarm lokal diikuti di ThingsBoard. Operasi yang gagal (ThingsBoard atau
+// jaringan mati) diulang tiap 30 detik; alarm yang hilang tanpa clear (shelve/maintenance) ikut di-clear.
+pub async fn run_mirror(client: Client, state: Arc<AppState>, config: TbAlarmConfig) {
+    let min_severity = config.min_severity;
+    let mut tb = TbAlarmClient::new(client, config, &state.device_id);
+    let mut changes = state.subscribe_alarms();
+    // id -> Some(alarm) = harus aktif di ThingsBoard, None = harus di-clear; dihapus setelah berhasil
+    let mut pending: BTreeMap<String, Option<Alarm>> = BTreeMap::new();
+    // Alarm yang sudah dibuat di ThingsBoard oleh mirror
+    let mut mirrored: BTreeSet<String> = BTreeSet::new();
+    resync(&state, min_severity, &mut pending, &mirrored);
+    let mut ticker = tokio::time::interval(Duration::from_secs(30));
+    loop {
+        tokio::select! {
+            change = changes.recv() => match change {
+                Ok(AlarmChange::Raised(alarm)) if alarm.severity >= min_severity => {
+                    pending.insert(alarm.id.clone(), Some(alarm));
+                }
+                Ok(AlarmChange::Cleared(alarm)) if alarm.severity >= min_severity => {
+                    pending.insert(alarm.id, None);
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("ThingsBoard alarm mirror lagged by {} change(s), resyncing", missed);
+                    resync(&state, min_severity, &mut pending, &mirrored);
+                }
+                Err(RecvError::Closed) => return,
+            },
+            _ = ticker.tick() => {
+                let active: BTreeSet<String> = state.alarms.lock().unwrap().active().map(|a| a.id.clone()).collect();
+                for id in mirrored.difference(&active) {
+                    pending.entry(id.clone()).or_insert(None);
+                }
+            }
+        }
+
+        // Urutan per id tidak penting: hanya status terakhir yang disimpan
+        while let Some((id, target)) = pending.pop_first() {
+            let result = match &target {
+                Some(alarm) => tb.create(alarm).await,
+                None => tb.clear(&id).await,
+            };
+            match result {
+                Ok(()) => {
+                    info!("🔔 ThingsBoard alarm {} {}", id, if target.is_some() { "created" } else { "cleared" });
+                    if target.is_some() {
+                        mirrored.insert(id);
+                    } else {
+                        mirrored.remove(&id);
+                    }
+                }
+                Err(e) => {
+                    warn!("ThingsBoard alarm {} not mirrored, retrying in 30s: {:#}", id, e);
+                    pending.insert(id, target);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// Semua alarm lokal yang aktif dibuat ulang (create idempotent di ThingsBoard)
+fn resync(state: &AppState, min_severity: Severity, pending: &mut BTreeMap<String, Option<Alarm>>, mirrored: &BTreeSet<String>) {
+    let alarms = state.alarms.lock().unwrap();
+    let active: Vec<Alarm> = alarms.active().filter(|a| a.severity >= min_severity).cloned().collect();
+    for id in mirrored.iter().filter(|id| !alarms.is_active(id)) {
+        pending.insert(id.clone(), None);
+    }
+    for alarm in active {
+        pending.insert(alarm.id.clone(), Some(alarm));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alarms::{AlarmManager, MaintenanceConfig};
+    use axum::http::{HeaderMap, Uri};
+    use std::sync::Mutex;
+
+    // Request yang diterima ThingsBoard palsu: (method path?query, header X-Authorization, body)
+    type Requests = Arc<Mutex<Vec<(String, String, Value)>>>;
+
+    // ThingsBoard palsu: login, lookup device "greenhouse" -> dev-1, alarm dibuat dengan id tb-<type>,
+    // daftar alarm aktif device berisi high_temp (tb-old) seperti setelah restart
+    async fn fake_thingsboard() -> (String, Requests) {
+        let requests: Requests = Arc::default();
+        let seen = requests.clone();
+        let handler = move |method: Method, uri: Uri, headers: HeaderMap, body: String| {
+            let seen = seen.clone();
+            async move {
+                let body: Value = serde_json::from_str(&body).unwrap_or(Value::Null);
+                let auth = headers.get("x-authorization").map(|v| v.to_str().unwrap().to_string()).unwrap_or_default();
+                seen.lock().unwrap().push((format!("{method} {uri}"), auth, body.clone()));
+                let reply = match (method, uri.path()) {
+                    (Method::POST, "/api/auth/login") => json!({ "token": "jwt-1" }),
+                    (Method::GET, "/api/tenant/devices") => json!({ "id": { "id": "dev-1" } }),
+                    (Method::POST, "/api/alarm") => json!({ "id": { "id": format!("tb-{}", body["type"].as_str().unwrap()) } }),
+                    (Method::GET, "/api/alarm/DEVICE/dev-1") => json!({ "data": [{ "type": "high_temp", "id": { "id": "tb-old" } }] }),
+                    _ => return String::new(),
+                };
+                reply.to_string()
+            }
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, axum::Router::new().fallback(handler)).await });
+        (url, requests)
+    }
+
+    fn client(url: String, severity: SeverityMap) -> TbAlarmClient {
+        let config = TbAlarmConfig {
+            url,
+            username: Secret::new("tenant@thingsboard.org"),
+            password: Secret::new("tenant"),
+            device: "greenhouse".to_string(),
+            severity,
+            ..Default::default()
+        };
+        TbAlarmClient::new(Client::builder().no_proxy().build().unwrap(), config, "esp32-1")
+    }
+
+    fn alarm(id: &str, severity: Severity) -> Alarm {
+        AlarmManager::new(&MaintenanceConfig::default()).raise(id, severity, format!("{id} test")).unwrap()
+    }
+
+    #[test]
+    fn maps_local_severity_to_thingsboard() {
+        let map = SeverityMap::default();
+        assert_eq!(map.get(Severity::Info), "INDETERMINATE");
+        assert_eq!(map.get(Severity::Warning), "WARNING");
+        assert_eq!(map.get(Severity::Critical), "CRITICAL");
+        assert!([Severity::Info, Severity::Warning, Severity::Critical].iter().all(|s| TB_SEVERITIES.contains(&map.get(*s))));
+
+        // Key yang tidak diisi di config tetap memakai default
+        let map: SeverityMap = toml::from_str("warning = \"MINOR\"\ncritical = \"MAJOR\"").unwrap();
+        assert_eq!(map.get(Severity::Info), "INDETERMINATE");
+        assert_eq!(map.get(Severity::Warning), "MINOR");
+        assert_eq!(map.get(Severity::Critical), "MAJOR");
+    }
+
+    #[tokio::test]
+    async fn create_and_clear_send_thingsboard_alarm_json() {
+        let (url, requests) = fake_thingsboard().await;
+        let severity = SeverityMap { critical: "MAJOR".to_string(), ..Default::default() };
+        let mut tb = client(url, severity);
+
+        tb.create(&alarm("sensor_fail", Severity::Critical)).await.unwrap();
+        tb.clear("sensor_fail").await.unwrap();
+        let seen = requests.lock().unwrap().clone();
+        let paths: Vec<&str> = seen.iter().map(|(path, _, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "POST /api/auth/login",
+                "GET /api/tenant/devices?deviceName=greenhouse",
+                "POST /api/alarm",
+                "POST /api/alarm/tb-sensor_fail/clear"
+            ]
+        );
+        assert_eq!(seen[0].2, json!({ "username": "tenant@thingsboard.org", "password": "tenant" }));
+        assert!(seen[1..].iter().all(|(_, auth, _)| auth == "Bearer jwt-1"));
+        // Aktif: alarm di device ThingsBoard dengan type = id lokal dan severity hasil mapping
+        assert_eq!(
+            seen[2].2,
+            json!({
+                "originator": { "entityType": "DEVICE", "id": "dev-1" },
+                "type": "sensor_fail",
+                "severity": "MAJOR",
+                "details": { "message": "sensor_fail test" },
+            })
+        );
+        // Kembali normal: clear tanpa body
+        assert_eq!(seen[3].2, Value::Null);
+    }
+
+    #[tokio::test]
+    async fn clear_without_created_id_looks_up_active_alarm() {
+        let (url, requests) = fake_thingsboard().await;
+        let mut tb = client(url, SeverityMap::default());
+
+        // Setelah restart id ThingsBoard tidak tersimpan: dicari di alarm aktif device
+        tb.clear("high_temp").await.unwrap();
+        // Alarm yang tidak ada di ThingsBoard tidak di-clear
+        tb.clear("low_humidity").await.unwrap();
+        let seen = requests.lock().unwrap().clone();
+        let paths: Vec<&str> = seen.iter().map(|(path, _, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "POST /api/auth/login",
+                "GET /api/tenant/devices?deviceName=greenhouse",
+                "GET /api/alarm/DEVICE/dev-1?searchStatus=ACTIVE&pageSize=100&page=0",
+                "POST /api/alarm/tb-old/clear",
+                "GET /api/alarm/DEVICE/dev-1?searchStatus=ACTIVE&pageSize=100&page=0"
+            ]
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+// Pemetaan key keluar ke ThingsBoard (telemetry dan atribut), supaya penamaan dan satuan bisa
+// disesuaikan dengan dashboard yang sudah ada tanpa ubah kode. Key tanpa aturan dikirim apa adanya.
+// InfluxDB, REST dan gRPC tetap memakai key dan satuan asli.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct KeyRule {
+    // Nama key baru; kosong = nama asli
+    pub rename: String,
+    // nilai * scale + offset, mis. °C -> °F: scale = 1.8, offset = 32
+    pub scale: f64,
+    pub offset: f64,
+    // Pembulatan setelah konversi
+    pub decimals: Option<u32>,
+    // true = key tidak dikirim
+    pub drop: bool,
+}
+
+impl Default for KeyRule {
+    fn default() -> Self {
+        Self { rename: String::new(), scale: 1.0, offset: 0.0, decimals: None, drop: false }
+    }
+}
+
+impl KeyRule {
+    // Hanya nilai numerik yang dikonversi; string/bool cukup di-rename
+    fn convert(&self, value: Value) -> Value {
+        let Some(v) = value.as_f64() else {
+            return value;
+        };
+        if self.scale == 1.0 && self.offset == 0.0 && self.decimals.is_none() {
+            return value;
+        }
+        let mut v = v * self.scale + self.offset;
+        if let Some(decimals) = self.decimals {
+            let factor = 10f64.powi(decimals as i32);
+            v = (v * factor).round() / factor;
+        }
+        serde_json::Number::from_f64(v).map(Value::Number).unwrap_or(value)
+    }
+}
+
+// [telemetry_keys.<key asli>]
+pub type KeyMapConfig = HashMap<String, KeyRule>;
+
+pub fn apply(rules: &KeyMapConfig, values: Map<String, Value>) -> Map<String, Value> {
+    if rules.is_empty() {
+        return values;
+    }
+    let mut mapped = Map::new();
+    for (key, value) in values {
+        match rules.get(&key) {
+            Some(rule) if rule.drop => {}
+            Some(rule) => {
+                let name = if rule.rename.is_empty() { key } else { rule.rename.clone() };
+                mapped.insert(name, rule.convert(value));
+            }
+            None => {
+                mapped.insert(key, value);
+            }
+        }
+    }
+    mapped
+}
@@ -0,0 +1,112 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::secrets::{self, Secret};
+use crate::zone::Zone;
+
+// Multi-tenant: zone milik kelompok riset lain (zone.tenant) ditulis ke org/bucket InfluxDB
+// tenant itu dengan token-nya sendiri, lewat sink influxdb_tenant_<name> (antrian, breaker dan
+// sink_health terpisah), dan query zone tersebut memakai kredensial yang sama. Opsional device
+// ThingsBoard sendiri: key zone tenant dikirim ke sana, bukan ke device bersama.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TenantConfig {
+    pub org: String,
+    // Kosong = bucket routing bawaan ([retention] / SENSOR_DATA) di org tenant
+    pub bucket: String,
+    // Bisa juga lewat env INFLUX_TOKEN_<NAME>(_FILE)
+    pub token: Secret,
+    // Opsional: access token device ThingsBoard tenant, atau env TB_TOKEN_TENANT_<NAME>(_FILE)
+    pub thingsboard_token: Secret,
+    // Kosong/0 = host dan port [connections]
+    pub thingsboard_host: String,
+    pub thingsboard_port: u16,
+}
+
+impl TenantConfig {
+    pub fn token_env(name: &str) -> String {
+        format!("INFLUX_TOKEN_{}", env_suffix(name))
+    }
+
+    pub fn thingsboard_token_env(name: &str) -> String {
+        format!("TB_TOKEN_TENANT_{}", env_suffix(name))
+    }
+}
+
+fn env_suffix(name: &str) -> String {
+    name.to_uppercase().replace('-', "_")
+}
+
+#[derive(Debug)]
+pub struct Tenant {
+    pub name: String,
+    pub org: String,
+    pub bucket: String,
+    token: Secret,
+}
+
+impl Tenant {
+    // Header Authorization InfluxDB tenant
+    pub fn auth(&self) -> reqwest::header::HeaderValue {
+        self.token.header("Token")
+    }
+
+    // Bucket tenant, atau bucket routing bawaan jika tidak diisi
+    pub fn bucket_or<'a>(&'a self, default: &'a str) -> &'a str {
+        if self.bucket.is_empty() { default } else { &self.bucket }
+    }
+
+    pub fn sink_name(&self) -> String {
+        format!("influxdb_tenant_{}", self.name)
+    }
+}
+
+struct Tenants {
+    tenants: Vec<Tenant>,
+    // nama zone -> indeks di tenants
+    zones: HashMap<String, usize>,
+}
+
+static TENANTS: OnceLock<Tenants> = OnceLock::new();
+
+// Dipanggil sekali saat startup setelah INFLUX_TOKEN; token tenant wajib ada
+pub fn install(config: &HashMap<String, TenantConfig>, zones: &[Zone]) -> Result<usize> {
+    let mut names: Vec<&String> = config.keys().collect();
+    names.sort();
+    let mut tenants = Vec::new();
+    for name in names {
+        let tenant = &config[name];
+        let token = secrets::load_or(&TenantConfig::token_env(name), &tenant.token)?;
+        if token.is_empty() {
+            return Err(anyhow!("Missing InfluxDB token for tenant '{}': set token or {}", name, TenantConfig::token_env(name)));
+        }
+        tenants.push(Tenant { name: name.clone(), org: tenant.org.clone(), bucket: tenant.bucket.clone(), token });
+    }
+    let mut by_zone = HashMap::new();
+    for zone in zones {
+        if let Some(name) = &zone.tenant {
+            let index = tenants.iter().position(|t| t.name == *name).ok_or_else(|| anyhow!("zone '{}' uses unknown tenant '{}'", zone.name, name))?;
+            by_zone.insert(zone.name.clone(), index);
+        }
+    }
+    let count = tenants.len();
+    let _ = TENANTS.set(Tenants { tenants, zones: by_zone });
+    Ok(count)
+}
+
+// Tenant pemilik zone; None = org/bucket bawaan
+pub fn for_zone(zone: &str) -> Option<&'static Tenant> {
+    let tenants = TENANTS.get()?;
+    tenants.zones.get(zone).map(|index| &tenants.tenants[*index])
+}
+
+pub fn all() -> &'static [Tenant] {
+    TENANTS.get().map(|t| t.tenants.as_slice()).unwrap_or_default()
+}
+
+// Label metrik per tenant ("default" = org bawaan)
+pub fn label(tenant: Option<&Tenant>) -> &str {
+    tenant.map_or("default", |t| t.name.as_str())
+}
@@ -0,0 +1,180 @@
+use serde::Deserialize;
+use std::time::Instant;
+
+use crate::serial::SensorData;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutlierMode {
+    // Titik outlier dibuang sebelum ke InfluxDB
+    #[default]
+    Reject,
+    // Titik tetap ditulis dengan outlier=1 (lihat write_sensor_to_influx)
+    Flag,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ValidationConfig {
+    pub mode: OutlierMode,
+    pub temperature_range: (f32, f32),
+    pub humidity_range: (f32, f32),
+    // Perubahan maksimum per detik terhadap titik valid terakhir
+    pub max_temperature_rate: f32,
+    pub max_humidity_rate: f32,
+    // Setelah sekian penolakan rate berturut-turut, nilai baru diterima sebagai baseline
+    pub rebaseline_after: u32,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            mode: OutlierMode::Reject,
+            temperature_range: (-20.0, 80.0),
+            humidity_range: (0.0, 100.0),
+            max_temperature_rate: 0.5,
+            max_humidity_rate: 2.0,
+            rebaseline_after: 3,
+        }
+    }
+}
+
+pub struct Validator {
+    config: ValidationConfig,
+    last_valid: Option<(f32, f32, Instant)>,
+    rate_rejections: u32,
+}
+
+impl Validator {
+    pub fn new(config: ValidationConfig) -> Self {
+        Self { config, last_valid: None, rate_rejections: 0 }
+    }
+
+    pub fn mode(&self) -> OutlierMode {
+        self.config.mode
+    }
+
+    // Kembalikan daftar alasan jika titik ini outlier, kosong jika valid
+    pub fn check(&mut self, data: &SensorData, now: Instant) -> Vec<String> {
+        let mut reasons = Vec::new();
+        let (t_min, t_max) = self.config.temperature_range;
+        let (h_min, h_max) = self.config.humidity_range;
+
+        if !data.temperature.is_finite() || data.temperature < t_min || data.temperature > t_max {
+            reasons.push(format!("temperature {:.2} outside [{}, {}]", data.temperature, t_min, t_max));
+        }
+        if !data.humidity.is_finite() || data.humidity < h_min || data.humidity > h_max {
+            reasons.push(format!("humidity {:.2} outside [{}, {}]", data.humidity, h_min, h_max));
+        }
+        if !reasons.is_empty() {
+            return reasons;
+        }
+
+        if let Some((last_t, last_h, at)) = self.last_valid {
+            let dt = now.saturating_duration_since(at).as_secs_f32().max(1.0);
+            let t_rate = (data.temperature - last_t).abs() / dt;
+            let h_rate = (data.humidity - last_h).abs() / dt;
+            if t_rate > self.config.max_temperature_rate {
+                reasons.push(format!("temperature rate {:.2}/s > {}/s", t_rate, self.config.max_temperature_rate));
+            }
+            if h_rate > self.config.max_humidity_rate {
+                reasons.push(format!("humidity rate {:.2}/s > {}/s", h_rate, self.config.max_humidity_rate));
+            }
+        }
+
+        if !reasons.is_empty() {
+            self.rate_rejections += 1;
+            if self.rate_rejections < self.config.rebaseline_after {
+                return reasons;
+            }
+            log::warn!("Sustained change after {} rate rejections, accepting new baseline", self.rate_rejections);
+            reasons.clear();
+        }
+
+        self.rate_rejections = 0;
+        self.last_valid = Some((data.temperature, data.humidity, now));
+        reasons
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn reading(temperature: f32, humidity: f32) -> SensorData {
+        SensorData {
+            timestamp: 0,
+            temperature,
+            humidity,
+            exhaust_fan_status: None,
+            pump_status: None,
+            fan_duty: None,
+            aux: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn rejects_values_outside_range() {
+        let mut validator = Validator::new(ValidationConfig::default());
+        let now = Instant::now();
+
+        assert!(validator.check(&reading(25.0, 50.0), now).is_empty());
+        let reasons = validator.check(&reading(80.5, 101.0), now);
+        assert_eq!(reasons.len(), 2);
+        assert!(reasons[0].starts_with("temperature 80.50 outside"));
+        assert!(reasons[1].starts_with("humidity 101.00 outside"));
+        assert_eq!(validator.check(&reading(f32::NAN, 50.0), now).len(), 1);
+
+        // Batas range sendiri masih valid
+        assert!(validator.check(&reading(-20.0, 0.0), now + Duration::from_secs(600)).is_empty());
+    }
+
+    #[test]
+    fn rejects_fast_changes_against_last_valid_point() {
+        let mut validator = Validator::new(ValidationConfig::default());
+        let start = Instant::now();
+
+        assert!(validator.check(&reading(25.0, 50.0), start).is_empty());
+        // 3 °C dalam 2 detik = 1.5 °C/s > 0.5
+        let reasons = validator.check(&reading(28.0, 50.0), start + Duration::from_secs(2));
+        assert_eq!(reasons, vec!["temperature rate 1.50/s > 0.5/s".to_string()]);
+        // Rate dihitung terhadap titik valid terakhir, bukan titik yang ditolak
+        assert!(validator.check(&reading(26.0, 53.0), start + Duration::from_secs(4)).is_empty());
+
+        // dt di bawah satu detik dihitung satu detik
+        assert!(validator.check(&reading(26.4, 54.5), start + Duration::from_millis(4100)).is_empty());
+        assert_eq!(validator.check(&reading(26.4, 57.0), start + Duration::from_millis(4200)).len(), 1);
+    }
+
+    #[test]
+    fn sustained_change_becomes_new_baseline() {
+        let mut validator = Validator::new(ValidationConfig::default());
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert!(validator.check(&reading(25.0, 50.0), at(0)).is_empty());
+        assert!(!validator.check(&reading(35.0, 50.0), at(1)).is_empty());
+        assert!(!validator.check(&reading(35.0, 50.0), at(2)).is_empty());
+        // Penolakan ketiga berturut-turut (rebaseline_after = 3) diterima
+        assert!(validator.check(&reading(35.0, 50.0), at(3)).is_empty());
+        assert!(validator.check(&reading(35.2, 50.0), at(4)).is_empty());
+
+        // Counter kembali nol setelah baseline baru
+        assert!(!validator.check(&reading(45.0, 50.0), at(5)).is_empty());
+        assert!(!validator.check(&reading(45.0, 50.0), at(6)).is_empty());
+        assert!(validator.check(&reading(45.0, 50.0), at(7)).is_empty());
+    }
+
+    #[test]
+    fn out_of_range_does_not_count_towards_rebaseline() {
+        let mut validator = Validator::new(ValidationConfig { rebaseline_after: 2, ..Default::default() });
+        let start = Instant::now();
+
+        assert!(validator.check(&reading(25.0, 50.0), start).is_empty());
+        for secs in 1..5 {
+            assert!(!validator.check(&reading(90.0, 50.0), start + Duration::from_secs(secs)).is_empty());
+        }
+        assert!(!validator.check(&reading(35.0, 50.0), start + Duration::from_secs(5)).is_empty());
+    }
+}
@@ -0,0 +1,45 @@
+use serde::Deserialize;
+
+// Vapor pressure deficit (kPa): selisih tekanan uap jenuh dan tekanan uap aktual.
+// Untuk eksperimen pertumbuhan tanaman VPD lebih bermakna daripada humidity mentah.
+pub fn saturation_kpa(temperature: f64) -> f64 {
+    // Persamaan Tetens
+    0.6108 * (17.27 * temperature / (temperature + 237.3)).exp()
+}
+
+pub fn vpd_kpa(temperature: f64, humidity: f64) -> f64 {
+    saturation_kpa(temperature) * (1.0 - humidity.clamp(0.0, 100.0) / 100.0)
+}
+
+// Titik embun (°C), kebalikan persamaan Tetens yang sama
+pub fn dew_point(temperature: f64, humidity: f64) -> f64 {
+    let gamma = (humidity.clamp(1.0, 100.0) / 100.0).ln() + 17.27 * temperature / (temperature + 237.3);
+    237.3 * gamma / (17.27 - gamma)
+}
+
+// Mode greenhouse: pump (misting) mengejar setpoint VPD menggantikan humidity_on_below
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct VpdTarget {
+    pub setpoint: f64,
+    // Fan ikut ON jika VPD di bawah setpoint - band (udara terlalu lembap)
+    pub fan: bool,
+    pub band: f64,
+}
+
+impl Default for VpdTarget {
+    fn default() -> Self {
+        Self { setpoint: 1.0, fan: false, band: 0.2 }
+    }
+}
+
+impl VpdTarget {
+    // VPD di atas setpoint = udara terlalu kering, pump ON
+    pub fn pump_demand(&self, vpd: f64) -> bool {
+        vpd > self.setpoint
+    }
+
+    pub fn fan_demand(&self, vpd: f64) -> bool {
+        self.fan && vpd < self.setpoint - self.band
+    }
+}
@@ -0,0 +1,461 @@
+use anyhow::{anyhow, Result};
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use crate::alarms::{self, Alarm, AlarmContext};
+use crate::bridge::{self, Influx, LastRow, SENSOR_MEAS};
+use crate::cascade::CascadeConfig;
+use crate::config::Config;
+use crate::control::{Actuator, ActuatorId};
+use crate::forecast::Forecaster;
+use crate::fusion::{self, FusionStrategy};
+use crate::line_protocol::Point;
+use crate::quality::Quality;
+use crate::setpoint::{SetpointConfig, SetpointSource};
+use crate::settings::Settings;
+use crate::state::AppState;
+use crate::vpd::VpdTarget;
+
+// Satu ruang yang dikontrol: sensor, stream DWSIM, aktuator dan ambang batasnya
+#[derive(Debug, Clone, Deserialize)]
+pub struct Zone {
+    pub name: String,
+    // device_id sensor utama zone ini
+    pub device_id: String,
+    // Sensor tambahan di zone yang sama; jika diisi, nilai kontrol hasil fusion
+    #[serde(default)]
+    pub sensors: Vec<ZoneSensor>,
+    #[serde(default)]
+    pub fusion: FusionStrategy,
+    #[serde(default = "default_dwsim_measurement")]
+    pub dwsim_measurement: String,
+    #[serde(default = "default_dwsim_stream")]
+    pub dwsim_stream: String,
+    // Jenis aktuator fisik milik zone; tiap zone punya instance sendiri (fan zone A != fan zone B)
+    #[serde(default)]
+    pub actuators: Vec<Actuator>,
+    // Pump ON jika humidity turun ke nilai ini, OFF lagi di nilai ini + [thresholds] humidity_band
+    #[serde(default = "default_humidity_on_below")]
+    pub humidity_on_below: f64,
+    // Band fan absolut zone ini; kosong = [thresholds] global
+    #[serde(default)]
+    pub temp_on: Option<f64>,
+    #[serde(default)]
+    pub temp_off: Option<f64>,
+    // Jika diisi, fan memakai kontrol kaskade (PID + feedforward DWSIM)
+    #[serde(default)]
+    pub cascade: Option<CascadeConfig>,
+    // Jika diisi, pump (dan opsional fan) mengejar setpoint VPD
+    #[serde(default)]
+    pub vpd: Option<VpdTarget>,
+    // Sumber setpoint suhu dan prioritasnya; tanpa ini dipakai [setpoint] global
+    #[serde(default)]
+    pub setpoint: Option<SetpointConfig>,
+    // Nama di [tenants]: data zone ditulis/dibaca di org/bucket tenant dengan token-nya sendiri
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ZoneSensor {
+    pub device_id: String,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_dwsim_measurement() -> String {
+    crate::DWSIM_MEAS.to_string()
+}
+
+fn default_dwsim_stream() -> String {
+    "Water_i".to_string()
+}
+
+fn default_humidity_on_below() -> f64 {
+    60.0
+}
+
+impl Zone {
+    // Zone implisit untuk konfigurasi lama tanpa [[zones]]
+    pub fn default_for(device_id: &str) -> Self {
+        Self {
+            name: "main".to_string(),
+            device_id: device_id.to_string(),
+            sensors: Vec::new(),
+            fusion: FusionStrategy::default(),
+            dwsim_measurement: default_dwsim_measurement(),
+            dwsim_stream: default_dwsim_stream(),
+            actuators: Actuator::ALL.to_vec(),
+            humidity_on_below: default_humidity_on_below(),
+            temp_on: None,
+            temp_off: None,
+            cascade: None,
+            vpd: None,
+            setpoint: None,
+            tenant: None,
+        }
+    }
+
+    // Semua sensor zone: sensor utama dulu, lalu sensor tambahan
+    pub fn all_sensors(&self) -> Vec<ZoneSensor> {
+        let mut all = vec![ZoneSensor { device_id: self.device_id.clone(), weight: default_weight() }];
+        all.extend(self.sensors.iter().filter(|s| s.device_id != self.device_id).cloned());
+        if let Some(primary) = self.sensors.iter().find(|s| s.device_id == self.device_id) {
+            all[0].weight = primary.weight;
+        }
+        all
+    }
+
+    // Terisi oleh Config::zones(); default = api lalu dwsim
+    pub fn setpoint_config(&self) -> SetpointConfig {
+        self.setpoint.clone().unwrap_or_default()
+    }
+
+    // Zone membaca suhu DWSIM jika DWSIM sumber setpoint atau zone memakai kaskade
+    pub fn uses_dwsim(&self) -> bool {
+        self.setpoint_config().uses(SetpointSource::Dwsim) || self.cascade.is_some()
+    }
+
+    pub fn has(&self, actuator: Actuator) -> bool {
+        self.actuators.contains(&actuator)
+    }
+
+    // Id aktuator jenis ini milik zone (state kontrol, mode, jam operasi, energi)
+    pub fn actuator(&self, kind: Actuator) -> ActuatorId {
+        ActuatorId::new(&self.name, kind)
+    }
+
+    pub fn actuator_ids(&self) -> impl Iterator<Item = ActuatorId> + '_ {
+        self.actuators.iter().map(|kind| self.actuator(*kind))
+    }
+}
+
+// Nama zone valid dan unik, band fan zone masuk akal
+pub fn validate(zones: &[Zone]) -> Result<()> {
+    let mut names = HashSet::new();
+    for zone in zones {
+        // Nama zone dipakai apa adanya sebagai tag line protocol dan string di query Flux
+        if zone.name.is_empty() || !zone.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(anyhow!("invalid zone name '{}' (allowed: A-Z a-z 0-9 _ -)", zone.name));
+        }
+        if !names.insert(zone.name.as_str()) {
+            return Err(anyhow!("duplicate zone '{}'", zone.name));
+        }
+        if zone.fusion == FusionStrategy::Weighted && zone.all_sensors().iter().any(|s| s.weight < 0.0) {
+            return Err(anyhow!("zone '{}' has a negative sensor weight", zone.name));
+        }
+        if zone.temp_on.into_iter().chain(zone.temp_off).any(|t| !t.is_finite()) {
+            return Err(anyhow!("zone '{}' has a non-finite temp_on/temp_off", zone.name));
+        }
+        if let (Some(on), Some(off)) = (zone.temp_on, zone.temp_off) {
+            if off >= on {
+                return Err(anyhow!("zone '{}': temp_off ({}) must be below temp_on ({})", zone.name, off, on));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Zone yang dilayani device sensor tertentu (utama atau tambahan)
+pub fn for_device<'a>(zones: &'a [Zone], device_id: &str) -> Option<&'a Zone> {
+    zones
+        .iter()
+        .find(|z| z.device_id == device_id || z.sensors.iter().any(|s| s.device_id == device_id))
+}
+
+// Konteks satu siklus kontrol: tujuan tulis, parameter aktif dan payload ThingsBoard yang dibangun per zone
+pub(crate) struct Cycle<'a> {
+    pub influx: &'a Influx,
+    pub state: &'a AppState,
+    pub config: &'a Config,
+    pub config_path: &'a str,
+    pub settings: Settings,
+    pub maintenance: bool,
+    pub payload: serde_json::Map<String, Value>,
+    pub data_quality: Quality,
+}
+
+impl Cycle<'_> {
+    // Satu zone: key ThingsBoard tetap seperti sebelumnya; lebih dari satu: prefix <zone>_
+    pub fn prefix(&self, zone: &Zone) -> String {
+        if self.state.zones.len() > 1 { format!("{}_", zone.name) } else { String::new() }
+    }
+
+    pub fn set(&mut self, zone: &Zone, key: &str, value: Value) {
+        let key = format!("{}{key}", self.prefix(zone));
+        self.payload.insert(key, value);
+    }
+
+    pub fn point(&self, measurement: &str, zone: &Zone) -> Point {
+        bridge::zone_point(self.state, measurement, &zone.name)
+    }
+
+    // Tulis point; gagal hanya dicatat, siklus tetap jalan
+    pub fn write(&self, point: Point, what: &str) {
+        if let Err(e) = bridge::write_points(self.influx, &[point]) {
+            error!("Failed to write {} to InfluxDB: {}", what, e);
+        }
+    }
+
+    // Perubahan alarm (raise = true, clear = false) ke InfluxDB
+    pub fn record_alarm(&self, change: Option<(Alarm, bool)>) {
+        if let Some((alarm, active)) = change {
+            if let Err(e) = bridge::write_alarm_to_influx(self.influx, &alarm, active) {
+                error!("Failed to write alarm to InfluxDB: {}", e);
+            }
+        }
+    }
+
+    // Raise jika `message` terisi, clear jika None
+    fn toggle_alarm(&self, id: &str, message: Option<String>, context: AlarmContext) -> Option<(Alarm, bool)> {
+        match message {
+            Some(message) => self.state.raise_alarm_with(id, alarms::Severity::Warning, message, context).map(|a| (a, true)),
+            None => self.state.clear_alarm(id).map(|a| (a, false)),
+        }
+    }
+}
+
+// Zone yang memakai DWSIM tetapi datanya hilang terlalu lama: alarm, kontrol jalan dengan sumber berikutnya
+pub(crate) fn check_dwsim(cx: &Cycle, zone: &Zone, dwsim_seen: &mut HashMap<String, Instant>, dwsim_temp: Option<f64>) {
+    let seen = dwsim_seen.entry(zone.name.clone()).or_insert_with(Instant::now);
+    if dwsim_temp.is_some() {
+        *seen = Instant::now();
+    }
+    let missing_for = seen.elapsed();
+    let message = (missing_for >= zone.setpoint_config().dwsim_missing_after)
+        .then(|| format!("Zone {} has no DWSIM data for {} min", zone.name, missing_for.as_secs() / 60));
+    let change = cx.toggle_alarm(&format!("dwsim_missing_{}", zone.name), message, AlarmContext::zone(zone.name.as_str()));
+    if let Some((alarm, true)) = &change {
+        warn!("🧪 {}", alarm.message);
+    }
+    cx.record_alarm(change);
+}
+
+// Gabungkan sensor zone; sensor stale tidak ikut fusion selama masih ada yang segar.
+// Nilai sensor di-fusion; status aktuator dari row zone/primary, fan_duty dari primary
+pub(crate) fn fuse_sensors(cx: &mut Cycle, zone: &Zone, rows: &HashMap<String, LastRow>, fields: &[String]) -> LastRow {
+    let sensors = zone.all_sensors();
+    let zone_row = rows.get("").cloned().unwrap_or_default();
+    let stale_after = cx.config.quality.stale_after();
+    let fresh = |device: &str| cx.state.last_sample_age(device).is_some_and(|age| age <= stale_after);
+    let mut candidates: Vec<((&str, f64, LastRow), bool)> = Vec::new();
+    let mut per_sensor = Vec::new();
+    for sensor in &sensors {
+        let row = rows.get(&sensor.device_id).cloned();
+        // Data lama sebelum ada tag device hanya dipakai untuk zone satu sensor
+        let row = if sensors.len() == 1 { row.or_else(|| Some(zone_row.clone())) } else { row };
+        let Some(row) = row else { continue };
+        if sensors.len() > 1 {
+            if let Some(t) = row.temp() { per_sensor.push((format!("{}_temperature", sensor.device_id), t)); }
+            if let Some(h) = row.hum() { per_sensor.push((format!("{}_humidity", sensor.device_id), h)); }
+        }
+        candidates.push(((sensor.device_id.as_str(), sensor.weight, row), sensors.len() == 1 || fresh(&sensor.device_id)));
+    }
+    for (key, value) in per_sensor {
+        cx.set(zone, &key, json!(value));
+    }
+    if candidates.iter().any(|(_, fresh)| *fresh) {
+        for ((device, _, _), _) in candidates.iter().filter(|(_, fresh)| !fresh) {
+            warn!("⏳ Zone {}: sensor {} stale, excluded from fusion", zone.name, device);
+        }
+    }
+    let readings = fusion::fresh_only(candidates);
+    let fuse = |field: &str| {
+        let values: Vec<(f64, f64)> = readings.iter().filter_map(|(_, w, r)| Some((r.get(field)?, *w))).collect();
+        zone.fusion.fuse(&values)
+    };
+    let primary = readings.first().map(|(_, _, r)| r.clone()).unwrap_or_default();
+    let mut fused = LastRow::default();
+    for field in fields {
+        let value = match field.as_str() {
+            "exhaust_fan_status" => zone_row.exhaust_fan_status().or(primary.exhaust_fan_status()),
+            "pump_status" => primary.pump_status().or(zone_row.pump_status()),
+            "fan_duty" => primary.fan_duty(),
+            _ => fuse(field),
+        };
+        fused.set(field, value);
+    }
+    if sensors.len() > 1 {
+        let used: Vec<&str> = readings.iter().map(|(d, _, _)| *d).collect();
+        cx.set(zone, "sensors_used", json!(used.len()));
+        info!("🧮 Zone {}: {} fusion of [{}]", zone.name, zone.fusion.as_str(), used.join(", "));
+        if let (Some(t), Some(h)) = (fused.temp(), fused.hum()) {
+            let point = cx
+                .point("zone_fusion", zone)
+                .tag("strategy", zone.fusion.as_str())
+                .float_prec("temperature", t, 2)
+                .float_prec("humidity", h, 2)
+                .float("sensors_used", used.len() as f64)
+                .timestamp(bridge::now_ns());
+            cx.write(point, "fused values");
+        }
+    }
+    fused
+}
+
+// Tandai data stale jika sampel sensor tidak datang sesuai cadence
+pub(crate) fn check_quality(cx: &mut Cycle, zone: &Zone) -> Quality {
+    let last_sample_age = zone.all_sensors().iter().filter_map(|s| cx.state.last_sample_age(&s.device_id)).min();
+    let quality = match last_sample_age {
+        Some(age) if age <= cx.config.quality.stale_after() => Quality::Good,
+        _ => Quality::Stale,
+    };
+    cx.set(zone, "data_quality", json!(quality.as_str()));
+    if quality == Quality::Stale {
+        cx.data_quality = Quality::Stale;
+        let age = last_sample_age.map(|a| a.as_secs_f64()).unwrap_or(-1.0);
+        warn!("⏳ Zone {}: sensor data stale (last sample {:.0}s ago)", zone.name, age);
+        let point = cx
+            .point(SENSOR_MEAS, zone)
+            .tag("time_source", "host")
+            .string("quality", Quality::Stale.as_str())
+            .float_prec("stale_seconds", age, 1)
+            .timestamp(bridge::now_ns());
+        cx.write(point, "stale marker");
+    }
+    let message = (quality == Quality::Stale).then(|| format!("Zone {} sensor data stale", zone.name));
+    cx.record_alarm(cx.toggle_alarm(&format!("stale_{}", zone.name), message, AlarmContext::zone(zone.name.as_str())));
+    quality
+}
+
+// Nilai sensor (mentah, terfilter, field tambahan) dan DWSIM ke payload ThingsBoard
+pub(crate) fn publish_readings(cx: &mut Cycle, zone: &Zone, sensor: &LastRow, dwsim_temp: Option<f64>) {
+    let mut values = vec![
+        ("sht20_temperature", sensor.temp()),
+        ("sht20_humidity", sensor.hum()),
+        ("fan_duty", sensor.fan_duty()),
+        ("dwsim_temperature", dwsim_temp),
+        ("sht20_temperature_filtered", sensor.temp_filtered()),
+        ("sht20_humidity_filtered", sensor.hum_filtered()),
+    ];
+    values.extend(cx.config.last_data.extra_fields.iter().map(|field| (field.as_str(), sensor.get(field))));
+    for (key, value) in values {
+        if let Some(value) = value {
+            cx.set(zone, key, json!(value));
+        }
+    }
+    if let Some(p) = sensor.pump_status() {
+        cx.set(zone, "pump_status", json!(p as i32));
+    }
+    if let Some(v) = sensor.control_vpd() {
+        cx.set(zone, "vpd", json!((v * 1000.0).round() / 1000.0));
+    }
+}
+
+// Prediksi suhu beberapa menit ke depan; alarm muncul sebelum ambang terlewati
+pub(crate) fn forecast(cx: &mut Cycle, zone: &Zone, forecasters: &mut HashMap<String, Forecaster>, temp: f64) {
+    let forecaster = forecasters
+        .entry(zone.name.clone())
+        .or_insert_with(|| Forecaster::new(cx.config.forecast.clone()));
+    forecaster.observe(Instant::now(), temp);
+    let predictions = forecaster.predict();
+    if !predictions.is_empty() {
+        let mut point = cx.point("forecast", zone);
+        for p in &predictions {
+            cx.set(zone, &format!("temperature_forecast_{}m", p.minutes), json!((p.value * 100.0).round() / 100.0));
+            point = point.float_prec(&format!("temperature_{}m", p.minutes), p.value, 2);
+        }
+        if let Some(slope) = forecaster.slope_per_hour() {
+            point = point.float_prec("slope_per_hour", slope, 3);
+        }
+        cx.write(point.timestamp(bridge::now_ns()), "forecast");
+    }
+    let forecast_id = format!("forecast_{}", zone.name);
+    let change = match forecaster.breach(temp, &predictions) {
+        Some(breach) => {
+            let message = format!(
+                "Zone {} temperature predicted to reach {:.1}°C in {} min (limit {:.1}°C)",
+                zone.name, breach.value, breach.minutes, breach.limit
+            );
+            let context = AlarmContext::zone(zone.name.as_str()).value(breach.value).threshold(breach.limit);
+            cx.state.raise_alarm_with(&forecast_id, cx.config.forecast.severity, message, context).map(|a| (a, true))
+        }
+        None => cx.state.clear_alarm(&forecast_id).map(|a| (a, false)),
+    };
+    if let Some((alarm, true)) = &change {
+        warn!("🔮 {}", alarm.message);
+    }
+    cx.record_alarm(change);
+}
+
+// Status ringkas untuk widget dashboard (comfort_status/comfort_index)
+pub(crate) fn comfort(cx: &mut Cycle, zone: &Zone, sensor: &LastRow, quality: Quality) {
+    let config = cx.config;
+    let band = config.comfort.band(cx.settings.temp_band(&zone.name).0, cx.settings.humidity_on_below(zone));
+    let comfort = config.comfort.evaluate(sensor.control_temp(), sensor.control_hum(), quality, band);
+    cx.set(zone, "comfort_status", json!(comfort.status.as_str()));
+    let mut point = cx.point("comfort", zone).string("status", comfort.status.as_str());
+    if let Some(index) = comfort.index {
+        cx.set(zone, "comfort_index", json!(index));
+        point = point.float_prec("index", index, 1);
+    }
+    cx.write(point.timestamp(bridge::now_ns()), "comfort status");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone(name: &str, device_id: &str, actuators: &[Actuator]) -> Zone {
+        Zone { name: name.to_string(), actuators: actuators.to_vec(), ..Zone::default_for(device_id) }
+    }
+
+    #[test]
+    fn accepts_plain_names() {
+        let zones = [zone("greenhouse_1", "esp32-1", &[Actuator::ExhaustFan]), zone("Lab-B", "esp32-2", &[Actuator::Pump])];
+        assert!(validate(&zones).is_ok());
+    }
+
+    #[test]
+    fn rejects_names_that_break_queries_or_tags() {
+        for name in ["", "main\"", "a\\b", "x\") or true or (\"", "green house", "a,b", "a=b", "zone\n", "zoné"] {
+            assert!(validate(&[zone(name, "esp32-1", &[])]).is_err(), "{name:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        assert!(validate(&[zone("a", "esp32-1", &[]), zone("a", "esp32-2", &[])]).is_err());
+    }
+
+    #[test]
+    fn each_zone_owns_its_actuators() {
+        let zones = [zone("a", "esp32-1", &[Actuator::ExhaustFan, Actuator::Pump]), zone("b", "esp32-2", &[Actuator::ExhaustFan])];
+        assert!(validate(&zones).is_ok());
+        assert_ne!(zones[0].actuator(Actuator::ExhaustFan), zones[1].actuator(Actuator::ExhaustFan));
+        let ids: Vec<String> = zones.iter().flat_map(Zone::actuator_ids).map(|id| id.to_string()).collect();
+        assert_eq!(ids, ["a/exhaust_fan", "a/pump", "b/exhaust_fan"]);
+    }
+
+    #[test]
+    fn actuator_id_round_trips_and_reads_legacy_keys() {
+        let id = ActuatorId::new("lab-b", Actuator::Heater);
+        assert_eq!(ActuatorId::try_from(id.to_string()), Ok(id));
+        assert_eq!(ActuatorId::try_from("pump".to_string()), Ok(ActuatorId::new("main", Actuator::Pump)));
+        assert!(ActuatorId::try_from("a/valve".to_string()).is_err());
+    }
+
+    #[test]
+    fn rejects_inverted_zone_band() {
+        let inverted = Zone { temp_on: Some(28.0), temp_off: Some(30.0), ..zone("a", "esp32-1", &[]) };
+        assert!(validate(&[inverted]).is_err());
+        let partial = Zone { temp_on: Some(32.0), ..zone("a", "esp32-1", &[]) };
+        assert!(validate(&[partial]).is_ok());
+    }
+
+    #[test]
+    fn uses_dwsim_only_as_setpoint_source_or_cascade() {
+        assert!(zone("a", "esp32-1", &[]).uses_dwsim());
+        let api_only = SetpointConfig { sources: vec![SetpointSource::Api, SetpointSource::Thingsboard], ..Default::default() };
+        let manual = Zone { setpoint: Some(api_only), ..zone("a", "esp32-1", &[]) };
+        assert!(!manual.uses_dwsim());
+        assert!(Zone { cascade: Some(CascadeConfig::default()), ..manual }.uses_dwsim());
+    }
+}
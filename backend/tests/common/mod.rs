@@ -0,0 +1,206 @@
+// Harness integration test: bridge dijalankan sebagai proses nyata, dengan
+// PTY sebagai port serial, wiremock sebagai InfluxDB dan broker MQTT mini.
+#![allow(dead_code)]
+
+use bytes::BytesMut;
+use nix::pty::openpty;
+use rumqttc::{ConnAck, ConnectReturnCode, Packet, PingResp, PubAck, QoS, SubAck, SubscribeReasonCode};
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+// Pasangan PTY: bridge membuka sisi slave seperti /dev/ttyUSB0, test menulis frame ke master
+pub struct VirtualSerial {
+    master: File,
+    _slave: OwnedFd,
+    pub path: PathBuf,
+}
+
+impl VirtualSerial {
+    pub fn open() -> Self {
+        let pty = openpty(None, None).expect("openpty");
+        let path = std::fs::read_link(format!("/proc/self/fd/{}", pty.slave.as_raw_fd())).expect("pty slave path");
+        Self { master: File::from(pty.master), _slave: pty.slave, path }
+    }
+
+    // Satu baris protokol ESP32, mis. "SENSOR_DATA|0|25.30|65.20"
+    pub fn send(&mut self, frame: &str) {
+        self.master.write_all(format!("{frame}\r\n").as_bytes()).expect("write frame");
+        self.master.flush().expect("flush frame");
+    }
+}
+
+// Stub InfluxDB: write selalu 204, query mengembalikan CSV kosong
+pub async fn influx_stub() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST")).and(path("/api/v2/write")).respond_with(ResponseTemplate::new(204)).mount(&server).await;
+    Mock::given(method("POST")).and(path("/api/v2/query")).respond_with(ResponseTemplate::new(200).set_body_string("")).mount(&server).await;
+    server
+}
+
+// Semua body line protocol yang diterima stub, dalam urutan kedatangan
+pub async fn written_lines(server: &MockServer) -> Vec<String> {
+    server
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|r| r.url.path() == "/api/v2/write")
+        .flat_map(|r| String::from_utf8_lossy(&r.body).lines().map(str::to_string).collect::<Vec<_>>())
+        .collect()
+}
+
+// (topic, payload) setiap PUBLISH yang diterima broker
+type Published = Arc<Mutex<Vec<(String, Vec<u8>)>>>;
+
+// Broker MQTT 3.1.1 minimal: CONNACK, SUBACK, PUBACK, PINGRESP; publish dicatat
+#[derive(Clone)]
+pub struct MqttBroker {
+    pub port: u16,
+    published: Published,
+}
+
+impl MqttBroker {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind broker");
+        let port = listener.local_addr().unwrap().port();
+        let broker = Self { port, published: Arc::default() };
+        let published = broker.published.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_client(stream, published.clone()));
+            }
+        });
+        broker
+    }
+
+    pub fn published(&self, topic: &str) -> Vec<serde_json::Value> {
+        self.published
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(t, _)| t == topic)
+            .filter_map(|(_, payload)| serde_json::from_slice(payload).ok())
+            .collect()
+    }
+}
+
+async fn serve_client(mut stream: tokio::net::TcpStream, published: Published) {
+    let mut buf = BytesMut::new();
+    loop {
+        let packet = match rumqttc::read(&mut buf, 1 << 20) {
+            Ok(packet) => packet,
+            Err(rumqttc::Error::InsufficientBytes(_)) => {
+                if stream.read_buf(&mut buf).await.unwrap_or(0) == 0 {
+                    return;
+                }
+                continue;
+            }
+            Err(_) => return,
+        };
+        let mut out = BytesMut::new();
+        match packet {
+            Packet::Connect(_) => {
+                ConnAck::new(ConnectReturnCode::Success, false).write(&mut out).unwrap();
+            }
+            Packet::Subscribe(sub) => {
+                let codes = sub.filters.iter().map(|_| SubscribeReasonCode::Success(QoS::AtLeastOnce)).collect();
+                SubAck::new(sub.pkid, codes).write(&mut out).unwrap();
+            }
+            Packet::Publish(publish) => {
+                if publish.qos != QoS::AtMostOnce {
+                    PubAck::new(publish.pkid).write(&mut out).unwrap();
+                }
+                published.lock().unwrap().push((publish.topic.clone(), publish.payload.to_vec()));
+            }
+            Packet::PingReq => {
+                PingResp.write(&mut out).unwrap();
+            }
+            Packet::Disconnect => return,
+            _ => {}
+        }
+        if !out.is_empty() && stream.write_all(&out).await.is_err() {
+            return;
+        }
+    }
+}
+
+// Proses bridge dengan config di direktori sementara; dimatikan saat di-drop
+pub struct Bridge {
+    child: Child,
+    pub dir: TempDir,
+}
+
+impl Bridge {
+    // `extra` ditambahkan ke config.toml setelah [connections]
+    pub fn spawn(influx_url: &str, broker: &MqttBroker, serial: &VirtualSerial, extra: &str) -> Self {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let config = format!(
+            r#"device_id = "sht20"
+
+[connections]
+influx_url = "{influx_url}"
+thingsboard_host = "127.0.0.1"
+thingsboard_port = {port}
+serial_port = "{serial}"
+
+[api]
+listen = "127.0.0.1:0"
+
+[grpc]
+enabled = false
+
+{extra}
+"#,
+            port = broker.port,
+            serial = serial.path.display(),
+        );
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, config).expect("write config");
+        let child = Command::new(env!("CARGO_BIN_EXE_influxdb-thingsboard-bridge"))
+            .current_dir(dir.path())
+            .env("BRIDGE_CONFIG", &config_path)
+            .env("INFLUX_TOKEN", "test-influx-token")
+            .env("TB_TOKEN", "test-tb-token")
+            .env("RUST_LOG", "info")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("spawn bridge");
+        Self { child, dir }
+    }
+}
+
+impl Drop for Bridge {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// Tunggu sampai `check` mengembalikan Some, atau panic setelah timeout
+pub async fn wait_for<T, F, Fut>(what: &str, timeout: Duration, mut check: F) -> T
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Option<T>>,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(value) = check().await {
+            return value;
+        }
+        if Instant::now() > deadline {
+            panic!("timed out after {:?} waiting for {}", timeout, what);
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
@@ -0,0 +1,120 @@
+// End-to-end: frame serial -> ingest -> InfluxDB line protocol -> ThingsBoard MQTT
+mod common;
+
+use common::{influx_stub, wait_for, written_lines, Bridge, MqttBroker, VirtualSerial};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+#[tokio::test]
+async fn sensor_frames_are_written_as_line_protocol() {
+    let influx = influx_stub().await;
+    let broker = MqttBroker::start().await;
+    let mut serial = VirtualSerial::open();
+    let _bridge = Bridge::spawn(&influx.uri(), &broker, &serial, "");
+
+    // Bridge perlu waktu membuka port; frame dikirim ulang sampai titiknya muncul
+    let line = wait_for("sensor point in InfluxDB", TIMEOUT, || {
+        serial.send("RELAY_STATUS|exhaust_fan:OFF|pump:ON");
+        serial.send("SENSOR_DATA|0|25.30|65.20");
+        let influx = &influx;
+        async move {
+            written_lines(influx).await.into_iter().find(|l| l.starts_with("sht20_sensor,zone=main,device=sht20,"))
+        }
+    })
+    .await;
+
+    assert!(line.contains("time_source=host"), "{line}");
+    assert!(line.contains(" temperature=25.30,humidity=65.20"), "{line}");
+    assert!(line.contains("quality=\"good\""), "{line}");
+    assert!(line.contains("pump_status=1"), "{line}");
+    assert!(line.contains("vpd="), "{line}");
+}
+
+#[tokio::test]
+async fn malformed_frames_are_ignored() {
+    let influx = influx_stub().await;
+    let broker = MqttBroker::start().await;
+    let mut serial = VirtualSerial::open();
+    let _bridge = Bridge::spawn(&influx.uri(), &broker, &serial, "");
+
+    wait_for("valid point after garbage", TIMEOUT, || {
+        serial.send("SENSOR_DATA|not-a-number|25.30|65.20");
+        serial.send("SENSOR_DATA|0|21.00");
+        serial.send("hello from bootloader");
+        serial.send("SENSOR_DATA|0|22.50|55.00");
+        let influx = &influx;
+        async move {
+            written_lines(influx).await.into_iter().find(|l| l.contains(" temperature=22.50,humidity=55.00"))
+        }
+    })
+    .await;
+
+    let sensor_points: Vec<String> = written_lines(&influx)
+        .await
+        .into_iter()
+        .filter(|l| l.starts_with("sht20_sensor,zone=main,device=sht20,"))
+        .collect();
+    assert!(sensor_points.iter().all(|l| l.contains(" temperature=22.50,humidity=55.00")), "{sensor_points:#?}");
+}
+
+#[tokio::test]
+async fn frames_with_bad_crc_are_dropped() {
+    let influx = influx_stub().await;
+    let broker = MqttBroker::start().await;
+    let mut serial = VirtualSerial::open();
+    let _bridge = Bridge::spawn(&influx.uri(), &broker, &serial, "");
+
+    wait_for("point from checksummed frame", TIMEOUT, || {
+        // Trailer |seq|crc16 milik frame 22.50, suhu rusak di jalan
+        serial.send("SENSOR_DATA|0|99.50|55.00|8|5658");
+        serial.send("SENSOR_DATA|0|22.50|55.00|8|5658");
+        let influx = &influx;
+        async move { written_lines(influx).await.into_iter().find(|l| l.contains(" temperature=22.50,humidity=55.00")) }
+    })
+    .await;
+
+    let lines = written_lines(&influx).await;
+    assert!(!lines.iter().any(|l| l.contains("99.50")), "{lines:#?}");
+}
+
+#[tokio::test]
+async fn out_of_range_readings_are_rejected() {
+    let influx = influx_stub().await;
+    let broker = MqttBroker::start().await;
+    let mut serial = VirtualSerial::open();
+    let _bridge = Bridge::spawn(&influx.uri(), &broker, &serial, "[validation]\nmode = \"reject\"\n");
+
+    wait_for("valid point after outlier", TIMEOUT, || {
+        serial.send("SENSOR_DATA|0|150.00|50.00");
+        serial.send("SENSOR_DATA|0|24.00|50.00");
+        let influx = &influx;
+        async move { written_lines(influx).await.into_iter().find(|l| l.contains(" temperature=24.00,")) }
+    })
+    .await;
+
+    let lines = written_lines(&influx).await;
+    assert!(!lines.iter().any(|l| l.contains("150.00")), "{lines:#?}");
+}
+
+#[tokio::test]
+async fn telemetry_is_published_to_thingsboard_with_timestamp() {
+    let influx = influx_stub().await;
+    let broker = MqttBroker::start().await;
+    let mut serial = VirtualSerial::open();
+    let _bridge = Bridge::spawn(&influx.uri(), &broker, &serial, "");
+    serial.send("SENSOR_DATA|0|25.30|65.20");
+
+    let telemetry = wait_for("telemetry on ThingsBoard broker", TIMEOUT, || {
+        let broker = &broker;
+        async move { broker.published("v1/devices/me/telemetry").into_iter().next() }
+    })
+    .await;
+
+    let ts = telemetry["ts"].as_u64().expect("ts in milliseconds");
+    let now_ms = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64;
+    assert!(now_ms.abs_diff(ts) < 60_000, "ts {ts} far from now {now_ms}");
+    let values = telemetry["values"].as_object().expect("values object");
+    assert!(values.contains_key("data_quality"), "{telemetry}");
+    assert!(!values.contains_key("shadow_mode"), "{telemetry}");
+}
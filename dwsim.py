@@ -36,10 +36,17 @@ except ImportError:
     class Observer:
         pass
 
-INFLUXDB_URL = "http://localhost:8086"
-INFLUXDB_ORG = "ITS"
-INFLUXDB_BUCKET = "DWSIM_DATA"
-INFLUXDB_TOKEN = "pFlhPKsrTfaJ6-iIKz46wwHuKPOkp8GBK_chLeWCxpTgeFryMn9feiUukWZe5DAm4ocDJUAlPlyBaw8zg9PDYQ=="
+# Koneksi InfluxDB dari environment atau file .env (lihat .env.example); token tidak disimpan di source
+try:
+    from dotenv import load_dotenv
+    load_dotenv()
+except ImportError:
+    pass
+
+INFLUXDB_URL = os.getenv("INFLUXDB_URL", "http://localhost:8086")
+INFLUXDB_ORG = os.getenv("INFLUXDB_ORG", "ITS")
+INFLUXDB_BUCKET = os.getenv("INFLUXDB_BUCKET", "DWSIM_DATA")
+INFLUXDB_TOKEN = os.getenv("INFLUXDB_TOKEN", "")
 
 # Default DWSIM XML file path
 DWSIM_XML_FILE = "/home/maulvin/Documents/SKT/dwsim.dwxmz"
@@ -622,6 +629,8 @@ class InfluxDBUploader:
     def __init__(self, url, org, bucket, token):
         if not INFLUXDB_AVAILABLE:
             raise RuntimeError("InfluxDB client not available")
+        if not token:
+            raise RuntimeError("INFLUXDB_TOKEN is not set (export it or put it in .env)")
 
         self.url = url
         self.org = org
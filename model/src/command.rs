@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+
+// AUTO = ikut logika kontrol device; ON/OFF = override manual
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RelayMode {
+    Auto,
+    On,
+    Off,
+}
+
+impl RelayMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_uppercase().as_str() {
+            "AUTO" => Some(RelayMode::Auto),
+            "ON" | "1" => Some(RelayMode::On),
+            "OFF" | "0" => Some(RelayMode::Off),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RelayMode::Auto => "AUTO",
+            RelayMode::On => "ON",
+            RelayMode::Off => "OFF",
+        }
+    }
+}
+
+impl From<bool> for RelayMode {
+    fn from(on: bool) -> Self {
+        if on { RelayMode::On } else { RelayMode::Off }
+    }
+}
+
+// Satu pasangan relay=mode pada frame RELAY|<actuator>=<mode>|...
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ActuatorCommand {
+    // Nama relay di device (exhaust_fan/fan, pump, heater); alias diselesaikan oleh penerima
+    pub actuator: String,
+    pub mode: RelayMode,
+}
+
+impl ActuatorCommand {
+    pub fn new(actuator: impl Into<String>, mode: RelayMode) -> Self {
+        Self { actuator: actuator.into(), mode }
+    }
+
+    pub fn to_frame(commands: &[ActuatorCommand]) -> String {
+        let pairs: Vec<String> = commands.iter().map(|c| format!("{}={}", c.actuator, c.mode.as_str())).collect();
+        format!("RELAY|{}", pairs.join("|"))
+    }
+
+    // Argumen setelah "RELAY|"; Err berisi alasan untuk NAK
+    pub fn parse_args(args: &str) -> Result<Vec<ActuatorCommand>, String> {
+        let commands = args
+            .split('|')
+            .filter(|part| !part.trim().is_empty())
+            .map(|part| {
+                let (actuator, mode) = part.split_once('=').ok_or_else(|| format!("expected key=value, got {part}"))?;
+                let (actuator, mode) = (actuator.trim(), mode.trim());
+                let mode = RelayMode::parse(mode).ok_or_else(|| format!("{actuator}: expected ON/OFF/AUTO"))?;
+                Ok(ActuatorCommand::new(actuator, mode))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        if commands.is_empty() {
+            return Err("no key=value pairs".to_string());
+        }
+        Ok(commands)
+    }
+}
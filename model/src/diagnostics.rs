@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+// DIAG|uptime=..|heap=..|rssi=..|resets=..|modbus_errors=..|fw=..|device=.. (setiap menit dari ESP32)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct Diagnostics {
+    pub uptime_s: u64,
+    pub free_heap: u64,
+    // None saat ESP32 di mode serial (Wi-Fi tidak tersambung)
+    pub rssi_dbm: Option<i32>,
+    // Reset tak terduga sejak flash, dihitung di NVS device
+    pub resets: u64,
+    pub modbus_errors: u64,
+    // Versi firmware dan DEVICE_ID hasil build; firmware lama tidak mengirimnya
+    pub firmware: Option<String>,
+    pub device: Option<String>,
+}
+
+impl Diagnostics {
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut diag = Diagnostics::default();
+        for part in line.strip_prefix("DIAG|")?.split('|') {
+            let (key, value) = part.split_once('=')?;
+            match key {
+                "uptime" => diag.uptime_s = value.parse().ok()?,
+                "heap" => diag.free_heap = value.parse().ok()?,
+                "rssi" => diag.rssi_dbm = value.parse().ok(),
+                "resets" => diag.resets = value.parse().ok()?,
+                "modbus_errors" => diag.modbus_errors = value.parse().ok()?,
+                "fw" if !value.is_empty() => diag.firmware = Some(value.to_string()),
+                "device" if !value.is_empty() => diag.device = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        Some(diag)
+    }
+
+    // Kebalikan parse; rssi "NA" saat tidak ada Wi-Fi
+    pub fn to_frame(&self) -> String {
+        let mut line = format!(
+            "DIAG|uptime={}|heap={}|rssi={}|resets={}|modbus_errors={}",
+            self.uptime_s,
+            self.free_heap,
+            self.rssi_dbm.map_or("NA".to_string(), |r| r.to_string()),
+            self.resets,
+            self.modbus_errors
+        );
+        if let Some(firmware) = &self.firmware {
+            line.push_str(&format!("|fw={firmware}"));
+        }
+        if let Some(device) = &self.device {
+            line.push_str(&format!("|device={device}"));
+        }
+        line
+    }
+
+    // Field numerik untuk InfluxDB/ThingsBoard
+    pub fn fields(&self) -> Vec<(&'static str, f64)> {
+        let mut fields = vec![
+            ("uptime_s", self.uptime_s as f64),
+            ("free_heap", self.free_heap as f64),
+            ("resets", self.resets as f64),
+            ("modbus_errors", self.modbus_errors as f64),
+        ];
+        if let Some(rssi) = self.rssi_dbm {
+            fields.push(("rssi_dbm", rssi as f64));
+        }
+        fields
+    }
+}
@@ -0,0 +1,150 @@
+// Model data kanonik DCS, dipakai bersama oleh backend (serial, REST API, alarm) dan firmware
+// ESP32 (encoder frame serial). Format frame teks (SENSOR_DATA, DIAG, RELAY) didefinisikan
+// di sini supaya kedua sisi tidak bisa menyimpang.
+mod alarm;
+mod command;
+mod diagnostics;
+mod sensor;
+
+pub use alarm::{Alarm, AlarmContext, Severity};
+pub use command::{ActuatorCommand, RelayMode};
+pub use diagnostics::Diagnostics;
+pub use sensor::{is_aux_field, AuxReading, SensorData};
+
+// Angka f32 yang bisa dipakai; "NaN", "inf" dan nilai di luar jangkauan f32 (mis. 1e39) ditolak
+pub fn finite(value: &str) -> Option<f32> {
+    value.parse::<f32>().ok().filter(|v| v.is_finite())
+}
+
+// Dokumen JSON schema semua tipe model (definisi bersama di "definitions")
+#[cfg(feature = "schema")]
+pub fn schema() -> serde_json::Value {
+    use std::collections::BTreeMap;
+
+    use schemars::gen::SchemaSettings;
+    use schemars::schema::Schema;
+
+    let mut generator = SchemaSettings::draft07().into_generator();
+    let types: BTreeMap<&str, Schema> = [
+        ("SensorData", generator.subschema_for::<SensorData>()),
+        ("AuxReading", generator.subschema_for::<AuxReading>()),
+        ("Diagnostics", generator.subschema_for::<Diagnostics>()),
+        ("ActuatorCommand", generator.subschema_for::<ActuatorCommand>()),
+        ("Alarm", generator.subschema_for::<Alarm>()),
+    ]
+    .into_iter()
+    .collect();
+    let definitions = generator.take_definitions();
+    serde_json::json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "dcs-model",
+        "version": env!("CARGO_PKG_VERSION"),
+        "types": types,
+        "definitions": definitions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> SensorData {
+        SensorData {
+            timestamp: 1_700_000_000_000_000_000,
+            temperature: 25.3,
+            humidity: 65.2,
+            exhaust_fan_status: None,
+            pump_status: None,
+            fan_duty: Some(60.0),
+            aux: vec![("soil_moisture".to_string(), 41.2)],
+        }
+    }
+
+    // Encoder frame dipakai firmware; hasilnya harus terbaca parser backend
+    #[test]
+    fn sensor_frame_round_trip() {
+        let data = sample();
+        let line = data.to_frame(Some(2));
+        assert_eq!(line, "SENSOR_DATA|1700000000000000000|25.30|65.20|slave=2|fan_duty=60|soil_moisture=41.2");
+        assert_eq!(SensorData::parse_frame(&line), Some((Some(2), data)));
+    }
+
+    #[test]
+    fn diagnostics_frame_round_trip() {
+        let diag = Diagnostics {
+            uptime_s: 3600,
+            free_heap: 120_000,
+            rssi_dbm: None,
+            resets: 1,
+            modbus_errors: 4,
+            firmware: Some("0.1.0".to_string()),
+            device: Some("esp32-01".to_string()),
+        };
+        assert_eq!(Diagnostics::parse(&diag.to_frame()), Some(diag));
+        let wifi = Diagnostics { rssi_dbm: Some(-61), firmware: None, device: None, ..Diagnostics::default() };
+        assert_eq!(Diagnostics::parse(&wifi.to_frame()), Some(wifi));
+    }
+
+    #[test]
+    fn relay_frame_round_trip() {
+        let line = ActuatorCommand::to_frame(&[ActuatorCommand::new("heater", true.into()), ActuatorCommand::new("pump", RelayMode::Auto)]);
+        assert_eq!(line, "RELAY|heater=ON|pump=AUTO");
+        let parsed = ActuatorCommand::parse_args(line.strip_prefix("RELAY|").unwrap()).unwrap();
+        assert_eq!(parsed, vec![ActuatorCommand::new("heater", RelayMode::On), ActuatorCommand::new("pump", RelayMode::Auto)]);
+        assert!(ActuatorCommand::parse_args("pump=MAYBE").is_err());
+        assert!(ActuatorCommand::parse_args("").is_err());
+    }
+
+    // Bentuk JSON dipakai REST API dan schema; serialize lalu deserialize harus identik
+    #[test]
+    fn json_round_trip() {
+        let data = sample();
+        assert_eq!(serde_json::from_value::<SensorData>(serde_json::to_value(&data).unwrap()).unwrap(), data);
+
+        let command = ActuatorCommand::new("exhaust_fan", RelayMode::Off);
+        let json = serde_json::to_value(&command).unwrap();
+        assert_eq!(json, serde_json::json!({"actuator": "exhaust_fan", "mode": "OFF"}));
+        assert_eq!(serde_json::from_value::<ActuatorCommand>(json).unwrap(), command);
+
+        let alarm = Alarm {
+            id: "temp_high".to_string(),
+            severity: Severity::Critical,
+            message: "Suhu tinggi".to_string(),
+            acknowledged: false,
+            context: AlarmContext::zone("lab").value(41.0),
+        };
+        let json = serde_json::to_value(&alarm).unwrap();
+        assert_eq!(json["severity"], "critical");
+        assert!(json["context"].get("threshold").is_none());
+        let back: Alarm = serde_json::from_value(json).unwrap();
+        assert_eq!((back.id, back.severity, back.context), (alarm.id, alarm.severity, alarm.context));
+    }
+
+    #[test]
+    fn finite_rejects_non_numbers() {
+        assert_eq!(finite("25.5"), Some(25.5));
+        for value in ["NaN", "inf", "-inf", "1e39", "", "abc"] {
+            assert_eq!(finite(value), None, "{value:?}");
+        }
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn schema_lists_all_types() {
+        let schema = schema();
+        for name in ["SensorData", "Diagnostics", "ActuatorCommand", "Alarm"] {
+            assert!(schema["types"].get(name).is_some(), "{name} missing from schema");
+        }
+        for name in ["RelayMode", "Severity", "AlarmContext"] {
+            assert!(schema["definitions"].get(name).is_some(), "{name} missing from definitions");
+        }
+    }
+
+    // model/schema.json harus diperbarui setiap kali model berubah
+    #[cfg(feature = "schema")]
+    #[test]
+    fn committed_schema_is_current() {
+        let committed: serde_json::Value = serde_json::from_str(include_str!("../schema.json")).unwrap();
+        assert_eq!(committed, schema(), "regenerate with `cargo run -- schema > ../model/schema.json` from backend/");
+    }
+}
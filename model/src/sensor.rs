@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+use crate::finite;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SensorData {
+    // Unix ns (atau waktu sejak boot sebelum jam ESP32 sinkron)
+    pub timestamp: u64,
+    pub temperature: f32,
+    pub humidity: f32,
+    #[serde(default)]
+    pub exhaust_fan_status: Option<bool>,
+    #[serde(default)]
+    pub pump_status: Option<bool>,
+    // Duty PWM exhaust fan (0-100 %), hanya dari firmware dengan fan PWM
+    #[serde(default)]
+    pub fan_duty: Option<f32>,
+    // Sensor tambahan dari frame extended (mis. soil_moisture, water_level), disimpan apa adanya
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aux: Vec<(String, f32)>,
+}
+
+// Nama field sensor tambahan: huruf kecil/angka/_, tidak bentrok dengan field yang diisi pipeline
+pub fn is_aux_field(name: &str) -> bool {
+    const RESERVED: [&str; 8] =
+        ["temperature", "humidity", "exhaust_fan_status", "pump_status", "vpd", "anomaly", "quality", "outlier"];
+    name.len() <= 32
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && !RESERVED.contains(&name)
+        && !name.ends_with("_filtered")
+}
+
+impl SensorData {
+    // Format: "SENSOR_DATA|timestamp|temperature|humidity[|slave=<addr>][|fan_duty=<pct>][|<aux>=<value>...]".
+    // Frame terpotong, tercampur log, atau nilai NaN/inf/overflow ditolak utuh (None).
+    pub fn parse_frame(line: &str) -> Option<(Option<u8>, SensorData)> {
+        let mut parts = line.strip_prefix("SENSOR_DATA|")?.split('|');
+        let timestamp = parts.next()?.parse::<u64>().ok()?;
+        let temperature = finite(parts.next()?)?;
+        let humidity = finite(parts.next()?)?;
+        let mut slave = None;
+        let mut fan_duty = None;
+        let mut aux = Vec::new();
+        for part in parts {
+            match part.split_once('=')? {
+                ("slave", value) => slave = Some(value.parse::<u8>().ok()?),
+                ("fan_duty", value) => fan_duty = Some(finite(value)?),
+                // Field numerik lain = sensor tambahan; nama dibatasi supaya aman sebagai field InfluxDB
+                (name, value) if is_aux_field(name) => match finite(value) {
+                    Some(value) => aux.push((name.to_string(), value)),
+                    None => log::warn!("Ignoring non-numeric aux field {}={}", name, value),
+                },
+                _ => {} // Field baru dari firmware yang lebih baru
+            }
+        }
+        Some((slave, SensorData {
+            timestamp,
+            temperature,
+            humidity,
+            exhaust_fan_status: None, // Diisi dari RELAY_STATUS
+            pump_status: None,
+            fan_duty,
+            aux,
+        }))
+    }
+
+    // Kebalikan parse_frame (tanpa trailer seq/crc, ditambahkan lapisan frame firmware)
+    pub fn to_frame(&self, slave: Option<u8>) -> String {
+        let mut line = format!("SENSOR_DATA|{}|{:.2}|{:.2}", self.timestamp, self.temperature, self.humidity);
+        if let Some(slave) = slave {
+            line.push_str(&format!("|slave={slave}"));
+        }
+        if let Some(duty) = self.fan_duty {
+            line.push_str(&format!("|fan_duty={duty:.0}"));
+        }
+        for (name, value) in &self.aux {
+            line.push_str(&format!("|{name}={value:.1}"));
+        }
+        line
+    }
+}
+
+// Bacaan slave tambahan di luar frame SENSOR_DATA (mis. probe kelembaban tanah RS485)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AuxReading {
+    // Unix ns (atau waktu sejak boot sebelum jam ESP32 sinkron)
+    pub timestamp: u64,
+    // Kelembaban tanah (%)
+    #[serde(default)]
+    pub soil_moisture: Option<f32>,
+    // Level air tandon (%)
+    #[serde(default)]
+    pub water_level: Option<f32>,
+}
+
+impl AuxReading {
+    // Format: "AUX_DATA|timestamp|soil_moisture=<pct>[|water_level=<pct>][|slave=<addr>]".
+    // Kanal lain diabaikan; frame tanpa kanal yang dikenal atau dengan nilai tidak valid ditolak (None).
+    pub fn parse_frame(line: &str) -> Option<(Option<u8>, AuxReading)> {
+        let mut parts = line.strip_prefix("AUX_DATA|")?.split('|');
+        let timestamp = parts.next()?.parse::<u64>().ok()?;
+        let mut reading = AuxReading { timestamp, soil_moisture: None, water_level: None };
+        let mut slave = None;
+        for part in parts {
+            match part.split_once('=')? {
+                ("slave", value) => slave = Some(value.parse::<u8>().ok()?),
+                ("soil_moisture", value) => reading.soil_moisture = Some(finite(value)?),
+                ("water_level", value) => reading.water_level = Some(finite(value)?),
+                _ => {} // Kanal baru dari firmware yang lebih baru
+            }
+        }
+        (!reading.fields().is_empty()).then_some((slave, reading))
+    }
+
+    // Kanal yang terisi, sebagai (nama field, nilai)
+    pub fn fields(&self) -> Vec<(&'static str, f32)> {
+        [("soil_moisture", self.soil_moisture), ("water_level", self.water_level)]
+            .into_iter()
+            .filter_map(|(name, value)| value.map(|v| (name, v)))
+            .collect()
+    }
+}
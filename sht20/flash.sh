@@ -16,7 +16,7 @@ echo "Flashing to ESP32..."
 # Wait a moment for port to be available
 sleep 2
 echo "Trying espflash..."
-if ! espflash flash --port /dev/ttyUSB0 --baud 115200 target/xtensa-esp32-espidf/debug/sht20; then
+if ! espflash flash --port /dev/ttyUSB0 --baud 115200 --partition-table partitions.csv target/xtensa-esp32-espidf/debug/sht20; then
     echo "espflash failed, trying esptool.py with larger flash size..."
     sleep 2
     # Method 2: Use esptool.py with 16MB flash size
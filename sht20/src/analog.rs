@@ -0,0 +1,97 @@
+use esp_idf_svc::hal::adc::attenuation::DB_11;
+use esp_idf_svc::hal::adc::oneshot::config::AdcChannelConfig;
+use esp_idf_svc::hal::adc::oneshot::{AdcChannelDriver, AdcDriver};
+use esp_idf_svc::hal::adc::ADC1;
+use esp_idf_svc::hal::gpio;
+use esp_idf_svc::sys::EspError;
+use std::rc::Rc;
+
+use crate::board::Board;
+
+// Sampel per pembacaan, dirata-rata untuk meredam noise ADC ESP32
+const OVERSAMPLE: u32 = 8;
+
+type ReadRaw = Box<dyn FnMut() -> Result<u16, EspError>>;
+
+// Satu input analog (ADC1, 0-3.1 V pada atenuasi 11 dB). Nilai mentah 12-bit dikirim ke
+// task kontrol; skala ke persen memakai Settings (soil_zero/soil_full, level_zero/level_full)
+struct Channel {
+    name: &'static str,
+    gpio: i32,
+    read: ReadRaw,
+}
+
+pub struct AnalogInputs {
+    channels: Vec<Channel>,
+}
+
+// Hanya pin ADC1; ADC2 tidak bisa dipakai selama Wi-Fi aktif
+fn open(adc: &Rc<AdcDriver<'static, ADC1>>, gpio: i32) -> anyhow::Result<ReadRaw> {
+    let config = AdcChannelConfig { attenuation: DB_11, ..Default::default() };
+    macro_rules! channel {
+        ($($n:literal => $pin:ident),*) => {
+            match gpio {
+                $($n => {
+                    // SAFETY: pin dari Board (pin map tervalidasi) dan tidak dipakai driver lain
+                    let mut channel = AdcChannelDriver::new(adc.clone(), unsafe { gpio::$pin::new() }, &config)?;
+                    Ok(Box::new(move || channel.read_raw()))
+                })*
+                _ => anyhow::bail!("GPIO{gpio} is not an ADC1 pin"),
+            }
+        };
+    }
+    channel!(32 => Gpio32, 33 => Gpio33, 34 => Gpio34, 35 => Gpio35, 36 => Gpio36, 37 => Gpio37, 38 => Gpio38, 39 => Gpio39)
+}
+
+impl AnalogInputs {
+    // Pin -1 = sensor itu tidak dipasang
+    pub fn start(adc1: ADC1, board: &Board) -> Self {
+        let mut channels = Vec::new();
+        let wanted = [("soil_moisture", board.soil_adc), ("water_level", board.level_adc)];
+        if wanted.iter().all(|(_, gpio)| *gpio < 0) {
+            return Self { channels };
+        }
+        let adc = match AdcDriver::new(adc1) {
+            Ok(adc) => Rc::new(adc),
+            Err(e) => {
+                log::error!("ADC1 setup failed: {e:?}");
+                return Self { channels };
+            }
+        };
+        for (name, gpio) in wanted.into_iter().filter(|(_, gpio)| *gpio >= 0) {
+            match open(&adc, gpio) {
+                Ok(read) => {
+                    log::info!("Analog input {name} on GPIO{gpio}");
+                    channels.push(Channel { name, gpio, read });
+                }
+                Err(e) => log::error!("Analog input {name} (GPIO{gpio}) setup failed: {e:?}"),
+            }
+        }
+        Self { channels }
+    }
+
+    // (nama, nilai mentah rata-rata 0-4095)
+    pub fn read(&mut self) -> Vec<(&'static str, u16)> {
+        let mut values = Vec::new();
+        for channel in self.channels.iter_mut() {
+            let mut sum = 0u32;
+            let mut ok = 0u32;
+            for _ in 0..OVERSAMPLE {
+                match (channel.read)() {
+                    Ok(raw) => {
+                        sum += raw as u32;
+                        ok += 1;
+                    }
+                    Err(e) => {
+                        log::warn!("ADC read {} (GPIO{}) failed: {e:?}", channel.name, channel.gpio);
+                        break;
+                    }
+                }
+            }
+            if ok > 0 {
+                values.push((channel.name, (sum / ok) as u16));
+            }
+        }
+        values
+    }
+}
@@ -0,0 +1,134 @@
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use std::time::{Duration, Instant};
+
+// Ring buffer sampel sensor utama di NVS, supaya data saat gateway/Wi-Fi tidak ada
+// bisa diambil lagi dengan perintah BACKLOG. Namespace sendiri, terpisah dari config.
+const NAMESPACE: &str = "backlog";
+
+// 12 halaman x 32 record, satu record per menit = ~6 jam terakhir (~6 KB NVS)
+const PAGES: u32 = 12;
+const PER_PAGE: u32 = 32;
+const RECORD_SIZE: usize = 16;
+const PAGE_BYTES: usize = PER_PAGE as usize * RECORD_SIZE;
+pub const RECORD_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Record {
+    pub timestamp: u64,
+    pub temperature: f32,
+    pub humidity: f32,
+    pub fan: bool,
+    pub pump: bool,
+}
+
+impl Record {
+    // [ts u64 LE][suhu i16 x100][kelembaban u16 x100][flag relay][padding]
+    fn encode(&self, out: &mut [u8]) {
+        out[0..8].copy_from_slice(&self.timestamp.to_le_bytes());
+        out[8..10].copy_from_slice(&((self.temperature * 100.0).round() as i16).to_le_bytes());
+        out[10..12].copy_from_slice(&((self.humidity * 100.0).round() as u16).to_le_bytes());
+        out[12] = self.fan as u8 | (self.pump as u8) << 1;
+        out[13..16].fill(0);
+    }
+
+    // Slot kosong (timestamp 0) = belum pernah ditulis
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let timestamp = u64::from_le_bytes(bytes[0..8].try_into().ok()?);
+        if timestamp == 0 {
+            return None;
+        }
+        Some(Self {
+            timestamp,
+            temperature: i16::from_le_bytes([bytes[8], bytes[9]]) as f32 / 100.0,
+            humidity: u16::from_le_bytes([bytes[10], bytes[11]]) as f32 / 100.0,
+            fan: bytes[12] & 0x01 != 0,
+            pump: bytes[12] & 0x02 != 0,
+        })
+    }
+}
+
+pub struct Backlog {
+    nvs: EspNvs<NvsDefault>,
+    // Jumlah record yang pernah ditulis; record ke-n ada di halaman (n / PER_PAGE) % PAGES
+    head: u32,
+    // Salinan halaman yang sedang diisi; ditulis utuh ke NVS setiap push
+    page: [u8; PAGE_BYTES],
+    last_push: Option<Instant>,
+}
+
+impl Backlog {
+    pub fn open(partition: EspDefaultNvsPartition) -> anyhow::Result<Self> {
+        let nvs = EspNvs::new(partition, NAMESPACE, true)?;
+        let head = nvs.get_u32("head")?.unwrap_or(0);
+        let mut backlog = Self { nvs, head, page: [0; PAGE_BYTES], last_push: None };
+        if head % PER_PAGE != 0 {
+            let key = Self::page_key(head / PER_PAGE);
+            let mut page = [0u8; PAGE_BYTES];
+            if let Ok(Some(stored)) = backlog.nvs.get_blob(&key, &mut page) {
+                backlog.page[..stored.len()].copy_from_slice(stored);
+            }
+        }
+        log::info!("Backlog: {} records buffered", backlog.len());
+        Ok(backlog)
+    }
+
+    fn page_key(page: u32) -> String {
+        format!("p{}", page % PAGES)
+    }
+
+    fn len(&self) -> u32 {
+        self.head - self.oldest()
+    }
+
+    // Halaman tertua ikut tertimpa saat halaman baru mulai diisi
+    fn oldest(&self) -> u32 {
+        let current_page = self.head / PER_PAGE;
+        current_page.saturating_sub(PAGES - 1) * PER_PAGE
+    }
+
+    // Maksimal satu record per RECORD_INTERVAL untuk menghemat siklus tulis flash
+    pub fn push(&mut self, record: Record) -> anyhow::Result<()> {
+        if self.last_push.is_some_and(|t| t.elapsed() < RECORD_INTERVAL) {
+            return Ok(());
+        }
+        let slot = (self.head % PER_PAGE) as usize;
+        if slot == 0 {
+            self.page.fill(0);
+        }
+        record.encode(&mut self.page[slot * RECORD_SIZE..(slot + 1) * RECORD_SIZE]);
+        self.nvs.set_blob(&Self::page_key(self.head / PER_PAGE), &self.page)?;
+        self.head += 1;
+        self.nvs.set_u32("head", self.head)?;
+        self.last_push = Some(Instant::now());
+        Ok(())
+    }
+
+    // Panggil `emit` untuk setiap record dengan timestamp > since, urut dari yang tertua
+    pub fn dump(&self, since: u64, mut emit: impl FnMut(&Record)) -> u32 {
+        let mut sent = 0;
+        let first_page = self.oldest() / PER_PAGE;
+        let last_page = self.head.div_ceil(PER_PAGE);
+        let mut page = [0u8; PAGE_BYTES];
+        for index in first_page..last_page {
+            let bytes: &[u8] = if index == self.head / PER_PAGE {
+                &self.page
+            } else {
+                match self.nvs.get_blob(&Self::page_key(index), &mut page) {
+                    Ok(Some(stored)) => stored,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        log::warn!("Backlog page {index} unreadable: {e:?}");
+                        continue;
+                    }
+                }
+            };
+            for record in bytes.chunks_exact(RECORD_SIZE).filter_map(Record::decode) {
+                if record.timestamp > since {
+                    emit(&record);
+                    sent += 1;
+                }
+            }
+        }
+        sent
+    }
+}
@@ -0,0 +1,53 @@
+use esp_idf_svc::sys;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::clock;
+use crate::config;
+
+// Counter di RTC slow memory: bertahan selama deep sleep, kembali 0 saat power-on/reset
+#[link_section = ".rtc.data"]
+static WAKES: AtomicU32 = AtomicU32::new(0);
+// Bangun berturut-turut tanpa pembacaan sensor valid
+#[link_section = ".rtc.data"]
+static FAILED_WAKES: AtomicU32 = AtomicU32::new(0);
+// Jam RTC tetap berjalan saat deep sleep, jadi status sinkron ikut disimpan
+#[link_section = ".rtc.data"]
+static CLOCK_SYNCED: AtomicBool = AtomicBool::new(false);
+
+pub fn enabled() -> bool {
+    config::BATTERY_MODE.eq_ignore_ascii_case("ON")
+}
+
+pub fn sleep_period() -> Duration {
+    Duration::from_secs(config::SLEEP_MINUTES * 60)
+}
+
+// Dipanggil sekali per bangun; mengembalikan nomor bangun sejak power-on (mulai 1)
+pub fn wake() -> u32 {
+    if CLOCK_SYNCED.load(Ordering::Relaxed) {
+        clock::mark_synced("rtc");
+    }
+    WAKES.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+pub fn record_result(success: bool) -> u32 {
+    if success {
+        FAILED_WAKES.store(0, Ordering::Relaxed);
+        0
+    } else {
+        FAILED_WAKES.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+// Tidur sampai siklus berikutnya; waktu bangun dikurangkan supaya interval tetap
+pub fn deep_sleep(awake_since: Instant) -> ! {
+    CLOCK_SYNCED.store(clock::is_synced(), Ordering::Relaxed);
+    let sleep = sleep_period().saturating_sub(awake_since.elapsed()).max(Duration::from_secs(1));
+    log::info!("😴 Deep sleep for {}s", sleep.as_secs());
+    // SAFETY: timer wakeup lalu deep sleep; fungsi ini tidak kembali (bangun = boot ulang)
+    unsafe {
+        sys::esp_sleep_enable_timer_wakeup(sleep.as_micros() as u64);
+        sys::esp_deep_sleep_start()
+    }
+}
@@ -0,0 +1,173 @@
+use esp_idf_svc::hal::delay::{FreeRtos, TickType};
+use esp_idf_svc::hal::i2c::I2cDriver;
+
+use crate::sensor::{Reading, SensorDriver};
+
+// Bosch BME280 di I2C, alamat 0x76 (SDO ke GND) atau 0x77
+pub const DEFAULT_ADDRESS: u8 = 0x76;
+
+const REG_CHIP_ID: u8 = 0xD0;
+const REG_CALIB_00: u8 = 0x88;
+const REG_CALIB_26: u8 = 0xE1;
+const REG_CTRL_HUM: u8 = 0xF2;
+const REG_STATUS: u8 = 0xF3;
+const REG_CTRL_MEAS: u8 = 0xF4;
+const REG_DATA: u8 = 0xF7;
+
+const CHIP_ID: u8 = 0x60;
+// Oversampling x1 untuk humidity, suhu dan tekanan; forced mode (satu konversi lalu sleep)
+const CTRL_HUM_X1: u8 = 0x01;
+const CTRL_MEAS_FORCED_X1: u8 = 0b001_001_01;
+const STATUS_MEASURING: u8 = 0x08;
+const I2C_TIMEOUT_MS: u64 = 100;
+
+// Koefisien trimming dari NVM chip (datasheet bagian 4.2.2)
+struct Calibration {
+    t1: f64,
+    t2: f64,
+    t3: f64,
+    p: [f64; 9],
+    h1: f64,
+    h2: f64,
+    h3: f64,
+    h4: f64,
+    h5: f64,
+    h6: f64,
+}
+
+impl Calibration {
+    fn parse(a: &[u8; 26], b: &[u8; 7]) -> Self {
+        let u16_at = |i: usize| u16::from_le_bytes([a[i], a[i + 1]]) as f64;
+        let i16_at = |i: usize| i16::from_le_bytes([a[i], a[i + 1]]) as f64;
+        let mut p = [0.0; 9];
+        p[0] = u16_at(6);
+        for (n, value) in p.iter_mut().enumerate().skip(1) {
+            *value = i16_at(6 + 2 * n);
+        }
+        Self {
+            t1: u16_at(0),
+            t2: i16_at(2),
+            t3: i16_at(4),
+            p,
+            h1: a[25] as f64,
+            h2: i16::from_le_bytes([b[0], b[1]]) as f64,
+            h3: b[2] as f64,
+            h4: (((b[3] as i8 as i16) << 4) | (b[4] & 0x0F) as i16) as f64,
+            h5: (((b[5] as i8 as i16) << 4) | (b[4] >> 4) as i16) as f64,
+            h6: b[6] as i8 as f64,
+        }
+    }
+
+    // Rumus kompensasi floating point dari datasheet (lampiran 8.1); hasil °C, Pa, %RH
+    fn compensate(&self, adc_t: f64, adc_p: f64, adc_h: f64) -> (f64, f64, f64) {
+        let var1 = (adc_t / 16384.0 - self.t1 / 1024.0) * self.t2;
+        let var2 = (adc_t / 131072.0 - self.t1 / 8192.0).powi(2) * self.t3;
+        let t_fine = var1 + var2;
+        let temperature = t_fine / 5120.0;
+
+        let p = &self.p;
+        let mut var1 = t_fine / 2.0 - 64000.0;
+        let mut var2 = var1 * var1 * p[5] / 32768.0;
+        var2 += var1 * p[4] * 2.0;
+        var2 = var2 / 4.0 + p[3] * 65536.0;
+        var1 = (p[2] * var1 * var1 / 524288.0 + p[1] * var1) / 524288.0;
+        var1 = (1.0 + var1 / 32768.0) * p[0];
+        let pressure = if var1 == 0.0 {
+            0.0
+        } else {
+            let mut pa = 1048576.0 - adc_p;
+            pa = (pa - var2 / 4096.0) * 6250.0 / var1;
+            let var1 = p[8] * pa * pa / 2147483648.0;
+            let var2 = pa * p[7] / 32768.0;
+            pa + (var1 + var2 + p[6]) / 16.0
+        };
+
+        let h = t_fine - 76800.0;
+        let h = (adc_h - (self.h4 * 64.0 + self.h5 / 16384.0 * h))
+            * (self.h2 / 65536.0 * (1.0 + self.h6 / 67108864.0 * h * (1.0 + self.h3 / 67108864.0 * h)));
+        let humidity = (h * (1.0 - self.h1 * h / 524288.0)).clamp(0.0, 100.0);
+
+        (temperature, pressure, humidity)
+    }
+}
+
+pub struct Bme280 {
+    i2c: I2cDriver<'static>,
+    address: u8,
+    // Dibaca saat read() pertama yang berhasil, supaya sensor yang belum terpasang saat
+    // boot tetap terlihat sebagai SENSOR_FAULT dan pulih sendiri
+    calibration: Option<Calibration>,
+}
+
+impl Bme280 {
+    pub fn new(i2c: I2cDriver<'static>, address: u8) -> Self {
+        Self { i2c, address, calibration: None }
+    }
+
+    fn read_registers(&mut self, register: u8, buf: &mut [u8]) -> Result<(), String> {
+        let timeout = TickType::new_millis(I2C_TIMEOUT_MS).ticks();
+        self.i2c.write_read(self.address, &[register], buf, timeout).map_err(|_| "no_response".to_string())
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) -> Result<(), String> {
+        let timeout = TickType::new_millis(I2C_TIMEOUT_MS).ticks();
+        self.i2c.write(self.address, &[register, value], timeout).map_err(|_| "no_response".to_string())
+    }
+
+    fn calibrate(&mut self) -> Result<(), String> {
+        let mut id = [0u8; 1];
+        self.read_registers(REG_CHIP_ID, &mut id)?;
+        if id[0] != CHIP_ID {
+            log::warn!("BME280 chip id 0x{:02X} (expected 0x{CHIP_ID:02X})", id[0]);
+            return Err("unexpected_reply".to_string());
+        }
+        let mut a = [0u8; 26];
+        let mut b = [0u8; 7];
+        self.read_registers(REG_CALIB_00, &mut a)?;
+        self.read_registers(REG_CALIB_26, &mut b)?;
+        self.calibration = Some(Calibration::parse(&a, &b));
+        log::info!("BME280 calibration loaded");
+        Ok(())
+    }
+}
+
+impl SensorDriver for Bme280 {
+    fn name(&self) -> &'static str {
+        "bme280"
+    }
+
+    fn read(&mut self) -> Result<Reading, String> {
+        if self.calibration.is_none() {
+            self.calibrate()?;
+        }
+        // ctrl_hum baru berlaku setelah ctrl_meas ditulis
+        self.write_register(REG_CTRL_HUM, CTRL_HUM_X1)?;
+        self.write_register(REG_CTRL_MEAS, CTRL_MEAS_FORCED_X1)?;
+
+        let mut status = [STATUS_MEASURING];
+        for _ in 0..10 {
+            FreeRtos::delay_ms(5);
+            self.read_registers(REG_STATUS, &mut status)?;
+            if status[0] & STATUS_MEASURING == 0 {
+                break;
+            }
+        }
+        if status[0] & STATUS_MEASURING != 0 {
+            return Err("timeout".to_string());
+        }
+
+        // press[19:0] temp[19:0] hum[15:0], big endian dengan 4 bit xlsb
+        let mut d = [0u8; 8];
+        self.read_registers(REG_DATA, &mut d)?;
+        let adc_p = ((d[0] as u32) << 12 | (d[1] as u32) << 4 | (d[2] as u32) >> 4) as f64;
+        let adc_t = ((d[3] as u32) << 12 | (d[4] as u32) << 4 | (d[5] as u32) >> 4) as f64;
+        let adc_h = u16::from_be_bytes([d[6], d[7]]) as f64;
+
+        let Some(calibration) = self.calibration.as_ref() else {
+            return Err("unexpected_reply".to_string());
+        };
+        let (temperature, pressure, humidity) = calibration.compensate(adc_t, adc_p, adc_h);
+        log::info!("BME280 pressure: {:.1} hPa", pressure / 100.0);
+        Ok(Reading::Climate { temperature: temperature as f32, humidity: humidity as f32 })
+    }
+}
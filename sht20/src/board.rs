@@ -0,0 +1,167 @@
+use crate::config;
+
+// Pemetaan pin + fitur carrier board. Default dari config.rs, key yang disimpan di NVS
+// (perintah BOARD) menimpanya saat boot sehingga satu image jalan di semua board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Board {
+    pub fan: i32,
+    pub pump: i32,
+    pub rs485_tx: i32,
+    pub rs485_rx: i32,
+    pub dht: i32,
+    pub i2c_sda: i32,
+    pub i2c_scl: i32,
+    pub led_tx: i32,
+    pub led_rx: i32,
+    pub oled_sda: i32,
+    pub oled_scl: i32,
+    pub btn_pump: i32,
+    pub btn_fan: i32,
+    pub soil_adc: i32,
+    pub level_adc: i32,
+    pub plc_tx: i32,
+    pub plc_rx: i32,
+    pub relay_low: bool,
+    pub leds: bool,
+    pub display: bool,
+    pub buttons: bool,
+    pub plc: bool,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self {
+            fan: config::PIN_FAN,
+            pump: config::PIN_PUMP,
+            rs485_tx: config::PIN_RS485_TX,
+            rs485_rx: config::PIN_RS485_RX,
+            dht: config::PIN_DHT,
+            i2c_sda: config::PIN_I2C_SDA,
+            i2c_scl: config::PIN_I2C_SCL,
+            led_tx: config::PIN_LED_TX,
+            led_rx: config::PIN_LED_RX,
+            oled_sda: config::PIN_OLED_SDA,
+            oled_scl: config::PIN_OLED_SCL,
+            btn_pump: config::PIN_BUTTON_PUMP,
+            btn_fan: config::PIN_BUTTON_FAN,
+            soil_adc: config::PIN_SOIL_ADC,
+            level_adc: config::PIN_LEVEL_ADC,
+            plc_tx: config::PIN_PLC_TX,
+            plc_rx: config::PIN_PLC_RX,
+            relay_low: config::RELAY_ACTIVE_LOW,
+            leds: config::ENABLE_LEDS,
+            display: config::ENABLE_DISPLAY,
+            buttons: config::ENABLE_BUTTONS,
+            plc: config::ENABLE_PLC,
+        }
+    }
+}
+
+// GPIO6-11 terhubung ke flash SPI; GPIO34-39 hanya input
+fn check_pin(key: &str, gpio: i32, output: bool) -> Result<(), String> {
+    let max = if output { 33 } else { 39 };
+    if !(0..=max).contains(&gpio) || (6..=11).contains(&gpio) {
+        let kind = if output { "output" } else { "input" };
+        return Err(format!("{key}: GPIO{gpio} is not a usable {kind} pin"));
+    }
+    Ok(())
+}
+
+impl Board {
+    pub const KEYS: [&'static str; 22] = [
+        "fan", "pump", "rs485_tx", "rs485_rx", "dht", "i2c_sda", "i2c_scl", "led_tx", "led_rx", "oled_sda", "oled_scl",
+        "btn_pump", "btn_fan", "soil_adc", "level_adc", "plc_tx", "plc_rx", "relay_low", "leds", "display", "buttons", "plc",
+    ];
+
+    // Flag disimpan sebagai 0/1, pin sebagai nomor GPIO
+    pub fn set(&mut self, key: &str, value: i32) -> Result<(), String> {
+        let flag = |value: i32| match value {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(format!("{key} must be 0 or 1")),
+        };
+        match key {
+            "fan" | "pump" | "rs485_tx" | "dht" | "i2c_sda" | "i2c_scl" | "led_tx" | "led_rx" | "oled_sda" | "oled_scl"
+            | "plc_tx" => check_pin(key, value, true)?,
+            "rs485_rx" | "plc_rx" => check_pin(key, value, false)?,
+            "btn_pump" | "btn_fan" if value != -1 => check_pin(key, value, false)?,
+            // ADC2 tidak bisa dipakai selama Wi-Fi aktif, jadi hanya ADC1 (GPIO32-39)
+            "soil_adc" | "level_adc" if value != -1 && !(32..=39).contains(&value) => {
+                return Err(format!("{key}: GPIO{value} is not an ADC1 pin (32-39)"))
+            }
+            _ => {}
+        }
+        match key {
+            "fan" => self.fan = value,
+            "pump" => self.pump = value,
+            "rs485_tx" => self.rs485_tx = value,
+            "rs485_rx" => self.rs485_rx = value,
+            "dht" => self.dht = value,
+            "i2c_sda" => self.i2c_sda = value,
+            "i2c_scl" => self.i2c_scl = value,
+            "led_tx" => self.led_tx = value,
+            "led_rx" => self.led_rx = value,
+            "oled_sda" => self.oled_sda = value,
+            "oled_scl" => self.oled_scl = value,
+            "btn_pump" => self.btn_pump = value,
+            "btn_fan" => self.btn_fan = value,
+            "soil_adc" => self.soil_adc = value,
+            "level_adc" => self.level_adc = value,
+            "plc_tx" => self.plc_tx = value,
+            "plc_rx" => self.plc_rx = value,
+            "relay_low" => self.relay_low = flag(value)?,
+            "leds" => self.leds = flag(value)?,
+            "display" => self.display = flag(value)?,
+            "buttons" => self.buttons = flag(value)?,
+            "plc" => self.plc = flag(value)?,
+            _ => return Err(format!("unknown key {key}")),
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<i32> {
+        match key {
+            "fan" => Some(self.fan),
+            "pump" => Some(self.pump),
+            "rs485_tx" => Some(self.rs485_tx),
+            "rs485_rx" => Some(self.rs485_rx),
+            "dht" => Some(self.dht),
+            "i2c_sda" => Some(self.i2c_sda),
+            "i2c_scl" => Some(self.i2c_scl),
+            "led_tx" => Some(self.led_tx),
+            "led_rx" => Some(self.led_rx),
+            "oled_sda" => Some(self.oled_sda),
+            "oled_scl" => Some(self.oled_scl),
+            "btn_pump" => Some(self.btn_pump),
+            "btn_fan" => Some(self.btn_fan),
+            "soil_adc" => Some(self.soil_adc),
+            "level_adc" => Some(self.level_adc),
+            "plc_tx" => Some(self.plc_tx),
+            "plc_rx" => Some(self.plc_rx),
+            "relay_low" => Some(self.relay_low as i32),
+            "leds" => Some(self.leds as i32),
+            "display" => Some(self.display as i32),
+            "buttons" => Some(self.buttons as i32),
+            "plc" => Some(self.plc as i32),
+            _ => None,
+        }
+    }
+
+    // Relay dan fan tidak boleh berbagi pin dengan apa pun
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, gpio) in [("fan", self.fan), ("pump", self.pump)] {
+            let clash = Self::KEYS
+                .iter()
+                .filter(|key| **key != name && !matches!(**key, "relay_low" | "leds" | "display" | "buttons" | "plc"))
+                .find(|key| self.get(key) == Some(gpio));
+            if let Some(other) = clash {
+                return Err(format!("{name} and {other} both use GPIO{gpio}"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn describe(&self) -> String {
+        Self::KEYS.iter().map(|key| format!("{key}={}", self.get(key).unwrap_or_default())).collect::<Vec<_>>().join("|")
+    }
+}
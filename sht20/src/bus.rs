@@ -0,0 +1,124 @@
+use esp_idf_svc::hal::uart::UartDriver;
+use std::rc::Rc;
+
+use crate::config;
+use crate::modbus;
+use crate::sensor::{Reading, SensorDriver};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlaveKind {
+    // Transmitter SHT20: input register 0x0001 suhu, 0x0002 kelembaban (x0.1)
+    Sht20,
+    // Probe kelembaban tanah: holding register 0x0000 (x0.1 %)
+    SoilMoisture,
+}
+
+impl SlaveKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SlaveKind::Sht20 => "sht20",
+            SlaveKind::SoilMoisture => "soil",
+        }
+    }
+}
+
+// Satu slave di bus RS485; semua slave berbagi UART1
+pub struct ModbusSensor {
+    uart: Rc<UartDriver<'static>>,
+    pub address: u8,
+    pub kind: SlaveKind,
+}
+
+impl ModbusSensor {
+    fn new(uart: &Rc<UartDriver<'static>>, address: u8, kind: SlaveKind) -> Self {
+        Self { uart: uart.clone(), address, kind }
+    }
+}
+
+impl SensorDriver for ModbusSensor {
+    fn name(&self) -> &'static str {
+        self.kind.as_str()
+    }
+
+    fn slave(&self) -> u8 {
+        self.address
+    }
+
+    fn read(&mut self) -> Result<Reading, String> {
+        match self.kind {
+            SlaveKind::Sht20 => {
+                let reading = modbus::read_sht20(&self.uart, self.address);
+                match (reading.temperature, reading.humidity) {
+                    (Some(t), Some(h)) => Ok(Reading::Climate { temperature: t as f32 / 10.0, humidity: h as f32 / 10.0 }),
+                    (t, h) => {
+                        log::warn!(
+                            "[{}] T raw: {}, H raw: {} - incomplete data",
+                            self.address,
+                            t.map_or("N/A".to_string(), |v| v.to_string()),
+                            h.map_or("N/A".to_string(), |v| v.to_string())
+                        );
+                        Err(reading.error.map_or("unexpected_reply".to_string(), |e| e.code()))
+                    }
+                }
+            }
+            SlaveKind::SoilMoisture => match modbus::read_holding_registers(&self.uart, self.address, 0x0000, 1) {
+                Ok(values) => Ok(Reading::Soil { moisture: values[0] as f32 / 10.0 }),
+                Err(e) => Err(e.code()),
+            },
+        }
+    }
+}
+
+// Daftar slave dari config.rs; dengan SCAN_ON_BOOT hanya yang menjawab yang dipoll,
+// dan alamat lain di SCAN_RANGE yang menjawab sebagai SHT20 ikut ditambahkan
+pub fn discover(uart: &Rc<UartDriver<'static>>) -> Vec<ModbusSensor> {
+    let configured = config::SLAVES.iter().map(|(address, kind)| ModbusSensor::new(uart, *address, *kind));
+    if !config::SCAN_ON_BOOT {
+        return configured.collect();
+    }
+
+    log::info!("🔍 Scanning RS485 addresses {:?}...", config::SCAN_RANGE);
+    let mut slaves: Vec<ModbusSensor> = Vec::new();
+    let mut missing = Vec::new();
+    for slave in configured {
+        let function = match slave.kind {
+            SlaveKind::Sht20 => modbus::READ_INPUT_REGISTERS,
+            SlaveKind::SoilMoisture => modbus::READ_HOLDING_REGISTERS,
+        };
+        if modbus::probe(uart, slave.address, function, 0x0000) {
+            slaves.push(slave);
+        } else {
+            missing.push(slave.address);
+        }
+    }
+    for address in config::SCAN_RANGE {
+        if config::SLAVES.iter().any(|(a, _)| *a == address) {
+            continue;
+        }
+        if modbus::probe(uart, address, modbus::READ_INPUT_REGISTERS, modbus::REG_TEMPERATURE) {
+            log::info!("🔍 Unlisted slave at {address} - polling as SHT20");
+            slaves.push(ModbusSensor::new(uart, address, SlaveKind::Sht20));
+        }
+    }
+
+    let found: Vec<String> = slaves.iter().map(|s| format!("{}:{}", s.address, s.kind.as_str())).collect();
+    let missing: Vec<String> = missing.iter().map(u8::to_string).collect();
+    println!("BUS_SCAN|slaves={}|missing={}", found.join(","), missing.join(","));
+
+    // Tidak ada yang menjawab (mis. transmitter belum dinyalakan): tetap poll daftar config
+    // supaya SENSOR_FAULT terus terlihat di backend
+    if slaves.is_empty() {
+        return config::SLAVES.iter().map(|(address, kind)| ModbusSensor::new(uart, *address, *kind)).collect();
+    }
+    slaves
+}
+
+// Perintah konsol `modbus scan`: probe seluruh SCAN_RANGE, daftar slave yang dipoll tidak berubah
+pub fn scan(uart: &Rc<UartDriver<'static>>) {
+    log::info!("🔍 Scanning RS485 addresses {:?}...", config::SCAN_RANGE);
+    let responding: Vec<String> = config::SCAN_RANGE
+        .filter(|address| modbus::probe(uart, *address, modbus::READ_INPUT_REGISTERS, modbus::REG_TEMPERATURE))
+        .map(|address| address.to_string())
+        .collect();
+    println!("MODBUS_SCAN|responding={}", responding.join(","));
+}
@@ -0,0 +1,97 @@
+use esp_idf_svc::hal::gpio::{AnyIOPin, Input, PinDriver, Pull};
+use std::time::{Duration, Instant};
+
+use crate::board::Board;
+use crate::control::Relay;
+
+// Level harus stabil selama ini sebelum dianggap berubah (bouncing kontak mekanik)
+const DEBOUNCE: Duration = Duration::from_millis(30);
+// Tahan selama ini = kembali ke AUTO
+const HOLD: Duration = Duration::from_secs(2);
+// Interval polling saat menunggu perintah
+pub const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Press {
+    // Dilepas sebelum HOLD: toggle ON/OFF
+    Short,
+    // Ditahan HOLD: kembali ke AUTO (langsung, tanpa menunggu dilepas)
+    Hold,
+}
+
+struct Button {
+    relay: Relay,
+    pin: PinDriver<'static, AnyIOPin, Input>,
+    // Level mentah terakhir dan sejak kapan
+    raw: bool,
+    raw_since: Instant,
+    // Level setelah debounce
+    pressed: bool,
+    pressed_at: Instant,
+    hold_sent: bool,
+}
+
+impl Button {
+    fn new(relay: Relay, gpio: i32) -> anyhow::Result<Self> {
+        // SAFETY: pin dari Board (pin map tervalidasi) dan tidak dipakai driver lain
+        let mut pin = PinDriver::input(unsafe { AnyIOPin::new(gpio) })?;
+        pin.set_pull(Pull::Up)?;
+        let now = Instant::now();
+        Ok(Self { relay, pin, raw: false, raw_since: now, pressed: false, pressed_at: now, hold_sent: false })
+    }
+
+    fn poll(&mut self) -> Option<Press> {
+        let now = Instant::now();
+        let raw = self.pin.is_low();
+        if raw != self.raw {
+            self.raw = raw;
+            self.raw_since = now;
+        }
+        if raw != self.pressed && now - self.raw_since >= DEBOUNCE {
+            self.pressed = raw;
+            if raw {
+                self.pressed_at = now;
+                self.hold_sent = false;
+            } else if !self.hold_sent {
+                return Some(Press::Short);
+            }
+        }
+        if self.pressed && !self.hold_sent && now - self.pressed_at >= HOLD {
+            self.hold_sent = true;
+            return Some(Press::Hold);
+        }
+        None
+    }
+}
+
+pub struct Buttons {
+    buttons: Vec<Button>,
+}
+
+impl Buttons {
+    // Pin -1 = tombol itu tidak dipasang
+    pub fn start(board: &Board) -> Self {
+        let mut buttons = Vec::new();
+        for (relay, gpio) in [(Relay::Pump, board.btn_pump), (Relay::Fan, board.btn_fan)] {
+            if !board.buttons || gpio < 0 {
+                continue;
+            }
+            match Button::new(relay, gpio) {
+                Ok(button) => {
+                    log::info!("Override button {} on GPIO{gpio}", relay.as_str());
+                    buttons.push(button);
+                }
+                Err(e) => log::error!("Button {} (GPIO{gpio}) setup failed: {e:?}", relay.as_str()),
+            }
+        }
+        Self { buttons }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buttons.is_empty()
+    }
+
+    pub fn poll(&mut self) -> Vec<(Relay, Press)> {
+        self.buttons.iter_mut().filter_map(|button| button.poll().map(|press| (button.relay, press))).collect()
+    }
+}
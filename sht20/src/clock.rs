@@ -0,0 +1,39 @@
+use esp_idf_svc::sys::{settimeofday, timeval};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Unix time 2020-01-01 (ns); di bawah ini jam ESP32 masih hitungan sejak boot
+const PLAUSIBLE_EPOCH_NS: u64 = 1_577_836_800_000_000_000;
+
+// true setelah jam disetel lewat SNTP atau baris TIME|<unix_ns> dari backend
+static SYNCED: AtomicBool = AtomicBool::new(false);
+
+pub fn now_ns() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64
+}
+
+pub fn is_synced() -> bool {
+    SYNCED.load(Ordering::Relaxed)
+}
+
+pub fn mark_synced(source: &str) {
+    if !SYNCED.swap(true, Ordering::Relaxed) {
+        log::info!("🕒 Clock synchronized via {source}");
+    }
+}
+
+pub fn set_unix_ns(unix_ns: u64) -> Result<(), String> {
+    if unix_ns < PLAUSIBLE_EPOCH_NS {
+        return Err(format!("implausible time {unix_ns}"));
+    }
+    let tv = timeval {
+        tv_sec: (unix_ns / 1_000_000_000) as _,
+        tv_usec: ((unix_ns % 1_000_000_000) / 1_000) as _,
+    };
+    // SAFETY: tv valid selama pemanggilan, timezone tidak dipakai
+    let rc = unsafe { settimeofday(&tv, std::ptr::null()) };
+    if rc != 0 {
+        return Err(format!("settimeofday failed ({rc})"));
+    }
+    Ok(())
+}
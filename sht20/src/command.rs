@@ -0,0 +1,336 @@
+use esp_idf_svc::hal::delay::BLOCK;
+use esp_idf_svc::hal::gpio;
+use esp_idf_svc::hal::uart::{UartConfig, UartDriver, UART0};
+use std::sync::mpsc::Sender;
+use std::thread;
+use dcs_model::ActuatorCommand;
+
+use crate::board::Board;
+use crate::clock;
+use crate::config;
+use crate::control::{Controller, Override, Relay, Settings};
+use crate::frame;
+use crate::network::Uplink;
+
+// Perintah dari backend (atau terminal) lewat UART0, satu per baris:
+//   SET|temp_on=30.0|temp_off=25.0|hum_on=40|hum_off=60|temp_offset=-1.2|hum_offset=-6.5
+//   SET|fan_min_on=60|fan_min_off=60|pump_min_on=30|pump_min_off=30   (waktu tahan relay AUTO, detik)
+//   SET|confirm_samples=3        (pembacaan berturut-turut sebelum relay AUTO berganti)
+//   RELAY|pump=ON|fan=AUTO
+//   FAN|60                      (duty exhaust fan 0-100 %, FAN|AUTO = kontrol suhu)
+//   UPLINK|mqtt
+//   OTA|http://host/sht20.bin   (tanpa URL = config::OTA_URL)
+//   TIME|<unix_ns>              (sinkronisasi jam dari backend)
+//   CONFIG_DUMP
+//   BACKLOG|<since_ns>          (kirim ulang sampel dari buffer flash, tanpa since = semua)
+//   BOARD|pump=5|relay_low=1    (pin map carrier board, berlaku setelah reboot; BOARD saja = tampilkan)
+//   HELLO                       (handshake saat port dibuka, dibalas HELLO|fw=<versi>|proto=<n>|device=<DEVICE_ID>)
+// Perintah lain dibalas ACK|... atau NAK|...|alasan.
+// Backend memberi prefix id pada perintah yang harus dikonfirmasi (protokol 3), balasan membawa
+// prefix yang sama supaya backend bisa mencocokkan, timeout dan retry:
+//   #42|RELAY|pump=ON  ->  #42|ACK|RELAY|pump=ON
+//
+// Untuk teknisi di terminal serial ada juga bentuk konsol (tanpa '|', dipisah spasi), lihat CONSOLE_HELP.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Set(Vec<(String, f32)>),
+    Relay(Vec<(Relay, Override)>),
+    FanDuty(Option<u8>),
+    Uplink(Uplink),
+    Ota(String),
+    Time(u64),
+    ConfigDump,
+    Backlog(u64),
+    Board(Vec<(String, i32)>),
+    Hello,
+    // Perintah dengan prefix #<id>| dari backend
+    Tagged(u32, Box<Command>),
+    // Perintah konsol diagnostik
+    Status,
+    Read,
+    ModbusScan,
+    Help,
+}
+
+const CONSOLE_HELP: &[&str] = &[
+    "help                       daftar perintah konsol",
+    "status                     pembacaan terakhir, relay, mode, uplink, uptime",
+    "read                       baca semua sensor sekarang (READ|...)",
+    "relay <fan|pump> <on|off|auto>",
+    "fan <0-100|auto>           duty exhaust fan",
+    "cal <temp|hum> <offset>    offset kalibrasi, disimpan di NVS",
+    "set <key> <value>          sama dengan SET|key=value",
+    "modbus scan                cari slave RS485 yang menjawab",
+    "uplink <influx|mqtt>",
+    "config                     sama dengan CONFIG_DUMP",
+];
+
+const MAX_LINE: usize = 256;
+
+impl Command {
+    // (id, perintah) untuk perintah ber-id; perintah biasa id None
+    pub fn untag(self) -> (Option<u32>, Command) {
+        match self {
+            Command::Tagged(id, command) => (Some(id), *command),
+            command => (None, command),
+        }
+    }
+}
+
+// "#<id>|<perintah>" -> (id, perintah)
+fn split_id(line: &str) -> Option<(u32, &str)> {
+    let (id, rest) = line.strip_prefix('#')?.split_once('|')?;
+    Some((id.parse().ok()?, rest))
+}
+
+// Balasan ACK/NAK dengan prefix id perintah asalnya
+pub fn tag(id: Option<u32>, reply: &str) -> String {
+    match id {
+        Some(id) => format!("#{id}|{reply}"),
+        None => reply.to_string(),
+    }
+}
+
+fn pairs(args: &str) -> Result<Vec<(&str, &str)>, String> {
+    let pairs: Vec<(&str, &str)> = args
+        .split('|')
+        .filter(|part| !part.trim().is_empty())
+        .map(|part| {
+            let (key, value) = part.split_once('=').ok_or_else(|| format!("expected key=value, got {part}"))?;
+            Ok((key.trim(), value.trim()))
+        })
+        .collect::<Result<_, String>>()?;
+    if pairs.is_empty() {
+        return Err("no key=value pairs".to_string());
+    }
+    Ok(pairs)
+}
+
+// Bentuk konsol: `relay pump on`, `cal temp -1.2`, ...; None = bukan kata kerja konsol
+fn parse_console(line: &str) -> Option<Result<Command, String>> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let verb = words.first()?.to_ascii_lowercase();
+    let command = match (verb.as_str(), &words[1..]) {
+        ("help" | "?", _) => Ok(Command::Help),
+        ("status", []) => Ok(Command::Status),
+        ("read", []) => Ok(Command::Read),
+        ("config", []) => Ok(Command::ConfigDump),
+        ("modbus", [sub]) if sub.eq_ignore_ascii_case("scan") => Ok(Command::ModbusScan),
+        ("relay", [relay, mode]) => parse(&format!("RELAY|{relay}={mode}")),
+        ("fan", [duty]) => parse(&format!("FAN|{duty}")),
+        ("cal", [field, offset]) => match field.to_ascii_lowercase().as_str() {
+            "temp" | "temperature" => parse(&format!("SET|temp_offset={offset}")),
+            "hum" | "humidity" => parse(&format!("SET|hum_offset={offset}")),
+            other => Err(format!("unknown field {other}, expected temp or hum")),
+        },
+        ("set", [key, value]) => parse(&format!("SET|{key}={value}")),
+        ("uplink", [uplink]) => parse(&format!("UPLINK|{uplink}")),
+        ("status" | "read" | "config" | "modbus" | "relay" | "fan" | "cal" | "set" | "uplink", _) => {
+            Err("invalid arguments, try: help".to_string())
+        }
+        _ => return None,
+    };
+    Some(command)
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    let line = line.trim();
+    if let Some((id, rest)) = split_id(line) {
+        return parse(rest).map(|command| Command::Tagged(id, Box::new(command)));
+    }
+    if !line.contains('|') {
+        if let Some(command) = parse_console(line) {
+            return command;
+        }
+    }
+    let (name, args) = line.split_once('|').unwrap_or((line, ""));
+    match name.to_ascii_uppercase().as_str() {
+        "SET" => pairs(args)?
+            .into_iter()
+            .map(|(key, value)| {
+                value.parse::<f32>().map(|v| (key.to_string(), v)).map_err(|_| format!("{key}: invalid number {value}"))
+            })
+            .collect::<Result<_, _>>()
+            .map(Command::Set),
+        "RELAY" => ActuatorCommand::parse_args(args)?
+            .into_iter()
+            .map(|command| {
+                let relay = Relay::parse(&command.actuator).ok_or_else(|| format!("unknown relay {}", command.actuator))?;
+                Ok((relay, Override::from(command.mode)))
+            })
+            .collect::<Result<_, String>>()
+            .map(Command::Relay),
+        "FAN" => match args.trim() {
+            mode if mode.eq_ignore_ascii_case("AUTO") => Ok(Command::FanDuty(None)),
+            duty => match duty.trim_end_matches('%').parse::<u8>() {
+                Ok(duty) if duty <= 100 => Ok(Command::FanDuty(Some(duty))),
+                _ => Err(format!("expected 0-100 or AUTO, got {duty}")),
+            },
+        },
+        "UPLINK" => Uplink::parse(args).map(Command::Uplink).ok_or_else(|| "expected influx or mqtt".to_string()),
+        "OTA" => {
+            let url = if args.trim().is_empty() { config::OTA_URL } else { args.trim() };
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!("invalid url {url}"));
+            }
+            Ok(Command::Ota(url.to_string()))
+        }
+        "TIME" => args.trim().parse().map(Command::Time).map_err(|_| format!("invalid unix_ns {}", args.trim())),
+        "CONFIG_DUMP" => Ok(Command::ConfigDump),
+        "HELLO" => Ok(Command::Hello),
+        "BACKLOG" => match args.trim() {
+            "" => Ok(Command::Backlog(0)),
+            since => since.parse().map(Command::Backlog).map_err(|_| format!("invalid since_ns {since}")),
+        },
+        "BOARD" if args.trim().is_empty() => Ok(Command::Board(Vec::new())),
+        "BOARD" => pairs(args)?
+            .into_iter()
+            .map(|(key, value)| {
+                value.parse::<i32>().map(|v| (key.to_string(), v)).map_err(|_| format!("{key}: invalid number {value}"))
+            })
+            .collect::<Result<_, _>>()
+            .map(Command::Board),
+        "" => Err("empty command".to_string()),
+        other => Err(format!("unknown command {other}")),
+    }
+}
+
+// Terapkan perintah; SET dan BOARD bersifat atomik (semua key valid atau tidak ada yang berubah).
+// `board` = pin map yang tersimpan (bukan yang sedang aktif sampai reboot).
+// Ok = baris ACK/CONFIG_DUMP, Err = baris NAK
+pub fn apply(command: &Command, controller: &mut Controller, uplink: &mut Uplink, board: &mut Board) -> Result<String, String> {
+    match command {
+        Command::Set(values) => {
+            let mut settings = controller.settings;
+            for (key, value) in values {
+                settings.set(key, *value).map_err(|e| format!("NAK|SET|{e}"))?;
+            }
+            settings.validate().map_err(|e| format!("NAK|SET|{e}"))?;
+            controller.settings = settings;
+            let applied: Vec<String> = values
+                .iter()
+                .map(|(key, _)| format!("{key}={:.2}", settings.get(key).unwrap_or_default()))
+                .collect();
+            Ok(format!("ACK|SET|{}", applied.join("|")))
+        }
+        Command::Relay(relays) => {
+            for (relay, mode) in relays {
+                controller.set_override(*relay, *mode);
+            }
+            let applied: Vec<String> = relays.iter().map(|(relay, mode)| format!("{}={}", relay.as_str(), mode.as_str())).collect();
+            Ok(format!("ACK|RELAY|{}", applied.join("|")))
+        }
+        Command::FanDuty(duty) => {
+            match duty {
+                Some(duty) => controller.set_fan_duty(*duty),
+                None => controller.set_override(Relay::Fan, Override::Auto),
+            }
+            Ok(format!("ACK|FAN|{}|duty={}", controller.override_mode(Relay::Fan).as_str(), controller.fan_duty()))
+        }
+        Command::Uplink(selected) => {
+            *uplink = *selected;
+            Ok(format!("ACK|UPLINK|{}", uplink.as_str()))
+        }
+        Command::Ota(url) => Ok(format!("ACK|OTA|{url}")),
+        Command::Time(unix_ns) => {
+            clock::set_unix_ns(*unix_ns).map_err(|e| format!("NAK|TIME|{e}"))?;
+            clock::mark_synced("serial");
+            Ok(format!("ACK|TIME|{unix_ns}"))
+        }
+        Command::Backlog(since) => Ok(format!("ACK|BACKLOG|since={since}")),
+        Command::Board(values) if values.is_empty() => Ok(format!("ACK|BOARD|{}", board.describe())),
+        Command::Board(values) => {
+            let mut updated = *board;
+            for (key, value) in values {
+                updated.set(key, *value).map_err(|e| format!("NAK|BOARD|{e}"))?;
+            }
+            updated.validate().map_err(|e| format!("NAK|BOARD|{e}"))?;
+            *board = updated;
+            Ok(format!("ACK|BOARD|{}|reboot=required", board.describe()))
+        }
+        Command::ConfigDump => {
+            let mut fields: Vec<String> = Settings::KEYS
+                .iter()
+                .map(|key| format!("{key}={:.2}", controller.settings.get(key).unwrap_or_default()))
+                .collect();
+            for relay in [Relay::Fan, Relay::Pump] {
+                fields.push(format!("{}={}", relay.as_str(), controller.override_mode(relay).as_str()));
+            }
+            fields.push(format!("fan_duty={}", controller.fan_duty()));
+            fields.push(format!("uplink={}", uplink.as_str()));
+            Ok(format!("CONFIG_DUMP|{}", fields.join("|")))
+        }
+        Command::Hello => Ok(format!(
+            "HELLO|fw={}|proto={}|device={}",
+            env!("CARGO_PKG_VERSION"),
+            frame::PROTOCOL_VERSION,
+            config::DEVICE_ID
+        )),
+        Command::Help => Ok(CONSOLE_HELP.join("\n")),
+        Command::Tagged(id, command) => apply(command, controller, uplink, board)
+            .map(|reply| tag(Some(*id), &reply))
+            .map_err(|nak| tag(Some(*id), &nak)),
+        // Dijawab oleh task kontrol/sensor (butuh pembacaan terakhir dan bus sensor)
+        Command::Status => Ok("ACK|STATUS".to_string()),
+        Command::Read => Ok("ACK|READ".to_string()),
+        Command::ModbusScan => Ok("ACK|MODBUS_SCAN".to_string()),
+    }
+}
+
+// Baca baris dari UART0 (USB serial yang sama dengan output SENSOR_DATA) di thread sendiri
+pub fn spawn_reader(
+    uart0: UART0,
+    tx_pin: gpio::Gpio1,
+    rx_pin: gpio::Gpio3,
+    commands: Sender<Command>,
+) -> anyhow::Result<()> {
+    let config = UartConfig::new().baudrate(115200.into());
+    let uart = UartDriver::new(
+        uart0,
+        tx_pin,
+        rx_pin,
+        Option::<gpio::Gpio0>::None,
+        Option::<gpio::Gpio0>::None,
+        &config,
+    )?;
+
+    thread::Builder::new().name("uart0-cmd".into()).stack_size(4096).spawn(move || {
+        let mut line = Vec::with_capacity(MAX_LINE);
+        let mut byte = [0u8; 1];
+        loop {
+            match uart.read(&mut byte, BLOCK) {
+                Ok(1) => match byte[0] {
+                    b'\n' | b'\r' => {
+                        if line.is_empty() {
+                            continue;
+                        }
+                        let text = String::from_utf8_lossy(&line).into_owned();
+                        line.clear();
+                        match parse(&text) {
+                            Ok(command) => {
+                                if commands.send(command).is_err() {
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                let (id, text) = split_id(text.trim()).map_or((None, text.trim()), |(id, rest)| (Some(id), rest));
+                                let name = text.split(['|', ' ']).next().unwrap_or("");
+                                println!("{}", tag(id, &format!("NAK|{}|{e}", name.to_ascii_uppercase())));
+                            }
+                        }
+                    }
+                    b => {
+                        if line.len() < MAX_LINE {
+                            line.push(b);
+                        }
+                    }
+                },
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!("UART0 command read error: {e:?}");
+                }
+            }
+        }
+    })?;
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::ops::RangeInclusive;
+
+use crate::bus::SlaveKind;
+
+// Konfigurasi jaringan, diisi lewat environment saat build (lihat flash.sh), mis.
+//   WIFI_SSID=blink WIFI_PASSWORD=... INFLUX_TOKEN=... cargo build
+// SSID kosong = firmware langsung jalan di mode serial-only (gateway USB)
+pub const WIFI_SSID: &str = match option_env!("WIFI_SSID") {
+    Some(v) => v,
+    None => "",
+};
+pub const WIFI_PASSWORD: &str = match option_env!("WIFI_PASSWORD") {
+    Some(v) => v,
+    None => "",
+};
+// Nilai Wi-Fi/InfluxDB/MQTT di sini hanya default: kredensial dari mode provisioning
+// (tombol fan ditahan saat boot, lihat provision.rs) disimpan di NVS dan didahulukan.
+// Password SoftAP provisioning (min. 8 karakter, kosong = AP terbuka)
+pub const PROVISION_PASSWORD: &str = match option_env!("PROVISION_PASSWORD") {
+    Some(v) => v,
+    None => "rustdcs-setup",
+};
+
+pub const INFLUX_URL: &str = match option_env!("INFLUX_URL") {
+    Some(v) => v,
+    None => "http://192.168.100.161:8086",
+};
+pub const INFLUX_ORG: &str = match option_env!("INFLUX_ORG") {
+    Some(v) => v,
+    None => "ITS",
+};
+pub const INFLUX_BUCKET: &str = match option_env!("INFLUX_BUCKET") {
+    Some(v) => v,
+    None => "SENSOR_DATA",
+};
+pub const INFLUX_TOKEN: &str = match option_env!("INFLUX_TOKEN") {
+    Some(v) => v,
+    None => "",
+};
+
+// Broker MQTT lokal untuk instalasi tanpa gateway Linux (uplink "mqtt")
+pub const MQTT_URL: &str = match option_env!("MQTT_URL") {
+    Some(v) => v,
+    None => "mqtt://192.168.100.161:1883",
+};
+pub const MQTT_USERNAME: &str = match option_env!("MQTT_USERNAME") {
+    Some(v) => v,
+    None => "",
+};
+pub const MQTT_PASSWORD: &str = match option_env!("MQTT_PASSWORD") {
+    Some(v) => v,
+    None => "",
+};
+// Topic: <prefix>/<DEVICE_ID>/telemetry, .../cmd (perintah), .../ack (balasan)
+pub const MQTT_TOPIC_PREFIX: &str = match option_env!("MQTT_TOPIC_PREFIX") {
+    Some(v) => v,
+    None => "rust-dcs",
+};
+// Uplink awal saat Wi-Fi tersambung ("influx" atau "mqtt"); bisa diganti lewat perintah UPLINK
+pub const UPLINK: &str = match option_env!("UPLINK") {
+    Some(v) => v,
+    None => "influx",
+};
+
+// Image firmware default untuk perintah OTA tanpa URL (mis. hasil `cargo build --release` di server)
+pub const OTA_URL: &str = match option_env!("OTA_URL") {
+    Some(v) => v,
+    None => "http://192.168.100.161:8000/sht20.bin",
+};
+
+// Tag device pada line protocol, sama dengan device_id default backend
+pub const DEVICE_ID: &str = match option_env!("DEVICE_ID") {
+    Some(v) => v,
+    None => "sht20",
+};
+
+// Upload gagal berturut-turut sebanyak ini -> turun ke mode serial-only
+pub const UPLOAD_FAILURES_BEFORE_FALLBACK: u32 = 3;
+// Jeda sebelum mencoba Wi-Fi lagi dari mode serial-only
+pub const WIFI_RETRY_SECS: u64 = 300;
+
+// Slave RS485 yang dipoll setiap siklus: (alamat Modbus, jenis). Slave SHT20
+// pertama adalah sensor utama untuk kontrol relay dan upload Wi-Fi.
+pub const SLAVES: &[(u8, SlaveKind)] = &[(1, SlaveKind::Sht20), (2, SlaveKind::Sht20), (3, SlaveKind::SoilMoisture)];
+// Scan alamat saat boot: slave config yang tidak menjawab dilewati, alamat lain yang menjawab ditambahkan
+pub const SCAN_ON_BOOT: bool = true;
+pub const SCAN_RANGE: RangeInclusive<u8> = 1..=16;
+
+// Driver sensor utama: "modbus" (transmitter RS485, lihat SLAVES), "sht3x" / "bme280"
+// (I2C, PIN_I2C_SDA/PIN_I2C_SCL) atau "dht22" (data di PIN_DHT)
+pub const SENSOR_DRIVER: &str = match option_env!("SENSOR_DRIVER") {
+    Some(v) => v,
+    None => "modbus",
+};
+
+// Siklus baca gagal berturut-turut pada sensor utama sebelum relay AUTO masuk state aman
+pub const SAFE_STATE_AFTER: u32 = 3;
+// State aman tiap relay ("ON" atau "OFF"); default keduanya mati
+pub const SAFE_FAN: &str = match option_env!("SAFE_FAN") {
+    Some(v) => v,
+    None => "OFF",
+};
+pub const SAFE_PUMP: &str = match option_env!("SAFE_PUMP") {
+    Some(v) => v,
+    None => "OFF",
+};
+
+// Pemetaan GPIO default (carrier board v1). Board lain memakai image yang sama dan cukup
+// menimpa key yang berbeda lewat perintah BOARD|key=value (disimpan di NVS, berlaku setelah
+// reboot), lihat board.rs
+pub const PIN_FAN: i32 = 2; // PWM exhaust fan (LEDC)
+pub const PIN_PUMP: i32 = 4; // relay pompa
+pub const PIN_RS485_TX: i32 = 16;
+pub const PIN_RS485_RX: i32 = 17;
+pub const PIN_DHT: i32 = 15;
+pub const PIN_I2C_SDA: i32 = 21; // sensor I2C (I2C0)
+pub const PIN_I2C_SCL: i32 = 22;
+pub const PIN_LED_TX: i32 = 18;
+pub const PIN_LED_RX: i32 = 19;
+pub const PIN_OLED_SDA: i32 = 25; // layar OLED (I2C1, --features oled)
+pub const PIN_OLED_SCL: i32 = 26;
+// Tombol override lokal ke GND (pull-up internal); -1 = tidak dipasang
+pub const PIN_BUTTON_PUMP: i32 = 32;
+pub const PIN_BUTTON_FAN: i32 = 33;
+// Input analog sensor tambahan (ADC1 saja: GPIO32-39); -1 = tidak dipasang. Skala per kanal
+// di Settings (SET|soil_zero=..|soil_full=..|level_zero=..|level_full=..)
+pub const PIN_SOIL_ADC: i32 = 34;
+pub const PIN_LEVEL_ADC: i32 = 35;
+// RS485 kedua (UART2) untuk Modbus RTU slave ke PLC, aktif dengan BOARD|plc=1
+pub const PIN_PLC_TX: i32 = 27;
+pub const PIN_PLC_RX: i32 = 14;
+// Modul relay optocoupler umumnya aktif low; berlaku untuk relay pompa dan output PWM fan
+pub const RELAY_ACTIVE_LOW: bool = false;
+
+// Fitur opsional per board
+pub const ENABLE_LEDS: bool = true;
+pub const ENABLE_DISPLAY: bool = true;
+pub const ENABLE_BUTTONS: bool = true;
+pub const ENABLE_PLC: bool = false;
+
+// Alamat dan baud Modbus RTU slave untuk PLC (lihat plc.rs untuk peta register)
+pub const PLC_SLAVE_ADDRESS: u8 = 10;
+pub const PLC_BAUD: u32 = 9600;
+
+// Mode baterai untuk node logging jarak jauh: bangun setiap SLEEP_MINUTES, baca sensor,
+// kirim frame (serial/Wi-Fi) lalu deep sleep; relay tidak dikendalikan. Build dengan BATTERY_MODE=ON
+pub const BATTERY_MODE: &str = match option_env!("BATTERY_MODE") {
+    Some(v) => v,
+    None => "OFF",
+};
+pub const SLEEP_MINUTES: u64 = 10;
@@ -0,0 +1,501 @@
+use std::time::{Duration, Instant};
+use dcs_model::RelayMode;
+
+use crate::config;
+
+// Default threshold relay (lihat README): motor/exhaust fan ON saat suhu >= 30°C,
+// OFF saat <= 25°C; pompa ON saat kelembaban <= 40%, OFF saat >= 60%
+pub const TEMP_MOTOR_ON: f32 = 30.0;
+pub const TEMP_MOTOR_OFF: f32 = 25.0;
+pub const HUMIDITY_PUMP_ON: f32 = 40.0;
+pub const HUMIDITY_PUMP_OFF: f32 = 60.0;
+
+// Duty minimum exhaust fan PWM saat AUTO menyala (di bawah ini kipas DC cenderung berhenti);
+// duty naik linear dari FAN_MIN_DUTY di temp_off sampai 100% di temp_on
+pub const FAN_MIN_DUTY: u8 = 30;
+
+// Waktu tahan minimum (detik) sebelum relay AUTO boleh berganti state lagi (proteksi motor/pompa)
+pub const FAN_MIN_ON: f32 = 60.0;
+pub const FAN_MIN_OFF: f32 = 60.0;
+pub const PUMP_MIN_ON: f32 = 30.0;
+pub const PUMP_MIN_OFF: f32 = 30.0;
+// Batas atas waktu tahan supaya salah ketik tidak mengunci relay berjam-jam
+const MAX_HOLD: f32 = 3600.0;
+
+// Jumlah pembacaan berturut-turut yang harus melewati threshold sebelum relay AUTO berganti;
+// satu bacaan nyasar (kabel RS485 berisik) tidak lagi menyalakan/mematikan pompa. 1 = langsung.
+pub const CONFIRM_SAMPLES: f32 = 3.0;
+const MAX_CONFIRM: f32 = 20.0;
+
+// Skala input analog: nilai ADC mentah (12-bit) di titik 0% dan 100%. Probe tanah kapasitif
+// turun saat basah, jadi zero > full boleh.
+pub const SOIL_ADC_ZERO: f32 = 3300.0; // probe di udara kering
+pub const SOIL_ADC_FULL: f32 = 1400.0; // probe terendam air
+pub const LEVEL_ADC_ZERO: f32 = 300.0; // pelampung di dasar tangki
+pub const LEVEL_ADC_FULL: f32 = 3800.0; // tangki penuh
+
+// Offset kalibrasi SHT20 (hasil perbandingan dengan termometer referensi)
+pub const TEMPERATURE_OFFSET: f32 = -1.2;
+pub const HUMIDITY_OFFSET: f32 = -6.5;
+
+// Threshold dan kalibrasi yang bisa diubah lewat perintah SET tanpa reflash.
+// Backend mengirim SET threshold fan + waktu tahan setiap port serial dibuka, sehingga
+// keputusan device dan logika virtual backend memakai band yang sama.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    pub temp_on: f32,
+    pub temp_off: f32,
+    pub hum_on: f32,
+    pub hum_off: f32,
+    pub temp_offset: f32,
+    pub hum_offset: f32,
+    pub fan_min_on: f32,
+    pub fan_min_off: f32,
+    pub pump_min_on: f32,
+    pub pump_min_off: f32,
+    pub soil_zero: f32,
+    pub soil_full: f32,
+    pub level_zero: f32,
+    pub level_full: f32,
+    pub confirm_samples: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            temp_on: TEMP_MOTOR_ON,
+            temp_off: TEMP_MOTOR_OFF,
+            hum_on: HUMIDITY_PUMP_ON,
+            hum_off: HUMIDITY_PUMP_OFF,
+            temp_offset: TEMPERATURE_OFFSET,
+            hum_offset: HUMIDITY_OFFSET,
+            fan_min_on: FAN_MIN_ON,
+            fan_min_off: FAN_MIN_OFF,
+            pump_min_on: PUMP_MIN_ON,
+            pump_min_off: PUMP_MIN_OFF,
+            soil_zero: SOIL_ADC_ZERO,
+            soil_full: SOIL_ADC_FULL,
+            level_zero: LEVEL_ADC_ZERO,
+            level_full: LEVEL_ADC_FULL,
+            confirm_samples: CONFIRM_SAMPLES,
+        }
+    }
+}
+
+impl Settings {
+    pub const KEYS: [&'static str; 15] = [
+        "temp_on", "temp_off", "hum_on", "hum_off", "temp_offset", "hum_offset", "fan_min_on", "fan_min_off", "pump_min_on",
+        "pump_min_off", "soil_zero", "soil_full", "level_zero", "level_full", "confirm_samples",
+    ];
+
+    pub fn set(&mut self, key: &str, value: f32) -> Result<(), String> {
+        if !value.is_finite() {
+            return Err(format!("{key} must be finite"));
+        }
+        match key {
+            "temp_on" => self.temp_on = value,
+            "temp_off" => self.temp_off = value,
+            "hum_on" => self.hum_on = value,
+            "hum_off" => self.hum_off = value,
+            "temp_offset" => self.temp_offset = value,
+            "hum_offset" => self.hum_offset = value,
+            "fan_min_on" => self.fan_min_on = value,
+            "fan_min_off" => self.fan_min_off = value,
+            "pump_min_on" => self.pump_min_on = value,
+            "pump_min_off" => self.pump_min_off = value,
+            "soil_zero" => self.soil_zero = value,
+            "soil_full" => self.soil_full = value,
+            "level_zero" => self.level_zero = value,
+            "level_full" => self.level_full = value,
+            "confirm_samples" => self.confirm_samples = value,
+            _ => return Err(format!("unknown key {key}")),
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<f32> {
+        match key {
+            "temp_on" => Some(self.temp_on),
+            "temp_off" => Some(self.temp_off),
+            "hum_on" => Some(self.hum_on),
+            "hum_off" => Some(self.hum_off),
+            "temp_offset" => Some(self.temp_offset),
+            "hum_offset" => Some(self.hum_offset),
+            "fan_min_on" => Some(self.fan_min_on),
+            "fan_min_off" => Some(self.fan_min_off),
+            "pump_min_on" => Some(self.pump_min_on),
+            "pump_min_off" => Some(self.pump_min_off),
+            "soil_zero" => Some(self.soil_zero),
+            "soil_full" => Some(self.soil_full),
+            "level_zero" => Some(self.level_zero),
+            "level_full" => Some(self.level_full),
+            "confirm_samples" => Some(self.confirm_samples),
+            _ => None,
+        }
+    }
+
+    // Band hysteresis harus valid, kalau tidak relay bisa toggle setiap siklus
+    pub fn validate(&self) -> Result<(), String> {
+        if self.temp_off >= self.temp_on {
+            return Err(format!("temp_off ({:.1}) must be below temp_on ({:.1})", self.temp_off, self.temp_on));
+        }
+        if self.hum_on >= self.hum_off {
+            return Err(format!("hum_on ({:.1}) must be below hum_off ({:.1})", self.hum_on, self.hum_off));
+        }
+        if !(0.0..=100.0).contains(&self.hum_on) || !(0.0..=100.0).contains(&self.hum_off) {
+            return Err("humidity thresholds must be within 0-100%".to_string());
+        }
+        for (key, hold) in [
+            ("fan_min_on", self.fan_min_on),
+            ("fan_min_off", self.fan_min_off),
+            ("pump_min_on", self.pump_min_on),
+            ("pump_min_off", self.pump_min_off),
+        ] {
+            if !(0.0..=MAX_HOLD).contains(&hold) {
+                return Err(format!("{key} must be within 0-{MAX_HOLD:.0} s"));
+            }
+        }
+        if !(1.0..=MAX_CONFIRM).contains(&self.confirm_samples) || self.confirm_samples.fract() != 0.0 {
+            return Err(format!("confirm_samples must be a whole number 1-{MAX_CONFIRM:.0}"));
+        }
+        for (name, zero, full) in [("soil", self.soil_zero, self.soil_full), ("level", self.level_zero, self.level_full)] {
+            if !(0.0..=4095.0).contains(&zero) || !(0.0..=4095.0).contains(&full) {
+                return Err(format!("{name}_zero/{name}_full must be raw ADC values 0-4095"));
+            }
+            if (zero - full).abs() < 1.0 {
+                return Err(format!("{name}_zero and {name}_full must differ"));
+            }
+        }
+        Ok(())
+    }
+
+    // Nilai ADC mentah -> persen (0-100) sesuai skala kanal; None = kanal tidak dikenal
+    pub fn confirm(&self) -> u32 {
+        self.confirm_samples as u32
+    }
+
+    pub fn scale_analog(&self, name: &str, raw: u16) -> Option<f32> {
+        let (zero, full) = match name {
+            "soil_moisture" => (self.soil_zero, self.soil_full),
+            "water_level" => (self.level_zero, self.level_full),
+            _ => return None,
+        };
+        Some(((raw as f32 - zero) / (full - zero) * 100.0).clamp(0.0, 100.0))
+    }
+}
+
+// Relay dengan band hysteresis, konfirmasi dan waktu tahan minimum: state hanya berganti setelah
+// threshold dilewati pada `confirm` pembacaan berturut-turut DAN state sekarang sudah bertahan min_on/min_off
+#[derive(Debug, Default)]
+struct Hysteresis {
+    on: bool,
+    since: Option<Instant>,
+    // (state yang diminta, jumlah pembacaan berturut-turut) selama jendela konfirmasi
+    pending: Option<(bool, u32)>,
+}
+
+impl Hysteresis {
+    // want: Some(true) = threshold ON dilewati, Some(false) = threshold OFF dilewati, None = di dalam band
+    fn update(&mut self, want: Option<bool>, min_on: f32, min_off: f32, confirm: u32, now: Instant) -> bool {
+        let Some(want) = want.filter(|want| *want != self.on) else {
+            self.pending = None;
+            return self.on;
+        };
+        let count = match self.pending {
+            Some((pending, count)) if pending == want => count.saturating_add(1),
+            _ => 1,
+        };
+        self.pending = Some((want, count));
+        if count < confirm {
+            return self.on;
+        }
+        let hold = Duration::from_secs_f32(if self.on { min_on } else { min_off });
+        if self.since.map_or(true, |since| now.duration_since(since) >= hold) {
+            self.set(want, now);
+        } else {
+            log::debug!("Relay change to {} held ({hold:?} minimum)", if want { "ON" } else { "OFF" });
+        }
+        self.on
+    }
+
+    // Tanpa waktu tahan dan konfirmasi (safe state)
+    fn set(&mut self, on: bool, now: Instant) {
+        self.pending = None;
+        if on != self.on {
+            self.on = on;
+            self.since = Some(now);
+        }
+    }
+
+    // Perubahan yang masih menunggu konfirmasi: (state diminta, pembacaan terkumpul)
+    fn confirming(&self, confirm: u32) -> Option<(bool, u32)> {
+        self.pending.filter(|(_, count)| *count < confirm)
+    }
+}
+
+// Relay AUTO yang sedang dalam jendela konfirmasi, untuk baris RELAY_PENDING
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pending {
+    pub relay: Relay,
+    pub want: bool,
+    pub count: u32,
+    pub confirm: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relay {
+    Fan,
+    Pump,
+}
+
+impl Relay {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "fan" | "exhaust_fan" | "motor" => Some(Relay::Fan),
+            "pump" => Some(Relay::Pump),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Relay::Fan => "exhaust_fan",
+            Relay::Pump => "pump",
+        }
+    }
+}
+
+// AUTO = ikut threshold; ON/OFF = override manual dari backend atau terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Override {
+    Auto,
+    On,
+    Off,
+}
+
+impl Override {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Override::Auto => "AUTO",
+            Override::On => "ON",
+            Override::Off => "OFF",
+        }
+    }
+
+    fn resolve(self, auto: bool) -> bool {
+        match self {
+            Override::Auto => auto,
+            Override::On => true,
+            Override::Off => false,
+        }
+    }
+}
+
+// Mode dari perintah RELAY (format bersama di dcs-model)
+impl From<RelayMode> for Override {
+    fn from(mode: RelayMode) -> Self {
+        match mode {
+            RelayMode::Auto => Override::Auto,
+            RelayMode::On => Override::On,
+            RelayMode::Off => Override::Off,
+        }
+    }
+}
+
+// Salinan state relay untuk task lain (upload, layar); Controller tetap milik task kontrol
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RelayState {
+    pub fan_duty: u8,
+    pub fan_on: bool,
+    pub pump_on: bool,
+    pub safe_state: bool,
+}
+
+// Keputusan relay: threshold dengan hysteresis, lalu override manual di atasnya
+pub struct Controller {
+    pub settings: Settings,
+    fan_override: Override,
+    pump_override: Override,
+    fan_auto: Hysteresis,
+    pump_auto: Hysteresis,
+    // Duty exhaust fan hasil kontrol proporsional (0 saat fan_auto mati)
+    fan_auto_duty: u8,
+    // Duty dari perintah FAN|<pct>; RELAY|fan=ON tanpa duty = 100%
+    fan_manual_duty: Option<u8>,
+    // Override dari tombol fisik (bukan perintah RELAY/FAN), dilaporkan sebagai mode LOCAL
+    fan_local: bool,
+    pump_local: bool,
+    // true selama relay AUTO ditahan di config::SAFE_FAN/SAFE_PUMP karena sensor gagal
+    safe_state: bool,
+}
+
+impl Controller {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            settings,
+            fan_override: Override::Auto,
+            pump_override: Override::Auto,
+            fan_auto: Hysteresis::default(),
+            pump_auto: Hysteresis::default(),
+            fan_auto_duty: 0,
+            fan_manual_duty: None,
+            fan_local: false,
+            pump_local: false,
+            safe_state: false,
+        }
+    }
+
+    // Override manual tetap berlaku, supaya operator masih bisa menjalankan pompa saat sensor mati
+    pub fn enter_safe_state(&mut self) {
+        let now = Instant::now();
+        self.fan_auto.set(config::SAFE_FAN.eq_ignore_ascii_case("ON"), now);
+        self.fan_auto_duty = if self.fan_auto.on { 100 } else { 0 };
+        self.pump_auto.set(config::SAFE_PUMP.eq_ignore_ascii_case("ON"), now);
+        self.safe_state = true;
+    }
+
+    pub fn in_safe_state(&self) -> bool {
+        self.safe_state
+    }
+
+    // Di antara threshold ON/OFF relay mempertahankan state sebelumnya; perubahan yang
+    // datang sebelum waktu tahan minimum habis ditunda ke siklus berikutnya
+    pub fn control_relays(&mut self, temperature: f32, humidity: f32) {
+        self.safe_state = false;
+        let now = Instant::now();
+        let settings = self.settings;
+        let fan_want = if temperature >= settings.temp_on {
+            Some(true)
+        } else if temperature <= settings.temp_off {
+            Some(false)
+        } else {
+            None
+        };
+        let fan_on = self.fan_auto.update(fan_want, settings.fan_min_on, settings.fan_min_off, settings.confirm(), now);
+        self.fan_auto_duty = if fan_on {
+            // Proporsional terhadap error suhu di atas temp_off
+            let span = (settings.temp_on - settings.temp_off).max(0.1);
+            let duty = (temperature - settings.temp_off) / span * 100.0;
+            duty.clamp(FAN_MIN_DUTY as f32, 100.0).round() as u8
+        } else {
+            0
+        };
+        let pump_want = if humidity <= settings.hum_on {
+            Some(true)
+        } else if humidity >= settings.hum_off {
+            Some(false)
+        } else {
+            None
+        };
+        self.pump_auto.update(pump_want, settings.pump_min_on, settings.pump_min_off, settings.confirm(), now);
+    }
+
+    // Relay AUTO yang threshold-nya sudah terlewati tapi belum terkonfirmasi; override manual
+    // tidak dilaporkan karena relay fisiknya tidak ikut keputusan AUTO
+    pub fn pending(&self) -> Vec<Pending> {
+        let confirm = self.settings.confirm();
+        [(Relay::Fan, &self.fan_auto, self.fan_override), (Relay::Pump, &self.pump_auto, self.pump_override)]
+            .into_iter()
+            .filter(|(_, _, mode)| *mode == Override::Auto)
+            .filter_map(|(relay, auto, _)| {
+                let (want, count) = auto.confirming(confirm)?;
+                Some(Pending { relay, want, count, confirm })
+            })
+            .collect()
+    }
+
+    pub fn set_override(&mut self, relay: Relay, mode: Override) {
+        match relay {
+            Relay::Fan => {
+                self.fan_override = mode;
+                self.fan_manual_duty = None;
+                self.fan_local = false;
+            }
+            Relay::Pump => {
+                self.pump_override = mode;
+                self.pump_local = false;
+            }
+        }
+    }
+
+    // Tombol di panel: sama dengan set_override tapi ditandai lokal (AUTO menghapus tandanya)
+    pub fn set_local_override(&mut self, relay: Relay, mode: Override) {
+        self.set_override(relay, mode);
+        let local = mode != Override::Auto;
+        match relay {
+            Relay::Fan => self.fan_local = local,
+            Relay::Pump => self.pump_local = local,
+        }
+    }
+
+    pub fn override_mode(&self, relay: Relay) -> Override {
+        match relay {
+            Relay::Fan => self.fan_override,
+            Relay::Pump => self.pump_override,
+        }
+    }
+
+    // Mode untuk frame RELAY_STATUS: AUTO, REMOTE (perintah backend/terminal) atau LOCAL (tombol)
+    pub fn mode_label(&self, relay: Relay) -> &'static str {
+        let (mode, local) = match relay {
+            Relay::Fan => (self.fan_override, self.fan_local),
+            Relay::Pump => (self.pump_override, self.pump_local),
+        };
+        match (mode, local) {
+            (Override::Auto, _) => "AUTO",
+            (_, true) => "LOCAL",
+            (_, false) => "REMOTE",
+        }
+    }
+
+    pub fn state(&self) -> RelayState {
+        RelayState {
+            fan_duty: self.fan_duty(),
+            fan_on: self.fan_on(),
+            pump_on: self.pump_on(),
+            safe_state: self.safe_state,
+        }
+    }
+
+    pub fn relay_on(&self, relay: Relay) -> bool {
+        match relay {
+            Relay::Fan => self.fan_on(),
+            Relay::Pump => self.pump_on(),
+        }
+    }
+
+    pub fn override_mode(&self, relay: Relay) -> Override {
+        match relay {
+            Relay::Fan => self.fan_override,
+            Relay::Pump => self.pump_override,
+        }
+    }
+
+    // 0 = OFF tetap menjadi override, bukan duty manual
+    pub fn set_fan_duty(&mut self, duty: u8) {
+        let duty = duty.min(100);
+        if duty == 0 {
+            self.set_override(Relay::Fan, Override::Off);
+        } else {
+            self.fan_override = Override::On;
+            self.fan_manual_duty = Some(duty);
+            self.fan_local = false;
+        }
+    }
+
+    pub fn fan_duty(&self) -> u8 {
+        match self.fan_override {
+            Override::Auto => self.fan_auto_duty,
+            Override::On => self.fan_manual_duty.unwrap_or(100),
+            Override::Off => 0,
+        }
+    }
+
+    pub fn fan_on(&self) -> bool {
+        self.fan_duty() > 0
+    }
+
+    pub fn pump_on(&self) -> bool {
+        self.pump_override.resolve(self.pump_auto.on)
+    }
+}
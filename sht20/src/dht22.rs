@@ -0,0 +1,86 @@
+use esp_idf_svc::hal::delay::{Ets, FreeRtos};
+use esp_idf_svc::hal::gpio::{AnyIOPin, InputOutput, PinDriver};
+use esp_idf_svc::hal::interrupt;
+use esp_idf_svc::sys::esp_timer_get_time;
+
+use crate::sensor::{Reading, SensorDriver};
+
+// DHT22/AM2302 lewat satu pin data open-drain (pull-up 4.7k-10k ke 3.3V).
+// Minimal 2 detik antar pembacaan; READ_INTERVAL 10 detik sudah cukup.
+
+// Bit "1" high ~70 µs, bit "0" high ~26 µs
+const ONE_THRESHOLD_US: i64 = 48;
+const EDGE_TIMEOUT_US: i64 = 120;
+
+pub struct Dht22 {
+    pin: PinDriver<'static, AnyIOPin, InputOutput>,
+}
+
+impl Dht22 {
+    pub fn new(pin: AnyIOPin) -> anyhow::Result<Self> {
+        let mut pin = PinDriver::input_output_od(pin)?;
+        pin.set_high()?;
+        Ok(Self { pin })
+    }
+
+    // Tunggu sampai level pin != `level`; hasilnya lama level itu bertahan (µs)
+    fn wait_while(&self, level: bool) -> Result<i64, String> {
+        // SAFETY: hanya membaca timer monotonic ESP-IDF
+        let start = unsafe { esp_timer_get_time() };
+        loop {
+            let elapsed = unsafe { esp_timer_get_time() } - start;
+            if self.pin.is_high() != level {
+                return Ok(elapsed);
+            }
+            if elapsed > EDGE_TIMEOUT_US {
+                return Err("timeout".to_string());
+            }
+        }
+    }
+
+    // Frame 40 bit: RH hi/lo, T hi/lo, checksum
+    fn read_frame(&mut self) -> Result<[u8; 5], String> {
+        // Start signal: tarik low >1 ms lalu lepas
+        self.pin.set_low().map_err(|_| "tx".to_string())?;
+        FreeRtos::delay_ms(2);
+        self.pin.set_high().map_err(|_| "tx".to_string())?;
+        Ets::delay_us(30);
+
+        // Timing per bit hanya puluhan µs: interrupt dimatikan selama frame (~5 ms)
+        interrupt::free(|| {
+            // Respons sensor: low 80 µs, high 80 µs
+            self.wait_while(true).map_err(|_| "no_response".to_string())?;
+            self.wait_while(false)?;
+            self.wait_while(true)?;
+
+            let mut data = [0u8; 5];
+            for bit in 0..40 {
+                self.wait_while(false)?;
+                let high = self.wait_while(true)?;
+                if high > ONE_THRESHOLD_US {
+                    data[bit / 8] |= 0x80 >> (bit % 8);
+                }
+            }
+            Ok(data)
+        })
+    }
+}
+
+impl SensorDriver for Dht22 {
+    fn name(&self) -> &'static str {
+        "dht22"
+    }
+
+    fn read(&mut self) -> Result<Reading, String> {
+        let data = self.read_frame()?;
+        let sum = data[..4].iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+        if sum != data[4] {
+            return Err("crc".to_string());
+        }
+        let humidity = u16::from_be_bytes([data[0], data[1]]) as f32 / 10.0;
+        // Bit 15 suhu = tanda negatif (bukan two's complement)
+        let magnitude = u16::from_be_bytes([data[2] & 0x7F, data[3]]) as f32 / 10.0;
+        let temperature = if data[2] & 0x80 != 0 { -magnitude } else { magnitude };
+        Ok(Reading::Climate { temperature, humidity })
+    }
+}
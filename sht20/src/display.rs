@@ -0,0 +1,88 @@
+// Ringkasan yang ditampilkan di layar setiap siklus
+pub struct Status<'a> {
+    pub temperature: Option<f32>,
+    pub humidity: Option<f32>,
+    pub fan_duty: u8,
+    pub pump_on: bool,
+    // Mis. "WiFi -61dBm influx" atau "Serial"
+    pub link: &'a str,
+    // Peringatan untuk teknisi (safe state, sensor gagal)
+    pub alert: Option<&'a str>,
+}
+
+impl Status<'_> {
+    fn lines(&self) -> [String; 5] {
+        let reading = |value: Option<f32>, unit: &str| value.map_or("--".to_string(), |v| format!("{v:.1}{unit}"));
+        [
+            format!("T {}  H {}", reading(self.temperature, "C"), reading(self.humidity, "%")),
+            format!("Fan {}%  Pump {}", self.fan_duty, if self.pump_on { "ON" } else { "OFF" }),
+            self.link.to_string(),
+            String::new(),
+            self.alert.unwrap_or("").to_string(),
+        ]
+    }
+}
+
+#[cfg(feature = "oled")]
+mod oled {
+    use embedded_graphics::mono_font::{ascii::FONT_6X10, MonoTextStyle};
+    use embedded_graphics::pixelcolor::BinaryColor;
+    use embedded_graphics::prelude::*;
+    use embedded_graphics::text::{Baseline, Text};
+    use esp_idf_svc::hal::gpio::AnyIOPin;
+    use esp_idf_svc::hal::i2c::{I2cConfig, I2cDriver, I2C1};
+    use esp_idf_svc::hal::units::FromValueType;
+    use ssd1306::mode::BufferedGraphicsMode;
+    use ssd1306::prelude::*;
+    use ssd1306::{I2CDisplayInterface, Ssd1306};
+
+    use super::Status;
+
+    type Panel = Ssd1306<I2CInterface<I2cDriver<'static>>, DisplaySize128x64, BufferedGraphicsMode<DisplaySize128x64>>;
+
+    pub struct Display {
+        panel: Panel,
+    }
+
+    impl Display {
+        pub fn start(i2c1: I2C1, sda: i32, scl: i32) -> anyhow::Result<Self> {
+            // SAFETY: pin dari Board (pin map tervalidasi) dan tidak dipakai driver lain
+            let (sda_pin, scl_pin) = unsafe { (AnyIOPin::new(sda), AnyIOPin::new(scl)) };
+            let i2c = I2cDriver::new(i2c1, sda_pin, scl_pin, &I2cConfig::new().baudrate(400.kHz().into()))?;
+            let mut panel = Ssd1306::new(I2CDisplayInterface::new(i2c), DisplaySize128x64, DisplayRotation::Rotate0)
+                .into_buffered_graphics_mode();
+            panel.init().map_err(|e| anyhow::anyhow!("SSD1306 init failed: {e:?}"))?;
+            log::info!("OLED ready - SDA=GPIO{sda}, SCL=GPIO{scl}");
+            Ok(Self { panel })
+        }
+
+        pub fn show(&mut self, status: &Status) {
+            let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+            self.panel.clear_buffer();
+            for (row, line) in status.lines().iter().enumerate() {
+                let _ = Text::with_baseline(line, Point::new(0, row as i32 * 12), style, Baseline::Top).draw(&mut self.panel);
+            }
+            if let Err(e) = self.panel.flush() {
+                log::warn!("OLED update failed: {e:?}");
+            }
+        }
+    }
+}
+
+#[cfg(feature = "oled")]
+pub use oled::Display;
+
+// Tanpa feature "oled" layar dilewati tanpa mengubah loop utama
+#[cfg(not(feature = "oled"))]
+pub struct Display;
+
+#[cfg(not(feature = "oled"))]
+impl Display {
+    pub fn start(_i2c1: esp_idf_svc::hal::i2c::I2C1, _sda: i32, _scl: i32) -> anyhow::Result<Self> {
+        anyhow::bail!("firmware built without the oled feature")
+    }
+
+    pub fn show(&mut self, status: &Status) {
+        let _ = status.lines();
+    }
+}
@@ -0,0 +1,22 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::modbus::calculate_crc16;
+
+// Versi protokol serial yang dilaporkan di HELLO; naikkan jika format frame berubah tidak kompatibel.
+// 2 = frame data dengan trailer |<seq>|<crc16>
+// 3 = perintah dengan prefix #<id>| dibalas ACK/NAK dengan prefix yang sama
+pub const PROTOCOL_VERSION: u32 = 3;
+
+// Nomor urut frame data (SENSOR_DATA, RELAY_STATUS, DIAG), dipakai bersama task kontrol dan comms.
+// Mulai dari 0 setiap boot; gateway mendeteksi frame hilang dari loncatan nomor.
+static SEQUENCE: AtomicU32 = AtomicU32::new(0);
+
+// Kirim frame ke gateway dengan trailer |<seq>|<crc16>: CRC-16/MODBUS atas "<frame>|<seq>",
+// 4 digit hex huruf besar. Kabel USB di panel kontrol berisik; baris rusak dibuang di backend.
+//   SENSOR_DATA|1718000000000000000|25.30|65.20|slave=1|fan_duty=60|42|57CC
+pub fn emit(frame: &str) {
+    let seq = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    let body = format!("{frame}|{seq}");
+    let crc = calculate_crc16(body.as_bytes());
+    println!("{body}|{crc:04X}");
+}
@@ -0,0 +1,121 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use esp_idf_svc::hal::gpio::{AnyOutputPin, Output, PinDriver};
+
+use crate::board::Board;
+
+// LED status: normal TX berganti setiap frame sensor dikirim, RX setiap perintah diterima.
+// Saat ada gangguan kedua LED menampilkan kode kedip supaya teknisi bisa mendiagnosis node
+// tanpa konsol serial (prioritas dari atas):
+//   safe state aktif        TX+RX kedip cepat bersamaan (5 Hz)
+//   Modbus tidak merespons  RX 2 kedip, jeda
+//   error CRC sensor        RX 3 kedip, jeda
+//   Wi-Fi menyambung        TX dan RX bergantian (1 Hz)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Normal,
+    WifiConnecting,
+    SensorCrc,
+    NoResponse,
+    SafeState,
+}
+
+// (tx, rx, durasi ms), diulang terus selama status aktif
+type Step = (bool, bool, u16);
+
+const PAUSE_MS: u16 = 1400;
+
+impl Status {
+    // Kode SENSOR_FAULT sensor utama -> pola kedip; kode lain (tx, exception) tidak punya pola sendiri
+    pub fn from_fault(code: &str) -> Option<Self> {
+        match code {
+            "crc" => Some(Status::SensorCrc),
+            "no_response" | "timeout" => Some(Status::NoResponse),
+            _ => None,
+        }
+    }
+
+    fn steps(self) -> &'static [Step] {
+        match self {
+            Status::Normal => &[],
+            Status::WifiConnecting => &[(true, false, 500), (false, true, 500)],
+            Status::SensorCrc => &[
+                (false, true, 200),
+                (false, false, 200),
+                (false, true, 200),
+                (false, false, 200),
+                (false, true, 200),
+                (false, false, PAUSE_MS),
+            ],
+            Status::NoResponse => &[(false, true, 200), (false, false, 200), (false, true, 200), (false, false, PAUSE_MS)],
+            Status::SafeState => &[(true, true, 100), (false, false, 100)],
+        }
+    }
+}
+
+// Diset task comms selama wifi.connect() (blocking); dibaca task kontrol untuk pola LED
+static WIFI_CONNECTING: AtomicBool = AtomicBool::new(false);
+
+pub fn set_wifi_connecting(connecting: bool) {
+    WIFI_CONNECTING.store(connecting, Ordering::Relaxed);
+}
+
+pub fn wifi_connecting() -> bool {
+    WIFI_CONNECTING.load(Ordering::Relaxed)
+}
+
+pub struct Leds {
+    tx: PinDriver<'static, AnyOutputPin, Output>,
+    rx: PinDriver<'static, AnyOutputPin, Output>,
+    status: Status,
+    step: usize,
+    next: Instant,
+}
+
+impl Leds {
+    pub fn start(board: &Board) -> anyhow::Result<Self> {
+        // SAFETY: pin dari Board (pin map tervalidasi) dan tidak dipakai driver lain
+        let (tx, rx) = unsafe { (AnyOutputPin::new(board.led_tx), AnyOutputPin::new(board.led_rx)) };
+        Ok(Self { tx: PinDriver::output(tx)?, rx: PinDriver::output(rx)?, status: Status::Normal, step: 0, next: Instant::now() })
+    }
+
+    // Kedip lalu lintas hanya saat tidak ada kode gangguan yang sedang ditampilkan
+    pub fn blink_tx(leds: &mut Option<Leds>) {
+        if let Some(leds) = leds.as_mut().filter(|leds| leds.status == Status::Normal) {
+            let _ = leds.tx.toggle();
+        }
+    }
+
+    pub fn blink_rx(leds: &mut Option<Leds>) {
+        if let Some(leds) = leds.as_mut().filter(|leds| leds.status == Status::Normal) {
+            let _ = leds.rx.toggle();
+        }
+    }
+
+    // Dipanggil setiap tick loop kontrol; pola baru dimulai dari langkah pertama
+    pub fn show(&mut self, status: Status, now: Instant) {
+        if status != self.status {
+            log::info!("💡 LED status: {status:?}");
+            self.status = status;
+            self.step = 0;
+            self.next = now;
+            if status == Status::Normal {
+                self.set(false, false);
+            }
+        }
+        let steps = status.steps();
+        if steps.is_empty() || now < self.next {
+            return;
+        }
+        let (tx, rx, ms) = steps[self.step % steps.len()];
+        self.set(tx, rx);
+        self.step = (self.step + 1) % steps.len();
+        self.next = now + Duration::from_millis(ms.into());
+    }
+
+    fn set(&mut self, tx: bool, rx: bool) {
+        let _ = self.tx.set_level(tx.into());
+        let _ = self.rx.set_level(rx.into());
+    }
+}
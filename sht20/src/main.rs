@@ -1,230 +1,730 @@
+mod analog;
+mod backlog;
+mod battery;
+mod board;
+mod bme280;
+mod buttons;
+mod bus;
+mod clock;
+mod command;
+mod config;
+mod control;
+mod dht22;
+mod display;
+mod frame;
+mod led;
+mod modbus;
+mod network;
+mod ota;
+mod plc;
+mod provision;
+mod recovery;
+mod sensor;
+mod sht3x;
+mod storage;
+mod tasks;
+
 use esp_idf_svc::hal::delay::FreeRtos;
-use esp_idf_svc::hal::gpio::{self, PinDriver};
+use esp_idf_svc::hal::gpio::{self, AnyIOPin, AnyOutputPin, Output, PinDriver};
+use esp_idf_svc::hal::i2c::{I2cConfig, I2cDriver, I2C0};
+use esp_idf_svc::hal::ledc::{config::TimerConfig, LedcDriver, LedcTimerDriver};
+use esp_idf_svc::hal::units::FromValueType;
 use esp_idf_svc::hal::peripherals::Peripherals;
 use esp_idf_svc::hal::uart::*;
 use esp_idf_svc::hal::uart::config::{DataBits, StopBits, FlowControl};
-// WiFi and HTTP dependencies removed for offline mode
-// use esp_idf_svc::wifi::{EspWifi, ClientConfiguration, Configuration as WifiConfiguration};
-// use esp_idf_svc::eventloop::EspSystemEventLoop;
-// use esp_idf_svc::nvs::EspDefaultNvsPartition;
-// use esp_idf_svc::sntp::{EspSntp, SntpConf};
-// use esp_idf_svc::http::client::{EspHttpConnection, Configuration as HttpConfiguration};
-// use embedded_svc::http::client::Client;
-// use embedded_svc::http::Method;
-// use embedded_io::Write;
-use std::time::{SystemTime, UNIX_EPOCH};
-
-
-
-fn calculate_crc16(data: &[u8]) -> u16 {
-    let mut crc: u16 = 0xFFFF;
-    for byte in data {
-        crc ^= *byte as u16;
-        for _ in 0..8 {
-            if crc & 0x0001 != 0 {
-                crc = (crc >> 1) ^ 0xA001;
-            } else {
-                crc >>= 1;
-            }
-        }
-    }
-    crc
-}
+use esp_idf_svc::eventloop::EspSystemEventLoop;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use dcs_model::SensorData;
+use std::rc::Rc;
+use std::sync::atomic::AtomicU32;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use backlog::{Backlog, Record};
+use board::Board;
+use bme280::Bme280;
+use buttons::{Buttons, Press};
+use command::Command;
+use control::{Controller, Override, Relay};
+use dht22::Dht22;
+use led::Leds;
+use network::{Network, Uplink};
+use sensor::{Reading, Sensor, SensorDriver};
+use sht3x::Sht3x;
+use storage::Storage;
+use tasks::{CommsConfig, Measurement, Outbound, SensorRequest};
 
-// All HTTP/InfluxDB functions removed for offline mode
-// Network connectivity, HTTP requests, and InfluxDB upload functions
-// are not needed when operating in offline serial-only mode
+// Jeda antar frame SENSOR_DATA/upload per sensor; sampling dan kontrol relay lebih cepat
+// (tasks::SAMPLE_INTERVAL)
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+// Periode loop task kontrol: perintah, tombol dan sampel baru diproses secepat ini
+const CONTROL_TICK: Duration = buttons::POLL_INTERVAL;
 
-// Relay control logic based on sensor readings
 
-fn send_sensor_data(temperature: f32, humidity: f32) {
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_nanos() as u64;
+// Wi-Fi + upload InfluxDB ada di network.rs; tanpa jaringan firmware tetap
+// berjalan sebagai serial gateway
 
-    // Output data ke serial untuk gateway
-    println!("SENSOR_DATA|{timestamp}|{temperature:.2}|{humidity:.2}");
-    println!("INFLUX_LINE|sht20_sensor temperature={temperature:.2},humidity={humidity:.2} {timestamp}");
+// Frekuensi PWM exhaust fan (driver MOSFET / input PWM kipas DC), di atas rentang dengar
+const FAN_PWM_HZ: u32 = 25_000;
+
+// Exhaust fan lewat PWM LEDC (kecepatan variabel), pompa tetap relay on/off
+struct Relays<'d> {
+    fan: LedcDriver<'d>,
+    pump: PinDriver<'d, AnyOutputPin, Output>,
+    // Board dengan modul relay aktif low: level dan duty PWM dibalik
+    active_low: bool,
 }
 
-// SNTP functions removed for offline mode  
-// fn setup_sntp() -> Result<()> {
-//     SNTP time sync not needed for offline operation
-// }
+impl Relays<'_> {
+    fn apply(&mut self, controller: &Controller) {
+        let max = self.fan.get_max_duty();
+        let duty = max * controller.fan_duty() as u32 / 100;
+        if let Err(e) = self.fan.set_duty(if self.active_low { max - duty } else { duty }) {
+            log::error!("Fan PWM error: {e:?}");
+        }
+        if let Err(e) = self.pump.set_level((controller.pump_on() != self.active_low).into()) {
+            log::error!("Pump relay error: {e:?}");
+        }
+    }
+}
 
-// WiFi setup functions removed for offline mode
-// fn setup_wifi() -> Result<()> {
-//     All WiFi related code commented out for offline operation
-// }
+fn on_off(on: bool) -> &'static str {
+    if on { "ON" } else { "OFF" }
+}
 
-fn read_sht20_sensor(peripherals: Peripherals) {
-    // Setup relay controls for motor and pump
+// fan_mode/pump_mode: AUTO, REMOTE (perintah RELAY/FAN) atau LOCAL (tombol di panel)
+fn send_relay_status(controller: &Controller) {
+    frame::emit(&format!(
+        "RELAY_STATUS|exhaust_fan:{}|pump:{}|fan_mode={}|pump_mode={}",
+        on_off(controller.fan_on()),
+        on_off(controller.pump_on()),
+        controller.mode_label(Relay::Fan),
+        controller.mode_label(Relay::Pump)
+    ));
+}
 
-    // Setup LED indicators for status
+// Task kontrol: pemilik relay, Controller, NVS dan buffer lokal. Menerima Measurement dari
+// task sensor dan perintah dari UART0/MQTT; upload dan balasan MQTT diteruskan ke task comms.
+struct Control<'d> {
+    controller: Controller,
+    relays: Relays<'d>,
+    storage: Option<Storage>,
+    backlog: Option<Backlog>,
+    leds: Option<Leds>,
+    uplink: Uplink,
+    // Salinan pin map untuk perintah BOARD (disimpan ke NVS, aktif setelah reboot)
+    board: Board,
+    outbound: Sender<Outbound>,
+    sensor_requests: Sender<SensorRequest>,
+    // Perintah konsol `read`: putaran sensor berikutnya juga dicetak sebagai READ|...
+    read_pending: bool,
+    // Pembacaan valid terakhir sensor utama (untuk layar)
+    last_reading: Option<(f32, f32)>,
+    // Kode kedip gangguan sensor utama (CRC / tidak merespons), hilang saat pembacaan valid
+    sensor_fault: Option<led::Status>,
+    // Nilai terakhir sensor tambahan (ADC, slave tanah RS485) untuk frame SENSOR_DATA utama
+    aux: Vec<(String, f32)>,
+    // Frame terakhir per slave: sampling tiap 2 detik, frame serial/upload tetap tiap REPORT_INTERVAL
+    last_report: Vec<(u8, Instant)>,
+    rounds: u32,
+}
 
-    // Initially turn off relays and LEDs
+impl Control<'_> {
+    fn report_due(&mut self, slave: u8) -> bool {
+        let now = Instant::now();
+        match self.last_report.iter_mut().find(|(address, _)| *address == slave) {
+            Some((_, last)) if now - *last < REPORT_INTERVAL => false,
+            Some((_, last)) => {
+                *last = now;
+                true
+            }
+            None => {
+                self.last_report.push((slave, now));
+                true
+            }
+        }
+    }
 
-    log::info!("Relay Control: Motor=GPIO2, Pump=GPIO4");
-    log::info!("LED Status: TX=GPIO18, RX=GPIO19");
-    let config = UartConfig::new()
-        .baudrate(9600.into())
-        .data_bits(DataBits::DataBits8)
-        .stop_bits(StopBits::STOP1)
-        .flow_control(FlowControl::None);
+    fn set_aux(&mut self, name: String, value: f32) {
+        match self.aux.iter_mut().find(|(field, _)| *field == name) {
+            Some((_, last)) => *last = value,
+            None => self.aux.push((name, value)),
+        }
+    }
 
-    let uart = UartDriver::new(
-        peripherals.uart1,
-        peripherals.pins.gpio16,
-        peripherals.pins.gpio17,
-        Option::<gpio::Gpio0>::None,
-        Option::<gpio::Gpio0>::None,
-        &config,
-    ).unwrap();
+    // Pola LED sesuai prioritas: safe state, gangguan sensor, Wi-Fi menyambung, normal
+    fn update_leds(&mut self) {
+        let Some(leds) = self.leds.as_mut() else { return };
+        let status = if self.controller.in_safe_state() {
+            led::Status::SafeState
+        } else if let Some(fault) = self.sensor_fault {
+            fault
+        } else if led::wifi_connecting() {
+            led::Status::WifiConnecting
+        } else {
+            led::Status::Normal
+        };
+        leds.show(status, Instant::now());
+    }
 
-    log::info!("UART ready - RS485 9600 baud");
+    fn publish_status(&self) {
+        let _ = self.outbound.send(Outbound::Status { reading: self.last_reading, relays: self.controller.state() });
+        plc::update(plc::Image {
+            reading: self.last_reading,
+            relays: self.controller.state(),
+            fan_mode: self.controller.override_mode(Relay::Fan),
+            pump_mode: self.controller.override_mode(Relay::Pump),
+        });
+    }
 
-    let slave_addr = 0x01;
-    let func_code = 0x04;
+    // Jawaban `status` di konsol, satu baris key=value
+    fn status_line(&self) -> String {
+        let reading = self.last_reading.map_or("t=NA|h=NA".to_string(), |(t, h)| format!("t={t:.1}|h={h:.1}"));
+        // SAFETY: hanya membaca timer ESP-IDF
+        let uptime = unsafe { esp_idf_svc::sys::esp_timer_get_time() } / 1_000_000;
+        format!(
+            "STATUS|{reading}|fan={}|fan_duty={}|fan_mode={}|pump={}|pump_mode={}|safe_state={}|uplink={}|uptime={uptime}",
+            on_off(self.controller.fan_on()),
+            self.controller.fan_duty(),
+            self.controller.mode_label(Relay::Fan),
+            on_off(self.controller.pump_on()),
+            self.controller.mode_label(Relay::Pump),
+            u8::from(self.controller.in_safe_state()),
+            self.uplink.as_str()
+        )
+    }
 
-    loop {
-        // Temperature reading
-        let temp_cmd = [
-            slave_addr, func_code, 0x00, 0x01, 0x00, 0x01
-        ];
-        let temp_crc = calculate_crc16(&temp_cmd);
-        let temp_request = [
-            temp_cmd[0], temp_cmd[1], temp_cmd[2], temp_cmd[3], temp_cmd[4], temp_cmd[5],
-            (temp_crc & 0xFF) as u8, ((temp_crc >> 8) & 0xFF) as u8
-        ];
-        
-        // Turn on TX LED
-        
-        match uart.write(&temp_request) {
-            Ok(_) => {},
-            Err(e) => {
-                log::error!("TX Failed: {e:?}");
-                FreeRtos::delay_ms(5000);
-                continue;
-            }
-        }
-        
-        // Turn off TX LED after transmission
-        
-        FreeRtos::delay_ms(500);
-        
-        let mut temp_response = [0u8; 16];
-        let mut temperature_raw = None;
-        
-        match uart.read(&mut temp_response, 3000) {
-            Ok(bytes_read) => {
-                if bytes_read > 0 {
-                }
-                if bytes_read >= 7 {
-                    let response_crc = ((temp_response[6] as u16) << 8) | (temp_response[5] as u16);
-                    let calculated_crc = calculate_crc16(&temp_response[..5]);
-                    if response_crc == calculated_crc {
-                        temperature_raw = Some(((temp_response[3] as u16) << 8) | (temp_response[4] as u16));
-                    } else {
-                        log::warn!("CRC mismatch - temperature");
+    fn handle_measurement(&mut self, measurement: Measurement) {
+        match measurement {
+            Measurement::Sample { slave, primary, reading: Reading::Climate { temperature, humidity } } => {
+                let temperature = temperature + self.controller.settings.temp_offset;
+                let humidity = humidity + self.controller.settings.hum_offset;
+                if self.read_pending {
+                    println!("READ|slave={slave}|temperature={temperature:.2}|humidity={humidity:.2}");
+                }
+                if !(temperature > -50.0 && temperature < 100.0 && humidity > 0.0 && humidity < 100.0) {
+                    log::warn!("[{slave}] Invalid readings (T: {temperature:.1}°C, H: {humidity:.1}%) - skipped");
+                    return;
+                }
+                if primary {
+                    self.sensor_fault = None;
+                    self.control(slave, temperature, humidity);
+                } else if self.report_due(slave) {
+                    log::info!("[{slave}] T: {temperature:.1}°C, H: {humidity:.1}%");
+                    send_sensor_data(temperature, humidity, slave, None, &[]);
+                }
+            }
+            Measurement::Sample { slave, reading: Reading::Soil { moisture }, .. } => {
+                if self.read_pending {
+                    println!("READ|slave={slave}|soil_moisture={moisture:.1}");
+                }
+                self.set_aux(format!("soil_moisture_{slave}"), moisture);
+                if self.report_due(slave) {
+                    log::info!("[{slave}] Soil moisture: {moisture:.1}%");
+                    frame::emit(&format!("AUX_DATA|{}|soil_moisture={moisture:.1}|slave={slave}", clock::now_ns()));
+                }
+            }
+            Measurement::Failed { slave, primary, name, code, consecutive } => {
+                log::warn!("[{slave}] {name} read failed");
+                if self.read_pending {
+                    println!("READ|slave={slave}|error={name}_read_failed");
+                }
+                if primary {
+                    self.sensor_fault = led::Status::from_fault(&code);
+                    self.last_reading = None;
+                    self.publish_status();
+                    if consecutive >= config::SAFE_STATE_AFTER && !self.controller.in_safe_state() {
+                        self.enter_safe_state(&format!("sensor_fault|consecutive={consecutive}"));
                     }
                 }
             }
-            Err(e) => {
-                log::error!("RX Temperature error: {e:?}");
-            }
-        }
-        
-        // Turn off RX LED after processing
-
-        FreeRtos::delay_ms(100);
-
-        let hum_cmd = [
-            slave_addr, func_code, 0x00, 0x00, 0x00, 0x01
-        ];
-        let hum_crc = calculate_crc16(&hum_cmd);
-        let hum_request = [
-            hum_cmd[0], hum_cmd[1], hum_cmd[2], hum_cmd[3], hum_cmd[4], hum_cmd[5],
-            (hum_crc & 0xFF) as u8, ((hum_crc >> 8) & 0xFF) as u8
-        ];
-
-        if uart.write(&hum_request).is_err() {
-            FreeRtos::delay_ms(5000);
-            continue;
-        }
-        
-        FreeRtos::delay_ms(200);
-        
-        let mut hum_response = [0u8; 16];
-        let mut humidity_raw = None;
-        
-        if let Ok(bytes_read) = uart.read(&mut hum_response, 3000) {
-            if bytes_read >= 7 {
-                let response_crc = ((hum_response[6] as u16) << 8) | (hum_response[5] as u16);
-                let calculated_crc = calculate_crc16(&hum_response[..5]);
-                if response_crc == calculated_crc {
-                    humidity_raw = Some(((hum_response[3] as u16) << 8) | (hum_response[4] as u16));
-                }
-            }
-        }
-
-        if humidity_raw.is_none() {
-            FreeRtos::delay_ms(100);
-            let hum_cmd2 = [
-                slave_addr, func_code, 0x00, 0x02, 0x00, 0x01
-            ];
-            let hum_crc2 = calculate_crc16(&hum_cmd2);
-            let hum_request2 = [
-                hum_cmd2[0], hum_cmd2[1], hum_cmd2[2], hum_cmd2[3], hum_cmd2[4], hum_cmd2[5],
-                (hum_crc2 & 0xFF) as u8, ((hum_crc2 >> 8) & 0xFF) as u8
-            ];
-
-            if uart.write(&hum_request2).is_ok() {
-                FreeRtos::delay_ms(200);
-                if let Ok(bytes_read) = uart.read(&mut hum_response, 3000) {
-                    if bytes_read >= 7 {
-                        let response_crc = ((hum_response[6] as u16) << 8) | (hum_response[5] as u16);
-                        let calculated_crc = calculate_crc16(&hum_response[..5]);
-                        if response_crc == calculated_crc {
-                            humidity_raw = Some(((hum_response[3] as u16) << 8) | (hum_response[4] as u16));
-                        }
+            Measurement::Analog { name, raw } => match self.controller.settings.scale_analog(name, raw) {
+                Some(value) => {
+                    if self.read_pending {
+                        println!("READ|analog={name}|raw={raw}|value={value:.1}");
                     }
+                    self.set_aux(name.to_string(), value);
+                }
+                None => log::warn!("Unknown analog input {name}"),
+            },
+            Measurement::RoundDone { has_primary } => {
+                self.read_pending = false;
+                self.rounds = self.rounds.saturating_add(1);
+                // Dua putaran sensor penuh tanpa panic/reset -> image hasil OTA dianggap sehat
+                if self.rounds == 2 {
+                    ota::confirm_boot();
+                }
+                if !has_primary && !self.controller.in_safe_state() {
+                    self.enter_safe_state("no_sensor");
                 }
             }
         }
+    }
+
+    // Relay diputuskan setiap sampel; perubahan langsung dilaporkan tanpa menunggu REPORT_INTERVAL
+    fn control(&mut self, slave: u8, temperature: f32, humidity: f32) {
+        if self.controller.in_safe_state() {
+            log::info!("✅ Primary sensor back - leaving safe state");
+        }
+        let before = self.controller.state();
+        self.controller.control_relays(temperature, humidity);
+        self.relays.apply(&self.controller);
+        self.last_reading = Some((temperature, humidity));
+        let changed = self.controller.state() != before;
+        if changed {
+            send_relay_status(&self.controller);
+        }
+        // Threshold terlewati tapi belum terkonfirmasi: relay belum berganti
+        for pending in self.controller.pending() {
+            println!(
+                "RELAY_PENDING|{}={}|count={}/{}",
+                pending.relay.as_str(),
+                on_off(pending.want),
+                pending.count,
+                pending.confirm
+            );
+        }
+        self.publish_status();
+        if !self.report_due(slave) {
+            return;
+        }
+
+        log::info!("[{slave}] T: {temperature:.1}°C, H: {humidity:.1}%");
+        send_sensor_data(temperature, humidity, slave, Some(self.controller.fan_duty()), &self.aux);
+        Leds::blink_tx(&mut self.leds);
+        log::info!(
+            "Motor: {} ({}%), Pump: {}",
+            on_off(self.controller.fan_on()),
+            self.controller.fan_duty(),
+            on_off(self.controller.pump_on())
+        );
+        send_relay_status(&self.controller);
+        // Jam belum sinkron = timestamp tidak berarti setelah reboot, jangan disimpan
+        if let Some(backlog) = self.backlog.as_mut().filter(|_| clock::is_synced()) {
+            let record = Record {
+                timestamp: clock::now_ns(),
+                temperature,
+                humidity,
+                fan: self.controller.fan_on(),
+                pump: self.controller.pump_on(),
+            };
+            if let Err(e) = backlog.push(record) {
+                log::error!("Backlog write failed: {e:?}");
+            }
+        }
+        let _ = self.outbound.send(Outbound::Upload { temperature, humidity, relays: self.controller.state() });
+    }
 
-        match (temperature_raw, humidity_raw) {
-            (Some(temp_raw), Some(hum_raw)) => {
-                let temperature_offset = -1.2;
-                let humidity_offset = -6.5;
-                
-                let temperature = (temp_raw as f32 / 10.0) + temperature_offset;
-                let humidity = (hum_raw as f32 / 10.0) + humidity_offset;
-                
-                log::info!("T: {temperature:.1}°C, H: {humidity:.1}%");
-                
-                if temperature > -50.0 && temperature < 100.0 && humidity > 0.0 && humidity < 100.0 {
-                    send_sensor_data(temperature, humidity);
-                } else {
-                    log::warn!("Invalid readings - skipped");
+    // Relay AUTO ke state aman; sensor tetap dicoba setiap putaran dan kontrol normal
+    // kembali pada pembacaan valid berikutnya
+    fn enter_safe_state(&mut self, reason: &str) {
+        log::error!("🛑 Entering safe state: {reason}");
+        self.controller.enter_safe_state();
+        self.relays.apply(&self.controller);
+        println!("SAFE_STATE|{reason}");
+        send_relay_status(&self.controller);
+        self.publish_status();
+    }
+
+    // Tekan singkat = toggle dari state relay sekarang, tahan = kembali ke AUTO
+    fn handle_button(&mut self, relay: Relay, press: Press) {
+        let mode = match press {
+            Press::Short if self.controller.relay_on(relay) => Override::Off,
+            Press::Short => Override::On,
+            Press::Hold => Override::Auto,
+        };
+        log::info!("🔘 Button {}: {}", relay.as_str(), mode.as_str());
+        self.controller.set_local_override(relay, mode);
+        self.relays.apply(&self.controller);
+        send_relay_status(&self.controller);
+        self.publish_status();
+    }
+
+    // SET/UPLINK/BOARD yang diterima langsung disimpan ke NVS supaya bertahan setelah power cycle
+    // Perintah ber-id dibalas dengan id yang sama (ACK/NAK); efek sampingnya sama dengan perintah biasa
+    fn handle_command(&mut self, cmd: Command) {
+        let previous = self.controller.settings;
+        let (id, cmd) = cmd.untag();
+        let reply = match command::apply(&cmd, &mut self.controller, &mut self.uplink, &mut self.board) {
+            Ok(reply) => command::tag(id, &reply),
+            Err(nak) => {
+                let nak = command::tag(id, &nak);
+                println!("{nak}");
+                let _ = self.outbound.send(Outbound::Reply(nak));
+                return;
+            }
+        };
+        Leds::blink_rx(&mut self.leds);
+        println!("{reply}");
+        let _ = self.outbound.send(Outbound::Reply(reply));
+        match cmd {
+            // Backend mengirim ulang threshold setiap reconnect; flash hanya ditulis jika ada perubahan
+            Command::Set(_) if self.controller.settings != previous => {
+                if let Some(storage) = self.storage.as_mut() {
+                    if let Err(e) = storage.save(&self.controller.settings) {
+                        log::error!("NVS save failed: {e:?}");
+                    }
                 }
             }
-            (Some(temp_raw), None) => {
-                let temperature = (temp_raw as f32 / 10.0) - 1.2;
-                log::warn!("T: {temperature:.1}°C, H: N/A - incomplete data");
+            Command::Relay(_) | Command::FanDuty(_) => {
+                self.relays.apply(&self.controller);
+                send_relay_status(&self.controller);
+                self.publish_status();
+            }
+            Command::Uplink(selected) => {
+                let _ = self.outbound.send(Outbound::SetUplink(selected));
+                if let Some(storage) = self.storage.as_mut() {
+                    if let Err(e) = storage.save_uplink(selected) {
+                        log::error!("NVS save failed: {e:?}");
+                    }
+                }
             }
-            (None, Some(hum_raw)) => {
-                let humidity = (hum_raw as f32 / 10.0) - 6.5;
-                log::warn!("T: N/A, H: {humidity:.1}% - incomplete data");
+            // Download berjalan di task comms; relay tetap dikendalikan selama OTA
+            Command::Ota(url) => {
+                let _ = self.outbound.send(Outbound::Ota(url));
             }
-            (None, None) => {
-                log::warn!("Sensor read failed");
+            Command::Backlog(since) => {
+                // Hanya lewat serial: satu baris per record, diakhiri BACKLOG_END
+                let sent = self.backlog.as_ref().map_or(0, |backlog| {
+                    backlog.dump(since, |r| {
+                        println!(
+                            "BACKLOG_DATA|{}|{:.2}|{:.2}|fan={}|pump={}",
+                            r.timestamp, r.temperature, r.humidity, r.fan as u8, r.pump as u8
+                        )
+                    })
+                });
+                println!("BACKLOG_END|sent={sent}");
             }
+            Command::Board(values) if !values.is_empty() => {
+                if let Some(storage) = self.storage.as_mut() {
+                    if let Err(e) = storage.save_board(&self.board) {
+                        log::error!("NVS save failed: {e:?}");
+                    }
+                }
+            }
+            Command::Status => {
+                let status = self.status_line();
+                println!("{status}");
+                let _ = self.outbound.send(Outbound::Reply(status));
+            }
+            Command::Read => {
+                self.read_pending = true;
+                let _ = self.sensor_requests.send(SensorRequest::ReadNow);
+            }
+            Command::ModbusScan => {
+                let _ = self.sensor_requests.send(SensorRequest::ModbusScan);
+            }
+            Command::Board(_) | Command::Time(_) | Command::ConfigDump | Command::Hello | Command::Help | Command::Tagged(..) => {}
         }
+    }
+}
 
-        // Wait 10 seconds between readings for better time-series data
-        FreeRtos::delay_ms(10000);
+// fan_duty dan field tambahan (soil_moisture, water_level, ...) hanya ada pada frame sensor
+// utama (yang mengendalikan exhaust fan). Format extended:
+//   SENSOR_DATA|ts|t|h|slave=1|fan_duty=60|soil_moisture=41.2|water_level=80.0
+fn send_sensor_data(temperature: f32, humidity: f32, slave: u8, fan_duty: Option<u8>, aux: &[(String, f32)]) {
+    // Unix time setelah sinkron (SNTP / TIME|...), sebelum itu masih waktu sejak boot
+    let data = SensorData {
+        timestamp: clock::now_ns(),
+        temperature,
+        humidity,
+        exhaust_fan_status: None,
+        pump_status: None,
+        fan_duty: fan_duty.map(f32::from),
+        aux: aux.to_vec(),
+    };
+    let influx: String = fan_duty
+        .iter()
+        .map(|duty| format!(",fan_duty={duty}"))
+        .chain(aux.iter().map(|(name, value)| format!(",{name}={value:.1}")))
+        .collect();
+
+    // Output data ke serial untuk gateway
+    frame::emit(&data.to_frame(Some(slave)));
+    println!("INFLUX_LINE|sht20_sensor,slave={slave} temperature={temperature:.2},humidity={humidity:.2}{influx} {}", data.timestamp);
+}
+
+// Sensor dipilih saat build (SENSOR_DRIVER); sensor suhu/kelembaban pertama =
+// sensor utama untuk kontrol relay dan upload Wi-Fi. Bus RS485 ikut dikembalikan
+// untuk perintah konsol `modbus scan` (None jika driver bukan modbus).
+fn start_sensors(i2c0: I2C0, uart1: UART1, board: &Board) -> (Vec<Sensor>, Option<Rc<UartDriver<'static>>>) {
+    // SAFETY: nomor GPIO dari pin map tervalidasi; setiap pin hanya dipakai satu driver
+    let pin = |gpio: i32| unsafe { AnyIOPin::new(gpio) };
+    let i2c_config = I2cConfig::new().baudrate(100.kHz().into());
+    match config::SENSOR_DRIVER {
+        "sht3x" | "bme280" => {
+            let i2c = I2cDriver::new(i2c0, pin(board.i2c_sda), pin(board.i2c_scl), &i2c_config).unwrap();
+            log::info!("I2C ready - SDA=GPIO{}, SCL=GPIO{}", board.i2c_sda, board.i2c_scl);
+            let driver: Box<dyn SensorDriver> = if config::SENSOR_DRIVER == "sht3x" {
+                Box::new(Sht3x::new(i2c, sht3x::DEFAULT_ADDRESS))
+            } else {
+                Box::new(Bme280::new(i2c, bme280::DEFAULT_ADDRESS))
+            };
+            (vec![Sensor::new(driver)], None)
+        }
+        "dht22" => match Dht22::new(pin(board.dht)) {
+            Ok(dht) => (vec![Sensor::new(Box::new(dht))], None),
+            Err(e) => {
+                log::error!("DHT22 pin setup failed: {e:?}");
+                (Vec::new(), None)
+            }
+        },
+        other => {
+            if other != "modbus" {
+                log::warn!("Unknown SENSOR_DRIVER '{other}' - using modbus");
+            }
+            let config = UartConfig::new()
+                .baudrate(9600.into())
+                .data_bits(DataBits::DataBits8)
+                .stop_bits(StopBits::STOP1)
+                .flow_control(FlowControl::None);
+
+            let uart = UartDriver::new(
+                uart1,
+                pin(board.rs485_tx),
+                pin(board.rs485_rx),
+                Option::<gpio::Gpio0>::None,
+                Option::<gpio::Gpio0>::None,
+                &config,
+            ).unwrap();
+
+            log::info!("UART ready - RS485 9600 baud");
+
+            // Daftar slave RS485 (config.rs + scan saat boot)
+            let uart = Rc::new(uart);
+            let sensors = bus::discover(&uart).into_iter().map(|slave| Sensor::new(Box::new(slave))).collect();
+            (sensors, Some(uart))
+        }
+    }
+}
+
+// Percobaan baca sensor utama per bangun dalam mode baterai (DHT22 butuh jeda 2 detik)
+const BATTERY_READ_ATTEMPTS: u32 = 3;
+// Setelah frame terkirim, tunggu sebentar untuk balasan gateway (mis. TIME|<unix_ns>)
+const BATTERY_COMMAND_WINDOW: Duration = Duration::from_millis(1500);
+
+// Node logging bertenaga baterai: satu pembacaan per bangun, relay tidak dikendalikan,
+// lalu deep sleep. Fungsi ini tidak kembali.
+fn battery_cycle(peripherals: Peripherals, nvs: Option<EspDefaultNvsPartition>, storage: Option<Storage>, board: Board) -> ! {
+    let awake_since = Instant::now();
+    let wake = battery::wake();
+    let settings = storage.as_ref().map(Storage::load).unwrap_or_default();
+    // Controller hanya untuk offset kalibrasi dan field relay di upload (selalu OFF)
+    let mut controller = Controller::new(settings);
+    let mut uplink = storage
+        .as_ref()
+        .and_then(Storage::load_uplink)
+        .or_else(|| Uplink::parse(config::UPLINK))
+        .unwrap_or(Uplink::Influx);
+    log::info!("🔋 Battery mode wake #{wake} - relay control disabled");
+
+    let credentials = storage.as_ref().map(Storage::load_credentials).unwrap_or_default();
+    let (command_tx, commands) = mpsc::channel();
+    let mut network = match EspSystemEventLoop::take() {
+        Ok(sysloop) => Network::start(peripherals.modem, sysloop, nvs, credentials, uplink, command_tx.clone()),
+        Err(e) => {
+            log::error!("Event loop unavailable: {e:?}");
+            None
+        }
+    };
+    if let Err(e) = command::spawn_reader(peripherals.uart0, peripherals.pins.gpio1, peripherals.pins.gpio3, command_tx) {
+        log::error!("UART0 command reader failed: {e:?}");
+    }
+    // MQTT tersambung asinkron; beri waktu singkat sebelum upload
+    if let Some(network) = network.as_mut() {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while network.online() && !network.ready() && Instant::now() < deadline {
+            network.maintain();
+            FreeRtos::delay_ms(250);
+        }
+        network.maintain();
+    }
+
+    let (mut sensors, _) = start_sensors(peripherals.i2c0, peripherals.uart1, &board);
+    let mut primary_ok = false;
+    for sensor in sensors.iter_mut() {
+        let address = sensor.slave();
+        let is_primary = !primary_ok && sensor.name() != "soil";
+        for attempt in 1..=BATTERY_READ_ATTEMPTS {
+            match sensor.poll() {
+                Some(Reading::Climate { temperature, humidity }) => {
+                    let temperature = temperature + controller.settings.temp_offset;
+                    let humidity = humidity + controller.settings.hum_offset;
+                    log::info!("[{address}] T: {temperature:.1}°C, H: {humidity:.1}%");
+                    send_sensor_data(temperature, humidity, address, None, &[]);
+                    if is_primary {
+                        primary_ok = true;
+                        if let Some(network) = network.as_mut() {
+                            network.upload(temperature, humidity, &controller.state());
+                        }
+                    }
+                    break;
+                }
+                Some(Reading::Soil { moisture }) => {
+                    frame::emit(&format!("AUX_DATA|{}|soil_moisture={moisture:.1}|slave={address}", clock::now_ns()));
+                    break;
+                }
+                None if attempt < BATTERY_READ_ATTEMPTS => FreeRtos::delay_ms(2000),
+                None => log::warn!("[{address}] {} read failed", sensor.name()),
+            }
+        }
+    }
+    let failed = battery::record_result(primary_ok);
+    println!("SLEEP|wake={wake}|failed_wakes={failed}|next_s={}", battery::sleep_period().as_secs());
+
+    let mut board = board;
+    let deadline = Instant::now() + BATTERY_COMMAND_WINDOW;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        let (id, cmd) = match commands.recv_timeout(remaining) {
+            Ok(cmd) => cmd.untag(),
+            Err(_) => break,
+        };
+        match cmd {
+            // Hanya sinkron jam dan dump konfigurasi; perintah relay/konfigurasi butuh mode normal
+            Command::Time(_) | Command::ConfigDump | Command::Hello | Command::Help => {
+                match command::apply(&cmd, &mut controller, &mut uplink, &mut board) {
+                    Ok(reply) | Err(reply) => println!("{}", command::tag(id, &reply)),
+                }
+            }
+            _ => println!("{}", command::tag(id, "NAK|BATTERY|command unavailable in battery mode")),
+        }
+    }
+    // Beri waktu UART/MQTT mengirim sisa buffer sebelum radio dan CPU mati
+    FreeRtos::delay_ms(200);
+    battery::deep_sleep(awake_since)
+}
+
+// `board` = pin map aktif (dimuat saat boot); perubahan lewat BOARD baru berlaku setelah reboot
+fn read_sht20_sensor(
+    peripherals: Peripherals,
+    nvs: Option<EspDefaultNvsPartition>,
+    mut storage: Option<Storage>,
+    board: Board,
+    resets: u32,
+) {
+    // SAFETY: nomor GPIO dari pin map tervalidasi; setiap pin hanya dipakai satu driver
+    let output = |gpio: i32| unsafe { AnyOutputPin::new(gpio) };
+
+    // Setup relay controls for motor and pump
+    let fan_timer = LedcTimerDriver::new(peripherals.ledc.timer0, &TimerConfig::default().frequency(FAN_PWM_HZ.Hz())).unwrap();
+    let mut relays = Relays {
+        fan: LedcDriver::new(peripherals.ledc.channel0, fan_timer, output(board.fan)).unwrap(),
+        pump: PinDriver::output(output(board.pump)).unwrap(),
+        active_low: board.relay_low,
+    };
+
+    // Setup LED indicators for status
+    let leds = if board.leds {
+        Leds::start(&board).map_err(|e| log::error!("Status LED setup failed: {e:?}")).ok()
+    } else {
+        None
+    };
+
+    // Konfigurasi tersimpan di NVS; tanpa NVS pakai default hasil compile
+    let settings = storage.as_ref().map(Storage::load).unwrap_or_default();
+    let controller = Controller::new(settings);
+    let backlog = nvs.clone().and_then(|partition| match Backlog::open(partition) {
+        Ok(backlog) => Some(backlog),
+        Err(e) => {
+            log::error!("Backlog unavailable: {e:?}");
+            None
+        }
+    });
+    relays.apply(&controller);
+
+    // Perintah dari gateway lewat UART0 (dan topic MQTT .../cmd jika uplink mqtt)
+    let (command_tx, commands) = mpsc::channel();
+    let (outbound, outbound_rx) = mpsc::channel();
+    let (measurement_tx, measurements) = mpsc::channel();
+    let (sensor_requests, sensor_requests_rx) = mpsc::channel();
+    let bus_errors = Arc::new(AtomicU32::new(0));
+
+    let uplink = storage
+        .as_ref()
+        .and_then(Storage::load_uplink)
+        .or_else(|| Uplink::parse(config::UPLINK))
+        .unwrap_or(Uplink::Influx);
+    let credentials = storage.as_ref().map(Storage::load_credentials).unwrap_or_default();
+
+    if let Err(e) = recovery::start_watchdog() {
+        log::error!("Task watchdog unavailable: {e:?}");
+    }
+    recovery::watch_task("control");
+
+    if let Err(e) = command::spawn_reader(peripherals.uart0, peripherals.pins.gpio1, peripherals.pins.gpio3, command_tx.clone()) {
+        log::error!("UART0 command reader failed: {e:?}");
+    }
+    // Override relay dari PLC masuk antrian perintah yang sama dengan UART0/MQTT
+    if board.plc {
+        if let Err(e) = plc::spawn(peripherals.uart2, &board, command_tx.clone()) {
+            log::error!("PLC Modbus slave failed: {e:?}");
+        }
+    }
+
+    log::info!(
+        "Relay Control: Motor=GPIO{}, Pump=GPIO{} (active {})",
+        board.fan,
+        board.pump,
+        if board.relay_low { "low" } else { "high" }
+    );
+    if leds.is_some() {
+        log::info!("LED Status: TX=GPIO{}, RX=GPIO{}", board.led_tx, board.led_rx);
+    }
+
+    let comms = CommsConfig { board, nvs, credentials, uplink, commands: command_tx, resets, bus_errors: bus_errors.clone() };
+    if let Err(e) = tasks::spawn_comms_task(peripherals.modem, peripherals.i2c1, comms, outbound_rx) {
+        log::error!("Comms task failed to start: {e:?}");
+        println!("MODE|serial|reason=wifi_init_failed");
+    }
+
+    let mut buttons = Buttons::start(&board);
+    let mut control = Control {
+        controller,
+        relays,
+        storage,
+        backlog,
+        leds,
+        uplink,
+        board,
+        outbound,
+        sensor_requests,
+        read_pending: false,
+        last_reading: None,
+        sensor_fault: None,
+        aux: Vec::new(),
+        last_report: Vec::new(),
+        rounds: 0,
+    };
+    let sensor_task = tasks::spawn_sensor_task(
+        peripherals.i2c0,
+        peripherals.uart1,
+        peripherals.adc1,
+        board,
+        measurement_tx,
+        sensor_requests_rx,
+        bus_errors,
+    );
+    if let Err(e) = sensor_task {
+        log::error!("Sensor task failed to start: {e:?}");
+        control.enter_safe_state("no_sensor");
+    }
+    control.publish_status();
+
+    loop {
+        recovery::feed();
+        for (relay, press) in buttons.poll() {
+            control.handle_button(relay, press);
+        }
+        while let Ok(measurement) = measurements.try_recv() {
+            control.handle_measurement(measurement);
+        }
+        control.update_leds();
+        match commands.recv_timeout(CONTROL_TICK) {
+            Ok(cmd) => control.handle_command(cmd),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => FreeRtos::delay_ms(CONTROL_TICK.as_millis() as u32),
+        }
     }
 }
 
@@ -232,9 +732,33 @@ fn main() {
     esp_idf_svc::sys::link_patches();
     esp_idf_svc::log::EspLogger::initialize_default();
 
-    log::info!("SHT20 Data Logger - Serial Gateway Mode");
+    log::info!("SHT20 Data Logger - Wi-Fi with serial gateway fallback");
     log::info!("Serial output every 10 seconds");
 
     let peripherals = Peripherals::take().unwrap();
-    read_sht20_sensor(peripherals);
+    let nvs = match EspDefaultNvsPartition::take() {
+        Ok(nvs) => Some(nvs),
+        Err(e) => {
+            log::error!("NVS unavailable: {e:?}");
+            None
+        }
+    };
+    let resets = recovery::report_boot_reason(nvs.as_ref());
+    // Konfigurasi + pin map tersimpan di NVS; tanpa NVS pakai default hasil compile
+    let storage = nvs.clone().and_then(|partition| match Storage::open(partition) {
+        Ok(storage) => Some(storage),
+        Err(e) => {
+            log::error!("NVS open failed: {e:?}");
+            None
+        }
+    });
+    let board = storage.as_ref().map(Storage::load_board).unwrap_or_default();
+    recovery::install_panic_hook(nvs.clone(), &board);
+    if provision::requested(&board) {
+        provision::run(peripherals.modem, nvs, storage);
+    }
+    if battery::enabled() {
+        battery_cycle(peripherals, nvs, storage, board);
+    }
+    read_sht20_sensor(peripherals, nvs, storage, board, resets);
 }
\ No newline at end of file
@@ -0,0 +1,221 @@
+use esp_idf_svc::hal::delay::TickType;
+use esp_idf_svc::hal::uart::UartDriver;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// Modbus RTU master sederhana untuk slave di RS485 (UART1)
+pub const READ_HOLDING_REGISTERS: u8 = 0x03;
+pub const READ_INPUT_REGISTERS: u8 = 0x04;
+
+// Register input SHT20: 0x0001 suhu, 0x0002 kelembaban (0x0000 juga kelembaban di sebagian modul)
+pub const REG_TEMPERATURE: u16 = 0x0001;
+pub const REG_HUMIDITY: u16 = 0x0002;
+pub const REG_HUMIDITY_ALT: u16 = 0x0000;
+
+// Batas menunggu frame respons lengkap; scan alamat memakai timeout pendek
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(1000);
+const PROBE_TIMEOUT: Duration = Duration::from_millis(200);
+
+// Retry per transaksi dengan backoff 100, 200, 400 ms (maks. 1 s)
+const MAX_ATTEMPTS: u32 = 4;
+const BACKOFF_BASE: Duration = Duration::from_millis(100);
+const BACKOFF_MAX: Duration = Duration::from_millis(1000);
+
+// Kode exception Modbus yang layak di-retry (slave sibuk / gagal sementara)
+const EXCEPTION_SLAVE_BUSY: u8 = 0x06;
+const EXCEPTION_DEVICE_FAILURE: u8 = 0x04;
+
+pub fn calculate_crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            if crc & 0x0001 != 0 {
+                crc = (crc >> 1) ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+#[derive(Debug)]
+pub enum ModbusError {
+    Tx(String),
+    Timeout { received: usize },
+    Crc,
+    UnexpectedReply,
+    // Respons function | 0x80 dari slave, berisi kode exception
+    Exception(u8),
+}
+
+impl ModbusError {
+    // Kode singkat untuk baris SENSOR_FAULT|<code>
+    pub fn code(&self) -> String {
+        match self {
+            ModbusError::Tx(_) => "tx".to_string(),
+            ModbusError::Timeout { received: 0 } => "no_response".to_string(),
+            ModbusError::Timeout { .. } => "timeout".to_string(),
+            ModbusError::Crc => "crc".to_string(),
+            ModbusError::UnexpectedReply => "unexpected_reply".to_string(),
+            ModbusError::Exception(code) => format!("exception_{code:02x}"),
+        }
+    }
+
+    // Exception "illegal function/address/value" tidak akan berubah dengan retry
+    fn retryable(&self) -> bool {
+        match self {
+            ModbusError::Exception(code) => matches!(*code, EXCEPTION_SLAVE_BUSY | EXCEPTION_DEVICE_FAILURE),
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for ModbusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModbusError::Tx(e) => write!(f, "TX failed: {e}"),
+            ModbusError::Timeout { received } => write!(f, "timeout ({received} bytes received)"),
+            ModbusError::Crc => write!(f, "CRC mismatch"),
+            ModbusError::UnexpectedReply => write!(f, "unexpected reply"),
+            ModbusError::Exception(code) => write!(f, "exception 0x{code:02X}"),
+        }
+    }
+}
+
+fn request(slave: u8, function: u8, start: u16, count: u16) -> [u8; 8] {
+    let [start_hi, start_lo] = start.to_be_bytes();
+    let [count_hi, count_lo] = count.to_be_bytes();
+    let body = [slave, function, start_hi, start_lo, count_hi, count_lo];
+    let [crc_lo, crc_hi] = calculate_crc16(&body).to_le_bytes();
+    [body[0], body[1], body[2], body[3], body[4], body[5], crc_lo, crc_hi]
+}
+
+// Baca sampai `buf` penuh atau timeout; UART bisa mengirim satu frame dalam beberapa potongan
+fn read_exact(uart: &UartDriver, buf: &mut [u8], timeout: Duration) -> Result<(), ModbusError> {
+    let deadline = Instant::now() + timeout;
+    let mut received = 0;
+    while received < buf.len() {
+        let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+            return Err(ModbusError::Timeout { received });
+        };
+        let ticks = TickType::new_millis(remaining.as_millis() as u64).ticks();
+        match uart.read(&mut buf[received..], ticks) {
+            Ok(0) => return Err(ModbusError::Timeout { received }),
+            Ok(n) => received += n,
+            Err(_) => return Err(ModbusError::Timeout { received }),
+        }
+    }
+    Ok(())
+}
+
+fn transact(
+    uart: &UartDriver,
+    slave: u8,
+    function: u8,
+    start: u16,
+    count: u16,
+    timeout: Duration,
+) -> Result<Vec<u16>, ModbusError> {
+    let _ = uart.clear_rx();
+    uart.write(&request(slave, function, start, count)).map_err(|e| ModbusError::Tx(format!("{e:?}")))?;
+
+    // addr + func + (byte count | exception code) dulu, sisanya tergantung jenis respons
+    let mut header = [0u8; 3];
+    read_exact(uart, &mut header, timeout)?;
+    if header[0] != slave {
+        return Err(ModbusError::UnexpectedReply);
+    }
+    if header[1] == function | 0x80 {
+        let mut crc = [0u8; 2];
+        read_exact(uart, &mut crc, timeout)?;
+        if u16::from_le_bytes(crc) != calculate_crc16(&header) {
+            return Err(ModbusError::Crc);
+        }
+        return Err(ModbusError::Exception(header[2]));
+    }
+    if header[1] != function || header[2] as usize != 2 * count as usize {
+        return Err(ModbusError::UnexpectedReply);
+    }
+
+    // data + crc
+    let mut frame = vec![0u8; 3 + 2 * count as usize + 2];
+    frame[..3].copy_from_slice(&header);
+    read_exact(uart, &mut frame[3..], timeout)?;
+    let (body, crc) = frame.split_at(frame.len() - 2);
+    if u16::from_le_bytes([crc[0], crc[1]]) != calculate_crc16(body) {
+        return Err(ModbusError::Crc);
+    }
+    Ok(body[3..].chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect())
+}
+
+// Baca `count` register berurutan mulai `start`, dengan retry + backoff
+fn read_registers(uart: &UartDriver, slave: u8, function: u8, start: u16, count: u16) -> Result<Vec<u16>, ModbusError> {
+    let mut backoff = BACKOFF_BASE;
+    let mut attempt = 1;
+    loop {
+        match transact(uart, slave, function, start, count, RESPONSE_TIMEOUT) {
+            Ok(values) => return Ok(values),
+            Err(e) if attempt < MAX_ATTEMPTS && e.retryable() => {
+                log::warn!("Modbus 0x{start:04X} attempt {attempt}/{MAX_ATTEMPTS} failed: {e} - retry in {}ms", backoff.as_millis());
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(BACKOFF_MAX);
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub fn read_input_registers(uart: &UartDriver, slave: u8, start: u16, count: u16) -> Result<Vec<u16>, ModbusError> {
+    read_registers(uart, slave, READ_INPUT_REGISTERS, start, count)
+}
+
+pub fn read_holding_registers(uart: &UartDriver, slave: u8, start: u16, count: u16) -> Result<Vec<u16>, ModbusError> {
+    read_registers(uart, slave, READ_HOLDING_REGISTERS, start, count)
+}
+
+// Satu percobaan singkat tanpa retry, untuk scan alamat saat boot. Exception pun
+// berarti ada slave yang menjawab di alamat itu.
+pub fn probe(uart: &UartDriver, slave: u8, function: u8, register: u16) -> bool {
+    matches!(
+        transact(uart, slave, function, register, 1, PROBE_TIMEOUT),
+        Ok(_) | Err(ModbusError::Exception(_))
+    )
+}
+
+// Hasil satu siklus baca; error terakhir dipakai untuk SENSOR_FAULT
+pub struct Sht20Reading {
+    pub temperature: Option<u16>,
+    pub humidity: Option<u16>,
+    pub error: Option<ModbusError>,
+}
+
+// Suhu + kelembaban dalam satu transaksi (register 0x0001-0x0002); baca satu per
+// satu seperti sebelumnya hanya jika transaksi gabungan gagal
+pub fn read_sht20(uart: &UartDriver, slave: u8) -> Sht20Reading {
+    let block_error = match read_input_registers(uart, slave, REG_TEMPERATURE, 2) {
+        Ok(values) => return Sht20Reading { temperature: Some(values[0]), humidity: Some(values[1]), error: None },
+        Err(e) => {
+            log::warn!("Modbus block read failed: {e} - falling back to single registers");
+            e
+        }
+    };
+    // Slave tidak menjawab sama sekali: single read juga pasti gagal, jangan buang waktu bus
+    if let ModbusError::Timeout { received: 0 } = block_error {
+        return Sht20Reading { temperature: None, humidity: None, error: Some(block_error) };
+    }
+    let mut error = None;
+    let mut read = |register: u16| match read_input_registers(uart, slave, register, 1) {
+        Ok(values) => values.first().copied(),
+        Err(e) => {
+            log::warn!("Modbus read 0x{register:04X} failed: {e}");
+            error = Some(e);
+            None
+        }
+    };
+    let temperature = read(REG_TEMPERATURE);
+    let humidity = read(REG_HUMIDITY_ALT).or_else(|| read(REG_HUMIDITY));
+    Sht20Reading { temperature, humidity, error }
+}
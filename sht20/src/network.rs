@@ -0,0 +1,382 @@
+use anyhow::{anyhow, bail, Result};
+use embedded_io::Write;
+use embedded_svc::http::client::Client;
+use embedded_svc::http::Method;
+use esp_idf_svc::eventloop::EspSystemEventLoop;
+use esp_idf_svc::hal::modem::Modem;
+use esp_idf_svc::http::client::{Configuration as HttpConfiguration, EspHttpConnection};
+use esp_idf_svc::mqtt::client::{EspMqttClient, EventPayload, MqttClientConfiguration, QoS};
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sntp::{EspSntp, SyncStatus};
+use esp_idf_svc::wifi::{AuthMethod, BlockingWifi, ClientConfiguration, Configuration as WifiConfiguration, EspWifi};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::clock;
+use crate::command::{self, Command};
+use crate::config;
+use crate::control::RelayState;
+use crate::led;
+use crate::provision::Credentials;
+
+// Mode aktif diumumkan lewat serial supaya gateway/teknisi tahu jalur data mana yang dipakai:
+//   MODE|wifi|ip=192.168.1.20
+//   MODE|serial|reason=...
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Wifi,
+    Serial,
+}
+
+// Jalur telemetry saat Wi-Fi aktif: HTTP langsung ke InfluxDB, atau publish ke
+// broker MQTT lokal (tanpa gateway Linux sama sekali)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Uplink {
+    Influx = 0,
+    Mqtt = 1,
+}
+
+impl Uplink {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "influx" | "influxdb" => Some(Uplink::Influx),
+            "mqtt" => Some(Uplink::Mqtt),
+            _ => None,
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Uplink::Influx),
+            1 => Some(Uplink::Mqtt),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Uplink::Influx => "influx",
+            Uplink::Mqtt => "mqtt",
+        }
+    }
+}
+
+fn topic(suffix: &str) -> String {
+    format!("{}/{}/{suffix}", config::MQTT_TOPIC_PREFIX, config::DEVICE_ID)
+}
+
+pub struct Network {
+    wifi: BlockingWifi<EspWifi<'static>>,
+    mode: Mode,
+    failures: u32,
+    last_attempt: Instant,
+    uplink: Uplink,
+    mqtt: Option<EspMqttClient<'static>>,
+    mqtt_connected: Arc<AtomicBool>,
+    subscribed: bool,
+    sntp: Option<EspSntp<'static>>,
+    // Perintah dari topic .../cmd masuk ke antrean yang sama dengan UART0
+    commands: Sender<Command>,
+    // Dari NVS (provisioning) atau default config.rs
+    credentials: Credentials,
+}
+
+fn announce_serial(reason: &str) {
+    println!("MODE|serial|reason={reason}");
+}
+
+impl Network {
+    // None = belum ada SSID (provisioning/config.rs) atau driver Wi-Fi gagal; firmware tetap jalan serial-only
+    pub fn start(
+        modem: Modem,
+        sysloop: EspSystemEventLoop,
+        nvs: Option<EspDefaultNvsPartition>,
+        credentials: Credentials,
+        uplink: Uplink,
+        commands: Sender<Command>,
+    ) -> Option<Self> {
+        if credentials.ssid.is_empty() {
+            announce_serial("no_wifi_config");
+            return None;
+        }
+        let mut network = match Self::init(modem, sysloop, nvs, credentials, uplink, commands) {
+            Ok(network) => network,
+            Err(e) => {
+                log::error!("❌ WiFi init failed: {e:?}");
+                announce_serial("wifi_init_failed");
+                return None;
+            }
+        };
+        network.connect();
+        Some(network)
+    }
+
+    fn init(
+        modem: Modem,
+        sysloop: EspSystemEventLoop,
+        nvs: Option<EspDefaultNvsPartition>,
+        credentials: Credentials,
+        uplink: Uplink,
+        commands: Sender<Command>,
+    ) -> Result<Self> {
+        let mut wifi = BlockingWifi::wrap(EspWifi::new(modem, sysloop.clone(), nvs)?, sysloop)?;
+        wifi.set_configuration(&WifiConfiguration::Client(ClientConfiguration {
+            ssid: credentials.ssid.as_str().try_into().map_err(|_| anyhow!("SSID too long"))?,
+            password: credentials.password.as_str().try_into().map_err(|_| anyhow!("password too long"))?,
+            auth_method: if credentials.password.is_empty() { AuthMethod::None } else { AuthMethod::WPA2Personal },
+            ..Default::default()
+        }))?;
+        wifi.start()?;
+        Ok(Self {
+            wifi,
+            mode: Mode::Serial,
+            failures: 0,
+            last_attempt: Instant::now(),
+            uplink,
+            mqtt: None,
+            mqtt_connected: Arc::new(AtomicBool::new(false)),
+            subscribed: false,
+            sntp: None,
+            commands,
+            credentials,
+        })
+    }
+
+    fn connect(&mut self) {
+        self.last_attempt = Instant::now();
+        log::info!("📡 Connecting to WiFi '{}'...", self.credentials.ssid);
+        // Bisa saja Wi-Fi masih tersambung dan hanya InfluxDB yang sempat tidak terjangkau
+        let result = if self.wifi.is_connected().unwrap_or(false) {
+            Ok(())
+        } else {
+            led::set_wifi_connecting(true);
+            let result = self.wifi.connect().and_then(|_| self.wifi.wait_netif_up());
+            led::set_wifi_connecting(false);
+            result
+        };
+        match result {
+            Ok(()) => {
+                let ip = self.wifi.wifi().sta_netif().get_ip_info().map(|info| info.ip.to_string()).unwrap_or_default();
+                log::info!("✅ WiFi connected, IP {ip}");
+                self.mode = Mode::Wifi;
+                self.failures = 0;
+                println!("MODE|wifi|ip={ip}|uplink={}", self.uplink.as_str());
+                if self.sntp.is_none() {
+                    match EspSntp::new_default() {
+                        Ok(sntp) => self.sntp = Some(sntp),
+                        Err(e) => log::warn!("SNTP start failed: {e:?}"),
+                    }
+                }
+                if self.uplink == Uplink::Mqtt {
+                    self.start_mqtt();
+                }
+            }
+            Err(e) => {
+                log::warn!("❌ WiFi connect failed: {e:?} - continuing in serial-only mode");
+                let _ = self.wifi.disconnect();
+                self.degrade("wifi_unavailable");
+            }
+        }
+    }
+
+    fn degrade(&mut self, reason: &str) {
+        if self.mode == Mode::Wifi {
+            log::warn!("🔄 Falling back to serial-only mode: {reason}");
+        }
+        self.mode = Mode::Serial;
+        self.stop_mqtt();
+        announce_serial(reason);
+    }
+
+    fn start_mqtt(&mut self) {
+        if self.mqtt.is_some() {
+            return;
+        }
+        let mqtt_config = MqttClientConfiguration {
+            client_id: Some(config::DEVICE_ID),
+            username: (!config::MQTT_USERNAME.is_empty()).then_some(config::MQTT_USERNAME),
+            password: (!config::MQTT_PASSWORD.is_empty()).then_some(config::MQTT_PASSWORD),
+            keep_alive_interval: Some(Duration::from_secs(30)),
+            ..Default::default()
+        };
+        let connected = self.mqtt_connected.clone();
+        let commands = self.commands.clone();
+        let command_topic = topic("cmd");
+        let client = EspMqttClient::new_cb(&self.credentials.mqtt_url, &mqtt_config, move |event| match event.payload() {
+            EventPayload::Connected(_) => connected.store(true, Ordering::Relaxed),
+            EventPayload::Disconnected => connected.store(false, Ordering::Relaxed),
+            EventPayload::Received { topic: Some(topic), data, .. } if topic == command_topic => {
+                match command::parse(&String::from_utf8_lossy(data)) {
+                    Ok(command) => {
+                        let _ = commands.send(command);
+                    }
+                    Err(e) => log::warn!("📭 Ignoring MQTT command: {e}"),
+                }
+            }
+            _ => {}
+        });
+        match client {
+            Ok(client) => {
+                log::info!("📡 MQTT client started: {}", self.credentials.mqtt_url);
+                self.mqtt = Some(client);
+                self.subscribed = false;
+            }
+            Err(e) => log::error!("❌ MQTT client failed: {e:?}"),
+        }
+    }
+
+    fn stop_mqtt(&mut self) {
+        self.mqtt = None;
+        self.subscribed = false;
+        self.mqtt_connected.store(false, Ordering::Relaxed);
+    }
+
+    pub fn online(&self) -> bool {
+        self.mode == Mode::Wifi
+    }
+
+    // Uplink siap menerima upload (MQTT butuh broker tersambung, tidak seperti HTTP Influx)
+    pub fn ready(&self) -> bool {
+        self.online() && (self.uplink == Uplink::Influx || self.mqtt_connected.load(Ordering::Relaxed))
+    }
+
+    // Kekuatan sinyal AP (dBm); None di mode serial
+    pub fn rssi(&self) -> Option<i32> {
+        if self.mode != Mode::Wifi {
+            return None;
+        }
+        self.wifi.wifi().get_rssi().ok()
+    }
+
+    // Ganti uplink saat runtime (perintah UPLINK); langsung berlaku jika Wi-Fi aktif
+    pub fn set_uplink(&mut self, uplink: Uplink) {
+        self.uplink = uplink;
+        self.failures = 0;
+        match uplink {
+            Uplink::Mqtt if self.mode == Mode::Wifi => self.start_mqtt(),
+            Uplink::Mqtt => {}
+            Uplink::Influx => self.stop_mqtt(),
+        }
+    }
+
+    // Balasan ACK/NAK juga dikirim ke topic .../ack supaya pengirim via MQTT menerimanya
+    pub fn publish_reply(&mut self, reply: &str) {
+        if !self.mqtt_connected.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Some(client) = self.mqtt.as_mut() {
+            if let Err(e) = client.enqueue(&topic("ack"), QoS::AtLeastOnce, false, reply.as_bytes()) {
+                log::warn!("MQTT ack publish failed: {e:?}");
+            }
+        }
+    }
+
+    // Dipanggil setiap siklus: deteksi Wi-Fi putus dan coba sambung lagi secara berkala
+    pub fn maintain(&mut self) {
+        if !clock::is_synced() && self.sntp.as_ref().is_some_and(|sntp| sntp.get_sync_status() == SyncStatus::Completed) {
+            clock::mark_synced("sntp");
+            println!("TIME_SYNC|sntp|{}", clock::now_ns());
+        }
+        let connected = self.mqtt_connected.load(Ordering::Relaxed);
+        if !connected {
+            self.subscribed = false;
+        }
+        if let Some(client) = self.mqtt.as_mut().filter(|_| connected && !self.subscribed) {
+            match client.subscribe(&topic("cmd"), QoS::AtLeastOnce) {
+                Ok(_) => {
+                    log::info!("✅ MQTT subscribed to {}", topic("cmd"));
+                    self.subscribed = true;
+                }
+                Err(e) => log::warn!("MQTT subscribe failed: {e:?}"),
+            }
+        }
+        match self.mode {
+            Mode::Wifi if !self.wifi.is_connected().unwrap_or(false) => self.degrade("wifi_disconnected"),
+            Mode::Serial if self.last_attempt.elapsed() >= Duration::from_secs(config::WIFI_RETRY_SECS) => self.connect(),
+            _ => {}
+        }
+    }
+
+    // Upload lewat uplink aktif; output serial tetap jalan di kedua mode
+    pub fn upload(&mut self, temperature: f32, humidity: f32, relays: &RelayState) {
+        if self.mode != Mode::Wifi {
+            return;
+        }
+        let result = match self.uplink {
+            // Sebelum jam tersinkron timestamp dikosongkan, InfluxDB memakai waktu server
+            Uplink::Influx => write_influx(&self.credentials, &format!(
+                "sht20_sensor,device={},firmware={} temperature={temperature:.2},humidity={humidity:.2},fan_duty={}{}",
+                config::DEVICE_ID,
+                env!("CARGO_PKG_VERSION"),
+                relays.fan_duty,
+                if clock::is_synced() { format!(" {}", clock::now_ns()) } else { String::new() }
+            )),
+            // Field sama dengan pemetaan default [mqtt_source] di backend
+            Uplink::Mqtt => self.publish_telemetry(&format!(
+                "{{\"temperature\":{temperature:.2},\"humidity\":{humidity:.2},\"exhaust_fan_status\":{},\"fan_duty\":{},\"pump_status\":{}}}",
+                u8::from(relays.fan_on),
+                relays.fan_duty,
+                u8::from(relays.pump_on)
+            )),
+        };
+        match result {
+            Ok(()) => self.failures = 0,
+            Err(e) => {
+                self.failures += 1;
+                log::error!(
+                    "❌ {} upload failed ({}/{}): {e:?}",
+                    self.uplink.as_str(),
+                    self.failures,
+                    config::UPLOAD_FAILURES_BEFORE_FALLBACK
+                );
+                if self.failures >= config::UPLOAD_FAILURES_BEFORE_FALLBACK {
+                    self.degrade(match self.uplink {
+                        Uplink::Influx => "influx_unreachable",
+                        Uplink::Mqtt => "mqtt_unreachable",
+                    });
+                }
+            }
+        }
+    }
+
+    fn publish_telemetry(&mut self, payload: &str) -> Result<()> {
+        if !self.mqtt_connected.load(Ordering::Relaxed) {
+            bail!("MQTT broker not connected");
+        }
+        let client = self.mqtt.as_mut().ok_or_else(|| anyhow!("MQTT client not started"))?;
+        client.enqueue(&topic("telemetry"), QoS::AtMostOnce, false, payload.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn write_influx(credentials: &Credentials, line: &str) -> Result<()> {
+    let url = format!(
+        "{}/api/v2/write?org={}&bucket={}&precision=ns",
+        credentials.influx_url,
+        config::INFLUX_ORG,
+        config::INFLUX_BUCKET
+    );
+    let connection = EspHttpConnection::new(&HttpConfiguration {
+        timeout: Some(Duration::from_secs(5)),
+        ..Default::default()
+    })?;
+    let mut client = Client::wrap(connection);
+    let auth = format!("Token {}", credentials.influx_token);
+    let length = line.len().to_string();
+    let headers = [
+        ("Authorization", auth.as_str()),
+        ("Content-Type", "text/plain; charset=utf-8"),
+        ("Content-Length", length.as_str()),
+    ];
+    let mut request = client.request(Method::Post, &url, &headers)?;
+    request.write_all(line.as_bytes())?;
+    request.flush()?;
+    let response = request.submit()?;
+    let status = response.status();
+    if !(200..300).contains(&status) {
+        bail!("InfluxDB returned HTTP {status}");
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use anyhow::{bail, Result};
+use embedded_io::Read;
+use embedded_svc::http::client::Client;
+use embedded_svc::http::Method;
+use esp_idf_svc::hal::reset;
+use esp_idf_svc::http::client::{Configuration as HttpConfiguration, EspHttpConnection};
+use esp_idf_svc::ota::{EspOta, SlotState};
+use std::time::Duration;
+
+// Update firmware lewat HTTP (slot ota_0/ota_1, lihat partitions.csv). Image baru
+// boot dalam status "pending verify"; jika tidak sampai confirm_boot() (panic,
+// reset, hang) bootloader otomatis kembali ke image sebelumnya.
+
+// Dipanggil setelah dua putaran sensor diproses task kontrol tanpa masalah
+pub fn confirm_boot() {
+    let mut ota = match EspOta::new() {
+        Ok(ota) => ota,
+        Err(e) => {
+            log::warn!("OTA unavailable: {e:?}");
+            return;
+        }
+    };
+    match ota.get_running_slot() {
+        Ok(slot) if slot.state == SlotState::Unverified => match ota.mark_running_slot_valid() {
+            Ok(()) => {
+                log::info!("✅ New firmware confirmed in slot {}", slot.label);
+                println!("OTA_STATUS|confirmed|slot={}", slot.label);
+            }
+            Err(e) => log::error!("❌ OTA confirm failed: {e:?}"),
+        },
+        Ok(_) => {}
+        Err(e) => log::warn!("OTA slot query failed: {e:?}"),
+    }
+}
+
+// Unduh image ke slot berikutnya lalu restart; tidak kembali jika sukses
+pub fn update_from_url(url: &str) -> Result<()> {
+    println!("OTA_STATUS|downloading|url={url}");
+    let connection = EspHttpConnection::new(&HttpConfiguration {
+        timeout: Some(Duration::from_secs(30)),
+        buffer_size: Some(4096),
+        ..Default::default()
+    })?;
+    let mut client = Client::wrap(connection);
+    let mut response = client.request(Method::Get, url, &[])?.submit()?;
+    let status = response.status();
+    if status != 200 {
+        bail!("HTTP {status}");
+    }
+
+    let mut ota = EspOta::new()?;
+    let mut update = ota.initiate_update()?;
+    let mut buf = [0u8; 4096];
+    let mut total = 0usize;
+    loop {
+        let n = match response.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) => {
+                let _ = update.abort();
+                bail!("download failed after {total} bytes: {e:?}");
+            }
+        };
+        if n == 0 {
+            break;
+        }
+        if let Err(e) = update.write(&buf[..n]) {
+            let _ = update.abort();
+            bail!("flash write failed after {total} bytes: {e:?}");
+        }
+        total += n;
+        // Download image ~1.5 MB bisa lebih lama dari timeout watchdog
+        crate::recovery::feed();
+        if total % (64 * 1024) < n {
+            println!("OTA_STATUS|progress|bytes={total}");
+        }
+    }
+    // complete() memvalidasi image dan mengganti boot partition
+    update.complete()?;
+    println!("OTA_STATUS|done|bytes={total}|rebooting");
+    log::info!("🔄 OTA complete ({total} bytes), restarting");
+    reset::restart();
+}
@@ -0,0 +1,264 @@
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::thread;
+
+use esp_idf_svc::hal::delay::{TickType, BLOCK};
+use esp_idf_svc::hal::gpio::{self, AnyIOPin};
+use esp_idf_svc::hal::uart::config::{DataBits, FlowControl, StopBits};
+use esp_idf_svc::hal::uart::{UartConfig, UartDriver, UART2};
+
+use crate::board::Board;
+use crate::command::Command;
+use crate::config;
+use crate::control::{Override, Relay, RelayState};
+use crate::modbus::{calculate_crc16, READ_HOLDING_REGISTERS, READ_INPUT_REGISTERS};
+
+// Modbus RTU slave di UART2 (RS485 kedua) supaya PLC bisa mem-poll node langsung. Bus master
+// ke SHT20 (UART1) tidak terpengaruh: task ini hanya membaca salinan state dari task kontrol
+// dan meneruskan tulisan override relay sebagai perintah RELAY biasa.
+//
+// Input register (FC 0x04):
+//   0 suhu x10 (°C, signed), 1 kelembaban x10 (%RH); 0x8000 = belum ada pembacaan valid
+//   2 exhaust fan ON, 3 pompa ON, 4 duty fan (%), 5 safe state, 6 pembacaan valid
+// Holding register (FC 0x03 baca, 0x06 / 0x10 tulis):
+//   0 mode fan, 1 mode pompa: 0 = AUTO, 1 = ON, 2 = OFF
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+const EXCEPTION_ILLEGAL_FUNCTION: u8 = 0x01;
+const EXCEPTION_ILLEGAL_ADDRESS: u8 = 0x02;
+const EXCEPTION_ILLEGAL_VALUE: u8 = 0x03;
+
+const INPUT_REGISTERS: u16 = 7;
+const HOLDING_REGISTERS: u16 = 2;
+const NO_READING: u16 = 0x8000;
+
+// Jeda antar frame: 3.5 karakter di 9600 baud ~4 ms, dibulatkan ke tick FreeRTOS
+const FRAME_GAP_MS: u64 = 20;
+const MAX_FRAME: usize = 256;
+
+// Salinan state untuk register, diperbarui task kontrol setiap publish_status
+#[derive(Debug, Clone, Copy)]
+pub struct Image {
+    pub reading: Option<(f32, f32)>,
+    pub relays: RelayState,
+    pub fan_mode: Override,
+    pub pump_mode: Override,
+}
+
+static IMAGE: Mutex<Image> = Mutex::new(Image {
+    reading: None,
+    relays: RelayState { fan_duty: 0, fan_on: false, pump_on: false, safe_state: false },
+    fan_mode: Override::Auto,
+    pump_mode: Override::Auto,
+});
+
+pub fn update(image: Image) {
+    if let Ok(mut current) = IMAGE.lock() {
+        *current = image;
+    }
+}
+
+fn snapshot() -> Image {
+    IMAGE.lock().map(|image| *image).unwrap_or_else(|e| *e.into_inner())
+}
+
+impl Image {
+    fn input(&self, register: u16) -> u16 {
+        let scaled = |value: f32| (value * 10.0).round() as i16 as u16;
+        match register {
+            0 => self.reading.map_or(NO_READING, |(t, _)| scaled(t)),
+            1 => self.reading.map_or(NO_READING, |(_, h)| scaled(h)),
+            2 => self.relays.fan_on as u16,
+            3 => self.relays.pump_on as u16,
+            4 => self.relays.fan_duty as u16,
+            5 => self.relays.safe_state as u16,
+            _ => self.reading.is_some() as u16,
+        }
+    }
+
+    fn holding(&self, register: u16) -> u16 {
+        mode_value(if register == 0 { self.fan_mode } else { self.pump_mode })
+    }
+}
+
+fn mode_value(mode: Override) -> u16 {
+    match mode {
+        Override::Auto => 0,
+        Override::On => 1,
+        Override::Off => 2,
+    }
+}
+
+fn parse_mode(value: u16) -> Option<Override> {
+    match value {
+        0 => Some(Override::Auto),
+        1 => Some(Override::On),
+        2 => Some(Override::Off),
+        _ => None,
+    }
+}
+
+fn holding_relay(register: u16) -> Relay {
+    if register == 0 { Relay::Fan } else { Relay::Pump }
+}
+
+fn with_crc(mut frame: Vec<u8>) -> Vec<u8> {
+    let crc = calculate_crc16(&frame);
+    frame.extend_from_slice(&crc.to_le_bytes());
+    frame
+}
+
+fn exception(address: u8, function: u8, code: u8) -> Vec<u8> {
+    with_crc(vec![address, function | 0x80, code])
+}
+
+fn word(frame: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([frame[at], frame[at + 1]])
+}
+
+// Satu frame request -> respons (None = bukan untuk node ini, CRC salah, atau broadcast).
+// Tulisan yang valid ditambahkan ke `writes`; semua nilai divalidasi dulu sebelum ada yang ditulis.
+pub fn handle(frame: &[u8], address: u8, image: &Image, writes: &mut Vec<(Relay, Override)>) -> Option<Vec<u8>> {
+    if frame.len() < 4 {
+        return None;
+    }
+    let (body, crc) = frame.split_at(frame.len() - 2);
+    if u16::from_le_bytes([crc[0], crc[1]]) != calculate_crc16(body) {
+        log::debug!("PLC frame CRC mismatch ({} bytes)", frame.len());
+        return None;
+    }
+    let (target, function) = (body[0], body[1]);
+    let broadcast = target == 0;
+    if target != address && !broadcast {
+        return None;
+    }
+    let reply = |response: Vec<u8>| (!broadcast).then_some(response);
+    match function {
+        READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS => {
+            if body.len() != 6 {
+                return reply(exception(address, function, EXCEPTION_ILLEGAL_VALUE));
+            }
+            let (start, count) = (word(body, 2), word(body, 4));
+            let limit = if function == READ_INPUT_REGISTERS { INPUT_REGISTERS } else { HOLDING_REGISTERS };
+            if count == 0 || count > 125 {
+                return reply(exception(address, function, EXCEPTION_ILLEGAL_VALUE));
+            }
+            if start.checked_add(count).map_or(true, |end| end > limit) {
+                return reply(exception(address, function, EXCEPTION_ILLEGAL_ADDRESS));
+            }
+            let mut response = vec![address, function, (count * 2) as u8];
+            for register in start..start + count {
+                let value = if function == READ_INPUT_REGISTERS { image.input(register) } else { image.holding(register) };
+                response.extend_from_slice(&value.to_be_bytes());
+            }
+            reply(with_crc(response))
+        }
+        WRITE_SINGLE_REGISTER => {
+            if body.len() != 6 {
+                return reply(exception(address, function, EXCEPTION_ILLEGAL_VALUE));
+            }
+            let (register, value) = (word(body, 2), word(body, 4));
+            if register >= HOLDING_REGISTERS {
+                return reply(exception(address, function, EXCEPTION_ILLEGAL_ADDRESS));
+            }
+            let Some(mode) = parse_mode(value) else {
+                return reply(exception(address, function, EXCEPTION_ILLEGAL_VALUE));
+            };
+            writes.push((holding_relay(register), mode));
+            // Respons 0x06 = echo request
+            reply(with_crc(body.to_vec()))
+        }
+        WRITE_MULTIPLE_REGISTERS => {
+            if body.len() < 7 {
+                return reply(exception(address, function, EXCEPTION_ILLEGAL_VALUE));
+            }
+            let (start, count, bytes) = (word(body, 2), word(body, 4), body[6] as usize);
+            if count == 0 || bytes != count as usize * 2 || body.len() != 7 + bytes {
+                return reply(exception(address, function, EXCEPTION_ILLEGAL_VALUE));
+            }
+            if start.checked_add(count).map_or(true, |end| end > HOLDING_REGISTERS) {
+                return reply(exception(address, function, EXCEPTION_ILLEGAL_ADDRESS));
+            }
+            let mut modes = Vec::new();
+            for (i, register) in (start..start + count).enumerate() {
+                let Some(mode) = parse_mode(word(body, 7 + 2 * i)) else {
+                    return reply(exception(address, function, EXCEPTION_ILLEGAL_VALUE));
+                };
+                modes.push((holding_relay(register), mode));
+            }
+            writes.extend(modes);
+            reply(with_crc(body[..6].to_vec()))
+        }
+        _ => reply(exception(address, function, EXCEPTION_ILLEGAL_FUNCTION)),
+    }
+}
+
+// Kumpulkan byte sampai bus diam FRAME_GAP_MS (akhir frame RTU)
+fn read_frame(uart: &UartDriver, frame: &mut Vec<u8>) {
+    frame.clear();
+    let mut buf = [0u8; 64];
+    let mut timeout = BLOCK;
+    loop {
+        match uart.read(&mut buf, timeout) {
+            Ok(0) | Err(_) => {
+                if !frame.is_empty() {
+                    return;
+                }
+            }
+            Ok(n) => {
+                if frame.len() + n > MAX_FRAME {
+                    // Sampah di bus (baud salah, dua master): buang dan mulai lagi
+                    frame.clear();
+                    timeout = BLOCK;
+                    continue;
+                }
+                frame.extend_from_slice(&buf[..n]);
+                timeout = TickType::new_millis(FRAME_GAP_MS).ticks();
+            }
+        }
+    }
+}
+
+pub fn spawn(uart2: UART2, board: &Board, commands: Sender<Command>) -> anyhow::Result<()> {
+    let uart_config = UartConfig::new()
+        .baudrate(config::PLC_BAUD.into())
+        .data_bits(DataBits::DataBits8)
+        .stop_bits(StopBits::STOP1)
+        .flow_control(FlowControl::None);
+    // SAFETY: nomor GPIO dari pin map tervalidasi; pin PLC tidak dipakai driver lain
+    let (tx, rx) = unsafe { (AnyIOPin::new(board.plc_tx), AnyIOPin::new(board.plc_rx)) };
+    let uart = UartDriver::new(uart2, tx, rx, Option::<gpio::Gpio0>::None, Option::<gpio::Gpio0>::None, &uart_config)?;
+    log::info!(
+        "🏭 PLC Modbus slave ready - address {}, {} baud, TX=GPIO{}, RX=GPIO{}",
+        config::PLC_SLAVE_ADDRESS,
+        config::PLC_BAUD,
+        board.plc_tx,
+        board.plc_rx
+    );
+
+    thread::Builder::new().name("plc-slave".into()).stack_size(4096).spawn(move || {
+        let mut frame = Vec::with_capacity(MAX_FRAME);
+        let mut writes = Vec::new();
+        loop {
+            read_frame(&uart, &mut frame);
+            writes.clear();
+            let response = handle(&frame, config::PLC_SLAVE_ADDRESS, &snapshot(), &mut writes);
+            if let Some(response) = response {
+                if let Err(e) = uart.write(&response) {
+                    log::warn!("PLC reply failed: {e:?}");
+                }
+            }
+            if !writes.is_empty() {
+                log::info!(
+                    "🏭 PLC override: {}",
+                    writes.iter().map(|(relay, mode)| format!("{}={}", relay.as_str(), mode.as_str())).collect::<Vec<_>>().join(", ")
+                );
+                if commands.send(Command::Relay(writes.clone())).is_err() {
+                    break;
+                }
+            }
+        }
+    })?;
+    Ok(())
+}
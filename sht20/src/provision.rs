@@ -0,0 +1,253 @@
+use anyhow::{anyhow, bail, Result};
+use embedded_io::{Read, Write};
+use embedded_svc::http::Method;
+use esp_idf_svc::eventloop::EspSystemEventLoop;
+use esp_idf_svc::hal::delay::FreeRtos;
+use esp_idf_svc::hal::gpio::{AnyIOPin, PinDriver, Pull};
+use esp_idf_svc::hal::modem::Modem;
+use esp_idf_svc::hal::reset;
+use esp_idf_svc::http::server::{Configuration as ServerConfiguration, EspHttpServer};
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::wifi::{AccessPointConfiguration, AuthMethod, BlockingWifi, Configuration as WifiConfiguration, EspWifi};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::board::Board;
+use crate::config;
+use crate::storage::Storage;
+
+// Mode provisioning: tahan tombol fan (atau pompa jika fan tidak dipasang) saat boot selama
+// PROVISION_HOLD. ESP32 membuka SoftAP "rust-dcs-<DEVICE_ID>" dengan halaman konfigurasi di
+// http://192.168.71.1 untuk Wi-Fi + endpoint InfluxDB/broker; hasilnya disimpan di NVS lalu reboot.
+const PROVISION_HOLD: Duration = Duration::from_secs(3);
+// Tanpa submit selama ini device reboot ke mode normal (tombol bisa saja tertekan tidak sengaja)
+const PROVISION_TIMEOUT: Duration = Duration::from_secs(600);
+const MAX_BODY: usize = 1024;
+
+// Kredensial jaringan; nilai hasil compile (config.rs) dipakai untuk key yang belum disimpan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub ssid: String,
+    pub password: String,
+    pub influx_url: String,
+    pub influx_token: String,
+    pub mqtt_url: String,
+}
+
+impl Default for Credentials {
+    fn default() -> Self {
+        Self {
+            ssid: config::WIFI_SSID.to_string(),
+            password: config::WIFI_PASSWORD.to_string(),
+            influx_url: config::INFLUX_URL.to_string(),
+            influx_token: config::INFLUX_TOKEN.to_string(),
+            mqtt_url: config::MQTT_URL.to_string(),
+        }
+    }
+}
+
+impl Credentials {
+    pub const KEYS: [&'static str; 5] = ["ssid", "password", "influx_url", "influx_token", "mqtt_url"];
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let (field, max) = match key {
+            "ssid" => (&mut self.ssid, 32),
+            "password" => (&mut self.password, 64),
+            "influx_url" => (&mut self.influx_url, 200),
+            "influx_token" => (&mut self.influx_token, 200),
+            "mqtt_url" => (&mut self.mqtt_url, 200),
+            _ => return Err(format!("unknown key {key}")),
+        };
+        if value.len() > max {
+            return Err(format!("{key} longer than {max} bytes"));
+        }
+        *field = value.to_string();
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        match key {
+            "ssid" => Some(&self.ssid),
+            "password" => Some(&self.password),
+            "influx_url" => Some(&self.influx_url),
+            "influx_token" => Some(&self.influx_token),
+            "mqtt_url" => Some(&self.mqtt_url),
+            _ => None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.password.is_empty() && self.password.len() < 8 {
+            return Err("WPA2 password needs at least 8 characters".to_string());
+        }
+        if !self.influx_url.starts_with("http://") && !self.influx_url.starts_with("https://") {
+            return Err(format!("invalid influx_url {}", self.influx_url));
+        }
+        if !self.mqtt_url.starts_with("mqtt://") && !self.mqtt_url.starts_with("mqtts://") {
+            return Err(format!("invalid mqtt_url {}", self.mqtt_url));
+        }
+        Ok(())
+    }
+}
+
+// Tombol ke GND dengan pull-up internal; dibaca sebelum Buttons::start memakai pin yang sama
+pub fn requested(board: &Board) -> bool {
+    let gpio = if board.btn_fan >= 0 { board.btn_fan } else { board.btn_pump };
+    if gpio < 0 {
+        return false;
+    }
+    // SAFETY: pin dari Board (pin map tervalidasi); driver dilepas sebelum fungsi kembali
+    let Ok(mut pin) = PinDriver::input(unsafe { AnyIOPin::new(gpio) }) else {
+        return false;
+    };
+    if pin.set_pull(Pull::Up).is_err() {
+        return false;
+    }
+    FreeRtos::delay_ms(10);
+    let start = Instant::now();
+    while pin.is_low() {
+        if start.elapsed() >= PROVISION_HOLD {
+            return true;
+        }
+        FreeRtos::delay_ms(50);
+    }
+    false
+}
+
+// Tidak kembali: reboot setelah kredensial disimpan atau setelah PROVISION_TIMEOUT
+pub fn run(modem: Modem, nvs: Option<EspDefaultNvsPartition>, storage: Option<Storage>) -> ! {
+    if let Err(e) = serve(modem, nvs, storage) {
+        log::error!("❌ Provisioning failed: {e:?}");
+        println!("PROVISION|failed|{e}");
+    }
+    FreeRtos::delay_ms(500);
+    reset::restart();
+}
+
+fn serve(modem: Modem, nvs: Option<EspDefaultNvsPartition>, storage: Option<Storage>) -> Result<()> {
+    let Some(mut storage) = storage else {
+        bail!("NVS unavailable, credentials cannot be stored");
+    };
+    let current = storage.load_credentials();
+    let ap_ssid = format!("rust-dcs-{}", config::DEVICE_ID);
+
+    let sysloop = EspSystemEventLoop::take()?;
+    let mut wifi = BlockingWifi::wrap(EspWifi::new(modem, sysloop.clone(), nvs)?, sysloop)?;
+    wifi.set_configuration(&WifiConfiguration::AccessPoint(AccessPointConfiguration {
+        ssid: ap_ssid.as_str().try_into().map_err(|_| anyhow!("AP SSID too long"))?,
+        password: config::PROVISION_PASSWORD.try_into().map_err(|_| anyhow!("AP password too long"))?,
+        auth_method: if config::PROVISION_PASSWORD.is_empty() { AuthMethod::None } else { AuthMethod::WPA2Personal },
+        channel: 1,
+        ..Default::default()
+    }))?;
+    wifi.start()?;
+    wifi.wait_netif_up()?;
+    let ip = wifi.wifi().ap_netif().get_ip_info()?.ip;
+    log::info!("📶 Provisioning AP '{ap_ssid}' up, open http://{ip}");
+    println!("PROVISION|ap={ap_ssid}|url=http://{ip}");
+
+    let (submitted, received) = mpsc::channel::<Credentials>();
+    let mut server = EspHttpServer::new(&ServerConfiguration::default())?;
+    let page = form(&current);
+    server.fn_handler("/", Method::Get, move |request| {
+        request.into_ok_response()?.write_all(page.as_bytes())?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+    server.fn_handler("/save", Method::Post, move |mut request| {
+        let mut body = Vec::new();
+        let mut chunk = [0u8; 256];
+        loop {
+            let n = request.read(&mut chunk)?;
+            if n == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..n]);
+            if body.len() > MAX_BODY {
+                bail!("form too large");
+            }
+        }
+        // Field kosong = nilai lama dipertahankan (password/token tidak ditampilkan di form)
+        let mut credentials = current.clone();
+        let result = form_fields(&String::from_utf8_lossy(&body))
+            .into_iter()
+            .filter(|(_, value)| !value.is_empty())
+            .try_for_each(|(key, value)| credentials.set(&key, &value))
+            .and_then(|_| credentials.validate());
+        match result {
+            Ok(()) => {
+                request.into_ok_response()?.write_all(b"<html><body><p>Saved. Rebooting...</p></body></html>")?;
+                let _ = submitted.send(credentials);
+            }
+            Err(e) => {
+                let html = format!("<html><body><p>Error: {}</p><a href=\"/\">Back</a></body></html>", escape(&e));
+                request.into_status_response(400)?.write_all(html.as_bytes())?;
+            }
+        }
+        Ok(())
+    })?;
+
+    let credentials = received.recv_timeout(PROVISION_TIMEOUT).map_err(|_| anyhow!("timeout, no credentials submitted"))?;
+    storage.save_credentials(&credentials)?;
+    log::info!("✅ Credentials saved for SSID '{}', rebooting", credentials.ssid);
+    println!("PROVISION|saved|ssid={}", credentials.ssid);
+    // Beri waktu respons HTTP terkirim sebelum AP dimatikan
+    FreeRtos::delay_ms(1000);
+    Ok(())
+}
+
+fn form(current: &Credentials) -> String {
+    let input = |key: &str, kind: &str, value: &str, placeholder: &str| {
+        format!(
+            "<label>{key}<br><input name=\"{key}\" type=\"{kind}\" value=\"{}\" placeholder=\"{}\"></label><br>",
+            escape(value),
+            escape(placeholder)
+        )
+    };
+    let secret = |value: &str| if value.is_empty() { "" } else { "(unchanged)" };
+    format!(
+        "<html><head><meta name=\"viewport\" content=\"width=device-width\"><title>{id}</title></head><body>\
+         <h3>{id}</h3><form method=\"post\" action=\"/save\">{}{}{}{}{}<br><button>Save</button></form></body></html>",
+        input("ssid", "text", &current.ssid, ""),
+        input("password", "password", "", secret(&current.password)),
+        input("influx_url", "text", &current.influx_url, ""),
+        input("influx_token", "password", "", secret(&current.influx_token)),
+        input("mqtt_url", "text", &current.mqtt_url, ""),
+        id = config::DEVICE_ID,
+    )
+}
+
+// application/x-www-form-urlencoded: key=value&..., '+' = spasi, %XX = byte
+fn form_fields(body: &str) -> Vec<(String, String)> {
+    body.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (url_decode(key), url_decode(value)))
+        .collect()
+}
+
+fn url_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
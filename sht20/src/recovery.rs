@@ -0,0 +1,134 @@
+use esp_idf_svc::hal::reset;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_svc::sys::{self, esp, EspError};
+
+use crate::board::Board;
+
+// Task watchdog: setiap task (sensor, kontrol, komunikasi) harus memanggil feed() lebih sering dari timeout ini,
+// kalau tidak ESP32 panic lalu reboot (BOOT_REASON|task_wdt)
+const WATCHDOG_TIMEOUT_MS: u32 = 60_000;
+
+// Pesan panic terakhir disimpan di NVS dan dilaporkan sekali saat boot berikutnya
+const NAMESPACE: &str = "boot";
+const PANIC_KEY: &str = "panic";
+// Jumlah reset tak terduga sejak flash, dilaporkan di frame DIAG
+const RESETS_KEY: &str = "resets";
+const MAX_PANIC_LEN: usize = 120;
+
+// Konfigurasi TWDT sekali saat boot; setiap task mendaftar sendiri lewat watch_task()
+pub fn start_watchdog() -> Result<(), EspError> {
+    let config = sys::esp_task_wdt_config_t {
+        timeout_ms: WATCHDOG_TIMEOUT_MS,
+        idle_core_mask: 0,
+        trigger_panic: true,
+    };
+    // TWDT biasanya sudah diinisialisasi ESP-IDF (CONFIG_ESP_TASK_WDT_INIT); jika belum, init sendiri
+    // SAFETY: config valid selama pemanggilan
+    unsafe {
+        if esp!(sys::esp_task_wdt_reconfigure(&config)).is_err() {
+            esp!(sys::esp_task_wdt_init(&config))?;
+        }
+    }
+    log::info!("🐕 Task watchdog armed ({}s)", WATCHDOG_TIMEOUT_MS / 1000);
+    Ok(())
+}
+
+// Daftarkan task yang memanggil; sejak itu task harus memanggil feed() sebelum timeout
+pub fn watch_task(name: &str) {
+    // SAFETY: NULL = task yang sedang berjalan
+    if let Err(e) = unsafe { esp!(sys::esp_task_wdt_add(std::ptr::null_mut())) } {
+        log::error!("Task watchdog unavailable for {name}: {e:?}");
+    }
+}
+
+pub fn feed() {
+    // SAFETY: hanya me-reset counter TWDT task ini; error (task belum terdaftar) diabaikan
+    unsafe {
+        sys::esp_task_wdt_reset();
+    }
+}
+
+// Panic: catat penyebab ke NVS, matikan relay, lalu restart.
+// Aktuator dimatikan langsung lewat driver ESP-IDF karena driver milik loop utama
+// tidak bisa diakses dari panic hook: relay pompa di board.pump, exhaust fan PWM LEDC channel 0
+pub fn install_panic_hook(nvs: Option<EspDefaultNvsPartition>, board: &Board) {
+    let pump = board.pump;
+    // Level "mati" mengikuti polaritas relay
+    let off_level = board.relay_low as u32;
+    std::panic::set_hook(Box::new(move |info| {
+        // SAFETY: pin/channel sudah dikonfigurasi oleh loop utama; error diabaikan
+        unsafe {
+            sys::gpio_set_level(pump, off_level);
+            sys::ledc_stop(sys::ledc_mode_t_LEDC_LOW_SPEED_MODE, sys::ledc_channel_t_LEDC_CHANNEL_0, off_level);
+        }
+        let mut message: String = info.to_string().replace(['\n', '|'], " ");
+        let mut len = message.len().min(MAX_PANIC_LEN);
+        while !message.is_char_boundary(len) {
+            len -= 1;
+        }
+        message.truncate(len);
+        log::error!("💥 Panic: {message}");
+        if let Some(partition) = nvs.clone() {
+            if let Ok(mut store) = EspNvs::<NvsDefault>::new(partition, NAMESPACE, true) {
+                let _ = store.set_str(PANIC_KEY, &message);
+            }
+        }
+        reset::restart();
+    }));
+}
+
+fn reset_reason() -> &'static str {
+    // SAFETY: hanya membaca register reset reason
+    match unsafe { sys::esp_reset_reason() } {
+        sys::esp_reset_reason_t_ESP_RST_POWERON => "poweron",
+        sys::esp_reset_reason_t_ESP_RST_EXT => "external",
+        sys::esp_reset_reason_t_ESP_RST_SW => "software",
+        sys::esp_reset_reason_t_ESP_RST_PANIC => "panic",
+        sys::esp_reset_reason_t_ESP_RST_INT_WDT => "int_wdt",
+        sys::esp_reset_reason_t_ESP_RST_TASK_WDT => "task_wdt",
+        sys::esp_reset_reason_t_ESP_RST_WDT => "wdt",
+        sys::esp_reset_reason_t_ESP_RST_DEEPSLEEP => "deepsleep",
+        sys::esp_reset_reason_t_ESP_RST_BROWNOUT => "brownout",
+        sys::esp_reset_reason_t_ESP_RST_SDIO => "sdio",
+        _ => "unknown",
+    }
+}
+
+// Sama dengan serial::is_unexpected_reset di backend
+fn is_unexpected(reason: &str) -> bool {
+    !matches!(reason, "poweron" | "external" | "software" | "deepsleep")
+}
+
+// BOOT_REASON|<reason>[|panic=<pesan>]; backend menghitung reset yang tidak disengaja.
+// Mengembalikan jumlah reset tak terduga yang tersimpan di NVS (termasuk boot ini).
+pub fn report_boot_reason(nvs: Option<&EspDefaultNvsPartition>) -> u32 {
+    let mut panic = None;
+    let mut store = nvs.and_then(|partition| EspNvs::<NvsDefault>::new(partition.clone(), NAMESPACE, true).ok());
+    if let Some(store) = store.as_mut() {
+        let mut buf = [0u8; MAX_PANIC_LEN + 8];
+        panic = store.get_str(PANIC_KEY, &mut buf).ok().flatten().map(str::to_string);
+        if panic.is_some() {
+            let _ = store.remove(PANIC_KEY);
+        }
+    }
+    // Panic hook me-restart lewat software, jadi pesan di NVS yang menandai panic
+    let reason = if panic.is_some() { "panic" } else { reset_reason() };
+    let mut resets = store.as_ref().and_then(|store| store.get_u32(RESETS_KEY).ok().flatten()).unwrap_or(0);
+    if is_unexpected(reason) {
+        resets += 1;
+        if let Some(store) = store.as_mut() {
+            let _ = store.set_u32(RESETS_KEY, resets);
+        }
+    }
+    match &panic {
+        Some(message) => {
+            log::warn!("Last reset: {reason} ({message})");
+            println!("BOOT_REASON|{reason}|panic={message}");
+        }
+        None => {
+            log::info!("Last reset: {reason}");
+            println!("BOOT_REASON|{reason}");
+        }
+    }
+    resets
+}
@@ -0,0 +1,98 @@
+// Abstraksi sensor: loop utama hanya melihat SensorDriver, sehingga firmware yang
+// sama jalan dengan transmitter RS485 (Modbus) maupun sensor I2C/one-wire langsung
+
+// Nilai fisik sebelum offset kalibrasi (°C, %RH, %)
+pub enum Reading {
+    Climate { temperature: f32, humidity: f32 },
+    Soil { moisture: f32 },
+}
+
+pub trait SensorDriver {
+    // Nama singkat untuk log, mis. "sht20", "bme280"
+    fn name(&self) -> &'static str;
+
+    // Alamat pada tag slave=<n> di frame serial; sensor lokal tunggal = 1 supaya
+    // backend menyimpannya sebagai device_id utama
+    fn slave(&self) -> u8 {
+        1
+    }
+
+    // Err berisi kode singkat untuk SENSOR_FAULT|<code>
+    fn read(&mut self) -> Result<Reading, String>;
+}
+
+// Driver + statistik error yang dilaporkan ke gateway
+pub struct Sensor {
+    driver: Box<dyn SensorDriver>,
+    health: LinkHealth,
+}
+
+impl Sensor {
+    pub fn new(driver: Box<dyn SensorDriver>) -> Self {
+        Self { driver, health: LinkHealth::default() }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.driver.name()
+    }
+
+    pub fn slave(&self) -> u8 {
+        self.driver.slave()
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.health.consecutive_failures
+    }
+
+    pub fn total_errors(&self) -> u32 {
+        self.health.total_errors
+    }
+
+    // Kode SENSOR_FAULT kegagalan terakhir (kosong jika belum pernah gagal)
+    pub fn last_fault(&self) -> &str {
+        &self.health.last_fault
+    }
+
+    pub fn poll(&mut self) -> Option<Reading> {
+        let slave = self.driver.slave();
+        match self.driver.read() {
+            Ok(reading) => {
+                self.health.record_success(slave);
+                Some(reading)
+            }
+            Err(code) => {
+                self.health.record_failure(slave, &code);
+                None
+            }
+        }
+    }
+}
+
+// Hitung kegagalan berturut-turut dan total error sejak boot, per sensor
+#[derive(Debug, Default)]
+pub struct LinkHealth {
+    pub consecutive_failures: u32,
+    pub total_errors: u32,
+    pub last_fault: String,
+}
+
+impl LinkHealth {
+    pub fn record_success(&mut self, slave: u8) {
+        if self.consecutive_failures > 0 {
+            log::info!("✅ Slave {slave} recovered after {} failed cycles", self.consecutive_failures);
+            println!("SENSOR_OK|recovered={}|slave={slave}", self.consecutive_failures);
+        }
+        self.consecutive_failures = 0;
+    }
+
+    // Setiap siklus gagal dilaporkan: SENSOR_FAULT|<code>|consecutive=<n>|total=<n>|slave=<addr>
+    pub fn record_failure(&mut self, slave: u8, code: &str) {
+        self.consecutive_failures += 1;
+        self.total_errors += 1;
+        self.last_fault = code.to_string();
+        println!(
+            "SENSOR_FAULT|{code}|consecutive={}|total={}|slave={slave}",
+            self.consecutive_failures, self.total_errors
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use esp_idf_svc::hal::delay::{FreeRtos, TickType};
+use esp_idf_svc::hal::i2c::I2cDriver;
+
+use crate::sensor::{Reading, SensorDriver};
+
+// Sensirion SHT30/31/35 di I2C, alamat 0x44 (ADDR ke GND) atau 0x45
+pub const DEFAULT_ADDRESS: u8 = 0x44;
+
+// Single shot, repeatability high, tanpa clock stretching; konversi maks. 15 ms
+const MEASURE_HIGH: [u8; 2] = [0x24, 0x00];
+const MEASURE_WAIT_MS: u32 = 20;
+const I2C_TIMEOUT_MS: u64 = 100;
+
+// CRC-8 Sensirion: polinom 0x31, init 0xFF, per 2 byte data
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0xFF;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x31 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+pub struct Sht3x {
+    i2c: I2cDriver<'static>,
+    address: u8,
+}
+
+impl Sht3x {
+    pub fn new(i2c: I2cDriver<'static>, address: u8) -> Self {
+        Self { i2c, address }
+    }
+}
+
+impl SensorDriver for Sht3x {
+    fn name(&self) -> &'static str {
+        "sht3x"
+    }
+
+    fn read(&mut self) -> Result<Reading, String> {
+        let timeout = TickType::new_millis(I2C_TIMEOUT_MS).ticks();
+        self.i2c.write(self.address, &MEASURE_HIGH, timeout).map_err(|_| "no_response".to_string())?;
+        FreeRtos::delay_ms(MEASURE_WAIT_MS);
+
+        // [T_hi T_lo T_crc RH_hi RH_lo RH_crc]
+        let mut buf = [0u8; 6];
+        self.i2c.read(self.address, &mut buf, timeout).map_err(|_| "timeout".to_string())?;
+        if crc8(&buf[0..2]) != buf[2] || crc8(&buf[3..5]) != buf[5] {
+            return Err("crc".to_string());
+        }
+        let raw_t = u16::from_be_bytes([buf[0], buf[1]]) as f32;
+        let raw_h = u16::from_be_bytes([buf[3], buf[4]]) as f32;
+        Ok(Reading::Climate {
+            temperature: -45.0 + 175.0 * raw_t / 65535.0,
+            humidity: 100.0 * raw_h / 65535.0,
+        })
+    }
+}
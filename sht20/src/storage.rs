@@ -0,0 +1,124 @@
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+
+use crate::board::Board;
+use crate::control::Settings;
+use crate::network::Uplink;
+use crate::provision::Credentials;
+
+// Namespace NVS untuk konfigurasi runtime (threshold + kalibrasi)
+const NAMESPACE: &str = "sht20";
+
+// Setiap nilai f32 disimpan sebagai bit u32 dengan nama key yang sama dengan perintah SET
+pub struct Storage {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl Storage {
+    pub fn open(partition: EspDefaultNvsPartition) -> anyhow::Result<Self> {
+        Ok(Self { nvs: EspNvs::new(partition, NAMESPACE, true)? })
+    }
+
+    // Key yang belum pernah disimpan memakai default hasil compile; kombinasi
+    // yang tidak valid (mis. NVS lama/rusak) dibuang seluruhnya
+    pub fn load(&self) -> Settings {
+        let mut settings = Settings::default();
+        let mut stored = 0;
+        for key in Settings::KEYS {
+            match self.nvs.get_u32(key) {
+                Ok(Some(bits)) => {
+                    if settings.set(key, f32::from_bits(bits)).is_ok() {
+                        stored += 1;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!("NVS read {key} failed: {e:?}"),
+            }
+        }
+        if let Err(e) = settings.validate() {
+            log::warn!("Stored config invalid ({e}) - using defaults");
+            return Settings::default();
+        }
+        if stored > 0 {
+            log::info!("Config loaded from NVS ({stored} keys)");
+        }
+        settings
+    }
+
+    pub fn save(&mut self, settings: &Settings) -> anyhow::Result<()> {
+        for key in Settings::KEYS {
+            if let Some(value) = settings.get(key) {
+                self.nvs.set_u32(key, value.to_bits())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load_uplink(&self) -> Option<Uplink> {
+        self.nvs.get_u8("uplink").ok().flatten().and_then(Uplink::from_u8)
+    }
+
+    pub fn save_uplink(&mut self, uplink: Uplink) -> anyhow::Result<()> {
+        self.nvs.set_u8("uplink", uplink as u8)?;
+        Ok(())
+    }
+
+    // Pin map disimpan per key dengan prefix "b_" (i32); map yang bentrok dibuang seluruhnya
+    pub fn load_board(&self) -> Board {
+        let mut board = Board::default();
+        let mut stored = 0;
+        for key in Board::KEYS {
+            match self.nvs.get_i32(&format!("b_{key}")) {
+                Ok(Some(value)) => match board.set(key, value) {
+                    Ok(()) => stored += 1,
+                    Err(e) => log::warn!("Stored board key ignored: {e}"),
+                },
+                Ok(None) => {}
+                Err(e) => log::warn!("NVS read b_{key} failed: {e:?}"),
+            }
+        }
+        if let Err(e) = board.validate() {
+            log::warn!("Stored board map invalid ({e}) - using defaults");
+            return Board::default();
+        }
+        if stored > 0 {
+            log::info!("Board map loaded from NVS ({stored} keys)");
+        }
+        board
+    }
+
+    pub fn save_board(&mut self, board: &Board) -> anyhow::Result<()> {
+        for key in Board::KEYS {
+            if let Some(value) = board.get(key) {
+                self.nvs.set_i32(&format!("b_{key}"), value)?;
+            }
+        }
+        Ok(())
+    }
+
+    // String Wi-Fi/endpoint dengan prefix "n_"; key kosong/tidak ada = default config.rs
+    pub fn load_credentials(&self) -> Credentials {
+        let mut credentials = Credentials::default();
+        let mut buf = [0u8; 256];
+        for key in Credentials::KEYS {
+            match self.nvs.get_str(&format!("n_{key}"), &mut buf) {
+                Ok(Some(value)) => {
+                    if let Err(e) = credentials.set(key, value) {
+                        log::warn!("Stored credential ignored: {e}");
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!("NVS read n_{key} failed: {e:?}"),
+            }
+        }
+        credentials
+    }
+
+    pub fn save_credentials(&mut self, credentials: &Credentials) -> anyhow::Result<()> {
+        for key in Credentials::KEYS {
+            if let Some(value) = credentials.get(key) {
+                self.nvs.set_str(&format!("n_{key}"), value)?;
+            }
+        }
+        Ok(())
+    }
+}
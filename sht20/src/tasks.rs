@@ -0,0 +1,270 @@
+use esp_idf_svc::eventloop::EspSystemEventLoop;
+use esp_idf_svc::hal::adc::ADC1;
+use esp_idf_svc::hal::i2c::{I2C0, I2C1};
+use esp_idf_svc::hal::modem::Modem;
+use esp_idf_svc::hal::uart::UART1;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sys::{esp_get_free_heap_size, esp_timer_get_time};
+use dcs_model::Diagnostics;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::analog::AnalogInputs;
+use crate::board::Board;
+use crate::bus;
+use crate::command::Command;
+use crate::control::RelayState;
+use crate::display::{self, Display};
+use crate::frame;
+use crate::network::{Network, Uplink};
+use crate::provision::Credentials;
+use crate::sensor::Reading;
+use crate::{config, ota, recovery};
+
+// Tiga task FreeRTOS (std::thread di ESP-IDF):
+//   sensor  - poll semua sensor setiap SAMPLE_INTERVAL, kirim Measurement ke task kontrol
+//   kontrol - loop utama (main.rs): relay, perintah, tombol, frame serial
+//   comms   - Wi-Fi/MQTT/InfluxDB, OTA, layar, DIAG; menerima Outbound dari task kontrol
+// Timeout Modbus atau upload HTTP yang lambat tidak lagi menahan relay maupun perintah.
+
+// Periode sampling sensor (kontrol relay merespons secepat ini)
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+// Periode task comms: maintain Wi-Fi, layar
+const COMMS_TICK: Duration = Duration::from_secs(1);
+// Frame DIAG kesehatan device
+const DIAG_INTERVAL: Duration = Duration::from_secs(60);
+
+const SENSOR_STACK: usize = 8 * 1024;
+// Wi-Fi, HTTP client dan OTA butuh stack besar
+const COMMS_STACK: usize = 16 * 1024;
+
+// sensor -> kontrol; nilai mentah sebelum offset kalibrasi
+pub enum Measurement {
+    Sample { slave: u8, primary: bool, reading: Reading },
+    Failed { slave: u8, primary: bool, name: &'static str, code: String, consecutive: u32 },
+    // Nilai ADC mentah (0-4095) input analog, diskalakan di task kontrol
+    Analog { name: &'static str, raw: u16 },
+    // Akhir satu putaran semua sensor
+    RoundDone { has_primary: bool },
+}
+
+// kontrol -> sensor, dari perintah konsol
+pub enum SensorRequest {
+    // Putaran baca berikutnya dimulai sekarang
+    ReadNow,
+    ModbusScan,
+}
+
+// kontrol -> comms
+pub enum Outbound {
+    Upload { temperature: f32, humidity: f32, relays: RelayState },
+    // Pembacaan valid terakhir sensor utama + state relay, untuk layar
+    Status { reading: Option<(f32, f32)>, relays: RelayState },
+    // ACK/NAK ke topic MQTT .../ack
+    Reply(String),
+    SetUplink(Uplink),
+    Ota(String),
+}
+
+// Sensor dibuat di dalam task karena driver (Rc<UartDriver>) tidak Send
+pub fn spawn_sensor_task(
+    i2c0: I2C0,
+    uart1: UART1,
+    adc1: ADC1,
+    board: Board,
+    measurements: Sender<Measurement>,
+    requests: Receiver<SensorRequest>,
+    bus_errors: Arc<AtomicU32>,
+) -> anyhow::Result<()> {
+    thread::Builder::new().name("sensor".into()).stack_size(SENSOR_STACK).spawn(move || {
+        // Scan bus saat boot bisa lama; task baru diawasi watchdog setelah sensor siap
+        let (mut sensors, rs485) = crate::start_sensors(i2c0, uart1, &board);
+        let mut analog = AnalogInputs::start(adc1, &board);
+        recovery::watch_task("sensor");
+        // Sensor suhu/kelembaban pertama = sensor utama untuk kontrol relay dan upload Wi-Fi
+        let primary = sensors.iter().find(|sensor| sensor.name() != "soil").map(|sensor| sensor.slave());
+        let mut next = Instant::now();
+        loop {
+            recovery::feed();
+            for sensor in sensors.iter_mut() {
+                let slave = sensor.slave();
+                let is_primary = Some(slave) == primary;
+                let message = match sensor.poll() {
+                    Some(reading) => Measurement::Sample { slave, primary: is_primary, reading },
+                    None => Measurement::Failed {
+                        slave,
+                        primary: is_primary,
+                        name: sensor.name(),
+                        code: sensor.last_fault().to_string(),
+                        consecutive: sensor.consecutive_failures(),
+                    },
+                };
+                if measurements.send(message).is_err() {
+                    return;
+                }
+            }
+            for (name, raw) in analog.read() {
+                let _ = measurements.send(Measurement::Analog { name, raw });
+            }
+            bus_errors.store(sensors.iter().map(|sensor| sensor.total_errors()).sum(), Ordering::Relaxed);
+            let _ = measurements.send(Measurement::RoundDone { has_primary: primary.is_some() });
+
+            next += SAMPLE_INTERVAL;
+            // Putaran lebih lama dari periode (mis. banyak slave timeout): jadwal ulang dari sekarang
+            if next < Instant::now() {
+                next = Instant::now();
+            }
+            // Menunggu putaran berikutnya sambil melayani perintah konsol
+            while let Some(wait) = next.checked_duration_since(Instant::now()) {
+                match requests.recv_timeout(wait) {
+                    Ok(SensorRequest::ReadNow) => next = Instant::now(),
+                    Ok(SensorRequest::ModbusScan) => {
+                        match rs485.as_ref() {
+                            Some(uart) => bus::scan(uart),
+                            None => println!("NAK|MODBUS_SCAN|sensor driver is not modbus"),
+                        }
+                        recovery::feed();
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => thread::sleep(wait),
+                }
+            }
+        }
+    })?;
+    Ok(())
+}
+
+pub struct CommsConfig {
+    pub board: Board,
+    pub nvs: Option<EspDefaultNvsPartition>,
+    pub credentials: Credentials,
+    pub uplink: Uplink,
+    // Perintah dari topic MQTT .../cmd diteruskan ke task kontrol
+    pub commands: Sender<Command>,
+    pub resets: u32,
+    pub bus_errors: Arc<AtomicU32>,
+}
+
+pub fn spawn_comms_task(modem: Modem, i2c1: I2C1, config: CommsConfig, outbound: Receiver<Outbound>) -> anyhow::Result<()> {
+    thread::Builder::new().name("comms".into()).stack_size(COMMS_STACK).spawn(move || {
+        let CommsConfig { board, nvs, credentials, mut uplink, commands, resets, bus_errors } = config;
+        // Wi-Fi opsional: gagal connect = tetap serial-only dan dicoba lagi berkala
+        let mut network = match EspSystemEventLoop::take() {
+            Ok(sysloop) => Network::start(modem, sysloop, nvs, credentials, uplink, commands),
+            Err(e) => {
+                log::error!("Event loop unavailable: {e:?}");
+                println!("MODE|serial|reason=wifi_init_failed");
+                None
+            }
+        };
+        let mut display = match board.display.then(|| Display::start(i2c1, board.oled_sda, board.oled_scl)) {
+            Some(Ok(display)) => Some(display),
+            Some(Err(e)) => {
+                log::info!("OLED display disabled: {e}");
+                None
+            }
+            None => None,
+        };
+        // Connect Wi-Fi pertama bisa lama; task baru diawasi watchdog setelah ini
+        recovery::watch_task("comms");
+        let mut status: (Option<(f32, f32)>, RelayState) = (None, RelayState::default());
+        let mut last_diag: Option<Instant> = None;
+        let mut last_tick = Instant::now();
+
+        loop {
+            recovery::feed();
+            match outbound.recv_timeout(COMMS_TICK) {
+                Ok(Outbound::Upload { temperature, humidity, relays }) => {
+                    if let Some(network) = network.as_mut() {
+                        network.upload(temperature, humidity, &relays);
+                    }
+                }
+                Ok(Outbound::Status { reading, relays }) => status = (reading, relays),
+                Ok(Outbound::Reply(reply)) => {
+                    if let Some(network) = network.as_mut() {
+                        network.publish_reply(&reply);
+                    }
+                }
+                Ok(Outbound::SetUplink(selected)) => {
+                    uplink = selected;
+                    if let Some(network) = network.as_mut() {
+                        network.set_uplink(selected);
+                    }
+                }
+                Ok(Outbound::Ota(url)) => {
+                    let online = network.as_ref().is_some_and(Network::online);
+                    let result = if online { ota::update_from_url(&url) } else { Err(anyhow::anyhow!("wifi offline")) };
+                    if let Err(e) = result {
+                        log::error!("❌ OTA failed: {e:?}");
+                        println!("OTA_STATUS|failed|{e}");
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            if last_tick.elapsed() < COMMS_TICK {
+                continue;
+            }
+            last_tick = Instant::now();
+
+            if let Some(network) = network.as_mut() {
+                network.maintain();
+            }
+            if let Some(display) = display.as_mut() {
+                update_display(display, &network, uplink, status);
+            }
+            if last_diag.map_or(true, |t| t.elapsed() >= DIAG_INTERVAL) {
+                send_diagnostics(&network, resets, bus_errors.load(Ordering::Relaxed));
+                last_diag = Some(Instant::now());
+            }
+        }
+    })?;
+    Ok(())
+}
+
+// DIAG|uptime=<s>|heap=<bytes>|rssi=<dBm atau NA>|resets=<n>|modbus_errors=<n>|fw=<versi>|device=<DEVICE_ID>|<seq>|<crc16>
+// fw dan device dipakai backend sebagai tag InfluxDB (firmware) dan untuk mencocokkan device_id config
+fn send_diagnostics(network: &Option<Network>, resets: u32, bus_errors: u32) {
+    // SAFETY: hanya membaca timer dan statistik heap ESP-IDF
+    let (uptime, heap) = unsafe { (esp_timer_get_time() / 1_000_000, esp_get_free_heap_size()) };
+    let diag = Diagnostics {
+        uptime_s: uptime as u64,
+        free_heap: heap.into(),
+        rssi_dbm: network.as_ref().and_then(Network::rssi),
+        resets: resets.into(),
+        modbus_errors: bus_errors.into(),
+        firmware: Some(env!("CARGO_PKG_VERSION").to_string()),
+        device: Some(config::DEVICE_ID.to_string()),
+    };
+    frame::emit(&diag.to_frame());
+}
+
+// Layar OLED lokal untuk teknisi di greenhouse (feature "oled")
+fn update_display(display: &mut Display, network: &Option<Network>, uplink: Uplink, status: (Option<(f32, f32)>, RelayState)) {
+    let (last, relays) = status;
+    let link = match network.as_ref().filter(|network| network.online()) {
+        Some(network) => match network.rssi() {
+            Some(rssi) => format!("WiFi {rssi}dBm {}", uplink.as_str()),
+            None => format!("WiFi {}", uplink.as_str()),
+        },
+        None => "Serial".to_string(),
+    };
+    let alert = if relays.safe_state {
+        Some("SAFE STATE")
+    } else if last.is_none() {
+        Some("NO SENSOR DATA")
+    } else {
+        None
+    };
+    display.show(&display::Status {
+        temperature: last.map(|(t, _)| t),
+        humidity: last.map(|(_, h)| h),
+        fan_duty: relays.fan_duty,
+        pump_on: relays.pump_on,
+        link: &link,
+        alert,
+    });
+}